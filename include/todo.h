@@ -0,0 +1,149 @@
+#ifndef TODO_H
+#define TODO_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdint.h>
+
+// A todo list loaded from a file or from JSON, with the changes made to it since.
+typedef struct TodoHandle TodoHandle;
+
+// Loads the todo list file at `path`, or an empty list if it does not exist. Returns
+// NULL if `path` is not valid UTF-8, or if the file cannot be read or parsed, with the
+// reason in `todo_last_error`.
+//
+// # Safety
+//
+// `path` must be a valid NUL-terminated string.
+TodoHandle *todo_load(const char *path);
+
+// Reads a todo list from `json`, in the format of the list file, or an empty list if
+// `json` is empty. Returns NULL if `json` is not valid UTF-8 or not a todo list.
+//
+// # Safety
+//
+// `json` must be a valid NUL-terminated string.
+TodoHandle *todo_from_json(const char *json);
+
+// Writes the list back to the file it was loaded from, backing up the previous version as
+// `save` does, if it has changed since it was loaded or last saved. Returns 0 on success
+// and -1 on failure, including for a list that came from `todo_from_json`.
+//
+// # Safety
+//
+// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+int32_t todo_save(TodoHandle *handle);
+
+// Saves the list automatically once `delay_ms` milliseconds have passed since its last
+// change, so a burst of changes is written once, and when the handle is freed. A negative
+// delay turns autosaving off. Returns 0 on success and -1 for a list that came from
+// `todo_from_json`, which has no file to save to.
+//
+// The library has no timer of its own: the host calls `todo_flush` from its event loop or
+// timer, e.g. every second.
+//
+// # Safety
+//
+// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+int32_t todo_set_autosave(TodoHandle *handle, int64_t delay_ms);
+
+// Saves the list if autosaving is on and its last change is at least the autosave delay
+// old. Returns 1 if it was saved, 0 if there was nothing to save yet, and -1 on failure.
+//
+// # Safety
+//
+// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+int32_t todo_flush(TodoHandle *handle);
+
+// Returns 1 if the list has changes that have not been saved, 0 if not, and -1 if
+// `handle` is NULL.
+//
+// # Safety
+//
+// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+int32_t todo_is_dirty(const TodoHandle *handle);
+
+// Adds a todo item with a priority from 1 to 5, returning its ID, or -1 if the task is
+// not valid UTF-8 or the priority is out of range.
+//
+// # Safety
+//
+// `handle` must come from `todo_load` or `todo_from_json` and not have been freed, and `task` must be a valid
+// NUL-terminated string.
+int64_t todo_add(TodoHandle *handle, const char *task, uint64_t priority);
+
+// Completes the todo item with the given ID, removing it from the list as `remove` does,
+// so the items after it move up one ID. Returns 0 on success and -1 for an unknown ID.
+//
+// # Safety
+//
+// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+int32_t todo_complete(TodoHandle *handle, uint64_t id);
+
+// Returns the todo items as a JSON array, in the format of the list file, or NULL if
+// `handle` is NULL. The string must be freed with `todo_string_free`.
+//
+// # Safety
+//
+// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+char *todo_list_json(const TodoHandle *handle);
+
+// Returns the todo items matching `query`, in the query language of `search`, as a JSON
+// array; with `fields:` in the query, each item has only those fields. Returns NULL if
+// the query is invalid, with the reason left for `todo_last_error`. The string must be
+// freed with `todo_string_free`.
+//
+// # Safety
+//
+// `handle` must come from `todo_load` or `todo_from_json` and not have been freed, and
+// `query` must be a valid NUL-terminated string.
+char *todo_query_json(const TodoHandle *handle, const char *query);
+
+// Applies `commands`, lines of add, edit and remove commands as in a `batch` file, all
+// together or not at all. Added tasks are created by the current user and in the context
+// of the list's directory (the current directory for a list from `todo_from_json`).
+// Returns 0 on success and -1 if any command fails, with the reason left for
+// `todo_last_error` and the list unchanged.
+//
+// # Safety
+//
+// `handle` must come from `todo_load` or `todo_from_json` and not have been freed, and
+// `commands` must be a valid NUL-terminated string.
+int32_t todo_batch(TodoHandle *handle, const char *commands);
+
+// Returns why the last `todo_load`, `todo_query_json`, `todo_batch`, `todo_save` or
+// `todo_flush` on this thread failed, or NULL if none has. The string must be freed with `todo_string_free`.
+char *todo_last_error(void);
+
+// Returns the whole list in the format of the list file, checksum included, for a host
+// to store and later pass to `todo_from_json`, or NULL if `handle` is NULL. The string
+// must be freed with `todo_string_free`.
+//
+// # Safety
+//
+// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+char *todo_to_json(const TodoHandle *handle);
+
+// Allocates a string of `len` bytes for a host that cannot pass its own memory, such as
+// JavaScript calling the WebAssembly build, to write an argument into. The host must
+// overwrite all `len` bytes with UTF-8 text containing no NUL, and free the string with
+// `todo_string_free`.
+char *todo_string_alloc(uintptr_t len);
+
+// Frees a string returned by this library, or allocated with `todo_string_alloc`. Does
+// nothing for NULL.
+//
+// # Safety
+//
+// `string` must come from this library and not have been freed.
+void todo_string_free(char *string);
+
+// Frees a handle, first saving any unsaved changes if autosaving is on, and discarding
+// them otherwise. Does nothing for NULL.
+//
+// # Safety
+//
+// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+void todo_free(TodoHandle *handle);
+
+#endif  /* TODO_H */
@@ -0,0 +1,181 @@
+"""Python bindings for the todo list engine, over its C library (`include/todo.h`).
+
+Build the library with `cargo build --release`, then load a list from a notebook:
+
+    import todo
+    with todo.TodoList.load("todos.json") as todos:
+        todos.batch('add "Call the plumber" 4\nremove 3')
+        urgent = todos.query("priority>=4 sort:due")
+        todos.save()
+
+The library is found through the `TODO_LIBRARY` environment variable, or in the `target`
+directory of the checkout this file is in.
+
+This is a ctypes wrapper rather than a PyO3 extension, so that it builds with the crate
+alone and runs on any Python 3; see the Python section of the README.
+"""
+
+import ctypes
+import json
+import os
+import sys
+
+_NAMES = {"darwin": "libtodo.dylib", "win32": "todo.dll"}
+
+
+def _find_library():
+    path = os.environ.get("TODO_LIBRARY")
+    if path:
+        return path
+    name = _NAMES.get(sys.platform, "libtodo.so")
+    root = os.path.join(os.path.dirname(os.path.abspath(__file__)), "..", "target")
+    for profile in ("release", "debug"):
+        path = os.path.join(root, profile, name)
+        if os.path.exists(path):
+            return path
+    raise OSError(
+        "Unable to find %s: build it with `cargo build --release` or set TODO_LIBRARY" % name
+    )
+
+
+_lib = ctypes.CDLL(_find_library())
+_handle = ctypes.c_void_p
+# Returned strings are kept as raw pointers, so they can be freed with todo_string_free.
+_string = ctypes.c_void_p
+for _name, _restype, _argtypes in [
+    ("todo_load", _handle, [ctypes.c_char_p]),
+    ("todo_from_json", _handle, [ctypes.c_char_p]),
+    ("todo_save", ctypes.c_int32, [_handle]),
+    ("todo_set_autosave", ctypes.c_int32, [_handle, ctypes.c_int64]),
+    ("todo_flush", ctypes.c_int32, [_handle]),
+    ("todo_is_dirty", ctypes.c_int32, [_handle]),
+    ("todo_add", ctypes.c_int64, [_handle, ctypes.c_char_p, ctypes.c_uint64]),
+    ("todo_complete", ctypes.c_int32, [_handle, ctypes.c_uint64]),
+    ("todo_list_json", _string, [_handle]),
+    ("todo_query_json", _string, [_handle, ctypes.c_char_p]),
+    ("todo_batch", ctypes.c_int32, [_handle, ctypes.c_char_p]),
+    ("todo_last_error", _string, []),
+    ("todo_to_json", _string, [_handle]),
+    ("todo_string_free", None, [_string]),
+    ("todo_free", None, [_handle]),
+]:
+    _function = getattr(_lib, _name)
+    _function.restype = _restype
+    _function.argtypes = _argtypes
+
+
+def _take_string(pointer):
+    """Returns a string from the library as `str`, freeing it, or None for NULL."""
+    if not pointer:
+        return None
+    try:
+        return ctypes.string_at(pointer).decode("utf-8")
+    finally:
+        _lib.todo_string_free(pointer)
+
+
+class TodoError(Exception):
+    """A change or query the engine rejected."""
+
+
+class TodoList:
+    """A todo list held by the engine. Changes are kept in memory until `save`."""
+
+    def __init__(self, handle):
+        if not handle:
+            raise TodoError("Unable to read the todo list")
+        self._handle = handle
+
+    @classmethod
+    def load(cls, path):
+        """Loads the list file at `path`, or an empty list if it does not exist."""
+        return cls(_lib.todo_load(os.fsencode(path)))
+
+    @classmethod
+    def from_json(cls, text=""):
+        """Reads a list in the format of the list file; an empty string is an empty list."""
+        return cls(_lib.todo_from_json(text.encode("utf-8")))
+
+    def save(self):
+        """Writes the list back to the file it was loaded from, backing up the old one, if
+        it has changed."""
+        if _lib.todo_save(self._checked()) != 0:
+            raise TodoError("Unable to save the todo list")
+
+    def autosave(self, delay=1.0):
+        """Saves the list `delay` seconds after a burst of changes ends, as checked by
+        `flush`, and when it is closed. None turns autosaving off."""
+        delay_ms = -1 if delay is None else int(delay * 1000)
+        if _lib.todo_set_autosave(self._checked(), delay_ms) != 0:
+            raise TodoError("Only a list loaded from a file can be saved automatically")
+
+    def flush(self):
+        """Saves the list if autosaving and the last change is old enough. Returns whether
+        it was saved."""
+        saved = _lib.todo_flush(self._checked())
+        if saved < 0:
+            raise TodoError("Unable to save the todo list")
+        return saved == 1
+
+    @property
+    def dirty(self):
+        """Whether the list has changes that have not been saved."""
+        return _lib.todo_is_dirty(self._checked()) == 1
+
+    def to_json(self):
+        """Returns the whole list in the format of the list file, checksum included."""
+        return _take_string(_lib.todo_to_json(self._checked()))
+
+    @property
+    def todos(self):
+        """The todo items, as dicts with the fields of the list file."""
+        return json.loads(_take_string(_lib.todo_list_json(self._checked())))
+
+    def query(self, query=""):
+        """Returns the todo items matching `query`, in the query language of `search`."""
+        result = _take_string(_lib.todo_query_json(self._checked(), query.encode("utf-8")))
+        if result is None:
+            raise TodoError(_take_string(_lib.todo_last_error()))
+        return json.loads(result)
+
+    def add(self, task, priority=3):
+        """Adds a task with a priority from 1 to 5, returning its ID."""
+        id = _lib.todo_add(self._checked(), task.encode("utf-8"), priority)
+        if id < 0:
+            raise TodoError("Invalid priority: %d for task: %s" % (priority, task))
+        return id
+
+    def complete(self, id):
+        """Completes a task, removing it as `remove` does, so later IDs move up one."""
+        if _lib.todo_complete(self._checked(), id) != 0:
+            raise TodoError("Invalid ID: %d" % id)
+
+    def batch(self, commands):
+        """Applies lines of add, edit and remove commands, all together or not at all."""
+        if isinstance(commands, (list, tuple)):
+            commands = "\n".join(commands)
+        if _lib.todo_batch(self._checked(), commands.encode("utf-8")) != 0:
+            raise TodoError(_take_string(_lib.todo_last_error()))
+
+    def close(self):
+        """Frees the list, saving unsaved changes only when autosaving."""
+        if self._handle:
+            _lib.todo_free(self._handle)
+            self._handle = None
+
+    def _checked(self):
+        if not self._handle:
+            raise TodoError("The todo list is closed")
+        return self._handle
+
+    def __len__(self):
+        return len(self.todos)
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def __del__(self):
+        self.close()
@@ -0,0 +1,111 @@
+//! `export --anonymize`: the todo list with its text replaced by placeholders, to share in
+//! a bug report without giving away what the tasks are or who they are for.
+//!
+//! Tasks, people, places, links, attachments, tags and projects become numbered
+//! placeholders such as `Task 3` or `person-2`. The same text always becomes the same
+//! placeholder, so items still share tags, projects and people as before, while IDs,
+//! priorities, dates, recurrences, dependencies and counts are kept as they are.
+
+use std::collections::BTreeMap;
+
+use crate::Todo;
+
+/// Numbers each distinct text of one kind in the order it is first seen.
+#[derive(Default)]
+struct Placeholders {
+    seen: BTreeMap<String, usize>,
+}
+
+impl Placeholders {
+    /// Returns the number of `text`, giving it the next one if it is new.
+    fn number(&mut self, text: &str) -> usize {
+        let next = self.seen.len() + 1;
+        *self.seen.entry(text.to_string()).or_insert(next)
+    }
+}
+
+/// Replaces the text of `todos` with placeholders, and their hashes, which are made from
+/// it, with new ones.
+pub fn anonymize(todos: &mut [Todo]) {
+    let mut tasks = Placeholders::default();
+    let mut people = Placeholders::default();
+    let mut places = Placeholders::default();
+    let mut urls = Placeholders::default();
+    let mut files = Placeholders::default();
+    let mut tags = Placeholders::default();
+    let mut projects = Placeholders::default();
+    for todo in todos.iter_mut() {
+        todo.task = format!("Task {}", tasks.number(&todo.task));
+        for person in [&mut todo.assignee, &mut todo.created_by]
+            .into_iter()
+            .flatten()
+        {
+            *person = format!("person-{}", people.number(person));
+        }
+        if let Some(location) = &mut todo.location {
+            *location = format!("place-{}", places.number(location));
+        }
+        if let Some(url) = &mut todo.url {
+            *url = format!("https://example.com/{}", urls.number(url));
+        }
+        for attachment in &mut todo.attachments {
+            // The extension is kept, as what is attached can matter to the bug
+            let extension = std::path::Path::new(attachment.as_str())
+                .extension()
+                .map(|extension| format!(".{}", extension.to_string_lossy()))
+                .unwrap_or_default();
+            *attachment = format!("attachment-{}{}", files.number(attachment), extension);
+        }
+        for tag in &mut todo.tags {
+            *tag = format!("tag-{}", tags.number(tag));
+        }
+        if let Some(project) = &mut todo.project {
+            *project = format!("project-{}", projects.number(project));
+        }
+        todo.hash.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::anonymize;
+    use crate::TodoList;
+
+    #[test]
+    fn test_anonymize() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Invoice Acme Corp", 4);
+        todo_list.add_todo("Call Jane at Acme", 2);
+        todo_list.add_todo("Invoice Acme Corp", 1);
+        todo_list.todos[0].tags = vec!["acme".to_string(), "money".to_string()];
+        todo_list.todos[1].tags = vec!["acme".to_string()];
+        todo_list.todos[0].assignee = Some("jane".to_string());
+        todo_list.todos[1].created_by = Some("jane".to_string());
+        todo_list.todos[1].assignee = Some("bob".to_string());
+        todo_list.todos[1].project = Some("acme-audit".to_string());
+        todo_list.todos[2].attachments = vec!["/home/me/acme/contract.pdf".to_string()];
+        todo_list.todos[2].url = Some("https://acme.example/board".to_string());
+        todo_list.todos[2].due = Some(1717232400);
+        todo_list.assign_hashes();
+        let hashes: Vec<String> = todo_list.todos.iter().map(|t| t.hash.clone()).collect();
+
+        anonymize(&mut todo_list.todos);
+        todo_list.assign_hashes();
+        let todos = &todo_list.todos;
+        let tasks: Vec<&str> = todos.iter().map(|todo| todo.task.as_str()).collect();
+        assert_eq!(tasks, ["Task 1", "Task 2", "Task 1"]);
+        assert_eq!(todos[0].tags, ["tag-1", "tag-2"]);
+        assert_eq!(todos[1].tags, ["tag-1"]);
+        assert_eq!(todos[0].assignee.as_deref(), Some("person-1"));
+        assert_eq!(todos[1].assignee.as_deref(), Some("person-2"));
+        assert_eq!(todos[1].created_by.as_deref(), Some("person-1"));
+        assert_eq!(todos[1].project.as_deref(), Some("project-1"));
+        assert_eq!(todos[2].attachments, ["attachment-1.pdf"]);
+        assert_eq!(todos[2].url.as_deref(), Some("https://example.com/1"));
+        assert_eq!(todos[2].due, Some(1717232400));
+        assert_eq!(todos[0].priority, 4);
+        assert!(todos.iter().all(|todo| !hashes.contains(&todo.hash)));
+        let json = todo_list.to_json();
+        assert!(!json.to_lowercase().contains("acme") && !json.contains("jane"));
+    }
+}
@@ -0,0 +1,313 @@
+//! Mirroring the Asana tasks assigned to you as todo items, for `sync asana`.
+//!
+//! The incomplete tasks assigned to you in the selected projects become tasks in projects
+//! of the same names, tagged with the sections they are in and linked to them, kept in step
+//! with them as `mirror` describes. Removing a mirrored task marks it done: the next sync
+//! completes it in Asana.
+
+use serde_json::{json, Value};
+
+use crate::dates::{self, Zone};
+use crate::mirror::{Item, Tracker};
+use crate::remote::{self, Remote, Response};
+
+/// The Asana API.
+const API: &str = "https://app.asana.com/api/1.0";
+/// The file holding the tasks mirrored by the last sync, relative to the todo list's
+/// directory.
+pub const STATE_FILE: &str = ".todo-asana";
+/// How many tasks each request asks for.
+const PAGE_SIZE: u32 = 100;
+
+/// An Asana task, with only the fields mirrored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Task {
+    pub gid: String,
+    pub name: String,
+    /// The `gid` of the person it is assigned to.
+    pub assignee: Option<String>,
+    /// Its due date, `YYYY-MM-DD`, when it has no time.
+    pub due_on: Option<String>,
+    /// When it is due, in RFC 3339, when it has a time.
+    pub due_at: Option<String>,
+    /// The sections it is in, with the `gid` of their project.
+    pub sections: Vec<(String, String)>,
+    pub url: Option<String>,
+}
+
+/// The Asana API, signed in to with a personal access token.
+pub struct Client {
+    remote: Remote,
+    authorization: String,
+}
+
+impl Client {
+    pub fn new(token: &str) -> Client {
+        Client {
+            remote: Remote::parse_with_port(API, 443).expect("The API URL is valid"),
+            authorization: format!("Bearer {}", token),
+        }
+    }
+
+    /// Sends a request for `path` under the API with a JSON body, unless it is null, and
+    /// reads the response.
+    fn call(&self, method: &str, path: &str, body: &Value) -> Result<Value, String> {
+        let body = match body {
+            Value::Null => String::new(),
+            body => body.to_string(),
+        };
+        let response = self
+            .remote
+            .request(
+                method,
+                path,
+                &[
+                    ("Authorization", &self.authorization),
+                    ("Accept", "application/json"),
+                    ("Content-Type", "application/json"),
+                ],
+                &body,
+            )
+            .map_err(|error| error.to_string())?;
+        if response.status != 200 {
+            return Err(error(&response));
+        }
+        serde_json::from_str(&response.body)
+            .map_err(|error| format!("Asana sent an invalid response: {}", error))
+    }
+
+    /// Returns the `gid` of the person the token belongs to.
+    pub fn me(&self) -> Result<String, String> {
+        let me = self.call("GET", "/users/me?opt_fields=gid", &Value::Null)?;
+        me["data"]["gid"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| "Asana sent no user".to_string())
+    }
+
+    /// Returns the name of the project `gid`.
+    pub fn project(&self, gid: &str) -> Result<String, String> {
+        let path = format!("/projects/{}?opt_fields=name", remote::encode(gid));
+        let project = self.call("GET", &path, &Value::Null)?;
+        project["data"]["name"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("Asana sent no name for project {}", gid))
+    }
+
+    /// Returns the incomplete tasks of the project `gid`.
+    pub fn tasks(&self, gid: &str) -> Result<Vec<Task>, String> {
+        let fields = "name,assignee,due_on,due_at,memberships.section.name,\
+                      memberships.project.gid,permalink_url";
+        let mut tasks = Vec::new();
+        let mut offset: Option<String> = None;
+        loop {
+            let mut path = format!(
+                "/tasks?project={}&completed_since=now&limit={}&opt_fields={}",
+                remote::encode(gid),
+                PAGE_SIZE,
+                remote::encode(fields)
+            );
+            if let Some(offset) = &offset {
+                path.push_str(&format!("&offset={}", remote::encode(offset)));
+            }
+            let page = self.call("GET", &path, &Value::Null)?;
+            tasks.extend(parse_tasks(&page)?);
+            match page["next_page"]["offset"].as_str() {
+                Some(next) => offset = Some(next.to_string()),
+                None => return Ok(tasks),
+            }
+        }
+    }
+
+    /// Marks the task `gid` complete.
+    pub fn complete(&self, gid: &str) -> Result<(), String> {
+        let path = format!("/tasks/{}", remote::encode(gid));
+        let body = json!({ "data": { "completed": true } });
+        self.call("PUT", &path, &body).map(|_| ())
+    }
+}
+
+/// Describes an unexpected response, with the messages Asana sent with it.
+fn error(response: &Response) -> String {
+    let body: Value = serde_json::from_str(&response.body).unwrap_or_default();
+    let messages: Vec<&str> = body["errors"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|error| error["message"].as_str())
+        .collect();
+    match messages.is_empty() {
+        true => format!("Asana answered with status {}", response.status),
+        false => format!(
+            "Asana answered with status {}: {}",
+            response.status,
+            messages.join("; ")
+        ),
+    }
+}
+
+/// Reads the tasks of a page of results.
+fn parse_tasks(page: &Value) -> Result<Vec<Task>, String> {
+    let tasks = page["data"].as_array().ok_or("Asana sent no tasks")?;
+    tasks
+        .iter()
+        .map(|task| {
+            let text = |value: &Value| value.as_str().map(str::to_string);
+            let sections = task["memberships"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|membership| {
+                    let project = text(&membership["project"]["gid"])?;
+                    Some((project, text(&membership["section"]["name"])?))
+                })
+                .collect();
+            Ok(Task {
+                gid: text(&task["gid"]).ok_or("A task has no gid")?,
+                name: text(&task["name"]).unwrap_or_default(),
+                assignee: text(&task["assignee"]["gid"]),
+                due_on: text(&task["due_on"]),
+                due_at: text(&task["due_at"]),
+                sections,
+                url: text(&task["permalink_url"]),
+            })
+        })
+        .collect()
+}
+
+/// Makes a tag of the name of a section, e.g. `in-progress` of `In progress`, or `None` for
+/// the default section of a project without any, `Untitled section`.
+fn section_tag(section: &str) -> Option<String> {
+    let section = section.trim().trim_end_matches(':');
+    if section.is_empty() || section.eq_ignore_ascii_case("untitled section") {
+        return None;
+    }
+    let words: Vec<String> = section.split_whitespace().map(str::to_lowercase).collect();
+    Some(words.join("-"))
+}
+
+/// Returns what the task mirroring `task`, of the project `project` named `name`, is, with
+/// its due date in the timezone `zone`, relative to the timestamp `now`.
+fn item(
+    task: &Task,
+    (project, name): (&str, &str),
+    default_priority: u64,
+    zone: Zone,
+    now: i64,
+) -> Result<Item, String> {
+    let due = match (&task.due_at, &task.due_on) {
+        (Some(due_at), _) => Some(
+            chrono::DateTime::parse_from_rfc3339(due_at)
+                .map_err(|_| format!("{}: invalid due time: {}", task.gid, due_at))?
+                .timestamp(),
+        ),
+        (None, Some(due_on)) => Some(
+            dates::parse_due(due_on, now, zone)
+                .map_err(|error| format!("{}: {}", task.gid, error))?,
+        ),
+        (None, None) => None,
+    };
+    Ok(Item {
+        key: task.gid.clone(),
+        task: task.name.trim().to_string(),
+        priority: default_priority,
+        due,
+        tags: task
+            .sections
+            .iter()
+            .filter(|(other, _)| other == project)
+            .filter_map(|(_, section)| section_tag(section))
+            .collect(),
+        project: Some(name.to_string()),
+        url: task.url.clone(),
+    })
+}
+
+/// A sync with the tasks assigned to you in some projects.
+pub struct Sync {
+    pub client: Client,
+    /// The `gid`s of the projects, as in their URLs.
+    pub projects: Vec<String>,
+    pub default_priority: u64,
+    /// The timezone dates without a time are in.
+    pub zone: Zone,
+}
+
+impl Tracker for Sync {
+    fn name(&self) -> &str {
+        "Asana"
+    }
+
+    fn source(&self) -> String {
+        "asana".to_string()
+    }
+
+    fn items(&self, now: i64) -> Result<Vec<Item>, String> {
+        let me = self.client.me()?;
+        let mut items: Vec<Item> = Vec::new();
+        for project in &self.projects {
+            let name = self.client.project(project)?;
+            for task in self.client.tasks(project)? {
+                let assigned = task.assignee.as_deref() == Some(me.as_str());
+                if !assigned || items.iter().any(|item| item.key == task.gid) {
+                    continue;
+                }
+                let project = (project.as_str(), name.as_str());
+                items.push(item(&task, project, self.default_priority, self.zone, now)?);
+            }
+        }
+        Ok(items)
+    }
+
+    fn complete(&self, key: &str) -> Result<(), String> {
+        self.client.complete(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{item, parse_tasks, section_tag};
+    use crate::dates::Zone;
+    use serde_json::json;
+
+    #[test]
+    fn test_item() {
+        assert_eq!(section_tag("In progress"), Some("in-progress".to_string()));
+        assert_eq!(section_tag("Backlog:"), Some("backlog".to_string()));
+        assert_eq!(section_tag("Untitled section"), None);
+
+        let page = json!({ "data": [
+            {
+                "gid": "1201", "name": "Draft the brief ",
+                "assignee": { "gid": "42", "resource_type": "user" },
+                "due_on": "2024-06-07", "due_at": null,
+                "memberships": [
+                    { "project": { "gid": "7" }, "section": { "name": "In progress" } },
+                    { "project": { "gid": "8" }, "section": { "name": "Later" } },
+                ],
+                "permalink_url": "https://app.asana.com/0/7/1201",
+            },
+            {
+                "gid": "1202", "name": "Call the printer", "assignee": null,
+                "due_on": "2024-06-07", "due_at": "2024-06-07T15:30:00.000Z",
+            },
+        ], "next_page": null });
+        let tasks = parse_tasks(&page).unwrap();
+        assert_eq!(tasks[0].assignee.as_deref(), Some("42"));
+        assert_eq!(tasks[1].assignee, None);
+
+        let first = item(&tasks[0], ("7", "Launch"), 3, Zone::Utc, 0).unwrap();
+        assert_eq!(
+            (first.key.as_str(), first.task.as_str()),
+            ("1201", "Draft the brief")
+        );
+        assert_eq!(first.due, Some(1717718400));
+        assert_eq!(first.tags, ["in-progress"]);
+        assert_eq!(first.project.as_deref(), Some("Launch"));
+        let second = item(&tasks[1], ("7", "Launch"), 3, Zone::Utc, 0).unwrap();
+        assert_eq!(second.due, Some(1717774200));
+        assert!(second.tags.is_empty());
+        assert!(parse_tasks(&json!({ "data": [{ "name": "No gid" }] })).is_err());
+    }
+}
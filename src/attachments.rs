@@ -0,0 +1,151 @@
+//! Files attached to todo items, either linked in place or copied into an `attachments`
+//! directory next to the todo list file.
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// The directory copied attachments are stored in, relative to the todo list file. Copies
+/// are recorded by this relative path so the list and its attachments can move together.
+pub const ATTACHMENTS_DIR: &str = "./attachments";
+
+/// The largest file, in bytes, that may be copied into the attachments directory.
+pub const MAX_COPY_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Resolves the file to attach, copying it into the attachments directory under `base`
+/// (the todo list's directory) when `copy` is set.
+///
+/// Returns the path to record on the todo item.
+///
+/// # Example
+///
+/// ```
+/// let reference = store_attachment(Path::new("./spec.pdf"), Path::new("."), true)?;
+/// ```
+pub fn store_attachment(source: &Path, base: &Path, copy: bool) -> io::Result<String> {
+    let metadata = fs::metadata(source)?;
+    if !metadata.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file", source.display()),
+        ));
+    }
+    if !copy {
+        return Ok(fs::canonicalize(source)?.display().to_string());
+    }
+    if metadata.len() > MAX_COPY_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} is larger than {} bytes; attach it without --copy instead",
+                source.display(),
+                MAX_COPY_SIZE
+            ),
+        ));
+    }
+    let name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "attachment".to_string());
+    fs::create_dir_all(base.join(ATTACHMENTS_DIR))?;
+    let reference = unique_reference(base, &name);
+    fs::copy(source, base.join(&reference))?;
+    Ok(reference.display().to_string())
+}
+
+/// Resolves a recorded attachment to a path: a linked file's own, or a copy's relative to
+/// `base`. Returns `None` for any other relative path, which could point anywhere once
+/// joined to `base`.
+pub fn resolve(base: &Path, reference: &str) -> Option<PathBuf> {
+    let path = Path::new(reference);
+    if is_stored_copy(reference) {
+        Some(base.join(path))
+    } else if path.is_absolute() {
+        Some(path.to_path_buf())
+    } else {
+        None
+    }
+}
+
+/// Returns whether the attachment was copied into the attachments directory, and so is
+/// owned by the todo list: whether it is a path such as `./attachments/spec.pdf`, with
+/// nothing that could lead out of the directory, such as `..`.
+pub fn is_stored_copy(reference: &str) -> bool {
+    let mut components = Path::new(reference).components();
+    components.next() == Some(Component::CurDir)
+        && components.next() == Some(Component::Normal("attachments".as_ref()))
+        && components.next().is_some()
+        && Path::new(reference)
+            .components()
+            .skip(2)
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Deletes the copied attachments among `references`, leaving linked files untouched.
+pub fn delete_stored_copies(base: &Path, references: &[String]) {
+    for reference in references
+        .iter()
+        .filter(|reference| is_stored_copy(reference))
+    {
+        if let Err(error) = fs::remove_file(base.join(reference)) {
+            if error.kind() != io::ErrorKind::NotFound {
+                println!("Unable to delete attachment {}: {}", reference, error);
+            }
+        }
+    }
+}
+
+/// Picks a reference in the attachments directory that does not clash with an existing copy.
+fn unique_reference(base: &Path, name: &str) -> PathBuf {
+    let directory = Path::new(ATTACHMENTS_DIR);
+    let mut reference = directory.join(name);
+    let mut suffix = 1;
+    while base.join(&reference).exists() {
+        reference = directory.join(format!("{}-{}", suffix, name));
+        suffix += 1;
+    }
+    reference
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{delete_stored_copies, is_stored_copy, resolve};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_is_stored_copy() {
+        assert!(is_stored_copy("./attachments/spec.pdf"));
+        assert!(!is_stored_copy("/home/alice/spec.pdf"));
+        assert!(!is_stored_copy("./attachments-old/spec.pdf"));
+        assert!(!is_stored_copy("./attachments"));
+        assert!(!is_stored_copy("attachments/spec.pdf"));
+    }
+
+    #[test]
+    fn test_traversal() {
+        let reference = "./attachments/../../victim.txt";
+        assert!(!is_stored_copy(reference));
+        assert!(!is_stored_copy("./attachments/notes/../../../victim.txt"));
+        assert_eq!(resolve(Path::new("lists"), reference), None);
+        assert_eq!(
+            resolve(Path::new("lists"), "/home/alice/spec.pdf"),
+            Some("/home/alice/spec.pdf".into())
+        );
+
+        // Removing a task with such a reference leaves the file it points to alone
+        let directory = std::env::temp_dir().join(format!("todo-traversal-{}", std::process::id()));
+        let base = directory.join("lists/work");
+        fs::create_dir_all(base.join("attachments")).unwrap();
+        let victim = directory.join("lists/victim.txt");
+        fs::write(&victim, "keep me").unwrap();
+        fs::write(base.join("attachments/spec.pdf"), "copy").unwrap();
+        delete_stored_copies(
+            &base,
+            &[reference.to_string(), "./attachments/spec.pdf".to_string()],
+        );
+        assert!(victim.exists());
+        assert!(!base.join("attachments/spec.pdf").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,183 @@
+//! `batch`: applying a file of `add`, `edit` and `remove` commands to the todo list at
+//! once, so that either all of them are saved or none is.
+
+use std::path::Path;
+
+use structopt::StructOpt;
+
+use crate::config::Config;
+use crate::ids::TaskRef;
+use crate::list::{NewTodo, Removal, TodoEdit};
+use crate::{workflow, Todo, TodoList};
+
+/// The commands a batch can hold, given as on the command line.
+#[derive(Debug, StructOpt)]
+pub enum Batched {
+    #[structopt(name = "add")]
+    Add(NewTodo),
+    #[structopt(name = "edit")]
+    Edit(TodoEdit),
+    #[structopt(name = "remove")]
+    Remove(Removal),
+}
+/// Parses the lines of a batch file into commands, each with its line number, checking that
+/// every one is an add, edit or remove.
+pub fn parse_batch(contents: &str) -> Result<Vec<(usize, Batched)>, String> {
+    let mut batch = Vec::new();
+    for (number, line) in (1..).zip(contents.lines()) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let words =
+            workflow::split_words(line).map_err(|error| format!("Line {}: {}", number, error))?;
+        if !matches!(
+            words.first().map(String::as_str),
+            Some("add" | "edit" | "remove")
+        ) {
+            return Err(format!(
+                "Line {}: '{}': only add, edit and remove can be batched",
+                number, line
+            ));
+        }
+        let command = Batched::from_iter_safe(std::iter::once("todo".to_string()).chain(words))
+            .map_err(|error| {
+                let message = error.message.lines().next().unwrap_or_default().to_string();
+                format!("Line {}: '{}': {}", number, line, message)
+            })?;
+        batch.push((number, command));
+    }
+    Ok(batch)
+}
+/// Applies batched commands to `todo_list` in order, each to the list as the one before
+/// left it, and returns the todo items removed, and apart from them those removed along
+/// with them by `--dependents cascade`. Stops at the first command that fails, leaving
+/// `todo_list` partly changed, so it must not be saved then.
+pub fn apply_batch(
+    todo_list: &mut TodoList,
+    batch: Vec<(usize, Batched)>,
+    config: &Config,
+    base: &Path,
+) -> Result<(Vec<Todo>, Vec<Todo>), String> {
+    let mut removed = Vec::new();
+    let mut cascaded = Vec::new();
+    for (number, command) in batch {
+        let invalid = |id: &u64| format!("Line {}: Invalid ID: {}", number, id);
+        let resolve = |todo_list: &TodoList, id: &TaskRef| {
+            todo_list
+                .resolve(id)
+                .map_err(|error| format!("Line {}: {}", number, error))
+        };
+        match command {
+            Batched::Add(new_todo) => {
+                new_todo
+                    .add_to(todo_list, config, base)
+                    .map_err(|error| format!("Line {}: {}", number, error))?;
+            }
+            Batched::Edit(edit) => {
+                edit.check_arguments()
+                    .map_err(|error| format!("Line {}: {}", number, error))?;
+                let id = resolve(todo_list, &edit.id)?;
+                if let Some(Err(error)) = edit.if_rev.map(|rev| todo_list.check_revision(id, rev)) {
+                    return Err(format!("Line {}: {}", number, error));
+                }
+                edit.apply_to(todo_list, config)
+                    .map_err(|error| format!("Line {}: {}", number, error))?;
+            }
+            Batched::Remove(Removal {
+                ids,
+                if_rev,
+                dependents,
+            }) => {
+                let ids = ids
+                    .iter()
+                    .map(|id| resolve(todo_list, id))
+                    .collect::<Result<Vec<u64>, String>>()?;
+                if let Some(id) = ids.iter().find(|id| todo_list.todo(**id).is_none()) {
+                    return Err(invalid(id));
+                }
+                match (if_rev, ids.as_slice()) {
+                    (None, _) => {}
+                    (Some(rev), [id]) => todo_list
+                        .check_revision(*id, rev)
+                        .map_err(|error| format!("Line {}: {}", number, error))?,
+                    (Some(_), _) => {
+                        return Err(format!("Line {}: --if-rev takes a single ID", number))
+                    }
+                }
+                let removing = todo_list
+                    .prepare_removal(&ids, dependents)
+                    .map_err(|error| format!("Line {}: {}", number, error))?;
+                for todo in removing
+                    .into_iter()
+                    .filter_map(|id| todo_list.remove_todo(id))
+                {
+                    match ids.contains(&todo.id) {
+                        true => removed.push(todo),
+                        false => cascaded.push(todo),
+                    }
+                }
+            }
+        }
+    }
+    Ok((removed, cascaded))
+}
+#[cfg(test)]
+mod tests {
+    use super::{apply_batch, parse_batch};
+    use crate::config::Config;
+    use crate::TodoList;
+    use std::path::Path;
+    #[test]
+    fn test_batch() {
+        let batch = parse_batch(
+            "# Weekly cleanup\n\nedit 1 --task \"Pay the rent\"\nremove 3 2\nadd 'Buy milk' 2 --due 2024-06-01\n",
+        )
+        .unwrap();
+        assert_eq!(
+            batch.iter().map(|(line, _)| *line).collect::<Vec<_>>(),
+            vec![3, 4, 5]
+        );
+        assert_eq!(
+            parse_batch("add x 3\nlist").unwrap_err(),
+            "Line 2: 'list': only add, edit and remove can be batched"
+        );
+        assert!(parse_batch("add \"Unclosed 3")
+            .unwrap_err()
+            .starts_with("Line 1: "));
+        assert!(parse_batch("remove")
+            .unwrap_err()
+            .starts_with("Line 1: 'remove': "));
+
+        let mut todo_list = TodoList::new();
+        for task in ["Pay rent", "Read docs", "Water plants"] {
+            todo_list.add_todo(task, 3);
+        }
+        let batch = parse_batch("edit 1 --task \"Pay the rent\"\nremove 3 2").unwrap();
+        let (removed, cascaded) =
+            apply_batch(&mut todo_list, batch, &Config::default(), Path::new(".")).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert!(cascaded.is_empty());
+        assert_eq!(todo_list.todos.len(), 1);
+        assert_eq!(todo_list.todos[0].task, "Pay the rent");
+
+        // The tasks removed because they depended on a removed one are kept apart
+        let mut cascading = todo_list.clone();
+        cascading.add_todo("Book flights", 3);
+        cascading.add_todo("Pack", 3);
+        cascading.add_dependencies(3, &[2]);
+        let batch = parse_batch("remove 2 --dependents cascade").unwrap();
+        let (removed, cascaded) =
+            apply_batch(&mut cascading, batch, &Config::default(), Path::new(".")).unwrap();
+        assert_eq!(removed[0].task, "Book flights");
+        assert_eq!(cascaded[0].task, "Pack");
+        assert_eq!(cascading.todos.len(), 1);
+
+        // The second line sees the list as the first left it
+        let batch = parse_batch("remove 1\nedit 1 --task x").unwrap();
+        assert_eq!(
+            apply_batch(&mut todo_list, batch, &Config::default(), Path::new(".")).unwrap_err(),
+            "Line 2: Invalid ID: 1"
+        );
+    }
+}
@@ -0,0 +1,598 @@
+//! Reading the busy times of an iCalendar (`.ics`) file, for `plan --calendar`, and its
+//! events and to-dos as todo items, for `import --format ics`.
+//!
+//! Only what is needed to know when someone is busy is read from each `VEVENT`: its start,
+//! end or duration, summary and recurrence (`RRULE`, `EXDATE` and instances moved with
+//! `RECURRENCE-ID`). Cancelled and transparent (free) events are left out. Times in Utc
+//! (ending in `Z`) are exact, and so are those with a `TZID` naming a timezone of the
+//! system's zoneinfo files; others, including those with a `TZID` it doesn't know, such as
+//! a Windows name, are read in the configured timezone.
+//!
+//! Imported, a `VEVENT` or `VTODO` is due at its `DUE`, or failing that its `DTSTART`, and
+//! takes its tags from `CATEGORIES`. Its hash is made from its `UID`, so importing the same
+//! calendar again matches the tasks it added the first time instead of adding them twice.
+
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::dates::Zone;
+use crate::ids;
+use crate::recurrence::Rule;
+use crate::zoneinfo;
+use crate::{is_url, priorities, Todo, TodoList};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// A time someone is busy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Busy {
+    pub start: i64,
+    pub end: i64,
+    pub summary: String,
+}
+
+/// A point in time as written in the calendar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Time {
+    local: NaiveDateTime,
+    /// The timezone `local` is in, when it is Utc or given by a `TZID`, rather than the
+    /// configured one.
+    zone: Option<Zone>,
+    /// Whether it is a date without a time, as all-day events have.
+    date: bool,
+}
+
+impl Time {
+    fn timestamp(&self, zone: Zone) -> Option<i64> {
+        self.zone.unwrap_or(zone).timestamp(self.local)
+    }
+}
+
+/// An event or to-do, possibly recurring.
+#[derive(Debug, Default)]
+struct Event {
+    /// Whether it is a `VTODO` rather than a `VEVENT`.
+    todo: bool,
+    /// The `X-WR-CALNAME` of the calendar it is in.
+    calendar: Option<String>,
+    uid: Option<String>,
+    summary: String,
+    start: Option<Time>,
+    end: Option<Time>,
+    /// The length given by `DURATION` instead of an end, in seconds.
+    duration: Option<i64>,
+    rule: Option<Rule>,
+    /// The start times of the occurrences left out or moved.
+    excluded: Vec<NaiveDateTime>,
+    /// The start time of the occurrence of another event this one replaces.
+    replaces: Option<NaiveDateTime>,
+    free: bool,
+    /// Whether it is completed or cancelled.
+    done: bool,
+    /// When a to-do is due.
+    due: Option<Time>,
+    categories: Vec<String>,
+    /// From 1, the most urgent, to 9, or 0 when it has none.
+    priority: u8,
+    location: Option<String>,
+    url: Option<String>,
+}
+
+/// Joins the lines the format folds at 75 characters, which continue with a space or tab.
+fn unfold(contents: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Reads a `TEXT` value, in which `\\`, `\,`, `\;` and `\n` are escaped.
+fn unescape(value: &str) -> String {
+    let mut text = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n' | 'N') => text.push('\n'),
+                Some(c) => text.push(c),
+                None => {}
+            },
+            (c, false) => text.push(c),
+        }
+    }
+    text
+}
+
+/// Splits a list of `TEXT` values at the commas not escaped.
+fn split_text(value: &str) -> Vec<String> {
+    let mut values = vec![String::new()];
+    let mut escaped = false;
+    for c in value.chars() {
+        match (c, escaped) {
+            (',', false) => values.push(String::new()),
+            (c, _) => values.last_mut().expect("There is a value").push(c),
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    values.iter().map(|value| unescape(value)).collect()
+}
+
+/// Parses a `DATE` (`20240606`) or `DATE-TIME` (`20240606T090000`, `20240606T090000Z`),
+/// in the timezone `tzid` given with it, if it is known.
+fn parse_time(value: &str, tzid: Option<Zone>) -> Option<Time> {
+    let (value, zone) = match value.strip_suffix('Z') {
+        Some(value) => (value, Some(Zone::Utc)),
+        None => (value, tzid),
+    };
+    if let Ok(local) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        return Some(Time {
+            local,
+            zone,
+            date: false,
+        });
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+    Some(Time {
+        local: date.and_time(chrono::NaiveTime::MIN),
+        zone: tzid,
+        date: true,
+    })
+}
+
+/// Returns the timezone named by the `TZID` parameter among `parameters`, e.g.
+/// `TZID=Europe/Berlin`, if there is one and it is in the system's zoneinfo files.
+fn tzid<'a>(parameters: impl Iterator<Item = &'a str>) -> Option<Zone> {
+    let name = parameters
+        .filter_map(|parameter| parameter.split_once('='))
+        .find(|(name, _)| name.eq_ignore_ascii_case("TZID"))?
+        .1;
+    // A leading / marks a name from a global registry, which the IANA names are
+    let name = name.trim_matches('"').trim_start_matches('/');
+    zoneinfo::load(name).ok().map(Zone::Named)
+}
+
+/// Parses a `DURATION` such as `PT1H30M`, `P1D` or `P2W` into seconds.
+fn parse_duration(value: &str) -> Option<i64> {
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(value) => (-1, value),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut rest = value.strip_prefix('P')?;
+    let mut total = 0;
+    let mut in_time = false;
+    while !rest.is_empty() {
+        if let Some(time) = rest.strip_prefix('T') {
+            in_time = true;
+            rest = time;
+            continue;
+        }
+        let end = rest.find(|c: char| !c.is_ascii_digit())?;
+        let amount: i64 = rest[..end].parse().ok()?;
+        let unit = match (&rest[end..end + 1], in_time) {
+            ("W", false) => 7 * 24 * 60 * 60,
+            ("D", false) => 24 * 60 * 60,
+            ("H", true) => 60 * 60,
+            ("M", true) => 60,
+            ("S", true) => 1,
+            _ => return None,
+        };
+        total += amount * unit;
+        rest = &rest[end + 1..];
+    }
+    Some(sign * total)
+}
+
+/// Reads the events and to-dos of an iCalendar file.
+fn parse_events(contents: &str) -> Result<Vec<Event>, String> {
+    let mut events = Vec::new();
+    let mut event: Option<Event> = None;
+    let mut calendar: Option<String> = None;
+    for (number, line) in unfold(contents).iter().enumerate() {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let mut parameters = name.split(';');
+        let name = parameters.next().unwrap_or_default().to_uppercase();
+        let tzid = tzid(parameters);
+        let parse_time = |value: &str| parse_time(value, tzid);
+        match (name.as_str(), value) {
+            ("BEGIN", "VCALENDAR") => calendar = None,
+            ("X-WR-CALNAME", name) if event.is_none() => calendar = Some(unescape(name)),
+            ("BEGIN", kind @ ("VEVENT" | "VTODO")) => {
+                event = Some(Event {
+                    todo: kind == "VTODO",
+                    calendar: calendar.clone(),
+                    ..Event::default()
+                })
+            }
+            ("END", "VEVENT" | "VTODO") => events.extend(event.take()),
+            (name, value) => {
+                let Some(event) = event.as_mut() else {
+                    continue;
+                };
+                let invalid = || format!("Line {}: invalid {}: {}", number + 1, name, value);
+                match name {
+                    "UID" => event.uid = Some(value.to_string()),
+                    "SUMMARY" => event.summary = unescape(value),
+                    "DTSTART" => event.start = Some(parse_time(value).ok_or_else(invalid)?),
+                    "DTEND" => event.end = Some(parse_time(value).ok_or_else(invalid)?),
+                    "DURATION" => event.duration = Some(parse_duration(value).ok_or_else(invalid)?),
+                    "RRULE" => {
+                        let rule = Rule::parse(value)
+                            .map_err(|error| format!("Line {}: {}", number + 1, error))?;
+                        event.rule = Some(rule);
+                    }
+                    "EXDATE" => {
+                        for value in value.split(',') {
+                            event
+                                .excluded
+                                .push(parse_time(value).ok_or_else(invalid)?.local);
+                        }
+                    }
+                    "RECURRENCE-ID" => {
+                        event.replaces = Some(parse_time(value).ok_or_else(invalid)?.local)
+                    }
+                    "TRANSP" => event.free = value.eq_ignore_ascii_case("TRANSPARENT"),
+                    "STATUS" => {
+                        let cancelled = value.eq_ignore_ascii_case("CANCELLED");
+                        event.free |= cancelled;
+                        event.done |= cancelled || value.eq_ignore_ascii_case("COMPLETED");
+                    }
+                    "COMPLETED" => event.done = true,
+                    "DUE" => event.due = Some(parse_time(value).ok_or_else(invalid)?),
+                    "CATEGORIES" => event.categories.extend(split_text(value)),
+                    "PRIORITY" => {
+                        event.priority = value
+                            .trim()
+                            .parse()
+                            .ok()
+                            .filter(|p| *p <= 9)
+                            .ok_or_else(invalid)?
+                    }
+                    "LOCATION" => event.location = Some(unescape(value)),
+                    "URL" => event.url = Some(value.to_string()),
+                    _ => {}
+                }
+            }
+        }
+    }
+    // An instance moved to another time replaces the occurrence of its recurring event
+    let mut moved: HashMap<String, Vec<NaiveDateTime>> = HashMap::new();
+    for event in &events {
+        if let (Some(uid), Some(replaces)) = (&event.uid, event.replaces) {
+            moved.entry(uid.clone()).or_default().push(replaces);
+        }
+    }
+    for event in events.iter_mut().filter(|event| event.replaces.is_none()) {
+        if let Some(moved) = event.uid.as_ref().and_then(|uid| moved.get(uid)) {
+            event.excluded.extend(moved);
+        }
+    }
+    Ok(events)
+}
+
+/// Reads the times someone is busy between the timestamps `from` and `to` from the
+/// contents of an iCalendar file, with times not in Utc or a known `TZID` in the timezone
+/// `zone`, sorted by start.
+///
+/// # Example
+///
+/// ```
+/// let busy = calendar::busy(&fs::read_to_string("my.ics")?, today, today + DAY, Zone::Local)?;
+/// ```
+pub fn busy(contents: &str, from: i64, to: i64, zone: Zone) -> Result<Vec<Busy>, String> {
+    let mut busy = Vec::new();
+    for event in parse_events(contents)? {
+        let Some(start) = event.start.filter(|_| !event.free && !event.todo) else {
+            continue;
+        };
+        let length = match (event.end, event.duration) {
+            (Some(end), _) => end.timestamp(zone).zip(start.timestamp(zone)),
+            (None, Some(duration)) => start.timestamp(zone).map(|start| (start + duration, start)),
+            // An event on a date lasts the day, and one at a time is over at once
+            (None, None) if start.date => start.timestamp(zone).map(|start| (start + DAY, start)),
+            (None, None) => None,
+        }
+        .map(|(end, start)| end - start)
+        .filter(|length| *length > 0);
+        let Some(length) = length else {
+            continue;
+        };
+        let starts: Vec<NaiveDateTime> = match &event.rule {
+            // A day to spare covers the difference between the calendar's clock and Utc
+            Some(rule) => rule
+                .occurrences(start.local)
+                .take_while(|local| local.and_utc().timestamp() < to + DAY)
+                .filter(|local| !event.excluded.contains(local))
+                .collect(),
+            None => vec![start.local],
+        };
+        for local in starts {
+            let Some(start) = Time { local, ..start }.timestamp(zone) else {
+                continue;
+            };
+            if start < to && start + length > from {
+                busy.push(Busy {
+                    start,
+                    end: start + length,
+                    summary: event.summary.clone(),
+                });
+            }
+        }
+    }
+    busy.sort_by_key(|busy| (busy.start, busy.end));
+    Ok(busy)
+}
+
+/// Returns the priority of a todo item on the default scale, 1 to 5, for an iCalendar `PRIORITY`, 1 to 9 with 1
+/// the most urgent, or `None` for 0, no priority.
+fn priority(priority: u8) -> Option<u64> {
+    match priority {
+        0 => None,
+        1 | 2 => Some(5),
+        3 | 4 => Some(4),
+        5 => Some(3),
+        6 | 7 => Some(2),
+        _ => Some(1),
+    }
+}
+
+/// Reads the events and to-dos of an iCalendar file as todo items, leaving out those
+/// completed or cancelled and, given `calendar`, those of other calendars. Times not in Utc
+/// or a known `TZID` are in the timezone `zone`, and a recurring event is due at its next occurrence after the
+/// timestamp `now`, or left out once it has none.
+pub fn tasks(
+    contents: &str,
+    calendar: Option<&str>,
+    zone: Zone,
+    default_priority: u64,
+    now: i64,
+) -> Result<Vec<Todo>, String> {
+    let mut list = TodoList::new();
+    for event in parse_events(contents)? {
+        let task = event.summary.trim();
+        // A moved instance is the same task as its recurring event
+        if event.done || event.replaces.is_some() || task.is_empty() {
+            continue;
+        }
+        if calendar.is_some_and(|calendar| event.calendar.as_deref() != Some(calendar)) {
+            continue;
+        }
+        let due = match (event.due.or(event.start), &event.rule) {
+            (Some(start), Some(rule)) => {
+                let next = rule
+                    .occurrences(start.local)
+                    .filter(|local| !event.excluded.contains(local))
+                    .filter_map(|local| Time { local, ..start }.timestamp(zone))
+                    .find(|due| *due > now);
+                match next {
+                    Some(due) => Some(due),
+                    None => continue,
+                }
+            }
+            (Some(start), None) => start.timestamp(zone),
+            (None, _) => None,
+        };
+        let priority = priority(event.priority)
+            .map(|priority| priorities::scale().rescale(priority))
+            .unwrap_or(default_priority);
+        let id = list
+            .add_todo(task, priority)
+            .ok_or_else(|| format!("Unable to add the task: {}", task))?;
+        let tags: Vec<String> = event
+            .categories
+            .iter()
+            .map(|category| category.split_whitespace().collect::<Vec<_>>().join("-"))
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        list.add_tags(id, &tags);
+        if let Some(due) = due {
+            list.set_due(id, due);
+        }
+        if let Some(location) = event
+            .location
+            .filter(|location| !location.trim().is_empty())
+        {
+            list.set_location(id, location.trim());
+        }
+        if let Some(url) = event.url.filter(|url| is_url(url)) {
+            list.set_url(id, &url);
+        }
+        if let (Some(uid), Some(todo)) = (&event.uid, list.todos.last_mut()) {
+            todo.hash = ids::generate(&[], &format!("ics:{}", uid));
+        }
+    }
+    Ok(list.todos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{busy, parse_duration, tasks};
+    use crate::dates::Zone;
+    use crate::zoneinfo::{self, TimeZone};
+
+    #[test]
+    fn test_busy() {
+        assert_eq!(parse_duration("PT1H30M"), Some(5400));
+        assert_eq!(parse_duration("P1DT2H"), Some(93600));
+        assert_eq!(parse_duration("PT1D"), None);
+
+        let calendar = "BEGIN:VCALENDAR\r
+BEGIN:VEVENT\r
+UID:standup\r
+SUMMARY:Standup\r
+DTSTART:20240603T090000\r
+DURATION:PT15M\r
+RRULE:FREQ=DAILY;BYDAY=MO,TU,WE,TH,FR\r
+EXDATE:20240605T090000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:standup\r
+RECURRENCE-ID:20240606T090000\r
+SUMMARY:Standup\r
+DTSTART:20240606T093000\r
+DTEND:20240606T094500\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Review\\, Q2\r
+DTSTART:20240606T120000Z\r
+DTEND:20240606T13\r
+ 0000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Focus time\r
+TRANSP:TRANSPARENT\r
+DTSTART:20240606T140000Z\r
+DTEND:20240606T160000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Offsite\r
+DTSTART;VALUE=DATE:20240607\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        // Thursday 2024-06-06, in Utc and two hours ahead of it
+        let day = 1717632000;
+        let hour = 60 * 60;
+        let found = busy(calendar, day, day + 24 * hour, Zone::Utc).unwrap();
+        let times: Vec<(i64, i64, &str)> = found
+            .iter()
+            .map(|busy| {
+                (
+                    (busy.start - day) / 60,
+                    (busy.end - day) / 60,
+                    busy.summary.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(times, [(570, 585, "Standup"), (720, 780, "Review, Q2")]);
+        let zone = Zone::parse("+02:00").unwrap();
+        let found = busy(calendar, day, day + 24 * hour, zone).unwrap();
+        assert_eq!(found[0].start, day + 7 * hour + 30 * 60);
+        assert_eq!(found[1].summary, "Review, Q2");
+        // The offsite lasts all of Friday, and only Thursday's standup was moved
+        let found = busy(calendar, day + 24 * hour, day + 48 * hour, Zone::Utc).unwrap();
+        let summaries: Vec<&str> = found.iter().map(|busy| busy.summary.as_str()).collect();
+        assert_eq!(summaries, ["Offsite", "Standup"]);
+        assert_eq!(found[0].end - found[0].start, 24 * hour);
+        let found = busy(calendar, day - 24 * hour, day, Zone::Utc).unwrap();
+        assert!(found.is_empty());
+
+        // Times with a TZID are in that timezone, whatever the configured one, and in the
+        // configured one when it is unknown
+        let berlin = TimeZone::from_rule("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        zoneinfo::insert("Test/Berlin", berlin);
+        let calendar = "BEGIN:VEVENT\r
+SUMMARY:Dentist\r
+DTSTART;TZID=Test/Berlin:20240606T090000\r
+DTEND;TZID=\"Test/Berlin\":20240606T100000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Call\r
+DTSTART;TZID=W. Europe Standard Time:20240606T150000\r
+DURATION:PT30M\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Weekly\r
+DTSTART;TZID=Test/Berlin:20240101T090000\r
+DURATION:PT1H\r
+RRULE:FREQ=WEEKLY;BYDAY=TH\r
+END:VEVENT\r
+";
+        for zone in [Zone::Utc, Zone::parse("-05:00").unwrap()] {
+            let found = busy(calendar, day, day + 24 * hour, zone).unwrap();
+            // 9:00 in Berlin is 7:00 Utc in summer, also for a series begun in winter
+            assert_eq!(found[0].start, day + 7 * hour);
+            assert_eq!(found[0].end, day + 8 * hour);
+            assert_eq!(found[1].start, day + 7 * hour);
+            assert_eq!(found[1].summary, "Weekly");
+        }
+        let found = busy(calendar, day, day + 24 * hour, Zone::Utc).unwrap();
+        assert_eq!(found[2].start, day + 15 * hour);
+
+        assert!(busy(
+            "BEGIN:VEVENT\nDTSTART:tomorrow\nEND:VEVENT",
+            0,
+            1,
+            Zone::Utc
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_tasks() {
+        let calendar = "BEGIN:VCALENDAR\r
+X-WR-CALNAME:Work\r
+BEGIN:VTODO\r
+UID:report@example.com\r
+SUMMARY:Send the report\r
+DUE:20240607T170000Z\r
+PRIORITY:1\r
+CATEGORIES:reports,Q2 close\r
+END:VTODO\r
+BEGIN:VTODO\r
+UID:done@example.com\r
+SUMMARY:Book the room\r
+STATUS:COMPLETED\r
+END:VTODO\r
+BEGIN:VEVENT\r
+UID:standup@example.com\r
+SUMMARY:Standup\r
+DTSTART:20240603T090000Z\r
+RRULE:FREQ=DAILY;COUNT=5\r
+EXDATE:20240606T090000Z\r
+LOCATION:Room 4\\, floor 2\r
+END:VEVENT\r
+END:VCALENDAR\r
+BEGIN:VCALENDAR\r
+X-WR-CALNAME:Home\r
+BEGIN:VEVENT\r
+SUMMARY:Dentist\r
+DTSTART;VALUE=DATE:20240610\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        // Wednesday 2024-06-05 at noon, in Utc
+        let now = 1717588800;
+        let todos = tasks(calendar, None, Zone::Utc, 3, now).unwrap();
+        let summary: Vec<(&str, u64, Option<i64>)> = todos
+            .iter()
+            .map(|todo| (todo.task.as_str(), todo.priority, todo.due))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Send the report", 5, Some(1717779600)),
+                // The standup of Thursday is left out, so Friday's is next
+                ("Standup", 3, Some(1717750800)),
+                ("Dentist", 3, Some(1717977600)),
+            ]
+        );
+        assert_eq!(todos[0].tags, ["reports", "Q2-close"]);
+        assert_eq!(todos[1].location.as_deref(), Some("Room 4, floor 2"));
+        // The same UID makes the same hash on every import
+        let again = tasks(calendar, Some("Work"), Zone::Utc, 3, now).unwrap();
+        assert_eq!(again.len(), 2);
+        assert_eq!(again[0].hash, todos[0].hash);
+        assert_ne!(todos[0].hash, todos[1].hash);
+        assert!(
+            tasks(calendar, Some("Home"), Zone::Utc, 3, now + 7 * 24 * 60 * 60)
+                .unwrap()
+                .iter()
+                .all(|todo| todo.task == "Dentist")
+        );
+        assert!(tasks(
+            "BEGIN:VTODO\nPRIORITY:high\nEND:VTODO",
+            None,
+            Zone::Utc,
+            3,
+            now
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,801 @@
+//! User configuration, read from a JSON file in the user's config directory.
+//!
+//! Settings are taken from, in order of precedence: command-line options, `TODO_<KEY>`
+//! environment variables (e.g. `TODO_DEFAULT_PRIORITY`), the config file, and the built-in
+//! defaults.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::dates::{DateFormat, Zone};
+use crate::formats::Format;
+use crate::locale::Locale;
+use crate::priorities::Scale;
+use crate::theme::Theme;
+use crate::workdays::Workdays;
+use crate::{asana, inbox, jira, priorities, remote, triage};
+
+/// The prefix of the environment variables overriding configuration keys.
+pub const ENV_PREFIX: &str = "TODO_";
+/// The environment variable naming the config file to read instead of the default one.
+pub const CONFIG_ENV: &str = "TODO_CONFIG";
+
+/// Every configuration key with a short description, in the order they are documented.
+pub const KEYS: &[(&str, &str)] = &[
+    (
+        "file",
+        "The todo list file, or the directory of a sharded list, used when --file is not given. Defaults to ./todos.json.",
+    ),
+    (
+        "file_format",
+        "The format of the default file, ./todos.<format>: \"json\", \"yaml\", \"toml\" or \"json5\". Other files are in the format of their extension.",
+    ),
+    (
+        "user",
+        "The name identifying you on shared lists. Defaults to $USER.",
+    ),
+    (
+        "list_mine_by_default",
+        "Whether list behaves like list --mine unless --all is given.",
+    ),
+    (
+        "default_priority",
+        "The priority given to tasks captured without one. Defaults to 3.",
+    ),
+    (
+        "priorities",
+        "The priorities from the least urgent to the most, with names typed instead of numbers and list colors, e.g. [{ \"name\": \"low\" }, { \"name\": \"high\", \"color\": \"red\" }]. Defaults to 1 to 5.",
+    ),
+    (
+        "projects",
+        "Defaults for new tasks by project, e.g. { \"work\": { \"priority\": 4, \"tags\": [\"work\"], \"due\": \"2d\" } }.",
+    ),
+    (
+        "inherit_project_tags",
+        "Whether every task in a project has the project's tags when filtering, even one added before they were set.",
+    ),
+    (
+        "locations",
+        "Named places, each mapped to the places considered near it.",
+    ),
+    (
+        "priority_aging",
+        "{ \"after_days\": n, \"on_load\": bool } raising the priority of old tasks.",
+    ),
+    ("signing_key", "The SSH private key used by sign."),
+    (
+        "allowed_signers",
+        "The allowed signers file used by verify.",
+    ),
+    (
+        "trusted_signer",
+        "The identity verify expects a shared list to be signed by.",
+    ),
+    (
+        "workflows",
+        "Named lists of commands for run, e.g. { \"morning\": [\"overdue\", \"search tag:{1}\"] }.",
+    ),
+    (
+        "views",
+        "Named queries for list --view, e.g. { \"deep-work\": \"priority>=4 and tag:focus\" }.",
+    ),
+    (
+        "default_view",
+        "The view list shows unless --view, --where or --all is given.",
+    ),
+    (
+        "default_sort",
+        "The order list sorts by when --sort is not given, e.g. \"due asc, priority desc\".",
+    ),
+    (
+        "ignore_accents",
+        "Whether searches and filters match whatever accents tasks have, so cafe finds Café.",
+    ),
+    (
+        "color",
+        "\"auto\", \"always\" or \"never\". Auto colors output to a terminal unless NO_COLOR is set.",
+    ),
+    (
+        "theme",
+        "The built-in color theme: \"default\", \"high-contrast\" or \"monochrome\".",
+    ),
+    (
+        "colors",
+        "Colors replacing the theme's, e.g. { \"priority5\": \"bold magenta\", \"overdue\": \"on-red\" }.",
+    ),
+    (
+        "date_format",
+        "How dates are shown in list, show and exports: \"iso\", \"short\", \"relative\" or a strftime string.",
+    ),
+    (
+        "priority_rules",
+        "Rules for add --suggest-priority and triage, e.g. [{ \"tag\": \"client\", \"adjust\": 1 }].",
+    ),
+    (
+        "daily_capacity",
+        "The time available for tasks each day that plan fills when --capacity is not given, e.g. \"6h\". Defaults to 8h.",
+    ),
+    (
+        "timezone",
+        "The timezone due dates are given and dates shown in: \"utc\" (the default), \"local\", a name such as \"Europe/Berlin\" or an offset such as \"+02:00\".",
+    ),
+    (
+        "locale",
+        "The language dates are typed in besides English and ISO, e.g. \"de\", \"fr\" or \"en-US\": its numeric dates, month and weekday names.",
+    ),
+    (
+        "holidays",
+        "The days off besides weekends that business days (--due +3bd) and --workdays tasks skip, e.g. [\"2026-04-03\", \"12-25\"], MM-DD for every year.",
+    ),
+    (
+        "plain",
+        "Whether to print labeled lines without color or graphics for screen readers, like --plain.",
+    ),
+    (
+        "remote",
+        "The URL of a list shared by serve that every command uses, like --remote.",
+    ),
+    (
+        "remote_token",
+        "The bearer token serve requires and --remote sends, to keep others off a shared list.",
+    ),
+    (
+        "remote_ca",
+        "A certificate to trust for an https:// remote, e.g. the one serve --tls-self-signed made.",
+    ),
+    (
+        "serve_tokens",
+        "More tokens serve accepts, each \"read-only\" or \"read-write\", e.g. { \"9f86d08\": \"read-only\" }.",
+    ),
+    (
+        "serve_tokens_file",
+        "A file of tokens serve accepts, a token and its scope on each line, read for every request.",
+    ),
+    (
+        "serve_rate_limit",
+        "How many requests a minute serve answers from each token or address, turning away more with 429.",
+    ),
+    (
+        "inbox",
+        "How serve makes tasks from what services post to /inbox/<name>, e.g. { \"github\": { \"task\": \"{/issue/title}\" } }.",
+    ),
+    (
+        "jira_url",
+        "The Jira site sync jira mirrors issues from, e.g. \"https://acme.atlassian.net\".",
+    ),
+    (
+        "jira_user",
+        "The email address sync jira signs in to Jira Cloud with, along with jira_token. Leave it out to sign in to Jira Server with a personal access token.",
+    ),
+    (
+        "jira_token",
+        "The API token, or personal access token, sync jira signs in to Jira with.",
+    ),
+    (
+        "jira_jql",
+        "The search sync jira mirrors when --jql is not given, e.g. \"assignee = currentUser() AND status != Done\".",
+    ),
+    (
+        "jira_transition",
+        "The transition sync jira moves an issue through when its task is removed. Defaults to \"Done\".",
+    ),
+    (
+        "asana_token",
+        "The personal access token sync asana signs in to Asana with.",
+    ),
+    (
+        "asana_projects",
+        "The projects sync asana mirrors your tasks from when --project is not given, by the IDs in their URLs, e.g. [\"1204512345678901\"].",
+    ),
+];
+
+/// Settings that apply to every invocation of the todo application.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The todo list file, or the directory of a sharded list, used when `--file` is not given.
+    pub file: Option<PathBuf>,
+    /// The format of the todo list file used when neither `--file` nor `file` is given.
+    pub file_format: Option<Format>,
+    /// The name identifying the current user on shared lists.
+    pub user: Option<String>,
+    /// Whether `list` only shows the current user's todo items unless `--all` is given.
+    pub list_mine_by_default: bool,
+    /// The priority given to todo items captured without one.
+    pub default_priority: Option<u64>,
+    /// The priorities from the least urgent to the most, five without names unless
+    /// configured otherwise.
+    pub priorities: Vec<priorities::Level>,
+    /// What todo items added to a project get unless told otherwise, by project.
+    pub projects: BTreeMap<String, ProjectDefaults>,
+    /// Whether todo items have the tags of their project's defaults when filtered, rather
+    /// than only those they were given.
+    pub inherit_project_tags: bool,
+    /// Named locations, each mapped to the other locations considered near it.
+    pub locations: BTreeMap<String, Vec<String>>,
+    /// The opt-in policy raising the priority of todo items that have been left open.
+    pub priority_aging: Option<AgingPolicy>,
+    /// The SSH private key used by `sign`.
+    pub signing_key: Option<PathBuf>,
+    /// The `allowed_signers` file listing the public keys `verify` trusts.
+    pub allowed_signers: Option<PathBuf>,
+    /// The identity `verify` expects a shared list to be signed by.
+    pub trusted_signer: Option<String>,
+    /// Named sequences of commands run by `run`, with `{1}`, `{2}`, ... and `{args}`
+    /// replaced by its arguments.
+    pub workflows: BTreeMap<String, Vec<String>>,
+    /// Named queries shown by `list --view`.
+    pub views: BTreeMap<String, String>,
+    /// The view `list` shows unless `--view`, `--where` or `--all` is given.
+    pub default_view: Option<String>,
+    /// The order `list` sorts by when `--sort` is not given, e.g. `"due asc, priority desc"`.
+    pub default_sort: Option<String>,
+    /// Whether queries match todo items whatever their accents, like `search --ignore-accents`.
+    pub ignore_accents: bool,
+    /// When output is colored.
+    pub color: ColorChoice,
+    /// The built-in theme output is colored with, `"default"` unless configured otherwise.
+    pub theme: Option<String>,
+    /// Colors replacing the theme's, by role, e.g. `{ "overdue": "bold red" }`.
+    pub colors: BTreeMap<String, String>,
+    /// How dates are shown: `"iso"`, `"short"`, `"relative"` or a strftime string.
+    pub date_format: Option<String>,
+    /// The rules `add --suggest-priority` and `triage` suggest priorities by, replacing the
+    /// built-in ones.
+    pub priority_rules: Vec<triage::Rule>,
+    /// The time `plan` fills each day when `--capacity` is not given, e.g. `"6h"`.
+    pub daily_capacity: Option<String>,
+    /// The timezone due dates are given and dates are shown in: `"utc"`, `"local"`, an IANA
+    /// name such as `"Europe/Berlin"` or an offset such as `"+02:00"`.
+    pub timezone: Option<String>,
+    /// The language dates are typed in besides English and ISO, e.g. `"de"` or `"en-US"`.
+    pub locale: Option<String>,
+    /// The days off besides weekends, each `"YYYY-MM-DD"`, or `"MM-DD"` for every year.
+    pub holidays: Vec<String>,
+    /// Whether output is written for screen readers: labeled lines without color, block
+    /// graphics or column layouts.
+    pub plain: bool,
+    /// The URL of a list shared by `serve` that commands run against instead of a local file.
+    pub remote: Option<String>,
+    /// The bearer token `serve` requires of requests, and `--remote` sends.
+    pub remote_token: Option<String>,
+    /// The certificate, or the authority signing it, trusted for an `https://` remote.
+    pub remote_ca: Option<PathBuf>,
+    /// More bearer tokens `serve` accepts, with what each allows.
+    pub serve_tokens: BTreeMap<String, remote::Scope>,
+    /// A file of bearer tokens `serve` accepts, each followed by its scope.
+    pub serve_tokens_file: Option<PathBuf>,
+    /// How many requests a minute `serve` answers from each client.
+    pub serve_rate_limit: Option<u32>,
+    /// How `serve` makes todo items from the payloads services post to `/inbox/<name>`, by
+    /// name.
+    pub inbox: BTreeMap<String, inbox::Mapping>,
+    /// The Jira site `sync jira` mirrors issues from.
+    pub jira_url: Option<String>,
+    /// The email address to sign in to Jira Cloud with, along with `jira_token`.
+    pub jira_user: Option<String>,
+    /// The API token, or personal access token without `jira_user`, to sign in to Jira with.
+    pub jira_token: Option<String>,
+    /// The search `sync jira` mirrors when `--jql` is not given.
+    pub jira_jql: Option<String>,
+    /// The transition an issue is moved through when its task is removed.
+    pub jira_transition: Option<String>,
+    /// The personal access token to sign in to Asana with.
+    pub asana_token: Option<String>,
+    /// The IDs of the projects `sync asana` mirrors when `--project` is not given.
+    pub asana_projects: Vec<String>,
+}
+
+/// When output is colored.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Color output to a terminal, unless `NO_COLOR` is set.
+    #[default]
+    Auto,
+    /// Always color output.
+    Always,
+    /// Never color output.
+    Never,
+}
+
+impl std::str::FromStr for ColorChoice {
+    type Err = String;
+    fn from_str(choice: &str) -> Result<ColorChoice, String> {
+        match choice {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("Unknown color choice: {}", choice)),
+        }
+    }
+}
+
+/// What todo items added to a project get unless told otherwise.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectDefaults {
+    /// The priority given to todo items added without one, instead of `default_priority`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u64>,
+    /// Tags added to every todo item.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// How long after being added todo items are due when no due date is given, e.g. `"2d"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+}
+
+/// Raises the priority of a todo item by one level for every `after_days` it stays open.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AgingPolicy {
+    /// The number of days a todo item may stay open before its priority is raised.
+    pub after_days: u64,
+    /// Whether the policy is applied every time the list is loaded, rather than only by `age`.
+    #[serde(default)]
+    pub on_load: bool,
+}
+
+impl Config {
+    /// The priority used when none is given, 3 unless configured otherwise.
+    pub fn default_priority(&self) -> u64 {
+        self.default_priority.unwrap_or(3)
+    }
+
+    /// Returns the defaults for todo items added to `project`, none if it has none.
+    pub fn project_defaults(&self, project: Option<&str>) -> ProjectDefaults {
+        project
+            .and_then(|project| self.projects.get(project))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns the tags the todo items of each project inherit, none unless
+    /// `inherit_project_tags` is set.
+    pub fn inherited_tags(&self) -> BTreeMap<String, Vec<String>> {
+        match self.inherit_project_tags {
+            true => self
+                .projects
+                .iter()
+                .filter(|(_, defaults)| !defaults.tags.is_empty())
+                .map(|(project, defaults)| (project.clone(), defaults.tags.clone()))
+                .collect(),
+            false => BTreeMap::new(),
+        }
+    }
+
+    /// Returns the name of the user running the application, preferring the configured
+    /// name over the `USER`/`USERNAME` environment variables.
+    pub fn current_user(&self) -> Option<String> {
+        self.user
+            .clone()
+            .or_else(|| {
+                ["USER", "USERNAME"]
+                    .iter()
+                    .find_map(|key| std::env::var(key).ok())
+            })
+            .filter(|user| !user.is_empty())
+    }
+
+    /// Returns whether output should be colored, which it never is in plain mode.
+    pub fn use_color(&self) -> bool {
+        if self.plain {
+            return false;
+        }
+        match self.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+        }
+    }
+
+    /// Returns the tokens `serve` accepts: `remote_token`, which can read and change the
+    /// list, `serve_tokens` and those in `serve_tokens_file`.
+    pub fn access(&self) -> remote::Access {
+        let mut tokens = self.serve_tokens.clone();
+        if let Some(token) = &self.remote_token {
+            tokens.insert(token.clone(), remote::Scope::ReadWrite);
+        }
+        remote::Access {
+            tokens,
+            file: self.serve_tokens_file.clone(),
+        }
+    }
+
+    /// Returns what `serve` needs to make todo items from the payloads posted to its inbox.
+    pub fn inbox(&self) -> Result<inbox::Inbox, String> {
+        Ok(inbox::Inbox {
+            mappings: self.inbox.clone(),
+            default_priority: self.default_priority(),
+            zone: self.timezone()?,
+        })
+    }
+
+    /// Returns the configured Jira site, signed in to with the configured token.
+    pub fn jira(&self) -> Result<jira::Server, String> {
+        let url = self
+            .jira_url
+            .as_deref()
+            .ok_or("Set jira_url in the config to the Jira site to sync with")?;
+        let token = self
+            .jira_token
+            .as_deref()
+            .ok_or("Set jira_token in the config, or TODO_JIRA_TOKEN, to sign in to Jira")?;
+        jira::Server::new(
+            url,
+            self.jira_user.as_deref(),
+            token,
+            self.remote_ca.clone(),
+        )
+    }
+
+    /// Returns the Asana API, signed in to with the configured token.
+    pub fn asana(&self) -> Result<asana::Client, String> {
+        self.asana_token
+            .as_deref()
+            .map(asana::Client::new)
+            .ok_or_else(|| {
+                "Set asana_token in the config, or TODO_ASANA_TOKEN, to sign in to Asana"
+                    .to_string()
+            })
+    }
+
+    /// Returns the configured theme, coloring output only when `use_color` allows it.
+    pub fn theme(&self) -> Result<Theme, String> {
+        Theme::new(
+            self.theme.as_deref().unwrap_or("default"),
+            &self.colors,
+            self.use_color(),
+        )
+    }
+
+    /// Returns the configured date format, each output's own when none is configured.
+    pub fn date_format(&self) -> Result<DateFormat, String> {
+        self.date_format
+            .as_deref()
+            .map_or(Ok(DateFormat::default()), DateFormat::parse)
+    }
+
+    /// Returns the configured priority rules, or the built-in ones when none are configured.
+    pub fn priority_rules(&self) -> Result<Vec<triage::Rule>, String> {
+        if self.priority_rules.is_empty() {
+            return Ok(triage::default_rules());
+        }
+        for rule in &self.priority_rules {
+            rule.check()?;
+        }
+        Ok(self.priority_rules.clone())
+    }
+
+    /// Returns the configured timezone, Utc when none is configured.
+    pub fn timezone(&self) -> Result<Zone, String> {
+        self.timezone.as_deref().map_or(Ok(Zone::Utc), Zone::parse)
+    }
+
+    /// Returns the configured locale, English when none is configured.
+    pub fn locale(&self) -> Result<Locale, String> {
+        self.locale.as_deref().map_or(Ok(Locale::En), Locale::parse)
+    }
+
+    /// Returns the priority scale, 1 to 5 when none is configured.
+    pub fn priorities(&self) -> Result<Scale, String> {
+        Scale::new(&self.priorities)
+    }
+
+    /// Returns the working days, without holidays when none are configured.
+    pub fn workdays(&self) -> Result<Workdays, String> {
+        Workdays::parse(&self.holidays)
+    }
+
+    /// Returns the query of the view called `name`, or an error listing the configured views.
+    pub fn view(&self, name: &str) -> Result<&str, String> {
+        match self.views.get(name) {
+            Some(query) => Ok(query),
+            None if self.views.is_empty() => {
+                Err(format!("Unknown view: '{}'. No views are configured", name))
+            }
+            None => Err(format!(
+                "Unknown view: '{}'. The configured views are: {}",
+                name,
+                self.views.keys().cloned().collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+
+    /// Returns whether a todo item at `location` should be listed when the user is `near`.
+    ///
+    /// A location is always near itself; the config may name further nearby locations.
+    pub fn is_near(&self, location: &str, near: &str) -> bool {
+        location.eq_ignore_ascii_case(near)
+            || self
+                .locations
+                .iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case(near))
+                .flat_map(|(_, nearby)| nearby)
+                .any(|nearby| nearby.eq_ignore_ascii_case(location))
+    }
+}
+
+/// Returns the location of the configuration file: `$TODO_CONFIG`,
+/// `$XDG_CONFIG_HOME/todo/config.json` or `~/.config/todo/config.json`.
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("todo").join("config.json"))
+}
+
+/// Loads the configuration file, falling back to the defaults when it does not exist, and
+/// applies any `TODO_<KEY>` environment variables on top.
+///
+/// # Example
+///
+/// ```
+/// let config = load_config();
+/// println!("{:?}", config.current_user());
+/// ```
+pub fn load_config() -> Config {
+    let config = match config_path() {
+        Some(path) if path.exists() => {
+            let contents = fs::read_to_string(&path).expect("Unable to read config file");
+            serde_json::from_str(&contents).expect("Unable to parse config file")
+        }
+        _ => Config::default(),
+    };
+    apply_env_overrides(config, |name| std::env::var(name).ok())
+}
+
+/// Returns the environment variable overriding a configuration key, e.g. `TODO_USER`.
+pub fn env_var(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.to_uppercase())
+}
+
+/// Overrides the keys of `config` that have a non-empty `TODO_<KEY>` variable, looked up
+/// with `var`.
+///
+/// A value is read as JSON when that gives a valid setting (`3`, `true`,
+/// `{"after_days": 14}`), and as a plain string otherwise. Values that are neither are
+/// ignored with a warning.
+fn apply_env_overrides(config: Config, var: impl Fn(&str) -> Option<String>) -> Config {
+    let mut config = serde_json::to_value(config).expect("Unable to serialize config");
+    for (key, _) in KEYS {
+        let name = env_var(key);
+        let raw = match var(&name).filter(|raw| !raw.is_empty()) {
+            Some(raw) => raw,
+            None => continue,
+        };
+        match with_setting(&config, key, &raw) {
+            Some(overridden) => config = overridden,
+            None => eprintln!(
+                "WARNING: ignoring {}={}, which is not a valid {}",
+                name, raw, key
+            ),
+        }
+    }
+    serde_json::from_value(config).expect("Unable to parse config")
+}
+
+/// Returns the settings `config` with `key` set to `raw`, read as JSON when that gives a
+/// valid setting and as a plain string otherwise, or `None` if it is neither.
+fn with_setting(config: &Value, key: &str, raw: &str) -> Option<Value> {
+    [
+        serde_json::from_str(raw).ok(),
+        Some(Value::String(raw.to_string())),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|value| {
+        let mut candidate = config.clone();
+        candidate[key] = value;
+        candidate
+    })
+    .find(|candidate| Config::deserialize(candidate).is_ok())
+}
+
+/// Checks that `key` is a configuration key.
+pub fn check_key(key: &str) -> Result<(), String> {
+    match KEYS.iter().any(|(known, _)| *known == key) {
+        true => Ok(()),
+        false => Err(format!(
+            "Unknown setting: '{}'. `config list --all` lists every one",
+            key
+        )),
+    }
+}
+
+/// Formats the value of a setting: a string as it is, anything else as JSON.
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Returns the settings of `config` that differ from the defaults, in the order of `KEYS`.
+pub fn changed(config: &Config) -> Vec<(&'static str, Value)> {
+    let config = serde_json::to_value(config).expect("Unable to serialize config");
+    let defaults = serde_json::to_value(Config::default()).expect("Unable to serialize config");
+    KEYS.iter()
+        .map(|(key, _)| (*key, config[*key].clone()))
+        .filter(|(key, value)| *value != defaults[*key])
+        .collect()
+}
+
+/// The settings written in a configuration file, without the defaults or the environment
+/// variables applied.
+pub struct ConfigFile {
+    path: PathBuf,
+    settings: Value,
+}
+
+impl ConfigFile {
+    /// Reads the configuration file at `path`, which has no settings if it does not exist.
+    pub fn load(path: PathBuf) -> Result<ConfigFile, String> {
+        let settings = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|error| format!("Unable to parse {}: {}", path.display(), error))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                Value::Object(Default::default())
+            }
+            Err(error) => return Err(format!("Unable to read {}: {}", path.display(), error)),
+        };
+        Ok(ConfigFile { path, settings })
+    }
+
+    /// Sets `key` to `raw`, read as `TODO_<KEY>` variables are.
+    pub fn set(&mut self, key: &str, raw: &str) -> Result<(), String> {
+        check_key(key)?;
+        self.settings = with_setting(&self.settings, key, raw)
+            .ok_or_else(|| format!("'{}' is not a valid {}", raw, key))?;
+        Ok(())
+    }
+
+    /// Removes `key`, so that it takes its default again. Returns whether it was set.
+    pub fn unset(&mut self, key: &str) -> Result<bool, String> {
+        check_key(key)?;
+        Ok(self
+            .settings
+            .as_object_mut()
+            .is_some_and(|settings| settings.remove(key).is_some()))
+    }
+
+    /// Writes the settings back to the file, creating its directory if need be.
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .map_err(|error| format!("Unable to create {}: {}", dir.display(), error))?;
+        }
+        let contents =
+            serde_json::to_string_pretty(&self.settings).expect("Unable to serialize config");
+        fs::write(&self.path, contents + "\n")
+            .map_err(|error| format!("Unable to write {}: {}", self.path.display(), error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_env_overrides, changed, check_key, format_value, ColorChoice, Config, ConfigFile,
+        KEYS,
+    };
+    use std::path::Path;
+
+    #[test]
+    fn test_keys_documented() {
+        let config = serde_json::to_value(Config::default()).unwrap();
+        let mut keys: Vec<&str> = config
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let mut documented: Vec<&str> = KEYS.iter().map(|(key, _)| *key).collect();
+        keys.sort_unstable();
+        documented.sort_unstable();
+        assert_eq!(keys, documented);
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let config: Config =
+            serde_json::from_str(r#"{ "user": "alice", "default_priority": 2 }"#).unwrap();
+        let config = apply_env_overrides(config, |name| match name {
+            "TODO_FILE" => Some("/srv/todos.json".to_string()),
+            "TODO_USER" => Some("123".to_string()),
+            "TODO_DEFAULT_PRIORITY" => Some("5".to_string()),
+            "TODO_COLOR" => Some("never".to_string()),
+            "TODO_LIST_MINE_BY_DEFAULT" => Some("yes".to_string()),
+            "TODO_PRIORITY_AGING" => Some(r#"{ "after_days": 14 }"#.to_string()),
+            "TODO_SIGNING_KEY" => Some(String::new()),
+            _ => None,
+        });
+        assert_eq!(config.file.as_deref(), Some(Path::new("/srv/todos.json")));
+        assert_eq!(config.user.as_deref(), Some("123"));
+        assert_eq!(config.default_priority(), 5);
+        assert_eq!(config.color, ColorChoice::Never);
+        assert!(!config.use_color());
+        assert!(!config.list_mine_by_default);
+        assert_eq!(
+            config.priority_aging.map(|policy| policy.after_days),
+            Some(14)
+        );
+        assert_eq!(config.signing_key, None);
+
+        let config: Config = serde_json::from_str(r#"{ "color": "always" }"#).unwrap();
+        assert!(config.use_color());
+        let config = apply_env_overrides(config, |name| match name {
+            "TODO_PLAIN" => Some("true".to_string()),
+            _ => None,
+        });
+        assert!(!config.use_color());
+    }
+
+    #[test]
+    fn test_view() {
+        let config: Config = serde_json::from_str(
+            r#"{ "views": { "deep-work": "priority>=4 and tag:focus", "errands": "has:location" } }"#,
+        )
+        .unwrap();
+        assert_eq!(config.view("deep-work"), Ok("priority>=4 and tag:focus"));
+        assert_eq!(
+            config.view("focus"),
+            Err("Unknown view: 'focus'. The configured views are: deep-work, errands".to_string())
+        );
+        assert!(Config::default().view("focus").is_err());
+    }
+
+    #[test]
+    fn test_is_near() {
+        let config: Config =
+            serde_json::from_str(r#"{ "locations": { "town": ["supermarket", "Pharmacy"] } }"#)
+                .unwrap();
+        assert!(config.is_near("town", "town"));
+        assert!(config.is_near("supermarket", "Town"));
+        assert!(config.is_near("pharmacy", "town"));
+        assert!(!config.is_near("home", "town"));
+        assert!(!config.is_near("town", "supermarket"));
+    }
+    #[test]
+    fn test_config_file() {
+        let path = std::env::temp_dir().join(format!("todo-config-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut file = ConfigFile::load(path.clone()).unwrap();
+        file.set("default_priority", "4").unwrap();
+        file.set("user", "alice").unwrap();
+        file.set("views", r#"{ "work": "tag:work" }"#).unwrap();
+        assert!(file.set("default_priority", "high").is_err());
+        assert!(file.set("colour", "never").is_err());
+        assert_eq!(file.unset("user"), Ok(true));
+        assert_eq!(file.unset("user"), Ok(false));
+        file.save().unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let config: Config = serde_json::from_str(&contents).unwrap();
+        assert_eq!(config.default_priority(), 4);
+        assert_eq!(config.user, None);
+        let changed: Vec<(&str, String)> = changed(&config)
+            .iter()
+            .map(|(key, value)| (*key, format_value(value)))
+            .collect();
+        assert_eq!(
+            changed,
+            [
+                ("default_priority", "4".to_string()),
+                ("views", r#"{"work":"tag:work"}"#.to_string())
+            ]
+        );
+        assert!(check_key("timezone").is_ok());
+    }
+}
@@ -0,0 +1,153 @@
+//! The active context: a tag or project that `list` is scoped to and new todo items are
+//! put in until it is cleared, kept in a file next to the todo list.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::Todo;
+
+/// The file holding the active context, relative to the todo list's directory.
+pub const CONTEXT_FILE: &str = ".todo-context";
+
+/// What a context scopes the todo list to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Context {
+    /// The todo items with a tag, e.g. `work`.
+    Tag(String),
+    /// The todo items in a project, written `project:<name>`.
+    Project(String),
+}
+
+impl Context {
+    /// Parses `<tag>` or `project:<name>`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// assert_eq!(Context::parse("work"), Ok(Context::Tag("work".to_string())));
+    /// ```
+    pub fn parse(input: &str) -> Result<Context, String> {
+        let input = input.trim();
+        let context = match input.strip_prefix("project:") {
+            Some(project) => Context::Project(project.to_string()),
+            None => Context::Tag(input.trim_start_matches('+').to_string()),
+        };
+        match &context {
+            Context::Tag(name) | Context::Project(name)
+                if name.is_empty() || name.contains(char::is_whitespace) =>
+            {
+                Err(format!(
+                    "Invalid context: '{}'. Expected a tag or project:<name>",
+                    input
+                ))
+            }
+            _ => Ok(context),
+        }
+    }
+
+    /// Returns whether `todo` belongs to the context, a tag context taking in the tags
+    /// `inherited` from its project.
+    pub fn matches(&self, todo: &Todo, inherited: &BTreeMap<String, Vec<String>>) -> bool {
+        match self {
+            Context::Tag(tag) => {
+                todo.tags.contains(tag)
+                    || todo
+                        .project
+                        .as_ref()
+                        .and_then(|project| inherited.get(project))
+                        .is_some_and(|tags| tags.contains(tag))
+            }
+            Context::Project(project) => todo.project.as_deref() == Some(project.as_str()),
+        }
+    }
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Context::Tag(tag) => write!(f, "{}", tag),
+            Context::Project(project) => write!(f, "project:{}", project),
+        }
+    }
+}
+
+/// Reads the active context of the todo list in `base`, if one is set. An unreadable or
+/// invalid context file is reported and ignored.
+pub fn load(base: &Path) -> Option<Context> {
+    let path = base.join(CONTEXT_FILE);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return None,
+        Err(error) => {
+            eprintln!("Ignoring {}: {}", path.display(), error);
+            return None;
+        }
+    };
+    match Context::parse(&contents) {
+        Ok(context) => Some(context),
+        Err(error) => {
+            eprintln!("Ignoring {}: {}", path.display(), error);
+            None
+        }
+    }
+}
+
+/// Makes `context` the active context of the todo list in `base`, or clears it.
+pub fn save(base: &Path, context: Option<&Context>) -> io::Result<()> {
+    let path = base.join(CONTEXT_FILE);
+    match context {
+        Some(context) => fs::write(path, format!("{}\n", context)),
+        None => match fs::remove_file(path) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Context;
+    use crate::TodoList;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Context::parse("work"), Ok(Context::Tag("work".to_string())));
+        assert_eq!(
+            Context::parse("+work"),
+            Ok(Context::Tag("work".to_string()))
+        );
+        assert_eq!(
+            Context::parse("project:home\n"),
+            Ok(Context::Project("home".to_string()))
+        );
+        assert!(Context::parse("").is_err());
+        assert!(Context::parse("project:").is_err());
+        assert!(Context::parse("two words").is_err());
+        assert_eq!(
+            Context::Project("home".to_string()).to_string(),
+            "project:home"
+        );
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Write report", 3);
+        todo_list.add_todo("Water plants", 2);
+        todo_list.add_tags(1, &["work".to_string()]);
+        todo_list.set_project(2, "home");
+        let work = Context::Tag("work".to_string());
+        let home = Context::Project("home".to_string());
+        let none = BTreeMap::new();
+        assert!(work.matches(&todo_list.todos[0], &none));
+        assert!(!work.matches(&todo_list.todos[1], &none));
+        assert!(home.matches(&todo_list.todos[1], &none));
+        assert!(!home.matches(&todo_list.todos[0], &none));
+        let inherited = BTreeMap::from([("home".to_string(), vec!["work".to_string()])]);
+        assert!(work.matches(&todo_list.todos[1], &inherited));
+    }
+}
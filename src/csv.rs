@@ -0,0 +1,193 @@
+//! Reading and writing CSV files (RFC 4180), for `import --format csv` and
+//! `export --format csv`.
+//!
+//! Fields are separated by a delimiter, a comma unless given, and may be quoted, in which
+//! case they can hold the delimiter, line breaks, and the quote itself written twice. A
+//! byte order mark at the start, as spreadsheets write, is skipped.
+
+/// A row of a CSV file and the line it starts on.
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    pub line: usize,
+    pub fields: Vec<String>,
+}
+
+/// How the fields of a CSV file are separated and quoted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dialect {
+    pub delimiter: char,
+    pub quote: char,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect {
+            delimiter: ',',
+            quote: '"',
+        }
+    }
+}
+
+/// Parses a delimiter or quote character given on the command line, where `tab` or `\t`
+/// stands for a tab.
+pub fn parse_char(input: &str) -> Result<char, String> {
+    let mut chars = input.chars();
+    match (input, chars.next(), chars.next()) {
+        ("tab" | "\\t", _, _) => Ok('\t'),
+        (_, Some(c), None) if c != '\n' && c != '\r' => Ok(c),
+        _ => Err(format!("Expected a single character, not '{}'", input)),
+    }
+}
+
+/// Splits the contents of a CSV file into records, leaving out blank lines.
+///
+/// # Example
+///
+/// ```
+/// let records = csv::parse("Title,Prio\n\"Buy milk, eggs\",2\n", Dialect::default())?;
+/// ```
+pub fn parse(contents: &str, dialect: Dialect) -> Result<Vec<Record>, String> {
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut start = 1;
+    // Whether the current field is quoted, and whether its closing quote has been read
+    let mut quoted = false;
+    let mut closed = false;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted && !closed {
+            match c {
+                c if c == dialect.quote && chars.peek() == Some(&dialect.quote) => {
+                    chars.next();
+                    field.push(c);
+                }
+                c if c == dialect.quote => closed = true,
+                c => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+        match c {
+            c if c == dialect.delimiter => {
+                fields.push(std::mem::take(&mut field));
+                (quoted, closed) = (false, false);
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                fields.push(std::mem::take(&mut field));
+                (quoted, closed) = (false, false);
+                if fields.iter().any(|field| !field.is_empty()) || fields.len() > 1 {
+                    records.push(Record {
+                        line: start,
+                        fields: std::mem::take(&mut fields),
+                    });
+                }
+                fields.clear();
+                line += 1;
+                start = line;
+            }
+            c if c == dialect.quote && field.is_empty() && !closed => quoted = true,
+            c if closed => {
+                return Err(format!(
+                    "Line {}: unexpected '{}' after a closing quote",
+                    line, c
+                ))
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted && !closed {
+        return Err(format!(
+            "Line {}: the quote opened here is not closed",
+            start
+        ));
+    }
+    fields.push(field);
+    if fields.iter().any(|field| !field.is_empty()) || fields.len() > 1 {
+        records.push(Record {
+            line: start,
+            fields,
+        });
+    }
+    Ok(records)
+}
+
+/// Writes `rows` as CSV with the default dialect, quoting the fields that hold a comma, a
+/// quote or a line break.
+pub fn write(rows: &[Vec<String>]) -> String {
+    let quote = |field: &String| match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.clone(),
+    };
+    rows.iter()
+        .map(|row| row.iter().map(quote).collect::<Vec<_>>().join(",") + "\r\n")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, parse_char, write, Dialect};
+
+    #[test]
+    fn test_parse() {
+        let fields = |contents: &str, dialect: Dialect| -> Vec<(usize, Vec<String>)> {
+            parse(contents, dialect)
+                .unwrap()
+                .into_iter()
+                .map(|record| (record.line, record.fields))
+                .collect()
+        };
+        let csv = "\u{feff}Title,Prio\r\n\"Buy milk, eggs\",2\r\n\r\n\"Say \"\"hi\"\"\nto Bob\",\n";
+        assert_eq!(
+            fields(csv, Dialect::default()),
+            [
+                (1, vec!["Title".to_string(), "Prio".to_string()]),
+                (2, vec!["Buy milk, eggs".to_string(), "2".to_string()]),
+                (4, vec!["Say \"hi\"\nto Bob".to_string(), String::new()]),
+            ]
+        );
+        let dialect = Dialect {
+            delimiter: ';',
+            quote: '\'',
+        };
+        assert_eq!(
+            fields("a;'b;c'", dialect),
+            [(1, vec!["a".to_string(), "b;c".to_string()])]
+        );
+
+        assert!(parse("a,\"b\nc", Dialect::default())
+            .unwrap_err()
+            .contains("Line 1"));
+        assert!(parse("a,\"b\"c", Dialect::default()).is_err());
+        assert_eq!(parse_char("tab"), Ok('\t'));
+        assert_eq!(parse_char(";"), Ok(';'));
+        assert!(parse_char(";;").is_err());
+    }
+
+    #[test]
+    fn test_write() {
+        let rows = vec![
+            vec!["task".to_string(), "tags".to_string()],
+            vec!["Say \"hi\"".to_string(), "work, home".to_string()],
+            vec!["Buy milk".to_string(), String::new()],
+        ];
+        let written = write(&rows);
+        assert_eq!(
+            written,
+            "task,tags\r\n\"Say \"\"hi\"\"\",\"work, home\"\r\nBuy milk,\r\n"
+        );
+        let read: Vec<Vec<String>> = parse(&written, Dialect::default())
+            .unwrap()
+            .into_iter()
+            .map(|record| record.fields)
+            .collect();
+        assert_eq!(read, rows);
+    }
+}
@@ -0,0 +1,733 @@
+//! Parsing of the dates, times and durations accepted on the command line, and the
+//! configurable format and timezone dates are displayed in.
+//!
+//! Times are stored as Unix timestamps. Those given on the command line for due dates,
+//! reminders and waits are in the configured timezone, Utc unless configured otherwise, and
+//! so are the dates displayed; other times, e.g. `--since`, are in Utc.
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{
+    DateTime, Datelike, FixedOffset, Local, NaiveDate, NaiveDateTime, Offset, TimeZone, Weekday,
+};
+
+use crate::locale::Locale;
+use crate::quick;
+use crate::workdays::{self, Workdays};
+use crate::zoneinfo;
+
+/// The timezone dates are given and displayed in, configured with `timezone`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Zone {
+    #[default]
+    Utc,
+    /// The system's timezone, following its daylight saving changes.
+    Local,
+    /// A fixed offset from Utc, e.g. `+05:30`.
+    Fixed(FixedOffset),
+    /// An IANA timezone, e.g. `Europe/Berlin`, following its daylight saving changes.
+    Named(&'static zoneinfo::TimeZone),
+}
+
+impl Zone {
+    /// Parses `utc`, `local`, an IANA name such as `Europe/Berlin`, or an offset such as
+    /// `+02:00`, `-0500` or `+9`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let zone = Zone::parse("+05:30")?;
+    /// ```
+    pub fn parse(zone: &str) -> Result<Zone, String> {
+        let invalid = || {
+            format!(
+                "Invalid timezone: '{}'. Expected utc, local, a name such as Europe/Paris or an offset such as +02:00",
+                zone
+            )
+        };
+        match zone.to_lowercase().as_str() {
+            "utc" | "z" => return Ok(Zone::Utc),
+            "local" => return Ok(Zone::Local),
+            _ => {}
+        }
+        let sign = match zone.get(..1) {
+            Some("+") => 1,
+            Some("-") => -1,
+            _ => return zoneinfo::load(zone).map(Zone::Named).map_err(|_| invalid()),
+        };
+        let digits = zone[1..].replace(':', "");
+        let (hours, minutes) = match digits.len() {
+            1 | 2 => (digits.as_str(), "0"),
+            4 => digits.split_at(2),
+            _ => return Err(invalid()),
+        };
+        let hours: i32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+        if minutes >= 60 {
+            return Err(invalid());
+        }
+        FixedOffset::east_opt(sign * (hours * 60 + minutes) * 60)
+            .map(Zone::Fixed)
+            .ok_or_else(invalid)
+    }
+
+    /// Returns the offset from Utc at the timestamp `timestamp`.
+    pub fn offset(&self, timestamp: i64) -> FixedOffset {
+        let utc = FixedOffset::east_opt(0).expect("Utc is a valid offset");
+        match self {
+            Zone::Utc => utc,
+            Zone::Local => DateTime::from_timestamp(timestamp, 0).map_or(utc, |datetime| {
+                Local.offset_from_utc_datetime(&datetime.naive_utc()).fix()
+            }),
+            Zone::Fixed(offset) => *offset,
+            Zone::Named(zone) => FixedOffset::east_opt(zone.offset(timestamp)).unwrap_or(utc),
+        }
+    }
+
+    /// Returns the wall-clock time in the timezone at the timestamp `timestamp`.
+    pub fn local(&self, timestamp: i64) -> NaiveDateTime {
+        DateTime::from_timestamp(timestamp, 0)
+            .unwrap_or_default()
+            .with_timezone(&self.offset(timestamp))
+            .naive_local()
+    }
+
+    /// Returns the timestamp of the wall-clock time `local` in the timezone, the earlier
+    /// one if the clocks go back over it, or `None` if they skip it.
+    pub fn timestamp(&self, local: NaiveDateTime) -> Option<i64> {
+        match self {
+            Zone::Utc => Some(local.and_utc().timestamp()),
+            Zone::Local => Local
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|datetime| datetime.timestamp()),
+            Zone::Fixed(offset) => offset
+                .from_local_datetime(&local)
+                .single()
+                .map(|datetime| datetime.timestamp()),
+            Zone::Named(zone) => {
+                // The offsets a day before and after are the only ones it could be in
+                let guess = local.and_utc().timestamp();
+                [
+                    zone.offset(guess - 24 * 3600),
+                    zone.offset(guess + 24 * 3600),
+                ]
+                .into_iter()
+                .map(|offset| guess - offset as i64)
+                .filter(|timestamp| (guess - timestamp) as i32 == zone.offset(*timestamp))
+                .min()
+            }
+        }
+    }
+
+    /// Returns the timestamp of the wall-clock time `local` like `timestamp`, or if the
+    /// clocks skip it, of the time an hour later, which they show instead.
+    pub fn timestamp_or_later(&self, local: NaiveDateTime) -> Option<i64> {
+        self.timestamp(local)
+            .or_else(|| self.timestamp(local + chrono::Duration::hours(1)))
+    }
+}
+
+/// Parses a date and time in one of the ISO formats, a bare date meaning midnight.
+fn parse_iso(input: &str) -> Option<NaiveDateTime> {
+    for format in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+    ] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(input, format) {
+            return Some(datetime);
+        }
+    }
+    NaiveDate::parse_from_str(input, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+}
+
+/// Parses a date such as `2024-06-01` or a date and time such as `2024-06-01 09:00`
+/// into a Unix timestamp. A bare date means midnight.
+///
+/// # Example
+///
+/// ```
+/// let timestamp = parse_datetime("2024-06-01 09:00")?;
+/// ```
+pub fn parse_datetime(input: &str) -> Result<i64, String> {
+    let input = input.trim();
+    parse_iso(input)
+        .map(|datetime| datetime.and_utc().timestamp())
+        .ok_or_else(|| format!("Invalid date: '{}'. Expected YYYY-MM-DD [HH:MM]", input))
+}
+
+/// Parses when something is due in the timezone `zone`, relative to the timestamp `now`:
+/// an ISO date and time such as `2024-06-01 17:00`, or a day (`today`, `tomorrow`, a
+/// weekday, `YYYY-MM-DD`) and a time of day (`9am`, `5:30pm`, `17:00`, `noon`) such as
+/// `today 17:00` or `fri 9am`. A day without a time means midnight, a time without a day
+/// means today, and a weekday means its next occurrence after today.
+///
+/// # Example
+///
+/// ```
+/// let due = parse_due("fri 9am", Utc::now().timestamp(), Zone::Local)?;
+/// ```
+pub fn parse_due(input: &str, now: i64, zone: Zone) -> Result<i64, String> {
+    parse_due_in(input, now, zone, Locale::En, &Workdays::default())
+}
+
+/// Parses when something is due like `parse_due`, also taking the day as it is written in
+/// `locale`, e.g. `15.11.2026`, `15. November` or `Freitag 9:00` in German, and as a number
+/// of the working days of `workdays` from today, e.g. `+3bd`.
+pub fn parse_due_in(
+    input: &str,
+    now: i64,
+    zone: Zone,
+    locale: Locale,
+    workdays: &Workdays,
+) -> Result<i64, String> {
+    let input = input.trim();
+    let invalid = || {
+        format!(
+            "Invalid date: '{}'. Expected YYYY-MM-DD [HH:MM], or a day and time such as \"today 17:00\" or \"fri 9am\"",
+            input
+        )
+    };
+    let today = zone.local(now).date();
+    let local = match parse_iso(input) {
+        Some(datetime) => datetime,
+        None => {
+            // The time of day is a word of its own, and the words around it the day
+            let mut time = None;
+            let mut day: Vec<&str> = Vec::new();
+            for word in input.split_whitespace() {
+                match quick::parse_time_word(word) {
+                    Some(_) if time.is_some() => return Err(invalid()),
+                    Some(clock) => time = Some(clock),
+                    None => day.push(word),
+                }
+            }
+            let day = day.join(" ");
+            let date = match day.as_str() {
+                "" if time.is_none() => return Err(invalid()),
+                "" => today,
+                day => workdays::parse_offset(day)
+                    .map(|days| workdays.add(today, days))
+                    .or_else(|| quick::parse_date_word(day, today))
+                    .or_else(|| locale.parse_date(day, today))
+                    .ok_or_else(invalid)?,
+            };
+            date.and_time(time.unwrap_or(chrono::NaiveTime::MIN))
+        }
+    };
+    zone.timestamp(local)
+        .ok_or_else(|| format!("'{}' does not exist in the configured timezone", input))
+}
+
+/// Parses a duration such as `90s`, `30m`, `1h`, `2d`, `1w` or `1h30m` into seconds.
+///
+/// # Example
+///
+/// ```
+/// assert_eq!(parse_duration("1h"), Ok(3600));
+/// ```
+pub fn parse_duration(input: &str) -> Result<i64, String> {
+    let input = input.trim();
+    let invalid = || format!("Invalid duration: '{}'. Expected e.g. 30m, 1h, 2d", input);
+    if input.is_empty() {
+        return Err(invalid());
+    }
+    let mut rest = input;
+    let mut total: i64 = 0;
+    while !rest.is_empty() {
+        let unit_start = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (amount, unit) = rest.split_at(unit_start);
+        let amount: i64 = amount.parse().map_err(|_| invalid())?;
+        let unit_end = unit
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(unit.len());
+        let unit_seconds = match &unit[..unit_end] {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            "w" => 7 * 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        total = amount
+            .checked_mul(unit_seconds)
+            .and_then(|seconds| total.checked_add(seconds))
+            .ok_or_else(invalid)?;
+        rest = &unit[unit_end..];
+    }
+    Ok(total)
+}
+
+/// Moves the due date `due` forward by `by` seconds, as of the timestamp `now`: from the
+/// due date itself if it is still to come, and from its time of day today in the timezone
+/// `zone` if it has passed, so that an overdue item is not overdue again straight away. It
+/// moves on the wall clock of `zone`, so a day later is at the same time of day even when
+/// daylight saving begins or ends in between. Returns an error if that is past the dates
+/// that can be represented.
+pub fn postpone(due: i64, by: i64, now: i64, zone: Zone) -> Result<i64, String> {
+    let from = match due >= now {
+        true => zone.local(due),
+        false => zone.local(now).date().and_time(zone.local(due).time()),
+    };
+    let too_far = || "Invalid duration: it moves the due date past the supported dates".to_string();
+    // A `Duration` holds milliseconds, so it can't be made from every number of seconds
+    let to = by
+        .checked_mul(1000)
+        .and_then(|by| from.checked_add_signed(chrono::Duration::milliseconds(by)))
+        .ok_or_else(too_far)?;
+    zone.timestamp_or_later(to)
+        .or_else(|| now.checked_add(by))
+        .ok_or_else(too_far)
+}
+
+/// How far `postpone` moves due dates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Postponement {
+    /// Forward by a number of seconds, as `postpone` moves them.
+    By(i64),
+    /// To a timestamp.
+    Until(i64),
+}
+
+impl Postponement {
+    /// Parses a duration such as `1d`, or else a date as `parse_due_in` does, e.g. `fri` or
+    /// `15.11.` in German.
+    pub fn parse(
+        input: &str,
+        now: i64,
+        zone: Zone,
+        locale: Locale,
+        workdays: &Workdays,
+    ) -> Result<Postponement, String> {
+        if let Ok(by) = parse_duration(input) {
+            return Ok(Postponement::By(by));
+        }
+        parse_due_in(input, now, zone, locale, workdays)
+            .map(Postponement::Until)
+            .map_err(|_| {
+                format!(
+                    "Invalid duration or date: '{}'. Expected a duration such as \"1d\" or \"2w\", or a date such as \"2024-06-10\" or \"fri 9am\"",
+                    input.trim()
+                )
+            })
+    }
+
+    /// Returns where the due date `due` moves to, as of the timestamp `now`, or an error if
+    /// that is past the supported dates.
+    pub fn apply(self, due: i64, now: i64, zone: Zone) -> Result<i64, String> {
+        match self {
+            Postponement::By(by) => postpone(due, by, now, zone),
+            Postponement::Until(until) => Ok(until),
+        }
+    }
+}
+
+/// Formats a number of seconds as hours and minutes, e.g. `1h30m`, `45m` or `26h`.
+pub fn format_duration(seconds: i64) -> String {
+    let minutes = seconds.max(0) / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, 0) => format!("{}h", hours),
+        (hours, minutes) => format!("{}h{}m", hours, minutes),
+    }
+}
+
+/// How dates are displayed: in a style configured with `date_format`, in the timezone
+/// configured with `timezone`, and with the occurrences of `--workdays` tasks on the working
+/// days of the configured `holidays`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DateFormat {
+    pub style: DateStyle,
+    pub zone: Zone,
+    pub workdays: Workdays,
+}
+
+/// The style dates are displayed in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum DateStyle {
+    /// Each output's own format, e.g. full timestamps in `list` and dates in exports.
+    #[default]
+    Default,
+    /// `2024-06-01T09:00:00Z`.
+    Iso,
+    /// `2024-06-01 09:00`.
+    Short,
+    /// How long ago or from now, e.g. `3 days ago` or `in 2 hours`.
+    Relative,
+    /// A strftime string, e.g. `%d %b %Y`.
+    Custom(String),
+}
+
+impl DateFormat {
+    /// Parses a preset (`iso`, `short`, `relative`) or a strftime string, for dates in Utc.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// assert_eq!(DateFormat::parse("short")?.style, DateStyle::Short);
+    /// ```
+    pub fn parse(format: &str) -> Result<DateFormat, String> {
+        let style = match format {
+            "default" => DateStyle::Default,
+            "iso" => DateStyle::Iso,
+            "short" => DateStyle::Short,
+            "relative" => DateStyle::Relative,
+            _ if !format.contains('%')
+                || StrftimeItems::new(format).any(|item| item == Item::Error) =>
+            {
+                return Err(format!(
+                    "Invalid date format: '{}'. Expected iso, short, relative or a strftime string such as \"%d %b %Y\"",
+                    format
+                ))
+            }
+            _ => DateStyle::Custom(format.to_string()),
+        };
+        Ok(DateFormat {
+            style,
+            zone: Zone::Utc,
+            workdays: Workdays::default(),
+        })
+    }
+
+    /// Returns the format displaying dates in the timezone `zone` instead.
+    pub fn in_zone(self, zone: Zone) -> DateFormat {
+        DateFormat { zone, ..self }
+    }
+
+    /// Returns the format showing occurrences on the working days of `workdays` instead.
+    pub fn with_workdays(self, workdays: Workdays) -> DateFormat {
+        DateFormat { workdays, ..self }
+    }
+
+    /// Formats the timestamp `timestamp`, relative to `now` for `relative`, or with the
+    /// strftime string `default` when no format is configured.
+    pub fn format(&self, timestamp: i64, now: i64, default: &str) -> String {
+        let pattern = match &self.style {
+            DateStyle::Default => default,
+            DateStyle::Iso if self.zone == Zone::Utc => "%Y-%m-%dT%H:%M:%SZ",
+            DateStyle::Iso => "%Y-%m-%dT%H:%M:%S%:z",
+            DateStyle::Short => "%Y-%m-%d %H:%M",
+            DateStyle::Relative => return self.format_relative(timestamp, now),
+            DateStyle::Custom(pattern) => pattern,
+        };
+        DateTime::from_timestamp(timestamp, 0)
+            .unwrap_or_default()
+            .with_timezone(&self.zone.offset(timestamp))
+            .format(pattern)
+            .to_string()
+    }
+
+    /// Formats a timestamp as how long before or after `now` it is, in the largest whole
+    /// unit up to days, e.g. `in 5 minutes` or `2 days ago`. Dates more than a month away
+    /// are shown as `YYYY-MM-DD`.
+    fn format_relative(&self, timestamp: i64, now: i64) -> String {
+        let seconds = (timestamp - now).abs();
+        let (amount, unit) = match seconds {
+            seconds if seconds < 60 => return "now".to_string(),
+            seconds if seconds < 60 * 60 => (seconds / 60, "minute"),
+            seconds if seconds < 24 * 60 * 60 => (seconds / (60 * 60), "hour"),
+            seconds if seconds < 31 * 24 * 60 * 60 => (seconds / (24 * 60 * 60), "day"),
+            _ => {
+                let date = DateFormat {
+                    style: DateStyle::Default,
+                    zone: self.zone,
+                    workdays: Workdays::default(),
+                };
+                return date.format(timestamp, now, "%Y-%m-%d");
+            }
+        };
+        let plural = if amount == 1 { "" } else { "s" };
+        if timestamp > now {
+            format!("in {} {}{}", amount, unit, plural)
+        } else {
+            format!("{} {}{} ago", amount, unit, plural)
+        }
+    }
+}
+
+/// Parses a point in the past such as `2 days ago`, `3h ago`, `yesterday`, `today`,
+/// `last monday` (the start of the most recent Monday before today), `this month` or
+/// `last month` (the start of this or the previous calendar month) or an absolute date,
+/// relative to the timestamp `now`.
+///
+/// # Example
+///
+/// ```
+/// let since = parse_since("2 days ago", Utc::now().timestamp())?;
+/// ```
+pub fn parse_since(input: &str, now: i64) -> Result<i64, String> {
+    let input = input.trim();
+    let start_of_today = now - now.rem_euclid(24 * 60 * 60);
+    let lowercase = input.to_lowercase();
+    match lowercase.as_str() {
+        "today" => return Ok(start_of_today),
+        "yesterday" => return Ok(start_of_today - 24 * 60 * 60),
+        "this month" | "last month" => {
+            let today = DateTime::from_timestamp(now, 0)
+                .unwrap_or_default()
+                .date_naive();
+            let (year, month) = match (lowercase.as_str(), today.month()) {
+                ("this month", month) => (today.year(), month),
+                (_, 1) => (today.year() - 1, 12),
+                (_, month) => (today.year(), month - 1),
+            };
+            let start = NaiveDate::from_ymd_opt(year, month, 1).expect("The 1st is a valid day");
+            return Ok(start.and_time(Default::default()).and_utc().timestamp());
+        }
+        _ => {}
+    }
+    if let Some(weekday) = lowercase
+        .strip_prefix("last ")
+        .and_then(|day| day.trim().parse::<Weekday>().ok())
+    {
+        let today = DateTime::from_timestamp(now, 0)
+            .unwrap_or_default()
+            .weekday()
+            .num_days_from_monday();
+        let days_back = (today + 6 - weekday.num_days_from_monday()) % 7 + 1;
+        return Ok(start_of_today - days_back as i64 * 24 * 60 * 60);
+    }
+    if let Some(ago) = input.strip_suffix("ago").map(str::trim) {
+        let ago = match ago.split_once(' ') {
+            Some((amount, unit)) => {
+                let unit = unit.trim().trim_end_matches('s');
+                let unit = match unit {
+                    "second" | "sec" => "s",
+                    "minute" | "min" => "m",
+                    "hour" => "h",
+                    "day" => "d",
+                    "week" => "w",
+                    _ => return Err(format!("Invalid time: '{}'", input)),
+                };
+                parse_duration(&format!("{}{}", amount, unit))?
+            }
+            None => parse_duration(ago)?,
+        };
+        return Ok(now - ago);
+    }
+    parse_datetime(input).map_err(|_| {
+        format!(
+            "Invalid time: '{}'. Expected e.g. \"2 days ago\", yesterday, \"last monday\", \"last month\" or YYYY-MM-DD",
+            input
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        format_duration, parse_datetime, parse_due, parse_due_in, parse_duration, parse_since,
+        postpone, DateFormat, Postponement, Zone,
+    };
+    use crate::locale::Locale;
+    use crate::workdays::Workdays;
+    use crate::zoneinfo::TimeZone;
+
+    #[test]
+    fn test_parse_datetime() {
+        assert_eq!(parse_datetime("2024-06-01"), Ok(1717200000));
+        assert_eq!(parse_datetime("2024-06-01 09:00"), Ok(1717232400));
+        assert_eq!(parse_datetime("2024-06-01T09:00:30"), Ok(1717232430));
+        assert!(parse_datetime("01/06/2024").is_err());
+        assert!(parse_datetime("2024-02-30").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Ok(90));
+        assert_eq!(parse_duration("30m"), Ok(1800));
+        assert_eq!(parse_duration("1h"), Ok(3600));
+        assert_eq!(parse_duration("2d"), Ok(172800));
+        assert_eq!(parse_duration("1w"), Ok(604800));
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("5").is_err());
+        assert!(parse_duration("5y").is_err());
+        assert_eq!(parse_duration("1h30m"), Ok(5400));
+        assert!(parse_duration("1h30").is_err());
+        assert!(parse_duration("").is_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(45 * 60), "45m");
+        assert_eq!(format_duration(5400), "1h30m");
+        assert_eq!(format_duration(26 * 3600), "26h");
+        assert_eq!(format_duration(0), "0m");
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = 1717232400; // 2024-06-01 09:00
+        assert_eq!(parse_since("2 days ago", now), Ok(now - 2 * 86400));
+        assert_eq!(parse_since("1 hour ago", now), Ok(now - 3600));
+        assert_eq!(parse_since("30m ago", now), Ok(now - 1800));
+        assert_eq!(parse_since("today", now), Ok(1717200000));
+        assert_eq!(parse_since("Yesterday", now), Ok(1717200000 - 86400));
+        assert_eq!(parse_since("2024-05-01", now), Ok(1714521600));
+        // 2024-06-01 is a Saturday.
+        assert_eq!(parse_since("last monday", now), Ok(1717200000 - 5 * 86400));
+        assert_eq!(parse_since("Last Sat", now), Ok(1717200000 - 7 * 86400));
+        assert_eq!(parse_since("last month", now), Ok(1714521600));
+        assert_eq!(parse_since("this month", now), Ok(1717200000));
+        assert_eq!(parse_since("last month", 1704067200), Ok(1701388800));
+        assert!(parse_since("last week", now).is_err());
+        assert!(parse_since("2 fortnights ago", now).is_err());
+        assert!(parse_since("whenever", now).is_err());
+    }
+
+    #[test]
+    fn test_date_format() {
+        // 2024-06-01 09:00 Utc.
+        let timestamp = 1717232400;
+        let full = "%Y-%m-%d %H:%M:%S";
+        let format = |format: &str, now: i64| {
+            DateFormat::parse(format)
+                .unwrap()
+                .format(timestamp, now, full)
+        };
+        assert_eq!(format("default", 0), "2024-06-01 09:00:00");
+        assert_eq!(format("iso", 0), "2024-06-01T09:00:00Z");
+        assert_eq!(format("short", 0), "2024-06-01 09:00");
+        assert_eq!(format("%d %b %Y", 0), "01 Jun 2024");
+        assert_eq!(format("relative", timestamp + 30), "now");
+        assert_eq!(format("relative", timestamp - 5 * 60), "in 5 minutes");
+        assert_eq!(format("relative", timestamp + 60 * 60), "1 hour ago");
+        assert_eq!(
+            format("relative", timestamp + 3 * 24 * 60 * 60),
+            "3 days ago"
+        );
+        assert_eq!(
+            format("relative", timestamp - 40 * 24 * 60 * 60),
+            "2024-06-01"
+        );
+        assert!(DateFormat::parse("%Q").is_err());
+        assert!(DateFormat::parse("long").is_err());
+
+        let zone = Zone::parse("+02:00").unwrap();
+        let format = |format: &str| {
+            DateFormat::parse(format)
+                .unwrap()
+                .in_zone(zone)
+                .format(timestamp, 0, full)
+        };
+        assert_eq!(format("default"), "2024-06-01 11:00:00");
+        assert_eq!(format("iso"), "2024-06-01T11:00:00+02:00");
+    }
+
+    #[test]
+    fn test_parse_due() {
+        let now = 1717232400; // 2024-06-01 09:00, a Saturday
+        let utc = Zone::Utc;
+        assert_eq!(parse_due("2024-06-01 17:00", now, utc), Ok(now + 8 * 3600));
+        assert_eq!(parse_due("today 17:00", now, utc), Ok(now + 8 * 3600));
+        assert_eq!(parse_due("5pm", now, utc), Ok(now + 8 * 3600));
+        assert_eq!(parse_due("tomorrow", now, utc), Ok(1717200000 + 86400));
+        // Friday 9am and 11pm are different deadlines
+        let friday = 1717200000 + 6 * 86400;
+        assert_eq!(parse_due("fri 9am", now, utc), Ok(friday + 9 * 3600));
+        assert_eq!(parse_due("11pm Friday", now, utc), Ok(friday + 23 * 3600));
+        assert_eq!(
+            parse_due("2024-06-10 noon", now, utc),
+            Ok(1717977600 + 12 * 3600)
+        );
+
+        // 17:00 at +02:00 is 15:00 Utc, and at 23:30 Utc on Friday it is Saturday there
+        let zone = Zone::parse("+02:00").unwrap();
+        assert_eq!(parse_due("today 17:00", now, zone), Ok(now + 6 * 3600));
+        assert_eq!(
+            parse_due("today 9am", 1717198200, zone),
+            Ok(1717200000 + 7 * 3600)
+        );
+        assert_eq!(
+            Zone::parse("-0530").unwrap().offset(0).local_minus_utc(),
+            -19800
+        );
+        assert!(Zone::parse("Mars/Olympus_Mons").is_err());
+        assert!(Zone::parse("+25:00").is_err());
+
+        // A named timezone follows its daylight saving changes, in and out of it
+        let berlin = TimeZone::from_rule("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        let berlin = Zone::Named(Box::leak(Box::new(berlin)));
+        assert_eq!(parse_due("2024-07-01 09:00", now, berlin), Ok(1719817200));
+        assert_eq!(parse_due("2024-01-15 09:00", now, berlin), Ok(1705305600));
+        // 02:30 comes twice on 2024-10-27, the first time in summer time, and not at all on
+        // 2024-03-31
+        assert_eq!(parse_due("2024-10-27 02:30", now, berlin), Ok(1729989000));
+        assert!(parse_due("2024-03-31 02:30", now, berlin).is_err());
+
+        assert!(parse_due("someday", now, utc).is_err());
+        assert!(parse_due("fri sat", now, utc).is_err());
+        assert!(parse_due("fri 9am please", now, utc).is_err());
+        assert!(parse_due("9am 5pm", now, utc).is_err());
+
+        let none = Workdays::default();
+        let de = Locale::parse("de").unwrap();
+        assert_eq!(
+            parse_due_in("5.6.2024 17:00", now, utc, de, &none),
+            Ok(1717545600 + 17 * 3600)
+        );
+        assert_eq!(
+            parse_due_in("Freitag 9:00", now, utc, de, &none),
+            Ok(friday + 9 * 3600)
+        );
+        assert_eq!(
+            parse_due_in("fri 9am", now, utc, de, &none),
+            Ok(friday + 9 * 3600)
+        );
+        assert!(parse_due("5.6.2024", now, utc).is_err());
+
+        // Business days skip the weekend and holidays: Monday, Wednesday and Thursday
+        let holidays = Workdays::parse(&["06-04".to_string()]).unwrap();
+        assert_eq!(
+            parse_due_in("+3bd 17:00", now, utc, Locale::En, &holidays),
+            Ok(1717200000 + 5 * 86400 + 17 * 3600)
+        );
+    }
+
+    #[test]
+    fn test_postpone() {
+        let now = 1717232400; // 2024-06-01 09:00
+        let utc = Zone::Utc;
+        let day = 24 * 3600;
+        // Due tomorrow at 17:00: a day later
+        assert_eq!(
+            postpone(now + 32 * 3600, day, now, utc),
+            Ok(now + 56 * 3600)
+        );
+        // Due three days ago at 17:00: tomorrow at 17:00
+        assert_eq!(
+            postpone(now - 64 * 3600, day, now, utc),
+            Ok(now + 32 * 3600)
+        );
+        // Due this morning at 8:00: in two hours, at 10:00
+        assert_eq!(postpone(now - 3600, 2 * 3600, now, utc), Ok(now + 3600));
+        // Due at 09:00 the day before summer time begins in Berlin: 09:00 the next day, 23
+        // hours later
+        let berlin = TimeZone::from_rule("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        let berlin = Zone::Named(Box::leak(Box::new(berlin)));
+        let due = 1711785600; // 2024-03-30 08:00 in Utc
+        assert_eq!(postpone(due, day, due - 3600, berlin), Ok(due + 23 * 3600));
+        // Past the dates that can be represented
+        let too_far =
+            Err("Invalid duration: it moves the due date past the supported dates".to_string());
+        assert_eq!(postpone(now, 99999999999 * day, now, utc), too_far);
+        assert_eq!(postpone(now, i64::MAX, now, utc), too_far);
+
+        // To a date instead, in the locale's words too
+        let none = Workdays::default();
+        let de = Locale::De;
+        assert_eq!(
+            Postponement::parse("2d", now, utc, de, &none),
+            Ok(Postponement::By(2 * day))
+        );
+        let monday = Postponement::parse("Montag 9:00", now, utc, de, &none).unwrap();
+        assert_eq!(monday, Postponement::Until(now + 2 * day));
+        assert_eq!(monday.apply(now - 64 * 3600, now, utc), Ok(now + 2 * day));
+        assert!(Postponement::parse("someday", now, utc, de, &none).is_err());
+    }
+}
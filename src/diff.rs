@@ -0,0 +1,221 @@
+//! Comparison of two versions of a todo list.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::theme::Theme;
+use crate::Todo;
+
+/// The differences between two versions of a todo list.
+#[derive(Debug, Default, Serialize)]
+pub struct ListDiff {
+    /// Todo items only present in the newer version.
+    pub added: Vec<Value>,
+    /// Todo items only present in the older version.
+    pub removed: Vec<Value>,
+    /// Todo items present in both versions with different details.
+    pub changed: Vec<ChangedTodo>,
+}
+
+/// A todo item whose details differ between two versions of a list.
+#[derive(Debug, Serialize)]
+pub struct ChangedTodo {
+    /// The todo item in the older version.
+    pub before: Value,
+    /// The todo item in the newer version.
+    pub after: Value,
+    /// The names of the fields that differ.
+    pub fields: Vec<String>,
+}
+
+impl ListDiff {
+    /// Returns whether the two versions are identical.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compares `before` with `after`.
+///
+/// IDs are renumbered when todo items are removed, so items are matched by their hash, or
+/// for lists saved before hashes by their creation time: first with an item that also has
+/// the same task, then with the item sharing the most details when several were created in
+/// the same second. Differences in the ID alone are not reported.
+pub fn diff_lists(before: &[Todo], after: &[Todo]) -> ListDiff {
+    let before: Vec<Value> = before.iter().map(to_value).collect();
+    let after: Vec<Value> = after.iter().map(to_value).collect();
+    let mut matched_before = vec![false; before.len()];
+    let mut pairs: Vec<Option<usize>> = vec![None; after.len()];
+
+    for (new_index, new) in after.iter().enumerate() {
+        let same = (0..before.len()).find(|old_index| {
+            !matched_before[*old_index]
+                && new.get("hash").is_some()
+                && before[*old_index].get("hash") == new.get("hash")
+        });
+        if let Some(old_index) = same {
+            matched_before[old_index] = true;
+            pairs[new_index] = Some(old_index);
+        }
+    }
+    for (new_index, new) in after.iter().enumerate() {
+        if pairs[new_index].is_some() {
+            continue;
+        }
+        let exact = (0..before.len()).find(|old_index| {
+            !matched_before[*old_index]
+                && before[*old_index]["created"] == new["created"]
+                && before[*old_index]["task"] == new["task"]
+        });
+        if let Some(old_index) = exact {
+            matched_before[old_index] = true;
+            pairs[new_index] = Some(old_index);
+        }
+    }
+    for (new_index, new) in after.iter().enumerate() {
+        if pairs[new_index].is_some() {
+            continue;
+        }
+        let closest = (0..before.len())
+            .filter(|old_index| {
+                !matched_before[*old_index] && before[*old_index]["created"] == new["created"]
+            })
+            .min_by_key(|old_index| changed_fields(&before[*old_index], new).len());
+        if let Some(old_index) = closest {
+            matched_before[old_index] = true;
+            pairs[new_index] = Some(old_index);
+        }
+    }
+
+    let mut diff = ListDiff::default();
+    for (new_index, new) in after.iter().enumerate() {
+        match pairs[new_index] {
+            Some(old_index) => {
+                let fields = changed_fields(&before[old_index], new);
+                if !fields.is_empty() {
+                    diff.changed.push(ChangedTodo {
+                        before: before[old_index].clone(),
+                        after: new.clone(),
+                        fields,
+                    });
+                }
+            }
+            None => diff.added.push(new.clone()),
+        }
+    }
+    diff.removed = before
+        .into_iter()
+        .zip(matched_before)
+        .filter(|(_, matched)| !matched)
+        .map(|(todo, _)| todo)
+        .collect();
+    diff
+}
+
+fn to_value(todo: &Todo) -> Value {
+    serde_json::to_value(todo).expect("Unable to serialize todo")
+}
+
+/// Lists the fields whose values differ, ignoring the ID and hash, which say which item it
+/// is rather than what it is.
+pub fn changed_fields(before: &Value, after: &Value) -> Vec<String> {
+    let empty = serde_json::Map::new();
+    let before = before.as_object().unwrap_or(&empty);
+    let after = after.as_object().unwrap_or(&empty);
+    let mut fields: Vec<String> = before
+        .keys()
+        .chain(after.keys().filter(|key| !before.contains_key(*key)))
+        .filter(|key| {
+            !["id", "hash"].contains(&key.as_str()) && before.get(*key) != after.get(*key)
+        })
+        .cloned()
+        .collect();
+    fields.sort();
+    fields
+}
+
+/// Prints a diff as `+`/`-`/`~` lines in the colors of `theme`, or as lines labeled in
+/// words when `plain` is set.
+pub fn print_diff(diff: &ListDiff, theme: &Theme, plain: bool) {
+    if diff.is_empty() {
+        println!("No differences");
+        return;
+    }
+    let [added, removed, changed] = if plain {
+        ["added:", "removed:", "changed:"]
+    } else {
+        ["+", "-", "~"]
+    };
+    for todo in &diff.added {
+        println!(
+            "{}",
+            theme.paint("added", &format!("{} {}", added, describe(todo)))
+        );
+    }
+    for todo in &diff.removed {
+        println!(
+            "{}",
+            theme.paint("removed", &format!("{} {}", removed, describe(todo)))
+        );
+    }
+    for change in &diff.changed {
+        println!(
+            "{}",
+            theme.paint(
+                "changed",
+                &format!("{} {}", changed, describe(&change.after))
+            )
+        );
+        for field in &change.fields {
+            let before = change.before.get(field).unwrap_or(&Value::Null);
+            let after = change.after.get(field).unwrap_or(&Value::Null);
+            if plain {
+                println!("    {}: was {}, now {}", field, before, after);
+            } else {
+                println!("    {}: {} -> {}", field, before, after);
+            }
+        }
+    }
+}
+
+fn describe(todo: &Value) -> String {
+    format!(
+        "{}: {}",
+        todo.get("id").unwrap_or(&Value::Null),
+        todo.get("task").and_then(Value::as_str).unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff_lists;
+    use crate::TodoList;
+
+    #[test]
+    fn test_diff_lists() {
+        let mut before = TodoList::new();
+        before.add_todo("task 1", 1);
+        before.add_todo("task 2", 2);
+        before.add_todo("task 3", 3);
+        before.todos[1].created -= 10;
+        before.todos[2].created -= 20;
+
+        let mut after = TodoList::new();
+        after.todos = serde_json::from_value(serde_json::to_value(&before.todos).unwrap()).unwrap();
+        after.remove_todo(1);
+        after.todos[0].priority = 5;
+        after.add_todo("task 4", 4);
+        after.todos[2].created += 5;
+
+        let diff = diff_lists(&before.todos, &after.todos);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0]["task"], "task 4");
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0]["task"], "task 1");
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].after["task"], "task 2");
+        assert_eq!(diff.changed[0].fields, vec!["priority"]);
+
+        assert!(diff_lists(&before.todos, &before.todos).is_empty());
+    }
+}
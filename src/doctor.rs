@@ -0,0 +1,237 @@
+//! Consistency checks over a loaded todo list, with optional repairs.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Datelike};
+
+use crate::attachments::{self, ATTACHMENTS_DIR};
+use crate::priorities;
+use crate::recurrence::Rule;
+use crate::TodoList;
+
+/// Checks `todo_list` for duplicate IDs, dependencies on missing tasks or on the task
+/// itself, invalid priorities, unparsable timestamps and recurrence rules, and attachment
+/// files that are missing or no longer referenced.
+///
+/// Attachments are resolved relative to `base`, the todo list's directory.
+/// Returns a description of every problem found. When `fix` is set the problems are
+/// repaired as they are found: IDs are renumbered, with the dependencies following them,
+/// broken dependencies dropped, priorities clamped to the scale, broken
+/// timestamps and rules dropped (or reset to `now` for creation times), missing
+/// attachments unlinked and orphaned copies deleted.
+///
+/// # Example
+///
+/// ```
+/// for problem in doctor::check(&mut todo_list, Path::new("."), false, Utc::now().timestamp()) {
+///     println!("{}", problem);
+/// }
+/// ```
+pub fn check(todo_list: &mut TodoList, base: &Path, fix: bool, now: i64) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let mut seen = HashSet::new();
+    let mut ids_broken = false;
+    for (expected, todo) in (1..).zip(&todo_list.todos) {
+        if !seen.insert(todo.id) {
+            problems.push(format!("Duplicate ID {} on '{}'", todo.id, todo.task));
+            ids_broken = true;
+        } else if todo.id != expected {
+            ids_broken = true;
+        }
+    }
+    for todo in &todo_list.todos {
+        for depend in &todo.depends {
+            if *depend == todo.id {
+                problems.push(format!(
+                    "Task {} ('{}') depends on itself",
+                    todo.id, todo.task
+                ));
+            } else if !seen.contains(depend) {
+                problems.push(format!(
+                    "Task {} ('{}') depends on task {}, which does not exist",
+                    todo.id, todo.task, depend
+                ));
+            }
+        }
+    }
+    if ids_broken {
+        problems.push("IDs are not numbered 1, 2, 3, ... in order".to_string());
+        if fix {
+            // Also drops the dependencies on missing tasks
+            todo_list.renumber();
+        }
+    }
+
+    let count = todo_list.todos.len() as u64;
+    for todo in &mut todo_list.todos {
+        let label = format!("Task {} ('{}')", todo.id, todo.task);
+        if fix {
+            let id = todo.id;
+            todo.depends
+                .retain(|depend| *depend != id && (1..=count).contains(depend));
+        }
+        let scale = priorities::scale();
+        if !scale.contains(todo.priority) {
+            problems.push(format!("{} has invalid priority {}", label, todo.priority));
+            if fix {
+                todo.priority = scale.clamp(todo.priority as i64);
+            }
+        }
+        if !valid_timestamp(todo.created) {
+            problems.push(format!("{} has an invalid creation time", label));
+            if fix {
+                todo.created = now;
+            }
+        }
+        for (name, timestamp) in [
+            ("due date", &mut todo.due),
+            ("modification time", &mut todo.modified),
+            ("aging time", &mut todo.aged),
+        ] {
+            if timestamp.is_some_and(|timestamp| !valid_timestamp(timestamp)) {
+                problems.push(format!("{} has an invalid {}", label, name));
+                if fix {
+                    *timestamp = None;
+                }
+            }
+        }
+        let invalid_reminders = todo
+            .reminders
+            .iter()
+            .filter(|reminder| !valid_timestamp(reminder.at))
+            .count();
+        if invalid_reminders > 0 {
+            problems.push(format!(
+                "{} has {} reminder(s) at invalid times",
+                label, invalid_reminders
+            ));
+            if fix {
+                todo.reminders
+                    .retain(|reminder| valid_timestamp(reminder.at));
+            }
+        }
+        if let Some(Err(error)) = todo.recur.as_deref().map(Rule::parse) {
+            problems.push(format!(
+                "{} has an invalid recurrence rule: {}",
+                label, error
+            ));
+            if fix {
+                todo.recur = None;
+            }
+        }
+        let missing: Vec<String> = todo
+            .attachments
+            .iter()
+            .filter(|attachment| {
+                !attachments::resolve(base, attachment).is_some_and(|path| path.exists())
+            })
+            .cloned()
+            .collect();
+        for attachment in &missing {
+            match attachments::resolve(base, attachment) {
+                Some(_) => problems.push(format!("{} links to missing file {}", label, attachment)),
+                None => problems.push(format!(
+                    "{} links to {}, which is neither in the attachments directory nor an absolute path",
+                    label, attachment
+                )),
+            }
+        }
+        if fix {
+            todo.attachments
+                .retain(|attachment| !missing.contains(attachment));
+        }
+    }
+
+    let referenced: HashSet<&str> = todo_list
+        .todos
+        .iter()
+        .flat_map(|todo| &todo.attachments)
+        .map(String::as_str)
+        .collect();
+    let orphans: Vec<String> = fs::read_dir(base.join(ATTACHMENTS_DIR))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| {
+            Path::new(ATTACHMENTS_DIR)
+                .join(entry.file_name())
+                .display()
+                .to_string()
+        })
+        .filter(|path| !referenced.contains(path.as_str()))
+        .collect();
+    for orphan in &orphans {
+        problems.push(format!("Attachment {} belongs to no task", orphan));
+    }
+    if fix {
+        attachments::delete_stored_copies(base, &orphans);
+    }
+
+    problems
+}
+
+/// Returns whether a timestamp can be displayed as a date between 1970 and 9999.
+fn valid_timestamp(timestamp: i64) -> bool {
+    DateTime::from_timestamp(timestamp, 0)
+        .is_some_and(|datetime| (1970..=9999).contains(&datetime.year()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    use crate::TodoList;
+    use std::path::Path;
+
+    #[test]
+    fn test_check() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+        todo_list.add_todo("task 2", 2);
+        todo_list.add_todo("task 3", 3);
+        let now = todo_list.todos[0].created;
+        assert!(check(&mut todo_list, Path::new("."), false, now).is_empty());
+
+        todo_list.todos[1].id = 1;
+        todo_list.todos[1].priority = 9;
+        todo_list.todos[2].created = -5;
+        todo_list.todos[2].due = Some(i64::MAX);
+        todo_list.todos[2].recur = Some("FREQ=SOMETIMES".to_string());
+        todo_list.todos[2].attachments = vec!["/nonexistent/spec.pdf".to_string()];
+
+        let problems = check(&mut todo_list, Path::new("."), false, now);
+        assert_eq!(problems.len(), 7);
+        assert_eq!(todo_list.todos[1].priority, 9);
+
+        let problems = check(&mut todo_list, Path::new("."), true, now);
+        assert_eq!(problems.len(), 7);
+        assert_eq!(todo_list.todos[1].id, 2);
+        assert_eq!(todo_list.todos[1].priority, 5);
+        assert_eq!(todo_list.todos[2].created, now);
+        assert_eq!(todo_list.todos[2].due, None);
+        assert_eq!(todo_list.todos[2].recur, None);
+        assert!(todo_list.todos[2].attachments.is_empty());
+
+        assert!(check(&mut todo_list, Path::new("."), false, now).is_empty());
+
+        // Dependencies follow renumbered tasks, and broken ones are dropped
+        todo_list.todos[0].id = 4;
+        todo_list.todos[1].depends = vec![2, 3, 4, 7];
+        todo_list.todos[2].depends = vec![4];
+        let problems = check(&mut todo_list, Path::new("."), false, now);
+        assert_eq!(
+            problems,
+            [
+                "Task 2 ('task 2') depends on itself",
+                "Task 2 ('task 2') depends on task 7, which does not exist",
+                "IDs are not numbered 1, 2, 3, ... in order",
+            ]
+        );
+        assert_eq!(check(&mut todo_list, Path::new("."), true, now).len(), 3);
+        assert_eq!(todo_list.todos[1].depends, [1, 3]);
+        assert_eq!(todo_list.todos[2].depends, [1]);
+        assert!(check(&mut todo_list, Path::new("."), false, now).is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+//! Reporting the errors that stop a command: as a line of text on stdout, or, when the
+//! command writes JSON (`list --format ndjson`, and `show`, `export` and `sql` with
+//! `--format json`), as a JSON object on stderr, so a tool wrapping the command can tell
+//! what went wrong without parsing sentences:
+//!
+//! ```json
+//! {"code":"invalid_query","message":"Unknown filter: colour:red","field":"where"}
+//! ```
+//!
+//! `id` or `hash` names the task an error is about, and `field` the option or setting,
+//! when there is one. A panic is reported the same way, with the code `internal`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+
+/// Whether errors are reported as JSON.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// An error that stops a command.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Failure {
+    /// What kind of error it is, e.g. `unknown_task` or `invalid_query`.
+    pub code: &'static str,
+    /// The error as it is printed without JSON.
+    pub message: String,
+    /// The ID of the task it is about.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    /// The hash prefix of the task it is about, when the task was given by one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// The option or setting it is about, e.g. `where` or `default_sort`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<&'static str>,
+}
+
+/// Reports errors, panics included, as JSON on stderr from now on.
+pub fn enable_json() {
+    JSON.store(true, Ordering::Relaxed);
+    std::panic::set_hook(Box::new(|info| {
+        let payload = info.payload();
+        let message = match (
+            payload.downcast_ref::<&str>(),
+            payload.downcast_ref::<String>(),
+        ) {
+            (Some(message), _) => message.to_string(),
+            (_, Some(message)) => message.clone(),
+            _ => "The program failed".to_string(),
+        };
+        Failure::new("internal", message).report();
+    }));
+}
+
+impl Failure {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Failure {
+        Failure {
+            code,
+            message: message.into(),
+            id: None,
+            hash: None,
+            field: None,
+        }
+    }
+
+    /// Names the task with the ID `id` as what the error is about.
+    pub fn id(mut self, id: u64) -> Failure {
+        self.id = Some(id);
+        self
+    }
+
+    /// Names the task given by the hash prefix `hash` as what the error is about.
+    pub fn hash(mut self, hash: &str) -> Failure {
+        self.hash = Some(hash.to_string());
+        self
+    }
+
+    /// Names the option or setting `field` as what the error is about.
+    pub fn field(mut self, field: &'static str) -> Failure {
+        self.field = Some(field);
+        self
+    }
+
+    /// Formats the error as a line of JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Unable to serialize an error")
+    }
+
+    /// Prints the error, as text on stdout or as JSON on stderr.
+    pub fn report(&self) {
+        match JSON.load(Ordering::Relaxed) {
+            true => eprintln!("{}", self.to_json()),
+            false => println!("{}", self.message),
+        }
+    }
+
+    /// Prints the error and exits with `status`.
+    pub fn exit(self, status: i32) -> ! {
+        self.report();
+        std::process::exit(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Failure;
+
+    #[test]
+    fn test_to_json() {
+        let failure = Failure::new("unknown_task", "Invalid ID").id(7);
+        assert_eq!(
+            failure.to_json(),
+            r#"{"code":"unknown_task","message":"Invalid ID","id":7}"#
+        );
+        let failure = Failure::new("invalid_query", "Unknown filter: colour:red").field("where");
+        assert_eq!(
+            failure.to_json(),
+            r#"{"code":"invalid_query","message":"Unknown filter: colour:red","field":"where"}"#
+        );
+        let failure = Failure::new("ambiguous_hash", "Hash a3 is ambiguous").hash("a3");
+        assert_eq!(failure.hash.as_deref(), Some("a3"));
+        assert!(failure.to_json().ends_with(r#""hash":"a3"}"#));
+    }
+}
@@ -0,0 +1,591 @@
+//! A C API over the todo list engine, for programs such as status-bar widgets that want to
+//! read and change a list without running the command-line program.
+//!
+//! A list is loaded into an opaque `TodoHandle`, changed in memory, and written back with
+//! `todo_save`. Strings returned to C are freed with `todo_string_free`, and handles with
+//! `todo_free`. A host that keeps the list somewhere other than a file, such as a web page
+//! storing it in `localStorage`, uses `todo_from_json` and `todo_to_json` instead of
+//! `todo_load` and `todo_save`.
+//!
+//! A long-running host such as a widget or notebook can leave saving to the library:
+//! with `todo_set_autosave`, changes are written once they have stopped for a while, as
+//! checked by `todo_flush` from the host's own timer, and when the handle is freed. Only
+//! lists with unsaved changes are ever written. The declarations are in `include/todo.h`, generated with
+//! `cbindgen --config cbindgen.toml --output include/todo.h`.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+
+use crate::batch::{apply_batch, parse_batch};
+use crate::config::Config;
+use crate::store::{list_directory, load_todo_list_from, save_todo_list_over};
+use crate::{attachments, now, now_millis, priorities, query, rebase, stats, Todo, TodoList};
+
+thread_local! {
+    /// Why the last `todo_load`, `todo_query_json`, `todo_batch` or save on this thread
+    /// failed.
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// A todo list loaded from a file or from JSON, with the changes made to it since.
+pub struct TodoHandle {
+    /// The file the list was loaded from, or `None` when it came from `todo_from_json`.
+    path: Option<PathBuf>,
+    todo_list: TodoList,
+    /// The todo items completed since the list was loaded, whose copied attachments are
+    /// deleted once the list is saved.
+    completed: Vec<Todo>,
+    /// The todo items removed along with completed ones because they depended on them,
+    /// whose copied attachments are deleted too but which are not counted as completed.
+    cascaded: Vec<Todo>,
+    /// When the list was last changed, in Unix milliseconds, if it has unsaved changes.
+    changed_at: Option<i64>,
+    /// How long after the last change `todo_flush` saves the list, in milliseconds, when
+    /// autosaving.
+    autosave: Option<i64>,
+    /// What the file held when the list was loaded or last saved, so that saving applies
+    /// the list's changes over those another process made meanwhile. Kept by each handle,
+    /// as several may have the same file open.
+    loaded: Option<String>,
+}
+
+impl TodoHandle {
+    fn new(path: Option<PathBuf>, todo_list: TodoList) -> TodoHandle {
+        TodoHandle {
+            path,
+            todo_list,
+            completed: Vec::new(),
+            cascaded: Vec::new(),
+            changed_at: None,
+            autosave: None,
+            loaded: None,
+        }
+    }
+
+    /// Records that the list has changed and needs saving.
+    fn changed(&mut self) {
+        self.changed_at = Some(now_millis());
+    }
+
+    /// Writes the list to its file if it has unsaved changes, deleting the copied
+    /// attachments of the items completed since the last save.
+    fn save(&mut self) -> Result<(), ()> {
+        let Some(path) = &self.path else {
+            return Err(());
+        };
+        if self.changed_at.is_none() {
+            return Ok(());
+        }
+        let loaded = self.loaded.as_deref();
+        let todo_list = &mut self.todo_list;
+        match catch_unwind(AssertUnwindSafe(|| {
+            save_todo_list_over(path, todo_list, loaded)
+        })) {
+            Ok(Ok(saved)) => self.loaded = saved,
+            Ok(Err(error)) => {
+                set_last_error(error);
+                return Err(());
+            }
+            Err(_) => return Err(()),
+        }
+        let base = list_directory(path);
+        // The log only feeds `stats --by`, so failing to write it does not fail the save
+        let _ = stats::record_completed(&base, &self.completed, now());
+        for todo in self.completed.drain(..).chain(self.cascaded.drain(..)) {
+            attachments::delete_stored_copies(&base, &todo.attachments);
+        }
+        self.changed_at = None;
+        Ok(())
+    }
+}
+
+/// Loads the todo list file at `path`, or an empty list if it does not exist. Returns
+/// NULL if `path` is not valid UTF-8, or if the file cannot be read or parsed, with the
+/// reason in `todo_last_error`.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn todo_load(path: *const c_char) -> *mut TodoHandle {
+    let Some(path) = str_arg(path) else {
+        return ptr::null_mut();
+    };
+    let path = PathBuf::from(path);
+    match load_todo_list_from(&path) {
+        Ok(todo_list) => {
+            let mut handle = TodoHandle::new(Some(path), todo_list.unwrap_or_default());
+            handle.loaded = handle.path.as_deref().and_then(rebase::loaded);
+            Box::into_raw(Box::new(handle))
+        }
+        Err(error) => {
+            set_last_error(error);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Reads a todo list from `json`, in the format of the list file, or an empty list if
+/// `json` is empty. Returns NULL if `json` is not valid UTF-8 or not a todo list.
+///
+/// # Safety
+///
+/// `json` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn todo_from_json(json: *const c_char) -> *mut TodoHandle {
+    let todo_list = match str_arg(json) {
+        Some("") => TodoList::new(),
+        Some(json) => match serde_json::from_str(json) {
+            Ok(todo_list) => todo_list,
+            Err(_) => return ptr::null_mut(),
+        },
+        None => return ptr::null_mut(),
+    };
+    Box::into_raw(Box::new(TodoHandle::new(None, todo_list)))
+}
+
+/// Writes the list back to the file it was loaded from, backing up the previous version as
+/// `save` does, if it has changed since it was loaded or last saved. Returns 0 on success
+/// and -1 on failure, including for a list that came from `todo_from_json`, and when another
+/// process changed or removed a task this list changed too, which `todo_last_error` then
+/// names. The list keeps its unsaved changes then.
+///
+/// # Safety
+///
+/// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn todo_save(handle: *mut TodoHandle) -> i32 {
+    match handle.as_mut().map(TodoHandle::save) {
+        Some(Ok(())) => 0,
+        _ => -1,
+    }
+}
+
+/// Saves the list automatically once `delay_ms` milliseconds have passed since its last
+/// change, so a burst of changes is written once, and when the handle is freed. A negative
+/// delay turns autosaving off. Returns 0 on success and -1 for a list that came from
+/// `todo_from_json`, which has no file to save to.
+///
+/// The library has no timer of its own: the host calls `todo_flush` from its event loop or
+/// timer, e.g. every second.
+///
+/// # Safety
+///
+/// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn todo_set_autosave(handle: *mut TodoHandle, delay_ms: i64) -> i32 {
+    match handle.as_mut() {
+        Some(handle) if handle.path.is_some() => {
+            handle.autosave = Some(delay_ms).filter(|delay| *delay >= 0);
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Saves the list if autosaving is on and its last change is at least the autosave delay
+/// old. Returns 1 if it was saved, 0 if there was nothing to save yet, and -1 if saving
+/// failed, as for `todo_save`.
+///
+/// # Safety
+///
+/// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn todo_flush(handle: *mut TodoHandle) -> i32 {
+    let Some(handle) = handle.as_mut() else {
+        return -1;
+    };
+    match (handle.autosave, handle.changed_at) {
+        (Some(delay), Some(changed_at)) if now_millis() - changed_at >= delay => {
+            match handle.save() {
+                Ok(()) => 1,
+                Err(()) => -1,
+            }
+        }
+        _ => 0,
+    }
+}
+
+/// Returns 1 if the list has changes that have not been saved, 0 if not, and -1 if
+/// `handle` is NULL.
+///
+/// # Safety
+///
+/// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn todo_is_dirty(handle: *const TodoHandle) -> i32 {
+    match handle.as_ref() {
+        Some(handle) => handle.changed_at.is_some() as i32,
+        None => -1,
+    }
+}
+
+/// Adds a todo item with a priority on the configured scale, returning its ID, or -1 if the
+/// task is not valid UTF-8 or the priority is out of range.
+///
+/// # Safety
+///
+/// `handle` must come from `todo_load` or `todo_from_json` and not have been freed, and `task` must be a valid
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn todo_add(
+    handle: *mut TodoHandle,
+    task: *const c_char,
+    priority: u64,
+) -> i64 {
+    match (handle.as_mut(), str_arg(task)) {
+        (Some(handle), Some(task)) if priorities::scale().contains(priority) => {
+            match handle.todo_list.add_todo(task, priority) {
+                Some(id) => {
+                    handle.changed();
+                    id as i64
+                }
+                None => -1,
+            }
+        }
+        _ => -1,
+    }
+}
+
+/// Completes the todo item with the given ID, removing it from the list as `remove` does,
+/// so the items after it move up one ID. Returns 0 on success and -1 for an unknown ID.
+///
+/// # Safety
+///
+/// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn todo_complete(handle: *mut TodoHandle, id: u64) -> i32 {
+    let Some(handle) = handle.as_mut() else {
+        return -1;
+    };
+    // Checked first, as `remove_todo` reports unknown IDs on stdout.
+    if handle.todo_list.todo(id).is_none() {
+        return -1;
+    }
+    handle.completed.extend(handle.todo_list.remove_todo(id));
+    handle.changed();
+    0
+}
+
+/// Returns the todo items as a JSON array, in the format of the list file, or NULL if
+/// `handle` is NULL. The string must be freed with `todo_string_free`.
+///
+/// # Safety
+///
+/// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn todo_list_json(handle: *const TodoHandle) -> *mut c_char {
+    let Some(handle) = handle.as_ref() else {
+        return ptr::null_mut();
+    };
+    serde_json::to_string(&handle.todo_list.todos)
+        .ok()
+        .and_then(|json| CString::new(json).ok())
+        .map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Returns the todo items matching `query`, in the query language of `search`, as a JSON
+/// array; with `fields:` in the query, each item has only those fields. Returns NULL if
+/// the query is invalid, with the reason left for `todo_last_error`. The string must be
+/// freed with `todo_string_free`.
+///
+/// # Safety
+///
+/// `handle` must come from `todo_load` or `todo_from_json` and not have been freed, and
+/// `query` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn todo_query_json(
+    handle: *const TodoHandle,
+    query: *const c_char,
+) -> *mut c_char {
+    let (Some(handle), Some(query)) = (handle.as_ref(), str_arg(query)) else {
+        return ptr::null_mut();
+    };
+    let now = now();
+    match query::Query::parse(query, now) {
+        Ok(query) => {
+            let matches = query.apply(handle.todo_list.todos.clone(), now);
+            serde_json::to_string(&matches)
+                .ok()
+                .and_then(|json| CString::new(json).ok())
+                .map_or(ptr::null_mut(), CString::into_raw)
+        }
+        Err(error) => {
+            set_last_error(error);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Applies `commands`, lines of add, edit and remove commands as in a `batch` file, all
+/// together or not at all. Added tasks are created by the current user and in the context
+/// of the list's directory (the current directory for a list from `todo_from_json`).
+/// Returns 0 on success and -1 if any command fails, with the reason left for
+/// `todo_last_error` and the list unchanged.
+///
+/// # Safety
+///
+/// `handle` must come from `todo_load` or `todo_from_json` and not have been freed, and
+/// `commands` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn todo_batch(handle: *mut TodoHandle, commands: *const c_char) -> i32 {
+    let (Some(handle), Some(commands)) = (handle.as_mut(), str_arg(commands)) else {
+        return -1;
+    };
+    let base = handle
+        .path
+        .as_deref()
+        .map_or_else(|| PathBuf::from("."), list_directory);
+    let mut updated_todo_list = handle.todo_list.clone();
+    let result = parse_batch(commands)
+        .and_then(|batch| apply_batch(&mut updated_todo_list, batch, &Config::default(), &base));
+    match result {
+        Ok((removed, cascaded)) => {
+            handle.todo_list = updated_todo_list;
+            handle.completed.extend(removed);
+            handle.cascaded.extend(cascaded);
+            handle.changed();
+            0
+        }
+        Err(error) => {
+            set_last_error(error);
+            -1
+        }
+    }
+}
+
+/// Returns why the last `todo_load`, `todo_query_json`, `todo_batch`, `todo_save` or
+/// `todo_flush` on this thread failed, or NULL if none has. The string must be freed with `todo_string_free`.
+#[no_mangle]
+pub extern "C" fn todo_last_error() -> *mut c_char {
+    LAST_ERROR.with(|error| {
+        error
+            .borrow()
+            .clone()
+            .and_then(|error| CString::new(error).ok())
+            .map_or(ptr::null_mut(), CString::into_raw)
+    })
+}
+
+/// Returns the whole list in the format of the list file, checksum included, for a host
+/// to store and later pass to `todo_from_json`, or NULL if `handle` is NULL. The string
+/// must be freed with `todo_string_free`.
+///
+/// # Safety
+///
+/// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn todo_to_json(handle: *const TodoHandle) -> *mut c_char {
+    let Some(handle) = handle.as_ref() else {
+        return ptr::null_mut();
+    };
+    CString::new(handle.todo_list.to_json()).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Allocates a string of `len` bytes for a host that cannot pass its own memory, such as
+/// JavaScript calling the WebAssembly build, to write an argument into. The host must
+/// overwrite all `len` bytes with UTF-8 text containing no NUL, and free the string with
+/// `todo_string_free`.
+#[no_mangle]
+pub extern "C" fn todo_string_alloc(len: usize) -> *mut c_char {
+    CString::new(vec![b' '; len]).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Frees a string returned by this library, or allocated with `todo_string_alloc`. Does
+/// nothing for NULL.
+///
+/// # Safety
+///
+/// `string` must come from this library and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn todo_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Frees a handle, first saving any unsaved changes if autosaving is on, and discarding
+/// them otherwise. Does nothing for NULL.
+///
+/// # Safety
+///
+/// `handle` must come from `todo_load` or `todo_from_json` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn todo_free(handle: *mut TodoHandle) {
+    if !handle.is_null() {
+        let mut handle = Box::from_raw(handle);
+        if handle.autosave.is_some() {
+            // Nothing can be reported from here, so a failed save is dropped with the list
+            let _ = handle.save();
+        }
+    }
+}
+
+/// Records why a call failed, for `todo_last_error`.
+fn set_last_error(error: String) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(error));
+}
+
+/// Reads a NUL-terminated UTF-8 string argument, or `None` if it is NULL or not UTF-8.
+unsafe fn str_arg<'a>(string: *const c_char) -> Option<&'a str> {
+    if string.is_null() {
+        return None;
+    }
+    CStr::from_ptr(string).to_str().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api() {
+        unsafe {
+            let path = CString::new("/nonexistent/todos.json").unwrap();
+            let handle = todo_load(path.as_ptr());
+            assert!(!handle.is_null());
+            let task = CString::new("Pay rent").unwrap();
+            assert_eq!(todo_add(handle, task.as_ptr(), 5), 1);
+            assert_eq!(todo_add(handle, task.as_ptr(), 9), -1);
+            let task = CString::new("Read docs").unwrap();
+            assert_eq!(todo_add(handle, task.as_ptr(), 2), 2);
+            assert_eq!(todo_complete(handle, 1), 0);
+            assert_eq!(todo_complete(handle, 7), -1);
+
+            let json = todo_list_json(handle);
+            let todos: serde_json::Value =
+                serde_json::from_str(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+            assert_eq!(todos[0]["id"], 1);
+            assert_eq!(todos[0]["task"], "Read docs");
+            assert_eq!(todos.as_array().unwrap().len(), 1);
+            todo_string_free(json);
+
+            let json = todo_to_json(handle);
+            let copy = todo_from_json(json);
+            todo_string_free(json);
+            todo_free(handle);
+            let todos = &(*copy).todo_list.todos;
+            assert_eq!(todos[0].task, "Read docs");
+            assert_eq!(todo_save(copy), -1);
+            todo_free(copy);
+            let json = CString::new("not json").unwrap();
+            assert!(todo_from_json(json.as_ptr()).is_null());
+            let json = CString::new("").unwrap();
+            let empty = todo_from_json(json.as_ptr());
+            assert!((*empty).todo_list.todos.is_empty());
+            todo_free(empty);
+
+            assert!(todo_load(ptr::null()).is_null());
+            assert_eq!(todo_add(ptr::null_mut(), ptr::null(), 3), -1);
+        }
+    }
+
+    #[test]
+    fn test_query_and_batch() {
+        unsafe {
+            let json = CString::new("").unwrap();
+            let handle = todo_from_json(json.as_ptr());
+            let commands =
+                CString::new("add \"Pay rent\" 5\n# later\nadd \"Read docs\" 2\n").unwrap();
+            assert_eq!(todo_batch(handle, commands.as_ptr()), 0);
+
+            let query = CString::new("priority>=3 fields:id,task").unwrap();
+            let json = todo_query_json(handle, query.as_ptr());
+            assert_eq!(
+                CStr::from_ptr(json).to_str().unwrap(),
+                r#"[{"id":1,"task":"Pay rent"}]"#
+            );
+            todo_string_free(json);
+            let query = CString::new("priority>>3").unwrap();
+            assert!(todo_query_json(handle, query.as_ptr()).is_null());
+            let error = todo_last_error();
+            assert!(!error.is_null());
+            todo_string_free(error);
+
+            let commands = CString::new("remove 1\nedit 9 --task Read").unwrap();
+            assert_eq!(todo_batch(handle, commands.as_ptr()), -1);
+            let error = todo_last_error();
+            assert_eq!(
+                CStr::from_ptr(error).to_str().unwrap(),
+                "Line 2: Invalid ID: 9"
+            );
+            todo_string_free(error);
+            assert_eq!((*handle).todo_list.todos.len(), 2);
+            todo_free(handle);
+        }
+    }
+
+    #[test]
+    fn test_autosave() {
+        let directory = std::env::temp_dir().join(format!("todo-ffi-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let file = directory.join("todos.json");
+        unsafe {
+            let path = CString::new(file.to_str().unwrap()).unwrap();
+            let handle = todo_load(path.as_ptr());
+            assert_eq!(todo_is_dirty(handle), 0);
+            assert_eq!(todo_save(handle), 0);
+            assert!(!file.exists());
+
+            let task = CString::new("Pay rent").unwrap();
+            todo_add(handle, task.as_ptr(), 5);
+            assert_eq!(todo_is_dirty(handle), 1);
+            assert_eq!(todo_flush(handle), 0);
+            assert_eq!(todo_set_autosave(handle, 60_000), 0);
+            assert_eq!(todo_flush(handle), 0);
+            assert_eq!(todo_set_autosave(handle, 0), 0);
+            assert_eq!(todo_flush(handle), 1);
+            assert_eq!(todo_is_dirty(handle), 0);
+            assert_eq!(todo_flush(handle), 0);
+            assert!(file.exists());
+
+            todo_add(handle, task.as_ptr(), 4);
+            todo_free(handle);
+            let handle = todo_load(path.as_ptr());
+            assert_eq!((*handle).todo_list.todos.len(), 2);
+            todo_free(handle);
+
+            let json = CString::new("").unwrap();
+            let handle = todo_from_json(json.as_ptr());
+            assert_eq!(todo_set_autosave(handle, 0), -1);
+            todo_free(handle);
+
+            // Two handles on the same file: changes to different tasks are both kept, while
+            // a change to a task the other handle changed fails to save
+            let first = todo_load(path.as_ptr());
+            let second = todo_load(path.as_ptr());
+            assert_eq!(todo_complete(first, 1), 0);
+            assert_eq!(todo_save(first), 0);
+            let task = CString::new("Read docs").unwrap();
+            assert_eq!(todo_add(second, task.as_ptr(), 2), 3);
+            assert_eq!(todo_save(second), 0);
+            assert_eq!(todo_complete(first, 1), 0);
+            assert_eq!(todo_save(first), 0);
+            let commands = CString::new("edit 1 --priority 1").unwrap();
+            assert_eq!(todo_batch(second, commands.as_ptr()), 0);
+            assert_eq!(todo_save(second), -1);
+            let error = todo_last_error();
+            assert!(CStr::from_ptr(error)
+                .to_str()
+                .unwrap()
+                .ends_with("was removed by another command at the same time"));
+            todo_string_free(error);
+            assert_eq!(todo_is_dirty(second), 1);
+            todo_free(first);
+            todo_free(second);
+            let handle = todo_load(path.as_ptr());
+            let tasks: Vec<&str> = (*handle)
+                .todo_list
+                .todos
+                .iter()
+                .map(|todo| todo.task.as_str())
+                .collect();
+            assert_eq!(tasks, ["Read docs"]);
+            todo_free(handle);
+        }
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+//! `focus`: one todo item alone on the screen, with how long it has had the focus, until it
+//! is marked done or the user quits.
+//!
+//! The screen is redrawn whenever a line is read, so pressing Enter brings the timer up to
+//! date without putting the terminal in raw mode. Marking a recurring item done checks off
+//! its current occurrence, as `done` does; any other item is removed and logged as
+//! completed, as `remove` does.
+
+use std::fmt::Write;
+
+use crate::dates::{self, DateFormat};
+use crate::Todo;
+
+/// What the user asks for while focusing.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    /// Mark the todo item done and stop.
+    Done,
+    /// Stop, leaving the todo item as it is.
+    Quit,
+    /// Redraw the screen, updating the timer.
+    Refresh,
+}
+
+/// Reads an answer to the prompt: nothing refreshes, `d` marks done and `q` quits. Returns
+/// `None` for anything else.
+pub fn parse_action(answer: &str) -> Option<Action> {
+    match answer.trim() {
+        "" => Some(Action::Refresh),
+        "d" | "done" => Some(Action::Done),
+        "q" | "quit" => Some(Action::Quit),
+        _ => None,
+    }
+}
+
+/// Formats `todo`, focused on for `elapsed` seconds, with its due date in the format `dates`
+/// relative to the timestamp `now`, or as labeled lines when `plain` is set.
+pub fn render(todo: &Todo, elapsed: i64, dates: &DateFormat, now: i64, plain: bool) -> String {
+    let mut details = vec![("priority", todo.priority.to_string())];
+    if let Some(project) = &todo.project {
+        details.push(("project", project.clone()));
+    }
+    if !todo.tags.is_empty() {
+        let tags: Vec<String> = todo.tags.iter().map(|tag| format!("+{}", tag)).collect();
+        details.push(("tags", tags.join(" ")));
+    }
+    if let Some(due) = todo.due {
+        details.push(("due", dates.format(due, now, "%Y-%m-%d %H:%M")));
+    }
+    if let Some(location) = &todo.location {
+        details.push(("location", location.clone()));
+    }
+    if let Some(url) = &todo.url {
+        details.push(("url", url.clone()));
+    }
+    if !todo.attachments.is_empty() {
+        details.push(("attachments", todo.attachments.join(", ")));
+    }
+    let timer = match todo.estimate {
+        Some(estimate) => format!(
+            "{} of {} estimated",
+            dates::format_duration(elapsed),
+            dates::format_duration(estimate)
+        ),
+        None => dates::format_duration(elapsed),
+    };
+    let mut output = String::new();
+    if plain {
+        let _ = writeln!(output, "task: {}", todo.task);
+        for (label, value) in details {
+            let _ = writeln!(output, "{}: {}", label, value);
+        }
+        let _ = writeln!(output, "focused for: {}", timer);
+        return output;
+    }
+    let _ = writeln!(output, "Focusing on task {}\n", todo.id);
+    let _ = writeln!(output, "    {}\n", todo.task);
+    for (label, value) in details {
+        let _ = writeln!(output, "    {}: {}", label, value);
+    }
+    let _ = writeln!(output, "\nFocused for {}", timer);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_action, render, Action};
+    use crate::dates::DateFormat;
+    use crate::TodoList;
+
+    #[test]
+    fn test_render() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Write the report", 4);
+        let todo = &mut todo_list.todos[0];
+        todo.tags = vec!["client".to_string()];
+        todo.project = Some("acme".to_string());
+        todo.estimate = Some(45 * 60);
+        let now = todo.created;
+        let short = DateFormat::parse("short").unwrap();
+
+        assert_eq!(
+            render(todo, 12 * 60, &short, now, false),
+            "Focusing on task 1\n\n    Write the report\n\n    priority: 4\n    \
+             project: acme\n    tags: +client\n\nFocused for 12m of 45m estimated\n"
+        );
+        todo.estimate = None;
+        assert_eq!(
+            render(todo, 90 * 60, &short, now, true),
+            "task: Write the report\npriority: 4\nproject: acme\ntags: +client\n\
+             focused for: 1h30m\n"
+        );
+
+        assert_eq!(parse_action("\n"), Some(Action::Refresh));
+        assert_eq!(parse_action(" d "), Some(Action::Done));
+        assert_eq!(parse_action("quit"), Some(Action::Quit));
+        assert_eq!(parse_action("x"), None);
+    }
+}
@@ -0,0 +1,397 @@
+//! Folding text so that searches and filters match it however it was typed.
+//!
+//! Text is lowercased with the case foldings `to_lowercase` leaves out (ß as ss, final ς as
+//! σ), and brought to Unicode's canonical decomposition (NFD), so "Café" typed with an é and
+//! "cafe\u{301}" typed with a combining accent fold alike. The decompositions cover the
+//! Latin, Greek and Cyrillic letters with diacritics. Stripping the accents as well lets
+//! "cafe" match "Café".
+
+/// The canonical decompositions of the lowercase letters with diacritics, by code point.
+const DECOMPOSITIONS: &[(char, &str)] = &[
+    ('à', "a\u{300}"),
+    ('á', "a\u{301}"),
+    ('â', "a\u{302}"),
+    ('ã', "a\u{303}"),
+    ('ä', "a\u{308}"),
+    ('å', "a\u{30a}"),
+    ('ç', "c\u{327}"),
+    ('è', "e\u{300}"),
+    ('é', "e\u{301}"),
+    ('ê', "e\u{302}"),
+    ('ë', "e\u{308}"),
+    ('ì', "i\u{300}"),
+    ('í', "i\u{301}"),
+    ('î', "i\u{302}"),
+    ('ï', "i\u{308}"),
+    ('ñ', "n\u{303}"),
+    ('ò', "o\u{300}"),
+    ('ó', "o\u{301}"),
+    ('ô', "o\u{302}"),
+    ('õ', "o\u{303}"),
+    ('ö', "o\u{308}"),
+    ('ù', "u\u{300}"),
+    ('ú', "u\u{301}"),
+    ('û', "u\u{302}"),
+    ('ü', "u\u{308}"),
+    ('ý', "y\u{301}"),
+    ('ÿ', "y\u{308}"),
+    ('ā', "a\u{304}"),
+    ('ă', "a\u{306}"),
+    ('ą', "a\u{328}"),
+    ('ć', "c\u{301}"),
+    ('ĉ', "c\u{302}"),
+    ('ċ', "c\u{307}"),
+    ('č', "c\u{30c}"),
+    ('ď', "d\u{30c}"),
+    ('ē', "e\u{304}"),
+    ('ĕ', "e\u{306}"),
+    ('ė', "e\u{307}"),
+    ('ę', "e\u{328}"),
+    ('ě', "e\u{30c}"),
+    ('ĝ', "g\u{302}"),
+    ('ğ', "g\u{306}"),
+    ('ġ', "g\u{307}"),
+    ('ģ', "g\u{327}"),
+    ('ĥ', "h\u{302}"),
+    ('ĩ', "i\u{303}"),
+    ('ī', "i\u{304}"),
+    ('ĭ', "i\u{306}"),
+    ('į', "i\u{328}"),
+    ('ĵ', "j\u{302}"),
+    ('ķ', "k\u{327}"),
+    ('ĺ', "l\u{301}"),
+    ('ļ', "l\u{327}"),
+    ('ľ', "l\u{30c}"),
+    ('ń', "n\u{301}"),
+    ('ņ', "n\u{327}"),
+    ('ň', "n\u{30c}"),
+    ('ō', "o\u{304}"),
+    ('ŏ', "o\u{306}"),
+    ('ő', "o\u{30b}"),
+    ('ŕ', "r\u{301}"),
+    ('ŗ', "r\u{327}"),
+    ('ř', "r\u{30c}"),
+    ('ś', "s\u{301}"),
+    ('ŝ', "s\u{302}"),
+    ('ş', "s\u{327}"),
+    ('š', "s\u{30c}"),
+    ('ţ', "t\u{327}"),
+    ('ť', "t\u{30c}"),
+    ('ũ', "u\u{303}"),
+    ('ū', "u\u{304}"),
+    ('ŭ', "u\u{306}"),
+    ('ů', "u\u{30a}"),
+    ('ű', "u\u{30b}"),
+    ('ų', "u\u{328}"),
+    ('ŵ', "w\u{302}"),
+    ('ŷ', "y\u{302}"),
+    ('ź', "z\u{301}"),
+    ('ż', "z\u{307}"),
+    ('ž', "z\u{30c}"),
+    ('ơ', "o\u{31b}"),
+    ('ư', "u\u{31b}"),
+    ('ǎ', "a\u{30c}"),
+    ('ǐ', "i\u{30c}"),
+    ('ǒ', "o\u{30c}"),
+    ('ǔ', "u\u{30c}"),
+    ('ǖ', "u\u{308}\u{304}"),
+    ('ǘ', "u\u{308}\u{301}"),
+    ('ǚ', "u\u{308}\u{30c}"),
+    ('ǜ', "u\u{308}\u{300}"),
+    ('ǟ', "a\u{308}\u{304}"),
+    ('ǡ', "a\u{307}\u{304}"),
+    ('ǣ', "æ\u{304}"),
+    ('ǧ', "g\u{30c}"),
+    ('ǩ', "k\u{30c}"),
+    ('ǫ', "o\u{328}"),
+    ('ǭ', "o\u{328}\u{304}"),
+    ('ǯ', "ʒ\u{30c}"),
+    ('ǰ', "j\u{30c}"),
+    ('ǵ', "g\u{301}"),
+    ('ǹ', "n\u{300}"),
+    ('ǻ', "a\u{30a}\u{301}"),
+    ('ǽ', "æ\u{301}"),
+    ('ǿ', "ø\u{301}"),
+    ('ȁ', "a\u{30f}"),
+    ('ȃ', "a\u{311}"),
+    ('ȅ', "e\u{30f}"),
+    ('ȇ', "e\u{311}"),
+    ('ȉ', "i\u{30f}"),
+    ('ȋ', "i\u{311}"),
+    ('ȍ', "o\u{30f}"),
+    ('ȏ', "o\u{311}"),
+    ('ȑ', "r\u{30f}"),
+    ('ȓ', "r\u{311}"),
+    ('ȕ', "u\u{30f}"),
+    ('ȗ', "u\u{311}"),
+    ('ș', "s\u{326}"),
+    ('ț', "t\u{326}"),
+    ('ȟ', "h\u{30c}"),
+    ('ȧ', "a\u{307}"),
+    ('ȩ', "e\u{327}"),
+    ('ȫ', "o\u{308}\u{304}"),
+    ('ȭ', "o\u{303}\u{304}"),
+    ('ȯ', "o\u{307}"),
+    ('ȱ', "o\u{307}\u{304}"),
+    ('ȳ', "y\u{304}"),
+    ('·', "·"),
+    ('ΐ', "ι\u{308}\u{301}"),
+    ('ά', "α\u{301}"),
+    ('έ', "ε\u{301}"),
+    ('ή', "η\u{301}"),
+    ('ί', "ι\u{301}"),
+    ('ΰ', "υ\u{308}\u{301}"),
+    ('ϊ', "ι\u{308}"),
+    ('ϋ', "υ\u{308}"),
+    ('ό', "ο\u{301}"),
+    ('ύ', "υ\u{301}"),
+    ('ώ', "ω\u{301}"),
+    ('й', "и\u{306}"),
+    ('ѐ', "е\u{300}"),
+    ('ё', "е\u{308}"),
+    ('ѓ', "г\u{301}"),
+    ('ї', "і\u{308}"),
+    ('ќ', "к\u{301}"),
+    ('ѝ', "и\u{300}"),
+    ('ў', "у\u{306}"),
+    ('ḁ', "a\u{325}"),
+    ('ḃ', "b\u{307}"),
+    ('ḅ', "b\u{323}"),
+    ('ḇ', "b\u{331}"),
+    ('ḉ', "c\u{327}\u{301}"),
+    ('ḋ', "d\u{307}"),
+    ('ḍ', "d\u{323}"),
+    ('ḏ', "d\u{331}"),
+    ('ḑ', "d\u{327}"),
+    ('ḓ', "d\u{32d}"),
+    ('ḕ', "e\u{304}\u{300}"),
+    ('ḗ', "e\u{304}\u{301}"),
+    ('ḙ', "e\u{32d}"),
+    ('ḛ', "e\u{330}"),
+    ('ḝ', "e\u{327}\u{306}"),
+    ('ḟ', "f\u{307}"),
+    ('ḡ', "g\u{304}"),
+    ('ḣ', "h\u{307}"),
+    ('ḥ', "h\u{323}"),
+    ('ḧ', "h\u{308}"),
+    ('ḩ', "h\u{327}"),
+    ('ḫ', "h\u{32e}"),
+    ('ḭ', "i\u{330}"),
+    ('ḯ', "i\u{308}\u{301}"),
+    ('ḱ', "k\u{301}"),
+    ('ḳ', "k\u{323}"),
+    ('ḵ', "k\u{331}"),
+    ('ḷ', "l\u{323}"),
+    ('ḹ', "l\u{323}\u{304}"),
+    ('ḻ', "l\u{331}"),
+    ('ḽ', "l\u{32d}"),
+    ('ḿ', "m\u{301}"),
+    ('ṁ', "m\u{307}"),
+    ('ṃ', "m\u{323}"),
+    ('ṅ', "n\u{307}"),
+    ('ṇ', "n\u{323}"),
+    ('ṉ', "n\u{331}"),
+    ('ṋ', "n\u{32d}"),
+    ('ṍ', "o\u{303}\u{301}"),
+    ('ṏ', "o\u{303}\u{308}"),
+    ('ṑ', "o\u{304}\u{300}"),
+    ('ṓ', "o\u{304}\u{301}"),
+    ('ṕ', "p\u{301}"),
+    ('ṗ', "p\u{307}"),
+    ('ṙ', "r\u{307}"),
+    ('ṛ', "r\u{323}"),
+    ('ṝ', "r\u{323}\u{304}"),
+    ('ṟ', "r\u{331}"),
+    ('ṡ', "s\u{307}"),
+    ('ṣ', "s\u{323}"),
+    ('ṥ', "s\u{301}\u{307}"),
+    ('ṧ', "s\u{30c}\u{307}"),
+    ('ṩ', "s\u{323}\u{307}"),
+    ('ṫ', "t\u{307}"),
+    ('ṭ', "t\u{323}"),
+    ('ṯ', "t\u{331}"),
+    ('ṱ', "t\u{32d}"),
+    ('ṳ', "u\u{324}"),
+    ('ṵ', "u\u{330}"),
+    ('ṷ', "u\u{32d}"),
+    ('ṹ', "u\u{303}\u{301}"),
+    ('ṻ', "u\u{304}\u{308}"),
+    ('ṽ', "v\u{303}"),
+    ('ṿ', "v\u{323}"),
+    ('ẁ', "w\u{300}"),
+    ('ẃ', "w\u{301}"),
+    ('ẅ', "w\u{308}"),
+    ('ẇ', "w\u{307}"),
+    ('ẉ', "w\u{323}"),
+    ('ẋ', "x\u{307}"),
+    ('ẍ', "x\u{308}"),
+    ('ẏ', "y\u{307}"),
+    ('ẑ', "z\u{302}"),
+    ('ẓ', "z\u{323}"),
+    ('ẕ', "z\u{331}"),
+    ('ẖ', "h\u{331}"),
+    ('ẗ', "t\u{308}"),
+    ('ẘ', "w\u{30a}"),
+    ('ẙ', "y\u{30a}"),
+    ('ẛ', "ſ\u{307}"),
+    ('ạ', "a\u{323}"),
+    ('ả', "a\u{309}"),
+    ('ấ', "a\u{302}\u{301}"),
+    ('ầ', "a\u{302}\u{300}"),
+    ('ẩ', "a\u{302}\u{309}"),
+    ('ẫ', "a\u{302}\u{303}"),
+    ('ậ', "a\u{323}\u{302}"),
+    ('ắ', "a\u{306}\u{301}"),
+    ('ằ', "a\u{306}\u{300}"),
+    ('ẳ', "a\u{306}\u{309}"),
+    ('ẵ', "a\u{306}\u{303}"),
+    ('ặ', "a\u{323}\u{306}"),
+    ('ẹ', "e\u{323}"),
+    ('ẻ', "e\u{309}"),
+    ('ẽ', "e\u{303}"),
+    ('ế', "e\u{302}\u{301}"),
+    ('ề', "e\u{302}\u{300}"),
+    ('ể', "e\u{302}\u{309}"),
+    ('ễ', "e\u{302}\u{303}"),
+    ('ệ', "e\u{323}\u{302}"),
+    ('ỉ', "i\u{309}"),
+    ('ị', "i\u{323}"),
+    ('ọ', "o\u{323}"),
+    ('ỏ', "o\u{309}"),
+    ('ố', "o\u{302}\u{301}"),
+    ('ồ', "o\u{302}\u{300}"),
+    ('ổ', "o\u{302}\u{309}"),
+    ('ỗ', "o\u{302}\u{303}"),
+    ('ộ', "o\u{323}\u{302}"),
+    ('ớ', "o\u{31b}\u{301}"),
+    ('ờ', "o\u{31b}\u{300}"),
+    ('ở', "o\u{31b}\u{309}"),
+    ('ỡ', "o\u{31b}\u{303}"),
+    ('ợ', "o\u{31b}\u{323}"),
+    ('ụ', "u\u{323}"),
+    ('ủ', "u\u{309}"),
+    ('ứ', "u\u{31b}\u{301}"),
+    ('ừ', "u\u{31b}\u{300}"),
+    ('ử', "u\u{31b}\u{309}"),
+    ('ữ', "u\u{31b}\u{303}"),
+    ('ự', "u\u{31b}\u{323}"),
+    ('ỳ', "y\u{300}"),
+    ('ỵ', "y\u{323}"),
+    ('ỷ', "y\u{309}"),
+    ('ỹ', "y\u{303}"),
+];
+
+/// The canonical combining classes of the combining diacritical marks, U+0300 to U+036F,
+/// which order the marks following a letter.
+const MARK_CLASSES: [u8; 112] = [
+    230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230,
+    230, 230, 232, 220, 220, 220, 220, 232, 216, 220, 220, 220, 220, 220, 202, 202, 220, 220, 220,
+    220, 202, 202, 220, 220, 220, 220, 220, 220, 220, 220, 220, 220, 220, 1, 1, 1, 1, 1, 220, 220,
+    220, 220, 230, 230, 230, 230, 230, 230, 230, 230, 240, 230, 220, 220, 220, 230, 230, 230, 220,
+    220, 0, 230, 230, 230, 220, 220, 220, 220, 230, 232, 220, 220, 230, 233, 234, 234, 233, 234,
+    234, 233, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230, 230,
+];
+
+/// Returns the canonical combining class of `c`, 0 for letters and anything else outside
+/// the combining diacritical marks.
+fn mark_class(c: char) -> u8 {
+    match c {
+        '\u{300}'..='\u{36f}' => MARK_CLASSES[c as usize - 0x300],
+        _ => 0,
+    }
+}
+
+/// Lowercases `text` and decomposes its letters, so two spellings of the same text fold to
+/// the same string.
+///
+/// # Example
+///
+/// ```
+/// assert_eq!(fold::fold("Café"), fold::fold("cafe\u{301}"));
+/// ```
+pub fn fold(text: &str) -> String {
+    let mut folded: Vec<char> = Vec::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match c {
+            'ß' => folded.extend(['s', 's']),
+            'ς' => folded.push('σ'),
+            'ſ' => folded.push('s'),
+            c => match DECOMPOSITIONS.binary_search_by_key(&c, |(letter, _)| *letter) {
+                Ok(index) => folded.extend(DECOMPOSITIONS[index].1.chars()),
+                Err(_) => folded.push(c),
+            },
+        }
+    }
+    // Marks in a row are put in the order of their classes, e.g. a dot below before a
+    // circumflex, as the sort is stable
+    let mut start = 0;
+    while start < folded.len() {
+        let end = (start..folded.len())
+            .find(|index| mark_class(folded[*index]) == 0)
+            .unwrap_or(folded.len());
+        folded[start..end].sort_by_key(|c| mark_class(*c));
+        start = end + 1;
+    }
+    folded.into_iter().collect()
+}
+
+/// Drops the accents from text folded by `fold`, along with the strokes of the letters that
+/// have them instead, e.g. ø and ł.
+pub fn strip_accents(folded: &str) -> String {
+    folded
+        .chars()
+        .filter(|c| !('\u{300}'..='\u{36f}').contains(c))
+        .map(|c| match c {
+            'ø' => 'o',
+            'đ' => 'd',
+            'ł' => 'l',
+            'ħ' => 'h',
+            'ŧ' => 't',
+            'ı' => 'i',
+            c => c,
+        })
+        .collect()
+}
+
+/// Returns whether folded `text` contains folded `word`, not counting a match whose last
+/// letter has an accent `word` does not, so "cafe" is not found in "café" unless the accents
+/// were stripped.
+pub fn contains(text: &str, word: &str) -> bool {
+    text.match_indices(word).any(|(index, _)| {
+        !text[index + word.len()..].starts_with(|c: char| ('\u{300}'..='\u{36f}').contains(&c))
+    })
+}
+
+/// Folds `text` with `fold`, also stripping its accents if `ignore_accents` is set.
+pub fn key(text: &str, ignore_accents: bool) -> String {
+    match ignore_accents {
+        true => strip_accents(&fold(text)),
+        false => fold(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{contains, fold, key, DECOMPOSITIONS};
+
+    #[test]
+    fn test_fold() {
+        assert!(DECOMPOSITIONS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(fold("Café"), "cafe\u{301}");
+        assert_eq!(fold("CAFE\u{301}"), fold("café"));
+        assert_eq!(fold("Straße"), fold("STRASSE"));
+        assert_eq!(fold("ΟΔΟΣ"), fold("οδος"));
+        // Vietnamese ệ, with its marks typed in either order
+        assert_eq!(fold("Ệ"), fold("e\u{302}\u{323}"));
+        assert_eq!(fold("ПРИВЕТ мир"), "привет мир");
+        assert_ne!(fold("café"), fold("cafe"));
+
+        assert_eq!(key("Café Crème", true), "cafe creme");
+        assert_eq!(key("Łódź", true), "lodz");
+        assert_eq!(key("Ñandú", true), key("NANDU", true));
+        assert_eq!(key("Ñandú", false), fold("ñandú"));
+        assert!(contains(&fold("Réserver le café"), &fold("CAFÉ")));
+        assert!(!contains(&fold("Réserver le café"), &fold("cafe")));
+        assert!(contains(&key("Réserver le café", true), &key("cafe", true)));
+    }
+}
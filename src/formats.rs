@@ -0,0 +1,166 @@
+//! The formats a todo list file can be written in: JSON, YAML, TOML or JSON5.
+//!
+//! The list is serialized to a `serde_json::Value` as for JSON, which each format then
+//! writes out and reads back, so the same serde attributes apply to all of them. A file's
+//! format follows its extension; files with any other extension are JSON.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{json5, toml, yaml};
+
+/// A format of the todo list file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Json,
+    #[serde(alias = "yml")]
+    Yaml,
+    Toml,
+    Json5,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "toml" => Ok(Format::Toml),
+            "json5" => Ok(Format::Json5),
+            _ => Err(format!(
+                "Unknown file format: '{}'. Expected json, yaml, toml or json5",
+                input
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+impl Format {
+    /// Returns the format of the file at `path` from its extension, looking past the number
+    /// that backups of the list end in, e.g. `todos.yaml.2`.
+    pub fn of(path: &Path) -> Format {
+        Format::named(path).unwrap_or_default()
+    }
+
+    /// Returns the format the extension of `path` names, if it names one.
+    pub fn named(path: &Path) -> Option<Format> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut parts = name.rsplit('.');
+        let last = parts.next().unwrap_or_default();
+        let extension = if !last.is_empty() && last.chars().all(|c| c.is_ascii_digit()) {
+            parts.next().unwrap_or_default()
+        } else {
+            last
+        };
+        if name.len() == extension.len() {
+            return None;
+        }
+        extension.parse().ok()
+    }
+
+    /// Returns the extension of files in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Toml => "toml",
+            Format::Json5 => "json5",
+        }
+    }
+}
+
+/// Reads a value written in `format`.
+pub fn parse(contents: &str, format: Format) -> Result<Value, String> {
+    match format {
+        Format::Json => serde_json::from_str(contents).map_err(|error| error.to_string()),
+        Format::Yaml => yaml::parse(contents),
+        Format::Toml => toml::parse(contents),
+        Format::Json5 => json5::parse(contents),
+    }
+}
+
+/// Writes out `value` in `format`. TOML has no null, so null values are left out, which
+/// reads back the same for the optional fields they come from.
+pub fn write(value: &Value, format: Format) -> String {
+    match format {
+        Format::Json => serde_json::to_string_pretty(value).expect("A value serializes to JSON"),
+        Format::Yaml => yaml::write(value),
+        Format::Toml => toml::write(value),
+        Format::Json5 => json5::write(value),
+    }
+}
+
+/// Returns the integer with `magnitude` and sign, if it fits in an `i64` or, when positive,
+/// a `u64`.
+pub fn signed(magnitude: u64, negative: bool) -> Option<Value> {
+    if !negative {
+        return Some(Value::from(magnitude));
+    }
+    match magnitude {
+        magnitude if magnitude <= i64::MAX as u64 => Some(Value::from(-(magnitude as i64))),
+        magnitude if magnitude == i64::MIN.unsigned_abs() => Some(Value::from(i64::MIN)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{parse, write, Format};
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(Format::of(Path::new("/srv/todos.yml")), Format::Yaml);
+        assert_eq!(Format::of(Path::new("todos.toml.3")), Format::Toml);
+        assert_eq!(Format::of(Path::new("todos.JSON5")), Format::Json5);
+        assert_eq!(Format::of(Path::new("todos.txt")), Format::Json);
+        assert_eq!(Format::named(Path::new("todos.txt")), None);
+        assert_eq!(Format::of(Path::new("toml")), Format::Json);
+        assert!("xml".parse::<Format>().is_err());
+
+        let value = serde_json::json!({
+            "todos": [
+                {
+                    "id": 1,
+                    "task": "Buy milk: 2 litres # semi-skimmed",
+                    "priority": 2,
+                    "created": -86400,
+                    "tags": ["home", "true", "3"],
+                    "reminders": [{ "at": 1717243200, "fired": false }],
+                },
+                {
+                    "id": 2,
+                    "task": "Write \"report\"\n\twith 'notes'\n",
+                    "priority": 5,
+                    "created": 1717243200,
+                    "done": [],
+                    "project": "",
+                    "estimate": 1.5,
+                },
+            ],
+            "checksum": "0123abcd",
+            "odd keys": { "a.b": "- x", "": "null", "é": "[1]" },
+        });
+        for format in [Format::Json, Format::Yaml, Format::Toml, Format::Json5] {
+            let written = write(&value, format);
+            assert_eq!(parse(&written, format), Ok(value.clone()), "{}", written);
+        }
+    }
+}
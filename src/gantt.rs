@@ -0,0 +1,177 @@
+//! A Mermaid Gantt chart of the scheduled todo items, to paste into GitHub or GitLab
+//! markdown.
+
+use std::fmt::Write;
+
+use chrono::DateTime;
+
+use crate::Todo;
+
+/// Renders the todo items that can be placed on a timeline as a fenced Mermaid Gantt chart,
+/// with a section per project.
+///
+/// An item starts when its wait date passes, or after the items it depends on, or its
+/// estimate before it is due; it ends when it is due or after its estimate (a day when it
+/// has none). Items due without a start or estimate are milestones, and items with
+/// priority 4 or 5 are marked critical. Items with no date to place them by are left out.
+///
+/// # Example
+///
+/// ```
+/// let chart = gantt::render(&todo_list.todos, "Todo list");
+/// ```
+pub fn render(todos: &[Todo], title: &str) -> String {
+    // Items without dates of their own can only be placed after items that are placed.
+    let mut placed: Vec<u64> = todos
+        .iter()
+        .filter(|todo| todo.wait.is_some() || todo.due.is_some())
+        .map(|todo| todo.id)
+        .collect();
+    loop {
+        let before = placed.len();
+        for todo in todos {
+            if !placed.contains(&todo.id) && todo.depends.iter().any(|id| placed.contains(id)) {
+                placed.push(todo.id);
+            }
+        }
+        if placed.len() == before {
+            break;
+        }
+    }
+
+    let mut projects: Vec<Option<&str>> = Vec::new();
+    for todo in todos.iter().filter(|todo| placed.contains(&todo.id)) {
+        if !projects.contains(&todo.project.as_deref()) {
+            projects.push(todo.project.as_deref());
+        }
+    }
+    projects.sort_by_key(|project| (project.is_none(), *project));
+
+    let mut output = String::new();
+    let _ = writeln!(output, "```mermaid\ngantt");
+    let _ = writeln!(output, "    title {}", clean(title));
+    let _ = writeln!(output, "    dateFormat YYYY-MM-DD HH:mm");
+    let _ = writeln!(output, "    axisFormat %m-%d");
+    for project in projects {
+        let _ = writeln!(
+            output,
+            "    section {}",
+            clean(project.unwrap_or("No project"))
+        );
+        for todo in todos
+            .iter()
+            .filter(|todo| todo.project.as_deref() == project && placed.contains(&todo.id))
+        {
+            let mut tags = Vec::new();
+            if todo.priority >= 4 {
+                tags.push("crit".to_string());
+            }
+            let after: Vec<String> = todo
+                .depends
+                .iter()
+                .filter(|id| placed.contains(id))
+                .map(|id| format!("t{}", id))
+                .collect();
+            let length = todo
+                .estimate
+                .map_or("1d".to_string(), |estimate| format!("{}m", estimate / 60));
+            let timing = match (todo.wait, todo.due, todo.estimate) {
+                (Some(start), Some(due), _) if due > start => {
+                    format!("{}, {}", timestamp(start), timestamp(due))
+                }
+                (Some(start), _, _) => format!("{}, {}", timestamp(start), length),
+                _ if !after.is_empty() => format!("after {}, {}", after.join(" "), length),
+                (None, Some(due), Some(estimate)) => {
+                    format!("{}, {}", timestamp(due - estimate), length)
+                }
+                (None, Some(due), None) => {
+                    tags.push("milestone".to_string());
+                    format!("{}, 0m", timestamp(due))
+                }
+                (None, None, _) => continue,
+            };
+            tags.push(format!("t{}", todo.id));
+            let _ = writeln!(
+                output,
+                "    {} :{}, {}",
+                clean(&todo.task),
+                tags.join(", "),
+                timing
+            );
+        }
+    }
+    let skipped = todos.len() - placed.len();
+    if skipped > 0 {
+        let _ = writeln!(
+            output,
+            "    %% {} task(s) without a wait date, due date or dependency are not shown",
+            skipped
+        );
+    }
+    let _ = writeln!(output, "```");
+    output
+}
+
+fn timestamp(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// Replaces the characters that separate the parts of a Mermaid Gantt line.
+fn clean(text: &str) -> String {
+    text.replace([':', ';', '#'], " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::TodoList;
+
+    #[test]
+    fn test_render() {
+        let mut todo_list = TodoList::new();
+        for (task, priority) in [
+            ("Book venue", 5),
+            ("Send invitations: all", 3),
+            ("Launch", 4),
+            ("Buy milk", 1),
+            ("Print flyers", 2),
+        ] {
+            todo_list.add_todo(task, priority);
+        }
+        let june = 1717200000; // 2024-06-01 00:00
+        let hour = 60 * 60;
+        todo_list.set_project(1, "party");
+        todo_list.set_project(2, "party");
+        todo_list.set_wait(1, june + 9 * hour);
+        todo_list.set_due(1, june + 33 * hour);
+        todo_list.add_dependencies(2, &[1]);
+        todo_list.set_estimate(2, 90 * 60);
+        todo_list.set_due(3, june + 240 * hour);
+        todo_list.set_due(5, june + 48 * hour);
+        todo_list.set_estimate(5, 2 * hour);
+
+        assert_eq!(
+            render(&todo_list.todos, "Plan"),
+            "```mermaid
+gantt
+    title Plan
+    dateFormat YYYY-MM-DD HH:mm
+    axisFormat %m-%d
+    section party
+    Book venue :crit, t1, 2024-06-01 09:00, 2024-06-02 09:00
+    Send invitations all :t2, after t1, 90m
+    section No project
+    Launch :crit, milestone, t3, 2024-06-11 00:00, 0m
+    Print flyers :t5, 2024-06-02 22:00, 120m
+    %% 1 task(s) without a wait date, due date or dependency are not shown
+```
+"
+        );
+    }
+}
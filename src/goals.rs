@@ -0,0 +1,226 @@
+//! Goals: higher-level objectives, e.g. a quarter's, that todo items are linked to, for
+//! `goal`.
+//!
+//! The goals are kept in a file next to the todo list, and a todo item names the goal it is
+//! linked to, as it names its project. Completed items leave the list, so a goal's progress
+//! counts its open items on the list and its done ones in the log of completed items that
+//! `stats` reads.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dates::{self, DateFormat};
+use crate::stats::Completed;
+use crate::Todo;
+
+/// The file holding the goals, relative to the todo list's directory.
+pub const GOALS_FILE: &str = ".todo-goals.json";
+
+/// How many characters wide the progress bar of a goal is.
+const BAR_WIDTH: usize = 20;
+
+/// An objective todo items are linked to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Goal {
+    pub name: String,
+    /// When it was added.
+    pub created: i64,
+    /// When it should be reached, e.g. the end of a quarter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub by: Option<i64>,
+}
+
+/// How far a goal has come.
+#[derive(Debug, Default, PartialEq)]
+pub struct Progress {
+    /// The number of linked todo items still on the list.
+    pub open: usize,
+    /// The number of linked todo items completed, as far back as the log goes.
+    pub done: usize,
+    /// The estimates of the open items added up, in seconds.
+    pub remaining: i64,
+    /// The number of open items without an estimate.
+    pub unestimated: usize,
+}
+
+/// Reads the goals of the todo list in `base`, which are none if there is no goals file
+/// yet.
+pub fn load(base: &Path) -> Result<Vec<Goal>, String> {
+    let path = base.join(GOALS_FILE);
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map_err(|error| format!("Unable to parse {}: {}", path.display(), error)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(format!("Unable to read {}: {}", path.display(), error)),
+    }
+}
+
+/// Writes `goals` as the goals of the todo list in `base`.
+pub fn save(base: &Path, goals: &[Goal]) -> Result<(), String> {
+    let path = base.join(GOALS_FILE);
+    let contents = serde_json::to_string_pretty(goals).expect("Unable to serialize goals");
+    fs::write(&path, contents)
+        .map_err(|error| format!("Unable to write {}: {}", path.display(), error))
+}
+
+/// Checks that `name` can name a goal, returning it trimmed.
+pub fn check_name(name: &str) -> Result<&str, String> {
+    match name.trim() {
+        "" => Err("A goal needs a name".to_string()),
+        name => Ok(name),
+    }
+}
+
+/// Works out the progress of the goal `name` from the open todo items `todos` and those
+/// `completed`.
+pub fn progress(name: &str, todos: &[Todo], completed: &[Completed]) -> Progress {
+    let linked = |todo: &&Todo| todo.goal.as_deref() == Some(name);
+    let mut progress = Progress {
+        done: completed
+            .iter()
+            .map(|item| &item.todo)
+            .filter(linked)
+            .count(),
+        ..Progress::default()
+    };
+    for todo in todos.iter().filter(linked) {
+        progress.open += 1;
+        match todo.estimate {
+            Some(estimate) => progress.remaining += estimate,
+            None => progress.unestimated += 1,
+        }
+    }
+    progress
+}
+
+/// Formats `goal` and its `progress`, with its date in the format `dates` relative to the
+/// timestamp `now`, or as labeled lines when `plain` is set.
+pub fn render(
+    goal: &Goal,
+    progress: &Progress,
+    dates: &DateFormat,
+    now: i64,
+    plain: bool,
+) -> String {
+    let total = progress.open + progress.done;
+    let by = goal.by.map(|by| dates.format(by, now, "%Y-%m-%d"));
+    let remaining = dates::format_duration(progress.remaining);
+    let mut output = String::new();
+    if plain {
+        let _ = writeln!(output, "goal: {}", goal.name);
+        if let Some(by) = by {
+            let _ = writeln!(output, "by: {}", by);
+        }
+        let _ = writeln!(output, "done: {} of {}", progress.done, total);
+        let _ = writeln!(output, "open: {}", progress.open);
+        let _ = writeln!(output, "estimated left: {}", remaining);
+        let _ = writeln!(output, "without an estimate: {}", progress.unestimated);
+        return output;
+    }
+    match by {
+        Some(by) => {
+            let _ = writeln!(output, "{} (by {})", goal.name, by);
+        }
+        None => {
+            let _ = writeln!(output, "{}", goal.name);
+        }
+    }
+    let filled = match total {
+        0 => 0,
+        total => progress.done * BAR_WIDTH / total,
+    };
+    let percent = match total {
+        0 => 0,
+        total => progress.done * 100 / total,
+    };
+    let _ = writeln!(
+        output,
+        "  {}{} {} of {} done ({}%)",
+        "█".repeat(filled),
+        "░".repeat(BAR_WIDTH - filled),
+        progress.done,
+        total,
+        percent
+    );
+    let _ = write!(
+        output,
+        "  {} open, {} estimated left",
+        progress.open, remaining
+    );
+    if progress.unestimated > 0 {
+        let _ = write!(output, ", {} without an estimate", progress.unestimated);
+    }
+    output.push('\n');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_name, progress, render, Goal, Progress};
+    use crate::dates::DateFormat;
+    use crate::stats::Completed;
+    use crate::TodoList;
+
+    #[test]
+    fn test_progress() {
+        let mut todo_list = TodoList::new();
+        for task in [
+            "Write the changelog",
+            "Fix the installer",
+            "Tag the release",
+            "Rest",
+        ] {
+            todo_list.add_todo(task, 3);
+        }
+        for todo in &mut todo_list.todos[..3] {
+            todo.goal = Some("Ship v2".to_string());
+        }
+        todo_list.todos[0].estimate = Some(90 * 60);
+        todo_list.todos[1].estimate = Some(60 * 60);
+        let completed: Vec<Completed> = todo_list.todos[..2]
+            .iter()
+            .map(|todo| Completed {
+                completed: todo.created,
+                todo: todo.clone(),
+            })
+            .collect();
+
+        let found = progress("Ship v2", &todo_list.todos, &completed);
+        assert_eq!(
+            found,
+            Progress {
+                open: 3,
+                done: 2,
+                remaining: 150 * 60,
+                unestimated: 1,
+            }
+        );
+        assert_eq!(
+            progress("Other", &todo_list.todos, &completed),
+            Progress::default()
+        );
+
+        let goal = Goal {
+            name: "Ship v2".to_string(),
+            created: 0,
+            by: Some(1719705600),
+        };
+        let short = DateFormat::parse("%Y-%m-%d").unwrap();
+        assert_eq!(
+            render(&goal, &found, &short, 0, false),
+            "Ship v2 (by 2024-06-30)\n  ████████░░░░░░░░░░░░ 2 of 5 done (40%)\n  \
+             3 open, 2h30m estimated left, 1 without an estimate\n"
+        );
+        assert_eq!(
+            render(&goal, &Progress::default(), &short, 0, true),
+            "goal: Ship v2\nby: 2024-06-30\ndone: 0 of 0\nopen: 0\nestimated left: 0m\n\
+             without an estimate: 0\n"
+        );
+
+        assert_eq!(check_name(" Ship v2 "), Ok("Ship v2"));
+        assert!(check_name(" ").is_err());
+    }
+}
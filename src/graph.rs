@@ -0,0 +1,127 @@
+//! The dependency graph of the todo list in Graphviz's DOT language, to render with e.g.
+//! `dot -Tsvg`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::priorities;
+use crate::tags;
+use crate::Todo;
+
+/// The fill color of each priority level, from 1 (green) to 5 (red).
+const PRIORITY_COLORS: [&str; 5] = ["#63be7b", "#b1d580", "#ffeb84", "#fbaa77", "#f8696b"];
+
+/// Renders `todos` as a DOT digraph with an edge from each todo item to the ones depending
+/// on it, at the timestamp `now`.
+///
+/// Items are grouped into a box per project and filled by priority. BLOCKED items have a
+/// dashed border, WAITING items grey text, and OVERDUE items a thick red border.
+///
+/// # Example
+///
+/// ```
+/// let dot = graph::dot(&todo_list.todos, Utc::now().timestamp());
+/// std::fs::write("todos.dot", dot)?;
+/// ```
+pub fn dot(todos: &[Todo], now: i64) -> String {
+    let mut output = String::new();
+    let _ = writeln!(output, "digraph todos {{");
+    let _ = writeln!(output, "  rankdir=LR;");
+    let _ = writeln!(
+        output,
+        "  node [shape=box, style=\"rounded,filled\", fontname=\"Helvetica\"];"
+    );
+
+    let mut projects: BTreeMap<Option<&str>, Vec<&Todo>> = BTreeMap::new();
+    for todo in todos {
+        projects
+            .entry(todo.project.as_deref())
+            .or_default()
+            .push(todo);
+    }
+    for (index, (project, todos)) in projects.iter().enumerate() {
+        let indent = match project {
+            Some(project) => {
+                let _ = writeln!(output, "  subgraph cluster_{} {{", index);
+                let _ = writeln!(output, "    label=\"{}\";", escape(project));
+                "    "
+            }
+            None => "  ",
+        };
+        for todo in todos {
+            let _ = writeln!(output, "{}{}", indent, node(todo, now));
+        }
+        if project.is_some() {
+            let _ = writeln!(output, "  }}");
+        }
+    }
+    for todo in todos {
+        for dependency in &todo.depends {
+            let _ = writeln!(output, "  t{} -> t{};", dependency, todo.id);
+        }
+    }
+    let _ = writeln!(output, "}}");
+    output
+}
+
+fn node(todo: &Todo, now: i64) -> String {
+    let scale = priorities::scale();
+    let color = PRIORITY_COLORS[(scale.band(todo.priority) - 1) as usize];
+    let mut attributes = vec![
+        format!(
+            "label=\"{}: {}\\npriority {}\"",
+            todo.id,
+            escape(&todo.task),
+            escape(&scale.label(todo.priority))
+        ),
+        format!("fillcolor=\"{}\"", color),
+    ];
+    let has = |tag| tags::virtual_tag(todo, tag, now) == Some(true);
+    if has("BLOCKED") {
+        attributes.push("style=\"rounded,filled,dashed\"".to_string());
+    }
+    if has("WAITING") {
+        attributes.push("fontcolor=\"#777777\"".to_string());
+    }
+    if has("OVERDUE") {
+        attributes.push("color=\"#c00000\", penwidth=2".to_string());
+    }
+    format!("t{} [{}];", todo.id, attributes.join(", "))
+}
+
+/// Escapes text for a double-quoted DOT string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dot;
+    use crate::TodoList;
+
+    #[test]
+    fn test_dot() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Book \"the\" venue", 5);
+        todo_list.add_todo("Send invitations", 3);
+        todo_list.add_todo("Buy milk", 1);
+        let now = todo_list.todos[0].created;
+        todo_list.set_project(1, "party");
+        todo_list.set_project(2, "party");
+        todo_list.add_dependencies(2, &[1]);
+        todo_list.set_due(1, now - 60);
+
+        let output = dot(&todo_list.todos, now);
+        assert!(output.starts_with("digraph todos {\n"));
+        assert!(output.ends_with("}\n"));
+        assert!(output.contains(
+            "  subgraph cluster_1 {\n    label=\"party\";\n    t1 [label=\"1: Book \\\"the\\\" venue\\npriority 5\", fillcolor=\"#f8696b\", color=\"#c00000\", penwidth=2];\n"
+        ));
+        assert!(output.contains("t2 [label=\"2: Send invitations\\npriority 3\", fillcolor=\"#ffeb84\", style=\"rounded,filled,dashed\"];"));
+        assert!(output
+            .contains("\n  t3 [label=\"3: Buy milk\\npriority 1\", fillcolor=\"#63be7b\"];\n"));
+        assert!(output.contains("  t1 -> t2;\n"));
+    }
+}
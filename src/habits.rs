@@ -0,0 +1,369 @@
+//! Streaks and histories of recurring todo items, for `show`, `habits` and
+//! `stats --recurring`.
+//!
+//! A recurring item stays on the list, and `done` checks off its current occurrence, the one
+//! that began last. An occurrence counts as kept if it was checked off before the next one
+//! began, so the streak is the number of kept occurrences in a row, not counting the current
+//! one while it is still open. `done --late` checks off the occurrence before the current
+//! one instead, which the history then counts as done late rather than skipped.
+
+use std::fmt::{self, Write};
+
+use chrono::Datelike;
+
+use crate::recurrence::{Frequency, Rule};
+use crate::Todo;
+
+/// How a recurring todo item has been kept up.
+#[derive(Debug, PartialEq)]
+pub struct Habit {
+    /// The number of occurrences in a row checked off, up to the current one.
+    pub streak: usize,
+    /// The number of occurrences this month that passed without being checked off.
+    pub missed: usize,
+    /// Whether the current occurrence is checked off.
+    pub done: bool,
+    /// What an occurrence is called, e.g. "day" for an item repeating daily.
+    unit: &'static str,
+}
+
+impl fmt::Display for Habit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let plural = if self.streak == 1 { "" } else { "s" };
+        write!(
+            f,
+            "done {} {}{} in a row, missed {} this month",
+            self.streak, self.unit, plural, self.missed
+        )
+    }
+}
+
+/// The timestamps when an occurrence begins, and when the next one does, if any.
+type Period = (i64, Option<i64>);
+
+/// Returns the rule of a recurring todo item and its occurrences that have begun by the
+/// timestamp `now`, in the item's timezone.
+fn periods(todo: &Todo, now: i64) -> Option<(Rule, Vec<Period>)> {
+    let rule = Rule::parse(todo.recur.as_deref()?).ok()?;
+    let mut periods: Vec<Period> = Vec::new();
+    for occurrence in rule.timestamps(todo.created, todo.zone()) {
+        if let Some(last) = periods.last_mut() {
+            last.1 = Some(occurrence);
+        }
+        if occurrence > now {
+            break;
+        }
+        periods.push((occurrence, None));
+    }
+    Some((rule, periods))
+}
+
+/// Returns whether the todo item was checked off between `start` and `end`.
+fn checked_off(todo: &Todo, (start, end): Period) -> bool {
+    let end = end.unwrap_or(i64::MAX);
+    todo.done.iter().any(|done| (start..end).contains(done))
+}
+
+/// Returns what an occurrence of `rule` is called in a streak.
+fn unit(rule: &Rule) -> &'static str {
+    let once = rule.by_day.len() <= 1 && rule.by_month_day.len() <= 1 && rule.by_month.len() <= 1;
+    match rule.freq {
+        _ if rule.interval > 1 => "time",
+        Frequency::Daily => "day",
+        Frequency::Weekly if once => "week",
+        Frequency::Monthly if once => "month",
+        Frequency::Yearly if once => "year",
+        _ => "time",
+    }
+}
+
+/// Works out how a todo item has been kept up at the timestamp `now`, or returns `None` if
+/// it does not recur.
+pub fn habit(todo: &Todo, now: i64) -> Option<Habit> {
+    let (rule, periods) = periods(todo, now)?;
+    let kept: Vec<bool> = periods
+        .iter()
+        .map(|period| checked_off(todo, *period))
+        .collect();
+    let done = kept.last().copied().unwrap_or_default();
+    // The current occurrence only breaks the streak once it is over
+    let past = &kept[..kept.len().saturating_sub(1)];
+    let streak = done as usize + past.iter().rev().take_while(|kept| **kept).count();
+    let zone = todo.zone();
+    let month = |timestamp| {
+        let date = zone.local(timestamp);
+        (date.year(), date.month())
+    };
+    let missed = periods
+        .iter()
+        .zip(past)
+        .filter(|((start, _), kept)| !**kept && month(*start) == month(now))
+        .count();
+    Some(Habit {
+        streak,
+        missed,
+        done,
+        unit: unit(&rule),
+    })
+}
+
+/// What became of an occurrence of a recurring todo item.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// Checked off before the next occurrence began.
+    OnTime,
+    /// Checked off with `done --late` after the next occurrence began.
+    Late,
+    /// Over without being checked off.
+    Skipped,
+    /// The current occurrence, not checked off yet.
+    Open,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Outcome::OnTime => "on time",
+            Outcome::Late => "late",
+            Outcome::Skipped => "skipped",
+            Outcome::Open => "open",
+        })
+    }
+}
+
+/// Returns when each occurrence of a todo item that has begun by the timestamp `now` began
+/// and what became of it, oldest first, or `None` if it does not recur.
+pub fn history(todo: &Todo, now: i64) -> Option<Vec<(i64, Outcome)>> {
+    let (_, periods) = periods(todo, now)?;
+    let current = periods.len().saturating_sub(1);
+    let history = periods
+        .iter()
+        .enumerate()
+        .map(|(index, period)| {
+            let start = period.0;
+            let outcome = match checked_off(todo, *period) {
+                true => Outcome::OnTime,
+                false if todo.late.contains(&start) => Outcome::Late,
+                false if index == current => Outcome::Open,
+                false => Outcome::Skipped,
+            };
+            (start, outcome)
+        })
+        .collect();
+    Some(history)
+}
+
+/// How many of the occurrences of a recurring todo item that are over were done on time,
+/// late or not at all.
+#[derive(Debug, Default, PartialEq)]
+pub struct Record {
+    pub on_time: usize,
+    pub late: usize,
+    pub skipped: usize,
+}
+
+impl Record {
+    /// Counts the outcomes of `history`, the current occurrence only once it is done.
+    pub fn of(history: &[(i64, Outcome)]) -> Record {
+        let mut record = Record::default();
+        for (_, outcome) in history {
+            match outcome {
+                Outcome::OnTime => record.on_time += 1,
+                Outcome::Late => record.late += 1,
+                Outcome::Skipped => record.skipped += 1,
+                Outcome::Open => {}
+            }
+        }
+        record
+    }
+}
+
+/// Formats the records of the recurring todo items `records` as a table, with the share of
+/// their occurrences done at all.
+pub fn render_records(records: &[(&Todo, Record)]) -> String {
+    let name = |todo: &Todo| format!("{}: {}", todo.id, todo.task);
+    let width = records
+        .iter()
+        .map(|(todo, _)| name(todo).chars().count())
+        .chain(["task".len()])
+        .max()
+        .unwrap_or_default();
+    let mut output = String::new();
+    let _ = writeln!(
+        output,
+        "{:width$}  {:>7}  {:>4}  {:>7}  {:>4}",
+        "task", "on time", "late", "skipped", "done"
+    );
+    for (todo, record) in records {
+        let total = record.on_time + record.late + record.skipped;
+        let done = match total {
+            0 => "-".to_string(),
+            total => format!("{}%", (record.on_time + record.late) * 100 / total),
+        };
+        let _ = writeln!(
+            output,
+            "{:width$}  {:>7}  {:>4}  {:>7}  {:>4}",
+            name(todo),
+            record.on_time,
+            record.late,
+            record.skipped,
+            done
+        );
+    }
+    output
+}
+
+/// Checks that the occurrence before the current one of a todo item can be checked off late
+/// at the timestamp `now`, returning when it began: it has to be over and not checked off
+/// already.
+pub fn check_late(todo: &Todo, now: i64) -> Result<i64, String> {
+    let Some((_, periods)) = periods(todo, now) else {
+        return Err(format!(
+            "Task {} does not recur; remove it once it is done",
+            todo.id
+        ));
+    };
+    let Some(period) = periods.len().checked_sub(2).map(|index| periods[index]) else {
+        return Err(format!("Task {} has no earlier occurrence", todo.id));
+    };
+    let start = period.0;
+    match checked_off(todo, period) || todo.late.contains(&start) {
+        true => Err(format!(
+            "Task {} is already done for its occurrence of {}",
+            todo.id,
+            todo.zone().local(start).format("%Y-%m-%d %H:%M")
+        )),
+        false => Ok(start),
+    }
+}
+
+/// Checks that the current occurrence of a todo item can be checked off at the timestamp
+/// `now`: it has to recur, have begun, and not be checked off already.
+pub fn check(todo: &Todo, now: i64) -> Result<(), String> {
+    let Some((_, periods)) = periods(todo, now) else {
+        return Err(format!(
+            "Task {} does not recur; remove it once it is done",
+            todo.id
+        ));
+    };
+    match periods.last() {
+        None => Err(format!("Task {} has not begun yet", todo.id)),
+        Some(period) if checked_off(todo, *period) => Err(format!(
+            "Task {} is already done for its occurrence of {}",
+            todo.id,
+            todo.zone().local(period.0).format("%Y-%m-%d %H:%M")
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, check_late, habit, history, render_records, Habit, Outcome, Record};
+    use crate::TodoList;
+
+    #[test]
+    fn test_habit() {
+        const DAY: i64 = 24 * 60 * 60;
+        // 2024-06-01 09:00, and an hour after the occurrence of 2024-06-15
+        let created = 1717232400;
+        let now = created + 14 * DAY + 60 * 60;
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Meditate", 2);
+        let todo = &mut todo_list.todos[0];
+        todo.created = created;
+        assert_eq!(habit(todo, now), None);
+        assert!(check(todo, now).unwrap_err().contains("does not recur"));
+
+        todo.recur = Some("FREQ=DAILY".to_string());
+        // Checked off on the 6th to the 14th, late in the day
+        todo.done = (5..14)
+            .map(|day| created + day * DAY + 12 * 60 * 60)
+            .collect();
+        let kept = habit(todo, now).unwrap();
+        assert_eq!((kept.streak, kept.missed, kept.done), (9, 5, false));
+        assert_eq!(
+            kept.to_string(),
+            "done 9 days in a row, missed 5 this month"
+        );
+        assert_eq!(check(todo, now), Ok(()));
+
+        todo.done.push(now);
+        assert_eq!(habit(todo, now).unwrap().streak, 10);
+        assert!(check(todo, now).unwrap_err().contains("2024-06-15 09:00"));
+        assert!(check(todo, created - DAY)
+            .unwrap_err()
+            .contains("not begun"));
+
+        todo.recur = Some("FREQ=WEEKLY".to_string());
+        todo.done = vec![created + 60, created + 7 * DAY + 60];
+        assert_eq!(
+            habit(todo, now),
+            Some(Habit {
+                streak: 2,
+                missed: 0,
+                done: false,
+                unit: "week",
+            })
+        );
+    }
+    #[test]
+    fn test_history() {
+        const DAY: i64 = 24 * 60 * 60;
+        // 2024-06-01 09:00, and an hour after the occurrence of 2024-06-05
+        let created = 1717232400;
+        let now = created + 4 * DAY + 60 * 60;
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Stretch", 2);
+        let todo = &mut todo_list.todos[0];
+        todo.created = created;
+        assert_eq!(history(todo, now), None);
+        assert!(check_late(todo, now)
+            .unwrap_err()
+            .contains("does not recur"));
+
+        todo.recur = Some("FREQ=DAILY".to_string());
+        // Done on the 1st and the 3rd, the 2nd the next day, and nothing on the 4th
+        todo.done = vec![created + 60, created + 2 * DAY + 60];
+        todo.late = vec![created + DAY];
+        let outcomes: Vec<Outcome> = history(todo, now)
+            .unwrap()
+            .into_iter()
+            .map(|(_, outcome)| outcome)
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                Outcome::OnTime,
+                Outcome::Late,
+                Outcome::OnTime,
+                Outcome::Skipped,
+                Outcome::Open
+            ]
+        );
+        let record = Record::of(&history(todo, now).unwrap());
+        assert_eq!(
+            record,
+            Record {
+                on_time: 2,
+                late: 1,
+                skipped: 1,
+            }
+        );
+        assert_eq!(
+            render_records(&[(todo, record)]),
+            "task        on time  late  skipped  done\n\
+             1: Stretch        2     1        1   75%\n"
+        );
+
+        // The 4th can still be done late, once
+        assert_eq!(check_late(todo, now), Ok(created + 3 * DAY));
+        todo.late.push(created + 3 * DAY);
+        assert!(check_late(todo, now)
+            .unwrap_err()
+            .contains("2024-06-04 09:00"));
+        assert!(check_late(todo, created + 60)
+            .unwrap_err()
+            .contains("no earlier occurrence"));
+    }
+}
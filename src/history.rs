@@ -0,0 +1,229 @@
+//! The history of a todo list file, which `list --as-of` shows the list from: every save
+//! appends a revision to a log next to the backups, which is never rotated or rewritten.
+//!
+//! A revision holds when it was saved, the hashes of the list's items in order, and the
+//! items that changed since the revision before, so the list as of any moment is rebuilt by
+//! replaying the revisions up to it. Removing an item renumbers the ones after it, which
+//! then count as changed too.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::formats::Format;
+use crate::store::{list_directory, BACKUP_DIR};
+use crate::{shards, Todo, TodoList};
+
+/// A save of the list, as a line of the history.
+#[derive(Debug, Serialize, Deserialize)]
+struct Revision {
+    /// The timestamp of the save.
+    at: i64,
+    /// The hashes of the items in the list, in order.
+    order: Vec<String>,
+    /// The items added or changed since the revision before.
+    changed: Vec<Todo>,
+}
+
+/// Returns the path of the history of the todo list file at `path`.
+pub fn history_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "todos.json".to_string());
+    list_directory(path)
+        .join(BACKUP_DIR)
+        .join(format!("{}.history", name))
+}
+
+/// Appends the list saved at `at` to the history of the list file at `path`, given what the
+/// file held before and when that was saved, if anything. A list saved before its history
+/// was kept starts it with the version it replaced.
+pub fn record(path: &Path, previous: Option<(&str, i64)>, todo_list: &TodoList, at: i64) {
+    let history = history_path(path);
+    let parse = |(contents, saved): (&str, i64)| {
+        let mut todo_list = TodoList::parse(contents, Format::of(path)).ok()?;
+        todo_list.assign_hashes();
+        Some((todo_list, saved))
+    };
+    let mut lines = String::new();
+    match previous.and_then(parse) {
+        Some((previous, _)) if history.exists() => {
+            lines.push_str(&revision(Some(&previous), todo_list, at));
+        }
+        Some((previous, saved)) => {
+            lines.push_str(&revision(None, &previous, saved.min(at)));
+            lines.push_str(&revision(Some(&previous), todo_list, at));
+        }
+        None => lines.push_str(&revision(None, todo_list, at)),
+    }
+    let result = std::fs::create_dir_all(list_directory(path).join(BACKUP_DIR)).and_then(|_| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&history)?
+            .write_all(lines.as_bytes())
+    });
+    if let Err(error) = result {
+        println!("Unable to record the history of the todo list: {}", error);
+    }
+}
+
+/// Serializes the revision saving `todo_list` at `at` as a line, with the items that differ
+/// from `previous`, or all of them if there's no previous version.
+fn revision(previous: Option<&TodoList>, todo_list: &TodoList, at: i64) -> String {
+    let serialize = |todo: &Todo| serde_json::to_string(todo).expect("Unable to serialize todo");
+    let before: HashMap<&str, String> = previous
+        .map(|previous| {
+            previous
+                .todos
+                .iter()
+                .map(|todo| (todo.hash.as_str(), serialize(todo)))
+                .collect()
+        })
+        .unwrap_or_default();
+    let revision = Revision {
+        at,
+        order: todo_list
+            .todos
+            .iter()
+            .map(|todo| todo.hash.clone())
+            .collect(),
+        changed: todo_list
+            .todos
+            .iter()
+            .filter(|todo| before.get(todo.hash.as_str()) != Some(&serialize(todo)))
+            .cloned()
+            .collect(),
+    };
+    let mut line = serde_json::to_string(&revision).expect("Unable to serialize revision");
+    line.push('\n');
+    line
+}
+
+/// Rebuilds the todo list at `path` as it was at the timestamp `at` from its history, which
+/// is the last version saved before or at that moment.
+pub fn as_of(path: &Path, at: i64) -> Result<TodoList, String> {
+    if shards::is_sharded(path) {
+        return Err("The history of a sharded list is not kept".to_string());
+    }
+    let history = history_path(path);
+    let contents = std::fs::read_to_string(&history).map_err(|error| {
+        if error.kind() == std::io::ErrorKind::NotFound {
+            format!(
+                "No history of {} is kept yet. It starts with the next change to the list",
+                path.display()
+            )
+        } else {
+            format!("Unable to read {}: {}", history.display(), error)
+        }
+    })?;
+    replay(&contents, at)
+}
+
+/// Replays the revisions in `contents` up to the timestamp `at`.
+fn replay(contents: &str, at: i64) -> Result<TodoList, String> {
+    let mut items: HashMap<String, Todo> = HashMap::new();
+    let mut order = None;
+    let mut first = None;
+    for (n, line) in contents.lines().enumerate() {
+        // A save interrupted while appending leaves a partial last line
+        let Ok(revision) = serde_json::from_str::<Revision>(line) else {
+            if n + 1 == contents.lines().count() {
+                break;
+            }
+            return Err(format!("Revision {} of the history is unreadable", n + 1));
+        };
+        first.get_or_insert(revision.at);
+        if revision.at > at {
+            break;
+        }
+        for todo in revision.changed {
+            items.insert(todo.hash.clone(), todo);
+        }
+        order = Some(revision.order);
+    }
+    let Some(order) = order else {
+        return Err(match first {
+            Some(first) => format!(
+                "The history only goes back to {}",
+                DateTime::from_timestamp(first, 0)
+                    .unwrap_or_default()
+                    .format("%Y-%m-%d %H:%M")
+            ),
+            None => "The history is empty".to_string(),
+        });
+    };
+    let mut todo_list = TodoList::new();
+    todo_list.todos = order
+        .iter()
+        .filter_map(|hash| items.get(hash).cloned())
+        .collect();
+    Ok(todo_list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replay, revision};
+    use crate::TodoList;
+
+    fn list(tasks: &[&str]) -> TodoList {
+        let mut todo_list = TodoList::new();
+        for task in tasks {
+            todo_list.add_todo(task, 1);
+        }
+        todo_list
+    }
+
+    fn tasks(todo_list: &TodoList) -> Vec<(u64, &str)> {
+        todo_list
+            .todos
+            .iter()
+            .map(|todo| (todo.id, todo.task.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_replay() {
+        let first = list(&["Buy milk", "Call mom"]);
+        let mut second = first.clone();
+        second.todos[1].task = "Call dad".to_string();
+        let mut third = second.clone();
+        third.remove_todo(1);
+        let history = [
+            revision(None, &first, 100),
+            revision(Some(&first), &second, 200),
+            revision(Some(&second), &third, 300),
+        ]
+        .concat();
+        // Only what changed is recorded
+        assert!(!history.lines().nth(1).unwrap().contains("Buy milk"));
+        assert_eq!(
+            tasks(&replay(&history, 150).unwrap()),
+            [(1, "Buy milk"), (2, "Call mom")]
+        );
+        assert_eq!(
+            tasks(&replay(&history, 299).unwrap()),
+            [(1, "Buy milk"), (2, "Call dad")]
+        );
+        assert_eq!(tasks(&replay(&history, 300).unwrap()), [(1, "Call dad")]);
+        assert_eq!(
+            replay(&history, 50).unwrap_err(),
+            "The history only goes back to 1970-01-01 00:01"
+        );
+        let interrupted = format!("{}{{\"at\": 400, \"ord", history);
+        assert_eq!(
+            tasks(&replay(&interrupted, 500).unwrap()),
+            [(1, "Call dad")]
+        );
+        let mangled = format!("x\n{}", history);
+        assert_eq!(
+            replay(&mangled, 500).unwrap_err(),
+            "Revision 1 of the history is unreadable"
+        );
+    }
+}
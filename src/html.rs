@@ -0,0 +1,159 @@
+//! A standalone HTML page of the todo list, sortable and filterable in the browser without
+//! any server-side code.
+
+use std::fmt::Write;
+
+use chrono::DateTime;
+
+use crate::dates::DateFormat;
+use crate::{is_url, Todo};
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; color: #222; }
+input { padding: 0.4em; width: 20em; margin-bottom: 1em; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.4em 0.8em; border-bottom: 1px solid #ddd; }
+th { cursor: pointer; user-select: none; background: #f4f4f4; }
+th[aria-sort=ascending]::after { content: \" \\25B2\"; }
+th[aria-sort=descending]::after { content: \" \\25BC\"; }
+.overdue { color: #b00; font-weight: bold; }";
+
+/// Sorts the table by a clicked column (again to reverse) using each cell's `data-sort`
+/// value, and hides the rows not containing the filter text.
+const SCRIPT: &str = "const table = document.querySelector('table');
+const rows = () => Array.from(table.tBodies[0].rows);
+document.querySelector('input').addEventListener('input', event => {
+  const filter = event.target.value.toLowerCase();
+  for (const row of rows()) {
+    row.hidden = !row.textContent.toLowerCase().includes(filter);
+  }
+});
+table.querySelectorAll('th').forEach((header, column) => {
+  header.addEventListener('click', () => {
+    const ascending = header.getAttribute('aria-sort') !== 'ascending';
+    table.querySelectorAll('th').forEach(other => other.removeAttribute('aria-sort'));
+    header.setAttribute('aria-sort', ascending ? 'ascending' : 'descending');
+    const key = row => row.cells[column].dataset.sort ?? row.cells[column].textContent;
+    const sorted = rows().sort((a, b) => {
+      const [x, y] = [key(a), key(b)];
+      const order = x === '' ? 1 : y === '' ? -1
+        : isNaN(x) || isNaN(y) ? x.localeCompare(y) : x - y;
+      return ascending || x === '' || y === '' ? order : -order;
+    });
+    table.tBodies[0].append(...sorted);
+  });
+});";
+
+/// Renders `todos` as a standalone HTML page titled `title`, with due dates in the format
+/// `dates`, marking the items due before the timestamp `now` as overdue.
+///
+/// # Example
+///
+/// ```
+/// let now = Utc::now().timestamp();
+/// let page = html::render(&todo_list.todos, "Team tasks", now, &DateFormat::default());
+/// std::fs::write("tasks.html", page)?;
+/// ```
+pub fn render(todos: &[Todo], title: &str, now: i64, dates: &DateFormat) -> String {
+    let mut page = String::new();
+    let _ = writeln!(page, "<!DOCTYPE html>");
+    let _ = writeln!(page, "<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">");
+    let _ = writeln!(page, "<title>{}</title>", escape(title));
+    let _ = writeln!(page, "<style>\n{}\n</style>\n</head>\n<body>", STYLE);
+    let _ = writeln!(page, "<h1>{}</h1>", escape(title));
+    let generated = DateTime::from_timestamp(now, 0).unwrap_or_default();
+    let _ = writeln!(
+        page,
+        "<p>{} open tasks, exported {} (Utc).</p>",
+        todos.len(),
+        generated.format("%Y-%m-%d %H:%M")
+    );
+    let _ = writeln!(
+        page,
+        "<input type=\"search\" placeholder=\"Filter tasks\" aria-label=\"Filter tasks\">"
+    );
+    let _ = writeln!(page, "<table>\n<thead>\n<tr>");
+    for heading in [
+        "ID", "Task", "Priority", "Project", "Tags", "Assignee", "Due",
+    ] {
+        let _ = writeln!(page, "<th scope=\"col\">{}</th>", heading);
+    }
+    let _ = writeln!(page, "</tr>\n</thead>\n<tbody>");
+    for todo in todos {
+        let text = |value: &Option<String>| escape(value.as_deref().unwrap_or_default());
+        let tags: Vec<String> = todo.tags.iter().map(|tag| format!("+{}", tag)).collect();
+        let (due_sort, due) = match todo.due {
+            Some(due) => (due.to_string(), dates.format(due, now, "%Y-%m-%d %H:%M")),
+            None => (String::new(), String::new()),
+        };
+        // Only web and mail links are linked, so a stored `javascript:` URL can't run
+        let task = match &todo.url {
+            Some(url) if is_url(url) => {
+                format!("<a href=\"{}\">{}</a>", escape(url), escape(&todo.task))
+            }
+            Some(url) => format!("{} {}", escape(&todo.task), escape(url)),
+            None => escape(&todo.task),
+        };
+        let overdue = if todo.due.is_some_and(|due| due < now) {
+            " class=\"overdue\""
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            page,
+            "<tr><td>{}</td><td data-sort=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td data-sort=\"{}\"{}>{}</td></tr>",
+            todo.id,
+            escape(&todo.task.to_lowercase()),
+            task,
+            todo.priority,
+            text(&todo.project),
+            escape(&tags.join(" ")),
+            text(&todo.assignee),
+            due_sort,
+            overdue,
+            due
+        );
+    }
+    let _ = writeln!(page, "</tbody>\n</table>");
+    let _ = writeln!(page, "<script>\n{}\n</script>\n</body>\n</html>", SCRIPT);
+    page
+}
+
+/// Escapes text for use in HTML content and quoted attributes.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::dates::DateFormat;
+    use crate::TodoList;
+
+    #[test]
+    fn test_render() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Fix <script> & \"quotes\"", 4);
+        todo_list.add_todo("Read docs", 2);
+        let now = todo_list.todos[0].created;
+        todo_list.set_due(1, now - 60);
+        todo_list.set_url(2, "https://example.com/?a=1&b=2");
+        todo_list.add_todo("click me", 1);
+        // As a list saved before URLs were checked could hold it
+        todo_list.todos[2].url = Some("javascript://%0Aalert(document.cookie)".to_string());
+
+        let page = render(&todo_list.todos, "Team tasks", now, &DateFormat::default());
+        assert!(page.starts_with("<!DOCTYPE html>"));
+        assert!(page.contains("<title>Team tasks</title>"));
+        assert!(page.contains("Fix &lt;script&gt; &amp; &quot;quotes&quot;"));
+        assert!(!page.contains("<script> &"));
+        assert!(page.contains("<a href=\"https://example.com/?a=1&amp;b=2\">Read docs</a>"));
+        assert_eq!(page.matches("class=\"overdue\"").count(), 1);
+        assert!(!page.contains("href=\"javascript"));
+        assert!(page.contains("click me javascript://%0Aalert(document.cookie)"));
+        assert_eq!(page.matches("<tr><td>").count(), 3);
+    }
+}
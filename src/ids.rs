@@ -0,0 +1,219 @@
+//! Short hashes that identify todo items, for commands to accept instead of their IDs.
+//!
+//! An ID is the position of a todo item in the list, so removing one renumbers those after
+//! it. Each item also gets a hash when it is added, which no change to the item or the list
+//! alters, and commands take any prefix of it that only one item's hash starts with, as git
+//! does with commit hashes. Hashes start with a letter, so a number is always an ID.
+//!
+//! Anything else is the start of a task's title, or words anywhere in it, e.g.
+//! `done "pay rent"`, ignoring case and accents. The one task whose whole title it is wins,
+//! then the one whose title starts with it, then the one containing it.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Todo;
+use crate::{fold, hash};
+
+/// The number of hex digits of a stored hash.
+const LENGTH: usize = 16;
+/// The fewest hex digits a hash is shown with.
+const MIN_ABBREV: usize = 4;
+
+/// How a command refers to a todo item: by its ID, a prefix of its hash or part of its
+/// title.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskRef {
+    Id(u64),
+    Hash(String),
+    Title(String),
+}
+
+impl FromStr for TaskRef {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        if let Ok(id) = input.parse() {
+            return Ok(TaskRef::Id(id));
+        }
+        let prefix = input.to_lowercase();
+        match prefix.chars().next() {
+            Some('a'..='f') if prefix.chars().all(|c| c.is_ascii_hexdigit()) => {
+                Ok(TaskRef::Hash(prefix))
+            }
+            _ if !input.trim().is_empty() => Ok(TaskRef::Title(input.trim().to_string())),
+            _ => Err(format!("'{}' is not a task ID, hash or title", input)),
+        }
+    }
+}
+
+impl fmt::Display for TaskRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TaskRef::Id(id) => write!(f, "{}", id),
+            TaskRef::Hash(prefix) => write!(f, "{}", prefix),
+            TaskRef::Title(title) => write!(f, "\"{}\"", title),
+        }
+    }
+}
+
+/// Makes a hash from `seed` that none of `todos` has, trying again with a counter until it
+/// starts with a letter.
+pub fn generate(todos: &[Todo], seed: &str) -> String {
+    let taken: HashSet<String> = todos.iter().map(|todo| todo.hash.clone()).collect();
+    generate_unused(&taken, seed)
+}
+
+/// Makes a hash from `seed` that is not among `taken`, as `generate` does, for making many
+/// hashes without going through the whole list for each.
+pub fn generate_unused(taken: &HashSet<String>, seed: &str) -> String {
+    (0..)
+        .map(|attempt| hash::sha256_hex(format!("{}:{}", seed, attempt).as_bytes()))
+        .map(|digest| digest[..LENGTH].to_string())
+        .find(|hash| hash.starts_with(|c: char| c.is_ascii_alphabetic()) && !taken.contains(hash))
+        .expect("Some attempt makes a new hash")
+}
+
+/// Returns how many hex digits the hashes of `todos` are shown with: the fewest that tell
+/// them all apart, and at least `MIN_ABBREV`.
+pub fn abbrev_len<'a>(todos: impl IntoIterator<Item = &'a Todo>) -> usize {
+    let mut hashes: Vec<&str> = todos.into_iter().map(|todo| todo.hash.as_str()).collect();
+    hashes.sort_unstable();
+    let shared = hashes
+        .windows(2)
+        .map(|pair| {
+            pair[0]
+                .chars()
+                .zip(pair[1].chars())
+                .take_while(|(a, b)| a == b)
+                .count()
+        })
+        .max()
+        .unwrap_or_default();
+    (shared + 1).clamp(MIN_ABBREV, LENGTH)
+}
+
+/// Returns the first `len` hex digits of the hash of `todo`.
+pub fn abbrev(todo: &Todo, len: usize) -> &str {
+    &todo.hash[..len.min(todo.hash.len())]
+}
+
+/// Returns the ID of the one todo item among `todos` whose hash starts with `prefix`.
+pub fn find(todos: &[Todo], prefix: &str) -> Result<u64, String> {
+    let matching: Vec<&Todo> = todos
+        .iter()
+        .filter(|todo| todo.hash.starts_with(prefix))
+        .collect();
+    match matching.as_slice() {
+        [] => Err(format!("No task has a hash starting with {}", prefix)),
+        [todo] => Ok(todo.id),
+        _ => {
+            let len = abbrev_len(matching.iter().copied());
+            let candidates: Vec<String> = matching
+                .iter()
+                .map(|todo| format!("{} ({})", abbrev(todo, len), todo.task))
+                .collect();
+            Err(format!(
+                "Hash {} is ambiguous: {}",
+                prefix,
+                candidates.join(", ")
+            ))
+        }
+    }
+}
+
+/// Returns the todo items among `todos` whose title is `title`, or else those whose title
+/// starts with it, or else those containing it, ignoring case and accents.
+pub fn matching_titles<'a>(todos: &'a [Todo], title: &str) -> Vec<&'a Todo> {
+    let title = fold::key(title, true);
+    let keys: Vec<(&Todo, String)> = todos
+        .iter()
+        .map(|todo| (todo, fold::key(&todo.task, true)))
+        .collect();
+    let matching = |test: &dyn Fn(&str) -> bool| -> Vec<&'a Todo> {
+        keys.iter()
+            .filter(|(_, key)| test(key))
+            .map(|(todo, _)| *todo)
+            .collect()
+    };
+    [
+        matching(&|key| key == title),
+        matching(&|key| key.starts_with(&title)),
+        matching(&|key| key.contains(&title)),
+    ]
+    .into_iter()
+    .find(|matching| !matching.is_empty())
+    .unwrap_or_default()
+}
+
+/// Returns the ID of the one todo item among `todos` that `title` picks out, as
+/// `matching_titles` matches them, or an error listing the candidates.
+pub fn find_title(todos: &[Todo], title: &str) -> Result<u64, String> {
+    match matching_titles(todos, title).as_slice() {
+        [] => Err(format!("No task's title contains \"{}\"", title)),
+        [todo] => Ok(todo.id),
+        matching => {
+            let candidates: Vec<String> = matching
+                .iter()
+                .map(|todo| format!("{} ({})", todo.id, todo.task))
+                .collect();
+            Err(format!(
+                "\"{}\" matches {} tasks: {}. Give more of the title, or an ID",
+                title,
+                matching.len(),
+                candidates.join(", ")
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{abbrev_len, find, find_title, generate, TaskRef};
+    use crate::TodoList;
+
+    #[test]
+    fn test_task_ref() {
+        assert_eq!("12".parse(), Ok(TaskRef::Id(12)));
+        assert_eq!("A3f".parse(), Ok(TaskRef::Hash("a3f".to_string())));
+        assert_eq!("buy".parse(), Ok(TaskRef::Title("buy".to_string())));
+        assert_eq!("3af".parse(), Ok(TaskRef::Title("3af".to_string())));
+        assert!("".parse::<TaskRef>().is_err());
+        assert!(" ".parse::<TaskRef>().is_err());
+
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Buy milk", 2);
+        todo_list.add_todo("Call mum", 2);
+        todo_list.add_todo("Pay rent", 2);
+        todo_list.todos[0].hash = "a3f91c0d2e4b5a67".to_string();
+        todo_list.todos[1].hash = "a3f02bb1c9d8e7f6".to_string();
+        todo_list.todos[2].hash = "c07e55aa01b2c3d4".to_string();
+        assert_eq!(abbrev_len(&todo_list.todos), 4);
+        assert_eq!(find(&todo_list.todos, "c"), Ok(3));
+        assert_eq!(find(&todo_list.todos, "a3f9"), Ok(1));
+        assert_eq!(
+            find(&todo_list.todos, "a3f"),
+            Err("Hash a3f is ambiguous: a3f9 (Buy milk), a3f0 (Call mum)".to_string())
+        );
+        assert!(find(&todo_list.todos, "d").is_err());
+
+        todo_list.add_todo("Pay rent deposit", 2);
+        todo_list.add_todo("Réserver la salle", 2);
+        assert_eq!(find_title(&todo_list.todos, "call"), Ok(2));
+        assert_eq!(find_title(&todo_list.todos, "MILK"), Ok(1));
+        assert_eq!(find_title(&todo_list.todos, "reserver"), Ok(5));
+        // The whole title wins over a longer one starting with it
+        assert_eq!(find_title(&todo_list.todos, "pay rent"), Ok(3));
+        assert_eq!(
+            find_title(&todo_list.todos, "pay"),
+            Err("\"pay\" matches 2 tasks: 3 (Pay rent), 4 (Pay rent deposit). Give more of the title, or an ID".to_string())
+        );
+        assert!(find_title(&todo_list.todos, "taxes").is_err());
+
+        let hash = generate(&todo_list.todos, "seed");
+        assert_eq!(hash.len(), 16);
+        assert!(hash.starts_with(|c: char| c.is_ascii_alphabetic()));
+        assert_ne!(generate(&todo_list.todos, "other seed"), hash);
+    }
+}
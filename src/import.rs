@@ -0,0 +1,615 @@
+//! Merging the todo items of another list into the todo list, for `import`.
+//!
+//! An imported item is the same task as an existing one if it has the same hash, or was
+//! created at the same time, which is how `diff` tells tasks apart across renumbering, or
+//! failing that if its text is the same ignoring case, punctuation and spacing. Items
+//! matching a task that differs are conflicts, resolved by the `--on-conflict` strategy; the
+//! rest are added.
+//!
+//! The items can also come from a CSV file, such as a spreadsheet, with a header row naming
+//! its columns and a mapping from those to the fields of a todo item, or from the events and
+//! to-dos of an iCalendar file (see `calendar`) or the notes of a Google Keep export (see
+//! `keep`).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::csv::{self, Dialect};
+use crate::dates::{self, Zone};
+use crate::diff;
+use crate::formats;
+use crate::{is_url, now, priorities, Todo, TodoList};
+
+/// What `import` does with an imported item that matches a different existing task.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Keep the existing task.
+    Skip,
+    /// Replace the existing task with the imported one, keeping its ID.
+    Overwrite,
+    /// Add the imported item as a new task next to the existing one.
+    Duplicate,
+    /// Ask which of the others to do for each conflict.
+    Prompt,
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = String;
+    fn from_str(strategy: &str) -> Result<Strategy, String> {
+        match strategy {
+            "skip" => Ok(Strategy::Skip),
+            "overwrite" => Ok(Strategy::Overwrite),
+            "duplicate" => Ok(Strategy::Duplicate),
+            "prompt" => Ok(Strategy::Prompt),
+            _ => Err(format!("Unknown strategy: {}", strategy)),
+        }
+    }
+}
+
+/// The format of the file `import` reads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// A todo list file, or one JSON object per line.
+    Json,
+    /// Comma-separated values with a header row.
+    Csv,
+    /// An iCalendar (`.ics`) file.
+    Ics,
+    /// A Google Keep note, or a folder of them from a Takeout.
+    Keep,
+}
+
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(format: &str) -> Result<Format, String> {
+        match format {
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "ics" => Ok(Format::Ics),
+            "keep" => Ok(Format::Keep),
+            _ => Err(format!("Unknown import format: {}", format)),
+        }
+    }
+}
+
+/// A field of a todo item that a CSV column can fill.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Task,
+    Priority,
+    Due,
+    Tags,
+    Project,
+    Assignee,
+    Location,
+    Url,
+    Estimate,
+}
+
+impl FromStr for Field {
+    type Err = String;
+    fn from_str(field: &str) -> Result<Field, String> {
+        match field.trim().to_lowercase().as_str() {
+            "task" => Ok(Field::Task),
+            "priority" => Ok(Field::Priority),
+            "due" => Ok(Field::Due),
+            "tags" | "tag" => Ok(Field::Tags),
+            "project" => Ok(Field::Project),
+            "assignee" => Ok(Field::Assignee),
+            "location" => Ok(Field::Location),
+            "url" => Ok(Field::Url),
+            "estimate" => Ok(Field::Estimate),
+            _ => Err(format!(
+                "Unknown field: '{}'. Expected task, priority, due, tags, project, assignee, \
+                 location, url or estimate",
+                field.trim()
+            )),
+        }
+    }
+}
+
+/// Which CSV column fills which field, e.g. `Title=task,Prio=priority,Deadline=due`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping(Vec<(String, Field)>);
+
+impl FromStr for Mapping {
+    type Err = String;
+    fn from_str(mapping: &str) -> Result<Mapping, String> {
+        let mut columns = Vec::new();
+        for pair in mapping.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let (column, name) = pair
+                .rsplit_once('=')
+                .ok_or_else(|| format!("Expected <column>=<field>, not '{}'", pair.trim()))?;
+            let field: Field = name.parse()?;
+            if field != Field::Tags && columns.iter().any(|(_, other)| *other == field) {
+                return Err(format!("More than one column is mapped to {}", name.trim()));
+            }
+            columns.push((column.trim().to_string(), field));
+        }
+        Ok(Mapping(columns))
+    }
+}
+
+/// How `import --format csv` reads a file.
+#[derive(Debug, Default)]
+pub struct CsvOptions {
+    pub dialect: Dialect,
+    /// The columns filling the fields, or `None` for those named after a field.
+    pub mapping: Option<Mapping>,
+}
+
+/// A row of a CSV file that was left out, and why.
+#[derive(Debug, PartialEq)]
+pub struct RowError {
+    pub line: usize,
+    pub error: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.error)
+    }
+}
+
+/// Reads todo items from the contents of a CSV file, by the columns `options` maps to their
+/// fields, with due dates in the timezone `zone` relative to the timestamp `now`. Rows
+/// without a priority get `default_priority`.
+///
+/// Returns the items of the rows that could be read, and why each of the others could not.
+/// The whole file is rejected if it can't be split into rows or its header lacks a mapped
+/// column.
+pub fn read_csv(
+    contents: &str,
+    options: &CsvOptions,
+    zone: Zone,
+    default_priority: u64,
+    now: i64,
+) -> Result<(Vec<Todo>, Vec<RowError>), String> {
+    let mut records = csv::parse(contents, options.dialect)?.into_iter();
+    let header = records.next().ok_or("The CSV file is empty")?;
+    let column = |name: &str| {
+        header
+            .fields
+            .iter()
+            .position(|column| column.trim().eq_ignore_ascii_case(name))
+    };
+    let columns: Vec<(usize, Field)> = match &options.mapping {
+        Some(Mapping(mapping)) => mapping
+            .iter()
+            .map(|(name, field)| {
+                let index = column(name).ok_or_else(|| {
+                    format!(
+                        "No column named '{}'. The columns are: {}",
+                        name,
+                        header.fields.join(", ")
+                    )
+                })?;
+                Ok((index, *field))
+            })
+            .collect::<Result<_, String>>()?,
+        None => (0..header.fields.len())
+            .filter_map(|index| Some((index, header.fields[index].parse().ok()?)))
+            .collect(),
+    };
+    if !columns.iter().any(|(_, field)| *field == Field::Task) {
+        return Err(
+            "No column holds the task. Map one with --map, e.g. --map \"Title=task\"".to_string(),
+        );
+    }
+    let mut todos = TodoList::new();
+    let mut errors = Vec::new();
+    for record in records {
+        if record.fields.len() > header.fields.len() {
+            errors.push(RowError {
+                line: record.line,
+                error: format!(
+                    "{} fields, but the header has {}",
+                    record.fields.len(),
+                    header.fields.len()
+                ),
+            });
+            continue;
+        }
+        let values: Vec<(&str, Field)> = columns
+            .iter()
+            .filter_map(|(index, field)| Some((record.fields.get(*index)?.trim(), *field)))
+            .filter(|(value, _)| !value.is_empty())
+            .collect();
+        if let Err(error) = read_row(&mut todos, &values, zone, default_priority, now) {
+            errors.push(RowError {
+                line: record.line,
+                error,
+            });
+        }
+    }
+    Ok((todos.todos, errors))
+}
+
+/// Adds the todo item of a CSV row to `todos` from the non-empty `values` of its mapped
+/// columns.
+fn read_row(
+    todos: &mut TodoList,
+    values: &[(&str, Field)],
+    zone: Zone,
+    default_priority: u64,
+    now: i64,
+) -> Result<(), String> {
+    let value = |field: Field| {
+        values
+            .iter()
+            .find(|(_, other)| *other == field)
+            .map(|(value, _)| *value)
+    };
+    let task = value(Field::Task).ok_or("No task")?;
+    let priority = match value(Field::Priority) {
+        Some(priority) => priorities::scale().parse(priority)?,
+        None => default_priority,
+    };
+    let due = value(Field::Due)
+        .map(|due| dates::parse_due(due, now, zone))
+        .transpose()?;
+    let estimate = value(Field::Estimate)
+        .map(dates::parse_duration)
+        .transpose()?;
+    if let Some(url) = value(Field::Url).filter(|url| !is_url(url)) {
+        return Err(format!("Invalid URL: {}", url));
+    }
+    let tags: Vec<String> = values
+        .iter()
+        .filter(|(_, field)| *field == Field::Tags)
+        .flat_map(|(tags, _)| tags.split(|c: char| c == ',' || c == ';' || c.is_whitespace()))
+        .map(|tag| tag.trim_start_matches('+'))
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect();
+    todos
+        .add_todo(task, priority)
+        .expect("The priority was checked");
+    let todo = todos.todos.last_mut().expect("The todo was just added");
+    todo.due = due;
+    todo.estimate = estimate;
+    todo.tags = tags;
+    todo.project = value(Field::Project).map(str::to_string);
+    todo.assignee = value(Field::Assignee).map(str::to_string);
+    todo.location = value(Field::Location).map(str::to_string);
+    todo.url = value(Field::Url).map(str::to_string);
+    Ok(())
+}
+
+/// What an import did with each imported item.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    /// Items that matched no task and were added.
+    pub added: usize,
+    /// Conflicting items that replaced the existing task.
+    pub overwritten: usize,
+    /// Conflicting items that were left out.
+    pub skipped: usize,
+    /// Conflicting items that were added as new tasks.
+    pub duplicated: usize,
+    /// Items identical to the task they matched.
+    pub unchanged: usize,
+}
+
+impl Summary {
+    /// Returns whether the import changed the list.
+    pub fn changed(&self) -> bool {
+        self.added + self.overwritten + self.duplicated > 0
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} overwritten, {} skipped, {} duplicated, {} unchanged",
+            self.added, self.overwritten, self.skipped, self.duplicated, self.unchanged
+        )
+    }
+}
+
+/// Reads the todo items of a todo list file, or of one JSON object per line as written by
+/// `list --format ndjson`.
+pub fn read(path: &Path) -> Result<Vec<Todo>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+    let format = formats::Format::of(path);
+    match TodoList::parse(&contents, format) {
+        Ok(todo_list) => return Ok(todo_list.todos),
+        Err(error) if format != formats::Format::Json => {
+            return Err(format!("Unable to parse {}: {}", path.display(), error))
+        }
+        Err(_) => {}
+    }
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|error| {
+                format!(
+                    "Unable to parse {}, line {}: {}",
+                    path.display(),
+                    index + 1,
+                    error
+                )
+            })
+        })
+        .collect()
+}
+
+/// Returns the text of a task as it is compared: lowercase words of letters and digits,
+/// separated by single spaces.
+fn normalize(task: &str) -> String {
+    task.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns the index of the existing task in `todos` that `todo` is, among those not
+/// `taken` by an earlier imported item.
+fn find_match(todos: &[Todo], taken: &[bool], todo: &Todo) -> Option<usize> {
+    let text = normalize(&todo.task);
+    let free = || (0..taken.len()).filter(|index| !taken[*index]);
+    if let Some(index) =
+        free().find(|index| !todo.hash.is_empty() && todos[*index].hash == todo.hash)
+    {
+        return Some(index);
+    }
+    let same_time: Vec<usize> = free()
+        .filter(|index| todos[*index].created == todo.created)
+        .collect();
+    same_time
+        .iter()
+        .copied()
+        .find(|index| normalize(&todos[*index].task) == text)
+        .or_else(|| match same_time[..] {
+            [index] => Some(index),
+            _ => None,
+        })
+        .or_else(|| free().find(|index| normalize(&todos[*index].task) == text))
+}
+
+/// Lists the fields in which `todo` differs from `existing`, ignoring its ID, creation time
+/// and revision, which only say where each copy has been.
+fn conflicting_fields(existing: &Todo, todo: &Todo) -> Vec<String> {
+    let value = |todo: &Todo| serde_json::to_value(todo).expect("Unable to serialize todo");
+    diff::changed_fields(&value(existing), &value(todo))
+        .into_iter()
+        .filter(|field| !["created", "modified", "rev"].contains(&field.as_str()))
+        .collect()
+}
+
+/// Merges `imported` into `todo_list`, resolving each conflict by `strategy`, or by `ask`
+/// with the existing task, the imported item and the fields they differ in when the
+/// strategy is `Prompt`. Dependencies between imported items follow them to their IDs in
+/// the list.
+pub fn merge(
+    todo_list: &mut TodoList,
+    imported: Vec<Todo>,
+    strategy: Strategy,
+    ask: &mut dyn FnMut(&Todo, &Todo, &[String]) -> Strategy,
+) -> Summary {
+    let mut summary = Summary::default();
+    let mut taken = vec![false; todo_list.todos.len()];
+    // The IDs the imported items ended up at, and the items whose dependencies use the
+    // imported IDs
+    let mut ids = HashMap::new();
+    let mut merged = Vec::new();
+    for mut todo in imported {
+        let conflict = match find_match(&todo_list.todos, &taken, &todo) {
+            Some(index) => {
+                taken[index] = true;
+                let existing = &todo_list.todos[index];
+                let fields = conflicting_fields(existing, &todo);
+                if fields.is_empty() {
+                    summary.unchanged += 1;
+                    ids.insert(todo.id, existing.id);
+                    continue;
+                }
+                match strategy {
+                    Strategy::Prompt => Some((index, ask(existing, &todo, &fields))),
+                    strategy => Some((index, strategy)),
+                }
+            }
+            None => None,
+        };
+        match conflict {
+            Some((index, Strategy::Overwrite)) => {
+                let existing = &todo_list.todos[index];
+                ids.insert(todo.id, existing.id);
+                todo.id = existing.id;
+                todo.hash = existing.hash.clone();
+                todo.rev = existing.rev;
+                todo.touch(now());
+                todo_list.todos[index] = todo;
+                merged.push(index);
+                summary.overwritten += 1;
+                continue;
+            }
+            Some((_, Strategy::Duplicate)) => summary.duplicated += 1,
+            Some((index, _)) => {
+                ids.insert(todo.id, todo_list.todos[index].id);
+                summary.skipped += 1;
+                continue;
+            }
+            None => summary.added += 1,
+        }
+        let id = todo_list.todos.len() as u64 + 1;
+        ids.insert(todo.id, id);
+        todo.id = id;
+        merged.push(todo_list.todos.len());
+        todo_list.todos.push(todo);
+    }
+    for index in merged {
+        let todo = &mut todo_list.todos[index];
+        todo.depends = todo
+            .depends
+            .iter()
+            .filter_map(|other| ids.get(other).copied())
+            .filter(|other| *other != todo.id)
+            .collect();
+        todo.depends.sort_unstable();
+        todo.depends.dedup();
+    }
+    // A duplicated item, or one from a list saved before hashes, gets a hash of its own
+    todo_list.assign_hashes();
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge, normalize, read_csv, CsvOptions, Mapping, Strategy, Summary};
+    use crate::csv::Dialect;
+    use crate::dates::Zone;
+    use crate::TodoList;
+
+    /// Returns a list, and one to import into it with a changed copy of its first task, a
+    /// copy of its second added separately, and a new task depending on both.
+    fn lists() -> (TodoList, TodoList) {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Pay rent", 5);
+        todo_list.add_todo("Read docs", 2);
+        todo_list.todos[1].created -= 10;
+        let mut other = TodoList::new();
+        other.add_todo("Pay the rent", 5);
+        other.todos[0].created = todo_list.todos[0].created;
+        other.add_todo("Read docs", 2);
+        other.todos[1].created -= 20;
+        other.add_todo("Water plants", 3);
+        other.todos[2].created -= 30;
+        other.todos[2].depends = vec![1, 2];
+        (todo_list, other)
+    }
+
+    #[test]
+    fn test_merge() {
+        assert_eq!(normalize("  Read, the DOCS! "), "read the docs");
+
+        let (mut todo_list, other) = lists();
+        let summary = merge(
+            &mut todo_list,
+            other.todos,
+            Strategy::Skip,
+            &mut |_, _, _| unreachable!(),
+        );
+        assert_eq!(
+            summary,
+            Summary {
+                added: 1,
+                skipped: 1,
+                unchanged: 1,
+                ..Summary::default()
+            }
+        );
+        let tasks: Vec<&str> = todo_list
+            .todos
+            .iter()
+            .map(|todo| todo.task.as_str())
+            .collect();
+        assert_eq!(tasks, ["Pay rent", "Read docs", "Water plants"]);
+        assert_eq!(todo_list.todos[2].depends, [1, 2]);
+
+        let (mut todo_list, other) = lists();
+        let summary = merge(
+            &mut todo_list,
+            other.todos,
+            Strategy::Overwrite,
+            &mut |_, _, _| unreachable!(),
+        );
+        assert_eq!((summary.overwritten, summary.added), (1, 1));
+        assert_eq!(todo_list.todos[0].task, "Pay the rent");
+        assert_eq!(todo_list.todos[0].rev, 1);
+
+        let (mut todo_list, other) = lists();
+        let mut asked = Vec::new();
+        let summary = merge(
+            &mut todo_list,
+            other.todos,
+            Strategy::Prompt,
+            &mut |existing, _, fields| {
+                asked.push((existing.id, fields.to_vec()));
+                Strategy::Duplicate
+            },
+        );
+        assert_eq!(asked, [(1, vec!["task".to_string()])]);
+        assert_eq!((summary.duplicated, summary.added), (1, 1));
+        assert_eq!(todo_list.todos.len(), 4);
+        assert_eq!(todo_list.todos[2].task, "Pay the rent");
+        // Water plants depended on imported item 1, now the duplicate at 3
+        assert_eq!(todo_list.todos[3].depends, [2, 3]);
+    }
+
+    #[test]
+    fn test_read_csv() {
+        // 2024-06-01 12:00
+        let now = 1717243200;
+        let csv = "Title;Prio;Deadline;Labels;Owner\n\
+                   Buy milk;2;2024-06-03;home +errand;\n\
+                   'Call Bob; then Ann';;;;ann\n\
+                   Fix roof;high;;;\n\
+                   ;3;;;\n\
+                   Pay rent;5;someday;;\n";
+        let options = CsvOptions {
+            dialect: Dialect {
+                delimiter: ';',
+                quote: '\'',
+            },
+            mapping: Some(
+                "Title=task, prio=priority,Deadline=due,Labels=tags,Owner=assignee"
+                    .parse()
+                    .unwrap(),
+            ),
+        };
+        let (todos, errors) = read_csv(csv, &options, Zone::Utc, 3, now).unwrap();
+        let tasks: Vec<(&str, u64)> = todos
+            .iter()
+            .map(|todo| (todo.task.as_str(), todo.priority))
+            .collect();
+        assert_eq!(tasks, [("Buy milk", 2), ("Call Bob; then Ann", 3)]);
+        assert_eq!(todos[0].due, Some(now + 36 * 60 * 60));
+        assert_eq!(todos[0].tags, ["home", "errand"]);
+        assert_eq!(todos[1].assignee.as_deref(), Some("ann"));
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("Line 4: Invalid priority: 'high'"));
+        assert_eq!(errors[1], "Line 5: No task");
+        assert!(errors[2].starts_with("Line 6: "));
+
+        // Columns named after fields need no mapping
+        let options = CsvOptions::default();
+        let (todos, _) = read_csv(
+            "task,Priority\nWater plants,1\n",
+            &options,
+            Zone::Utc,
+            3,
+            now,
+        )
+        .unwrap();
+        assert_eq!(
+            (todos[0].task.as_str(), todos[0].priority),
+            ("Water plants", 1)
+        );
+        assert!(
+            read_csv("Title\nWater plants\n", &options, Zone::Utc, 3, now)
+                .unwrap_err()
+                .contains("--map")
+        );
+        let options = CsvOptions {
+            mapping: Some("Name=task".parse().unwrap()),
+            ..CsvOptions::default()
+        };
+        assert!(
+            read_csv("Title\nWater plants\n", &options, Zone::Utc, 3, now)
+                .unwrap_err()
+                .contains("No column named 'Name'")
+        );
+        assert!("Title=task,Name=task".parse::<Mapping>().is_err());
+        assert!("Title=colour".parse::<Mapping>().is_err());
+        assert!("Title".parse::<Mapping>().is_err());
+    }
+}
@@ -0,0 +1,248 @@
+//! `POST /inbox`: todo items made from what other services send `serve`, such as an IFTTT
+//! applet, a GitHub webhook or a form backend, so the list becomes an inbox for all of them.
+//!
+//! A service's payload is turned into a todo item by a mapping in the `inbox` setting: a
+//! template for each field of the item, in which `{/pointer}` stands for the value at that
+//! JSON pointer in the payload, e.g. `"{/issue/title} ({/repository/name})"`. Each service
+//! posts to `/inbox/<name>` to use the mapping of that name; `/inbox` uses the one named
+//! `default`, or takes each field from the member of the payload with its name. A form is
+//! read as an object of its fields.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::dates::{self, Zone};
+use crate::remote::parse_parameters;
+use crate::{is_url, priorities, TodoList};
+
+/// How to make a todo item from a payload: a template for each of its fields. A field left
+/// out, or whose template comes out empty, is not set.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mapping {
+    pub task: String,
+    /// A priority, its number or its name; the configured default priority when empty.
+    pub priority: String,
+    /// A due date as `add --due` takes it, e.g. `2024-06-01` or `fri 9am`.
+    pub due: String,
+    /// Each is split into tags at spaces and commas.
+    pub tags: Vec<String>,
+    pub project: String,
+    pub assignee: String,
+    pub location: String,
+    pub url: String,
+}
+
+impl Mapping {
+    /// Takes each field from the member of the payload with its name, e.g. from
+    /// `{ "task": "Call Sam", "priority": 4, "tags": ["phone"] }`.
+    fn by_name() -> Mapping {
+        Mapping {
+            task: "{/task}".to_string(),
+            priority: "{/priority}".to_string(),
+            due: "{/due}".to_string(),
+            tags: vec!["{/tags}".to_string()],
+            project: "{/project}".to_string(),
+            assignee: "{/assignee}".to_string(),
+            location: "{/location}".to_string(),
+            url: "{/url}".to_string(),
+        }
+    }
+}
+
+/// What `serve` needs to add todo items from payloads.
+#[derive(Debug, Default, Clone)]
+pub struct Inbox {
+    /// The mappings from the `inbox` setting, by name.
+    pub mappings: BTreeMap<String, Mapping>,
+    pub default_priority: u64,
+    /// The timezone due dates are given in.
+    pub zone: Zone,
+}
+
+impl Inbox {
+    /// Returns the mapping called `name`, or the one `/inbox` uses without a name.
+    pub fn mapping(&self, name: Option<&str>) -> Option<Mapping> {
+        match name {
+            Some(name) => self.mappings.get(name).cloned(),
+            None => Some(
+                self.mappings
+                    .get("default")
+                    .cloned()
+                    .unwrap_or_else(Mapping::by_name),
+            ),
+        }
+    }
+
+    /// Adds the todo item `mapping` makes from `payload` at the timestamp `now` to `list`,
+    /// returning its ID. Nothing is added if a field is invalid.
+    pub fn add(
+        &self,
+        list: &mut TodoList,
+        mapping: &Mapping,
+        payload: &Value,
+        now: i64,
+    ) -> Result<u64, String> {
+        let field = |template: &str| render(template, payload).trim().to_string();
+        let task = field(&mapping.task);
+        if task.is_empty() {
+            return Err("The payload has no task".to_string());
+        }
+        let priority = match field(&mapping.priority).as_str() {
+            "" => self.default_priority,
+            priority => priorities::scale().parse(priority)?,
+        };
+        let due = match field(&mapping.due).as_str() {
+            "" => None,
+            due => Some(dates::parse_due(due, now, self.zone)?),
+        };
+        let url = field(&mapping.url);
+        if !url.is_empty() && !is_url(&url) {
+            return Err(format!("Invalid URL: '{}'", url));
+        }
+        let mut tags: Vec<String> = Vec::new();
+        for template in &mapping.tags {
+            for tag in render(template, payload).split([' ', ',']) {
+                if !tag.is_empty() && !tags.iter().any(|known| known == tag) {
+                    tags.push(tag.to_string());
+                }
+            }
+        }
+        let id = list
+            .add_todo(&task, priority)
+            .ok_or_else(|| "Unable to add the task".to_string())?;
+        if let Some(due) = due {
+            list.set_due(id, due);
+        }
+        list.add_tags(id, &tags);
+        if !url.is_empty() {
+            list.set_url(id, &url);
+        }
+        for (template, set) in [
+            (
+                &mapping.project,
+                TodoList::set_project as fn(&mut TodoList, u64, &str),
+            ),
+            (&mapping.assignee, TodoList::delegate_todo),
+            (&mapping.location, TodoList::set_location),
+        ] {
+            let value = field(template);
+            if !value.is_empty() {
+                set(list, id, &value);
+            }
+        }
+        Ok(id)
+    }
+}
+
+/// Replaces each `{/pointer}` in `template` with the value at that JSON pointer in
+/// `payload`: a string as it is, an array as its elements separated by spaces, and nothing
+/// when there is no value or it is null.
+pub fn render(template: &str, payload: &Value) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{/") {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        rendered.push_str(&rest[..start]);
+        if let Some(value) = payload.pointer(&rest[start + 1..end]) {
+            rendered.push_str(&text(value));
+        }
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Returns `value` as text in a todo item.
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Array(values) => values.iter().map(text).collect::<Vec<_>>().join(" "),
+        value => value.to_string(),
+    }
+}
+
+/// Reads a payload: JSON, or with `form`, the fields of a form, `a=1&b=2`, as an object of
+/// strings.
+pub fn parse_payload(body: &str, form: bool) -> Result<Value, String> {
+    if form {
+        let fields: Map<String, Value> = parse_parameters(body.trim())?
+            .into_iter()
+            .map(|(name, value)| (name, Value::String(value)))
+            .collect();
+        return Ok(Value::Object(fields));
+    }
+    serde_json::from_str(body).map_err(|error| format!("Invalid payload: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_payload, render, Inbox, Mapping};
+    use crate::TodoList;
+    use serde_json::json;
+
+    #[test]
+    fn test_render() {
+        let payload =
+            json!({ "issue": { "title": "Crash", "labels": ["bug", "ui"], "number": 7 } });
+        assert_eq!(
+            render("#{/issue/number}: {/issue/title}", &payload),
+            "#7: Crash"
+        );
+        assert_eq!(render("{/issue/labels}", &payload), "bug ui");
+        assert_eq!(render("{/issue/body}!", &payload), "!");
+        assert_eq!(render("{not a pointer}", &payload), "{not a pointer}");
+    }
+
+    #[test]
+    fn test_add() {
+        let github = Mapping {
+            task: "Fix #{/issue/number}: {/issue/title}".to_string(),
+            tags: vec!["github".to_string(), "{/issue/labels}".to_string()],
+            project: "{/repository/name}".to_string(),
+            url: "{/issue/html_url}".to_string(),
+            ..Mapping::default()
+        };
+        let inbox = Inbox {
+            mappings: [("github".to_string(), github)].into(),
+            default_priority: 3,
+            ..Inbox::default()
+        };
+        let mut list = TodoList::new();
+        let payload = json!({
+            "issue": {
+                "number": 7,
+                "title": "Crash on start",
+                "labels": ["bug"],
+                "html_url": "https://github.com/acme/app/issues/7",
+            },
+            "repository": { "name": "app" },
+        });
+        let mapping = inbox.mapping(Some("github")).unwrap();
+        let id = inbox.add(&mut list, &mapping, &payload, 0).unwrap();
+        let todo = list.todo(id).unwrap();
+        assert_eq!(todo.task, "Fix #7: Crash on start");
+        assert_eq!(todo.priority, 3);
+        assert_eq!(todo.tags, ["github", "bug"]);
+        assert_eq!(todo.project.as_deref(), Some("app"));
+        assert!(todo.url.is_some());
+        assert!(inbox.mapping(Some("jira")).is_none());
+
+        let form = parse_payload("task=Call+Sam&priority=5&due=2024-06-01", true).unwrap();
+        let mapping = inbox.mapping(None).unwrap();
+        let id = inbox.add(&mut list, &mapping, &form, 0).unwrap();
+        let todo = list.todo(id).unwrap();
+        assert_eq!((todo.task.as_str(), todo.priority), ("Call Sam", 5));
+        assert!(todo.due.is_some());
+        for payload in [json!({}), json!({ "task": "Call", "priority": 9 })] {
+            assert!(inbox.add(&mut list, &mapping, &payload, 0).is_err());
+        }
+        assert_eq!(list.todos.len(), 2);
+    }
+}
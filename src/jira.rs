@@ -0,0 +1,418 @@
+//! Mirroring Jira issues as todo items, for `sync jira`.
+//!
+//! The issues a JQL search finds become tasks titled with their key, e.g. `ACME-12 Fix the
+//! login page`, tagged `jira` and linked to the issue, kept in step with them as `mirror`
+//! describes. Removing a mirrored task marks it done: the next sync moves its issue through
+//! a transition, `Done` unless configured otherwise.
+
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use crate::dates::{self, Zone};
+use crate::mirror::{self, Item, Tracker};
+use crate::remote::{Remote, Response};
+use crate::{priorities, TodoList};
+
+/// The file holding the keys mirrored by the last sync, relative to the todo list's
+/// directory.
+pub const STATE_FILE: &str = ".todo-jira";
+/// The tag of the mirrored tasks.
+pub const TAG: &str = "jira";
+/// The transition a done task's issue is moved through unless configured otherwise.
+pub const DONE_TRANSITION: &str = "Done";
+/// How many issues each search request asks for.
+const PAGE_SIZE: u64 = 100;
+
+/// An issue, with only the fields mirrored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub key: String,
+    pub summary: String,
+    /// The name of its priority, e.g. `High`.
+    pub priority: Option<String>,
+    /// Its due date, `YYYY-MM-DD`.
+    pub due: Option<String>,
+}
+
+/// A Jira site, e.g. `https://acme.atlassian.net`, and how to sign in to it.
+pub struct Server {
+    remote: Remote,
+    /// The URL as configured, without a trailing slash.
+    url: String,
+    /// The value of the `Authorization` header.
+    authorization: String,
+}
+
+impl Server {
+    /// Connects to the site at `url` as `user` with an API token, as Jira Cloud expects, or
+    /// with a personal access token alone, as Jira Server and Data Center do.
+    pub fn new(
+        url: &str,
+        user: Option<&str>,
+        token: &str,
+        ca: Option<PathBuf>,
+    ) -> Result<Server, String> {
+        let default_port = if url.starts_with("https://") { 443 } else { 80 };
+        let remote = Remote::parse_with_port(url, default_port)?.trusting(ca);
+        let authorization = match user {
+            Some(user) => format!("Basic {}", base64(format!("{}:{}", user, token).as_bytes())),
+            None => format!("Bearer {}", token),
+        };
+        Ok(Server {
+            remote,
+            url: url.trim_end_matches('/').to_string(),
+            authorization,
+        })
+    }
+
+    /// Returns what the hashes of the tasks mirroring its issues are made from.
+    pub fn source(&self) -> String {
+        format!("jira:{}", self.url)
+    }
+
+    /// Returns the web page of the issue `key`.
+    pub fn browse(&self, key: &str) -> String {
+        format!("{}/browse/{}", self.url, key)
+    }
+
+    /// Sends a request for `path` under the REST API with a JSON body, unless it is null.
+    fn call(&self, method: &str, path: &str, body: &Value) -> Result<Response, String> {
+        let body = match body {
+            Value::Null => String::new(),
+            body => body.to_string(),
+        };
+        self.remote
+            .request(
+                method,
+                &format!("/rest/api/2{}", path),
+                &[
+                    ("Authorization", &self.authorization),
+                    ("Accept", "application/json"),
+                    ("Content-Type", "application/json"),
+                ],
+                &body,
+            )
+            .map_err(|error| error.to_string())
+    }
+
+    /// Describes an unexpected response, with the messages Jira sent with it.
+    fn error(&self, response: &Response) -> String {
+        let body: Value = serde_json::from_str(&response.body).unwrap_or_default();
+        let messages: Vec<String> = body["errorMessages"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .chain(
+                body["errors"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(field, error)| format!("{}: {}", field, error)),
+            )
+            .collect();
+        match messages.is_empty() {
+            true => format!("{} answered with status {}", self.url, response.status),
+            false => format!(
+                "{} answered with status {}: {}",
+                self.url,
+                response.status,
+                messages.join("; ")
+            ),
+        }
+    }
+
+    /// Returns the issues `jql` finds.
+    pub fn search(&self, jql: &str) -> Result<Vec<Issue>, String> {
+        let fields = ["summary", "priority", "duedate"];
+        let mut issues = Vec::new();
+        // Jira Cloud pages through a search with a token, Server and Data Center by offset
+        let mut token: Option<String> = None;
+        loop {
+            let mut body = json!({ "jql": jql, "fields": fields, "maxResults": PAGE_SIZE });
+            if let Some(token) = &token {
+                body["nextPageToken"] = json!(token);
+            }
+            let response = self.call("POST", "/search/jql", &body)?;
+            if response.status == 404 && token.is_none() {
+                break;
+            }
+            let page = self.page(&response)?;
+            issues.extend(parse_issues(&page)?);
+            match page["nextPageToken"].as_str() {
+                Some(next) if page["isLast"] != json!(true) => token = Some(next.to_string()),
+                _ => return Ok(issues),
+            }
+        }
+        loop {
+            let body = json!({
+                "jql": jql,
+                "fields": fields,
+                "startAt": issues.len(),
+                "maxResults": PAGE_SIZE,
+            });
+            let page = self.page(&self.call("POST", "/search", &body)?)?;
+            let found = parse_issues(&page)?;
+            let total = page["total"].as_u64().unwrap_or_default() as usize;
+            let last = found.is_empty();
+            issues.extend(found);
+            if last || issues.len() >= total {
+                return Ok(issues);
+            }
+        }
+    }
+
+    /// Reads a page of search results.
+    fn page(&self, response: &Response) -> Result<Value, String> {
+        if response.status != 200 {
+            return Err(self.error(response));
+        }
+        serde_json::from_str(&response.body)
+            .map_err(|error| format!("{} sent an invalid response: {}", self.url, error))
+    }
+
+    /// Moves the issue `key` through the transition named `name`, or leading to the status
+    /// of that name.
+    pub fn transition(&self, key: &str, name: &str) -> Result<(), String> {
+        let path = format!("/issue/{}/transitions", key);
+        let response = self.call("GET", &path, &Value::Null)?;
+        let transitions = self.page(&response)?;
+        let transitions = transitions["transitions"].as_array().cloned();
+        let transitions = transitions.unwrap_or_default();
+        let named = |transition: &Value, name: &str| {
+            [&transition["name"], &transition["to"]["name"]]
+                .iter()
+                .any(|other| {
+                    other
+                        .as_str()
+                        .is_some_and(|other| other.eq_ignore_ascii_case(name))
+                })
+        };
+        let Some(transition) = transitions
+            .iter()
+            .find(|transition| named(transition, name))
+        else {
+            let names: Vec<&str> = transitions
+                .iter()
+                .filter_map(|transition| transition["name"].as_str())
+                .collect();
+            return Err(format!(
+                "{} has no transition to {}. Expected one of: {}",
+                key,
+                name,
+                names.join(", ")
+            ));
+        };
+        let body = json!({ "transition": { "id": transition["id"] } });
+        let response = self.call("POST", &path, &body)?;
+        match response.status {
+            200 | 204 => Ok(()),
+            _ => Err(self.error(&response)),
+        }
+    }
+}
+
+/// Reads the issues of a page of search results.
+fn parse_issues(page: &Value) -> Result<Vec<Issue>, String> {
+    let issues = page["issues"]
+        .as_array()
+        .ok_or("The search results have no issues")?;
+    issues
+        .iter()
+        .map(|issue| {
+            let key = issue["key"].as_str().ok_or("An issue has no key")?;
+            let fields = &issue["fields"];
+            let text = |value: &Value| value.as_str().map(str::to_string);
+            Ok(Issue {
+                key: key.to_string(),
+                summary: text(&fields["summary"]).unwrap_or_default(),
+                priority: text(&fields["priority"]["name"]),
+                due: text(&fields["duedate"]),
+            })
+        })
+        .collect()
+}
+
+/// Returns the priority of a todo item for the name of a Jira priority, from the default
+/// scheme or the older one, or `None` for one it doesn't know.
+pub fn priority(name: &str) -> Option<u64> {
+    match name.trim().to_lowercase().as_str() {
+        "highest" | "blocker" => Some(5),
+        "high" | "critical" => Some(4),
+        "medium" | "major" => Some(3),
+        "low" | "minor" => Some(2),
+        "lowest" | "trivial" => Some(1),
+        _ => None,
+    }
+}
+
+/// A sync with the issues a search finds.
+pub struct Sync {
+    pub server: Server,
+    pub jql: String,
+    /// The transition that marks an issue done.
+    pub transition: String,
+    pub default_priority: u64,
+    /// The timezone due dates are in.
+    pub zone: Zone,
+}
+
+impl Tracker for Sync {
+    fn name(&self) -> &str {
+        "Jira"
+    }
+
+    fn source(&self) -> String {
+        self.server.source()
+    }
+
+    fn mirrored(&self, list: &TodoList, keys: &[String]) -> Vec<(u64, String)> {
+        let titles = list
+            .todos
+            .iter()
+            .filter_map(|todo| todo.task.split_whitespace().next());
+        let keys = titles.chain(keys.iter().map(String::as_str));
+        mirror::mirrored(list, &self.server.source(), keys)
+    }
+
+    fn items(&self, now: i64) -> Result<Vec<Item>, String> {
+        let issues = self.server.search(&self.jql)?;
+        items(&self.server, &issues, self.default_priority, self.zone, now)
+    }
+
+    fn complete(&self, key: &str) -> Result<(), String> {
+        self.server.transition(key, &self.transition)
+    }
+}
+
+/// Returns what the tasks mirroring `issues` of `server` are: titled with their key,
+/// tagged `jira` and with their due dates in the timezone `zone`, relative to the
+/// timestamp `now`. Issues without a known priority get `default_priority`.
+pub fn items(
+    server: &Server,
+    issues: &[Issue],
+    default_priority: u64,
+    zone: Zone,
+    now: i64,
+) -> Result<Vec<Item>, String> {
+    issues
+        .iter()
+        .map(|issue| {
+            let due = issue
+                .due
+                .as_deref()
+                .map(|due| dates::parse_due(due, now, zone))
+                .transpose()
+                .map_err(|error| format!("{}: {}", issue.key, error))?;
+            Ok(Item {
+                key: issue.key.clone(),
+                task: format!("{} {}", issue.key, issue.summary.trim()),
+                priority: issue
+                    .priority
+                    .as_deref()
+                    .and_then(priority)
+                    .map(|priority| priorities::scale().rescale(priority))
+                    .unwrap_or(default_priority),
+                due,
+                tags: vec![TAG.to_string()],
+                project: None,
+                url: Some(server.browse(&issue.key)),
+            })
+        })
+        .collect()
+}
+
+/// Encodes `data` in base64, for HTTP basic authentication.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for index in 0..4 {
+            match index <= chunk.len() {
+                true => encoded.push(ALPHABET[(bits >> (18 - 6 * index) & 63) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base64, items, mirror, parse_issues, priority, Server, Sync, Tracker};
+    use crate::dates::Zone;
+    use crate::TodoList;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_issues() {
+        assert_eq!(base64(b"sam@acme.com:t0ken"), "c2FtQGFjbWUuY29tOnQwa2Vu");
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(priority("Highest"), Some(5));
+        assert_eq!(priority("minor"), Some(2));
+        assert_eq!(priority("P1"), None);
+
+        let page = json!({ "issues": [
+            { "key": "ACME-1", "fields": {
+                "summary": "Fix login", "priority": { "name": "High" }, "duedate": "2024-06-07"
+            } },
+            { "key": "ACME-2", "fields": { "summary": "Write docs", "priority": null } },
+        ] });
+        let issues = parse_issues(&page).unwrap();
+        assert_eq!(issues[0].priority.as_deref(), Some("High"));
+        assert_eq!(issues[0].due.as_deref(), Some("2024-06-07"));
+        assert_eq!(
+            (issues[1].priority.as_ref(), issues[1].due.as_ref()),
+            (None, None)
+        );
+        assert!(parse_issues(&json!({ "issues": [{ "fields": {} }] })).is_err());
+    }
+
+    #[test]
+    fn test_items() {
+        let server = Server::new("https://acme.atlassian.net/", None, "token", None).unwrap();
+        let page = json!({ "issues": [
+            { "key": "ACME-1", "fields": {
+                "summary": "Fix login", "priority": { "name": "High" }, "duedate": "2024-06-07"
+            } },
+            { "key": "ACME-2", "fields": { "summary": "Write docs" } },
+        ] });
+        let issues = parse_issues(&page).unwrap();
+        let items = items(&server, &issues, 3, Zone::Utc, 0).unwrap();
+        assert_eq!(
+            (items[0].task.as_str(), items[0].priority),
+            ("ACME-1 Fix login", 4)
+        );
+        assert_eq!(items[0].due, Some(1717718400));
+        assert_eq!(items[0].tags, ["jira"]);
+        assert_eq!(
+            items[0].url.as_deref(),
+            Some("https://acme.atlassian.net/browse/ACME-1")
+        );
+        assert_eq!(items[1].priority, 3);
+
+        // The tasks are found by the key they start with
+        let mut list = TodoList::new();
+        list.add_todo("ACME-1 Water the plants", 2);
+        mirror::mirror(&mut list, &server.source(), &[], &items, 0).unwrap();
+        let sync = Sync {
+            server,
+            jql: String::new(),
+            transition: "Done".to_string(),
+            default_priority: 3,
+            zone: Zone::Utc,
+        };
+        assert_eq!(
+            sync.mirrored(&list, &[]),
+            [(2, "ACME-1".to_string()), (3, "ACME-2".to_string())]
+        );
+    }
+}
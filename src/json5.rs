@@ -0,0 +1,398 @@
+//! Reading and writing JSON5 (https://spec.json5.org), for todo list files in that format.
+//!
+//! JSON5 is JSON with some of JavaScript's syntax: comments, trailing commas, unquoted keys,
+//! single-quoted strings and numbers such as `0x1F`, `.5` or `+1`. Infinity and NaN, which
+//! JSON has no numbers for, are not read.
+
+use serde_json::{Map, Number, Value};
+
+use crate::formats::signed;
+
+/// Writes out `value` indented like `serde_json::to_string_pretty`, with keys unquoted where
+/// they can be and a comma after every item, so adding an item changes one line.
+pub fn write(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(value, 0, &mut out);
+    out
+}
+
+fn write_value(value: &Value, indent: usize, out: &mut String) {
+    let pad = "  ".repeat(indent + 1);
+    match value {
+        Value::Array(items) if !items.is_empty() => {
+            out.push_str("[\n");
+            for item in items {
+                out.push_str(&pad);
+                write_value(item, indent + 1, out);
+                out.push_str(",\n");
+            }
+            out.push_str(&"  ".repeat(indent));
+            out.push(']');
+        }
+        Value::Object(map) if !map.is_empty() => {
+            out.push_str("{\n");
+            for (key, item) in map {
+                out.push_str(&pad);
+                if is_identifier(key) {
+                    out.push_str(key);
+                } else {
+                    out.push_str(&Value::String(key.clone()).to_string());
+                }
+                out.push_str(": ");
+                write_value(item, indent + 1, out);
+                out.push_str(",\n");
+            }
+            out.push_str(&"  ".repeat(indent));
+            out.push('}');
+        }
+        _ => out.push_str(&value.to_string()),
+    }
+}
+
+/// Returns whether `key` can be written without quotes.
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Reads a JSON5 document.
+pub fn parse(contents: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: contents.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    parser.skip_blank()?;
+    let value = parser.value()?;
+    parser.skip_blank()?;
+    match parser.peek() {
+        None => Ok(value),
+        Some(c) => Err(parser.error(&format!("unexpected '{}' after the value", c))),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("Line {}: {}", self.line, message)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(self.error(&format!("expected '{}', found '{}'", expected, c))),
+            None => Err(self.error(&format!("expected '{}' before the end", expected))),
+        }
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_blank(&mut self) -> Result<(), String> {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() || c == '\u{feff}' => {
+                    self.next();
+                }
+                Some('/') if self.chars.get(self.pos + 1) == Some(&'/') => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.next();
+                    }
+                }
+                Some('/') if self.chars.get(self.pos + 1) == Some(&'*') => {
+                    let line = self.line;
+                    self.pos += 2;
+                    loop {
+                        match self.next() {
+                            Some('*') if self.peek() == Some('/') => {
+                                self.next();
+                                break;
+                            }
+                            Some(_) => {}
+                            None => {
+                                return Err(format!("Line {}: the comment is not closed", line))
+                            }
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some(quote @ ('"' | '\'')) => {
+                self.next();
+                self.string(quote).map(Value::String)
+            }
+            Some(c) if c.is_ascii_digit() || matches!(c, '-' | '+' | '.') => self.number(),
+            Some(c) if c.is_alphabetic() => match self.word().as_str() {
+                "null" => Ok(Value::Null),
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                "Infinity" | "NaN" => Err(self.error("Infinity and NaN are not supported")),
+                word => Err(self.error(&format!("unexpected '{}'", word))),
+            },
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+            None => Err(self.error("expected a value before the end")),
+        }
+    }
+
+    /// Reads the letters, digits, `_` and `$` at the current position.
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self
+            .peek()
+            .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+        {
+            word.push(c);
+            self.next();
+        }
+        word
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut map = Map::new();
+        loop {
+            self.skip_blank()?;
+            let key = match self.peek() {
+                Some('}') => {
+                    self.next();
+                    return Ok(Value::Object(map));
+                }
+                Some(quote @ ('"' | '\'')) => {
+                    self.next();
+                    self.string(quote)?
+                }
+                Some(c) if c.is_alphabetic() || c == '_' || c == '$' => self.word(),
+                Some(c) => return Err(self.error(&format!("expected a key, found '{}'", c))),
+                None => return Err(self.error("expected '}' before the end")),
+            };
+            self.skip_blank()?;
+            self.expect(':')?;
+            self.skip_blank()?;
+            let value = self.value()?;
+            if map.insert(key.clone(), value).is_some() {
+                return Err(self.error(&format!("duplicate key '{}'", key)));
+            }
+            self.skip_blank()?;
+            match self.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Object(map)),
+                Some(c) => return Err(self.error(&format!("expected ',' or '}}', found '{}'", c))),
+                None => return Err(self.error("expected '}' before the end")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank()?;
+            if self.peek() == Some(']') {
+                self.next();
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_blank()?;
+            match self.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(items)),
+                Some(c) => return Err(self.error(&format!("expected ',' or ']', found '{}'", c))),
+                None => return Err(self.error("expected ']' before the end")),
+            }
+        }
+    }
+
+    /// Reads the rest of a string opened with `quote`.
+    fn string(&mut self, quote: char) -> Result<String, String> {
+        let line = self.line;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some(c) if c == quote => return Ok(string),
+                Some('\\') => match self.next() {
+                    Some('\n') | Some('\u{2028}') | Some('\u{2029}') => {}
+                    Some('\r') => {
+                        if self.peek() == Some('\n') {
+                            self.next();
+                        }
+                    }
+                    Some('b') => string.push('\u{8}'),
+                    Some('f') => string.push('\u{c}'),
+                    Some('n') => string.push('\n'),
+                    Some('r') => string.push('\r'),
+                    Some('t') => string.push('\t'),
+                    Some('v') => string.push('\u{b}'),
+                    Some('0') if !self.peek().is_some_and(|c| c.is_ascii_digit()) => {
+                        string.push('\0')
+                    }
+                    Some('x') => string.push(self.hex_char(2)?),
+                    Some('u') => {
+                        let high = self.hex(4)?;
+                        let code = if (0xd800..0xdc00).contains(&high)
+                            && self.chars.get(self.pos..self.pos + 2) == Some(&['\\', 'u'])
+                        {
+                            self.pos += 2;
+                            let low = self.hex(4)?;
+                            0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+                        } else {
+                            high
+                        };
+                        string.push(
+                            char::from_u32(code).ok_or_else(|| self.error("invalid \\u escape"))?,
+                        );
+                    }
+                    Some(c) if c.is_ascii_digit() => {
+                        return Err(self.error(&format!("invalid escape '\\{}'", c)))
+                    }
+                    Some(c) => string.push(c),
+                    None => break,
+                },
+                Some('\n') | Some('\r') => {
+                    return Err(format!("Line {}: the string is not closed", line))
+                }
+                Some(c) => string.push(c),
+                None => break,
+            }
+        }
+        Err(format!("Line {}: the string is not closed", line))
+    }
+
+    /// Reads `digits` hex digits.
+    fn hex(&mut self, digits: usize) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..digits {
+            let digit = self
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("invalid hex escape"))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn hex_char(&mut self, digits: usize) -> Result<char, String> {
+        let code = self.hex(digits)?;
+        char::from_u32(code).ok_or_else(|| self.error("invalid hex escape"))
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let negative = match self.peek() {
+            Some('-') => {
+                self.next();
+                true
+            }
+            Some('+') => {
+                self.next();
+                false
+            }
+            _ => false,
+        };
+        let word: String = {
+            let mut word = String::new();
+            while let Some(c) = self
+                .peek()
+                .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-'))
+            {
+                // A sign only follows an exponent
+                if matches!(c, '+' | '-') && !word.ends_with(['e', 'E']) {
+                    break;
+                }
+                word.push(c);
+                self.next();
+            }
+            word
+        };
+        let invalid = || self.error(&format!("invalid number '{}'", word));
+        if word == "Infinity" || word == "NaN" {
+            return Err(self.error("Infinity and NaN are not supported"));
+        }
+        if let Some(hex) = word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+            let magnitude = u64::from_str_radix(hex, 16).map_err(|_| invalid())?;
+            return signed(magnitude, negative).ok_or_else(invalid);
+        }
+        let valid = word
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_digit() || c == '.')
+            && !word.contains(|c: char| c.is_ascii_alphabetic() && c != 'e' && c != 'E');
+        if !valid {
+            return Err(invalid());
+        }
+        if !word.contains(['.', 'e', 'E']) {
+            if let Ok(magnitude) = word.parse::<u64>() {
+                return signed(magnitude, negative).ok_or_else(invalid);
+            }
+        }
+        let magnitude: f64 = word.parse().map_err(|_| invalid())?;
+        let number = if negative { -magnitude } else { magnitude };
+        Number::from_f64(number)
+            .map(Value::Number)
+            .ok_or_else(invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{parse, write};
+
+    #[test]
+    fn test_json5() {
+        let contents = "// The list
+            {
+              todos: [
+                { id: 1, task: 'Say \"hi\"', priority: +2, $rev: 0x1F, },
+                /* Done */ { 'id': 2, \"task\": 'Pay\\x20rent\\
+ now', estimate: .5e1, due: -3, },
+              ],
+            }";
+        assert_eq!(
+            parse(contents),
+            Ok(json!({
+                "todos": [
+                    { "id": 1, "task": "Say \"hi\"", "priority": 2, "$rev": 31 },
+                    { "id": 2, "task": "Pay rent now", "estimate": 5.0, "due": -3 },
+                ],
+            }))
+        );
+        assert_eq!(
+            write(&json!({ "todos": [{ "id": 1, "due date": null }], "tags": [] })),
+            "{\n  tags: [],\n  todos: [\n    {\n      \"due date\": null,\n      id: 1,\n    },\n  ],\n}"
+        );
+
+        assert!(parse("{ a: 1, a: 2 }")
+            .unwrap_err()
+            .contains("duplicate key"));
+        assert!(parse("[1, 2").is_err());
+        assert!(parse("{ a: 'b\n' }").unwrap_err().starts_with("Line 1"));
+        assert!(parse("{ a: NaN }").is_err());
+        assert!(parse("1 2").is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! Reading the notes of a Google Keep export, the JSON files in the `Keep` folder of a
+//! Google Takeout, as todo items, for `import --format keep`.
+//!
+//! Each unchecked item of a checklist note becomes a task in the project named after the
+//! note's title, and a note without a checklist becomes one task, its title or else the
+//! first line of its text. Labels become tags. Checked items, and notes in the trash or the
+//! archive, are left out as done. An item's hash is made from its note and text, so
+//! importing the same export again matches the tasks it added the first time.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::ids;
+use crate::{is_url, Todo, TodoList};
+
+/// A note, with only the fields read from it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Note {
+    title: String,
+    text_content: String,
+    list_content: Vec<Item>,
+    labels: Vec<Label>,
+    annotations: Vec<Annotation>,
+    is_trashed: bool,
+    is_archived: bool,
+    /// When it was created, in microseconds since the epoch.
+    created_timestamp_usec: i64,
+}
+
+/// An item of a checklist note.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Item {
+    text: String,
+    is_checked: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Label {
+    name: String,
+}
+
+/// A link Keep found in a note.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Annotation {
+    url: String,
+}
+
+/// Reads the todo items of the note in the file at `path`, or of every note in it if it is
+/// a folder, such as `Takeout/Keep`. Items get `default_priority`.
+pub fn read(path: &Path, default_priority: u64) -> Result<Vec<Todo>, String> {
+    let unreadable =
+        |error: std::io::Error| format!("Unable to read {}: {}", path.display(), error);
+    let mut files = vec![path.to_path_buf()];
+    if path.is_dir() {
+        files = fs::read_dir(path)
+            .map_err(unreadable)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|file| {
+                file.extension()
+                    .is_some_and(|extension| extension == "json")
+            })
+            .collect();
+        files.sort();
+    }
+    let mut todos = Vec::new();
+    for file in files {
+        let contents = fs::read_to_string(&file)
+            .map_err(|error| format!("Unable to read {}: {}", file.display(), error))?;
+        let note = tasks(&contents, default_priority)
+            .map_err(|error| format!("Unable to parse {}: {}", file.display(), error))?;
+        todos.extend(note);
+    }
+    Ok(todos)
+}
+
+/// Reads the todo items of a note from the contents of its JSON file.
+pub fn tasks(contents: &str, default_priority: u64) -> Result<Vec<Todo>, String> {
+    let note: Note = serde_json::from_str(contents).map_err(|error| error.to_string())?;
+    if note.is_trashed || note.is_archived {
+        return Ok(Vec::new());
+    }
+    let mut list = TodoList::new();
+    let title = note.title.trim();
+    let tasks: Vec<&str> = match note.list_content.is_empty() {
+        true => title
+            .lines()
+            .chain(note.text_content.lines())
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .into_iter()
+            .collect(),
+        false => note
+            .list_content
+            .iter()
+            .filter(|item| !item.is_checked)
+            .map(|item| item.text.trim())
+            .filter(|text| !text.is_empty())
+            .collect(),
+    };
+    let tags: Vec<String> = note
+        .labels
+        .iter()
+        .map(|label| label.name.split_whitespace().collect::<Vec<_>>().join("-"))
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    let url = note
+        .annotations
+        .iter()
+        .map(|annotation| annotation.url.as_str())
+        .find(|url| is_url(url));
+    let created = note.created_timestamp_usec / 1_000_000;
+    for task in tasks {
+        let id = list
+            .add_todo(task, default_priority)
+            .ok_or_else(|| format!("Unable to add the task: {}", task))?;
+        let todo = list.todos.last_mut().expect("The todo was just added");
+        todo.hash = ids::generate(&[], &format!("keep:{}:{}:{}", created, title, task));
+        if created > 0 {
+            todo.created = created;
+        }
+        todo.tags = tags.clone();
+        if !note.list_content.is_empty() && !title.is_empty() {
+            list.set_project(id, title);
+        }
+        if let Some(url) = url {
+            list.set_url(id, url);
+        }
+    }
+    Ok(list.todos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tasks;
+
+    #[test]
+    fn test_tasks() {
+        let checklist = r#"{
+            "color": "DEFAULT", "isTrashed": false, "isPinned": true, "isArchived": false,
+            "listContent": [
+                { "textHtml": "Milk", "text": "Milk", "isChecked": false },
+                { "textHtml": "Eggs", "text": "Eggs", "isChecked": true },
+                { "textHtml": "Bread", "text": " Bread ", "isChecked": false }
+            ],
+            "title": "Groceries",
+            "userEditedTimestampUsec": 1717600000000000,
+            "createdTimestampUsec": 1717500000000000,
+            "labels": [{ "name": "home" }, { "name": "weekly shop" }]
+        }"#;
+        let todos = tasks(checklist, 3).unwrap();
+        let summary: Vec<(&str, u64, i64)> = todos
+            .iter()
+            .map(|todo| (todo.task.as_str(), todo.priority, todo.created))
+            .collect();
+        assert_eq!(summary, [("Milk", 3, 1717500000), ("Bread", 3, 1717500000)]);
+        assert_eq!(todos[1].project.as_deref(), Some("Groceries"));
+        assert_eq!(todos[1].tags, ["home", "weekly-shop"]);
+        // The same note makes the same hashes on every import
+        assert_eq!(tasks(checklist, 3).unwrap()[0].hash, todos[0].hash);
+        assert_ne!(todos[0].hash, todos[1].hash);
+
+        let text = r#"{ "title": "", "textContent": "\nRenew passport\nBefore June",
+            "annotations": [{ "source": "WEBLINK", "url": "https://gov.example/passport" }] }"#;
+        let todos = tasks(text, 3).unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].task, "Renew passport");
+        assert_eq!(todos[0].project, None);
+        assert_eq!(
+            todos[0].url.as_deref(),
+            Some("https://gov.example/passport")
+        );
+        let trashed = r#"{ "title": "Old", "isTrashed": true }"#;
+        assert!(tasks(trashed, 3).unwrap().is_empty());
+        assert!(tasks(r#"{ "title": ["Old"] }"#, 3).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+//! Laying `list` out to fit the terminal: as a table with a column per detail when it fits,
+//! as two lines per item, the task over its details, when it does not, and as a single
+//! column of IDs and tasks on the narrowest terminals.
+//!
+//! The width is the terminal's, or the one given with `--width`. Output that goes to a file
+//! or pipe has no width unless one is given, and is printed as a table as wide as it needs.
+
+use std::io::IsTerminal;
+
+use crate::theme::Theme;
+
+/// The width assumed for a terminal whose size cannot be read.
+const DEFAULT_WIDTH: usize = 80;
+/// The space between the columns of a table.
+const GAP: &str = "  ";
+/// How narrow the task and URL columns of a table are allowed to get before the table is
+/// given up for two lines per item.
+const MIN_TASK: usize = 20;
+const MIN_URL: usize = 12;
+/// The narrowest width two lines per item are used at.
+const MIN_CONDENSED: usize = 40;
+
+/// How a todo item is painted: in a theme role such as `priority2`, or its own highlight
+/// color.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Paint {
+    Role(String),
+    Color(String),
+}
+
+impl Paint {
+    fn apply(&self, theme: &Theme, text: &str) -> String {
+        match self {
+            Paint::Role(role) => theme.paint(role, text),
+            Paint::Color(color) => theme.paint_color(color, text),
+        }
+    }
+}
+
+/// A todo item as `list` shows it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub id: u64,
+    pub hash: String,
+    /// The task followed by its tags.
+    pub task: String,
+    /// The details shown after the task, labeled, in the same order for every item and
+    /// empty where the item has none, e.g. `("due", "2024-06-01 09:00")`.
+    pub details: Vec<(&'static str, String)>,
+    pub paint: Paint,
+    /// Whether the due date has passed, so it is painted as overdue.
+    pub overdue: bool,
+}
+
+/// Returns the width of the terminal output goes to, or `None` when it goes to a file or
+/// pipe. A terminal whose size cannot be read is taken to be as wide as `COLUMNS`, or 80.
+pub fn terminal_width() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let columns = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|&columns| columns > 0);
+    Some(window_width().or(columns).unwrap_or(DEFAULT_WIDTH))
+}
+
+/// Asks the terminal on standard output for its number of columns.
+#[cfg(unix)]
+fn window_width() -> Option<usize> {
+    use std::os::raw::{c_int, c_ulong};
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct WindowSize {
+        rows: u16,
+        columns: u16,
+        x_pixels: u16,
+        y_pixels: u16,
+    }
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const TIOCGWINSZ: c_ulong = 0x4008_7468;
+
+    let mut size = WindowSize::default();
+    // SAFETY: TIOCGWINSZ writes a `struct winsize`, which `WindowSize` is laid out as
+    let result = unsafe { ioctl(1, TIOCGWINSZ, &mut size as *mut WindowSize) };
+    (result == 0 && size.columns > 0).then_some(size.columns as usize)
+}
+
+#[cfg(not(unix))]
+fn window_width() -> Option<usize> {
+    None
+}
+
+/// Lays `rows` out in the widest layout that fits `width`, or as a table of its natural
+/// width when the width is unknown, colored by `theme`.
+pub fn render(rows: &[Row], width: Option<usize>, theme: &Theme) -> String {
+    let table = Table::new(rows);
+    match width {
+        None => table.render(rows, theme),
+        Some(width) => match table.fit(width) {
+            Some(table) => table.render(rows, theme),
+            None if width >= MIN_CONDENSED => condensed(rows, width, theme),
+            None => minimal(rows, width, theme),
+        },
+    }
+}
+
+/// The columns of a table and how wide each is.
+struct Table {
+    /// The header of each column and the index of the detail it shows, if it is not one of
+    /// the ID, hash and task.
+    columns: Vec<(String, Option<usize>)>,
+    widths: Vec<usize>,
+}
+
+impl Table {
+    /// Makes a table of `rows` with every column as wide as its widest cell, leaving out
+    /// the details no item has.
+    fn new(rows: &[Row]) -> Table {
+        let mut columns = vec![
+            ("ID".to_string(), None),
+            ("HASH".to_string(), None),
+            ("TASK".to_string(), None),
+        ];
+        if let Some(first) = rows.first() {
+            for (index, (label, _)) in first.details.iter().enumerate() {
+                if rows.iter().any(|row| !row.details[index].1.is_empty()) {
+                    columns.push((header(label), Some(index)));
+                }
+            }
+        }
+        let widths = columns
+            .iter()
+            .enumerate()
+            .map(|(column, (header, _))| {
+                rows.iter()
+                    .map(|row| length(&cell(row, &columns, column)))
+                    .fold(length(header), usize::max)
+            })
+            .collect();
+        Table { columns, widths }
+    }
+
+    /// Narrows the URL and then the task column for the table to fit `width`, or returns
+    /// `None` if it cannot without making them too narrow to read.
+    fn fit(mut self, width: usize) -> Option<Table> {
+        let mut excess = self.width().saturating_sub(width);
+        for (header, least) in [("URL", MIN_URL), ("TASK", MIN_TASK)] {
+            if let Some(column) = self.columns.iter().position(|(name, _)| name == header) {
+                let narrower = self.widths[column].saturating_sub(excess).max(least);
+                excess -= self.widths[column].saturating_sub(narrower).min(excess);
+                self.widths[column] = narrower.min(self.widths[column]);
+            }
+        }
+        (excess == 0).then_some(self)
+    }
+
+    fn width(&self) -> usize {
+        self.widths.iter().sum::<usize>() + GAP.len() * (self.widths.len() - 1)
+    }
+
+    fn render(&self, rows: &[Row], theme: &Theme) -> String {
+        let last = self.columns.len() - 1;
+        let mut output = String::new();
+        for (column, (header, _)) in self.columns.iter().enumerate() {
+            output.push_str(&pad(header, self.widths[column], column == last));
+            output.push_str(if column == last { "\n" } else { GAP });
+        }
+        for row in rows {
+            for column in 0..self.columns.len() {
+                let text = truncate(&cell(row, &self.columns, column), self.widths[column]);
+                let painted = match self.columns[column] {
+                    (_, None) => row.paint.apply(theme, &text),
+                    (_, Some(index)) if row.overdue && row.details[index].0 == "due" => {
+                        theme.paint("overdue", &text)
+                    }
+                    _ => text.clone(),
+                };
+                output.push_str(&painted);
+                if column < last {
+                    output.push_str(&" ".repeat(self.widths[column] - length(&text)));
+                    output.push_str(GAP);
+                }
+            }
+            output.push('\n');
+        }
+        output
+    }
+}
+
+/// Returns the text of `row` in the `column`th of `columns`.
+fn cell(row: &Row, columns: &[(String, Option<usize>)], column: usize) -> String {
+    match (column, columns[column].1) {
+        (0, None) => row.id.to_string(),
+        (1, None) => row.hash.clone(),
+        (_, None) => row.task.clone(),
+        (_, Some(index)) => row.details[index].1.clone(),
+    }
+}
+
+/// Returns the table header of the detail labeled `label`.
+fn header(label: &str) -> String {
+    match label {
+        "priority" => "PRI".to_string(),
+        "assigned to" => "ASSIGNEE".to_string(),
+        "created by" => "BY".to_string(),
+        "attachments" => "FILES".to_string(),
+        label => label.to_uppercase(),
+    }
+}
+
+/// Lays each item out as its ID, hash and task, then its details indented on a second line.
+fn condensed(rows: &[Row], width: usize, theme: &Theme) -> String {
+    let mut output = String::new();
+    for row in rows {
+        let line = format!("{} {} {}", row.id, row.hash, row.task);
+        output.push_str(&row.paint.apply(theme, &truncate(&line, width)));
+        output.push('\n');
+        let mut room = width.saturating_sub(4);
+        let mut details = String::new();
+        for (label, value) in row.details.iter().filter(|(_, value)| !value.is_empty()) {
+            let separator = if details.is_empty() { "    " } else { ", " };
+            let detail = format!("{}: {}", label, value);
+            if room < separator.len() + 1 {
+                break;
+            }
+            if !details.is_empty() {
+                room -= separator.len();
+            }
+            let text = truncate(&detail, room);
+            room -= length(&text);
+            details.push_str(separator);
+            if row.overdue && *label == "due" {
+                details.push_str(&theme.paint("overdue", &text));
+            } else {
+                details.push_str(&text);
+            }
+            if text != detail {
+                break;
+            }
+        }
+        if !details.is_empty() {
+            output.push_str(&details);
+            output.push('\n');
+        }
+    }
+    output
+}
+
+/// Lays each item out as its ID and task alone.
+fn minimal(rows: &[Row], width: usize, theme: &Theme) -> String {
+    let mut output = String::new();
+    for row in rows {
+        let line = truncate(&format!("{} {}", row.id, row.task), width);
+        output.push_str(&row.paint.apply(theme, &line));
+        output.push('\n');
+    }
+    output
+}
+
+/// Returns the number of columns `text` takes up, counting a character as one column.
+fn length(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Shortens `text` to at most `width` columns, ending it in an ellipsis if cut.
+fn truncate(text: &str, width: usize) -> String {
+    if length(text) <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    if width > 0 {
+        truncated.push('…');
+    }
+    truncated
+}
+
+/// Pads `text` with spaces to `width` columns, unless it ends the line.
+fn pad(text: &str, width: usize, last: bool) -> String {
+    let text = truncate(text, width);
+    if last {
+        return text;
+    }
+    let padding = width - length(&text);
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, Paint, Row};
+    use crate::theme::Theme;
+
+    #[test]
+    fn test_render() {
+        let row = |id, task: &str, due: &str, url: &str| Row {
+            id,
+            hash: format!("ab{}", id),
+            task: task.to_string(),
+            details: vec![
+                ("priority", "2".to_string()),
+                ("due", due.to_string()),
+                ("project", String::new()),
+                ("url", url.to_string()),
+            ],
+            paint: Paint::Role("priority2".to_string()),
+            overdue: false,
+        };
+        let rows = [
+            row(1, "Buy milk +home", "2024-06-01 09:00", ""),
+            row(
+                12,
+                "Write the quarterly report for the board",
+                "",
+                "https://example.com/reports/2024/q2",
+            ),
+        ];
+        let theme = Theme::default();
+
+        let table = render(&rows, None, &theme);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("ID  HASH  TASK"));
+        assert!(lines[0].contains("PRI  DUE"));
+        assert!(!lines[0].contains("PROJECT"));
+        assert!(lines[2].ends_with("https://example.com/reports/2024/q2"));
+
+        let narrowed = render(&rows, Some(90), &theme);
+        assert!(narrowed.lines().all(|line| line.chars().count() <= 90));
+        assert!(narrowed.contains("Write the quarterly report for the board"));
+        assert!(narrowed.contains("https://exampl…"));
+
+        let condensed = render(&rows, Some(50), &theme);
+        assert_eq!(
+            condensed.lines().collect::<Vec<_>>(),
+            [
+                "1 ab1 Buy milk +home",
+                "    priority: 2, due: 2024-06-01 09:00",
+                "12 ab12 Write the quarterly report for the board",
+                "    priority: 2, url: https://example.com/reports…",
+            ]
+        );
+
+        assert_eq!(
+            render(&rows, Some(20), &theme),
+            "1 Buy milk +home\n12 Write the quarte…\n"
+        );
+    }
+}
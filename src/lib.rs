@@ -0,0 +1,101 @@
+//! The todo list engine: the list and its items, loading and saving them, and everything
+//! the commands do with them. The command-line program in `main.rs` is built on it, as is
+//! the C API in `ffi`.
+
+pub mod anonymize;
+pub mod asana;
+pub mod attachments;
+pub mod batch;
+pub mod calendar;
+pub mod config;
+pub mod context;
+pub mod csv;
+pub mod dates;
+pub mod diff;
+pub mod doctor;
+pub mod ffi;
+pub mod focus;
+pub mod fold;
+pub mod formats;
+pub mod gantt;
+pub mod goals;
+pub mod graph;
+pub mod habits;
+pub mod hash;
+pub mod history;
+pub mod html;
+pub mod ids;
+pub mod import;
+pub mod inbox;
+pub mod jira;
+pub mod json5;
+pub mod keep;
+pub mod layout;
+pub mod list;
+pub mod locale;
+pub mod mirror;
+pub mod openapi;
+pub mod pdf;
+pub mod plan;
+pub mod predict;
+pub mod priorities;
+pub mod qr;
+pub mod query;
+pub mod quick;
+pub mod rebase;
+pub mod recurrence;
+pub mod regex;
+pub mod remote;
+pub mod shards;
+pub mod signing;
+pub mod sql;
+pub mod stats;
+pub mod store;
+pub mod sync;
+pub mod tags;
+pub mod theme;
+pub mod timings;
+pub mod tls;
+pub mod toml;
+pub mod triage;
+pub mod workdays;
+pub mod workflow;
+pub mod yaml;
+pub mod zoneinfo;
+
+pub use list::{Todo, TodoList};
+
+/// The file the todo list is stored in unless another is configured or given with `--file`.
+pub const TODO_FILE: &str = "./todos.json";
+/// Returns the current Unix timestamp.
+pub fn now() -> i64 {
+    now_millis().div_euclid(1000)
+}
+/// Returns the current Unix time in milliseconds. WebAssembly in a browser has no clock of
+/// its own, so there the page supplies one as the `todo_host_now` import, e.g. `Date.now()`.
+pub fn now_millis() -> i64 {
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    {
+        extern "C" {
+            fn todo_host_now() -> f64;
+        }
+        unsafe { todo_host_now() as i64 }
+    }
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    {
+        chrono::Utc::now().timestamp_millis()
+    }
+}
+/// Returns whether `url` is a web or mail link, `http://…`, `https://…` or `mailto:…`, the
+/// only schemes safe to open and to put in a shared page.
+pub fn is_url(url: &str) -> bool {
+    if url.chars().any(char::is_whitespace) {
+        return false;
+    }
+    match url.split_once("://") {
+        Some((scheme, rest)) => {
+            ["http", "https"].contains(&scheme.to_ascii_lowercase().as_str()) && !rest.is_empty()
+        }
+        None => url.starts_with("mailto:") && url.len() > "mailto:".len(),
+    }
+}
@@ -0,0 +1,1979 @@
+//! The todo list and its items, and the changes `add`, `edit` and `remove` make to it.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashSet};
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use crate::config::Config;
+use crate::dates::{self, DateFormat, Zone};
+use crate::formats::Format;
+use crate::ids::TaskRef;
+use crate::recurrence::Rule;
+use crate::theme::Theme;
+use crate::workdays::Workdays;
+use crate::{
+    context, csv, formats, habits, hash, ids, is_url, layout, now, now_millis, pdf, priorities,
+    tags, triage,
+};
+
+/// How `show` and `list --plain` display timestamps unless a date format is configured.
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S.%f";
+/// How many of the latest occurrences of a recurring todo item `show` lists.
+const HISTORY_SHOWN: usize = 10;
+/// The default format of the dates `list` shows, to the minute to keep its columns narrow.
+const LIST_DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+/// Represents a todo item with associated details.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Todo {
+    /// The unique identifier of the todo item.
+    pub id: u64,
+    /// The hash identifying the todo item however the list is renumbered, see `ids`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+    /// The task description of the todo item.
+    pub task: String,
+    /// The priority level of the todo item, from 1 to the number of configured priorities.
+    pub priority: u64,
+    /// The timestamp when the todo item was created.
+    pub created: i64,
+    /// The recurrence rule of the todo item, anchored at its creation time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recur: Option<String>,
+    /// Whether an occurrence falling on a weekend or holiday moves to the next working day.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub workdays: bool,
+    /// The timezone its occurrences are computed in, e.g. `Europe/Berlin`, so that they keep
+    /// their time of day over daylight saving changes. Utc when it has none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// The timestamps when the occurrences of a recurring todo item were checked off.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub done: Vec<i64>,
+    /// The beginnings of the occurrences of a recurring todo item checked off late, once the
+    /// next one had begun.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub late: Vec<i64>,
+    /// The person responsible for the todo item, if it has been delegated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    /// The person who created the todo item.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    /// Where the todo item can be done.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// A link related to the todo item.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Paths of the files attached to the todo item.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<String>,
+    /// The timestamp when the priority of the todo item was last raised by aging.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aged: Option<i64>,
+    /// The timestamp when the todo item is due.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<i64>,
+    /// The tags of the todo item.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// The project the todo item belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// The name of the goal the todo item is linked to, see `goals`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub goal: Option<String>,
+    /// The reminders set for the todo item.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reminders: Vec<Reminder>,
+    /// The IDs of the todo items that have to be done before this one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends: Vec<u64>,
+    /// The timestamp until which the todo item is waiting, e.g. on someone else.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait: Option<i64>,
+    /// How long the todo item is expected to take, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<i64>,
+    /// Whether the todo item is on the someday/maybe list, left out of `list`, `overdue`,
+    /// `plan`, `triage` and aging until it is taken off.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub someday: bool,
+    /// The start of the day `plan today` planned the todo item for, once the plan was
+    /// accepted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub planned: Option<i64>,
+    /// The timestamp when the todo item was last changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<i64>,
+    /// The color the todo item is listed in, e.g. "bold red", instead of its priority's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlight: Option<String>,
+    /// The number of times the todo item has been changed, checked by `--if-rev`.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub rev: u64,
+}
+/// A point in time at which the user wants to be reminded of a todo item.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Reminder {
+    /// The timestamp when the reminder fires.
+    pub at: i64,
+    /// Whether the reminder has already been delivered.
+    #[serde(default)]
+    pub fired: bool,
+}
+impl Todo {
+    /// Returns the timezone the occurrences of the todo item are computed in: its own, or
+    /// Utc.
+    pub fn zone(&self) -> Zone {
+        self.timezone
+            .as_deref()
+            .and_then(|zone| Zone::parse(zone).ok())
+            .unwrap_or(Zone::Utc)
+    }
+    /// Returns the timestamp of the next occurrence of a recurring todo item after the
+    /// timestamp `now`, on the next working day of `workdays` if it falls on a day off and
+    /// the item was added with `--workdays`, or `None` if it does not recur or its rule is
+    /// exhausted.
+    fn next_occurrence(&self, now: i64, workdays: &Workdays) -> Option<i64> {
+        let rule = Rule::parse(self.recur.as_deref()?).ok()?;
+        let zone = self.zone();
+        // An occurrence that has passed may still move to a working day to come
+        let next = rule
+            .occurrences(zone.local(self.created))
+            .map(|occurrence| match self.workdays {
+                true => workdays.shift(occurrence),
+                false => occurrence,
+            })
+            .filter_map(|occurrence| zone.timestamp_or_later(occurrence))
+            .find(|occurrence| *occurrence > now);
+        next
+    }
+    /// Returns whether the todo item is planned for the day containing the timestamp `at`.
+    pub fn planned_at(&self, at: i64) -> bool {
+        self.planned
+            .is_some_and(|start| start <= at && at < start + 24 * 60 * 60)
+    }
+    /// Returns every detail of the todo item as labeled values, in the order `show` prints
+    /// them, with dates in the format `dates` and virtual tags evaluated at the timestamp
+    /// `now`.
+    fn details(&self, now: i64, dates: &DateFormat) -> Vec<(&'static str, String)> {
+        let date = |timestamp| dates.format(timestamp, now, TIMESTAMP_FORMAT);
+        let mut details = vec![
+            ("id", self.id.to_string()),
+            ("hash", self.hash.clone()),
+            ("task", self.task.clone()),
+            ("priority", priorities::scale().label(self.priority)),
+            ("created", date(self.created)),
+        ];
+        if let Some(modified) = self.modified {
+            details.push(("modified", date(modified)));
+        }
+        if self.rev > 0 {
+            details.push(("revision", self.rev.to_string()));
+        }
+        if let Some(created_by) = &self.created_by {
+            details.push(("created by", created_by.clone()));
+        }
+        if let Some(assignee) = &self.assignee {
+            details.push(("assigned to", assignee.clone()));
+        }
+        if let Some(recur) = &self.recur {
+            details.push(("recurrence", recur.clone()));
+        }
+        if let Some(timezone) = &self.timezone {
+            details.push(("timezone", timezone.clone()));
+        }
+        if let Some(next) = self.next_occurrence(now, &dates.workdays) {
+            details.push(("next", dates.format(next, now, "%Y-%m-%d %H:%M:%S")));
+        }
+        if let Some(habit) = habits::habit(self, now) {
+            details.push(("habit", habit.to_string()));
+        }
+        let history = habits::history(self, now).unwrap_or_default();
+        let shown = history.len().saturating_sub(HISTORY_SHOWN);
+        for (start, outcome) in &history[shown..] {
+            let start = dates.format(*start, now, "%Y-%m-%d %H:%M");
+            details.push(("occurrence", format!("{} {}", start, outcome)));
+        }
+        if let Some(due) = self.due {
+            details.push(("due", date(due)));
+        }
+        for reminder in &self.reminders {
+            let state = if reminder.fired { " (fired)" } else { "" };
+            details.push(("reminder", format!("{}{}", date(reminder.at), state)));
+        }
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self.tags.iter().map(|tag| format!("+{}", tag)).collect();
+            details.push(("tags", tags.join(" ")));
+        }
+        if let Some(project) = &self.project {
+            details.push(("project", project.clone()));
+        }
+        if let Some(goal) = &self.goal {
+            details.push(("goal", goal.clone()));
+        }
+        if !self.depends.is_empty() {
+            let depends: Vec<String> = self.depends.iter().map(u64::to_string).collect();
+            details.push(("depends on", depends.join(", ")));
+        }
+        if let Some(wait) = self.wait {
+            details.push(("waiting until", date(wait)));
+        }
+        if let Some(estimate) = self.estimate {
+            details.push(("estimate", dates::format_duration(estimate)));
+        }
+        if self.someday {
+            details.push(("someday", "yes".to_string()));
+        }
+        if let Some(planned) = self.planned {
+            details.push(("planned for", dates.format(planned, now, "%Y-%m-%d")));
+        }
+        let virtual_tags = tags::virtual_tags(self, now);
+        if !virtual_tags.is_empty() {
+            details.push(("virtual tags", virtual_tags.join(" ")));
+        }
+        if let Some(highlight) = &self.highlight {
+            details.push(("highlight", highlight.clone()));
+        }
+        if let Some(location) = &self.location {
+            details.push(("location", location.clone()));
+        }
+        if let Some(url) = &self.url {
+            details.push(("url", url.clone()));
+        }
+        for attachment in &self.attachments {
+            details.push(("attachment", attachment.clone()));
+        }
+        details
+    }
+    /// Returns whether the todo item has `tag`, which may be a virtual tag such as `OVERDUE`
+    /// evaluated at the timestamp `now`.
+    pub fn has_tag(&self, tag: &str, now: i64) -> bool {
+        tags::virtual_tag(self, tag, now).unwrap_or_else(|| self.tags.iter().any(|t| t == tag))
+    }
+    /// Returns the timestamp when the todo item was last changed, or created if it never was.
+    pub fn last_modified(&self) -> i64 {
+        self.modified.unwrap_or(self.created)
+    }
+    /// Puts the todo item in `context`, if one is set, unless it has a project of its own.
+    pub fn apply_context(&mut self, context: Option<&context::Context>) {
+        match context {
+            Some(context::Context::Tag(tag)) if !self.tags.contains(tag) => {
+                self.tags.push(tag.clone())
+            }
+            Some(context::Context::Project(project)) if self.project.is_none() => {
+                self.project = Some(project.clone())
+            }
+            _ => {}
+        }
+    }
+    /// Records that the todo item was changed at the timestamp `now`, moving it to its next
+    /// revision.
+    pub fn touch(&mut self, now: i64) {
+        self.modified = Some(now);
+        self.rev += 1;
+    }
+}
+/// Represents a collection of todo items.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TodoList {
+    /// The list of todo items stored in the todo list with a Vec.
+    pub todos: Vec<Todo>,
+    /// The SHA-256 checksum of the todo items when the list was last saved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// The projects loaded from a sharded list, when only some of them were.
+    #[serde(skip)]
+    pub shards: Option<BTreeSet<String>>,
+}
+impl TodoList {
+    /// Creates a new `TodoList` instance with an empty list of todos.
+    pub fn new() -> TodoList {
+        TodoList {
+            todos: Vec::new(),
+            checksum: None,
+            shards: None,
+        }
+    }
+    /// Computes the checksum of the todo items, independent of how the file is formatted.
+    pub fn compute_checksum(&self) -> String {
+        let serialized = serde_json::to_string(&self.todos).expect("Unable to serialize todo list");
+        hash::sha256_hex(serialized.as_bytes())
+    }
+    /// Serializes the list in `format`, with the checksum of its items.
+    pub fn to_format(&self, format: Format) -> String {
+        let mut serialized = serde_json::to_value(self).expect("Unable to serialize todo list");
+        serialized["checksum"] = serde_json::Value::String(self.compute_checksum());
+        formats::write(&serialized, format)
+    }
+    /// Serializes the list as JSON, the default format of the list file and the one lists
+    /// are sent over HTTP in.
+    pub fn to_json(&self) -> String {
+        self.to_format(Format::Json)
+    }
+    /// Reads a list written in `format`.
+    pub fn parse(contents: &str, format: Format) -> Result<TodoList, String> {
+        formats::parse(contents, format)
+            .and_then(|value| serde_json::from_value(value).map_err(|error| error.to_string()))
+    }
+    /// Returns whether the stored checksum matches the todo items. Lists saved before
+    /// checksums were introduced have none and are trusted.
+    pub fn verify_checksum(&self) -> bool {
+        self.checksum
+            .as_ref()
+            .is_none_or(|checksum| *checksum == self.compute_checksum())
+    }
+    /// Adds a new todo item to the todo list with the specified task and priority,
+    /// returning its ID if it was added.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - The task description for the new todo item.
+    /// * `priority` - The priority level for the new todo item.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut todo_list = TodoList::new();
+    /// todo_list.add_todo("Complete the assignment", 3);
+    /// ```
+    pub fn add_todo(&mut self, task: &str, priority: u64) -> Option<u64> {
+        if priorities::scale().contains(priority) {
+            let todo = self.new_todo(task, priority);
+            let id = todo.id;
+            self.todos.push(todo);
+            Some(id)
+        } else {
+            println!(
+                "Invalid priority: {} for task: {}. Not Added",
+                priority, task
+            );
+            None
+        }
+    }
+    /// Builds the todo item that would be added next to the list, at revision 0, for the
+    /// caller to fill in before pushing it.
+    pub fn new_todo(&self, task: &str, priority: u64) -> Todo {
+        let id = self.todos.len() as u64 + 1;
+        let seed = format!("{}:{}:{}", now_millis(), id, task);
+        Todo {
+            id,
+            hash: ids::generate(&self.todos, &seed),
+            task: task.to_string(),
+            priority,
+            created: now(),
+            recur: None,
+            workdays: false,
+            timezone: None,
+            done: Vec::new(),
+            late: Vec::new(),
+            assignee: None,
+            created_by: None,
+            location: None,
+            url: None,
+            attachments: Vec::new(),
+            aged: None,
+            due: None,
+            tags: Vec::new(),
+            project: None,
+            goal: None,
+            reminders: Vec::new(),
+            depends: Vec::new(),
+            wait: None,
+            estimate: None,
+            someday: false,
+            planned: None,
+            modified: None,
+            highlight: None,
+            rev: 0,
+        }
+    }
+    /// Returns the todo item with the given ID, if any.
+    pub fn todo(&self, id: u64) -> Option<&Todo> {
+        self.todos.iter().find(|todo| todo.id == id)
+    }
+    /// Returns a mutable reference to the todo item with the given ID, if any, recording
+    /// that it was modified.
+    pub fn todo_mut(&mut self, id: u64) -> Option<&mut Todo> {
+        let todo = self.todos.iter_mut().find(|todo| todo.id == id)?;
+        todo.touch(now());
+        Some(todo)
+    }
+    /// Returns the ID of the todo item `task` refers to. An ID is returned as it is, even if
+    /// no todo item has it, for the command to report.
+    pub fn resolve(&self, task: &TaskRef) -> Result<u64, String> {
+        match task {
+            TaskRef::Id(id) => Ok(*id),
+            // A word such as "bed" could be either, so it is a title when no hash starts with it
+            TaskRef::Hash(prefix)
+                if !self.todos.iter().any(|todo| todo.hash.starts_with(prefix))
+                    && !ids::matching_titles(&self.todos, prefix).is_empty() =>
+            {
+                ids::find_title(&self.todos, prefix)
+            }
+            TaskRef::Hash(prefix) => ids::find(&self.todos, prefix),
+            TaskRef::Title(title) => ids::find_title(&self.todos, title),
+        }
+    }
+    /// Gives a hash to every todo item without one, e.g. saved before hashes were introduced
+    /// or imported, or whose hash an earlier item has. The hash is made from the item's
+    /// creation, ID and task, so it stays the same until the list is saved with it.
+    pub fn assign_hashes(&mut self) {
+        let mut earlier = HashSet::with_capacity(self.todos.len());
+        for todo in &mut self.todos {
+            if todo.hash.is_empty() || earlier.contains(&todo.hash) {
+                let seed = format!("{}:{}:{}", todo.created, todo.id, todo.task);
+                todo.hash = ids::generate_unused(&earlier, &seed);
+            }
+            earlier.insert(todo.hash.clone());
+        }
+    }
+    /// Checks that the todo item with the given ID is still at revision `expected`, so a
+    /// change made since it was read is not overwritten.
+    pub fn check_revision(&self, id: u64, expected: u64) -> Result<(), String> {
+        match self.todo(id) {
+            Some(todo) if todo.rev == expected => Ok(()),
+            Some(todo) => Err(format!(
+                "Task {} has changed: it is at revision {}, not {}",
+                id, todo.rev, expected
+            )),
+            None => Err("Invalid ID".to_string()),
+        }
+    }
+    /// Sets the recurrence rule of a todo item after validating it.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The unique identifier of the todo item.
+    /// * `rule` - An RFC 5545 recurrence rule, e.g. `FREQ=WEEKLY;BYDAY=MO,WE`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut todo_list = TodoList::new();
+    /// todo_list.add_todo("Water the plants", 2);
+    /// todo_list.set_recurrence(1, "FREQ=WEEKLY;BYDAY=SA");
+    /// ```
+    pub fn set_recurrence(&mut self, id: u64, rule: &str) {
+        if let Err(error) = Rule::parse(rule) {
+            println!("Invalid recurrence rule: {}", error);
+            return;
+        }
+        match self.todo_mut(id) {
+            Some(todo) => todo.recur = Some(rule.to_string()),
+            None => println!("Invalid ID"),
+        }
+    }
+    /// Returns the IDs of the todo items outside `ids` that depend on one of them.
+    pub fn dependents(&self, ids: &[u64]) -> Vec<u64> {
+        self.todos
+            .iter()
+            .filter(|todo| !ids.contains(&todo.id))
+            .filter(|todo| todo.depends.iter().any(|other| ids.contains(other)))
+            .map(|todo| todo.id)
+            .collect()
+    }
+    /// Gets the todo items `ids` ready to be removed, handling the items depending on them
+    /// as `mode` says, and returns the IDs to remove, the highest first so that renumbering
+    /// does not shift the others.
+    pub fn prepare_removal(&mut self, ids: &[u64], mode: Dependents) -> Result<Vec<u64>, String> {
+        let mut ids = ids.to_vec();
+        match mode {
+            Dependents::Unblock => {}
+            Dependents::Block => {
+                let dependents: Vec<String> = self
+                    .dependents(&ids)
+                    .into_iter()
+                    .filter_map(|id| self.todo(id))
+                    .map(|todo| format!("{} ({})", todo.id, todo.task))
+                    .collect();
+                if !dependents.is_empty() {
+                    return Err(format!(
+                        "Other tasks depend on it: {}. Use --dependents cascade to remove them \
+                         too, relink to pass on its dependencies, or unblock to drop them",
+                        dependents.join(", ")
+                    ));
+                }
+            }
+            Dependents::Cascade => loop {
+                let dependents = self.dependents(&ids);
+                if dependents.is_empty() {
+                    break;
+                }
+                ids.extend(dependents);
+            },
+            Dependents::Relink => {
+                for id in self.dependents(&ids) {
+                    // Follow the dependencies through the removed items to the ones staying
+                    let mut pending = self.todo(id).expect("A dependent").depends.clone();
+                    let mut seen = Vec::new();
+                    let mut depends = Vec::new();
+                    while let Some(other) = pending.pop() {
+                        if other == id || seen.contains(&other) {
+                            continue;
+                        }
+                        seen.push(other);
+                        match ids.contains(&other) {
+                            true => pending
+                                .extend(self.todo(other).map_or(&[][..], |todo| &todo.depends)),
+                            false => depends.push(other),
+                        }
+                    }
+                    depends.sort_unstable();
+                    let todo = self.todo_mut(id).expect("A dependent");
+                    todo.depends = depends;
+                }
+            }
+        }
+        ids.sort_unstable_by_key(|id| Reverse(*id));
+        ids.dedup();
+        Ok(ids)
+    }
+    /// Removes a todo item from the todo list based on its ID and resets the IDs of the rest,
+    /// returning the removed item.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The unique identifier of the todo item to be removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut todo_list = TodoList::new();
+    /// todo_list.add_todo("Complete the assignment", 3);
+    /// todo_list.remove_todo(1);
+    /// ```
+    pub fn remove_todo(&mut self, id: u64) -> Option<Todo> {
+        match self.todos.iter().position(|todo| todo.id == id) {
+            Some(index) => {
+                let removed = self.todos.remove(index);
+                for (new_id, todo) in (1..).zip(self.todos.iter_mut()) {
+                    todo.id = new_id;
+                    // Dependencies follow the renumbering, and the removed item no longer
+                    // blocks anything.
+                    todo.depends.retain(|&other| other != id);
+                    for other in &mut todo.depends {
+                        if *other > id {
+                            *other -= 1;
+                        }
+                    }
+                }
+                Some(removed)
+            }
+            None => {
+                println!("Invalid ID. Nothing deleted.");
+                None
+            }
+        }
+    }
+    /// Clears all todo items from the todo list, returning the removed items.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut todo_list = TodoList::new();
+    /// todo_list.add_todo("Complete the assignment", 3);
+    /// todo_list.clear_todo();
+    /// ```
+    pub fn clear_todo(&mut self) -> Vec<Todo> {
+        std::mem::take(&mut self.todos)
+    }
+    /// Displays the todo items with `display_todos` in the colors of `theme` to fit
+    /// `width`, or with `display_todos_plain` when `plain` is set, with dates in the format
+    /// `dates`.
+    pub fn display(&self, theme: &Theme, plain: bool, dates: &DateFormat, width: Option<usize>) {
+        if plain {
+            self.display_todos_plain(dates);
+        } else {
+            self.display_todos(theme, dates, width);
+        }
+    }
+    /// Displays every detail of each todo item as labeled `key: value` lines, with a blank
+    /// line between items, for screen readers.
+    fn display_todos_plain(&self, dates: &DateFormat) {
+        if self.todos.is_empty() {
+            println!("No tasks left!");
+        }
+        let now = Utc::now().timestamp();
+        for (index, todo) in self.todos.iter().enumerate() {
+            if index > 0 {
+                println!();
+            }
+            for (label, value) in todo.details(now, dates) {
+                println!("{}: {}", label, value);
+            }
+        }
+    }
+    /// Displays the details of all todo items in the todo list with dates in the format
+    /// `dates`, laid out to fit `width` as `layout` does, each colored by its priority, or
+    /// as blocked or waiting, with overdue due dates highlighted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut todo_list = TodoList::new();
+    /// todo_list.add_todo("Complete the assignment", 3);
+    /// todo_list.display_todos(&Theme::default(), &DateFormat::default(), Some(80));
+    /// ```
+    fn display_todos(&self, theme: &Theme, dates: &DateFormat, width: Option<usize>) {
+        if self.todos.is_empty() {
+            println!("No tasks left!");
+            return;
+        }
+        let now = Utc::now().timestamp();
+        let abbrev_len = ids::abbrev_len(&self.todos);
+        let rows: Vec<layout::Row> = self
+            .todos
+            .iter()
+            .map(|todo| {
+                let date = |timestamp| dates.format(timestamp, now, LIST_DATE_FORMAT);
+                let paint = match &todo.highlight {
+                    Some(color) => layout::Paint::Color(color.clone()),
+                    None if todo.has_tag("WAITING", now) => layout::Paint::Role("waiting".into()),
+                    None if todo.has_tag("BLOCKED", now) => layout::Paint::Role("blocked".into()),
+                    None => match priorities::scale().color(todo.priority) {
+                        Some(color) => layout::Paint::Color(color.to_string()),
+                        None => layout::Paint::Role(priorities::scale().role(todo.priority)),
+                    },
+                };
+                let mut task = todo.task.clone();
+                for tag in &todo.tags {
+                    task.push_str(&format!(" +{}", tag));
+                }
+                let next = todo.next_occurrence(now, &dates.workdays).map(date);
+                let attachments = match todo.attachments.len() {
+                    0 => String::new(),
+                    count => count.to_string(),
+                };
+                layout::Row {
+                    id: todo.id,
+                    hash: ids::abbrev(todo, abbrev_len).to_string(),
+                    task,
+                    details: vec![
+                        ("priority", priorities::scale().label(todo.priority)),
+                        ("due", todo.due.map(date).unwrap_or_default()),
+                        ("next", next.unwrap_or_default()),
+                        ("project", todo.project.clone().unwrap_or_default()),
+                        ("assigned to", todo.assignee.clone().unwrap_or_default()),
+                        ("created by", todo.created_by.clone().unwrap_or_default()),
+                        ("at", todo.location.clone().unwrap_or_default()),
+                        ("url", todo.url.clone().unwrap_or_default()),
+                        ("attachments", attachments),
+                        ("created", date(todo.created)),
+                    ],
+                    paint,
+                    overdue: todo.has_tag("OVERDUE", now),
+                }
+            })
+            .collect();
+        print!("{}", layout::render(&rows, width, theme));
+    }
+    /// Formats the todo items as a plain-text checklist, one `[ ] task` line per item with
+    /// its due date, in the format `dates`, and URL.
+    pub fn checklist(&self, dates: &DateFormat) -> String {
+        let now = Utc::now().timestamp();
+        let mut checklist = String::new();
+        for todo in &self.todos {
+            checklist.push_str(&format!("[ ] {}", todo.task));
+            if let Some(due) = todo.due {
+                let due = dates.format(due, now, "%Y-%m-%d");
+                checklist.push_str(&format!(" (due {})", due));
+            }
+            if let Some(url) = &todo.url {
+                checklist.push_str(&format!(" {}", url));
+            }
+            checklist.push('\n');
+        }
+        checklist
+    }
+    /// Writes the todo items as CSV, a row each under a header naming the fields as `import
+    /// --format csv` reads them, with due dates in `zone`.
+    pub fn to_csv(&self, zone: Zone) -> String {
+        let header = [
+            "id", "task", "priority", "due", "tags", "project", "assignee", "location", "url",
+            "estimate",
+        ];
+        let mut rows = vec![header.iter().map(|field| field.to_string()).collect()];
+        for todo in &self.todos {
+            rows.push(vec![
+                todo.id.to_string(),
+                todo.task.clone(),
+                priorities::scale().label(todo.priority),
+                todo.due
+                    .map(|due| zone.local(due).format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default(),
+                todo.tags.join(" "),
+                todo.project.clone().unwrap_or_default(),
+                todo.assignee.clone().unwrap_or_default(),
+                todo.location.clone().unwrap_or_default(),
+                todo.url.clone().unwrap_or_default(),
+                todo.estimate
+                    .map(dates::format_duration)
+                    .unwrap_or_default(),
+            ]);
+        }
+        csv::write(&rows)
+    }
+    /// Numbers the todo items from 1 in their order, as after loading a list file, keeping
+    /// the dependencies between them and dropping those on items no longer on the list.
+    pub fn renumber(&mut self) {
+        let ids: Vec<u64> = self.todos.iter().map(|todo| todo.id).collect();
+        for (new_id, todo) in (1..).zip(self.todos.iter_mut()) {
+            todo.id = new_id;
+            todo.depends = todo
+                .depends
+                .iter()
+                .filter_map(|other| ids.iter().position(|id| id == other))
+                .map(|index| index as u64 + 1)
+                .collect();
+            todo.depends.sort_unstable();
+        }
+    }
+    /// Lays the todo items out as a printable PDF checklist, grouped by project with the
+    /// items without one last, showing each item's due date, in the format `dates`, and
+    /// priority.
+    pub fn checklist_pdf(&self, now: i64, dates: &DateFormat) -> Vec<u8> {
+        let date = DateTime::from_timestamp(now, 0).unwrap_or_default();
+        let mut document = pdf::Document::new(&format!("Todo list - {}", date.format("%Y-%m-%d")));
+        let mut projects: Vec<Option<&str>> = self
+            .todos
+            .iter()
+            .map(|todo| todo.project.as_deref())
+            .collect();
+        projects.sort_by_key(|project| (project.is_none(), *project));
+        projects.dedup();
+        for project in projects {
+            if self.todos.iter().any(|todo| todo.project.is_some()) {
+                document.heading(project.unwrap_or("No project"));
+            }
+            for todo in self
+                .todos
+                .iter()
+                .filter(|todo| todo.project.as_deref() == project)
+            {
+                let mut note = format!("priority {}", priorities::scale().label(todo.priority));
+                if let Some(due) = todo.due {
+                    let due = dates.format(due, now, "%Y-%m-%d %H:%M");
+                    note.push_str(&format!(", due {}", due));
+                }
+                document.item(&todo.task, &note);
+            }
+        }
+        document.finish()
+    }
+    /// Writes each todo item as a JSON object on its own line, so the output can be
+    /// processed line by line by tools such as `jq` or `grep`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut output = std::io::stdout().lock();
+    /// todo_list.write_ndjson(&mut output)?;
+    /// ```
+    pub fn write_ndjson(&self, output: &mut impl Write) -> std::io::Result<()> {
+        for todo in &self.todos {
+            serde_json::to_writer(&mut *output, todo)?;
+            output.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+    /// Displays every detail of a single todo item, one per line.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The unique identifier of the todo item to be shown.
+    /// * `dates` - The format dates are shown in.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut todo_list = TodoList::new();
+    /// todo_list.add_todo("Complete the assignment", 3);
+    /// todo_list.show_todo(1, &DateFormat::default());
+    /// ```
+    pub fn show_todo(&self, id: u64, dates: &DateFormat) {
+        match self.todo(id) {
+            Some(todo) => {
+                for (label, value) in todo.details(Utc::now().timestamp(), dates) {
+                    println!("{}: {}", label, value);
+                }
+            }
+            None => println!("Invalid ID"),
+        }
+    }
+    /// Attaches a previously stored file to a todo item.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The unique identifier of the todo item.
+    /// * `reference` - The path of the stored or linked file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut todo_list = TodoList::new();
+    /// todo_list.add_todo("Review the spec", 3);
+    /// todo_list.attach_file(1, "/home/alice/spec.pdf");
+    /// ```
+    pub fn attach_file(&mut self, id: u64, reference: &str) -> bool {
+        match self.todo_mut(id) {
+            Some(todo) => {
+                if !todo
+                    .attachments
+                    .iter()
+                    .any(|existing| existing == reference)
+                {
+                    todo.attachments.push(reference.to_string());
+                }
+                true
+            }
+            None => {
+                println!("Invalid ID");
+                false
+            }
+        }
+    }
+    /// Raises the priority of every todo item by one level for each `after_days` it has been
+    /// open since it was created or last raised, up to the highest priority (5).
+    ///
+    /// Returns the number of todo items whose priority changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `after_days` - The number of days a todo item may stay open before being raised.
+    /// * `now` - The current timestamp.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut todo_list = TodoList::new();
+    /// todo_list.add_todo("Complete the assignment", 3);
+    /// todo_list.age_todos(14, Utc::now().timestamp());
+    /// ```
+    pub fn age_todos(&mut self, after_days: u64, now: i64) -> usize {
+        let period = after_days.max(1) as i64 * 24 * 60 * 60;
+        let mut raised = 0;
+        for todo in &mut self.todos {
+            let most_urgent = priorities::scale().levels();
+            if todo.priority >= most_urgent || todo.someday {
+                continue;
+            }
+            let since = todo.aged.unwrap_or(todo.created);
+            let periods = (now - since) / period;
+            if periods > 0 {
+                todo.priority = (todo.priority + periods as u64).min(most_urgent);
+                todo.aged = Some(since + periods * period);
+                todo.touch(now);
+                raised += 1;
+            }
+        }
+        raised
+    }
+    /// Assigns a todo item to the given person.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The unique identifier of the todo item to be assigned.
+    /// * `assignee` - The person responsible for the todo item.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut todo_list = TodoList::new();
+    /// todo_list.add_todo("Take out the bins", 2);
+    /// todo_list.delegate_todo(1, "alice");
+    /// ```
+    pub fn delegate_todo(&mut self, id: u64, assignee: &str) {
+        match self.todo_mut(id) {
+            Some(todo) => todo.assignee = Some(assignee.to_string()),
+            None => println!("Invalid ID"),
+        }
+    }
+    /// Sets the link of a todo item after checking that it looks like a URL.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The unique identifier of the todo item.
+    /// * `url` - An absolute URL, e.g. `https://example.com/pull/1`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut todo_list = TodoList::new();
+    /// todo_list.add_todo("Review PR", 4);
+    /// todo_list.set_url(1, "https://example.com/pull/1");
+    /// ```
+    pub fn set_url(&mut self, id: u64, url: &str) {
+        if !is_url(url) {
+            println!(
+                "Invalid URL: {}. Expected an http, https or mailto link",
+                url
+            );
+            return;
+        }
+        match self.todo_mut(id) {
+            Some(todo) => todo.url = Some(url.to_string()),
+            None => println!("Invalid ID"),
+        }
+    }
+    /// Sets the due date of a todo item.
+    pub fn set_due(&mut self, id: u64, due: i64) {
+        match self.todo_mut(id) {
+            Some(todo) => todo.due = Some(due),
+            None => println!("Invalid ID"),
+        }
+    }
+    /// Adds a reminder to a todo item. Reminders are kept in the order they fire.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The unique identifier of the todo item.
+    /// * `at` - The timestamp when the reminder should fire.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut todo_list = TodoList::new();
+    /// todo_list.add_todo("Pay rent", 5);
+    /// todo_list.add_reminder(1, Utc::now().timestamp() + 3600);
+    /// ```
+    pub fn add_reminder(&mut self, id: u64, at: i64) {
+        match self.todo_mut(id) {
+            Some(todo) => {
+                if !todo.reminders.iter().any(|reminder| reminder.at == at) {
+                    todo.reminders.push(Reminder { at, fired: false });
+                    todo.reminders.sort_by_key(|reminder| reminder.at);
+                }
+            }
+            None => println!("Invalid ID"),
+        }
+    }
+    /// Marks every reminder that is due at `now` as fired, returning the IDs and tasks of
+    /// the todo items they belong to together with the reminder times.
+    pub fn fire_reminders(&mut self, now: i64) -> Vec<(u64, String, i64)> {
+        let mut fired = Vec::new();
+        for todo in &mut self.todos {
+            for reminder in &mut todo.reminders {
+                if !reminder.fired && reminder.at <= now {
+                    reminder.fired = true;
+                    fired.push((todo.id, todo.task.clone(), reminder.at));
+                }
+            }
+        }
+        fired
+    }
+    /// Adds tags to a todo item, ignoring ones it already has.
+    pub fn add_tags(&mut self, id: u64, tags: &[String]) {
+        match self.todo_mut(id) {
+            Some(todo) => {
+                for tag in tags {
+                    if !todo.tags.contains(tag) {
+                        todo.tags.push(tag.clone());
+                    }
+                }
+            }
+            None => println!("Invalid ID"),
+        }
+    }
+    /// Records that a todo item can only be done after the items `others`, refusing
+    /// unknown IDs and dependencies that would form a cycle.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut todo_list = TodoList::new();
+    /// todo_list.add_todo("Book the venue", 4);
+    /// todo_list.add_todo("Send the invitations", 3);
+    /// todo_list.add_dependencies(2, &[1]);
+    /// ```
+    pub fn add_dependencies(&mut self, id: u64, others: &[u64]) {
+        if self.todo(id).is_none() {
+            println!("Invalid ID");
+            return;
+        }
+        for &other in others {
+            if self.todo(other).is_none() {
+                println!("Invalid ID: {}", other);
+                return;
+            }
+            if self.depends_on(other, id) {
+                println!(
+                    "{} already depends on {}, directly or indirectly",
+                    other, id
+                );
+                return;
+            }
+        }
+        if let Some(todo) = self.todo_mut(id) {
+            for &other in others {
+                if !todo.depends.contains(&other) {
+                    todo.depends.push(other);
+                }
+            }
+            todo.depends.sort_unstable();
+        }
+    }
+    /// Removes dependencies of a todo item.
+    pub fn remove_dependencies(&mut self, id: u64, others: &[u64]) {
+        match self.todo_mut(id) {
+            Some(todo) => todo.depends.retain(|other| !others.contains(other)),
+            None => println!("Invalid ID"),
+        }
+    }
+    /// Returns whether the item `id` depends on `other` directly or through other items.
+    fn depends_on(&self, id: u64, other: u64) -> bool {
+        let mut pending = vec![id];
+        let mut seen = Vec::new();
+        while let Some(current) = pending.pop() {
+            if current == other {
+                return true;
+            }
+            if seen.contains(&current) {
+                continue;
+            }
+            seen.push(current);
+            if let Some(todo) = self.todo(current) {
+                pending.extend(&todo.depends);
+            }
+        }
+        false
+    }
+    /// Sets how long a todo item is expected to take, in seconds.
+    pub fn set_estimate(&mut self, id: u64, estimate: i64) {
+        match self.todo_mut(id) {
+            Some(todo) => todo.estimate = Some(estimate),
+            None => println!("Invalid ID"),
+        }
+    }
+    /// Sets the date until which a todo item is waiting.
+    pub fn set_wait(&mut self, id: u64, wait: i64) {
+        match self.todo_mut(id) {
+            Some(todo) => todo.wait = Some(wait),
+            None => println!("Invalid ID"),
+        }
+    }
+    /// Sets the project a todo item belongs to.
+    pub fn set_project(&mut self, id: u64, project: &str) {
+        match self.todo_mut(id) {
+            Some(todo) => todo.project = Some(project.to_string()),
+            None => println!("Invalid ID"),
+        }
+    }
+    /// Sets the location of a todo item.
+    pub fn set_location(&mut self, id: u64, location: &str) {
+        match self.todo_mut(id) {
+            Some(todo) => todo.location = Some(location.to_string()),
+            None => println!("Invalid ID"),
+        }
+    }
+    /// Records who created a todo item.
+    pub fn set_creator(&mut self, id: u64, creator: &str) {
+        if let Some(todo) = self.todo_mut(id) {
+            todo.created_by = Some(creator.to_string());
+        }
+    }
+    /// Keeps only the todo items created by the given person.
+    pub fn retain_creator(&mut self, creator: &str) {
+        self.todos
+            .retain(|todo| todo.created_by.as_deref() == Some(creator));
+    }
+    /// Keeps only the todo items assigned to the given person.
+    pub fn retain_assignee(&mut self, assignee: &str) {
+        self.todos
+            .retain(|todo| todo.assignee.as_deref() == Some(assignee));
+    }
+}
+/// The todo items `add` creates.
+#[derive(Debug, StructOpt)]
+pub struct NewTodo {
+    /// The task descriptions, one todo item each. A trailing number or priority name is the
+    /// priority when --priority is not given, as in add "Pay rent" 5.
+    #[structopt(required = true)]
+    pub tasks: Vec<String>,
+    /// The priority of every new todo item, a number from 1 to 5 or the name of a
+    /// configured priority. Defaults to the configured default_priority.
+    #[structopt(long)]
+    pub priority: Option<String>,
+    /// An RFC 5545 recurrence rule, e.g. "FREQ=MONTHLY;BYDAY=2TU".
+    #[structopt(long)]
+    pub recur: Option<String>,
+    /// Move occurrences falling on a weekend or a configured holiday to the next working day.
+    #[structopt(long, requires = "recur")]
+    pub workdays: bool,
+    /// The timezone the due date is given in and occurrences are computed in, e.g.
+    /// "Europe/Berlin". Defaults to the configured timezone.
+    #[structopt(long)]
+    pub timezone: Option<String>,
+    /// A link related to the new todo item.
+    #[structopt(long)]
+    pub url: Option<String>,
+    /// Where the new todo item can be done, e.g. "supermarket".
+    #[structopt(long)]
+    pub location: Option<String>,
+    /// When the new todo item is due, e.g. "2024-06-01", "2024-06-01 17:00", "today 17:00",
+    /// "fri 9am" or "+3bd" for three working days from today, in the configured timezone.
+    #[structopt(long)]
+    pub due: Option<String>,
+    /// The project the new todo item belongs to, whose configured defaults it gets.
+    #[structopt(long, alias = "list")]
+    pub project: Option<String>,
+    /// How long the new todo item is expected to take, e.g. "30m" or "1h30m".
+    #[structopt(long)]
+    pub estimate: Option<String>,
+    /// Suggest a priority from the configured priority_rules and ask to confirm or override
+    /// it, instead of using the default priority.
+    #[structopt(long, conflicts_with = "priority")]
+    pub suggest_priority: bool,
+}
+impl NewTodo {
+    /// Returns the trailing priority of `add <task> <priority>`: a last argument after the
+    /// first that is a number or the name of a priority.
+    fn trailing_priority(&self) -> Option<&str> {
+        match self.tasks.as_slice() {
+            [_, .., last]
+                if last.parse::<u64>().is_ok() || priorities::scale().parse(last).is_ok() =>
+            {
+                Some(last)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the tasks to add and their priority: `--priority`, the trailing priority of
+    /// `add <task> <priority>`, or the default configured for `project` or else for every
+    /// todo item.
+    fn tasks_and_priority(
+        &self,
+        config: &Config,
+        project: Option<&str>,
+    ) -> Result<(Vec<String>, u64), String> {
+        let mut tasks = self.tasks.clone();
+        let scale = priorities::scale();
+        let priority = match (&self.priority, self.trailing_priority()) {
+            (Some(priority), _) => scale.parse(priority)?,
+            (None, Some(priority)) => {
+                tasks.pop();
+                scale.parse(priority)?
+            }
+            (None, None) => config
+                .project_defaults(project)
+                .priority
+                .unwrap_or(config.default_priority()),
+        };
+        Ok((tasks, priority))
+    }
+
+    /// Returns whether the priority is suggested by the priority rules: with
+    /// `--suggest-priority`, unless a trailing priority is given.
+    pub fn suggests_priority(&self) -> bool {
+        self.suggest_priority && self.trailing_priority().is_none()
+    }
+
+    /// Checks every option and adds a todo item for each task to `todo_list`, created by
+    /// the current user and in the active context, with the defaults configured for its
+    /// project, returning their IDs. Nothing is added if an option is invalid.
+    pub fn add_to(
+        self,
+        todo_list: &mut TodoList,
+        config: &Config,
+        base: &Path,
+    ) -> Result<Vec<u64>, String> {
+        let project = self.project.clone().or_else(|| match context::load(base) {
+            Some(context::Context::Project(project)) => Some(project),
+            _ => None,
+        });
+        let defaults = config.project_defaults(project.as_deref());
+        let (tasks, priority) = self.tasks_and_priority(config, project.as_deref())?;
+        let suggest = self.suggests_priority();
+        let rules = match suggest {
+            true => config.priority_rules()?,
+            false => Vec::new(),
+        };
+        let zone = match &self.timezone {
+            Some(zone) => Zone::parse(zone)?,
+            None => config.timezone()?,
+        };
+        let locale = config.locale()?;
+        let workdays = config.workdays()?;
+        let due = match (&self.due, &defaults.due) {
+            (Some(due), _) => Some(dates::parse_due_in(due, now(), zone, locale, &workdays)?),
+            (None, Some(offset)) => Some(
+                now()
+                    + dates::parse_duration(offset).map_err(|error| {
+                        let project = project.as_deref().unwrap_or_default();
+                        format!("Invalid due date for project {}: {}", project, error)
+                    })?,
+            ),
+            (None, None) => None,
+        };
+        let estimate = self
+            .estimate
+            .as_deref()
+            .map(dates::parse_duration)
+            .transpose()?;
+        if let Some(Err(error)) = self.recur.as_deref().map(Rule::parse) {
+            return Err(format!("Invalid recurrence rule: {}", error));
+        }
+        if let Some(url) = self.url.as_deref().filter(|url| !is_url(url)) {
+            return Err(format!(
+                "Invalid URL: {}. Expected an http, https or mailto link",
+                url
+            ));
+        }
+        if !priorities::scale().contains(priority) {
+            return Err(format!(
+                "Invalid priority: {} for task: {}. Expected {}",
+                priority,
+                tasks.join(", "),
+                priorities::scale().expected()
+            ));
+        }
+        let context = context::load(base);
+        let mut ids = Vec::new();
+        // Each item is built in full before it is added, so it starts at revision 0
+        for task in &tasks {
+            let mut todo = todo_list.new_todo(task, priority);
+            if let Some(rule) = &self.recur {
+                todo.recur = Some(rule.clone());
+                todo.workdays = self.workdays;
+                // Its occurrences stay in the timezone it was added in
+                todo.timezone = self.timezone.clone().or_else(|| config.timezone.clone());
+            }
+            todo.url = self.url.clone();
+            todo.due = due;
+            todo.location = self.location.clone();
+            todo.project = self.project.clone();
+            todo.estimate = estimate;
+            todo.created_by = config.current_user();
+            todo.apply_context(context.as_ref());
+            for tag in &defaults.tags {
+                if !todo.tags.contains(tag) {
+                    todo.tags.push(tag.clone());
+                }
+            }
+            if suggest {
+                todo.priority = triage::suggest(&todo, &rules, priority, now()).priority;
+            }
+            ids.push(todo.id);
+            todo_list.todos.push(todo);
+        }
+        Ok(ids)
+    }
+}
+/// The changes `edit` makes to a todo item.
+#[derive(Debug, StructOpt)]
+pub struct TodoEdit {
+    /// The unique identifier of the todo item to be edited, a prefix of its hash or part of
+    /// its title.
+    pub id: TaskRef,
+    /// The new task description.
+    #[structopt(long)]
+    pub task: Option<String>,
+    /// The new priority, a number from 1 to 5 or the name of a configured priority.
+    #[structopt(long)]
+    pub priority: Option<String>,
+    /// The new due date, e.g. "2024-06-01", "2024-06-01 17:00", "today 17:00" or "fri 9am",
+    /// in the configured timezone.
+    #[structopt(long)]
+    pub due: Option<String>,
+    /// Remove the due date.
+    #[structopt(long, conflicts_with = "due")]
+    pub clear_due: bool,
+    /// Only edit the todo item if it is still at this revision, e.g. as read by show.
+    #[structopt(long)]
+    pub if_rev: Option<u64>,
+    /// Arguments after the ID, only accepted so that the old `edit <task> <id>` order gets
+    /// the error of `check_arguments` rather than a generic one.
+    #[structopt(hidden = true)]
+    pub extra: Vec<String>,
+}
+impl TodoEdit {
+    /// Rejects arguments after the ID, pointing out the new argument order to anyone still
+    /// giving the task first, as in `edit "Pay rent" 2`.
+    pub fn check_arguments(&self) -> Result<(), String> {
+        match (&self.id, self.extra.first()) {
+            (_, None) => Ok(()),
+            (TaskRef::Title(title), Some(extra)) if extra.parse::<u64>().is_ok() => Err(format!(
+                "'{}' is not a task ID. The ID comes first now: edit <id> --task <new task>",
+                title
+            )),
+            (_, Some(extra)) => Err(format!("Unexpected argument '{}'", extra)),
+        }
+    }
+
+    /// Checks every change and makes them all to the todo item in `todo_list`, as one
+    /// revision. Nothing is changed if any of them is invalid.
+    pub fn apply_to(self, todo_list: &mut TodoList, config: &Config) -> Result<(), String> {
+        self.check_arguments()?;
+        if self.task.is_none() && self.priority.is_none() && self.due.is_none() && !self.clear_due {
+            return Err(
+                "Nothing to change: give --task, --priority, --due or --clear-due".to_string(),
+            );
+        }
+        let zone = config.timezone()?;
+        let locale = config.locale()?;
+        let workdays = config.workdays()?;
+        let due = self
+            .due
+            .as_deref()
+            .map(|due| dates::parse_due_in(due, now(), zone, locale, &workdays))
+            .transpose()?;
+        let priority = self
+            .priority
+            .as_deref()
+            .map(|priority| priorities::scale().parse(priority))
+            .transpose()?;
+        let id = todo_list.resolve(&self.id)?;
+        let todo = todo_list
+            .todo_mut(id)
+            .ok_or_else(|| format!("Invalid ID: {}", self.id))?;
+        if let Some(task) = self.task {
+            todo.task = task;
+        }
+        if let Some(priority) = priority {
+            todo.priority = priority;
+        }
+        if due.is_some() || self.clear_due {
+            todo.due = due;
+        }
+        Ok(())
+    }
+}
+/// The todo items `remove` removes.
+#[derive(Debug, StructOpt)]
+pub struct Removal {
+    /// The unique identifiers of the todo items to be removed, prefixes of their
+    /// hashes or parts of their titles.
+    #[structopt(required = true)]
+    pub ids: Vec<TaskRef>,
+    /// Only remove the todo item if it is still at this revision. Takes a single ID.
+    #[structopt(long)]
+    pub if_rev: Option<u64>,
+    /// What to do with the todo items depending on the removed ones: "unblock" them,
+    /// "block" the removal, "cascade" it to them, or "relink" them to the removed items'
+    /// own dependencies.
+    #[structopt(
+        long,
+        value_name = "mode",
+        default_value = "unblock",
+        possible_values = &["unblock", "block", "cascade", "relink"]
+    )]
+    pub dependents: Dependents,
+}
+/// What `remove` does with the todo items that depend on the ones removed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dependents {
+    /// Drop the dependency, as for an item that is done.
+    Unblock,
+    /// Remove nothing.
+    Block,
+    /// Remove them too, and the items depending on those.
+    Cascade,
+    /// Make them depend on what the removed item depended on instead.
+    Relink,
+}
+impl std::str::FromStr for Dependents {
+    type Err = String;
+    fn from_str(mode: &str) -> Result<Dependents, String> {
+        match mode {
+            "unblock" => Ok(Dependents::Unblock),
+            "block" => Ok(Dependents::Block),
+            "cascade" => Ok(Dependents::Cascade),
+            "relink" => Ok(Dependents::Relink),
+            _ => Err(format!(
+                "Unknown mode: {}. Expected unblock, block, cascade or relink",
+                mode
+            )),
+        }
+    }
+}
+/// Returns whether a count is zero, so it can be left out when serializing.
+fn is_zero(count: &u64) -> bool {
+    *count == 0
+}
+/// Returns whether a flag is unset, for skipping it when serializing.
+fn is_false(flag: &bool) -> bool {
+    !*flag
+}
+#[cfg(test)]
+mod tests {
+    use super::{Dependents, TodoEdit};
+    use crate::batch::{apply_batch, parse_batch, Batched};
+    use crate::config::Config;
+    use crate::dates::{DateFormat, Zone};
+    use crate::workdays::Workdays;
+    use crate::TodoList;
+    use std::path::Path;
+    use structopt::StructOpt;
+    /// Parses the arguments of an `edit` command.
+    fn edit(args: &[&str]) -> TodoEdit {
+        TodoEdit::from_iter_safe(std::iter::once("edit").chain(args.iter().copied())).unwrap()
+    }
+    #[test]
+    fn test_clear() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+        todo_list.add_todo("task 2", 2);
+        todo_list.clear_todo();
+        assert_eq!(todo_list.todos.len(), 0);
+
+        todo_list.clear_todo();
+        assert_eq!(todo_list.todos.len(), 0);
+    }
+    #[test]
+    fn test_add() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+        assert_eq!(todo_list.todos.len(), 1);
+
+        todo_list.add_todo("Invalid task", 0);
+        assert_eq!(todo_list.todos.len(), 1);
+
+        todo_list.add_todo("Invalid task", 6);
+        assert_eq!(todo_list.todos.len(), 1);
+    }
+    #[test]
+    fn test_delete() {
+        let mut todo_list = TodoList::new();
+        todo_list.remove_todo(1);
+        assert_eq!(todo_list.todos.len(), 0);
+
+        todo_list.add_todo("task 1", 1);
+        todo_list.add_todo("task 2", 2);
+        todo_list.add_todo("task 3", 3);
+
+        todo_list.remove_todo(0);
+        assert_eq!(todo_list.todos.len(), 3);
+
+        todo_list.remove_todo(4);
+        assert_eq!(todo_list.todos.len(), 3);
+
+        todo_list.remove_todo(1);
+        assert_eq!(todo_list.todos.len(), 2);
+
+        assert_eq!(todo_list.todos[0].task, "task 2");
+        assert_eq!(todo_list.todos[1].task, "task 3");
+    }
+    #[test]
+    fn test_edit() {
+        let mut todo_list = TodoList::new();
+
+        todo_list.add_todo("task 1", 1);
+        todo_list.add_todo("task 2", 2);
+        todo_list.add_todo("task 3", 3);
+
+        edit(&["1", "--task", "edited task"])
+            .apply_to(&mut todo_list, &Config::default())
+            .unwrap();
+        assert_eq!(todo_list.todos[0].task, "edited task");
+
+        assert_eq!(
+            edit(&["4", "--task", "bad edited task"]).apply_to(&mut todo_list, &Config::default()),
+            Err("Invalid ID: 4".to_string())
+        );
+        assert_eq!(todo_list.todos[0].task, "edited task");
+        assert_eq!(todo_list.todos[1].task, "task 2");
+        assert_eq!(todo_list.todos[2].task, "task 3");
+
+        // Several fields change together, as one revision
+        edit(&[
+            "2",
+            "--task",
+            "Pay rent",
+            "--priority",
+            "5",
+            "--due",
+            "2024-06-01",
+        ])
+        .apply_to(&mut todo_list, &Config::default())
+        .unwrap();
+        assert_eq!(todo_list.todos[1].task, "Pay rent");
+        assert_eq!(todo_list.todos[1].priority, 5);
+        assert!(todo_list.todos[1].due.is_some());
+        assert_eq!(todo_list.todos[1].rev, 1);
+        edit(&["2", "--clear-due"])
+            .apply_to(&mut todo_list, &Config::default())
+            .unwrap();
+        assert_eq!(todo_list.todos[1].due, None);
+        assert_eq!(todo_list.todos[1].rev, 2);
+
+        assert_eq!(
+            edit(&["2", "--priority", "9"]).apply_to(&mut todo_list, &Config::default()),
+            Err("Invalid priority: '9'. Expected 1 to 5".to_string())
+        );
+        assert_eq!(
+            edit(&["2"]).apply_to(&mut todo_list, &Config::default()),
+            Err("Nothing to change: give --task, --priority, --due or --clear-due".to_string())
+        );
+        assert!(
+            TodoEdit::from_iter_safe(["edit", "--clear-due", "--due", "2024-06-01", "2"]).is_err()
+        );
+        let error = edit(&["Pay rent", "2"])
+            .apply_to(&mut todo_list, &Config::default())
+            .unwrap_err();
+        assert!(error.contains("The ID comes first now"));
+        assert_eq!(todo_list.todos[1].rev, 2);
+
+        // Part of the title picks the task out too
+        edit(&["pay", "--task", "Pay the rent"])
+            .apply_to(&mut todo_list, &Config::default())
+            .unwrap();
+        assert_eq!(todo_list.todos[1].task, "Pay the rent");
+    }
+    #[test]
+    fn test_delegate() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+        todo_list.add_todo("task 2", 2);
+        todo_list.add_todo("task 3", 3);
+
+        todo_list.delegate_todo(1, "alice");
+        todo_list.delegate_todo(3, "bob");
+        todo_list.delegate_todo(4, "carol");
+        assert_eq!(todo_list.todos[0].assignee.as_deref(), Some("alice"));
+        assert_eq!(todo_list.todos[1].assignee, None);
+
+        todo_list.retain_assignee("alice");
+        assert_eq!(todo_list.todos.len(), 1);
+        assert_eq!(todo_list.todos[0].task, "task 1");
+    }
+    #[test]
+    fn test_creator() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+        todo_list.add_todo("task 2", 2);
+
+        todo_list.set_creator(2, "alice");
+        todo_list.set_creator(3, "bob");
+        assert_eq!(todo_list.todos[0].created_by, None);
+
+        todo_list.retain_creator("alice");
+        assert_eq!(todo_list.todos.len(), 1);
+        assert_eq!(todo_list.todos[0].task, "task 2");
+    }
+    #[test]
+    fn test_attach() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+        todo_list.add_todo("task 2", 2);
+
+        assert!(todo_list.attach_file(1, "/tmp/spec.pdf"));
+        assert!(todo_list.attach_file(1, "/tmp/spec.pdf"));
+        assert!(!todo_list.attach_file(3, "/tmp/spec.pdf"));
+        assert_eq!(todo_list.todos[0].attachments, vec!["/tmp/spec.pdf"]);
+
+        let removed = todo_list.remove_todo(1).unwrap();
+        assert_eq!(removed.attachments, vec!["/tmp/spec.pdf"]);
+        assert!(todo_list.todos[0].attachments.is_empty());
+    }
+    #[test]
+    fn test_url() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+
+        todo_list.set_url(1, "not a url");
+        assert_eq!(todo_list.todos[0].url, None);
+
+        todo_list.set_url(1, "https://example.com/pull/1");
+        assert_eq!(
+            todo_list.todos[0].url.as_deref(),
+            Some("https://example.com/pull/1")
+        );
+
+        assert!(crate::is_url("mailto:alice@example.com"));
+        assert!(!crate::is_url("https://"));
+        assert!(!crate::is_url("https://exa mple.com"));
+        assert!(crate::is_url("HTTP://example.com"));
+        assert!(!crate::is_url("javascript://%0Aalert(document.cookie)"));
+        assert!(!crate::is_url("file:///etc/passwd"));
+    }
+    #[test]
+    fn test_age() {
+        let day = 24 * 60 * 60;
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+        todo_list.add_todo("task 2", 4);
+        todo_list.add_todo("task 3", 5);
+        let created = todo_list.todos[0].created;
+
+        assert_eq!(todo_list.age_todos(7, created + 6 * day), 0);
+
+        assert_eq!(todo_list.age_todos(7, created + 15 * day), 2);
+        assert_eq!(todo_list.todos[0].priority, 3);
+        assert_eq!(todo_list.todos[1].priority, 5);
+        assert_eq!(todo_list.todos[2].priority, 5);
+
+        // Aging again is a no-op until another full period has passed.
+        assert_eq!(todo_list.age_todos(7, created + 20 * day), 0);
+        assert_eq!(todo_list.age_todos(7, created + 21 * day), 1);
+        assert_eq!(todo_list.todos[0].priority, 4);
+    }
+    #[test]
+    fn test_reminders() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+        todo_list.add_todo("task 2", 2);
+
+        todo_list.add_reminder(1, 200);
+        todo_list.add_reminder(1, 100);
+        todo_list.add_reminder(1, 100);
+        todo_list.add_reminder(2, 300);
+        todo_list.add_reminder(3, 100);
+        let times: Vec<i64> = todo_list.todos[0].reminders.iter().map(|r| r.at).collect();
+        assert_eq!(times, vec![100, 200]);
+
+        let fired = todo_list.fire_reminders(250);
+        assert_eq!(
+            fired,
+            vec![
+                (1, "task 1".to_string(), 100),
+                (1, "task 1".to_string(), 200)
+            ]
+        );
+        assert!(todo_list.fire_reminders(250).is_empty());
+        assert_eq!(todo_list.fire_reminders(300).len(), 1);
+    }
+    #[test]
+    fn test_tags() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+
+        todo_list.add_tags(1, &["work".to_string(), "urgent".to_string()]);
+        todo_list.add_tags(1, &["work".to_string()]);
+        assert_eq!(todo_list.todos[0].tags, vec!["work", "urgent"]);
+    }
+    #[test]
+    fn test_dependencies() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+        todo_list.add_todo("task 2", 2);
+        todo_list.add_todo("task 3", 3);
+        todo_list.add_todo("task 4", 4);
+
+        todo_list.add_dependencies(4, &[3, 1]);
+        todo_list.add_dependencies(3, &[2]);
+        assert_eq!(todo_list.todos[3].depends, vec![1, 3]);
+        // Cycles and unknown IDs are refused.
+        todo_list.add_dependencies(2, &[4]);
+        todo_list.add_dependencies(1, &[1]);
+        todo_list.add_dependencies(1, &[5]);
+        assert!(todo_list.todos[0].depends.is_empty());
+        assert!(todo_list.todos[1].depends.is_empty());
+
+        // Removing an item unblocks its dependents and renumbers the rest.
+        todo_list.remove_todo(1);
+        assert_eq!(todo_list.todos[2].depends, vec![2]);
+        assert_eq!(todo_list.todos[1].depends, vec![1]);
+        todo_list.remove_dependencies(3, &[2]);
+        assert!(todo_list.todos[2].depends.is_empty());
+    }
+    #[test]
+    fn test_prepare_removal() {
+        // 2 and 3 depend on 1, and 4 on 3 and 2
+        let list = || {
+            let mut todo_list = TodoList::new();
+            for task in ["task 1", "task 2", "task 3", "task 4", "task 5"] {
+                todo_list.add_todo(task, 3);
+            }
+            todo_list.add_dependencies(1, &[5]);
+            todo_list.add_dependencies(2, &[1]);
+            todo_list.add_dependencies(3, &[1]);
+            todo_list.add_dependencies(4, &[2, 3]);
+            todo_list
+        };
+        let mut todo_list = list();
+        assert_eq!(todo_list.dependents(&[1]), [2, 3]);
+        assert_eq!(
+            todo_list.prepare_removal(&[1, 1], Dependents::Unblock),
+            Ok(vec![1])
+        );
+
+        assert_eq!(
+            todo_list.prepare_removal(&[1], Dependents::Block),
+            Err(
+                "Other tasks depend on it: 2 (task 2), 3 (task 3). Use --dependents cascade to \
+                 remove them too, relink to pass on its dependencies, or unblock to drop them"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            todo_list.prepare_removal(&[4], Dependents::Block),
+            Ok(vec![4])
+        );
+
+        assert_eq!(
+            todo_list.prepare_removal(&[1], Dependents::Cascade),
+            Ok(vec![4, 3, 2, 1])
+        );
+
+        // Dependencies are followed through every removed item
+        assert_eq!(
+            todo_list.prepare_removal(&[1, 3], Dependents::Relink),
+            Ok(vec![3, 1])
+        );
+        assert_eq!(todo_list.todos[1].depends, [5]);
+        assert_eq!(todo_list.todos[3].depends, [2, 5]);
+        assert!(todo_list.todos[1].modified.is_some());
+        todo_list.remove_todo(3);
+        todo_list.remove_todo(1);
+        assert_eq!(todo_list.todos[0].depends, [3]);
+        assert_eq!(todo_list.todos[1].depends, [1, 3]);
+        todo_list = list();
+        assert_eq!(
+            todo_list.prepare_removal(&[1], Dependents::Relink),
+            Ok(vec![1])
+        );
+        assert_eq!(todo_list.todos[3].depends, [2, 3]);
+    }
+    #[test]
+    fn test_modified() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+        todo_list.add_todo("task 2", 2);
+        assert_eq!(todo_list.todos[0].modified, None);
+        assert_eq!(
+            todo_list.todos[0].last_modified(),
+            todo_list.todos[0].created
+        );
+
+        edit(&["2", "--task", "edited task"])
+            .apply_to(&mut todo_list, &Config::default())
+            .unwrap();
+        assert!(todo_list.todos[1].modified.is_some());
+        assert_eq!(todo_list.todos[0].modified, None);
+
+        assert!(edit(&["0", "--task", "bad edited task"])
+            .apply_to(&mut todo_list, &Config::default())
+            .is_err());
+        assert_eq!(todo_list.todos[0].modified, None);
+    }
+    #[test]
+    fn test_checksum() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+        assert!(todo_list.verify_checksum());
+
+        todo_list.checksum = Some(todo_list.compute_checksum());
+        assert!(todo_list.verify_checksum());
+
+        todo_list.todos[0].task = "tampered".to_string();
+        assert!(!todo_list.verify_checksum());
+    }
+    #[test]
+    fn test_recurrence() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+
+        todo_list.set_recurrence(1, "FREQ=SOMETIMES");
+        assert_eq!(todo_list.todos[0].recur, None);
+
+        todo_list.set_recurrence(2, "FREQ=DAILY");
+        assert_eq!(todo_list.todos[0].recur, None);
+
+        todo_list.set_recurrence(1, "FREQ=DAILY;COUNT=2");
+        assert_eq!(
+            todo_list.todos[0].recur.as_deref(),
+            Some("FREQ=DAILY;COUNT=2")
+        );
+
+        let created = todo_list.todos[0].created;
+        let none = Workdays::default();
+        assert_eq!(
+            todo_list.todos[0].next_occurrence(created, &none),
+            Some(created + 24 * 3600)
+        );
+        assert_eq!(
+            todo_list.todos[0].next_occurrence(created + 24 * 3600, &none),
+            None
+        );
+
+        // Moved off the weekend and a holiday on Monday, from Friday to Tuesday
+        let todo = &mut todo_list.todos[0];
+        todo.recur = Some("FREQ=DAILY".to_string());
+        todo.created = 1717146000; // 2024-05-31 09:00, a Friday
+        todo.workdays = true;
+        let friday = todo.created;
+        let holidays = Workdays::parse(&["2024-06-03".to_string()]).unwrap();
+        assert_eq!(
+            todo.next_occurrence(friday, &holidays),
+            Some(friday + 4 * 24 * 3600)
+        );
+        todo.workdays = false;
+        assert_eq!(
+            todo.next_occurrence(friday, &holidays),
+            Some(friday + 24 * 3600)
+        );
+
+        // In its own timezone, added at 23:00 on Friday in Utc, 01:00 on Saturday there: the
+        // occurrence on Sunday moves to Tuesday at 01:00, 23:00 on Monday in Utc
+        todo.workdays = true;
+        todo.created = 1717196400;
+        todo.timezone = Some("+02:00".to_string());
+        assert_eq!(
+            todo.next_occurrence(todo.created, &holidays),
+            Some(1717455600)
+        );
+    }
+    #[test]
+    fn test_revisions() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Pay rent", 3);
+        assert_eq!(todo_list.todos[0].rev, 0);
+        assert_eq!(todo_list.check_revision(1, 0), Ok(()));
+        edit(&["1", "--task", "Pay the rent"])
+            .apply_to(&mut todo_list, &Config::default())
+            .unwrap();
+        todo_list.set_due(1, 1717200000);
+        assert_eq!(todo_list.todos[0].rev, 2);
+        assert_eq!(
+            todo_list.check_revision(1, 1),
+            Err("Task 1 has changed: it is at revision 2, not 1".to_string())
+        );
+        assert_eq!(
+            todo_list.check_revision(2, 0),
+            Err("Invalid ID".to_string())
+        );
+        // Unchanged items are saved without a revision, as before revisions existed
+        assert!(!serde_json::to_string(&TodoList::new().todos)
+            .unwrap()
+            .contains("rev"));
+
+        let batch = parse_batch("edit 1 --task \"Pay rent\" --if-rev 1").unwrap();
+        assert_eq!(
+            apply_batch(&mut todo_list, batch, &Config::default(), Path::new(".")).unwrap_err(),
+            "Line 1: Task 1 has changed: it is at revision 2, not 1"
+        );
+    }
+    #[test]
+    fn test_add_tasks() {
+        let config: Config = serde_json::from_str(r#"{ "default_priority": 2 }"#).unwrap();
+        let mut todo_list = TodoList::new();
+        for (line, ids) in [
+            ("add \"Pay rent\" 5", vec![1]),
+            ("add one two three --priority 4", vec![2, 3, 4]),
+            ("add \"Read docs\" Water", vec![5, 6]),
+            ("add Solo", vec![7]),
+        ] {
+            let (_, command) = parse_batch(line).unwrap().remove(0);
+            let Batched::Add(new_todo) = command else {
+                panic!("Not an add: {}", line);
+            };
+            assert_eq!(
+                new_todo.add_to(&mut todo_list, &config, Path::new(".")),
+                Ok(ids)
+            );
+        }
+        let priorities: Vec<(&str, u64)> = todo_list
+            .todos
+            .iter()
+            .map(|todo| (todo.task.as_str(), todo.priority))
+            .collect();
+        assert_eq!(
+            priorities,
+            [
+                ("Pay rent", 5),
+                ("one", 4),
+                ("two", 4),
+                ("three", 4),
+                ("Read docs", 2),
+                ("Water", 2),
+                ("Solo", 2)
+            ]
+        );
+    }
+    #[test]
+    fn test_project_defaults() {
+        let config: Config = serde_json::from_str(
+            r#"{ "projects": { "work": { "priority": 4, "tags": ["work"], "due": "2d" } } }"#,
+        )
+        .unwrap();
+        let mut todo_list = TodoList::new();
+        for line in [
+            "add \"Fix login bug\" --list work",
+            "add \"Ship it\" 5 --project work --due 2024-06-01",
+            "add \"Water the plants\"",
+        ] {
+            let (_, command) = parse_batch(line).unwrap().remove(0);
+            let Batched::Add(new_todo) = command else {
+                panic!("Not an add: {}", line);
+            };
+            new_todo
+                .add_to(&mut todo_list, &config, Path::new("."))
+                .unwrap();
+        }
+        let [fix, ship, water] = &todo_list.todos[..] else {
+            panic!("Not three todos");
+        };
+        assert_eq!(
+            (fix.priority, &fix.tags[..]),
+            (4, &["work".to_string()][..])
+        );
+        // Due two days after it was added, give or take the second it took
+        let offset = fix.due.unwrap() - fix.created;
+        assert!((2 * 24 * 3600 - 1..=2 * 24 * 3600).contains(&offset));
+        // What is given explicitly wins over the defaults
+        assert_eq!((ship.priority, ship.due), (5, Some(1717200000)));
+        assert_eq!(ship.tags, ["work"]);
+        assert_eq!((water.priority, water.due), (3, None));
+        assert!(water.tags.is_empty());
+    }
+    #[test]
+    fn test_checklist() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Pay rent", 5);
+        todo_list.add_todo("Read docs", 2);
+        todo_list.set_due(1, 1717200000);
+        todo_list.set_url(2, "https://example.com");
+        assert_eq!(
+            todo_list.checklist(&DateFormat::default()),
+            "[ ] Pay rent (due 2024-06-01)\n[ ] Read docs https://example.com\n"
+        );
+        assert!(todo_list
+            .checklist(&DateFormat::parse("iso").unwrap())
+            .starts_with("[ ] Pay rent (due 2024-06-01T00:00:00Z)\n"));
+    }
+    #[test]
+    fn test_export_part() {
+        let mut todo_list = TodoList::new();
+        for task in ["Pay rent", "Draft the offer, v2", "Send the offer"] {
+            todo_list.add_todo(task, 3);
+        }
+        todo_list.set_due(2, 1717243200);
+        todo_list.add_tags(2, &["client-x".to_string(), "sales".to_string()]);
+        todo_list.todos[1].estimate = Some(5400);
+        todo_list.add_dependencies(3, &[1, 2]);
+        todo_list.todos.remove(0);
+        assert_eq!(
+            todo_list.to_csv(Zone::Utc),
+            "id,task,priority,due,tags,project,assignee,location,url,estimate\r\n\
+             2,\"Draft the offer, v2\",3,2024-06-01 12:00,client-x sales,,,,,1h30m\r\n\
+             3,Send the offer,3,,,,,,,\r\n"
+        );
+        todo_list.renumber();
+        let ids: Vec<u64> = todo_list.todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, [1, 2]);
+        assert_eq!(todo_list.todos[1].depends, [1]);
+    }
+    #[test]
+    fn test_write_ndjson() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("task 1", 1);
+        todo_list.add_todo("task 2", 2);
+        let mut output = Vec::new();
+        todo_list.write_ndjson(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["task"], "task 2");
+        assert!(output.ends_with('\n'));
+    }
+}
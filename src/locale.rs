@@ -0,0 +1,459 @@
+//! The language dates are typed in, configured with `locale`: the names of its months and
+//! weekdays and its words for today and tomorrow, and the order of the day and month in its
+//! numeric dates, e.g. `15.11.2026` in German or `11/15/2026` in American English.
+//!
+//! The English names and ISO dates are understood whatever the locale. Names are compared
+//! in any case and with or without their accents, so `marz` finds `März`, and abbreviations
+//! may end in a period, e.g. `Di.` or `févr.`.
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::fold;
+
+/// A language dates can be typed in.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Locale {
+    /// English, with ISO dates only, since its numeric dates are ambiguous.
+    #[default]
+    En,
+    /// American English: month, day and year.
+    EnUs,
+    /// British English: day, month and year.
+    EnGb,
+    De,
+    Es,
+    Fr,
+    It,
+    Nl,
+    Pt,
+}
+
+/// The order of the parts of a numeric date.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Order {
+    DayMonth,
+    MonthDay,
+}
+
+/// The words of a language.
+struct Words {
+    today: &'static [&'static str],
+    tomorrow: &'static [&'static str],
+    /// The names of each weekday from Monday, full and abbreviated.
+    weekdays: [&'static [&'static str]; 7],
+    /// The names of each month from January, full and abbreviated.
+    months: [&'static [&'static str]; 12],
+    /// Words that may come between a day, month and year, e.g. `de` in `15 de noviembre`.
+    fillers: &'static [&'static str],
+}
+
+const ENGLISH: Words = Words {
+    today: &["today"],
+    tomorrow: &["tomorrow"],
+    weekdays: [
+        &["monday", "mon"],
+        &["tuesday", "tue", "tues"],
+        &["wednesday", "wed"],
+        &["thursday", "thu", "thurs"],
+        &["friday", "fri"],
+        &["saturday", "sat"],
+        &["sunday", "sun"],
+    ],
+    months: [
+        &["january", "jan"],
+        &["february", "feb"],
+        &["march", "mar"],
+        &["april", "apr"],
+        &["may"],
+        &["june", "jun"],
+        &["july", "jul"],
+        &["august", "aug"],
+        &["september", "sep", "sept"],
+        &["october", "oct"],
+        &["november", "nov"],
+        &["december", "dec"],
+    ],
+    fillers: &["of", "the"],
+};
+
+const GERMAN: Words = Words {
+    today: &["heute"],
+    tomorrow: &["morgen"],
+    weekdays: [
+        &["montag", "mo"],
+        &["dienstag", "di"],
+        &["mittwoch", "mi"],
+        &["donnerstag", "do"],
+        &["freitag", "fr"],
+        &["samstag", "sonnabend", "sa"],
+        &["sonntag", "so"],
+    ],
+    months: [
+        &["januar", "jänner", "jan"],
+        &["februar", "feb"],
+        &["märz", "mär", "mrz"],
+        &["april", "apr"],
+        &["mai"],
+        &["juni", "jun"],
+        &["juli", "jul"],
+        &["august", "aug"],
+        &["september", "sep", "sept"],
+        &["oktober", "okt"],
+        &["november", "nov"],
+        &["dezember", "dez"],
+    ],
+    fillers: &[],
+};
+
+const SPANISH: Words = Words {
+    today: &["hoy"],
+    tomorrow: &["mañana"],
+    weekdays: [
+        &["lunes", "lun"],
+        &["martes", "mar"],
+        &["miércoles", "mié"],
+        &["jueves", "jue"],
+        &["viernes", "vie"],
+        &["sábado", "sáb"],
+        &["domingo", "dom"],
+    ],
+    months: [
+        &["enero", "ene"],
+        &["febrero", "feb"],
+        &["marzo", "mar"],
+        &["abril", "abr"],
+        &["mayo", "may"],
+        &["junio", "jun"],
+        &["julio", "jul"],
+        &["agosto", "ago"],
+        &["septiembre", "setiembre", "sep", "sept"],
+        &["octubre", "oct"],
+        &["noviembre", "nov"],
+        &["diciembre", "dic"],
+    ],
+    fillers: &["de", "del"],
+};
+
+const FRENCH: Words = Words {
+    today: &["aujourd'hui", "aujourd’hui"],
+    tomorrow: &["demain"],
+    weekdays: [
+        &["lundi", "lun"],
+        &["mardi", "mar"],
+        &["mercredi", "mer"],
+        &["jeudi", "jeu"],
+        &["vendredi", "ven"],
+        &["samedi", "sam"],
+        &["dimanche", "dim"],
+    ],
+    months: [
+        &["janvier", "janv"],
+        &["février", "févr", "fév"],
+        &["mars"],
+        &["avril", "avr"],
+        &["mai"],
+        &["juin"],
+        &["juillet", "juil"],
+        &["août"],
+        &["septembre", "sept"],
+        &["octobre", "oct"],
+        &["novembre", "nov"],
+        &["décembre", "déc"],
+    ],
+    fillers: &["le"],
+};
+
+const ITALIAN: Words = Words {
+    today: &["oggi"],
+    tomorrow: &["domani"],
+    weekdays: [
+        &["lunedì", "lun"],
+        &["martedì", "mar"],
+        &["mercoledì", "mer"],
+        &["giovedì", "gio"],
+        &["venerdì", "ven"],
+        &["sabato", "sab"],
+        &["domenica", "dom"],
+    ],
+    months: [
+        &["gennaio", "gen"],
+        &["febbraio", "feb"],
+        &["marzo", "mar"],
+        &["aprile", "apr"],
+        &["maggio", "mag"],
+        &["giugno", "giu"],
+        &["luglio", "lug"],
+        &["agosto", "ago"],
+        &["settembre", "set"],
+        &["ottobre", "ott"],
+        &["novembre", "nov"],
+        &["dicembre", "dic"],
+    ],
+    fillers: &["il"],
+};
+
+const DUTCH: Words = Words {
+    today: &["vandaag"],
+    tomorrow: &["morgen"],
+    weekdays: [
+        &["maandag", "ma"],
+        &["dinsdag", "di"],
+        &["woensdag", "wo"],
+        &["donderdag", "do"],
+        &["vrijdag", "vr"],
+        &["zaterdag", "za"],
+        &["zondag", "zo"],
+    ],
+    months: [
+        &["januari", "jan"],
+        &["februari", "feb"],
+        &["maart", "mrt"],
+        &["april", "apr"],
+        &["mei"],
+        &["juni", "jun"],
+        &["juli", "jul"],
+        &["augustus", "aug"],
+        &["september", "sep", "sept"],
+        &["oktober", "okt"],
+        &["november", "nov"],
+        &["december", "dec"],
+    ],
+    fillers: &[],
+};
+
+const PORTUGUESE: Words = Words {
+    today: &["hoje"],
+    tomorrow: &["amanhã"],
+    weekdays: [
+        &["segunda-feira", "segunda", "seg"],
+        &["terça-feira", "terça", "ter"],
+        &["quarta-feira", "quarta", "qua"],
+        &["quinta-feira", "quinta", "qui"],
+        &["sexta-feira", "sexta", "sex"],
+        &["sábado", "sáb"],
+        &["domingo", "dom"],
+    ],
+    months: [
+        &["janeiro", "jan"],
+        &["fevereiro", "fev"],
+        &["março", "mar"],
+        &["abril", "abr"],
+        &["maio", "mai"],
+        &["junho", "jun"],
+        &["julho", "jul"],
+        &["agosto", "ago"],
+        &["setembro", "set"],
+        &["outubro", "out"],
+        &["novembro", "nov"],
+        &["dezembro", "dez"],
+    ],
+    fillers: &["de"],
+};
+
+impl Locale {
+    /// Parses a locale such as `de`, `fr_FR`, `pt-BR.UTF-8` or `en-US`, by its language
+    /// and, for English, its country.
+    pub fn parse(locale: &str) -> Result<Locale, String> {
+        let lower = locale.trim().to_lowercase().replace('_', "-");
+        let lower = lower.split('.').next().unwrap_or_default();
+        let (language, country) = lower.split_once('-').unwrap_or((lower, ""));
+        match (language, country) {
+            ("en", "us") => Ok(Locale::EnUs),
+            ("en", "") => Ok(Locale::En),
+            ("en", _) => Ok(Locale::EnGb),
+            ("de", _) => Ok(Locale::De),
+            ("es", _) => Ok(Locale::Es),
+            ("fr", _) => Ok(Locale::Fr),
+            ("it", _) => Ok(Locale::It),
+            ("nl", _) => Ok(Locale::Nl),
+            ("pt", _) => Ok(Locale::Pt),
+            _ => Err(format!(
+                "Unknown locale: '{}'. Expected en, en-US, en-GB, de, es, fr, it, nl or pt",
+                locale
+            )),
+        }
+    }
+
+    fn words(self) -> &'static Words {
+        match self {
+            Locale::En | Locale::EnUs | Locale::EnGb => &ENGLISH,
+            Locale::De => &GERMAN,
+            Locale::Es => &SPANISH,
+            Locale::Fr => &FRENCH,
+            Locale::It => &ITALIAN,
+            Locale::Nl => &DUTCH,
+            Locale::Pt => &PORTUGUESE,
+        }
+    }
+
+    fn order(self) -> Option<Order> {
+        match self {
+            Locale::En => None,
+            Locale::EnUs => Some(Order::MonthDay),
+            _ => Some(Order::DayMonth),
+        }
+    }
+
+    /// Parses a date typed in the locale relative to `today`: its word for today or
+    /// tomorrow, a weekday meaning its next occurrence after today, a numeric date such as
+    /// `15.11.2026` or `15.11.`, or a day and a month name such as `15. November 2026` or
+    /// `15 de noviembre`. A date without a year means its next occurrence from today.
+    pub fn parse_date(self, phrase: &str, today: NaiveDate) -> Option<NaiveDate> {
+        let words: Vec<String> = phrase
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty())
+            .map(|word| fold::key(word.trim_end_matches('.'), true))
+            .collect();
+        let tables = [self.words(), &ENGLISH];
+        let named =
+            |word: &str, names: &[&str]| names.iter().any(|name| fold::key(name, true) == word);
+        if let [word] = words.as_slice() {
+            for table in tables {
+                if named(word, table.today) {
+                    return Some(today);
+                }
+                if named(word, table.tomorrow) {
+                    return today.succ_opt();
+                }
+                if let Some(weekday) = table.weekdays.iter().position(|names| named(word, names)) {
+                    let ahead =
+                        (7 + weekday as i64 - today.weekday().num_days_from_monday() as i64 - 1)
+                            % 7
+                            + 1;
+                    return Some(today + Duration::days(ahead));
+                }
+            }
+            return self.parse_numeric(phrase.trim(), today);
+        }
+        // A day, a month name and perhaps a year, in either order, e.g. "November 15, 2026"
+        let words: Vec<&String> = words
+            .iter()
+            .filter(|word| !tables.iter().any(|table| named(word, table.fillers)))
+            .collect();
+        let month = |word: &str| {
+            tables
+                .iter()
+                .find_map(|table| table.months.iter().position(|names| named(word, names)))
+                .map(|month| month as u32 + 1)
+        };
+        let (day, month, year) = match words.as_slice() {
+            [day, name] | [name, day] if month(name).is_some() => (day, month(name)?, None),
+            [day, name, year] | [name, day, year] if month(name).is_some() => {
+                (day, month(name)?, Some(year))
+            }
+            _ => return None,
+        };
+        let day = parse_day(day)?;
+        let year = match year {
+            Some(year) => Some(parse_year(year)?),
+            None => None,
+        };
+        date(year, month, day, today)
+    }
+
+    /// Parses a numeric date in the locale's order, separated by `.`, `/` or `-`.
+    fn parse_numeric(self, word: &str, today: NaiveDate) -> Option<NaiveDate> {
+        let order = self.order()?;
+        let parts: Vec<&str> = word.trim_end_matches('.').split(['.', '/', '-']).collect();
+        if parts.iter().any(|part| part.is_empty()) {
+            return None;
+        }
+        let (first, second, year) = match parts.as_slice() {
+            [first, second] => (first, second, None),
+            [first, second, year] => (first, second, Some(parse_year(year)?)),
+            _ => return None,
+        };
+        let (first, second): (u32, u32) = (first.parse().ok()?, second.parse().ok()?);
+        let (day, month) = match order {
+            Order::DayMonth => (first, second),
+            Order::MonthDay => (second, first),
+        };
+        date(year, month, day, today)
+    }
+}
+
+/// Reads a day of the month, perhaps with an ordinal ending such as `1er`, `2nd` or `3º`.
+fn parse_day(word: &str) -> Option<u32> {
+    let digits = word.trim_end_matches(|c: char| !c.is_ascii_digit());
+    match word.len() - digits.len() {
+        0..=2 => digits.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Reads a year, two digits meaning one in this century.
+fn parse_year(word: &str) -> Option<i32> {
+    let year: i32 = word.parse().ok()?;
+    match word.len() {
+        2 => Some(2000 + year),
+        4 => Some(year),
+        _ => None,
+    }
+}
+
+/// Returns the date, or without a year its next occurrence from `today`.
+fn date(year: Option<i32>, month: u32, day: u32, today: NaiveDate) -> Option<NaiveDate> {
+    match year {
+        Some(year) => NaiveDate::from_ymd_opt(year, month, day),
+        None => NaiveDate::from_ymd_opt(today.year(), month, day)
+            .filter(|date| *date >= today)
+            .or_else(|| NaiveDate::from_ymd_opt(today.year() + 1, month, day)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Locale;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_parse_date() {
+        // A Saturday
+        let today = NaiveDate::from_ymd_opt(2026, 10, 17).unwrap();
+        let on = |month, day| NaiveDate::from_ymd_opt(2026, month, day);
+        let de = Locale::parse("de_DE.UTF-8").unwrap();
+        assert_eq!(de.parse_date("15.11.2026", today), on(11, 15));
+        assert_eq!(de.parse_date("15.11.", today), on(11, 15));
+        assert_eq!(
+            de.parse_date("3.2.27", today),
+            NaiveDate::from_ymd_opt(2027, 2, 3)
+        );
+        // Passed this year, so next year's
+        assert_eq!(
+            de.parse_date("1. März", today),
+            NaiveDate::from_ymd_opt(2027, 3, 1)
+        );
+        assert_eq!(de.parse_date("15. november 2026", today), on(11, 15));
+        assert_eq!(de.parse_date("Freitag", today), on(10, 23));
+        assert_eq!(de.parse_date("Di.", today), on(10, 20));
+        assert_eq!(de.parse_date("morgen", today), on(10, 18));
+        // English names work in any locale
+        assert_eq!(de.parse_date("fri", today), on(10, 23));
+        assert_eq!(de.parse_date("15.13.2026", today), None);
+        assert_eq!(de.parse_date("übermorgen", today), None);
+
+        let es = Locale::parse("es").unwrap();
+        assert_eq!(es.parse_date("15 de noviembre de 2026", today), on(11, 15));
+        assert_eq!(es.parse_date("mañana", today), on(10, 18));
+        assert_eq!(es.parse_date("manana", today), on(10, 18));
+        let fr = Locale::parse("fr-CA").unwrap();
+        assert_eq!(fr.parse_date("1er déc.", today), on(12, 1));
+        assert_eq!(fr.parse_date("aujourd'hui", today), Some(today));
+        assert_eq!(fr.parse_date("25/12/2026", today), on(12, 25));
+
+        let us = Locale::parse("en-US").unwrap();
+        assert_eq!(us.parse_date("11/15/2026", today), on(11, 15));
+        assert_eq!(us.parse_date("November 15, 2026", today), on(11, 15));
+        assert_eq!(
+            Locale::parse("en_GB")
+                .unwrap()
+                .parse_date("15/11/2026", today),
+            on(11, 15)
+        );
+        // Plain English leaves numeric dates to ISO
+        assert_eq!(Locale::En.parse_date("11/15/2026", today), None);
+        assert_eq!(Locale::En.parse_date("15 Nov", today), on(11, 15));
+
+        assert!(Locale::parse("tlh").is_err());
+    }
+}
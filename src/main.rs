@@ -1,38 +1,158 @@
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::Path;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 
-use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
-use std::cmp::Reverse;
+use std::collections::BTreeSet;
 
-use chrono::{DateTime, Utc};
-use std::time::Duration;
-use std::time::UNIX_EPOCH;
+use chrono::Utc;
+use std::time::{Duration, Instant};
+
+mod errors;
+mod manpage;
+mod plugin;
+mod setup;
+
+use todo::{
+    anonymize, asana, attachments, calendar, config, context, csv, dates, diff, doctor, focus,
+    fold, formats, gantt, goals, graph, habits, html, ids, import, jira, keep, layout, locale,
+    mirror, openapi, plan, predict, priorities, qr, query, quick, regex, remote, shards, signing,
+    sql, stats, tags, theme, timings, tls, triage, workdays, workflow,
+};
+
+use config::{load_config, Config};
+use dates::{DateFormat, Zone};
+use errors::Failure;
+use formats::Format;
+use ids::TaskRef;
+use locale::Locale;
+use theme::Theme;
+use workdays::Workdays;
+
+use todo::batch::{apply_batch, parse_batch};
+use todo::list::{Dependents, NewTodo, Removal, TodoEdit, TIMESTAMP_FORMAT};
+use todo::remote::SyncAction;
+use todo::store::{
+    backup_path, list_directory, load_todo_list, load_todo_list_as_of, load_todo_list_from,
+    try_save_todo_list,
+};
+use todo::{now, Todo, TodoList, TODO_FILE};
+
+/// The command-line arguments: options shared by every command, and the command itself.
+#[derive(Debug, StructOpt)]
+struct Opt {
+    /// The todo list file to operate on, overriding the `file` set in the config.
+    #[structopt(
+        name = "list-file",
+        short = "f",
+        long = "file",
+        global = true,
+        parse(from_os_str)
+    )]
+    file: Option<PathBuf>,
+    /// Print labeled lines without color, block graphics or column layouts, for screen
+    /// readers.
+    #[structopt(long, global = true)]
+    plain: bool,
+    /// When to color output, overriding the configured choice.
+    #[structopt(
+        long,
+        global = true,
+        value_name = "when",
+        possible_values = &["auto", "always", "never"]
+    )]
+    color: Option<config::ColorChoice>,
+    /// Run the command against the list shared by `serve` at this URL, e.g.
+    /// http://nas.local:8080, overriding the configured remote. Empty to use a local file.
+    #[structopt(long, global = true, value_name = "url")]
+    remote: Option<String>,
+    /// Lay lists out to fit this many columns instead of the terminal's width, e.g. when
+    /// writing them to a file.
+    #[structopt(long, global = true, value_name = "columns")]
+    width: Option<usize>,
+    /// Report how long loading the list, running the command and saving the list took, and
+    /// how many items there were, on stderr.
+    #[structopt(long, global = true)]
+    timings: bool,
+    #[structopt(subcommand)]
+    command: Command,
+}
 
 /// Represents the available commands for the todo application.
 #[derive(Debug, StructOpt)]
 pub enum Command {
     /// Add a new todo item with a task and priority.
     #[structopt(name = "add")]
-    Add {
-        /// The task description for the new todo item.
-        task: String,
-        /// The priority level for the new todo item.
-        priority: u64,
-    },
+    Add(NewTodo),
 
     /// Remove a todo item by its ID
     #[structopt(name = "remove")]
-    Remove {
-        /// The unique identifier of the todo item to be removed.
-        id: u64,
-    },
+    Remove(Removal),
 
     /// List all todo items.  
     #[structopt(name = "list")]
-    List,
+    List {
+        /// Only list todo items assigned to this person.
+        #[structopt(long)]
+        assignee: Option<String>,
+        /// Only list todo items assigned to the current user.
+        #[structopt(long, conflicts_with = "assignee")]
+        mine: bool,
+        /// List every todo item, even when the config defaults to `--mine` or to a view, or a
+        /// context is active, and those on the someday/maybe list.
+        #[structopt(long, conflicts_with_all = &["assignee", "mine"])]
+        all: bool,
+        /// Only list the todo items on the someday/maybe list, which are otherwise left out.
+        #[structopt(long, conflicts_with = "all")]
+        someday: bool,
+        /// Only list todo items created by this person.
+        #[structopt(long)]
+        created_by: Option<String>,
+        /// Only list todo items at this location or at a location configured as near it.
+        #[structopt(long)]
+        near: Option<String>,
+        /// Only list todo items with this tag.
+        #[structopt(long)]
+        tag: Option<String>,
+        /// Only list todo items changed since this time, e.g. "2 days ago" or "2024-06-01".
+        #[structopt(long)]
+        modified_since: Option<String>,
+        /// List the todo items as they were at this time, e.g. "last monday" or "3 days ago",
+        /// as far back as the history goes.
+        #[structopt(long)]
+        as_of: Option<String>,
+        /// Only list todo items matching a query, e.g. "(priority >= 4 and tag:work) or overdue".
+        #[structopt(long = "where")]
+        filter: Option<String>,
+        /// Only list todo items matching a view, a query named in the config.
+        #[structopt(long)]
+        view: Option<String>,
+        /// Sort by these keys, e.g. "due asc, priority desc, created asc", instead of the
+        /// configured default order.
+        #[structopt(long)]
+        sort: Option<String>,
+        /// Only print the IDs of the todo items, one per line.
+        #[structopt(long, conflicts_with = "format")]
+        ids_only: bool,
+        /// How to print the todo items: "text", or "ndjson" for one JSON object per line.
+        #[structopt(long, default_value = "text", possible_values = &["text", "ndjson"])]
+        format: ListFormat,
+        /// Keep running and print the list again whenever the list file or the active context
+        /// changes, e.g. in a terminal pane, until interrupted.
+        #[structopt(long, conflicts_with = "as-of")]
+        watch: bool,
+        /// List the completed todo items instead, in the order they were completed, with
+        /// when each was completed and how long it was open.
+        #[structopt(
+            long,
+            conflicts_with_all = &["mine", "near", "modified-since", "as-of", "view", "sort", "ids-only", "watch"]
+        )]
+        done: bool,
+        /// With `--done`, only list the todo items completed since this time, e.g.
+        /// "last month" or "2024-06-01".
+        #[structopt(long, requires = "done")]
+        since: Option<String>,
+    },
 
     /// Display help information about the todo application.
     #[structopt(name = "help")]
@@ -49,150 +169,772 @@ pub enum Command {
     /// Print out todo list based on when they where created from earliest to latest.
     #[structopt(name = "schedule")]
     Schedule,
-    /// Edit the task of a todo item by providing a new task name and the ID of the todo item.
+    /// Change any of the task, priority and due date of a todo item, e.g.
+    /// edit 3 --task "Pay the rent" --priority 5.
     #[structopt(name = "edit")]
-    Edit {
-        /// The new task description for the todo item.
-        task: String,
-        /// The unique identifier of the todo item to be edited.
-        id: u64,
-    },
-}
-/// Represents a todo item with associated details.
-#[derive(Debug, Serialize, Deserialize)]
-struct Todo {
-    /// The unique identifier of the todo item.
-    id: u64,
-    /// The task description of the todo item.
-    task: String,
-    /// The priority level of the todo item (1-5 inclusive).
-    priority: u64,
-    /// The timestamp when the todo item was created.
-    created: i64,
-}
-/// Represents a collection of todo items.
-#[derive(Debug, Serialize, Deserialize)]
-struct TodoList {
-    /// The list of todo items stored in the todo list with a Vec.
-    todos: Vec<Todo>,
+    Edit(TodoEdit),
+    /// Add a todo item from a single free-form string, e.g.
+    /// "Pay rent tomorrow 9am p:5 +finance @home".
+    #[structopt(name = "quick")]
+    Quick {
+        /// The task with optional date, time, `p:<priority>`, `+tag` and `@context` words.
+        text: String,
+    },
+    /// Show the tasks added, removed and changed since another todo list file or a backup.
+    #[structopt(name = "diff")]
+    Diff {
+        /// The todo list file to compare against.
+        #[structopt(parse(from_os_str), required_unless = "backup")]
+        file: Option<PathBuf>,
+        /// Compare against the n-th most recent backup instead of a file.
+        #[structopt(long, conflicts_with = "file")]
+        backup: Option<usize>,
+        /// Print the differences as JSON.
+        #[structopt(long)]
+        json: bool,
+    },
+    /// Sign a todo list file with an SSH (ed25519) key, writing `<file>.sig`.
+    #[structopt(name = "sign")]
+    Sign {
+        /// The todo list file to sign, the current list by default.
+        #[structopt(parse(from_os_str))]
+        file: Option<PathBuf>,
+        /// The private key to sign with, overriding `signing_key` in the config.
+        #[structopt(long, parse(from_os_str))]
+        key: Option<PathBuf>,
+    },
+    /// Check that a todo list file was signed by a trusted person.
+    #[structopt(name = "verify")]
+    Verify {
+        /// The todo list file to verify; its signature is read from `<file>.sig`.
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// The identity expected to have signed the file, overriding `trusted_signer`.
+        #[structopt(long)]
+        signer: Option<String>,
+        /// The allowed signers file, overriding `allowed_signers` in the config.
+        #[structopt(long, parse(from_os_str))]
+        allowed_signers: Option<PathBuf>,
+    },
+    /// Check the todo list for inconsistencies, repairing them with `--fix`.
+    #[structopt(name = "doctor")]
+    Doctor {
+        /// Repair the problems found.
+        #[structopt(long)]
+        fix: bool,
+    },
+    /// Print the man page in roff format, e.g. `man > todo.1`.
+    #[structopt(name = "man")]
+    Man,
+    /// Replace the todo list with one of its backups.
+    #[structopt(name = "restore")]
+    Restore {
+        /// The backup to restore, 1 being the most recent.
+        backup: usize,
+    },
+    /// Show every detail of a single todo item.
+    #[structopt(name = "show")]
+    Show {
+        /// The unique identifier of the todo item to be shown, a prefix of its hash or part
+        /// of its title.
+        id: TaskRef,
+        /// Show a QR code of the task and its URL instead, to scan with a phone.
+        #[structopt(long)]
+        qr: bool,
+        /// Also predict when it will be done, from how long similar completed todo items
+        /// took.
+        #[structopt(long, conflicts_with = "qr")]
+        predict: bool,
+        /// How to show the todo item: "text", the default, or "json" for the item as it is in
+        /// the list file.
+        // Not defaulted, since clap counts a default as given, which would conflict with
+        // every --qr and --predict
+        #[structopt(
+            long,
+            possible_values = &["text", "json"],
+            conflicts_with_all = &["qr", "predict"]
+        )]
+        format: Option<ShowFormat>,
+    },
+    /// Export the todo list as a checklist, in plain text, as a printable PDF or as a web page,
+    /// as a Gantt chart, as a spreadsheet, or as a list file, whole or only the todo items
+    /// matching the filters of `list`.
+    #[structopt(name = "export")]
+    Export {
+        /// The format of the checklist: "text", "pdf" grouped by project, "html" with
+        /// sorting and filtering in the browser, "mermaid-gantt" for markdown, "csv" with a
+        /// row per todo item as `import --format csv` reads it, or "json" for the list file
+        /// itself.
+        #[structopt(
+            long,
+            default_value = "text",
+            possible_values = &["text", "pdf", "html", "mermaid-gantt", "csv", "json"]
+        )]
+        format: ExportFormat,
+        /// Only export todo items assigned to this person.
+        #[structopt(long)]
+        assignee: Option<String>,
+        /// Only export todo items assigned to the current user.
+        #[structopt(long, conflicts_with = "assignee")]
+        mine: bool,
+        /// Only export the todo items on the someday/maybe list.
+        #[structopt(long)]
+        someday: bool,
+        /// Only export todo items created by this person.
+        #[structopt(long)]
+        created_by: Option<String>,
+        /// Only export todo items at this location or at a location configured as near it.
+        #[structopt(long)]
+        near: Option<String>,
+        /// Only export todo items with this tag.
+        #[structopt(long)]
+        tag: Option<String>,
+        /// Only export todo items changed since this time, e.g. "2 days ago" or "2024-06-01".
+        #[structopt(long)]
+        modified_since: Option<String>,
+        /// Only export todo items matching a query, e.g. "project:client-x and !tag:internal".
+        #[structopt(long = "where")]
+        filter: Option<String>,
+        /// Only export todo items matching a view, a query named in the config.
+        #[structopt(long)]
+        view: Option<String>,
+        /// Print a QR code of the text checklist instead, to scan with a phone.
+        #[structopt(long)]
+        qr: bool,
+        /// Replace the tasks, people, places, links, attachments, tags and projects with
+        /// placeholders, keeping everything else, e.g. to share the list in a bug report.
+        #[structopt(long)]
+        anonymize: bool,
+        /// The file to write the checklist to, instead of stdout.
+        #[structopt(short = "o", long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Attach a file to a todo item.
+    #[structopt(name = "attach")]
+    Attach {
+        /// The unique identifier of the todo item, a prefix of its hash or part of its title.
+        id: TaskRef,
+        /// The file to attach.
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// Copy the file into the attachments directory instead of linking to it.
+        #[structopt(long)]
+        copy: bool,
+    },
+    /// Open the link of a todo item in the default browser.
+    #[structopt(name = "open")]
+    Open {
+        /// The unique identifier of the todo item whose link should be opened, or a prefix of
+        /// its hash.
+        id: TaskRef,
+    },
+    /// Raise the priority of todo items that have been open for too long.
+    #[structopt(name = "age")]
+    Age {
+        /// The number of days a todo item may stay open before its priority is raised,
+        /// overriding the configured policy.
+        #[structopt(long)]
+        after_days: Option<u64>,
+    },
+    /// Set a reminder for a todo item.
+    #[structopt(name = "remind")]
+    Remind {
+        /// The unique identifier of the todo item, a prefix of its hash or part of its title.
+        id: TaskRef,
+        /// When to be reminded, e.g. "2024-06-01 09:00" or "fri 9am".
+        #[structopt(long, required_unless = "before-due")]
+        at: Option<String>,
+        /// How long before the due date to be reminded, e.g. "1h" or "2d".
+        #[structopt(long, conflicts_with = "at")]
+        before_due: Option<String>,
+    },
+    /// Print the reminders that have come due since the last check and mark them as fired.
+    #[structopt(name = "reminders")]
+    Reminders,
+    /// Assign a todo item to someone.
+    #[structopt(name = "delegate")]
+    Delegate {
+        /// The unique identifier of the todo item to be assigned, a prefix of its hash or part
+        /// of its title.
+        id: TaskRef,
+        /// The person responsible for the todo item.
+        assignee: String,
+    },
+    /// Pin a color on a todo item, which list shows it in instead of its priority's color.
+    #[structopt(name = "highlight")]
+    Highlight {
+        /// The unique identifier of the todo item, a prefix of its hash or part of its title.
+        id: TaskRef,
+        /// The color, e.g. "red", "bold bright-yellow" or "black on-yellow".
+        #[structopt(value_name = "color", required_unless = "clear")]
+        highlight: Vec<String>,
+        /// Remove the highlight instead.
+        #[structopt(long, conflicts_with = "highlight")]
+        clear: bool,
+    },
+    /// Show, set or clear the active context, which `list` is scoped to and new todo items
+    /// are put in.
+    #[structopt(name = "context")]
+    Context {
+        #[structopt(subcommand)]
+        action: Option<ContextAction>,
+    },
+    /// Show or change the settings without editing the config file by hand.
+    #[structopt(name = "config")]
+    Config {
+        #[structopt(subcommand)]
+        action: ConfigAction,
+    },
+    /// Add goals, link todo items to them, and show how far each has come.
+    #[structopt(name = "goal")]
+    Goal {
+        #[structopt(subcommand)]
+        action: GoalAction,
+    },
+    /// Change tags across the whole list.
+    #[structopt(name = "tag")]
+    Tag {
+        #[structopt(subcommand)]
+        action: TagAction,
+    },
+    /// Record that a todo item can only be done after others, making it BLOCKED until they
+    /// are removed.
+    #[structopt(name = "depend")]
+    Depend {
+        /// The unique identifier of the todo item, a prefix of its hash or part of its title.
+        id: TaskRef,
+        /// The IDs or hash prefixes of the todo items it depends on.
+        #[structopt(required = true)]
+        on: Vec<TaskRef>,
+        /// Remove these dependencies instead.
+        #[structopt(long)]
+        remove: bool,
+    },
+    /// Put a todo item on the someday/maybe list, out of the default views, without
+    /// deleting it.
+    #[structopt(name = "someday")]
+    Someday {
+        /// The unique identifier of the todo item, a prefix of its hash or part of its title.
+        id: TaskRef,
+        /// Take it off the someday/maybe list instead.
+        #[structopt(long)]
+        clear: bool,
+    },
+    /// Push the due dates of todo items forward, e.g. every overdue one after a bad week.
+    #[structopt(name = "postpone")]
+    Postpone {
+        /// How far, e.g. "1d", "2h" or "1w", or to when, e.g. "fri" or "2024-06-10 9:00". An
+        /// overdue item is moved from its time of day today, any other from its due date.
+        duration: String,
+        /// The unique identifiers of the todo items, prefixes of their hashes or parts of their
+        /// titles.
+        #[structopt(required_unless = "all-overdue")]
+        ids: Vec<TaskRef>,
+        /// Postpone every overdue todo item instead, except those on the someday/maybe list.
+        #[structopt(long, conflicts_with = "ids")]
+        all_overdue: bool,
+        /// Only postpone the todo items matching a query, e.g. "tag:work".
+        #[structopt(long = "where")]
+        filter: Option<String>,
+        /// Postpone without asking for confirmation.
+        #[structopt(long)]
+        yes: bool,
+    },
+    /// Mark a todo item as WAITING until a date, e.g. for a reply from someone else.
+    #[structopt(name = "wait")]
+    Wait {
+        /// The unique identifier of the todo item, a prefix of its hash or part of its title.
+        id: TaskRef,
+        /// When to stop waiting, e.g. "2024-06-01", "2024-06-01 09:00" or "mon 9am".
+        until: String,
+    },
+    /// Print the dependency graph of the todo list, to render with Graphviz.
+    #[structopt(name = "graph")]
+    Graph {
+        /// The format of the graph: "dot" for Graphviz.
+        #[structopt(long, default_value = "dot", possible_values = &["dot"])]
+        format: GraphFormat,
+    },
+    /// Set how long a todo item is expected to take, for `plan`.
+    #[structopt(name = "estimate")]
+    Estimate {
+        /// The unique identifier of the todo item, a prefix of its hash or part of its title.
+        id: TaskRef,
+        /// The expected time, e.g. "30m" or "1h30m".
+        estimate: String,
+    },
+    /// Lay the estimated todo items out over today, tomorrow and the rest of the week,
+    /// flagging what doesn't fit or would be late, or pick today's with "plan today".
+    #[structopt(name = "plan")]
+    Plan {
+        /// "today" to pick the most urgent todo items that fit into today, and mark them
+        /// planned once the plan is accepted.
+        #[structopt(possible_values = &["today"])]
+        day: Option<String>,
+        /// The time available for tasks each day, e.g. "6h". Defaults to the configured
+        /// daily_capacity, or 8h.
+        #[structopt(long)]
+        capacity: Option<String>,
+        /// With "today", accept the plan proposed without asking.
+        #[structopt(long, requires = "day")]
+        yes: bool,
+        /// With "today", propose a new plan even if one was accepted today.
+        #[structopt(long, requires = "day")]
+        regenerate: bool,
+        /// Plan today only, into the free time between the busy times of an iCalendar file.
+        #[structopt(long, value_name = "file", parse(from_os_str), conflicts_with = "day")]
+        calendar: Option<PathBuf>,
+        /// The working day the free time is in, with --calendar.
+        // Not `requires = "calendar"`, which its default value would always trigger
+        #[structopt(long, value_name = "from-to", default_value = "09:00-17:00")]
+        hours: String,
+    },
+    /// Suggest priorities from the configured priority_rules for the todo items they apply
+    /// to, asking to confirm or override each one that differs.
+    #[structopt(name = "triage")]
+    Triage {
+        /// Only triage the todo items matching a query, e.g. "tag:client".
+        #[structopt(long)]
+        filter: Option<String>,
+        /// Accept every suggestion without asking.
+        #[structopt(long)]
+        yes: bool,
+        /// Only print the suggestions, without asking or changing anything.
+        #[structopt(long, conflicts_with = "yes")]
+        dry_run: bool,
+    },
+    /// Read a todo list as JSON from stdin, and write the items matching a query to stdout.
+    #[structopt(name = "filter")]
+    Filter {
+        /// The query, e.g. "tag:work priority>=3 !has:assignee sort:-priority limit:10".
+        query: String,
+    },
+    /// List the todo items matching a query, exiting with status 1 when there are none.
+    #[structopt(name = "search")]
+    Search {
+        /// The query, e.g. "report tag:work sort:-priority".
+        query: String,
+        /// Only print the number of matching todo items.
+        #[structopt(long)]
+        count_only: bool,
+        /// Also match tasks with words like those of the query, e.g. misspelled, ranking the
+        /// todo items by how well they match unless the query sorts them.
+        #[structopt(long)]
+        fuzzy: bool,
+        /// Match the words of the query whatever accents the tasks have, e.g. "cafe" for
+        /// "Café", as the ignore_accents setting does for every query.
+        #[structopt(long)]
+        ignore_accents: bool,
+    },
+    /// List the todo items past their due date, exiting with status 1 when there are none.
+    #[structopt(name = "overdue")]
+    Overdue {
+        /// Only print the number of overdue todo items.
+        #[structopt(long)]
+        count_only: bool,
+    },
+    /// Print the number of todo items, or of those matching a query, exiting with status 1
+    /// when it is zero. As in list, those on the someday/maybe list are left out.
+    #[structopt(name = "count")]
+    Count {
+        /// The query, e.g. "tag:work !has:assignee".
+        query: Option<String>,
+        /// Only count the todo items on the someday/maybe list.
+        #[structopt(long)]
+        someday: bool,
+    },
+    /// Run a workflow: a named sequence of commands defined in the config.
+    #[structopt(name = "run")]
+    Run {
+        /// The name of the workflow.
+        name: String,
+        /// The arguments substituted for {1}, {2}, ... and {args} in the workflow's commands.
+        args: Vec<String>,
+    },
+    /// Apply the add, edit and remove commands in a file with one load and one save, changing
+    /// nothing if any of them fails.
+    #[structopt(name = "batch")]
+    Batch {
+        /// The file with one command per line, e.g. add "Buy milk" 2, or - for stdin. Blank
+        /// lines and lines starting with # are skipped.
+        #[structopt(parse(from_os_str))]
+        commands: PathBuf,
+    },
+    /// Run an SQL query over the todo items with sqlite3, for reports the filters can't express.
+    #[structopt(name = "sql")]
+    Sql {
+        /// The query, e.g. "SELECT task, priority FROM todos WHERE due < date('now')".
+        #[structopt(required_unless = "schema")]
+        query: Option<String>,
+        /// How to print the result: "table", "csv" or "json".
+        #[structopt(long, default_value = "table", possible_values = &["table", "csv", "json"])]
+        format: sql::OutputMode,
+        /// Print the tables the todo items are loaded into instead.
+        #[structopt(long, conflicts_with = "query")]
+        schema: bool,
+    },
+    /// Merge the todo items of another todo list file into the list, e.g. a copy edited on
+    /// another machine, adding the new ones and resolving those that changed.
+    #[structopt(name = "import")]
+    Import {
+        /// The todo list file, the output of list --format ndjson, or with --format csv or ics
+        /// a CSV or iCalendar file, to import. With --format keep, a Keep note or the Keep
+        /// folder of a Google Takeout.
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// The format of the file: "json" for a todo list or ndjson, "csv" with a header row
+        /// naming its columns, "ics" for the events and to-dos of a calendar, or "keep" for
+        /// Google Keep notes.
+        #[structopt(
+            long,
+            default_value = "json",
+            possible_values = &["json", "csv", "ics", "keep"]
+        )]
+        format: import::Format,
+        /// Import only the events and to-dos of the calendar with this name (X-WR-CALNAME).
+        #[structopt(long, value_name = "name")]
+        calendar: Option<String>,
+        /// Which CSV column fills which field, e.g. "Title=task,Prio=priority,Deadline=due".
+        /// Without it, the columns named after a field fill it.
+        #[structopt(long, value_name = "columns")]
+        map: Option<import::Mapping>,
+        /// The character separating the fields of the CSV file, a comma by default, e.g. ";"
+        /// or tab.
+        #[structopt(long, value_name = "char", parse(try_from_str = csv::parse_char))]
+        delimiter: Option<char>,
+        /// The character quoting the fields of the CSV file, a double quote by default.
+        #[structopt(long, value_name = "char", parse(try_from_str = csv::parse_char))]
+        quote: Option<char>,
+        /// What to do with an item that matches a task, by creation time or text, but
+        /// differs from it: keep the task, replace it, add the item as well, or ask.
+        #[structopt(
+            long,
+            value_name = "strategy",
+            default_value = "skip",
+            possible_values = &["skip", "overwrite", "duplicate", "prompt"]
+        )]
+        on_conflict: import::Strategy,
+    },
+    /// Write the todo list to a file in another format, e.g. todos.yaml, or as a sharded list,
+    /// for --file or the file setting to name instead.
+    #[structopt(name = "convert")]
+    Convert {
+        /// The file to write, in the format of its extension: .json, .yaml or .yml, .toml
+        /// or .json5, or a directory ending in / for a sharded list.
+        #[structopt(parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Show how many todo items there are and in what state, per tag or project, or list the
+    /// stale ones.
+    #[structopt(name = "stats")]
+    Stats {
+        /// List the todo items untouched the longest instead, with their age and when they
+        /// were last changed.
+        #[structopt(long)]
+        stale: bool,
+        /// How long a todo item must have gone unchanged to be stale, e.g. "90d".
+        #[structopt(long, value_name = "duration", default_value = stats::STALE_AFTER)]
+        older_than: String,
+        /// Break the statistics down by tag or project instead: open, completed in the last
+        /// 30 days, average time to complete, and overdue.
+        #[structopt(
+            long,
+            value_name = "tag|project",
+            possible_values = &["tag", "project"],
+            conflicts_with = "stale"
+        )]
+        by: Option<stats::GroupBy>,
+        /// Chart the tasks by priority, the completions by day of the week and the tasks
+        /// added in each of the last 12 weeks, after the counts.
+        #[structopt(long, conflicts_with_all = &["stale", "by"])]
+        charts: bool,
+        /// List how many occurrences of each recurring todo item were done on time, late or
+        /// skipped instead.
+        #[structopt(long, conflicts_with_all = &["stale", "by", "charts"])]
+        recurring: bool,
+    },
+    /// Check off the current occurrence of a recurring todo item, keeping up its streak.
+    #[structopt(name = "done")]
+    Done {
+        /// The unique identifier of the todo item, a prefix of its hash or part of its title.
+        id: TaskRef,
+        /// Check off the occurrence before the current one instead, as done late.
+        #[structopt(long)]
+        late: bool,
+        /// Only check it off if the todo item is still at this revision, e.g. as read by show.
+        #[structopt(long)]
+        if_rev: Option<u64>,
+    },
+    /// Predict when each open todo item will be done, from how long similar completed ones
+    /// took.
+    #[structopt(name = "forecast")]
+    Forecast {
+        /// Only forecast the todo items matching a query, e.g. "project:acme".
+        #[structopt(long = "where")]
+        filter: Option<String>,
+    },
+    /// Show only one todo item on a cleared screen, with how long it has had the focus, until
+    /// it is marked done or you quit.
+    #[structopt(name = "focus")]
+    Focus {
+        /// The unique identifier of the todo item, a prefix of its hash or part of its title.
+        id: TaskRef,
+    },
+    /// Show how each recurring todo item has been kept up: its streak, the occurrences missed
+    /// this month, and whether the current one is done.
+    #[structopt(name = "habits")]
+    Habits,
+    /// Substitute a regular expression in the tasks of the todo items, printing every change.
+    #[structopt(name = "replace")]
+    Replace {
+        /// The regular expression to look for, e.g. "ACME" or "(?i)q(\d) report".
+        #[structopt(long)]
+        pattern: String,
+        /// The text to put in its place, where $1, $2, ... stand for the groups of the match.
+        #[structopt(long = "with", value_name = "text")]
+        replacement: String,
+        /// Only change the todo items matching a query, e.g. "tag:client".
+        #[structopt(long)]
+        filter: Option<String>,
+        /// Only print the changes, without saving them.
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Share the todo list over HTTP or HTTPS, for other machines to use with --remote.
+    #[structopt(name = "serve")]
+    Serve {
+        /// The address to listen on; 0.0.0.0 accepts connections from other machines.
+        #[structopt(long, default_value = "127.0.0.1")]
+        bind: String,
+        /// The port to listen on.
+        #[structopt(long, default_value = "8080")]
+        port: u16,
+        /// Serve HTTPS with the certificate chain in this PEM file.
+        #[structopt(long, value_name = "file", parse(from_os_str), requires = "tls-key")]
+        tls_cert: Option<PathBuf>,
+        /// The private key of --tls-cert, in a PEM file.
+        #[structopt(long, value_name = "file", parse(from_os_str), requires = "tls-cert")]
+        tls_key: Option<PathBuf>,
+        /// Serve HTTPS with a self-signed certificate, made with the openssl command the
+        /// first time and kept in .todo-tls next to the list.
+        #[structopt(long, conflicts_with = "tls-cert")]
+        tls_self_signed: bool,
+        /// A host name or address the self-signed certificate is for, e.g. nas.local, as
+        /// clients reach the server; localhost and --bind are always included.
+        #[structopt(long = "tls-name", value_name = "name", requires = "tls-self-signed")]
+        tls_names: Vec<String>,
+        /// Print the OpenAPI description of the API, also served at /openapi.json, instead
+        /// of serving.
+        #[structopt(long)]
+        spec: bool,
+    },
+    /// Show, send or discard the changes made with --remote while its server could not be
+    /// reached, or mirror Jira issues or Asana tasks as tasks.
+    #[structopt(name = "sync")]
+    Sync {
+        #[structopt(subcommand)]
+        action: SyncAction,
+    },
+    /// Any other command runs the todo-<name> program on PATH, a plugin, with the rest of
+    /// the arguments and the list and global options in its environment.
+    #[structopt(external_subcommand)]
+    External(Vec<String>),
 }
-
-impl TodoList {
-    /// Creates a new `TodoList` instance with an empty list of todos.
-    fn new() -> TodoList {
-        TodoList { todos: Vec::new() }
-    }
-    /// Adds a new todo item to the todo list with the specified task and priority.
-    ///
-    /// # Arguments
-    ///
-    /// * `task` - The task description for the new todo item.
-    /// * `priority` - The priority level for the new todo item.
-    ///
-    /// # Example
-    ///
-    /// ```
-    /// let mut todo_list = TodoList::new();
-    /// todo_list.add_todo("Complete the assignment", 3);
-    /// ```
-    fn add_todo(&mut self, task: &str, priority: u64) {
-        let id = self.todos.len() as u64 + 1;
-        let todo = Todo {
-            id,
-            task: task.to_string(),
-            priority,
-            created: Utc::now().timestamp(),
-        };
-        if priority <= 5 && priority > 0 {
-            self.todos.push(todo);
-        } else {
-            println!(
-                "Invalid priority: {} for task: {}. Not Added",
-                priority, task
-            );
+impl Command {
+    /// Whether the command writes JSON, and so reports its errors as JSON too.
+    fn writes_json(&self) -> bool {
+        matches!(
+            self,
+            Command::List {
+                format: ListFormat::Ndjson,
+                ..
+            } | Command::Show {
+                format: Some(ShowFormat::Json),
+                ..
+            } | Command::Export {
+                format: ExportFormat::Json,
+                ..
+            } | Command::Sql {
+                format: sql::OutputMode::Json,
+                ..
+            }
+        )
+    }
+}
+/// The ways `tag` changes tags.
+// structopt gives `tag` the help of this enum over that of `Command::Tag`, so it is set here
+// rather than taken from the doc comment.
+#[derive(Debug, StructOpt)]
+#[structopt(about = "Change tags across the whole list")]
+pub enum TagAction {
+    /// Rename a tag on every todo item, open or completed, e.g. "+clientA" to "+acme-corp".
+    #[structopt(name = "rename")]
+    Rename {
+        /// The tag to rename, with or without its +.
+        from: String,
+        /// Its new name.
+        to: String,
+    },
+}
+/// The ways `context` can change the active context.
+#[derive(Debug, StructOpt)]
+#[structopt(
+    about = "Show, set or clear the active context, which `list` is scoped to and new todo items are put in"
+)]
+pub enum ContextAction {
+    /// Scope `list` to a tag, e.g. "work", or a project, e.g. "project:home", and put new todo
+    /// items in it.
+    #[structopt(name = "set")]
+    Set {
+        /// The tag or project:<name>.
+        context: String,
+    },
+    /// Stop scoping `list` and new todo items.
+    #[structopt(name = "clear")]
+    Clear,
+}
+/// The ways `config` shows and changes the settings.
+#[derive(Debug, StructOpt)]
+#[structopt(about = "Show or change the settings without editing the config file by hand")]
+pub enum ConfigAction {
+    /// Print the value of a setting, as overridden by its TODO_<KEY> variable if set.
+    #[structopt(name = "get")]
+    Get { key: String },
+    /// Set a setting in the config file, creating it if need be. The value is read as JSON,
+    /// e.g. 4 or {"work": "tag:work"}, or else as a string.
+    #[structopt(name = "set")]
+    Set { key: String, value: String },
+    /// Remove a setting from the config file, so that it takes its default again.
+    #[structopt(name = "unset")]
+    Unset { key: String },
+    /// List the settings that differ from the defaults.
+    #[structopt(name = "list")]
+    List {
+        /// List every setting instead, with what it does.
+        #[structopt(long)]
+        all: bool,
+    },
+    /// Open the config file in $VISUAL or $EDITOR, checking it once the editor exits.
+    #[structopt(name = "edit")]
+    Edit,
+}
+/// The ways `goal` manages goals.
+#[derive(Debug, StructOpt)]
+#[structopt(about = "Add goals, link todo items to them, and show how far each has come")]
+pub enum GoalAction {
+    /// Add a goal, e.g. "Ship v2".
+    #[structopt(name = "add")]
+    Add {
+        name: String,
+        /// When it should be reached, e.g. "2024-06-30".
+        #[structopt(long, value_name = "date")]
+        by: Option<String>,
+    },
+    /// Remove a goal, unlinking its todo items.
+    #[structopt(name = "remove")]
+    Remove { name: String },
+    /// Link todo items to a goal, replacing the goal they were linked to.
+    #[structopt(name = "link")]
+    Link {
+        name: String,
+        /// The IDs or hash prefixes of the todo items.
+        #[structopt(required = true)]
+        ids: Vec<TaskRef>,
+    },
+    /// Unlink todo items from their goal.
+    #[structopt(name = "unlink")]
+    Unlink {
+        /// The IDs or hash prefixes of the todo items.
+        #[structopt(required = true)]
+        ids: Vec<TaskRef>,
+    },
+    /// Show how far every goal, or one, has come: its open and done todo items and the
+    /// estimates left.
+    #[structopt(name = "show")]
+    Show { name: Option<String> },
+}
+/// The ways `list` can print todo items.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListFormat {
+    /// One human-readable line per todo item.
+    Text,
+    /// One JSON object per line (newline-delimited JSON).
+    Ndjson,
+}
+impl std::str::FromStr for ListFormat {
+    type Err = String;
+    fn from_str(format: &str) -> Result<ListFormat, String> {
+        match format {
+            "text" => Ok(ListFormat::Text),
+            "ndjson" => Ok(ListFormat::Ndjson),
+            _ => Err(format!("Unknown format: {}", format)),
         }
     }
-    /// Removes a todo item from the todo list based on its ID and resets the IDs of the rest.
-    ///
-    /// # Arguments
-    ///
-    /// * `id` - The unique identifier of the todo item to be removed.
-    ///
-    /// # Example
-    ///
-    /// ```
-    /// let mut todo_list = TodoList::new();
-    /// todo_list.add_todo("Complete the assignment", 3);
-    /// todo_list.remove_todo(1);
-    /// ```
-    fn remove_todo(&mut self, id: u64) {
-        let size = self.todos.len();
-        self.todos.retain(|todo| todo.id != id);
-        if size == self.todos.len() {
-            println!("Invalid ID. Nothing deleted.");
-        } else {
-            let mut new_ids: u64 = 1;
-            for todo in &mut self.todos {
-                todo.id = new_ids;
-                new_ids += 1;
-            }
-        }
-    }
-    /// Clears all todo items from the todo list.
-    ///
-    /// # Example
-    ///
-    /// ```
-    /// let mut todo_list = TodoList::new();
-    /// todo_list.add_todo("Complete the assignment", 3);
-    /// todo_list.clear_todo();
-    /// ```
-    fn clear_todo(&mut self) {
-        self.todos.clear();
-    }
-    /// Displays the details of all todo items in the todo list.
-    ///
-    /// # Example
-    ///
-    /// ```
-    /// let mut todo_list = TodoList::new();
-    /// todo_list.add_todo("Complete the assignment", 3);
-    /// todo_list.display_todos();
-    /// ```
-    fn display_todos(&self) {
-        if self.todos.is_empty() {
-            println!("No tasks left!");
-        } else {
-            for todo in &self.todos {
-                let d = UNIX_EPOCH + Duration::from_secs(todo.created as u64);
-                let datetime = DateTime::<Utc>::from(d);
-                let timestamp_str = datetime.format("%Y-%m-%d %H:%M:%S.%f").to_string();
-                println!("{}: {}, created: {}", todo.id, todo.task, timestamp_str);
-            }
-        }
-    }
-    /// Edits the task of a todo item in the todo list.
-    ///
-    /// # Arguments
-    ///
-    /// * `new_task` - The new task description for the todo item.
-    /// * `id` - The unique identifier of the todo item to be edited.
-    ///
-    /// # Example
-    ///
-    /// ```
-    /// let mut todo_list = TodoList::new();
-    /// todo_list.add_todo("Complete the assignment", 3);
-    /// todo_list.edit_todo("Updated task", 0);
-    /// ```
-    fn edit_todo(&mut self, new_task: &str, id: u64) {
-        if (id - 1) < self.todos.len() as u64 {
-            self.todos[(id - 1) as usize].task = new_task.to_string();
-        } else {
-            println!("Invalid ID");
+}
+/// The ways `show` can print a todo item.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShowFormat {
+    /// A labeled line per detail.
+    Text,
+    /// The todo item as a JSON object.
+    Json,
+}
+impl std::str::FromStr for ShowFormat {
+    type Err = String;
+    fn from_str(format: &str) -> Result<ShowFormat, String> {
+        match format {
+            "text" => Ok(ShowFormat::Text),
+            "json" => Ok(ShowFormat::Json),
+            _ => Err(format!("Unknown format: {}", format)),
+        }
+    }
+}
+/// The formats `export` can write.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    /// A plain-text checklist.
+    Text,
+    /// A printable PDF checklist.
+    Pdf,
+    /// A standalone HTML page.
+    Html,
+    /// A Mermaid Gantt chart for markdown.
+    MermaidGantt,
+    /// A spreadsheet, as CSV.
+    Csv,
+    /// The list file, as JSON.
+    Json,
+}
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+    fn from_str(format: &str) -> Result<ExportFormat, String> {
+        match format {
+            "text" => Ok(ExportFormat::Text),
+            "pdf" => Ok(ExportFormat::Pdf),
+            "html" => Ok(ExportFormat::Html),
+            "mermaid-gantt" => Ok(ExportFormat::MermaidGantt),
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(format!("Unknown format: {}", format)),
         }
     }
 }
+/// The formats `graph` can write.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    /// Graphviz's DOT language.
+    Dot,
+}
+impl std::str::FromStr for GraphFormat {
+    type Err = String;
+    fn from_str(format: &str) -> Result<GraphFormat, String> {
+        match format {
+            "dot" => Ok(GraphFormat::Dot),
+            _ => Err(format!("Unknown format: {}", format)),
+        }
+    }
+}
+
 /// The main function of the command-line todo list application.
 ///
 /// # Examples
@@ -202,27 +944,527 @@ impl TodoList {
 /// cargo run -- <command>
 /// ```
 fn main() {
-    // Load the todo list from a file or create a new one if the file doesn't exist
-    let todo_list: TodoList = load_todo_list().unwrap_or_else(TodoList::new);
-    // Parse the command-line arguments into a Command enum
-    let command = Command::from_args();
+    // Parse the command-line arguments into the global options and a Command enum. Usage
+    // errors exit with 2 rather than clap's 1, which `search` and others use for no matches
+    let opt = match Opt::from_args_safe() {
+        Ok(opt) => opt,
+        // --help and --version are printed to stdout, with status 0
+        Err(error) if !error.use_stderr() => error.exit(),
+        Err(error) => {
+            eprintln!("{}", error.message);
+            std::process::exit(2);
+        }
+    };
+    if opt.command.writes_json() {
+        errors::enable_json();
+    }
+    if opt.timings {
+        timings::enable();
+    }
+    // Load the user's settings, falling back to the defaults
+    let mut config: Config = load_config();
+    // The first time, with neither settings nor a list here, offer to set things up instead
+    // of creating the list wherever this happens to run
+    let default_file =
+        PathBuf::from(TODO_FILE).with_extension(config.file_format.unwrap_or_default().extension());
+    let first_run = opt.file.is_none()
+        && config.file.is_none()
+        && !default_file.exists()
+        && std::io::stdin().is_terminal()
+        && !matches!(
+            opt.command,
+            Command::Help
+                | Command::Config { .. }
+                | Command::Filter { .. }
+                | Command::Serve { .. }
+                | Command::External(_)
+        );
+    if let Some(path) = config::config_path().filter(|path| first_run && !path.exists()) {
+        match offer_setup(path, &config.priorities().unwrap_or_default()) {
+            Ok(()) => config = load_config(),
+            Err(error) => println!("{}", error),
+        }
+    }
+    config.plain |= opt.plain;
+    if let Some(color) = opt.color {
+        config.color = color;
+    }
+    // Every colored print goes through the theme, which is colorless when color is off
+    let theme = config.theme().unwrap_or_else(|error| {
+        eprintln!("Ignoring the configured colors: {}", error);
+        Theme::default()
+    });
+    let zone = config.timezone().unwrap_or_else(|error| {
+        eprintln!("Ignoring the configured timezone: {}", error);
+        Zone::Utc
+    });
+    let locale = config.locale().unwrap_or_else(|error| {
+        eprintln!("Ignoring the configured locale: {}", error);
+        Locale::En
+    });
+    // Every priority given or shown goes through the scale, so it is set before any is
+    priorities::set(config.priorities().unwrap_or_else(|error| {
+        eprintln!("Ignoring the configured priorities: {}", error);
+        priorities::Scale::default()
+    }));
+    let workdays = config.workdays().unwrap_or_else(|error| {
+        eprintln!("Ignoring the configured holidays: {}", error);
+        Workdays::default()
+    });
+    let dates = config
+        .date_format()
+        .unwrap_or_else(|error| {
+            eprintln!("Ignoring the configured date format: {}", error);
+            DateFormat::default()
+        })
+        .in_zone(zone)
+        .with_workdays(workdays.clone());
+    // Lists are laid out to fit the terminal, unless a width is given or output is piped
+    let width = opt.width.or_else(layout::terminal_width);
+    // Config only deals with the settings, so it needs neither a list nor a remote
+    if let Command::Config { action } = &opt.command {
+        if let Err(error) = run_config(action, &config) {
+            println!("{}", error);
+            std::process::exit(2);
+        }
+        return;
+    }
+    // With a remote, the command runs on a copy of the served list, which is sent back after
+    let remote = opt
+        .remote
+        .clone()
+        .or_else(|| config.remote.clone())
+        .filter(|remote| !remote.is_empty());
+    if let Some(url) = remote.filter(|_| {
+        !matches!(
+            opt.command,
+            Command::Serve { .. } | Command::Filter { .. } | Command::Help | Command::External(_)
+        )
+    }) {
+        if opt.file.is_some() {
+            Failure::new(
+                "invalid_option",
+                "--file and --remote can't be used together",
+            )
+            .field("file")
+            .exit(2);
+        }
+        if let Command::List { watch: true, .. } = opt.command {
+            Failure::new("invalid_option", "list --watch does not work with --remote")
+                .field("watch")
+                .exit(2);
+        }
+        let token = config.remote_token.as_deref();
+        let status = remote::Remote::parse(&url).and_then(|remote| {
+            let remote = remote.trusting(config.remote_ca.clone());
+            match &opt.command {
+                Command::Sync { action } => remote::sync(&remote, token, action, &dates),
+                _ => remote::run(&remote, token),
+            }
+        });
+        match status {
+            Ok(status) => std::process::exit(status),
+            Err(error) => Failure::new("remote", error).exit(1),
+        }
+    }
+    // Work on the file given on the command line, then the configured one, then ./todos.json
+    // or, with file_format, ./todos.yaml and so on
+    let file: PathBuf = opt.file.or_else(|| config.file.clone()).unwrap_or_else(|| {
+        PathBuf::from(TODO_FILE).with_extension(config.file_format.unwrap_or_default().extension())
+    });
+    let base = list_directory(&file);
+    // A plugin works on the list itself, given where it is in its environment
+    if let Command::External(args) = &opt.command {
+        let remote = opt.remote.as_ref().or(config.remote.as_ref());
+        let remote = remote
+            .map(String::as_str)
+            .filter(|remote| !remote.is_empty());
+        let variables = plugin::environment(&file, remote, &config, width);
+        match plugin::run(args, &variables) {
+            Ok(status) => std::process::exit(status),
+            Err(error) => {
+                println!("{}", error);
+                std::process::exit(2);
+            }
+        }
+    }
+    // Filter works on stdin and stdout only, so it never loads or saves the list file
+    if let Command::Filter { query } = &opt.command {
+        if let Err(error) = run_filter(query, &config) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    // Serve reads the list file afresh for every request
+    if let Command::Serve {
+        bind,
+        port,
+        tls_cert,
+        tls_key,
+        tls_self_signed,
+        tls_names,
+        spec,
+    } = &opt.command
+    {
+        if *spec {
+            println!("{:#}", openapi::spec());
+            return;
+        }
+        if shards::is_sharded(&file) {
+            println!("serve works on a list file, not a sharded list");
+            std::process::exit(2);
+        }
+        // Check the tokens file now rather than on the first request
+        let access = config.access();
+        if let Err(error) = access.scope("") {
+            println!("{}", error);
+            std::process::exit(2);
+        }
+        let inbox = match config.inbox() {
+            Ok(inbox) => inbox,
+            Err(error) => {
+                println!("{}", error);
+                std::process::exit(2);
+            }
+        };
+        // The certificate is loaded before listening, so a wrong one stops serve at once
+        let tls_files = match (tls_cert, tls_key) {
+            (Some(cert), Some(key)) => Some((cert.clone(), key.clone())),
+            _ if *tls_self_signed => match self_signed_certificate(&base, bind, tls_names) {
+                Ok(files) => Some(files),
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(1);
+                }
+            },
+            _ => None,
+        };
+        let acceptor = match &tls_files {
+            Some((cert, key)) => match tls::Acceptor::new(cert, key) {
+                Ok(acceptor) => Some(acceptor),
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(1);
+                }
+            },
+            None => None,
+        };
+        let listener = match std::net::TcpListener::bind((bind.as_str(), *port)) {
+            Ok(listener) => listener,
+            Err(error) => {
+                println!("Unable to listen on {}:{}: {}", bind, port, error);
+                std::process::exit(1);
+            }
+        };
+        println!(
+            "Serving {} on {}://{}:{}/todos (Ctrl-C to stop)",
+            file.display(),
+            if acceptor.is_some() { "https" } else { "http" },
+            bind,
+            port
+        );
+        if *tls_self_signed {
+            if let Some((cert, _)) = &tls_files {
+                // Clients trust the certificate itself, so it is worth checking it is this one
+                match tls::fingerprint(cert) {
+                    Ok(fingerprint) => println!(
+                        "Certificate {} (SHA-256 {}); set it as remote_ca on the clients",
+                        cert.display(),
+                        fingerprint
+                    ),
+                    Err(error) => println!("{}", error),
+                }
+            }
+        }
+        let mut limit = remote::RateLimit::new(config.serve_rate_limit);
+        let tls = acceptor.as_ref();
+        let served = remote::serve(listener, &file, &access, tls, &mut limit, &inbox);
+        if let Err(error) = served {
+            println!("Stopped serving: {}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    // Load the todo list from a file or create a new one if the file doesn't exist. Of a
+    // sharded list, only the shards of the projects the command reads are loaded
+    let loading = Instant::now();
+    let scope = match shards::is_sharded(&file) {
+        true => shard_scope(&opt.command, &config, &base),
+        false => None,
+    };
+    let mut todo_list: TodoList = match scope {
+        Some(projects) => {
+            let mut todo_list = shards::load(&file, Some(&projects))
+                .unwrap_or_else(|error| Failure::new("invalid_list", error).exit(2));
+            todo_list.assign_hashes();
+            todo_list
+        }
+        None => match load_todo_list(&file) {
+            Ok(todo_list) => todo_list.unwrap_or_default(),
+            // Restoring a backup is how a list that can't be read is recovered
+            Err(_) if matches!(opt.command, Command::Restore { .. }) => TodoList::new(),
+            Err(error) if matches!(opt.command, Command::Doctor { .. }) => {
+                println!("{}", error);
+                println!("Found 1 problem(s), which `doctor --fix` cannot repair");
+                std::process::exit(1);
+            }
+            Err(error) => Failure::new("invalid_list", error).exit(2),
+        },
+    };
+    timings::loaded(loading, todo_list.todos.len());
+    // Apply the priority aging policy if the user opted into running it on every load
+    if let Some(policy) = config
+        .priority_aging
+        .as_ref()
+        .filter(|policy| policy.on_load)
+    {
+        if todo_list.age_todos(policy.after_days, Utc::now().timestamp()) > 0 {
+            save_todo_list(&file, &todo_list);
+        }
+    }
     // Execute the appropriate action based on the parsed command
-    match command {
+    match opt.command {
         // Add a new todo item to the list
-        Command::Add { task, priority } => {
+        Command::Add(new_todo) => {
             let mut updated_todo_list = todo_list;
-            updated_todo_list.add_todo(&task, priority);
-            save_todo_list(&updated_todo_list);
+            let suggest = new_todo.suggests_priority();
+            match new_todo.add_to(&mut updated_todo_list, &config, &base) {
+                Ok(ids) => {
+                    // The rules were checked when the priorities were suggested
+                    let rules = config.priority_rules().unwrap_or_default();
+                    for id in ids.iter().filter(|_| suggest) {
+                        let todo = updated_todo_list
+                            .todo(*id)
+                            .expect("The todo was just added");
+                        let suggestion =
+                            triage::suggest(todo, &rules, config.default_priority(), now());
+                        println!(
+                            "Task {} ({}): suggested priority {}",
+                            id, todo.task, suggestion
+                        );
+                        if let triage::Answer::Priority(priority) =
+                            ask_priority(suggestion.priority, false)
+                        {
+                            // Still part of adding it, so it stays at revision 0
+                            let todo = updated_todo_list
+                                .todos
+                                .iter_mut()
+                                .find(|todo| todo.id == *id);
+                            todo.expect("The todo was just added").priority = priority;
+                        }
+                    }
+                    save_todo_list(&file, &updated_todo_list);
+                    // A single task keeps the quiet output of the one-task form
+                    if ids.len() > 1 {
+                        let ids: Vec<String> = ids.iter().map(u64::to_string).collect();
+                        println!("Added {} tasks: {}", ids.len(), ids.join(", "));
+                    }
+                }
+                Err(error) => println!("{}. Not Added", error),
+            }
         }
         // Remove a todo item from the list
-        Command::Remove { id } => {
+        Command::Remove(Removal {
+            ids,
+            if_rev,
+            dependents,
+        }) => {
+            let ids: Vec<u64> = ids.iter().map(|id| resolve_id(&todo_list, id)).collect();
+            if let Some(rev) = if_rev {
+                if ids.len() != 1 {
+                    println!("--if-rev takes a single ID");
+                    std::process::exit(2);
+                }
+                if let Err(error) = todo_list.check_revision(ids[0], rev) {
+                    println!("{}. Nothing deleted", error);
+                    std::process::exit(3);
+                }
+            }
             let mut updated_todo_list = todo_list;
-            updated_todo_list.remove_todo(id);
-            save_todo_list(&updated_todo_list);
+            let unblocked: Vec<String> = match dependents {
+                Dependents::Unblock => updated_todo_list
+                    .dependents(&ids)
+                    .into_iter()
+                    .filter_map(|id| updated_todo_list.todo(id))
+                    .filter(|todo| todo.depends.iter().all(|other| ids.contains(other)))
+                    .map(|todo| todo.task.clone())
+                    .collect(),
+                _ => Vec::new(),
+            };
+            let removing = match updated_todo_list.prepare_removal(&ids, dependents) {
+                Ok(removing) => removing,
+                Err(error) => {
+                    println!("{}. Nothing deleted", error);
+                    std::process::exit(1);
+                }
+            };
+            // Removing the highest IDs first leaves the IDs of the others as they were
+            let (removed, cascaded): (Vec<Todo>, Vec<Todo>) = removing
+                .into_iter()
+                .filter_map(|id| updated_todo_list.remove_todo(id))
+                .partition(|todo| ids.contains(&todo.id));
+            save_todo_list(&file, &updated_todo_list);
+            for task in unblocked {
+                println!("No longer blocked: {}", task);
+            }
+            for todo in cascaded.iter().rev() {
+                println!(
+                    "Also removed {} ({}), which depended on a removed task",
+                    todo.id, todo.task
+                );
+            }
+            // The tasks removed along with them were not done, so only those asked for count
+            if let Err(error) = stats::record_completed(&base, &removed, now()) {
+                println!("{}", error);
+            }
+            for todo in removed.into_iter().chain(cascaded) {
+                attachments::delete_stored_copies(&base, &todo.attachments);
+            }
         }
         // Display the list of todos
-        Command::List => {
-            todo_list.display_todos();
+        Command::List {
+            assignee,
+            mine,
+            all,
+            someday,
+            created_by,
+            near,
+            tag,
+            modified_since,
+            as_of,
+            filter,
+            view,
+            sort,
+            ids_only,
+            format,
+            watch,
+            done,
+            since,
+        } => {
+            if done {
+                let now = Utc::now().timestamp();
+                let since = match since
+                    .map(|since| dates::parse_since(&since, now))
+                    .transpose()
+                {
+                    Ok(since) => since,
+                    Err(error) => Failure::new("invalid_date", error).field("since").exit(2),
+                };
+                let filter = match filter.map(|filter| parse_query(&filter, now, &config)) {
+                    Some(Ok(query)) => Some(query),
+                    Some(Err(error)) => Failure::new("invalid_query", error).field("where").exit(2),
+                    None => None,
+                };
+                let mut completed = stats::completed_since(&base, since);
+                completed.retain(|item| {
+                    let todo = &item.todo;
+                    assignee
+                        .as_ref()
+                        .is_none_or(|assignee| todo.assignee.as_ref() == Some(assignee))
+                        && created_by
+                            .as_ref()
+                            .is_none_or(|creator| todo.created_by.as_ref() == Some(creator))
+                        && tag.as_ref().is_none_or(|tag| todo.has_tag(tag, now))
+                        && filter.as_ref().is_none_or(|query| query.matches(todo, now))
+                });
+                match format {
+                    _ if completed.is_empty() => {
+                        Failure::new("no_matches", "No completed tasks").exit(1)
+                    }
+                    ListFormat::Text => print!(
+                        "{}",
+                        stats::render_completed(&completed, &dates, now, config.plain)
+                    ),
+                    ListFormat::Ndjson => {
+                        for item in &completed {
+                            println!(
+                                "{}",
+                                serde_json::to_string(item).expect("Unable to serialize")
+                            );
+                        }
+                    }
+                }
+                return;
+            }
+            if watch {
+                if let Err(error) = watch_list(&file, &base, config.plain) {
+                    let message = format!("Unable to watch {}: {}", file.display(), error);
+                    Failure::new("io", message).exit(1);
+                }
+                return;
+            }
+            let mut updated_todo_list = todo_list;
+            // Virtual tags and queries are evaluated at the time the list is shown as of
+            let mut now = Utc::now().timestamp();
+            if let Some(as_of) = as_of {
+                match dates::parse_since(&as_of, now)
+                    .and_then(|at| load_todo_list_as_of(&file, at).map(|list| (at, list)))
+                {
+                    Ok((at, todo_list)) => {
+                        now = at;
+                        updated_todo_list = todo_list;
+                    }
+                    Err(error) => {
+                        Failure::new("invalid_date", error).field("as-of").report();
+                        return;
+                    }
+                }
+            }
+            let filters = Filters {
+                assignee,
+                mine,
+                someday,
+                created_by,
+                near,
+                tag,
+                modified_since,
+                filter,
+                view,
+                defaults: !all,
+            };
+            // The default order comes first so a view's or query's own sort can override it.
+            let (default_sort, sort) = match sort {
+                Some(sort) => (None, Some(sort)),
+                None => (config.default_sort.clone(), None),
+            };
+            if let Err(failure) = filters.select(
+                &mut updated_todo_list,
+                default_sort.as_deref(),
+                now,
+                &config,
+                &base,
+            ) {
+                failure.report();
+                return;
+            }
+            if let Some(sort) = sort {
+                match query::SortOrder::parse(&sort) {
+                    Ok(order) => order.sort(&mut updated_todo_list.todos),
+                    Err(error) => Failure::new("invalid_sort", error).field("sort").exit(2),
+                }
+            }
+            match format {
+                _ if ids_only => {
+                    for todo in &updated_todo_list.todos {
+                        println!("{}", todo.id);
+                    }
+                }
+                ListFormat::Text => updated_todo_list.display(&theme, config.plain, &dates, width),
+                ListFormat::Ndjson => {
+                    let mut output = std::io::BufWriter::new(std::io::stdout().lock());
+                    let result = updated_todo_list
+                        .write_ndjson(&mut output)
+                        .and_then(|_| output.flush());
+                    // A closed pipe, e.g. `| head`, just means no more output is wanted
+                    match result {
+                        Err(error) if error.kind() != std::io::ErrorKind::BrokenPipe => {
+                            eprintln!("Unable to write the todo list: {}", error)
+                        }
+                        _ => {}
+                    }
+                }
+            }
         }
         // Display help information
         Command::Help => {
@@ -231,80 +1473,2185 @@ fn main() {
         // Clear all todos from the list
         Command::Clear => {
             let mut updated_todo_list = todo_list;
-            updated_todo_list.clear_todo();
-            save_todo_list(&updated_todo_list);
+            let removed = updated_todo_list.clear_todo();
+            save_todo_list(&file, &updated_todo_list);
+            for todo in removed {
+                attachments::delete_stored_copies(&base, &todo.attachments);
+            }
         }
         // Prioritize and display todos
         Command::Prioritize => {
             let mut updated_todo_list = todo_list;
-            updated_todo_list
-                .todos
-                .sort_by_key(|todo| Reverse(todo.priority));
-            updated_todo_list.display_todos();
+            query::SortOrder::parse("priority desc")
+                .expect("Invalid sort order")
+                .sort(&mut updated_todo_list.todos);
+            updated_todo_list.display(&theme, config.plain, &dates, width);
         }
         // Display todos by creation date
         Command::Schedule => {
             let mut updated_todo_list = todo_list;
-            updated_todo_list.todos.sort_by_key(|todo| todo.created);
-            updated_todo_list.display_todos();
+            query::SortOrder::parse("created asc")
+                .expect("Invalid sort order")
+                .sort(&mut updated_todo_list.todos);
+            updated_todo_list.display(&theme, config.plain, &dates, width);
+        }
+        // Change the fields of a todo item
+        Command::Edit(edit) => {
+            if let Err(error) = edit.check_arguments() {
+                println!("{}. Not changed", error);
+                std::process::exit(2);
+            }
+            let id = resolve_id(&todo_list, &edit.id);
+            if let Some(Err(error)) = edit.if_rev.map(|rev| todo_list.check_revision(id, rev)) {
+                println!("{}. Not changed", error);
+                std::process::exit(3);
+            }
+            let mut updated_todo_list = todo_list;
+            match edit.apply_to(&mut updated_todo_list, &config) {
+                Ok(()) => save_todo_list(&file, &updated_todo_list),
+                Err(error) => {
+                    println!("{}. Not changed", error);
+                    std::process::exit(2);
+                }
+            }
+        }
+        // Add a todo item from a free-form string
+        Command::Quick { text } => match quick::parse_quick(&text, zone.local(now())) {
+            Ok(quick) => {
+                let mut updated_todo_list = todo_list;
+                let priority = quick.priority.unwrap_or(config.default_priority());
+                if !priorities::scale().contains(priority) {
+                    println!(
+                        "Invalid priority: {} for task: {}. Not Added",
+                        priority, quick.task
+                    );
+                    return;
+                }
+                // Built in full before it is added, so it starts at revision 0
+                let mut todo = updated_todo_list.new_todo(&quick.task, priority);
+                todo.due = quick.due.and_then(|due| zone.timestamp(due));
+                for tag in quick.tags {
+                    if !todo.tags.contains(&tag) {
+                        todo.tags.push(tag);
+                    }
+                }
+                todo.location = quick.context;
+                todo.created_by = config.current_user();
+                todo.apply_context(context::load(&base).as_ref());
+                let id = todo.id;
+                updated_todo_list.todos.push(todo);
+                save_todo_list(&file, &updated_todo_list);
+                println!("Added task {}", id);
+            }
+            Err(error) => println!("{}. Not Added", error),
+        },
+        // Compare the todo list with another file or a backup
+        Command::Diff {
+            file: other_file,
+            backup,
+            json,
+        } => {
+            let other_path = match (other_file, backup) {
+                (Some(other_file), _) => other_file,
+                (None, Some(backup)) => backup_path(&file, backup),
+                (None, None) => return,
+            };
+            let other = match load_todo_list_from(&other_path) {
+                Ok(Some(other)) => other,
+                Ok(None) => {
+                    println!("No todo list found at {}", other_path.display());
+                    return;
+                }
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(2);
+                }
+            };
+            let differences = diff::diff_lists(&other.todos, &todo_list.todos);
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&differences).expect("Unable to serialize diff")
+                );
+            } else {
+                diff::print_diff(&differences, &theme, config.plain);
+            }
+        }
+        // Sign a todo list file
+        Command::Sign {
+            file: sign_file,
+            key,
+        } => {
+            let sign_file = sign_file.unwrap_or_else(|| file.clone());
+            let key = match key.or(config.signing_key) {
+                Some(key) => key,
+                None => {
+                    println!("No signing key given. Pass --key or set signing_key in the config");
+                    return;
+                }
+            };
+            match signing::sign_file(&sign_file, &key) {
+                Ok(signature) => println!("Signature written to {}", signature.display()),
+                Err(error) => println!("Unable to sign {}: {}", sign_file.display(), error),
+            }
+        }
+        // Verify the signature of a todo list file
+        Command::Verify {
+            file: verify_file,
+            signer,
+            allowed_signers,
+        } => {
+            let (signer, allowed_signers) = match (
+                signer.or(config.trusted_signer),
+                allowed_signers.or(config.allowed_signers),
+            ) {
+                (Some(signer), Some(allowed_signers)) => (signer, allowed_signers),
+                _ => {
+                    println!("Pass --signer and --allowed-signers or set trusted_signer and allowed_signers in the config");
+                    return;
+                }
+            };
+            match signing::verify_file(&verify_file, &signer, &allowed_signers) {
+                Ok(()) => println!(
+                    "Good signature from {} on {}",
+                    signer,
+                    verify_file.display()
+                ),
+                Err(error) => {
+                    println!("Bad signature on {}: {}", verify_file.display(), error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        // Check the todo list for problems
+        Command::Doctor { fix } => {
+            let mut updated_todo_list = todo_list;
+            let problems =
+                doctor::check(&mut updated_todo_list, &base, fix, Utc::now().timestamp());
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            if problems.is_empty() {
+                println!("No problems found");
+            } else if fix {
+                save_todo_list(&file, &updated_todo_list);
+                println!("Fixed {} problem(s)", problems.len());
+            } else {
+                println!(
+                    "Found {} problem(s). Run `doctor --fix` to repair them",
+                    problems.len()
+                );
+                std::process::exit(1);
+            }
+        }
+        // Print the man page
+        Command::Man => {
+            print!("{}", manpage::render());
+        }
+        // Restore a backup of the todo list
+        Command::Restore { backup } => match load_todo_list_from(&backup_path(&file, backup)) {
+            Ok(Some(restored)) => {
+                if !restored.verify_checksum() {
+                    println!("Backup {} failed its checksum too. Not restored", backup);
+                    return;
+                }
+                save_todo_list(&file, &restored);
+                println!("Restored backup {}", backup);
+            }
+            Ok(None) => println!(
+                "No backup {} found at {}",
+                backup,
+                backup_path(&file, backup).display()
+            ),
+            Err(error) => {
+                println!("{}. Not restored", error);
+                std::process::exit(2);
+            }
+        },
+        // Show the details of a single todo item
+        Command::Show {
+            id,
+            qr,
+            predict,
+            format,
+        } => {
+            let id = resolve_id(&todo_list, &id);
+            match todo_list.todo(id) {
+                None => Failure::new("unknown_task", "Invalid ID").id(id).exit(2),
+                Some(todo) if format == Some(ShowFormat::Json) => println!(
+                    "{}",
+                    serde_json::to_string_pretty(todo).expect("Unable to serialize")
+                ),
+                Some(todo) if qr => {
+                    let text = match &todo.url {
+                        Some(url) => format!("{}\n{}", todo.task, url),
+                        None => todo.task.clone(),
+                    };
+                    print_qr(&text, config.plain);
+                }
+                Some(todo) if predict => {
+                    todo_list.show_todo(id, &dates);
+                    let now = now();
+                    let completed = stats::load_completed(&base);
+                    match predict::predict(todo, &completed, now) {
+                        Some(prediction) => println!(
+                            "prediction: {}",
+                            predict::describe(&prediction, &dates, now)
+                        ),
+                        None => println!("prediction: not enough history"),
+                    }
+                }
+                _ => todo_list.show_todo(id, &dates),
+            }
+        }
+        // Export the todo list as a checklist
+        Command::Export {
+            format,
+            assignee,
+            mine,
+            someday,
+            created_by,
+            near,
+            tag,
+            modified_since,
+            filter,
+            view,
+            qr,
+            anonymize,
+            output,
+        } => {
+            let mut todo_list = todo_list;
+            let filters = Filters {
+                assignee,
+                mine,
+                someday,
+                created_by,
+                near,
+                tag,
+                modified_since,
+                filter,
+                view,
+                defaults: false,
+            };
+            let now = Utc::now().timestamp();
+            if let Err(failure) = filters.select(&mut todo_list, None, now, &config, &base) {
+                failure.exit(2);
+            }
+            if anonymize {
+                anonymize::anonymize(&mut todo_list.todos);
+                todo_list.assign_hashes();
+            }
+            let contents = match format {
+                _ if qr => {
+                    if format != ExportFormat::Text {
+                        Failure::new("invalid_option", "--qr only works with the text format")
+                            .field("qr")
+                            .report();
+                        return;
+                    }
+                    print_qr(&todo_list.checklist(&dates), config.plain);
+                    return;
+                }
+                ExportFormat::Text => todo_list.checklist(&dates).into_bytes(),
+                ExportFormat::Pdf => todo_list.checklist_pdf(Utc::now().timestamp(), &dates),
+                ExportFormat::Html => html::render(
+                    &todo_list.todos,
+                    "Todo list",
+                    Utc::now().timestamp(),
+                    &dates,
+                )
+                .into_bytes(),
+                ExportFormat::MermaidGantt => {
+                    gantt::render(&todo_list.todos, "Todo list").into_bytes()
+                }
+                ExportFormat::Csv => todo_list.to_csv(zone).into_bytes(),
+                ExportFormat::Json => {
+                    // A list file numbers its items from 1, so a part of the list is renumbered
+                    todo_list.renumber();
+                    format!("{}\n", todo_list.to_json()).into_bytes()
+                }
+            };
+            match output {
+                Some(output) => match std::fs::write(&output, contents) {
+                    Ok(()) => println!("Exported to {}", output.display()),
+                    Err(error) => {
+                        let message = format!("Unable to write {}: {}", output.display(), error);
+                        Failure::new("io", message).report();
+                    }
+                },
+                None if format == ExportFormat::Pdf && std::io::stdout().is_terminal() => {
+                    println!(
+                        "Not writing a PDF to the terminal. Use --output <file> or redirect it"
+                    )
+                }
+                None => {
+                    if let Err(error) = std::io::stdout().write_all(&contents) {
+                        eprintln!("Unable to write the checklist: {}", error);
+                    }
+                }
+            }
+        }
+        // Attach a file to a todo item
+        Command::Attach { id, path, copy } => {
+            let id = resolve_id(&todo_list, &id);
+            let mut updated_todo_list = todo_list;
+            if updated_todo_list.todo(id).is_none() {
+                println!("Invalid ID");
+                return;
+            }
+            match attachments::store_attachment(&path, &base, copy) {
+                Ok(reference) => {
+                    updated_todo_list.attach_file(id, &reference);
+                    save_todo_list(&file, &updated_todo_list);
+                }
+                Err(error) => println!("Unable to attach {}: {}", path.display(), error),
+            }
+        }
+        // Open the link of a todo item
+        Command::Open { id } => {
+            let id = resolve_id(&todo_list, &id);
+            match todo_list.todo(id) {
+                Some(Todo { url: Some(url), .. }) => {
+                    if let Err(error) = open_in_browser(url) {
+                        println!("Unable to open {}: {}", url, error);
+                    }
+                }
+                Some(_) => println!("Task {} has no URL", id),
+                None => println!("Invalid ID"),
+            }
+        }
+        // Raise the priority of old todo items
+        Command::Age { after_days } => {
+            let after_days = after_days.or_else(|| {
+                config
+                    .priority_aging
+                    .as_ref()
+                    .map(|policy| policy.after_days)
+            });
+            match after_days {
+                Some(after_days) => {
+                    let mut updated_todo_list = todo_list;
+                    let raised = updated_todo_list.age_todos(after_days, Utc::now().timestamp());
+                    save_todo_list(&file, &updated_todo_list);
+                    println!("Raised the priority of {} task(s)", raised);
+                }
+                None => println!("No aging policy configured. Pass --after-days <days>"),
+            }
+        }
+        // Set a reminder for a todo item
+        Command::Remind { id, at, before_due } => {
+            let id = resolve_id(&todo_list, &id);
+            let mut updated_todo_list = todo_list;
+            let due = match updated_todo_list.todo(id) {
+                Some(todo) => todo.due,
+                None => {
+                    println!("Invalid ID");
+                    return;
+                }
+            };
+            let at = match (at, before_due) {
+                (Some(at), _) => dates::parse_due_in(&at, now(), zone, locale, &workdays),
+                (None, Some(before)) => match due {
+                    Some(due) => dates::parse_duration(&before).map(|before| due - before),
+                    None => Err(format!("Task {} has no due date", id)),
+                },
+                (None, None) => Err("Pass --at or --before-due".to_string()),
+            };
+            match at {
+                Ok(at) => {
+                    updated_todo_list.add_reminder(id, at);
+                    save_todo_list(&file, &updated_todo_list);
+                }
+                Err(error) => println!("{}", error),
+            }
+        }
+        // Deliver the reminders that have come due
+        Command::Reminders => {
+            let mut updated_todo_list = todo_list;
+            let fired = updated_todo_list.fire_reminders(Utc::now().timestamp());
+            for (id, task, at) in &fired {
+                let at = dates.format(*at, Utc::now().timestamp(), TIMESTAMP_FORMAT);
+                println!("Reminder: {}: {} ({})", id, task, at);
+            }
+            if !fired.is_empty() {
+                save_todo_list(&file, &updated_todo_list);
+            }
+        }
+        // Assign a todo item to someone
+        Command::Delegate { id, assignee } => {
+            let id = resolve_id(&todo_list, &id);
+            let mut updated_todo_list = todo_list;
+            updated_todo_list.delegate_todo(id, &assignee);
+            save_todo_list(&file, &updated_todo_list);
+        }
+        // Pin a color on a todo item
+        Command::Highlight {
+            id,
+            highlight,
+            clear,
+        } => {
+            let highlight = Some(highlight.join(" ")).filter(|_| !clear);
+            if let Some(Err(error)) = highlight.as_deref().map(theme::check_color) {
+                println!("{}", error);
+                std::process::exit(2);
+            }
+            let id = resolve_id(&todo_list, &id);
+            let mut updated_todo_list = todo_list;
+            match updated_todo_list.todo_mut(id) {
+                Some(todo) => todo.highlight = highlight,
+                None => {
+                    println!("Invalid ID");
+                    std::process::exit(2);
+                }
+            }
+            save_todo_list(&file, &updated_todo_list);
+        }
+        // Put a todo on the someday/maybe list, or take it off
+        Command::Someday { id, clear } => {
+            let id = resolve_id(&todo_list, &id);
+            let mut updated_todo_list = todo_list;
+            match updated_todo_list.todo_mut(id) {
+                Some(todo) => todo.someday = !clear,
+                None => {
+                    println!("Invalid ID");
+                    std::process::exit(2);
+                }
+            }
+            save_todo_list(&file, &updated_todo_list);
+        }
+        // Push the due dates of some todos, or of every overdue one, forward
+        Command::Postpone {
+            duration,
+            ids,
+            all_overdue,
+            filter,
+            yes,
+        } => {
+            let by = match dates::Postponement::parse(&duration, now(), zone, locale, &workdays) {
+                Ok(by) => by,
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(2);
+                }
+            };
+            let now = now();
+            let query = match parse_query(filter.as_deref().unwrap_or_default(), now, &config) {
+                Ok(query) => query,
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(2);
+                }
+            };
+            let ids: Vec<u64> = match all_overdue {
+                true => todo_list
+                    .todos
+                    .iter()
+                    .filter(|todo| todo.has_tag("OVERDUE", now) && !todo.someday)
+                    .map(|todo| todo.id)
+                    .collect(),
+                false => ids.iter().map(|id| resolve_id(&todo_list, id)).collect(),
+            };
+            postpone(todo_list, &file, &ids, (&query, by), yes, zone);
+        }
+        // Show, set or clear the active context
+        Command::Context { action } => match action {
+            None => match context::load(&base) {
+                Some(context) => println!("{}", context),
+                None => println!("No context is set"),
+            },
+            Some(ContextAction::Set { context }) => match context::Context::parse(&context) {
+                Ok(context) => match context::save(&base, Some(&context)) {
+                    Ok(()) => println!("Context set to {}", context),
+                    Err(error) => println!("Unable to set the context: {}", error),
+                },
+                Err(error) => println!("{}", error),
+            },
+            Some(ContextAction::Clear) => match context::save(&base, None) {
+                Ok(()) => println!("Context cleared"),
+                Err(error) => println!("Unable to clear the context: {}", error),
+            },
+        },
+        // Manage the goals and the todos linked to them
+        Command::Goal { action } => {
+            if let Err(error) = run_goal(
+                action,
+                todo_list,
+                (&file, &base),
+                zone,
+                (locale, &workdays),
+                &dates,
+                config.plain,
+            ) {
+                println!("{}", error);
+                std::process::exit(2);
+            }
+        }
+        // Rename a tag on every todo item, in the log of completed ones too
+        Command::Tag {
+            action: TagAction::Rename { from, to },
+        } => {
+            let (from, to) = match (tags::parse_name(&from), tags::parse_name(&to)) {
+                (Ok(from), Ok(to)) => (from, to),
+                (Err(error), _) | (_, Err(error)) => {
+                    println!("{}", error);
+                    std::process::exit(2);
+                }
+            };
+            let now = now();
+            let mut updated_todo_list = todo_list;
+            let mut open = 0;
+            for todo in &mut updated_todo_list.todos {
+                if tags::rename(todo, &from, &to) {
+                    todo.touch(now);
+                    open += 1;
+                }
+            }
+            if open > 0 {
+                save_todo_list(&file, &updated_todo_list);
+            }
+            let completed = stats::update_completed(&base, |todo| tags::rename(todo, &from, &to))
+                .unwrap_or_else(|error| {
+                    println!("{}", error);
+                    std::process::exit(2);
+                });
+            // A context on the old tag follows it
+            if let Some(context::Context::Tag(tag)) = context::load(&base) {
+                if fold::key(&tag, false) == fold::key(&from, false) {
+                    let context = context::Context::Tag(to.clone());
+                    if let Err(error) = context::save(&base, Some(&context)) {
+                        println!("Unable to save the context: {}", error);
+                    }
+                }
+            }
+            if open + completed == 0 {
+                println!("No task is tagged +{}", from);
+                std::process::exit(1);
+            }
+            println!(
+                "Renamed +{} to +{} on {} open and {} completed task(s)",
+                from, to, open, completed
+            );
+        }
+        // Add or remove dependencies of a todo item
+        Command::Depend { id, on, remove } => {
+            let id = resolve_id(&todo_list, &id);
+            let on: Vec<u64> = on
+                .iter()
+                .map(|other| resolve_id(&todo_list, other))
+                .collect();
+            let mut updated_todo_list = todo_list;
+            if remove {
+                updated_todo_list.remove_dependencies(id, &on);
+            } else {
+                updated_todo_list.add_dependencies(id, &on);
+            }
+            save_todo_list(&file, &updated_todo_list);
+        }
+        // Mark a todo item as waiting until a date
+        Command::Wait { id, until } => {
+            match dates::parse_due_in(&until, now(), zone, locale, &workdays) {
+                Ok(until) => {
+                    let id = resolve_id(&todo_list, &id);
+                    let mut updated_todo_list = todo_list;
+                    updated_todo_list.set_wait(id, until);
+                    save_todo_list(&file, &updated_todo_list);
+                }
+                Err(error) => println!("{}", error),
+            }
+        }
+        // Print the dependency graph
+        Command::Graph { format } => match format {
+            GraphFormat::Dot => print!("{}", graph::dot(&todo_list.todos, Utc::now().timestamp())),
+        },
+        // Set how long a todo item is expected to take
+        Command::Estimate { id, estimate } => match dates::parse_duration(&estimate) {
+            Ok(estimate) => {
+                let id = resolve_id(&todo_list, &id);
+                let mut updated_todo_list = todo_list;
+                updated_todo_list.set_estimate(id, estimate);
+                save_todo_list(&file, &updated_todo_list);
+            }
+            Err(error) => println!("{}", error),
+        },
+        // Plan the estimated todos over the week, or into today's free time
+        Command::Plan {
+            calendar: Some(calendar),
+            hours,
+            ..
+        } => {
+            let now = now();
+            let today = zone.local(now).date();
+            let hours = hours
+                .split_once('-')
+                .and_then(|(from, to)| {
+                    let from = zone.timestamp(today.and_time(quick::parse_time_word(from)?))?;
+                    let to = zone.timestamp(today.and_time(quick::parse_time_word(to)?))?;
+                    Some((from, to)).filter(|_| from < to)
+                })
+                .ok_or_else(|| format!("Invalid hours: '{}'. Expected e.g. 09:00-17:00", hours));
+            let busy = hours.and_then(|(from, to)| {
+                std::fs::read_to_string(&calendar)
+                    .map_err(|error| format!("Unable to read {}: {}", calendar.display(), error))
+                    .and_then(|contents| calendar::busy(&contents, from, to, zone))
+                    .map(|busy| (from, to, busy))
+            });
+            match busy {
+                Ok((from, to, busy)) => {
+                    let plan = plan::plan_day(&todo_list.todos, &busy, from, to, now);
+                    print!("{}", plan.render(zone));
+                }
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(2);
+                }
+            }
+        }
+        Command::Plan {
+            day,
+            capacity,
+            yes,
+            regenerate,
+            ..
+        } => {
+            let capacity = capacity
+                .or_else(|| config.daily_capacity.clone())
+                .unwrap_or_else(|| plan::CAPACITY.to_string());
+            let capacity = match dates::parse_duration(&capacity) {
+                Ok(capacity) if capacity > 0 && capacity <= 24 * 60 * 60 => capacity,
+                Ok(_) => {
+                    println!("The capacity must be more than 0 and at most 24h");
+                    return;
+                }
+                Err(error) => {
+                    println!("{}", error);
+                    return;
+                }
+            };
+            if day.is_none() {
+                let plan = plan::plan(&todo_list.todos, capacity, Utc::now().timestamp());
+                print!("{}", plan.render());
+                return;
+            }
+            plan_today(todo_list, &file, capacity, (yes, regenerate), zone);
+        }
+        // Suggest priorities and confirm them one by one
+        Command::Triage {
+            filter,
+            yes,
+            dry_run,
+        } => {
+            let now = now();
+            let query = parse_query(filter.as_deref().unwrap_or_default(), now, &config);
+            let (query, rules) =
+                match query.and_then(|query| config.priority_rules().map(|rules| (query, rules))) {
+                    Ok(parsed) => parsed,
+                    Err(error) => {
+                        println!("{}", error);
+                        std::process::exit(2);
+                    }
+                };
+            // Only the todos a rule applies to, and whose priority it would change
+            let suggestions: Vec<(u64, triage::Suggestion)> = todo_list
+                .todos
+                .iter()
+                .filter(|todo| !todo.someday && query.matches(todo, now))
+                .filter_map(|todo| {
+                    let suggestion = triage::suggest(todo, &rules, config.default_priority(), now);
+                    (!suggestion.reasons.is_empty() && suggestion.priority != todo.priority)
+                        .then_some((todo.id, suggestion))
+                })
+                .collect();
+            if suggestions.is_empty() {
+                println!("No task needs a different priority");
+                std::process::exit(1);
+            }
+            let mut updated_todo_list = todo_list;
+            let mut changed = 0;
+            for (id, suggestion) in suggestions {
+                let todo = updated_todo_list
+                    .todo(id)
+                    .expect("Suggested for a listed todo");
+                println!(
+                    "{}: {}: priority {} -> {}",
+                    id, todo.task, todo.priority, suggestion
+                );
+                let answer = match (dry_run, yes) {
+                    (true, _) => continue,
+                    (false, true) => triage::Answer::Priority(suggestion.priority),
+                    (false, false) => ask_priority(suggestion.priority, true),
+                };
+                match answer {
+                    triage::Answer::Priority(priority) if priority != todo.priority => {
+                        updated_todo_list
+                            .todo_mut(id)
+                            .expect("Suggested for a listed todo")
+                            .priority = priority;
+                        changed += 1;
+                    }
+                    triage::Answer::Quit => break,
+                    _ => {}
+                }
+            }
+            if changed > 0 {
+                save_todo_list(&file, &updated_todo_list);
+            }
+            if !dry_run {
+                println!("Changed {} task(s)", changed);
+            }
+        }
+        // Handled before the todo list is loaded
+        Command::Config { .. }
+        | Command::Filter { .. }
+        | Command::Serve { .. }
+        | Command::External(_) => unreachable!(),
+        // Mirror the issues of a Jira search
+        Command::Sync {
+            action: SyncAction::Jira { jql, transition },
+        } => {
+            let Some(jql) = jql.or_else(|| config.jira_jql.clone()) else {
+                println!("Give --jql or set jira_jql in the config");
+                std::process::exit(2);
+            };
+            let transition = transition
+                .or_else(|| config.jira_transition.clone())
+                .unwrap_or_else(|| jira::DONE_TRANSITION.to_string());
+            let tracker = config.jira().map(|server| jira::Sync {
+                server,
+                jql,
+                transition,
+                default_priority: config.default_priority(),
+                zone,
+            });
+            sync_tracker(&file, &base.join(jira::STATE_FILE), todo_list, tracker);
+        }
+        // Mirror the Asana tasks assigned to the user
+        Command::Sync {
+            action: SyncAction::Asana { mut projects },
+        } => {
+            if projects.is_empty() {
+                projects = config.asana_projects.clone();
+            }
+            if projects.is_empty() {
+                println!("Give --project or set asana_projects in the config");
+                std::process::exit(2);
+            }
+            let tracker = config.asana().map(|client| asana::Sync {
+                client,
+                projects,
+                default_priority: config.default_priority(),
+                zone,
+            });
+            sync_tracker(&file, &base.join(asana::STATE_FILE), todo_list, tracker);
+        }
+        // Without a remote there is nothing to sync
+        Command::Sync { .. } => {
+            println!("sync needs a remote: give --remote or set remote in the config");
+            std::process::exit(2);
         }
-        // Edit the task of a todo item
-        Command::Edit { task, id } => {
+        // Run each command of a workflow in turn
+        Command::Run { name, args } => {
+            let steps = match config.workflows.get(&name) {
+                Some(steps) => steps,
+                None => {
+                    println!("No workflow named {} in the config", name);
+                    std::process::exit(2);
+                }
+            };
+            if let Err(error) = run_workflow(steps, &args, &file) {
+                println!("Unable to run workflow {}: {}", name, error);
+                std::process::exit(2);
+            }
+        }
+        // Apply a file of commands all at once
+        Command::Batch { commands } => {
+            let contents = if commands == Path::new("-") {
+                let mut contents = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut contents)
+                    .map(|_| contents)
+            } else {
+                std::fs::read_to_string(&commands)
+            };
+            let contents = match contents {
+                Ok(contents) => contents,
+                Err(error) => {
+                    println!("Unable to read {}: {}", commands.display(), error);
+                    std::process::exit(2);
+                }
+            };
+            let mut updated_todo_list = todo_list;
+            let result = parse_batch(&contents).and_then(|batch| {
+                let count = batch.len();
+                apply_batch(&mut updated_todo_list, batch, &config, &base)
+                    .map(|removed| (count, removed))
+            });
+            match result {
+                Ok((count, (removed, cascaded))) => {
+                    save_todo_list(&file, &updated_todo_list);
+                    if let Err(error) = stats::record_completed(&base, &removed, now()) {
+                        println!("{}", error);
+                    }
+                    for todo in removed.into_iter().chain(cascaded) {
+                        attachments::delete_stored_copies(&base, &todo.attachments);
+                    }
+                    println!("Applied {} command(s)", count);
+                }
+                Err(error) => {
+                    println!("{}. Nothing was changed", error);
+                    std::process::exit(2);
+                }
+            }
+        }
+        // Merge another todo list into this one
+        Command::Import {
+            file: import_file,
+            format,
+            calendar,
+            map,
+            delimiter,
+            quote,
+            on_conflict,
+        } => {
+            let csv_only = map.is_some() || delimiter.is_some() || quote.is_some();
+            if format != import::Format::Csv && csv_only {
+                println!("--map, --delimiter and --quote only apply to --format csv");
+                std::process::exit(2);
+            }
+            if format != import::Format::Ics && calendar.is_some() {
+                println!("--calendar only applies to --format ics");
+                std::process::exit(2);
+            }
+            let read = match format {
+                import::Format::Json => import::read(&import_file).map(|todos| (todos, Vec::new())),
+                import::Format::Csv => {
+                    let default = csv::Dialect::default();
+                    let options = import::CsvOptions {
+                        dialect: csv::Dialect {
+                            delimiter: delimiter.unwrap_or(default.delimiter),
+                            quote: quote.unwrap_or(default.quote),
+                        },
+                        mapping: map,
+                    };
+                    std::fs::read_to_string(&import_file)
+                        .map_err(|error| {
+                            format!("Unable to read {}: {}", import_file.display(), error)
+                        })
+                        .and_then(|contents| {
+                            import::read_csv(
+                                &contents,
+                                &options,
+                                zone,
+                                config.default_priority(),
+                                now(),
+                            )
+                        })
+                }
+                import::Format::Ics => std::fs::read_to_string(&import_file)
+                    .map_err(|error| format!("Unable to read {}: {}", import_file.display(), error))
+                    .and_then(|contents| {
+                        calendar::tasks(
+                            &contents,
+                            calendar.as_deref(),
+                            zone,
+                            config.default_priority(),
+                            now(),
+                        )
+                    })
+                    .map(|todos| (todos, Vec::new())),
+                import::Format::Keep => keep::read(&import_file, config.default_priority())
+                    .map(|todos| (todos, Vec::new())),
+            };
+            let (imported, rejected) = match read {
+                Ok(read) => read,
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(2);
+                }
+            };
+            let count = imported.len();
             let mut updated_todo_list = todo_list;
-            updated_todo_list.edit_todo(&task, id);
-            save_todo_list(&updated_todo_list);
+            let summary = import::merge(
+                &mut updated_todo_list,
+                imported,
+                on_conflict,
+                &mut ask_conflict,
+            );
+            if summary.changed() {
+                save_todo_list(&file, &updated_todo_list);
+            }
+            println!(
+                "Imported {} task(s) from {}: {}",
+                count,
+                import_file.display(),
+                summary
+            );
+            if !rejected.is_empty() {
+                println!("Left out {} row(s):", rejected.len());
+                for row in &rejected {
+                    println!("    {}", row);
+                }
+                std::process::exit(1);
+            }
+        }
+        // Write the todo list to a file in another format
+        Command::Convert { output } if shards::is_sharded(&output) => {
+            if output.join(shards::INDEX_FILE).exists() {
+                println!(
+                    "{} already holds a list. Not overwriting it",
+                    output.display()
+                );
+                std::process::exit(1);
+            }
+            if let Err(error) = shards::save(&output, &todo_list, now()) {
+                println!("{}", error);
+                std::process::exit(1);
+            }
+            let projects = shards::Index::load(&output).map_or(0, |index| index.projects.len());
+            println!(
+                "Converted {} task(s) from {} to {} shard(s) in {}. Use --file {} or the file setting to work on it",
+                todo_list.todos.len(),
+                file.display(),
+                projects,
+                output.display(),
+                output.display()
+            );
+        }
+        Command::Convert { output } => {
+            let Some(format) = Format::named(&output) else {
+                println!(
+                    "Unable to tell the format of {} from its extension. Use .json, .yaml, .yml, .toml or .json5",
+                    output.display()
+                );
+                std::process::exit(2);
+            };
+            if output.exists() {
+                println!("{} already exists. Not overwriting it", output.display());
+                std::process::exit(1);
+            }
+            if let Err(error) = std::fs::write(&output, todo_list.to_format(format)) {
+                println!("Unable to write {}: {}", output.display(), error);
+                std::process::exit(1);
+            }
+            println!(
+                "Converted {} task(s) from {} to {} ({}). Use --file {} or the file setting to work on it",
+                todo_list.todos.len(),
+                file.display(),
+                output.display(),
+                format,
+                output.display()
+            );
+        }
+        // Count the todos, per tag or project, or list the stale ones
+        Command::Stats {
+            stale,
+            older_than,
+            by,
+            charts,
+            recurring,
+        } => {
+            let now = Utc::now().timestamp();
+            if recurring {
+                let records: Vec<(&Todo, habits::Record)> = todo_list
+                    .todos
+                    .iter()
+                    .filter_map(|todo| {
+                        let history = habits::history(todo, now)?;
+                        Some((todo, habits::Record::of(&history)))
+                    })
+                    .collect();
+                if records.is_empty() {
+                    println!("No recurring tasks");
+                    std::process::exit(1);
+                }
+                print!("{}", habits::render_records(&records));
+                return;
+            }
+            if let Some(by) = by {
+                let completed = stats::load_completed(&base);
+                let groups = stats::by_group(&todo_list.todos, &completed, by, now);
+                print!("{}", stats::render_groups(&groups, by));
+                return;
+            }
+            let threshold = match dates::parse_duration(&older_than) {
+                Ok(threshold) => threshold,
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(2);
+                }
+            };
+            if !stale {
+                print!(
+                    "{}",
+                    stats::stats(&todo_list.todos, threshold, now).render(&older_than)
+                );
+                if charts {
+                    let completed = stats::load_completed(&base);
+                    let charts = stats::charts(&todo_list.todos, &completed, zone, now);
+                    print!("\n{}", charts.render(config.plain));
+                }
+                return;
+            }
+            let stale = stats::stale(&todo_list.todos, threshold, now);
+            if stale.is_empty() {
+                println!("No task has gone untouched for {}", older_than);
+            } else {
+                print!("{}", stats::render_stale(&stale, &dates, now));
+            }
+        }
+        // Check off the current occurrence of a recurring todo
+        Command::Done { id, late, if_rev } => {
+            let id = resolve_id(&todo_list, &id);
+            if let Some(Err(error)) = if_rev.map(|rev| todo_list.check_revision(id, rev)) {
+                println!("{}. Not checked off", error);
+                std::process::exit(3);
+            }
+            let now = now();
+            let checked = match todo_list.todo(id) {
+                Some(todo) if late => habits::check_late(todo, now).map(Some),
+                Some(todo) => habits::check(todo, now).map(|()| None),
+                None => Err("Invalid ID".to_string()),
+            };
+            let occurrence = match checked {
+                Ok(occurrence) => occurrence,
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(2);
+                }
+            };
+            let mut updated_todo_list = todo_list;
+            let todo = updated_todo_list.todo_mut(id).expect("Checked above");
+            match occurrence {
+                Some(start) => todo.late.push(start),
+                None => todo.done.push(now),
+            }
+            if let Some(habit) = habits::habit(todo, now) {
+                println!("{}: {}", id, habit);
+            }
+            save_todo_list(&file, &updated_todo_list);
+        }
+        // Predict when the open todos will be done
+        Command::Forecast { filter } => {
+            let now = now();
+            let query = match parse_query(filter.as_deref().unwrap_or_default(), now, &config) {
+                Ok(query) => query,
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(2);
+                }
+            };
+            let completed = stats::load_completed(&base);
+            let mut rows: Vec<(&Todo, Option<predict::Prediction>)> = todo_list
+                .todos
+                .iter()
+                .filter(|todo| todo.recur.is_none() && !todo.someday)
+                .filter(|todo| query.matches(todo, now))
+                .map(|todo| (todo, predict::predict(todo, &completed, now)))
+                .collect();
+            if rows.is_empty() {
+                println!("No tasks to forecast");
+                std::process::exit(1);
+            }
+            print!("{}", predict::render_forecast(&mut rows, &dates, now));
+        }
+        // Show one todo alone until it is done
+        Command::Focus { id } => {
+            let id = resolve_id(&todo_list, &id);
+            let Some(todo) = todo_list.todo(id).cloned() else {
+                println!("Invalid ID");
+                std::process::exit(2);
+            };
+            let started = now();
+            loop {
+                let at = now();
+                if !config.plain {
+                    // Clear the screen and move the cursor to the top left
+                    print!("\x1b[2J\x1b[H");
+                }
+                print!(
+                    "{}",
+                    focus::render(&todo, at - started, &dates, at, config.plain)
+                );
+                print!("\nEnter updates the timer, [d]one or [q]uit: ");
+                let _ = std::io::stdout().flush();
+                let mut answer = String::new();
+                let action = match std::io::stdin().read_line(&mut answer) {
+                    Ok(0) | Err(_) => Some(focus::Action::Quit),
+                    Ok(_) => focus::parse_action(&answer),
+                };
+                match action {
+                    Some(focus::Action::Done) => break,
+                    Some(focus::Action::Quit) => {
+                        let focused = dates::format_duration(now() - started);
+                        println!("\nFocused on task {} for {}", id, focused);
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+            let now = now();
+            let focused = dates::format_duration(now - started);
+            let mut updated_todo_list = todo_list;
+            if todo.recur.is_some() {
+                if let Err(error) = habits::check(&todo, now) {
+                    println!("{}", error);
+                    std::process::exit(2);
+                }
+                updated_todo_list
+                    .todo_mut(id)
+                    .expect("Resolved above")
+                    .done
+                    .push(now);
+                save_todo_list(&file, &updated_todo_list);
+            } else {
+                let removed: Vec<Todo> = updated_todo_list.remove_todo(id).into_iter().collect();
+                save_todo_list(&file, &updated_todo_list);
+                if let Err(error) = stats::record_completed(&base, &removed, now) {
+                    println!("{}", error);
+                }
+                attachments::delete_stored_copies(&base, &todo.attachments);
+            }
+            println!("Done: {} (focused for {})", todo.task, focused);
+        }
+        // Show the streaks of the recurring todos
+        Command::Habits => {
+            let now = now();
+            let mut found = false;
+            for todo in &todo_list.todos {
+                if let Some(habit) = habits::habit(todo, now) {
+                    let mark = if habit.done { "x" } else { " " };
+                    println!("[{}] {}: {} ({})", mark, todo.id, todo.task, habit);
+                    found = true;
+                }
+            }
+            if !found {
+                println!("No recurring tasks");
+                std::process::exit(1);
+            }
+        }
+        // Substitute a pattern in the tasks
+        Command::Replace {
+            pattern,
+            replacement,
+            filter,
+            dry_run,
+        } => {
+            let now = Utc::now().timestamp();
+            let regex =
+                regex::Regex::new(&pattern).map_err(|error| format!("Invalid pattern: {}", error));
+            let query = parse_query(filter.as_deref().unwrap_or_default(), now, &config);
+            let (regex, query) = match regex.and_then(|regex| query.map(|query| (regex, query))) {
+                Ok(parsed) => parsed,
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(2);
+                }
+            };
+            let mut changes: Vec<(u64, String)> = Vec::new();
+            for todo in todo_list
+                .todos
+                .iter()
+                .filter(|todo| query.matches(todo, now))
+            {
+                let task = regex
+                    .replace_all(&todo.task, &replacement)
+                    .unwrap_or_else(|error| {
+                        println!("Task {}: {}. Nothing was saved", todo.id, error);
+                        std::process::exit(2);
+                    });
+                if task != todo.task {
+                    println!("{}: {} -> {}", todo.id, todo.task, task);
+                    changes.push((todo.id, task));
+                }
+            }
+            if changes.is_empty() {
+                println!("No matching tasks");
+                std::process::exit(1);
+            }
+            if dry_run {
+                println!("{} task(s) would change. Nothing was saved", changes.len());
+                return;
+            }
+            let mut updated_todo_list = todo_list;
+            for (id, task) in &changes {
+                if let Some(todo) = updated_todo_list.todo_mut(*id) {
+                    todo.task = task.clone();
+                }
+            }
+            save_todo_list(&file, &updated_todo_list);
+            println!("Changed {} task(s)", changes.len());
         }
+        // List the todos matching a query
+        Command::Search {
+            query,
+            count_only,
+            fuzzy,
+            ignore_accents,
+        } => {
+            let now = Utc::now().timestamp();
+            let query = parse_query(&query, now, &config).map(|query| {
+                let query = if fuzzy { query.fuzzy() } else { query };
+                if ignore_accents {
+                    query.ignore_accents()
+                } else {
+                    query
+                }
+            });
+            match query {
+                Ok(query) => print_matches(
+                    query.select(todo_list.todos, now),
+                    count_only,
+                    &theme,
+                    config.plain,
+                    &dates,
+                    width,
+                ),
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(2);
+                }
+            }
+        }
+        // List the todos past their due date, soonest first
+        Command::Overdue { count_only } => {
+            let now = Utc::now().timestamp();
+            let query = query::Query::parse("overdue sort:due", now).expect("Invalid query");
+            let mut todos = query.select(todo_list.todos, now);
+            todos.retain(|todo| !todo.someday);
+            print_matches(todos, count_only, &theme, config.plain, &dates, width);
+        }
+        // Count the todos, or those matching a query
+        Command::Count { query, someday } => {
+            let now = Utc::now().timestamp();
+            let mut todos = todo_list.todos;
+            todos.retain(|todo| todo.someday == someday);
+            match parse_query(query.as_deref().unwrap_or_default(), now, &config) {
+                Ok(query) => print_matches(
+                    query.select(todos, now),
+                    true,
+                    &theme,
+                    config.plain,
+                    &dates,
+                    width,
+                ),
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(2);
+                }
+            }
+        }
+        // Query the todos with SQL
+        Command::Sql {
+            query,
+            format,
+            schema,
+        } => match query {
+            Some(query) if !schema => {
+                let format = match format {
+                    sql::OutputMode::Table if config.plain => sql::OutputMode::Lines,
+                    format => format,
+                };
+                match sql::query(&todo_list.todos, &query, format) {
+                    Ok(output) => print!("{}", output),
+                    Err(error) => Failure::new("sql", error).exit(2),
+                }
+            }
+            _ => print!("{}", sql::SCHEMA),
+        },
     }
 }
-/// Loads a todo list from a JSON file.
-///
-/// # Returns
-///
-/// Returns an `Option<TodoList>` containing the loaded todo list if the file exists;
-/// returns `None` otherwise.
-///
-/// # Example
-///
-/// ```
-/// let loaded_todo_list = load_todo_list();
-/// if let Some(todo_list) = loaded_todo_list {
-///     // Process the loaded todo list...
-/// } else {
-///     // No todo list file found.
-/// }
-/// ```
-fn load_todo_list() -> Option<TodoList> {
-    let path = Path::new("./todos.json");
-    if path.exists() {
-        let mut file = File::open(path).expect("Unable to open todo list file");
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .expect("Unable to read todo list file");
-        let todo_list: TodoList = serde_json::from_str(&contents).expect("Unable to parse JSON");
-        Some(todo_list)
+
+/// Opens a URL with the platform's default handler.
+fn open_in_browser(url: &str) -> std::io::Result<()> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
     } else {
-        None
+        std::process::Command::new("xdg-open")
+    };
+    let status = command.arg(url).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "browser exited with {}",
+            status
+        )))
     }
 }
-/// Saves a todo list to a JSON file.
+/// Saves a todo list to a file in the format of its extension, backing up the previous
+/// version, as `try_save_todo_list` does. If the changes made to the list can't be applied
+/// to a file another process changed meanwhile, or the file can't be written, nothing is
+/// saved and the program exits with status 3.
 ///
 /// # Arguments
 ///
+/// * `path` - The todo list file to write.
 /// * `todo_list` - The todo list to be saved.
 ///
 /// # Example
 ///
 /// ```
 /// let todo_list = TodoList::new();
-/// save_todo_list(&todo_list);
+/// save_todo_list(Path::new("./todos.json"), &todo_list);
 /// ```
-fn save_todo_list(todo_list: &TodoList) {
-    let path = Path::new("./todos.json");
-    let serialized =
-        serde_json::to_string_pretty(&todo_list).expect("Unable to serialize todo list");
-    let mut file = File::create(path).expect("Unable to create todo list file");
-    file.write_all(serialized.as_bytes())
-        .expect("Unable to write todo list to file");
+fn save_todo_list(path: &Path, todo_list: &TodoList) {
+    if let Err(error) = try_save_todo_list(path, todo_list) {
+        println!("{}. Nothing saved", error);
+        std::process::exit(3);
+    }
+}
+/// Mirrors the items of `tracker` in the todo list at `path`, with the keys mirrored by the
+/// last sync kept in `state_file`, and prints what changed. Exits with status 1 if the
+/// tracker could not be reached, or told of every task marked done.
+fn sync_tracker(
+    path: &Path,
+    state_file: &Path,
+    todo_list: TodoList,
+    tracker: Result<impl mirror::Tracker, String>,
+) {
+    let loaded =
+        tracker.and_then(|tracker| mirror::State::load(state_file).map(|state| (tracker, state)));
+    let (tracker, mut state) = match loaded {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            println!("{}", error);
+            std::process::exit(2);
+        }
+    };
+    let mut updated_todo_list = todo_list;
+    let synced = mirror::sync(&mut updated_todo_list, &tracker, &mut state, now());
+    let summary = match state.save(state_file).and(synced) {
+        Ok(summary) => summary,
+        Err(error) => {
+            println!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    for key in &summary.done {
+        println!("Marked {} done in {}", key, tracker.name());
+    }
+    for (key, error) in &summary.failed {
+        println!(
+            "Unable to mark {} done in {}: {}",
+            key,
+            tracker.name(),
+            error
+        );
+    }
+    if summary.changed() {
+        save_todo_list(path, &updated_todo_list);
+    }
+    println!("Synced with {}: {}", tracker.name(), summary);
+    if !summary.failed.is_empty() {
+        std::process::exit(1);
+    }
+}
+/// Returns the self-signed certificate and key `serve --tls-self-signed` uses for the list
+/// in `base`, making them for localhost, `bind` and `names` unless they exist.
+fn self_signed_certificate(
+    base: &Path,
+    bind: &str,
+    names: &[String],
+) -> Result<(PathBuf, PathBuf), String> {
+    let directory = base.join(tls::SELF_SIGNED_DIR);
+    let (cert, key) = (directory.join("cert.pem"), directory.join("key.pem"));
+    if cert.exists() && key.exists() {
+        return Ok((cert, key));
+    }
+    std::fs::create_dir_all(&directory)
+        .map_err(|error| format!("Unable to create {}: {}", directory.display(), error))?;
+    let mut all = vec!["localhost".to_string()];
+    // A certificate for every address is no certificate at all
+    let unspecified = bind
+        .parse::<std::net::IpAddr>()
+        .is_ok_and(|address| address.is_unspecified());
+    for name in std::iter::once(bind).chain(names.iter().map(String::as_str)) {
+        if (unspecified && name == bind) || all.iter().any(|known| known == name) {
+            continue;
+        }
+        all.push(name.to_string());
+    }
+    tls::self_signed(&cert, &key, &all)?;
+    println!("Made a self-signed certificate for {}", all.join(", "));
+    Ok((cert, key))
+}
+/// The filters `list` and `export` select todo items with.
+struct Filters {
+    assignee: Option<String>,
+    mine: bool,
+    someday: bool,
+    created_by: Option<String>,
+    near: Option<String>,
+    tag: Option<String>,
+    modified_since: Option<String>,
+    filter: Option<String>,
+    view: Option<String>,
+    /// Whether `list_mine_by_default`, the `default_view`, the active context and leaving out
+    /// the someday/maybe list apply too, as they do to `list` without `--all`.
+    defaults: bool,
+}
+impl Filters {
+    /// Keeps the todo items of `todo_list` the filters select at `now`, with the active
+    /// context in `base`, sorting them by `default_sort` before any view or query, so that
+    /// their own sort wins. Exits with status 2 on an invalid view, query or `default_sort`,
+    /// and returns the other errors.
+    fn select(
+        self,
+        todo_list: &mut TodoList,
+        default_sort: Option<&str>,
+        now: i64,
+        config: &Config,
+        base: &Path,
+    ) -> Result<(), Failure> {
+        let mine =
+            self.mine || (config.list_mine_by_default && self.defaults && self.assignee.is_none());
+        let assignee = if mine {
+            config.current_user()
+        } else {
+            self.assignee
+        };
+        if let Some(assignee) = assignee {
+            todo_list.retain_assignee(&assignee);
+        } else if mine {
+            return Err(
+                Failure::new("no_user", "Unable to determine the current user").field("mine"),
+            );
+        }
+        if let Some(created_by) = self.created_by {
+            todo_list.retain_creator(&created_by);
+        }
+        if let Some(since) = self.modified_since {
+            let since = dates::parse_since(&since, Utc::now().timestamp())
+                .map_err(|error| Failure::new("invalid_date", error).field("modified-since"))?;
+            todo_list.todos.retain(|todo| todo.last_modified() >= since);
+        }
+        let inherited = config.inherited_tags();
+        if let Some(tag) = self.tag {
+            todo_list.todos.retain(|todo| {
+                todo.has_tag(&tag, now)
+                    || todo
+                        .project
+                        .as_ref()
+                        .and_then(|project| inherited.get(project))
+                        .is_some_and(|tags| tags.contains(&tag))
+            });
+        }
+        if let Some(near) = self.near {
+            todo_list.todos.retain(|todo| {
+                todo.location
+                    .as_deref()
+                    .is_some_and(|location| config.is_near(location, &near))
+            });
+        }
+        if let Some(context) = context::load(base).filter(|_| self.defaults) {
+            todo_list
+                .todos
+                .retain(|todo| context.matches(todo, &inherited));
+        }
+        if self.defaults || self.someday {
+            todo_list.todos.retain(|todo| todo.someday == self.someday);
+        }
+        let view = match self.view {
+            Some(view) => Some(view),
+            None if self.filter.is_none() && self.defaults => config.default_view.clone(),
+            None => None,
+        };
+        let view = match view.as_deref().map(|view| config.view(view)).transpose() {
+            Ok(view) => view,
+            Err(error) => Failure::new("invalid_view", error).field("view").exit(2),
+        };
+        if let Some(default_sort) = default_sort {
+            match query::SortOrder::parse(default_sort) {
+                Ok(order) => order.sort(&mut todo_list.todos),
+                Err(error) => {
+                    let message = format!("Invalid default_sort in the config: {}", error);
+                    Failure::new("invalid_config", message)
+                        .field("default_sort")
+                        .exit(2);
+                }
+            }
+        }
+        for (field, filter) in [("view", view), ("where", self.filter.as_deref())] {
+            let Some(filter) = filter else {
+                continue;
+            };
+            match parse_query(filter, now, config) {
+                Ok(query) => {
+                    todo_list.todos = query.select(std::mem::take(&mut todo_list.todos), now)
+                }
+                Err(error) => Failure::new("invalid_query", error).field(field).exit(2),
+            }
+        }
+        Ok(())
+    }
+}
+/// Returns the only projects `command` reads the todo items of, when it reads only some,
+/// from its query, view and the active context, found in `base`.
+fn shard_scope(command: &Command, config: &Config, base: &Path) -> Option<BTreeSet<String>> {
+    let now = now();
+    let projects = |query: &str| {
+        parse_query(query, now, config)
+            .ok()
+            .and_then(|query| query.projects())
+    };
+    let scopes: Vec<BTreeSet<String>> = match command {
+        Command::List {
+            done: false,
+            as_of: None,
+            watch: false,
+            filter,
+            view,
+            all,
+            ..
+        } => {
+            let view = match view {
+                Some(view) => Some(view.as_str()),
+                None if filter.is_none() && !all => config.default_view.as_deref(),
+                None => None,
+            };
+            let view = view.and_then(|view| config.view(view).ok());
+            let mut scopes: Vec<BTreeSet<String>> = [filter.as_deref(), view]
+                .into_iter()
+                .flatten()
+                .filter_map(projects)
+                .collect();
+            if let Some(context::Context::Project(project)) = context::load(base).filter(|_| !all) {
+                scopes.push(BTreeSet::from([project]));
+            }
+            scopes
+        }
+        Command::Export { filter, view, .. } => {
+            let view = view.as_deref().and_then(|view| config.view(view).ok());
+            [filter.as_deref(), view]
+                .into_iter()
+                .flatten()
+                .filter_map(projects)
+                .collect()
+        }
+        Command::Count {
+            query: Some(query), ..
+        } => projects(query).into_iter().collect(),
+        _ => Vec::new(),
+    };
+    scopes
+        .into_iter()
+        .reduce(|a, b| a.intersection(&b).cloned().collect())
+}
+/// Asks on stdin what `import --on-conflict prompt` does with an imported item that differs
+/// from the task `existing` in `fields`, skipping it once stdin ends.
+fn ask_conflict(existing: &Todo, todo: &Todo, fields: &[String]) -> import::Strategy {
+    let value = |todo: &Todo| serde_json::to_value(todo).expect("Unable to serialize todo");
+    let (before, after) = (value(existing), value(todo));
+    println!("Task {} ({}) differs:", existing.id, existing.task);
+    for field in fields {
+        let null = serde_json::Value::Null;
+        let before = before.get(field).unwrap_or(&null);
+        let after = after.get(field).unwrap_or(&null);
+        println!("    {}: {} -> {}", field, before, after);
+    }
+    loop {
+        print!("[s]kip, [o]verwrite or [d]uplicate? ");
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        match std::io::stdin().read_line(&mut answer) {
+            Ok(0) | Err(_) => return import::Strategy::Skip,
+            Ok(_) => {}
+        }
+        match answer.trim() {
+            "s" | "skip" => return import::Strategy::Skip,
+            "o" | "overwrite" => return import::Strategy::Overwrite,
+            "d" | "duplicate" => return import::Strategy::Duplicate,
+            _ => {}
+        }
+    }
+}
+/// Proposes the todo items of `todo_list`, saved at `file`, to work on today in `capacity`
+/// seconds, and marks them planned for today once the plan is accepted: at once with `yes`,
+/// or when asked, leaving out the items named and proposing again until it is. Shows the
+/// plan already accepted today instead, unless `regenerate` is set.
+fn plan_today(
+    todo_list: TodoList,
+    file: &Path,
+    capacity: i64,
+    (yes, regenerate): (bool, bool),
+    zone: dates::Zone,
+) {
+    let now = now();
+    let today = zone
+        .local(now)
+        .date()
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or_default();
+    let start = zone.timestamp(today).unwrap_or(now);
+    let accepted: Vec<&Todo> = todo_list
+        .todos
+        .iter()
+        .filter(|todo| todo.planned == Some(start))
+        .collect();
+    if !accepted.is_empty() && !regenerate {
+        let items: Vec<plan::Planned> = accepted
+            .iter()
+            .map(|todo| plan::Planned {
+                id: todo.id,
+                task: todo.task.clone(),
+                estimate: todo.estimate.unwrap_or_default(),
+                due: todo.due,
+                late: todo.due.is_some_and(|due| due < now),
+            })
+            .collect();
+        print!("{}", plan::render_today(&items, capacity));
+        println!("Accepted earlier today; plan today --regenerate proposes a new plan");
+        return;
+    }
+    let mut excluded = Vec::new();
+    let items = loop {
+        let items = plan::propose(&todo_list.todos, capacity, &excluded, now);
+        if items.is_empty() {
+            println!(
+                "No estimated task fits into today; set estimates with estimate <id> <duration>"
+            );
+            std::process::exit(1);
+        }
+        print!("{}", plan::render_today(&items, capacity));
+        if yes {
+            break items;
+        }
+        print!("Enter accepts, IDs leave those tasks out and propose again, q quits: ");
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        match std::io::stdin().read_line(&mut answer) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        match plan::parse_answer(&answer) {
+            Some(plan::Answer::Accept) => break items,
+            Some(plan::Answer::Exclude(ids)) => excluded.extend(ids),
+            Some(plan::Answer::Quit) => return,
+            None => println!("Unknown answer: {}", answer.trim()),
+        }
+    };
+    let mut updated_todo_list = todo_list;
+    for todo in &mut updated_todo_list.todos {
+        let planned = items.iter().any(|item| item.id == todo.id);
+        if planned != (todo.planned == Some(start)) {
+            todo.planned = Some(start).filter(|_| planned);
+            todo.touch(now);
+        }
+    }
+    save_todo_list(file, &updated_todo_list);
+    println!(
+        "Planned {} task(s) for today; list --where planned:today lists them",
+        items.len()
+    );
+}
+/// Offers the first-run setup and writes the config file at `path` with its answers, or
+/// empty if the user declines so that it is not offered again. Writes nothing if stdin ends
+/// before the setup does. The default priority is asked for on `scale`.
+fn offer_setup(path: PathBuf, scale: &priorities::Scale) -> Result<(), String> {
+    let answers = setup::run(
+        &mut std::io::stdin().lock(),
+        &mut std::io::stdout(),
+        setup::default_file(),
+        scale,
+    );
+    let settings = match answers {
+        Some(setup::Setup::Settings(settings)) => settings,
+        Some(setup::Setup::Declined) => Vec::new(),
+        None => {
+            println!();
+            return Ok(());
+        }
+    };
+    let mut file = config::ConfigFile::load(path.clone())?;
+    for (key, value) in &settings {
+        file.set(key, value)?;
+    }
+    // The list is created on the first save, in a directory that may not exist yet
+    if let Some((_, list)) = settings.iter().find(|(key, _)| *key == "file") {
+        if let Some(dir) = Path::new(list)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(dir)
+                .map_err(|error| format!("Unable to create {}: {}", dir.display(), error))?;
+        }
+    }
+    file.save()?;
+    println!(
+        "Wrote {}. Change the settings any time with config set or config edit.",
+        path.display()
+    );
+    Ok(())
+}
+/// Runs a `config` action, with `config` the settings in effect.
+fn run_config(action: &ConfigAction, config: &Config) -> Result<(), String> {
+    let path = || {
+        config::config_path().ok_or_else(|| {
+            format!(
+                "Unable to find the config file: set HOME or {}",
+                config::CONFIG_ENV
+            )
+        })
+    };
+    match action {
+        ConfigAction::Get { key } => {
+            config::check_key(key)?;
+            let settings = serde_json::to_value(config).expect("Unable to serialize config");
+            match &settings[key.as_str()] {
+                serde_json::Value::Null => {
+                    println!("{} is not set", key);
+                    std::process::exit(1);
+                }
+                value => println!("{}", config::format_value(value)),
+            }
+        }
+        ConfigAction::Set { key, value } => {
+            let mut file = config::ConfigFile::load(path()?)?;
+            file.set(key, value)?;
+            file.save()?;
+        }
+        ConfigAction::Unset { key } => {
+            let mut file = config::ConfigFile::load(path()?)?;
+            match file.unset(key)? {
+                true => file.save()?,
+                false => println!("{} is not set in the config file", key),
+            }
+        }
+        ConfigAction::List { all: false } => {
+            for (key, value) in config::changed(config) {
+                println!("{} = {}", key, config::format_value(&value));
+            }
+        }
+        ConfigAction::List { all: true } => {
+            let settings = serde_json::to_value(config).expect("Unable to serialize config");
+            for (key, description) in config::KEYS {
+                let value = match &settings[*key] {
+                    serde_json::Value::Null => "(not set)".to_string(),
+                    value => config::format_value(value),
+                };
+                println!("{} = {}\n    {}", key, value, description);
+            }
+        }
+        ConfigAction::Edit => {
+            let path = path()?;
+            if !path.exists() {
+                config::ConfigFile::load(path.clone())?.save()?;
+            }
+            let editor = ["VISUAL", "EDITOR"]
+                .iter()
+                .find_map(|name| {
+                    std::env::var(name)
+                        .ok()
+                        .filter(|editor| !editor.trim().is_empty())
+                })
+                .unwrap_or_else(|| "vi".to_string());
+            // The editor may come with arguments, e.g. "code --wait"
+            let mut words = editor.split_whitespace();
+            let program = words.next().expect("The editor is not blank");
+            let status = std::process::Command::new(program)
+                .args(words)
+                .arg(&path)
+                .status()
+                .map_err(|error| format!("Unable to run {}: {}", editor, error))?;
+            if !status.success() {
+                return Err(format!("{} exited with {}", editor, status));
+            }
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+            if let Err(error) = serde_json::from_str::<Config>(&contents) {
+                return Err(format!(
+                    "{} is not valid now: {}. Run config edit again to fix it",
+                    path.display(),
+                    error
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+/// Runs a `goal` action on the goals of the todo list at `file` in `base`, with dates in
+/// the timezone `zone` and shown in the format `dates`, or as labeled lines when `plain` is
+/// set.
+fn run_goal(
+    action: GoalAction,
+    todo_list: TodoList,
+    (file, base): (&Path, &Path),
+    zone: dates::Zone,
+    (locale, workdays): (Locale, &Workdays),
+    dates: &DateFormat,
+    plain: bool,
+) -> Result<(), String> {
+    let mut goals = goals::load(base)?;
+    let find = |goals: &[goals::Goal], name: &str| {
+        let name = goals::check_name(name)?;
+        goals
+            .iter()
+            .position(|goal| goal.name == name)
+            .ok_or_else(|| format!("No goal named '{}'", name))
+    };
+    let mut updated_todo_list = todo_list;
+    match action {
+        GoalAction::Add { name, by } => {
+            let name = goals::check_name(&name)?;
+            if goals.iter().any(|goal| goal.name == name) {
+                return Err(format!("There already is a goal named '{}'", name));
+            }
+            let by = by
+                .map(|by| dates::parse_due_in(&by, now(), zone, locale, workdays))
+                .transpose()?;
+            goals.push(goals::Goal {
+                name: name.to_string(),
+                created: now(),
+                by,
+            });
+            goals::save(base, &goals)?;
+            println!("Added goal {}", name);
+        }
+        GoalAction::Remove { name } => {
+            let goal = goals.remove(find(&goals, &name)?);
+            let mut unlinked = 0;
+            for todo in &mut updated_todo_list.todos {
+                if todo.goal.as_ref() == Some(&goal.name) {
+                    todo.goal = None;
+                    todo.touch(now());
+                    unlinked += 1;
+                }
+            }
+            if unlinked > 0 {
+                save_todo_list(file, &updated_todo_list);
+            }
+            goals::save(base, &goals)?;
+            match unlinked {
+                0 => println!("Removed goal {}", goal.name),
+                1 => println!("Removed goal {}, unlinking 1 task", goal.name),
+                _ => println!("Removed goal {}, unlinking {} tasks", goal.name, unlinked),
+            }
+        }
+        GoalAction::Link { name, ids } => {
+            let goal = &goals[find(&goals, &name)?];
+            let ids: Vec<u64> = ids
+                .iter()
+                .map(|id| updated_todo_list.resolve(id))
+                .collect::<Result<_, _>>()?;
+            for id in ids {
+                let todo = updated_todo_list
+                    .todo_mut(id)
+                    .ok_or_else(|| format!("Invalid ID: {}", id))?;
+                todo.goal = Some(goal.name.clone());
+            }
+            save_todo_list(file, &updated_todo_list);
+        }
+        GoalAction::Unlink { ids } => {
+            let ids: Vec<u64> = ids
+                .iter()
+                .map(|id| updated_todo_list.resolve(id))
+                .collect::<Result<_, _>>()?;
+            for id in ids {
+                let todo = updated_todo_list
+                    .todo_mut(id)
+                    .ok_or_else(|| format!("Invalid ID: {}", id))?;
+                todo.goal = None;
+            }
+            save_todo_list(file, &updated_todo_list);
+        }
+        GoalAction::Show { name } => {
+            if let Some(name) = name {
+                goals = vec![goals.swap_remove(find(&goals, &name)?)];
+            }
+            if goals.is_empty() {
+                println!("No goals yet; add one with goal add <name>");
+                return Ok(());
+            }
+            let completed = stats::load_completed(base);
+            let now = now();
+            for (index, goal) in goals.iter().enumerate() {
+                if index > 0 {
+                    println!();
+                }
+                let progress = goals::progress(&goal.name, &updated_todo_list.todos, &completed);
+                print!("{}", goals::render(goal, &progress, dates, now, plain));
+            }
+        }
+    }
+    Ok(())
+}
+/// Returns the ID of the todo item `task` refers to in `todo_list`, exiting with status 2
+/// if no single todo item's hash starts with the prefix given, or no single title matches.
+fn resolve_id(todo_list: &TodoList, task: &TaskRef) -> u64 {
+    todo_list.resolve(task).unwrap_or_else(|error| {
+        // An error for titles some tasks match means they matched several
+        let ambiguous_title = |title: &str| ids::matching_titles(&todo_list.todos, title).len() > 1;
+        let failure = match task {
+            TaskRef::Id(id) => Failure::new("unknown_task", error).id(*id),
+            TaskRef::Hash(prefix) => {
+                // An error for a prefix some hashes start with means it matched several
+                let ambiguous = todo_list
+                    .todos
+                    .iter()
+                    .any(|todo| todo.hash.starts_with(prefix));
+                let code = match (ambiguous, ambiguous_title(prefix)) {
+                    (true, _) => "ambiguous_hash",
+                    (false, true) => "ambiguous_title",
+                    (false, false) => "unknown_task",
+                };
+                Failure::new(code, error).hash(prefix)
+            }
+            TaskRef::Title(title) => {
+                let code = match ambiguous_title(title) {
+                    true => "ambiguous_title",
+                    false => "unknown_task",
+                };
+                Failure::new(code, error)
+            }
+        };
+        failure.exit(2)
+    })
+}
+/// Asks to confirm the suggested priority `suggested` of a todo item, reading the answer
+/// from stdin until it is valid. The end of input quits if the item can be skipped, and
+/// accepts the suggestion otherwise.
+fn ask_priority(suggested: u64, skippable: bool) -> triage::Answer {
+    let choices = if skippable { ", [s]kip or [q]uit" } else { "" };
+    loop {
+        print!(
+            "Enter accepts {}, or type {}{}: ",
+            suggested,
+            priorities::scale().expected(),
+            choices
+        );
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        match std::io::stdin().read_line(&mut answer) {
+            Ok(0) | Err(_) if skippable => return triage::Answer::Quit,
+            Ok(0) | Err(_) => return triage::Answer::Priority(suggested),
+            Ok(_) => {}
+        }
+        if let Some(answer) = triage::parse_answer(&answer, suggested, skippable) {
+            return answer;
+        }
+    }
+}
+
+/// Runs the commands of a workflow against the todo list file at `file`, each as a separate
+/// invocation of this program. Every command is checked before the first one runs, so a
+/// typo does not leave the workflow half done. The workflow stops at the first command that
+/// fails, and the program exits with that command's status.
+fn run_workflow(steps: &[String], args: &[String], file: &Path) -> Result<(), String> {
+    let commands = workflow::expand(steps, args)?;
+    let mut invocations = Vec::new();
+    for command in &commands {
+        let line = command.join(" ");
+        let opt =
+            Opt::from_iter_safe(std::iter::once("todo").chain(command.iter().map(String::as_str)))
+                .map_err(|error| {
+                    let message = error.message.lines().next().unwrap_or_default().to_string();
+                    format!("'{}': {}", line, message)
+                })?;
+        if let Command::Run { .. } = opt.command {
+            return Err(format!("'{}': workflows cannot run other workflows", line));
+        }
+        let mut arguments: Vec<std::ffi::OsString> = Vec::new();
+        if opt.file.is_none() {
+            arguments.push("--file".into());
+            arguments.push(file.as_os_str().to_owned());
+        }
+        arguments.extend(command.iter().map(Into::into));
+        invocations.push((line, arguments));
+    }
+    let program = std::env::current_exe().map_err(|error| error.to_string())?;
+    for (line, arguments) in invocations {
+        println!("==> {}", line);
+        let status = std::process::Command::new(&program)
+            .args(arguments)
+            .status()
+            .map_err(|error| format!("'{}': {}", line, error))?;
+        if !status.success() {
+            // A command killed by a signal has no status of its own
+            let code = status.code().unwrap_or(1);
+            println!("Stopped at '{}', which exited with status {}", line, code);
+            std::process::exit(code);
+        }
+    }
+    Ok(())
+}
+/// Runs this `list` command again without `--watch` each time the todo list file at `file`
+/// or the context file in `base` changes, clearing the terminal first unless `plain` is
+/// set. Changes are found by checking the files' modification times twice a second.
+fn watch_list(file: &Path, base: &Path, plain: bool) -> std::io::Result<()> {
+    let program = std::env::current_exe()?;
+    let arguments: Vec<std::ffi::OsString> = std::env::args_os()
+        .skip(1)
+        .filter(|argument| argument != "--watch")
+        .collect();
+    let watched = [shards::watched(file), base.join(context::CONTEXT_FILE)];
+    let state = || -> Vec<Option<(std::time::SystemTime, u64)>> {
+        watched
+            .iter()
+            .map(|path| {
+                let metadata = std::fs::metadata(path).ok()?;
+                Some((metadata.modified().ok()?, metadata.len()))
+            })
+            .collect()
+    };
+    loop {
+        let seen = state();
+        let updated = Utc::now().format("%H:%M:%S");
+        if plain {
+            println!("Updated at {}:", updated);
+        } else {
+            // Clear the screen and move the cursor to the top left
+            print!("\x1b[2J\x1b[H");
+            println!("{} at {} (Ctrl-C to stop)", file.display(), updated);
+        }
+        std::io::stdout().flush()?;
+        std::process::Command::new(&program)
+            .args(&arguments)
+            .status()?;
+        while state() == seen {
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        if plain {
+            println!();
+        }
+    }
+}
+/// Prints `text` as a QR code drawn with block characters, or just the text it would
+/// encode when `plain` is set.
+fn print_qr(text: &str, plain: bool) {
+    if plain {
+        println!("QR codes are not shown in plain mode. The code would contain:");
+        print!("{}", text);
+        if !text.ends_with('\n') {
+            println!();
+        }
+        return;
+    }
+    match qr::QrCode::encode(text.as_bytes()) {
+        Ok(code) => print!("{}", code.render()),
+        Err(error) => println!("Unable to make a QR code: {}", error),
+    }
+}
+/// Prints the todo items found by `search`, `overdue` or `count`, or just how many there are
+/// when `count_only` is set, in the colors of `theme` or as labeled lines when `plain` is
+/// set, with dates in the format `dates`, laid out to fit `width`. Exits with status 1 when there are none, so scripts can branch on the result
+/// without parsing the output.
+fn print_matches(
+    todos: Vec<Todo>,
+    count_only: bool,
+    theme: &Theme,
+    plain: bool,
+    dates: &DateFormat,
+    width: Option<usize>,
+) {
+    let found = !todos.is_empty();
+    if count_only {
+        println!("{}", todos.len());
+    } else if found {
+        let mut matches = TodoList::new();
+        matches.todos = todos;
+        matches.display(theme, plain, dates, width);
+    } else {
+        println!("No matching tasks");
+    }
+    if !found {
+        std::process::exit(1);
+    }
+}
+/// Pushes the due dates of the todo items `ids` matching `query` forward by `by` seconds,
+/// in the timezone `zone`, once the user confirms the summary unless `yes` is set. Items
+/// without a due date are left as they are.
+fn postpone(
+    todo_list: TodoList,
+    file: &Path,
+    ids: &[u64],
+    (query, by): (&query::Query, dates::Postponement),
+    yes: bool,
+    zone: dates::Zone,
+) {
+    let now = now();
+    let date = |timestamp: i64| zone.local(timestamp).format("%Y-%m-%d %H:%M").to_string();
+    let mut moves: Vec<(u64, i64)> = Vec::new();
+    for &id in ids {
+        let todo = match todo_list.todo(id) {
+            Some(todo) if query.matches(todo, now) => todo,
+            _ => continue,
+        };
+        match todo.due {
+            Some(due) => {
+                let postponed = match by.apply(due, now, zone) {
+                    Ok(postponed) => postponed,
+                    Err(error) => {
+                        println!("{}. Not postponed", error);
+                        std::process::exit(2);
+                    }
+                };
+                println!(
+                    "{}: {}: {} -> {}",
+                    id,
+                    todo.task,
+                    date(due),
+                    date(postponed)
+                );
+                moves.push((id, postponed));
+            }
+            None => println!("{}: {}: no due date, left as it is", id, todo.task),
+        }
+    }
+    if moves.is_empty() {
+        println!("Nothing to postpone");
+        return;
+    }
+    if !yes {
+        print!("Postpone {} task(s)? [y/N] ", moves.len());
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        match std::io::stdin().read_line(&mut answer) {
+            Ok(0) | Err(_) => return,
+            Ok(_) if !answer.trim().eq_ignore_ascii_case("y") => {
+                println!("Not postponed");
+                return;
+            }
+            Ok(_) => {}
+        }
+    }
+    let mut updated_todo_list = todo_list;
+    for (id, due) in &moves {
+        updated_todo_list.set_due(*id, *due);
+    }
+    save_todo_list(file, &updated_todo_list);
+    println!("Postponed {} task(s)", moves.len());
+}
+/// Parses a query with `Query::parse`, with the tags todo items inherit from their project
+/// and ignoring accents if `config` says so.
+fn parse_query(input: &str, now: i64, config: &Config) -> Result<query::Query, String> {
+    let query = query::Query::parse(input, now)?.inherit_tags(config.inherited_tags());
+    Ok(match config.ignore_accents {
+        true => query.ignore_accents(),
+        false => query,
+    })
+}
+/// Reads a todo list from stdin (a list file, or a bare array of todo items) and writes the
+/// items matching `query` to stdout as a list file.
+fn run_filter(query: &str, config: &Config) -> Result<(), String> {
+    let now = Utc::now().timestamp();
+    let query = parse_query(query, now, config)?;
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|error| format!("Unable to read stdin: {}", error))?;
+    let todos: Vec<Todo> = serde_json::from_str::<TodoList>(&input)
+        .map(|todo_list| todo_list.todos)
+        .or_else(|_| serde_json::from_str(&input))
+        .map_err(|error| format!("Unable to parse the todo list on stdin: {}", error))?;
+    let output = serde_json::json!({ "todos": query.apply(todos, now) });
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&output).expect("Unable to serialize todo list")
+    );
+    Ok(())
 }
 /// Displays help information about the command-line todo list application.
 fn display_help() {
@@ -312,81 +3659,251 @@ fn display_help() {
         "simple command-line todo list
 
             USAGE:
-                cargo run -- <command>
+                cargo run -- [-f <file>] [--remote <url>] [--plain] [--color <when>] [--width <columns>] [--timings] <command>
+            OPTIONS:
+                -f, --file <file>               Use this todo list file instead of ./todos.json or the configured file
+                --remote <url>                  Run the command against the list shared by serve at this URL, e.g. http://nas.local:8080
+                --plain                         Print labeled lines without color, block graphics or columns, for screen readers
+                --color <when>                  Color output auto (to a terminal, unless NO_COLOR is set), always or never
+                --width <columns>               Lay lists out to fit this width instead of the terminal's, e.g. when writing to a file
+                --timings                       Report how long loading, the command and saving took, with item counts, on stderr
             ARGS:
-                add <task-name> <priority>      Add a task to the list, include priority of task (1-5) inclusive
-                remove <task-id>                Remove a task at the given index
+                <id>                            A task's ID, a prefix of its hash that no other task's starts with, e.g. a3f, or part of its title, e.g. \"pay rent\"
+                add <task-name>... [priority]   Add one or more tasks to the list, include priority of task (1-5) inclusive, or its name (see Priorities)
+                    [--priority <n>]            Give every task this priority, e.g. add \"Buy milk\" \"Call mum\" --priority 3 or --priority high
+                    [--recur <rule>]            Repeat the task using an RFC 5545 rule, e.g. FREQ=MONTHLY;BYDAY=2TU
+                    [--workdays]                Move occurrences falling on a weekend or holiday to the next working day
+                    [--timezone <zone>]         The timezone of the due date and occurrences, e.g. Europe/Berlin, instead of the configured one
+                    [--url <url>]               Link the task to a web page or mail address (http, https or mailto)
+                    [--location <place>]        Record where the task can be done
+                    [--due <date>]              Set when the task is due, e.g. 2024-06-01, \"2024-06-01 17:00\", \"fri 9am\" or +3bd
+                    [--project <name>]          Put the task in a project, with its configured defaults (alias --list)
+                    [--estimate <time>]         Set how long the task should take, e.g. 30m or 1h30m
+                    [--suggest-priority]        Suggest a priority from the priority rules and ask to confirm it
+                remove <task-id>...             Remove the tasks at the given indexes
+                    [--if-rev <n>]              Only remove the task if it is still at revision n (takes a single ID)
+                    [--dependents <mode>]       What to do with the tasks depending on it: unblock (the default), block, cascade or relink
                 list                            List the todos
+                    [--assignee <name>|--mine]  Only list the todos assigned to someone, or to you
+                    [--all]                     List everyone's todos, even when the config defaults to --mine or a view, and someday ones
+                    [--someday]                 Only list the todos on the someday/maybe list, which are otherwise left out
+                    [--created-by <name>]       Only list the todos created by someone
+                    [--near <place>]            Only list the todos at a place, or at places configured as near it
+                    [--tag <tag>]               Only list the todos with a tag
+                    [--modified-since <when>]   Only list the todos changed since e.g. \"2 days ago\", yesterday or 2024-06-01
+                    [--as-of <when>]            List the todos as they were then, e.g. \"last monday\", from the history
+                    [--where <query>]            Only list the todos matching a query, e.g. \"(priority >= 4 and tag:work) or overdue\"
+                    [--view <name>]             Only list the todos matching a view, a query named in the config
+                    [--sort <order>]            Sort by several keys, e.g. \"due asc, priority desc, created asc\"
+                    [--format ndjson]           Print one JSON object per line, e.g. to pipe into jq
+                    [--ids-only]                Print only the IDs, e.g. list --tag defunct --ids-only | xargs todo remove
+                    [--watch]                   Keep running and redraw the list whenever the file or context changes, e.g. in a tmux pane
+                    [--done]                    List the completed todos instead, with when each was completed and how long it was open
+                    [--since <when>]            With --done, only those completed since then, e.g. \"last month\" or 2024-06-01
                 clear                           Clear all the todos   
                 prioritize                      List the todos in order of priority (highest to lowest)
                 help                            Print help information
-                schedule                        List the todos by the date they were created (in Utc)
-                edit <id>                       Change the name of a task given id
+                man                             Print the man page, e.g. man > todo.1
+                schedule                        List the todos by the date they were created
+                edit <id>                       Change the task, priority or due date of the task given id
+                    [--task <text>]             The new task description
+                    [--priority <n>]            The new priority, from 1 to 5 or its name
+                    [--due <date>]              The new due date, e.g. \"2024-06-01\", \"2024-06-01 17:00\" or \"today 17:00\"
+                    [--clear-due]               Remove the due date
+                    [--if-rev <n>]              Only edit the task if it is still at revision n, e.g. as read by show
+                quick <text>                    Add a task from one string, e.g. \"Pay rent tomorrow 9am p:5 +finance @home\"
+                delegate <id> <name>            Assign a task to someone
+                highlight <id> <color>          Show a task in its own color in list, e.g. highlight 3 black on-yellow, instead of its priority's
+                    [--clear]                   Remove the highlight
+                context [set <context>|clear]   Show, set or clear the tag or project:<name> that list and new tasks are scoped to
+                config get|set|unset <key>      Print a setting, or set or remove it in the config file (see Configuration)
+                config list [--all]             List the settings that differ from the defaults, or every one with what it does
+                config edit                     Open the config file in $VISUAL or $EDITOR and check it afterwards
+                goal add <name> [--by <date>]   Add a goal, e.g. goal add \"Ship v2\" --by 2024-06-30 (see Goals)
+                goal link <name> <id>...        Link tasks to a goal; goal unlink <id>... unlinks them
+                goal show [name]                Show how far each goal has come: tasks open and done, estimates left
+                goal remove <name>              Remove a goal, unlinking its tasks
+                tag rename <tag> <new-tag>      Rename a tag on every task, open or completed, e.g. tag rename +clientA +acme-corp
+                depend <id> <id>... [--remove]  Make a task wait for others to be removed first, tagging it BLOCKED
+                wait <id> <date>                Tag a task WAITING until a date, e.g. 2024-06-01
+                someday <id> [--clear]          Put a task on the someday/maybe list, out of list, overdue, plan, triage and aging, or take it off
+                postpone <duration|date> <id>...  Push the due dates of tasks forward, e.g. by 1d or to fri, after printing the changes and asking to confirm
+                    [--all-overdue]             Postpone every overdue task instead, except someday ones
+                    [--where <query>]           Only postpone the tasks matching a query, e.g. \"tag:work\"
+                    [--yes]                     Postpone without asking
+                graph [--format dot]            Print the dependencies between tasks for Graphviz, e.g. graph | dot -Tsvg > todos.svg
+                estimate <id> <time>            Set how long a task should take, e.g. 1h30m
+                plan [--capacity <time>]        Fit the estimated tasks into today, tomorrow and the week at e.g. 6h a day (default daily_capacity or 8h)
+                plan today [--yes]              Pick the most urgent tasks that fit into today and mark them planned:today once accepted
+                    [--regenerate]              Propose a new plan even if one was accepted today
+                    [--calendar <file.ics>]     Plan today only, into the free time between the meetings of an iCalendar file
+                    [--hours <from-to>]         The working day around the meetings, 09:00-17:00 by default
+                triage [--filter <query>]       Go through the tasks whose priority the rules would change, e.g. triage --filter tag:work
+                    [--yes]                     Apply every suggestion without asking
+                    [--dry-run]                 Only print the suggestions
+                age [--after-days <days>]       Raise the priority of tasks left open for too long
+                remind <id> --at <time>         Remind yourself of a task at a time, e.g. \"2024-06-01 09:00\" or \"fri 9am\"
+                remind <id> --before-due <dur>  Remind yourself of a task some time before it is due, e.g. 1h or 2d
+                reminders                       Print the reminders that have come due; run it from cron or a status bar
+                show <id> [--qr]                Show every detail of a task, or a QR code of it to scan with a phone
+                    [--predict]                 Also predict when it will be done, from how long similar tasks took
+                    [--format json]             Print the task as it is in the list file, as JSON
+                export [--qr] [-o <file>]       Export the list as a plain-text checklist, or as a QR code
+                export --format pdf [-o <file>] Export a printable PDF checklist grouped by project, with due dates
+                export --format html [-o <file>] Export a standalone web page of the tasks, sortable and filterable in the browser
+                export --format csv [-o <file>] Export a spreadsheet of the tasks, a row each, as import --format csv reads it
+                export --format mermaid-gantt   Export a Mermaid Gantt chart of the tasks with wait and due dates, estimates and dependencies
+                export --format json [-o <file>] Export the list file itself as JSON
+                    [--where <query>]           Only export the matching tasks; --tag, --assignee, --mine and the other filters of list work too
+                    [--anonymize]               Replace tasks, people, places, links, tags and projects with placeholders, in any format
+                diff <file> [--json]            Show the tasks added, removed and changed since another todo list file
+                diff --backup <n> [--json]      Show the changes since the n-th most recent backup in .todo-backups
+                doctor [--fix]                  Check the list for duplicate IDs, broken dependencies, bad priorities, dates and attachments
+                restore <n>                     Replace the todo list with the n-th most recent backup
+                sign [file] [--key <key>]       Sign a todo list file with an SSH ed25519 key, writing <file>.sig
+                verify <file> [--signer <id>]   Check that a todo list file was signed by someone in an allowed signers file
+                    [--allowed-signers <path>]
+                open <id>                       Open the task's URL in the default browser
+                attach <id> <path> [--copy]     Attach a file to a task, copying it into ./attachments with --copy
+                filter <query>                  Read a todo list as JSON from stdin and write the tasks matching a query to stdout
+                search <query> [--count-only]   List the tasks matching a query (see Queries), or just count them
+                    [--fuzzy]                   Also match misspelled words, e.g. invioce for invoice, best matches first
+                    [--ignore-accents]          Match whatever accents the tasks have, e.g. cafe for Café
+                overdue [--count-only]          List the tasks past their due date, or just count them
+                count [query]                   Print how many tasks there are, or how many match a query, leaving out someday ones
+                    [--someday]                 Only count the tasks on the someday/maybe list
+                run <name> [args...]            Run a workflow, a named list of commands from the config
+                sql <query> [--format <f>]      Run an SQL query over the tasks with sqlite3 (see SQL); --schema shows the tables
+                batch <file>                    Apply the add, edit and remove commands in a file (or - for stdin) all at once, or none if one fails
+                stats                           Count the tasks by priority and state, e.g. overdue, blocked or stale
+                    [--stale]                   List the tasks untouched the longest instead, with their age and last change
+                    [--older-than <duration>]   How long a task must go unchanged to be stale, 60d by default
+                    [--by <tag|project>]        Per tag or project: open, completed in 30 days, average time to complete, overdue
+                    [--charts]                  Also chart the tasks by priority, completions by weekday and tasks added per week
+                    [--recurring]               Per recurring task: occurrences done on time, late and skipped
+                done <id>                       Check off the current occurrence of a recurring task
+                    [--late]                    Check off the one before it instead, as done late
+                    [--if-rev <n>]              Only check it off if the task is still at revision n
+                forecast [--where <query>]      Predict when each open task will likely be done, from how long similar tasks took
+                focus <id>                      Show only one task on a cleared screen with a timer, until you mark it done or quit
+                habits                          Show the streak of each recurring task and the occurrences missed this month
+                replace --pattern <re> --with <text>  Substitute a regular expression in every task, printing each change (see Replacing)
+                    [--filter <query>]          Only change the tasks matching a query, e.g. tag:client
+                    [--dry-run]                 Only print the changes, without saving them
+                import <file>                   Merge the tasks of another todo list file, or of list --format ndjson output (see Imports)
+                    [--on-conflict <strategy>]  For a task that changed: skip (the default), overwrite, duplicate or prompt
+                    [--format csv]              Read a CSV file, e.g. exported from a spreadsheet, instead of JSON
+                    [--map <columns>]           Which field each CSV column holds, e.g. \"Title=task,Prio=priority\"
+                    [--delimiter <char>]        Separate CSV fields with this character, or tab, instead of a comma
+                    [--quote <char>]            Quote CSV fields with this character instead of \"
+                convert <file>                  Write the list to a file in the format of its extension: .json, .yaml, .toml or .json5 (see File formats)
+                convert <dir>/                  Write the list as a sharded list, a file per project in a directory (see Sharded lists)
+                serve                           Share the todo list over HTTP or HTTPS for other machines to use with --remote (see Sharing a list)
+                    [--bind <address>]          Listen on this address instead of 127.0.0.1, e.g. 0.0.0.0 for other machines
+                    [--port <port>]             Listen on this port instead of 8080
+                    [--tls-cert <file> --tls-key <file>]  Serve HTTPS with this certificate and private key
+                    [--tls-self-signed]         Serve HTTPS with a self-signed certificate made on first use
+                    [--tls-name <name>...]      Also make the self-signed certificate for this host name or address
+                    [--spec]                    Print the OpenAPI description of the API instead of serving
+                sync status|now|discard         Show, send or forget the changes made with --remote while its server was unreachable
+                <name> [args...]                Run todo-<name> from PATH, a plugin, with the list in its environment (see Plugins)
         "
     );
 }
 #[cfg(test)]
 mod tests {
-    use crate::TodoList;
+    use super::{Command, Opt, ShowFormat};
+    use std::path::{Path, PathBuf};
+    use structopt::StructOpt;
+    use todo::config::Config;
+    use todo::store::backup_path;
+    use todo::TodoList;
     #[test]
-    fn test_clear() {
-        let mut todo_list = TodoList::new();
-        todo_list.add_todo("task 1", 1);
-        todo_list.add_todo("task 2", 2);
-        todo_list.clear_todo();
-        assert_eq!(todo_list.todos.len(), 0);
+    fn test_file_option() {
+        let opt = Opt::from_iter(["todo", "sign", "shared.json", "-f", "lists/work.json"]);
+        assert_eq!(opt.file, Some(PathBuf::from("lists/work.json")));
+        match opt.command {
+            Command::Sign { file, .. } => assert_eq!(file, Some(PathBuf::from("shared.json"))),
+            command => panic!("unexpected command {:?}", command),
+        }
+        assert_eq!(Opt::from_iter(["todo", "list"]).file, None);
 
-        todo_list.clear_todo();
-        assert_eq!(todo_list.todos.len(), 0);
+        assert_eq!(
+            backup_path(Path::new("lists/work.json"), 2),
+            Path::new("lists/.todo-backups/work.json.2")
+        );
+        assert_eq!(
+            backup_path(Path::new("todos.json"), 1),
+            Path::new("./.todo-backups/todos.json.1")
+        );
     }
     #[test]
-    fn test_add() {
-        let mut todo_list = TodoList::new();
-        todo_list.add_todo("task 1", 1);
-        assert_eq!(todo_list.todos.len(), 1);
-
-        todo_list.add_todo("Invalid task", 0);
-        assert_eq!(todo_list.todos.len(), 1);
-
-        todo_list.add_todo("Invalid task", 6);
-        assert_eq!(todo_list.todos.len(), 1);
+    fn test_show_options() {
+        let show = |args: &[&str]| {
+            Opt::from_iter_safe(["todo", "show", "1"].iter().chain(args)).map(|opt| opt.command)
+        };
+        assert!(matches!(
+            show(&["--qr"]),
+            Ok(Command::Show { qr: true, .. })
+        ));
+        assert!(matches!(
+            show(&["--predict"]),
+            Ok(Command::Show { predict: true, .. })
+        ));
+        assert!(matches!(
+            show(&["--format", "json"]),
+            Ok(Command::Show {
+                format: Some(ShowFormat::Json),
+                ..
+            })
+        ));
+        assert!(show(&["--qr", "--format", "json"]).is_err());
     }
     #[test]
-    fn test_delete() {
+    fn test_add_at_revision_0() {
+        let parse = |args: &[&str]| Opt::from_iter(["todo"].iter().chain(args)).command;
+        let Command::Add(new_todo) = parse(&[
+            "add",
+            "Pay rent",
+            "--recur",
+            "FREQ=MONTHLY",
+            "--due",
+            "2024-06-01",
+            "--url",
+            "https://example.com/rent",
+            "--location",
+            "Bank",
+            "--estimate",
+            "10m",
+        ]) else {
+            panic!("Not an add");
+        };
         let mut todo_list = TodoList::new();
-        todo_list.remove_todo(1);
-        assert_eq!(todo_list.todos.len(), 0);
-
-        todo_list.add_todo("task 1", 1);
-        todo_list.add_todo("task 2", 2);
-        todo_list.add_todo("task 3", 3);
-
-        todo_list.remove_todo(0);
-        assert_eq!(todo_list.todos.len(), 3);
-
-        todo_list.remove_todo(4);
-        assert_eq!(todo_list.todos.len(), 3);
-
-        todo_list.remove_todo(1);
-        assert_eq!(todo_list.todos.len(), 2);
-
-        assert_eq!(todo_list.todos[0].task, "task 2");
-        assert_eq!(todo_list.todos[1].task, "task 3");
+        new_todo
+            .add_to(&mut todo_list, &Config::default(), Path::new("."))
+            .unwrap();
+        assert_eq!(todo_list.todos[0].rev, 0);
+        for args in [
+            &["edit", "1", "--task", "Pay the rent", "--if-rev", "0"][..],
+            &["done", "1", "--if-rev", "0"],
+            &["remove", "1", "--if-rev", "0"],
+        ] {
+            let rev = match parse(args) {
+                Command::Edit(edit) => edit.if_rev,
+                Command::Done { if_rev, .. } => if_rev,
+                Command::Remove(removal) => removal.if_rev,
+                command => panic!("unexpected command {:?}", command),
+            };
+            assert_eq!(todo_list.check_revision(1, rev.unwrap()), Ok(()));
+        }
     }
     #[test]
-    fn test_edit() {
-        let mut todo_list = TodoList::new();
-
-        todo_list.add_todo("task 1", 1);
-        todo_list.add_todo("task 2", 2);
-        todo_list.add_todo("task 3", 3);
-
-        todo_list.edit_todo("edited task", 1);
-        assert_eq!(todo_list.todos[0].task, "edited task");
-
-        todo_list.edit_todo("bad edited task", 4);
-        assert_eq!(todo_list.todos[0].task, "edited task");
-        assert_eq!(todo_list.todos[1].task, "task 2");
-        assert_eq!(todo_list.todos[2].task, "task 3");
+    fn test_subcommand_help() {
+        let mut help = Vec::new();
+        Command::clap().write_help(&mut help).unwrap();
+        let help = String::from_utf8(help).unwrap();
+        // The doc comments of the subcommands' own enums are not their help
+        assert!(!help.contains("The ways"));
+        assert!(help.contains("Change tags across the whole list"));
     }
 }
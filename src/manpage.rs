@@ -0,0 +1,224 @@
+//! Generation of a roff man page from the command-line definition.
+
+use std::fmt::Write;
+
+use structopt::StructOpt;
+
+use todo::attachments::ATTACHMENTS_DIR;
+use todo::config::{self, CONFIG_ENV, KEYS};
+use todo::context::CONTEXT_FILE;
+use todo::store::BACKUP_DIR;
+use todo::tags::VIRTUAL_TAGS;
+use todo::theme::{ROLES, THEMES};
+use todo::TODO_FILE;
+
+use crate::Command;
+
+/// Renders the man page for the application in roff, covering every subcommand with its
+/// arguments and flags, the configuration keys, and the files and environment it uses.
+///
+/// # Example
+///
+/// ```
+/// print!("{}", manpage::render());
+/// ```
+pub fn render() -> String {
+    let name = Command::clap().get_name().to_string();
+    let version = env!("CARGO_PKG_VERSION");
+    let mut page = String::new();
+
+    let _ = writeln!(
+        page,
+        ".TH {} 1 \"\" \"{} {}\" \"User Commands\"",
+        escape(&name.to_uppercase()),
+        escape(&name),
+        version
+    );
+    let _ = writeln!(
+        page,
+        ".SH NAME\n{} \\- simple command-line todo list",
+        escape(&name)
+    );
+    let _ = writeln!(
+        page,
+        ".SH SYNOPSIS\n.B {}\n[\\fB\\-f\\fR \\fIfile\\fR] [\\fB\\-\\-remote\\fR \\fIurl\\fR] [\\fB\\-\\-plain\\fR] [\\fB\\-\\-color\\fR \\fIwhen\\fR]\n.I command\n[\\fIargs\\fR]",
+        escape(&name)
+    );
+    let _ = writeln!(
+        page,
+        ".SH DESCRIPTION\nKeeps a prioritized todo list in \\fB{}\\fR in the current directory, or in the file given with \\fB\\-f\\fR/\\fB\\-\\-file\\fR or configured as \\fBfile\\fR. Backups and copied attachments are kept next to the list file.",
+        escape(TODO_FILE)
+    );
+
+    let _ = writeln!(page, ".SH COMMANDS");
+    for subcommand in subcommands() {
+        let Some(help) = long_help(&name, &subcommand) else {
+            continue;
+        };
+        let _ = writeln!(page, ".SS {}", escape(&subcommand));
+        let _ = writeln!(page, ".nf");
+        for line in help.lines() {
+            let _ = writeln!(page, "{}", escape(line));
+        }
+        let _ = writeln!(page, ".fi");
+    }
+
+    let _ = writeln!(page, ".SH VIRTUAL TAGS");
+    let _ = writeln!(
+        page,
+        "These tags are worked out when a query runs rather than stored, and can be used wherever a tag can, e.g. \\fBlist \\-\\-tag OVERDUE\\fR."
+    );
+    for (tag, description) in VIRTUAL_TAGS {
+        let _ = writeln!(page, ".TP\n.B {}\n{}", escape(tag), escape(description));
+    }
+
+    let _ = writeln!(page, ".SH THEMES");
+    let _ = writeln!(
+        page,
+        "The \\fBtheme\\fR key picks one of these color themes, and \\fBcolors\\fR replaces its color for some of the roles below."
+    );
+    for (theme, description) in THEMES {
+        let _ = writeln!(page, ".TP\n.B {}\n{}", escape(theme), escape(description));
+    }
+    let _ = writeln!(page, ".PP\nThe roles are:");
+    for (role, description) in ROLES {
+        let _ = writeln!(page, ".TP\n.B {}\n{}", escape(role), escape(description));
+    }
+
+    let _ = writeln!(page, ".SH CONFIGURATION");
+    let _ = writeln!(
+        page,
+        "Settings are read from the JSON object in \\fI$TODO_CONFIG\\fR, \\fI$XDG_CONFIG_HOME/todo/config.json\\fR, or \\fI~/.config/todo/config.json\\fR. Each key can be overridden with a \\fBTODO_\\fR\\fIKEY\\fR environment variable, e.g. \\fBTODO_DEFAULT_PRIORITY=5\\fR, and command-line options override both."
+    );
+    for (key, description) in KEYS {
+        let _ = writeln!(page, ".TP\n.B {}\n{}", escape(key), escape(description));
+    }
+
+    let _ = writeln!(page, ".SH FILES");
+    let config_path = config::config_path()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| "~/.config/todo/config.json".to_string());
+    for (path, description) in [
+        (TODO_FILE.to_string(), "The todo list."),
+        (
+            format!("{}/<file>.<n>", BACKUP_DIR),
+            "The n-th most recent previous version of the todo list.",
+        ),
+        (
+            ATTACHMENTS_DIR.to_string(),
+            "Files copied in by attach --copy.",
+        ),
+        (
+            CONTEXT_FILE.to_string(),
+            "The active context set by context set, next to the todo list.",
+        ),
+        (config_path, "The configuration file."),
+    ] {
+        let _ = writeln!(page, ".TP\n.I {}\n{}", escape(&path), escape(description));
+    }
+
+    let _ = writeln!(page, ".SH ENVIRONMENT");
+    let overrides: Vec<String> = KEYS.iter().map(|(key, _)| config::env_var(key)).collect();
+    for (variable, description) in [
+        (
+            overrides.join(", ").as_str(),
+            "Override the configuration key of the same name. Values are read as JSON, or as text when that is not valid.",
+        ),
+        (CONFIG_ENV, "The configuration file to read instead of the default one."),
+        (
+            "USER, USERNAME",
+            "Identify the current user when the user key is not configured.",
+        ),
+        ("XDG_CONFIG_HOME, HOME", "Locate the configuration file."),
+        (
+            "NO_COLOR",
+            "Disables colored output when set, unless color is configured or given as always.",
+        ),
+    ] {
+        let _ = writeln!(
+            page,
+            ".TP\n.B {}\n{}",
+            escape(variable),
+            escape(description)
+        );
+    }
+    page
+}
+
+/// Returns the names of the subcommands, in the order `--help` lists them. clap 2 has no
+/// public way to list them, so they are read from the SUBCOMMANDS section of the help,
+/// where each starts a line indented by four spaces.
+fn subcommands() -> Vec<String> {
+    let mut help = Vec::new();
+    if Command::clap().write_long_help(&mut help).is_err() {
+        return Vec::new();
+    }
+    String::from_utf8_lossy(&help)
+        .lines()
+        .skip_while(|line| *line != "SUBCOMMANDS:")
+        .filter_map(|line| line.strip_prefix("    "))
+        .filter(|line| !line.starts_with(' '))
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns what `<name> <subcommand> --help` prints, without the heading naming the
+/// program and its version.
+fn long_help(name: &str, subcommand: &str) -> Option<String> {
+    let error = Command::clap()
+        .get_matches_from_safe([name, subcommand, "--help"])
+        .err()
+        .filter(|error| error.kind == structopt::clap::ErrorKind::HelpDisplayed)?;
+    let heading = format!("{}-{} ", name, subcommand);
+    Some(
+        error
+            .message
+            .lines()
+            .skip_while(|line| line.starts_with(&heading))
+            .collect::<Vec<&str>>()
+            .join("\n"),
+    )
+}
+
+/// Escapes text for roff: backslashes, and control characters at the start of a line.
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    if text.starts_with('.') || text.starts_with('\'') {
+        format!("\\&{}", text)
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, render};
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(".hidden"), "\\&.hidden");
+        assert_eq!(escape("--fix"), "\\-\\-fix");
+        assert_eq!(escape("a\\b"), "a\\eb");
+    }
+
+    #[test]
+    fn test_render() {
+        let page = render();
+        assert!(page.starts_with(".TH "));
+        for section in [
+            "NAME",
+            "SYNOPSIS",
+            "COMMANDS",
+            "THEMES",
+            "CONFIGURATION",
+            "FILES",
+            "ENVIRONMENT",
+        ] {
+            assert!(page.contains(&format!(".SH {}\n", section)), "{}", section);
+        }
+        assert!(page.contains(".SS add\n"));
+        assert!(page.contains(".SS doctor\n"));
+        assert!(page.contains("list_mine_by_default"));
+    }
+}
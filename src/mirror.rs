@@ -0,0 +1,350 @@
+//! Mirroring the items of another tracker as todo items, for `sync jira` and `sync asana`.
+//!
+//! Each item becomes a task whose hash is made from the tracker and the item's key, which
+//! is how the task is found again however the list is renumbered. A sync updates the tasks
+//! whose items changed, adds those of new items and removes those of the items no longer
+//! found. A task removed from the list since the last sync was marked done, which the
+//! tracker is then told. The keys mirrored by the last sync are kept in a file next to the
+//! list to tell which tasks were removed.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ids;
+use crate::TodoList;
+
+/// What a task mirrors of an item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    /// What identifies the item in its tracker, e.g. `ACME-12`.
+    pub key: String,
+    pub task: String,
+    pub priority: u64,
+    pub due: Option<i64>,
+    pub tags: Vec<String>,
+    pub project: Option<String>,
+    /// The item's page in the tracker.
+    pub url: Option<String>,
+}
+
+/// A tracker whose items are mirrored.
+pub trait Tracker {
+    /// Its name, e.g. `Jira`.
+    fn name(&self) -> &str;
+    /// What the hashes of the tasks mirroring its items are made from.
+    fn source(&self) -> String;
+    /// Returns the IDs and keys of the tasks of `list` mirroring its items, among those
+    /// with the keys `keys`.
+    fn mirrored(&self, list: &TodoList, keys: &[String]) -> Vec<(u64, String)> {
+        mirrored(list, &self.source(), keys.iter().map(String::as_str))
+    }
+    /// Returns what the tasks mirroring its items are, as of the timestamp `now`.
+    fn items(&self, now: i64) -> Result<Vec<Item>, String>;
+    /// Marks the item `key` done.
+    fn complete(&self, key: &str) -> Result<(), String>;
+}
+
+/// The keys mirrored by the last sync.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    pub keys: Vec<String>,
+}
+
+impl State {
+    /// Reads the state file, or returns an empty state if there is none.
+    pub fn load(path: &Path) -> Result<State, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|error| format!("Unable to parse {}: {}", path.display(), error)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(State::default()),
+            Err(error) => Err(format!("Unable to read {}: {}", path.display(), error)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self).expect("Unable to serialize state");
+        fs::write(path, contents)
+            .map_err(|error| format!("Unable to write {}: {}", path.display(), error))
+    }
+}
+
+/// What a sync did.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub added: usize,
+    pub updated: usize,
+    /// Tasks removed because their item is no longer found.
+    pub removed: usize,
+    /// The items of the tasks marked done that the tracker was told of.
+    pub done: Vec<String>,
+    /// The items it could not be told of, and why.
+    pub failed: Vec<(String, String)>,
+}
+
+impl Summary {
+    /// Returns whether the sync changed the list.
+    pub fn changed(&self) -> bool {
+        self.added + self.updated + self.removed > 0
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} updated, {} removed, {} marked done",
+            self.added,
+            self.updated,
+            self.removed,
+            self.done.len()
+        )
+    }
+}
+
+/// Makes the hash of the task mirroring the item `key` of the tracker `source`, e.g.
+/// `jira:https://acme.atlassian.net`.
+pub fn hash(source: &str, key: &str) -> String {
+    ids::generate(&[], &format!("{}:{}", source, key))
+}
+
+/// Returns the IDs and keys of the tasks of `list` mirroring the items `keys` of the
+/// tracker `source`.
+pub fn mirrored<'a>(
+    list: &TodoList,
+    source: &str,
+    keys: impl IntoIterator<Item = &'a str>,
+) -> Vec<(u64, String)> {
+    let mut mirrored: Vec<(u64, String)> = Vec::new();
+    for key in keys {
+        let hash = hash(source, key);
+        if let Some(todo) = list.todos.iter().find(|todo| todo.hash == hash) {
+            if !mirrored.iter().any(|(id, _)| *id == todo.id) {
+                mirrored.push((todo.id, key.to_string()));
+            }
+        }
+    }
+    mirrored.sort_unstable();
+    mirrored
+}
+
+/// Returns the keys of `state` without a task among `mirrored`, marked done since.
+pub fn done(mirrored: &[(u64, String)], state: &State) -> Vec<String> {
+    state
+        .keys
+        .iter()
+        .filter(|key| !mirrored.iter().any(|(_, other)| other == *key))
+        .cloned()
+        .collect()
+}
+
+/// Makes the tasks of `list` mirror `items` of the tracker `source`: adding those without a
+/// task, updating the tasks that differ and removing the `mirrored` tasks of the items no
+/// longer found. Changes are made at the timestamp `now`.
+pub fn mirror(
+    list: &mut TodoList,
+    source: &str,
+    mirrored: &[(u64, String)],
+    items: &[Item],
+    now: i64,
+) -> Result<Summary, String> {
+    let mut summary = Summary::default();
+    for item in items {
+        let existing = mirrored
+            .iter()
+            .find(|(_, key)| *key == item.key)
+            .and_then(|(id, _)| list.todos.iter_mut().find(|todo| todo.id == *id));
+        match existing {
+            Some(todo) => {
+                let mirrors = (
+                    &todo.task,
+                    todo.priority,
+                    todo.due,
+                    &todo.tags,
+                    &todo.project,
+                ) == (
+                    &item.task,
+                    item.priority,
+                    item.due,
+                    &item.tags,
+                    &item.project,
+                );
+                if !mirrors {
+                    todo.task = item.task.clone();
+                    todo.priority = item.priority;
+                    todo.due = item.due;
+                    todo.tags = item.tags.clone();
+                    todo.project = item.project.clone();
+                    todo.touch(now);
+                    summary.updated += 1;
+                }
+            }
+            None => {
+                list.add_todo(&item.task, item.priority)
+                    .ok_or_else(|| format!("Unable to add {}", item.key))?;
+                let todo = list.todos.last_mut().expect("The todo was just added");
+                todo.hash = hash(source, &item.key);
+                todo.created = now;
+                todo.due = item.due;
+                todo.tags = item.tags.clone();
+                todo.project = item.project.clone();
+                todo.url = item.url.clone();
+                summary.added += 1;
+            }
+        }
+    }
+    // Removing renumbers the tasks after, so from the last
+    for (id, key) in mirrored.iter().rev() {
+        if !items.iter().any(|item| item.key == *key) {
+            list.remove_todo(*id);
+            summary.removed += 1;
+        }
+    }
+    Ok(summary)
+}
+
+/// Syncs `list` with `tracker` at the timestamp `now`: first marking done the items whose
+/// tasks were removed since the last sync, as `state` tells, then mirroring the items. An
+/// item that could not be marked done is not mirrored, and kept in `state` to try again.
+pub fn sync(
+    list: &mut TodoList,
+    tracker: &impl Tracker,
+    state: &mut State,
+    now: i64,
+) -> Result<Summary, String> {
+    let mut completed = Vec::new();
+    let mut failed = Vec::new();
+    for key in done(&tracker.mirrored(list, &state.keys), state) {
+        match tracker.complete(&key) {
+            Ok(()) => completed.push(key),
+            Err(error) => failed.push((key, error)),
+        }
+    }
+    state.keys.retain(|key| !completed.contains(key));
+    let mut items = tracker.items(now)?;
+    items.retain(|item| !failed.iter().any(|(key, _)| *key == item.key));
+    let keys: Vec<String> = state
+        .keys
+        .iter()
+        .cloned()
+        .chain(items.iter().map(|item| item.key.clone()))
+        .collect();
+    let mirrored = tracker.mirrored(list, &keys);
+    let summary = mirror(list, &tracker.source(), &mirrored, &items, now)?;
+    state.keys = items.into_iter().map(|item| item.key).collect();
+    state.keys.extend(failed.iter().map(|(key, _)| key.clone()));
+    Ok(Summary {
+        done: completed,
+        failed,
+        ..summary
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{done, mirror, mirrored, sync, Item, State, Tracker};
+    use crate::TodoList;
+
+    /// A tracker whose items are `items`, and that can't mark `stuck` done.
+    struct Fake {
+        items: Vec<Item>,
+        stuck: &'static str,
+    }
+
+    impl Tracker for Fake {
+        fn name(&self) -> &str {
+            "Fake"
+        }
+
+        fn source(&self) -> String {
+            "fake".to_string()
+        }
+
+        fn items(&self, _now: i64) -> Result<Vec<Item>, String> {
+            Ok(self.items.clone())
+        }
+
+        fn complete(&self, key: &str) -> Result<(), String> {
+            match key == self.stuck {
+                true => Err("No transition".to_string()),
+                false => Ok(()),
+            }
+        }
+    }
+
+    fn item(key: &str, task: &str) -> Item {
+        Item {
+            key: key.to_string(),
+            task: task.to_string(),
+            priority: 3,
+            due: None,
+            tags: vec!["work".to_string()],
+            project: None,
+            url: None,
+        }
+    }
+
+    #[test]
+    fn test_mirror() {
+        let mut items = vec![item("1", "Fix login"), item("2", "Write docs")];
+        let mut list = TodoList::new();
+        list.add_todo("Water the plants", 2);
+        let summary = mirror(&mut list, "test", &[], &items, 0).unwrap();
+        assert_eq!((summary.added, summary.updated, summary.removed), (2, 0, 0));
+        assert_eq!(list.todos[1].tags, ["work"]);
+
+        // Syncing again changes nothing, until an item changes or is no longer found
+        let found = mirrored(&list, "test", ["1", "2", "3"]);
+        assert_eq!(found, [(2, "1".to_string()), (3, "2".to_string())]);
+        let summary = mirror(&mut list, "test", &found, &items, 0).unwrap();
+        assert!(!summary.changed());
+        items[1].task = "Write the docs".to_string();
+        items.remove(0);
+        let summary = mirror(&mut list, "test", &found, &items, 0).unwrap();
+        assert_eq!((summary.added, summary.updated, summary.removed), (0, 1, 1));
+        let tasks: Vec<&str> = list.todos.iter().map(|todo| todo.task.as_str()).collect();
+        assert_eq!(tasks, ["Water the plants", "Write the docs"]);
+        assert!(mirrored(&list, "other", ["2"]).is_empty());
+
+        // A mirrored task removed since the last sync was marked done
+        let state = State {
+            keys: vec!["2".to_string(), "3".to_string()],
+        };
+        assert_eq!(done(&mirrored(&list, "test", ["2", "3"]), &state), ["3"]);
+        list.remove_todo(2);
+        assert_eq!(done(&mirrored(&list, "test", ["2"]), &state), ["2", "3"]);
+    }
+
+    #[test]
+    fn test_sync() {
+        let tracker = Fake {
+            items: vec![
+                item("1", "Fix login"),
+                item("2", "Write docs"),
+                item("3", "Ship"),
+            ],
+            stuck: "2",
+        };
+        let mut list = TodoList::new();
+        let mut state = State::default();
+        let summary = sync(&mut list, &tracker, &mut state, 0).unwrap();
+        assert_eq!(summary.added, 3);
+        assert_eq!(state.keys, ["1", "2", "3"]);
+
+        // The tasks removed are marked done, and one that can't be is not added back
+        list.remove_todo(1);
+        list.remove_todo(1);
+        let summary = sync(&mut list, &tracker, &mut state, 0).unwrap();
+        assert_eq!(summary.done, ["1"]);
+        assert_eq!(
+            summary.failed,
+            [("2".to_string(), "No transition".to_string())]
+        );
+        let tasks: Vec<&str> = list.todos.iter().map(|todo| todo.task.as_str()).collect();
+        assert_eq!(tasks, ["Ship", "Fix login"]);
+        assert_eq!(state.keys, ["1", "3", "2"]);
+    }
+}
@@ -0,0 +1,334 @@
+//! The OpenAPI 3 description of the API `serve` offers, served at `/openapi.json` and
+//! printed by `serve --spec`, so clients in other languages can be generated from it.
+//!
+//! The query parameters and the reasons of the statuses come from `remote`, where requests
+//! are handled, and the fields of a todo item are checked against what `Todo` serializes,
+//! so the description follows the handlers as they change.
+
+use serde_json::{json, Map, Value};
+
+use crate::remote::{self, MAX_PAGE_SIZE, PARAMETERS};
+
+/// The fields of a todo item: their names, JSON types and descriptions. The first four are
+/// always present.
+const TODO_FIELDS: &[(&str, &str, &str)] = &[
+    (
+        "id",
+        "integer",
+        "Its ID, which changes when the list is renumbered.",
+    ),
+    ("task", "string", "What is to be done."),
+    (
+        "priority",
+        "integer",
+        "From 1 to the number of configured priorities, 5 by default, the highest the most urgent.",
+    ),
+    ("created", "integer", "When it was created, in Unix time."),
+    ("hash", "string", "Stays its own when renumbering."),
+    ("recur", "string", "The recurrence rule, e.g. every monday."),
+    (
+        "workdays",
+        "boolean",
+        "Whether its occurrences move off weekends and holidays.",
+    ),
+    (
+        "timezone",
+        "string",
+        "The timezone its occurrences are computed in, e.g. Europe/Berlin.",
+    ),
+    ("done", "timestamps", "When each occurrence was done."),
+    (
+        "late",
+        "timestamps",
+        "When each occurrence done late began.",
+    ),
+    ("assignee", "string", "The person responsible for it."),
+    ("created_by", "string", "The person who created it."),
+    ("location", "string", "Where it can be done."),
+    ("url", "string", "A link related to it."),
+    ("attachments", "strings", "The paths of its attached files."),
+    ("aged", "integer", "When aging last raised its priority."),
+    ("due", "integer", "When it is due, in Unix time."),
+    ("tags", "strings", "Its tags."),
+    ("project", "string", "The project it belongs to."),
+    ("goal", "string", "The goal it is linked to."),
+    ("reminders", "reminders", "When to be reminded of it."),
+    ("depends", "integers", "The IDs of the items to do first."),
+    (
+        "wait",
+        "integer",
+        "When it stops waiting, e.g. on someone else.",
+    ),
+    (
+        "estimate",
+        "integer",
+        "How long it should take, in seconds.",
+    ),
+    (
+        "someday",
+        "boolean",
+        "Whether it is on the someday/maybe list.",
+    ),
+    (
+        "planned",
+        "integer",
+        "The start of the day plan today planned it for.",
+    ),
+    ("modified", "integer", "When it was last changed."),
+    (
+        "highlight",
+        "string",
+        "The color it is listed in, e.g. bold red.",
+    ),
+    ("rev", "integer", "How many times it has been changed."),
+];
+
+/// Returns the OpenAPI 3 description of the API.
+pub fn spec() -> Value {
+    let token = json!([{ "bearer": [] }, {}]);
+    let parameters: Vec<Value> = PARAMETERS
+        .iter()
+        .map(|(name, description)| {
+            let schema = match *name {
+                "limit" => json!({ "type": "integer", "minimum": 1, "maximum": MAX_PAGE_SIZE }),
+                _ => json!({ "type": "string" }),
+            };
+            json!({
+                "name": name,
+                "in": "query",
+                "description": description,
+                "schema": schema,
+            })
+        })
+        .collect();
+    let etag = json!({
+        "etag": {
+            "description": "The checksum of the list, to send back in If-Match.",
+            "schema": { "type": "string" },
+        }
+    });
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "todo",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "A todo list shared by `todo serve`.",
+        },
+        "paths": {
+            "/todos": {
+                "get": {
+                    "summary": "Get the todo list, or a page of the items matching the parameters",
+                    "operationId": "getTodos",
+                    "security": token,
+                    "parameters": parameters,
+                    "responses": responses(&[
+                        (200, json!({
+                            "description": "The whole list without parameters, a page with them",
+                            "headers": etag,
+                            "content": { "application/json": { "schema": {
+                                "oneOf": [
+                                    { "$ref": "#/components/schemas/TodoList" },
+                                    { "$ref": "#/components/schemas/Page" },
+                                ]
+                            } } },
+                        })),
+                        (400, Value::Null),
+                        (401, Value::Null),
+                        (429, Value::Null),
+                    ]),
+                },
+                "put": {
+                    "summary": "Replace the todo list, if it has not changed since it was read",
+                    "operationId": "putTodos",
+                    "security": token,
+                    "parameters": [{
+                        "name": "If-Match",
+                        "in": "header",
+                        "required": true,
+                        "description": "The ETag of the list as it was read, or * to replace it whatever it is.",
+                        "schema": { "type": "string" },
+                    }],
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": {
+                            "$ref": "#/components/schemas/TodoList"
+                        } } },
+                    },
+                    "responses": responses(&[
+                        (204, json!({ "description": "Replaced", "headers": etag })),
+                        (400, Value::Null),
+                        (401, Value::Null),
+                        (403, Value::Null),
+                        (412, Value::Null),
+                        (428, Value::Null),
+                        (429, Value::Null),
+                    ]),
+                },
+            },
+            "/inbox": { "post": inbox_operation("postInbox", false, &token) },
+            "/inbox/{name}": { "post": inbox_operation("postInboxNamed", true, &token) },
+        },
+        "components": {
+            "securitySchemes": { "bearer": { "type": "http", "scheme": "bearer" } },
+            "schemas": {
+                "Todo": todo_schema(),
+                "Reminder": {
+                    "type": "object",
+                    "required": ["at"],
+                    "properties": {
+                        "at": { "type": "integer", "description": "When it fires." },
+                        "fired": { "type": "boolean", "description": "Whether it has fired." },
+                    },
+                },
+                "TodoList": {
+                    "type": "object",
+                    "required": ["todos"],
+                    "properties": {
+                        "todos": { "type": "array", "items": { "$ref": "#/components/schemas/Todo" } },
+                        "checksum": { "type": "string" },
+                    },
+                },
+                "Page": {
+                    "type": "object",
+                    "required": ["todos"],
+                    "properties": {
+                        "todos": { "type": "array", "items": { "$ref": "#/components/schemas/Todo" } },
+                        "next": {
+                            "type": "string",
+                            "description": "The cursor of the next page, left out on the last one.",
+                        },
+                    },
+                },
+            },
+        },
+    })
+}
+
+/// Describes posting a payload to the inbox, with the mapping named in the path when `named`.
+fn inbox_operation(id: &str, named: bool, security: &Value) -> Value {
+    let parameters = if named {
+        json!([{
+            "name": "name",
+            "in": "path",
+            "required": true,
+            "description": "The mapping in the inbox setting that makes the task.",
+            "schema": { "type": "string" },
+        }])
+    } else {
+        json!([])
+    };
+    json!({
+        "summary": "Add a todo item made from a payload, as the inbox setting maps it",
+        "operationId": id,
+        "security": security,
+        "parameters": parameters,
+        "requestBody": {
+            "required": true,
+            "content": {
+                "application/json": { "schema": { "type": "object" } },
+                "application/x-www-form-urlencoded": { "schema": { "type": "object" } },
+            },
+        },
+        "responses": responses(&[
+            (201, json!({
+                "description": "Added",
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "properties": { "id": { "type": "integer" } },
+                } } },
+            })),
+            (400, Value::Null),
+            (401, Value::Null),
+            (403, Value::Null),
+            (404, Value::Null),
+            (429, Value::Null),
+        ]),
+    })
+}
+
+/// Describes the responses with the statuses in `statuses`, each with its description, or
+/// the reason of its status and a plain text explanation when it is null.
+fn responses(statuses: &[(u16, Value)]) -> Value {
+    let mut responses = Map::new();
+    for (status, response) in statuses {
+        let response = match response {
+            Value::Null => json!({
+                "description": remote::reason(*status),
+                "content": { "text/plain": { "schema": { "type": "string" } } },
+            }),
+            response => response.clone(),
+        };
+        responses.insert(status.to_string(), response);
+    }
+    Value::Object(responses)
+}
+
+/// The schema of a todo item, from `TODO_FIELDS`.
+fn todo_schema() -> Value {
+    let properties: Map<String, Value> = TODO_FIELDS
+        .iter()
+        .map(|(name, kind, description)| {
+            let mut schema = match *kind {
+                "timestamps" | "integers" => {
+                    json!({ "type": "array", "items": { "type": "integer" } })
+                }
+                "strings" => json!({ "type": "array", "items": { "type": "string" } }),
+                "reminders" => json!({
+                    "type": "array",
+                    "items": { "$ref": "#/components/schemas/Reminder" }
+                }),
+                kind => json!({ "type": kind }),
+            };
+            schema["description"] = json!(description);
+            (name.to_string(), schema)
+        })
+        .collect();
+    let required: Vec<&str> = TODO_FIELDS[..4].iter().map(|(name, _, _)| *name).collect();
+    json!({ "type": "object", "required": required, "properties": properties })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{spec, TODO_FIELDS};
+    use crate::TodoList;
+
+    #[test]
+    fn test_todo_fields() {
+        // Every field set, so none is skipped when serializing
+        let list: TodoList = serde_json::from_str(
+            r#"{ "todos": [{
+                "id": 1, "hash": "ab12", "task": "Pay rent", "priority": 5, "created": 0,
+                "recur": "monthly", "workdays": true, "timezone": "Europe/Berlin", "done": [1], "late": [0], "assignee": "sam", "created_by": "kim",
+                "location": "bank", "url": "https://bank.example", "attachments": ["a.pdf"],
+                "aged": 1, "due": 2, "tags": ["home"], "project": "flat", "goal": "Move out",
+                "reminders": [{ "at": 1 }], "depends": [2], "wait": 1, "estimate": 60,
+                "someday": true, "planned": 1, "modified": 1, "highlight": "red", "rev": 3
+            }] }"#,
+        )
+        .unwrap();
+        let todo = serde_json::to_value(&list.todos[0]).unwrap();
+        let fields: Vec<&str> = todo
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let mut described: Vec<&str> = TODO_FIELDS.iter().map(|(name, _, _)| *name).collect();
+        described.sort_unstable();
+        assert_eq!(fields, described);
+    }
+
+    #[test]
+    fn test_spec() {
+        let spec = spec();
+        let get = &spec["paths"]["/todos"]["get"];
+        assert_eq!(get["parameters"][0]["name"], "tag");
+        assert_eq!(get["responses"]["429"]["description"], "Too Many Requests");
+        let inbox = &spec["paths"]["/inbox/{name}"]["post"];
+        assert_eq!(inbox["parameters"][0]["in"], "path");
+        assert_eq!(
+            spec["components"]["schemas"]["Todo"]["properties"]["due"]["type"],
+            "integer"
+        );
+    }
+}
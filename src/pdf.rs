@@ -0,0 +1,275 @@
+//! A minimal PDF writer for printable checklists, using the standard Helvetica fonts so
+//! nothing needs to be embedded.
+
+use std::fmt::Write;
+
+/// The page width and height in points (A4).
+const PAGE_SIZE: (f64, f64) = (595.0, 842.0);
+const MARGIN: f64 = 56.0;
+const TITLE_SIZE: f64 = 18.0;
+const HEADING_SIZE: f64 = 13.0;
+const TEXT_SIZE: f64 = 11.0;
+const NOTE_SIZE: f64 = 9.0;
+const BOX_SIZE: f64 = 9.0;
+/// The space between the checkbox and the text of an item.
+const INDENT: f64 = 18.0;
+
+/// A checklist laid out onto as many pages as it needs.
+///
+/// # Example
+///
+/// ```
+/// let mut document = Document::new("Todo list");
+/// document.heading("Home");
+/// document.item("Pay rent", "due 2024-06-01");
+/// std::fs::write("todos.pdf", document.finish())?;
+/// ```
+pub struct Document {
+    pages: Vec<String>,
+    /// The baseline of the next line on the current page.
+    y: f64,
+}
+
+impl Document {
+    /// Starts a document with `title` at the top of the first page.
+    pub fn new(title: &str) -> Document {
+        let mut document = Document {
+            pages: vec![String::new()],
+            y: PAGE_SIZE.1 - MARGIN,
+        };
+        document.text("F2", TITLE_SIZE, MARGIN, title);
+        document.y -= TITLE_SIZE * 1.2;
+        document
+    }
+
+    /// Adds a group heading, starting a new page if it would be left alone at the bottom.
+    pub fn heading(&mut self, heading: &str) {
+        self.make_room(HEADING_SIZE * 1.8 + TEXT_SIZE * 1.5);
+        self.y -= HEADING_SIZE * 0.8;
+        self.text("F2", HEADING_SIZE, MARGIN, heading);
+        self.y -= HEADING_SIZE;
+    }
+
+    /// Adds an unchecked item with its text wrapped to the page width, and a smaller grey
+    /// note, such as a due date, underneath when `note` is not empty.
+    pub fn item(&mut self, text: &str, note: &str) {
+        let width = PAGE_SIZE.0 - 2.0 * MARGIN - INDENT;
+        let lines = wrap(text, width, TEXT_SIZE);
+        let note_height = if note.is_empty() {
+            0.0
+        } else {
+            NOTE_SIZE * 1.3
+        };
+        self.make_room(lines.len() as f64 * TEXT_SIZE * 1.3 + note_height + TEXT_SIZE * 0.4);
+        let box_bottom = self.y - 1.0;
+        let _ = writeln!(
+            self.page(),
+            "0.8 w {:.1} {:.1} {:.1} {:.1} re S",
+            MARGIN,
+            box_bottom,
+            BOX_SIZE,
+            BOX_SIZE
+        );
+        for line in lines {
+            self.text("F1", TEXT_SIZE, MARGIN + INDENT, &line);
+            self.y -= TEXT_SIZE * 1.3;
+        }
+        if !note.is_empty() {
+            let _ = writeln!(self.page(), "0.4 g");
+            self.text("F1", NOTE_SIZE, MARGIN + INDENT, note);
+            let _ = writeln!(self.page(), "0 g");
+            self.y -= note_height;
+        }
+        self.y -= TEXT_SIZE * 0.4;
+    }
+
+    /// Serializes the document to the bytes of a PDF file.
+    pub fn finish(self) -> Vec<u8> {
+        let page_count = self.pages.len();
+        // Objects 1-4 are the catalog, page tree and fonts; each page then has a page
+        // object followed by its content stream.
+        let kids: Vec<String> = (0..page_count)
+            .map(|i| format!("{} 0 R", 5 + 2 * i))
+            .collect();
+        let mut objects: Vec<Vec<u8>> = vec![
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids.join(" "),
+                page_count
+            )
+            .into_bytes(),
+            font("Helvetica"),
+            font("Helvetica-Bold"),
+        ];
+        for (i, content) in self.pages.iter().enumerate() {
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                    PAGE_SIZE.0,
+                    PAGE_SIZE.1,
+                    6 + 2 * i
+                )
+                .into_bytes(),
+            );
+            let content = encode(content);
+            let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+            stream.extend(content);
+            stream.extend(b"endstream");
+            objects.push(stream);
+        }
+
+        let mut output = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(output.len());
+            output.extend(format!("{} 0 obj\n", i + 1).bytes());
+            output.extend(object);
+            output.extend(b"\nendobj\n");
+        }
+        let xref = output.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            trailer,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        );
+        output.extend(trailer.bytes());
+        output
+    }
+
+    fn page(&mut self) -> &mut String {
+        self.pages.last_mut().expect("A document always has a page")
+    }
+
+    /// Starts a new page unless `height` points still fit on the current one.
+    fn make_room(&mut self, height: f64) {
+        if self.y - height < MARGIN {
+            self.pages.push(String::new());
+            self.y = PAGE_SIZE.1 - MARGIN;
+        }
+    }
+
+    fn text(&mut self, font: &str, size: f64, x: f64, text: &str) {
+        let y = self.y;
+        let _ = writeln!(
+            self.page(),
+            "BT /{} {} Tf {:.1} {:.1} Td ({}) Tj ET",
+            font,
+            size,
+            x,
+            y,
+            escape(text)
+        );
+    }
+}
+
+fn font(name: &str) -> Vec<u8> {
+    format!(
+        "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+        name
+    )
+    .into_bytes()
+}
+
+/// Escapes the characters with a meaning inside a PDF string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('(', "\\(")
+        .replace(')', "\\)")
+}
+
+/// Converts text to single-byte WinAnsi (Latin-1) characters, replacing anything else
+/// with `?`, since the standard fonts have no other glyphs.
+fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c as u32 {
+            code @ (0x0A | 0x0D | 0x20..=0x7E | 0xA0..=0xFF) => code as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Splits text into lines that fit `width` points at font `size`, estimating the average
+/// Helvetica character at just over half the font size.
+fn wrap(text: &str, width: f64, size: f64) -> Vec<String> {
+    let max_chars = ((width / (size * 0.55)) as usize).max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word = word.to_string();
+        while word.chars().count() > max_chars {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            let rest = word.split_off(word.char_indices().nth(max_chars).map_or(0, |(i, _)| i));
+            lines.push(std::mem::replace(&mut word, rest));
+        }
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > max_chars {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&word);
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode, wrap, Document};
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("Pay the rent", 100.0, 10.0), vec!["Pay the rent"]);
+        assert_eq!(
+            wrap("one two three four", 50.0, 10.0),
+            vec!["one two", "three", "four"]
+        );
+        assert_eq!(
+            wrap("abcdefghijkl", 27.5, 10.0),
+            vec!["abcde", "fghij", "kl"]
+        );
+        assert_eq!(wrap("", 100.0, 10.0), vec![""]);
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("Café (ok) ✓"), b"Caf\xE9 (ok) ?".to_vec());
+    }
+
+    #[test]
+    fn test_document() {
+        let mut document = Document::new("Todo list");
+        document.heading("Home");
+        for i in 0..60 {
+            document.item(&format!("Task {} (with parentheses)", i), "due 2024-06-01");
+        }
+        document.item("Café ✓", "");
+        let pdf = document.finish();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("(Task 0 \\(with parentheses\\)) Tj"));
+        assert!(text.contains("/Count 3"));
+
+        // Every cross-reference entry points at the start of its object.
+        let startxref = text.rfind("startxref\n").unwrap();
+        let xref: usize = text[startxref..].lines().nth(1).unwrap().parse().unwrap();
+        assert!(pdf[xref..].starts_with(b"xref\n"));
+        let entries = String::from_utf8_lossy(&pdf[xref..]).into_owned();
+        for (number, entry) in entries.lines().skip(3).take(10).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", number + 1).as_bytes()));
+        }
+    }
+}
@@ -0,0 +1,580 @@
+//! Capacity planning: laying the estimated todo items out over today, tomorrow and the
+//! rest of the week, so a day is never planned with more work than fits into it, or into
+//! the free time between today's meetings, and picking the most urgent items that fit into
+//! today for `plan today`, which marks them as planned once the plan is accepted.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, Datelike};
+
+use crate::calendar::Busy;
+use crate::dates::{format_duration, Zone};
+use crate::Todo;
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// The shortest free time worth planning work into, in seconds.
+const MIN_SLOT: i64 = 15 * 60;
+
+/// The time available for tasks each day when neither `--capacity` nor `daily_capacity`
+/// says otherwise.
+pub const CAPACITY: &str = "8h";
+
+/// A todo item placed in the plan.
+#[derive(Debug, PartialEq)]
+pub struct Planned {
+    pub id: u64,
+    pub task: String,
+    /// The estimated time it takes, in seconds.
+    pub estimate: i64,
+    pub due: Option<i64>,
+    /// Whether it is only finished after its due date.
+    pub late: bool,
+}
+
+/// The todo items that fit into each part of the week at the daily capacity.
+#[derive(Debug, PartialEq)]
+pub struct Plan {
+    /// The working time available per day, in seconds.
+    pub capacity: i64,
+    pub today: Vec<Planned>,
+    pub tomorrow: Vec<Planned>,
+    /// The items planned after tomorrow and before the end of Sunday.
+    pub this_week: Vec<Planned>,
+    /// The number of days after tomorrow left in the week.
+    pub days_left: i64,
+    /// The items that don't fit into the week.
+    pub overflow: Vec<Planned>,
+    /// The number of items left out because they have no estimate.
+    pub unestimated: usize,
+    /// The number of items left out because they are waiting.
+    pub waiting: usize,
+}
+
+/// Plans the estimated todo items from the timestamp `now`, `capacity` seconds a day.
+///
+/// Items are taken earliest due date first (those without one last), then highest
+/// priority first, and each is planned on the day its work would be finished when the
+/// items before it are done first. Items waiting until a later date, and those on the
+/// someday/maybe list, are left out.
+///
+/// # Example
+///
+/// ```
+/// let plan = plan::plan(&todo_list.todos, 6 * 60 * 60, Utc::now().timestamp());
+/// print!("{}", plan.render());
+/// ```
+pub fn plan(todos: &[Todo], capacity: i64, now: i64) -> Plan {
+    let today = now - now.rem_euclid(DAY);
+    let weekday = DateTime::from_timestamp(now, 0)
+        .unwrap_or_default()
+        .weekday()
+        .num_days_from_monday() as i64;
+    let days_left = (5 - weekday).max(0);
+
+    let (unestimated, waiting) = left_out(todos, now);
+    let mut plan = Plan {
+        capacity,
+        today: Vec::new(),
+        tomorrow: Vec::new(),
+        this_week: Vec::new(),
+        days_left,
+        overflow: Vec::new(),
+        unestimated,
+        waiting,
+    };
+    let mut planned = 0;
+    for todo in candidates(todos, now) {
+        let estimate = todo.estimate.unwrap_or_default();
+        planned += estimate;
+        // The day the work is finished on, counting today as 0.
+        let day = (planned - 1).max(0) / capacity.max(1);
+        let item = Planned {
+            id: todo.id,
+            task: todo.task.clone(),
+            estimate,
+            due: todo.due,
+            late: todo.due.is_some_and(|due| due < today + (day + 1) * DAY),
+        };
+        match day {
+            0 => plan.today.push(item),
+            1 => plan.tomorrow.push(item),
+            day if day < 2 + days_left => plan.this_week.push(item),
+            _ => plan.overflow.push(item),
+        }
+    }
+    plan
+}
+
+/// Returns the estimated todo items that can be worked on at the timestamp `now`, earliest
+/// due date first (those without one last), then highest priority first.
+fn candidates(todos: &[Todo], now: i64) -> Vec<&Todo> {
+    let mut candidates: Vec<&Todo> = todos
+        .iter()
+        .filter(|todo| todo.estimate.is_some() && todo.wait.is_none_or(|wait| wait <= now))
+        .filter(|todo| !todo.someday)
+        .collect();
+    candidates.sort_by_key(|todo| {
+        (
+            todo.due.is_none(),
+            todo.due,
+            std::cmp::Reverse(todo.priority),
+            todo.id,
+        )
+    });
+    candidates
+}
+
+/// Counts the todo items left out of a plan at the timestamp `now`, because they have no
+/// estimate or are waiting.
+fn left_out(todos: &[Todo], now: i64) -> (usize, usize) {
+    let todos = todos.iter().filter(|todo| !todo.someday);
+    let unestimated = todos.clone().filter(|todo| todo.estimate.is_none()).count();
+    let waiting = todos
+        .filter(|todo| todo.estimate.is_some() && todo.wait.is_some_and(|wait| wait > now))
+        .count();
+    (unestimated, waiting)
+}
+
+/// Scores how pressing `todo` is at the timestamp `now`: an overdue item the most, then
+/// one due within the week the more the sooner it is due, each level of priority counting
+/// as much as a day nearer its due date.
+pub fn urgency(todo: &Todo, now: i64) -> i64 {
+    let due = match todo.due {
+        None => 0,
+        Some(due) if due < now => 100,
+        Some(due) => (7 - (due - now) / DAY).max(0) * 10,
+    };
+    due + todo.priority as i64 * 10
+}
+
+/// Picks the todo items to work on today from the timestamp `now`: the most urgent ones
+/// that can be worked on and fit into `capacity` seconds together, leaving out blocked
+/// items and those `excluded`. An item too long for the time left is passed over for the
+/// shorter ones after it.
+///
+/// # Example
+///
+/// ```
+/// let today = plan::propose(&todo_list.todos, 6 * 60 * 60, &[], Utc::now().timestamp());
+/// ```
+pub fn propose(todos: &[Todo], capacity: i64, excluded: &[u64], now: i64) -> Vec<Planned> {
+    let mut candidates: Vec<&Todo> = candidates(todos, now)
+        .into_iter()
+        .filter(|todo| todo.depends.is_empty() && !excluded.contains(&todo.id))
+        .collect();
+    candidates.sort_by_key(|todo| std::cmp::Reverse(urgency(todo, now)));
+    let mut planned = Vec::new();
+    let mut total = 0;
+    for todo in candidates {
+        let estimate = todo.estimate.unwrap_or_default();
+        if total + estimate > capacity {
+            continue;
+        }
+        total += estimate;
+        planned.push(Planned {
+            id: todo.id,
+            task: todo.task.clone(),
+            estimate,
+            due: todo.due,
+            late: todo.due.is_some_and(|due| due < now + total),
+        });
+    }
+    planned
+}
+
+/// What the user answers when asked to accept a proposed plan for today.
+#[derive(Debug, PartialEq)]
+pub enum Answer {
+    Accept,
+    /// Propose again without the todo items with these IDs.
+    Exclude(Vec<u64>),
+    Quit,
+}
+
+/// Reads an answer to a proposed plan: nothing accepts it, IDs separated by spaces or
+/// commas leave those items out, and `q` quits. Returns `None` for anything else.
+pub fn parse_answer(answer: &str) -> Option<Answer> {
+    match answer.trim() {
+        "" | "a" | "accept" => Some(Answer::Accept),
+        "q" | "quit" => Some(Answer::Quit),
+        answer => answer
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|id| !id.is_empty())
+            .map(|id| id.parse().ok())
+            .collect::<Option<Vec<u64>>>()
+            .map(Answer::Exclude),
+    }
+}
+
+/// Renders the todo items planned for today out of `capacity` seconds, marking those that
+/// would be finished after they are due.
+pub fn render_today(items: &[Planned], capacity: i64) -> String {
+    let mut output = String::new();
+    let _ = writeln!(
+        output,
+        "Today ({} planned of {}):",
+        format_duration(total(items)),
+        format_duration(capacity)
+    );
+    render_items(&mut output, items);
+    output
+}
+
+/// A free time between meetings and the todo items planned into it.
+#[derive(Debug, PartialEq)]
+pub struct Slot {
+    pub start: i64,
+    pub end: i64,
+    pub items: Vec<Planned>,
+}
+
+/// The todo items that fit into the free time of a working day.
+#[derive(Debug, PartialEq)]
+pub struct DayPlan {
+    /// When the working day starts and ends.
+    pub start: i64,
+    pub end: i64,
+    /// The number of busy times in the working day.
+    pub meetings: usize,
+    pub slots: Vec<Slot>,
+    /// The items that fit into none of the free times.
+    pub overflow: Vec<Planned>,
+    pub unestimated: usize,
+    pub waiting: usize,
+}
+
+/// Plans the estimated todo items into the free time between the timestamps `start` and
+/// `end` of a working day, around the times someone is `busy`, from the timestamp `now`.
+///
+/// Free times shorter than 15 minutes are left alone. Items are taken in the same order as
+/// by `plan`, and each goes into the first free time it still fits into, so a short item
+/// can fill the gap before a long one.
+///
+/// # Example
+///
+/// ```
+/// let busy = calendar::busy(&contents, start, end, zone)?;
+/// print!("{}", plan::plan_day(&todo_list.todos, &busy, start, end, now).render(zone));
+/// ```
+pub fn plan_day(todos: &[Todo], busy: &[Busy], start: i64, end: i64, now: i64) -> DayPlan {
+    let mut slots = Vec::new();
+    let mut free = start.max(now);
+    let mut meetings = 0;
+    for busy in busy
+        .iter()
+        .filter(|busy| busy.start < end && busy.end > start)
+    {
+        meetings += 1;
+        if busy.start - free >= MIN_SLOT {
+            slots.push(Slot {
+                start: free,
+                end: busy.start,
+                items: Vec::new(),
+            });
+        }
+        free = free.max(busy.end);
+    }
+    if end - free >= MIN_SLOT {
+        slots.push(Slot {
+            start: free,
+            end,
+            items: Vec::new(),
+        });
+    }
+
+    let (unestimated, waiting) = left_out(todos, now);
+    let mut plan = DayPlan {
+        start,
+        end,
+        meetings,
+        slots,
+        overflow: Vec::new(),
+        unestimated,
+        waiting,
+    };
+    for todo in candidates(todos, now) {
+        let estimate = todo.estimate.unwrap_or_default();
+        let slot = plan
+            .slots
+            .iter_mut()
+            .find(|slot| slot.start + total(&slot.items) + estimate <= slot.end);
+        let finished = slot
+            .as_ref()
+            .map_or(i64::MAX, |slot| slot.start + total(&slot.items) + estimate);
+        let item = Planned {
+            id: todo.id,
+            task: todo.task.clone(),
+            estimate,
+            due: todo.due,
+            late: todo.due.is_some_and(|due| due < finished),
+        };
+        match slot {
+            Some(slot) => slot.items.push(item),
+            None => plan.overflow.push(item),
+        }
+    }
+    plan
+}
+
+impl DayPlan {
+    /// Renders the plan as text with times in the timezone `zone`, marking the items that
+    /// would be finished after they are due.
+    pub fn render(&self, zone: Zone) -> String {
+        let time = |timestamp| zone.local(timestamp).format("%H:%M");
+        let free: i64 = self.slots.iter().map(|slot| slot.end - slot.start).sum();
+        let mut output = String::new();
+        let _ = writeln!(
+            output,
+            "Free today between {} and {} around {} meeting(s): {}",
+            time(self.start),
+            time(self.end),
+            self.meetings,
+            format_duration(free)
+        );
+        if self.slots.is_empty() {
+            let _ = writeln!(output, "  no free time left");
+        }
+        for slot in &self.slots {
+            let _ = writeln!(
+                output,
+                "{}-{} ({} planned of {}):",
+                time(slot.start),
+                time(slot.end),
+                format_duration(total(&slot.items)),
+                format_duration(slot.end - slot.start)
+            );
+            render_items(&mut output, &slot.items);
+        }
+        if !self.overflow.is_empty() {
+            let _ = writeln!(
+                output,
+                "Not fitting into today's free time ({}):",
+                format_duration(total(&self.overflow))
+            );
+            render_items(&mut output, &self.overflow);
+        }
+        render_left_out(&mut output, self.unestimated, self.waiting);
+        output
+    }
+}
+
+impl Plan {
+    /// Renders the plan as text, marking the items that would be finished after they are
+    /// due.
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        let sections = [
+            ("Today", &self.today, 1),
+            ("Tomorrow", &self.tomorrow, 1),
+            ("Rest of the week", &self.this_week, self.days_left),
+        ];
+        for (title, items, days) in sections {
+            if days == 0 {
+                continue;
+            }
+            let _ = writeln!(
+                output,
+                "{} ({} planned of {}):",
+                title,
+                format_duration(total(items)),
+                format_duration(self.capacity * days)
+            );
+            render_items(&mut output, items);
+        }
+        if !self.overflow.is_empty() {
+            let _ = writeln!(
+                output,
+                "Overflow ({} that doesn't fit this week):",
+                format_duration(total(&self.overflow))
+            );
+            render_items(&mut output, &self.overflow);
+        }
+        render_left_out(&mut output, self.unestimated, self.waiting);
+        output
+    }
+}
+
+fn render_left_out(output: &mut String, unestimated: usize, waiting: usize) {
+    if unestimated > 0 {
+        let _ = writeln!(
+            output,
+            "{} task(s) without an estimate are not planned; set one with estimate <id> <duration>",
+            unestimated
+        );
+    }
+    if waiting > 0 {
+        let _ = writeln!(output, "{} waiting task(s) are not planned", waiting);
+    }
+}
+
+fn total(items: &[Planned]) -> i64 {
+    items.iter().map(|item| item.estimate).sum()
+}
+
+fn render_items(output: &mut String, items: &[Planned]) {
+    if items.is_empty() {
+        let _ = writeln!(output, "  nothing");
+    }
+    for item in items {
+        let _ = write!(
+            output,
+            "  {}: {} ({}",
+            item.id,
+            item.task,
+            format_duration(item.estimate)
+        );
+        if let Some(due) = item.due {
+            let due = DateTime::from_timestamp(due, 0).unwrap_or_default();
+            let _ = write!(output, ", due {}", due.format("%Y-%m-%d %H:%M"));
+        }
+        let _ = writeln!(output, "){}", if item.late { " LATE" } else { "" });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_answer, plan, plan_day, propose, render_today, urgency, Answer};
+    use crate::calendar::Busy;
+    use crate::dates::Zone;
+    use crate::TodoList;
+
+    #[test]
+    fn test_plan() {
+        // Thursday 2024-06-06 09:00 Utc.
+        let now = 1717664400;
+        let hour = 60 * 60;
+        let mut todo_list = TodoList::new();
+        for (task, priority) in [
+            ("Report", 3),
+            ("Slides", 5),
+            ("Taxes", 2),
+            ("Garden", 1),
+            ("Inbox", 4),
+            ("Call", 4),
+        ] {
+            todo_list.add_todo(task, priority);
+        }
+        todo_list.set_estimate(1, 4 * hour);
+        todo_list.set_estimate(2, 2 * hour);
+        todo_list.set_estimate(3, 6 * hour);
+        todo_list.set_estimate(4, 20 * hour);
+        todo_list.set_estimate(5, hour);
+        todo_list.set_due(1, now + 3 * hour);
+        todo_list.set_due(3, now - hour);
+        todo_list.set_wait(6, now + hour);
+        todo_list.set_estimate(6, hour);
+        todo_list.add_todo("Learn the banjo", 5);
+        todo_list.todos[6].someday = true;
+
+        let plan = plan(&todo_list.todos, 6 * hour, now);
+        let ids =
+            |items: &[super::Planned]| -> Vec<u64> { items.iter().map(|item| item.id).collect() };
+        // Taxes are overdue and fill today, so the report due today is finished late.
+        assert_eq!(ids(&plan.today), vec![3]);
+        assert!(plan.today[0].late);
+        assert_eq!(ids(&plan.tomorrow), vec![1, 2]);
+        assert!(plan.tomorrow[0].late);
+        assert!(!plan.tomorrow[1].late);
+        assert_eq!(plan.days_left, 2);
+        assert_eq!(ids(&plan.this_week), vec![5]);
+        assert_eq!(ids(&plan.overflow), vec![4]);
+        assert_eq!(plan.unestimated, 0);
+        assert_eq!(plan.waiting, 1);
+
+        let output = plan.render();
+        assert!(output.starts_with(
+            "Today (6h planned of 6h):\n  3: Taxes (6h, due 2024-06-06 08:00) LATE\n"
+        ));
+        assert!(output.contains("Rest of the week (1h planned of 12h):\n  5: Inbox (1h)\n"));
+        assert!(output.contains("Overflow (20h that doesn't fit this week):\n"));
+    }
+
+    #[test]
+    fn test_plan_day() {
+        // Thursday 2024-06-06 09:00 to 17:00 Utc, planned at 09:05
+        let start = 1717664400;
+        let hour = 60 * 60;
+        let end = start + 8 * hour;
+        let meeting = |from: i64, to: i64| Busy {
+            start: start + from * 60,
+            end: start + to * 60,
+            summary: "Meeting".to_string(),
+        };
+        // Free 09:05-10:00, 11:00-11:10 (too short), 12:00-14:00 and 16:00-17:00
+        let busy = [meeting(60, 120), meeting(130, 180), meeting(300, 420)];
+        let mut todo_list = TodoList::new();
+        for (task, estimate) in [("Report", 2), ("Slides", 1), ("Garden", 6), ("Inbox", 1)] {
+            let id = todo_list.add_todo(task, 3).unwrap();
+            todo_list.set_estimate(id, estimate * hour / 2);
+        }
+        todo_list.set_due(1, start + 3 * hour);
+        todo_list.add_todo("Unestimated", 3);
+
+        let plan = plan_day(&todo_list.todos, &busy, start, end, start + 5 * 60);
+        let slots: Vec<(i64, i64, Vec<u64>)> = plan
+            .slots
+            .iter()
+            .map(|slot| {
+                let ids = slot.items.iter().map(|item| item.id).collect();
+                ((slot.start - start) / 60, (slot.end - start) / 60, ids)
+            })
+            .collect();
+        // The report needs an hour, so it waits for noon and is late; the rest fill in
+        assert_eq!(
+            slots,
+            [(5, 60, vec![2]), (180, 300, vec![1, 4]), (420, 480, vec![])]
+        );
+        assert!(plan.slots[1].items[0].late);
+        assert_eq!(plan.overflow[0].id, 3);
+        assert_eq!(plan.meetings, 3);
+
+        let output = plan.render(Zone::Utc);
+        assert!(output.starts_with(
+            "Free today between 09:00 and 17:00 around 3 meeting(s): 3h55m\n09:05-10:00 (30m planned of 55m):\n  2: Slides (30m)\n"
+        ));
+        assert!(output.contains("16:00-17:00 (0m planned of 1h):\n  nothing\n"));
+        assert!(output.contains("Not fitting into today's free time (3h):\n  3: Garden (3h)\n"));
+        assert!(output.ends_with("1 task(s) without an estimate are not planned; set one with estimate <id> <duration>\n"));
+    }
+
+    #[test]
+    fn test_propose() {
+        // Thursday 2024-06-06 09:00 Utc.
+        let now = 1717664400;
+        let hour = 60 * 60;
+        let mut todo_list = TodoList::new();
+        for (task, priority, estimate) in [
+            ("Report", 3, 3),
+            ("Slides", 5, 2),
+            ("Taxes", 2, 4),
+            ("Inbox", 1, 1),
+            ("Call", 4, 1),
+        ] {
+            let id = todo_list.add_todo(task, priority).unwrap();
+            todo_list.set_estimate(id, estimate * hour);
+        }
+        todo_list.set_due(1, now + 8 * hour);
+        todo_list.set_due(3, now - hour);
+        todo_list.add_dependencies(5, &[4]);
+        assert_eq!(urgency(&todo_list.todos[2], now), 120);
+        assert_eq!(urgency(&todo_list.todos[0], now), 100);
+
+        // Taxes and the report fill 7 hours, so the slides are passed over for the inbox
+        let ids =
+            |items: &[super::Planned]| -> Vec<u64> { items.iter().map(|item| item.id).collect() };
+        let today = propose(&todo_list.todos, 8 * hour, &[], now);
+        assert_eq!(ids(&today), [3, 1, 4]);
+        assert!(today[0].late && !today[1].late);
+        assert_eq!(
+            ids(&propose(&todo_list.todos, 8 * hour, &[3], now)),
+            [1, 2, 4]
+        );
+        assert!(render_today(&today, 8 * hour).starts_with("Today (8h planned of 8h):\n  3: Taxes"));
+
+        assert_eq!(parse_answer("\n"), Some(Answer::Accept));
+        assert_eq!(parse_answer("3, 4"), Some(Answer::Exclude(vec![3, 4])));
+        assert_eq!(parse_answer("q"), Some(Answer::Quit));
+        assert_eq!(parse_answer("later"), None);
+    }
+}
@@ -0,0 +1,148 @@
+//! Plugins: running `todo foo` runs a `todo-foo` program found on `PATH`, as git does with
+//! its subcommands, so commands can be added without rebuilding the application.
+//!
+//! The plugin gets the rest of the arguments as they were given, and the list it should work
+//! on and the global options in its environment, already combined with the config file:
+//!
+//! - `TODO_FILE`: the absolute path of the todo list, unless a remote is used
+//! - `TODO_REMOTE`: the URL of the served list, when one is used
+//! - `TODO_PLAIN`: `true` for output written for screen readers, otherwise `false`
+//! - `TODO_COLOR`: `auto`, `always` or `never`
+//! - `TODO_WIDTH`: how many columns wide output may be, when known
+//! - `TODO_BIN`: this program, for the plugin to run other commands with
+//!
+//! All but the last two are also the variables that override settings, so the commands a
+//! plugin runs work on the same list with the same options.
+
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::Command;
+
+use todo::config::{ColorChoice, Config};
+
+/// What a plugin's program is named before the name of the command it adds.
+pub const PREFIX: &str = "todo-";
+
+/// Returns the program that runs the command `name`, or an error if the name could not be a
+/// plugin's, e.g. a path.
+pub fn program(name: &str) -> Result<String, String> {
+    let valid = !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    match valid {
+        true => Ok(format!("{}{}", PREFIX, name)),
+        false => Err(format!("Unknown command: {}", name)),
+    }
+}
+
+/// Returns the environment a plugin runs in: the todo list `file`, or the `remote` in its
+/// place, and the output options of `config` and `width`.
+pub fn environment(
+    file: &Path,
+    remote: Option<&str>,
+    config: &Config,
+    width: Option<usize>,
+) -> Vec<(&'static str, String)> {
+    let mut variables = Vec::new();
+    match remote {
+        Some(remote) => variables.push(("TODO_REMOTE", remote.to_string())),
+        None => {
+            let file = match std::env::current_dir() {
+                Ok(dir) => dir.join(file),
+                Err(_) => file.to_path_buf(),
+            };
+            variables.push(("TODO_FILE", file.display().to_string()));
+        }
+    }
+    variables.push(("TODO_PLAIN", config.plain.to_string()));
+    let color = match config.color {
+        ColorChoice::Auto => "auto",
+        ColorChoice::Always => "always",
+        ColorChoice::Never => "never",
+    };
+    variables.push(("TODO_COLOR", color.to_string()));
+    if let Some(width) = width {
+        variables.push(("TODO_WIDTH", width.to_string()));
+    }
+    if let Ok(bin) = std::env::current_exe() {
+        variables.push(("TODO_BIN", bin.display().to_string()));
+    }
+    variables
+}
+
+/// Runs the plugin for `args`, the command's name followed by its arguments, in the
+/// environment `variables`, and returns its exit status.
+pub fn run(args: &[String], variables: &[(&'static str, String)]) -> Result<i32, String> {
+    let (name, args) = args
+        .split_first()
+        .ok_or_else(|| "No command given".to_string())?;
+    let program = program(name)?;
+    let status = Command::new(&program)
+        .args(args)
+        .envs(variables.iter().map(|(name, value)| (name, value)))
+        .status()
+        .map_err(|error| match error.kind() {
+            ErrorKind::NotFound => format!(
+                "Unknown command: {} (no {} on PATH either; see help)",
+                name, program
+            ),
+            _ => format!("Unable to run {}: {}", program, error),
+        })?;
+    // A plugin killed by a signal has no exit status, so it counts as having failed
+    Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{environment, program, run};
+    use std::path::Path;
+    use todo::config::{ColorChoice, Config};
+
+    #[test]
+    fn test_plugin() {
+        assert_eq!(program("hello"), Ok("todo-hello".to_string()));
+        assert_eq!(
+            program("weekly_review-2"),
+            Ok("todo-weekly_review-2".to_string())
+        );
+        assert!(program("../hello").is_err());
+        assert!(program("-x").is_err());
+        assert!(program("").is_err());
+
+        let config = Config {
+            plain: true,
+            color: ColorChoice::Never,
+            ..Config::default()
+        };
+        let variables = environment(Path::new("/home/alice/todos.json"), None, &config, Some(80));
+        let value = |name| {
+            variables
+                .iter()
+                .find(|(other, _)| *other == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(value("TODO_FILE"), Some("/home/alice/todos.json"));
+        assert_eq!(value("TODO_REMOTE"), None);
+        assert_eq!(value("TODO_PLAIN"), Some("true"));
+        assert_eq!(value("TODO_COLOR"), Some("never"));
+        assert_eq!(value("TODO_WIDTH"), Some("80"));
+        let relative = environment(Path::new("todos.json"), None, &Config::default(), None);
+        assert!(Path::new(&relative[0].1).is_absolute());
+        let remote = environment(
+            Path::new("todos.json"),
+            Some("http://nas.local:8080"),
+            &Config::default(),
+            None,
+        );
+        assert_eq!(
+            remote[0],
+            ("TODO_REMOTE", "http://nas.local:8080".to_string())
+        );
+        assert!(!remote.iter().any(|(name, _)| *name == "TODO_FILE"));
+
+        let missing = run(&["no-such-plugin-here".to_string()], &[]).unwrap_err();
+        assert!(missing.starts_with("Unknown command: no-such-plugin-here"));
+    }
+}
@@ -0,0 +1,251 @@
+//! Predicting when open todo items will be done from how long similar ones took, for
+//! `show --predict` and `forecast`.
+//!
+//! The similar items are the completed ones in the log `stats` reads with the same priority
+//! and a tag in common, or failing enough of those the same priority, or failing those all
+//! of them. Only those that took longer than the open item has been open so far count, so
+//! an item that has been lying around for a while is not predicted to be done yesterday.
+
+use std::fmt::{self, Write};
+
+use crate::dates::DateFormat;
+use crate::stats::Completed;
+use crate::Todo;
+
+/// How many similar completed items a prediction needs.
+pub const MIN_SAMPLES: usize = 3;
+
+/// Which completed todo items a prediction was made from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Basis {
+    /// Those with the tag and the priority.
+    TagAndPriority(String, u64),
+    /// Those with the priority.
+    Priority(u64),
+    /// Every one.
+    All,
+}
+
+impl fmt::Display for Basis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Basis::TagAndPriority(tag, priority) => {
+                write!(f, "tasks tagged +{} of priority {}", tag, priority)
+            }
+            Basis::Priority(priority) => write!(f, "tasks of priority {}", priority),
+            Basis::All => f.write_str("all tasks"),
+        }
+    }
+}
+
+/// When an open todo item is likely to be done.
+#[derive(Debug, PartialEq)]
+pub struct Prediction {
+    /// When half of the similar items were done by, at its age.
+    pub likely: i64,
+    /// When four in five of them were done by.
+    pub by: i64,
+    /// How many similar items it was made from.
+    pub samples: usize,
+    pub basis: Basis,
+}
+
+/// Returns the value below which `percent` of the sorted `values` fall.
+fn percentile(values: &[i64], percent: usize) -> i64 {
+    values[(values.len() - 1) * percent / 100]
+}
+
+/// Predicts when `todo`, open at the timestamp `now`, will be done from the todo items
+/// `completed`, or returns `None` if too few similar items took as long as it has been open,
+/// or if it recurs and so is never done.
+pub fn predict(todo: &Todo, completed: &[Completed], now: i64) -> Option<Prediction> {
+    if todo.recur.is_some() {
+        return None;
+    }
+    let age = now - todo.created;
+    let mut bases: Vec<Basis> = todo
+        .tags
+        .iter()
+        .map(|tag| Basis::TagAndPriority(tag.clone(), todo.priority))
+        .collect();
+    bases.extend([Basis::Priority(todo.priority), Basis::All]);
+    bases.into_iter().find_map(|basis| {
+        let mut durations: Vec<i64> = completed
+            .iter()
+            .filter(|item| match &basis {
+                Basis::TagAndPriority(tag, priority) => {
+                    item.todo.priority == *priority && item.todo.tags.contains(tag)
+                }
+                Basis::Priority(priority) => item.todo.priority == *priority,
+                Basis::All => true,
+            })
+            .map(|item| item.completed - item.todo.created)
+            .filter(|duration| *duration > age)
+            .collect();
+        if durations.len() < MIN_SAMPLES {
+            return None;
+        }
+        durations.sort_unstable();
+        Some(Prediction {
+            likely: todo.created + percentile(&durations, 50),
+            by: todo.created + percentile(&durations, 80),
+            samples: durations.len(),
+            basis,
+        })
+    })
+}
+
+/// Describes `prediction` in a line, with dates in the format `dates` relative to the
+/// timestamp `now`.
+pub fn describe(prediction: &Prediction, dates: &DateFormat, now: i64) -> String {
+    format!(
+        "likely done {}, 80% chance by {} (from {} {})",
+        dates.format(prediction.likely, now, "%Y-%m-%d"),
+        dates.format(prediction.by, now, "%Y-%m-%d"),
+        prediction.samples,
+        prediction.basis
+    )
+}
+
+/// Formats the open todo items `rows` with their predictions as a table, soonest first and
+/// those without one last, with dates in the format `dates` relative to the timestamp `now`.
+pub fn render_forecast(
+    rows: &mut [(&Todo, Option<Prediction>)],
+    dates: &DateFormat,
+    now: i64,
+) -> String {
+    rows.sort_by_key(|(todo, prediction)| {
+        (
+            prediction.is_none(),
+            prediction.as_ref().map(|prediction| prediction.likely),
+            todo.id,
+        )
+    });
+    let name = |todo: &Todo| format!("{}: {}", todo.id, todo.task);
+    let width = rows
+        .iter()
+        .map(|(todo, _)| name(todo).chars().count())
+        .chain(["task".len()])
+        .max()
+        .unwrap_or_default();
+    let date = |timestamp| dates.format(timestamp, now, "%Y-%m-%d");
+    let mut output = String::new();
+    let _ = writeln!(
+        output,
+        "{:width$}  {:10}  {:10}  from",
+        "task", "likely", "80% by"
+    );
+    for (todo, prediction) in rows.iter() {
+        let _ = match prediction {
+            Some(prediction) => writeln!(
+                output,
+                "{:width$}  {:10}  {:10}  {} {}",
+                name(todo),
+                date(prediction.likely),
+                date(prediction.by),
+                prediction.samples,
+                prediction.basis
+            ),
+            None => writeln!(
+                output,
+                "{:width$}  {:10}  {:10}  not enough history",
+                name(todo),
+                "-",
+                "-"
+            ),
+        };
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe, predict, render_forecast, Basis, Prediction};
+    use crate::dates::DateFormat;
+    use crate::stats::Completed;
+    use crate::TodoList;
+
+    #[test]
+    fn test_predict() {
+        const DAY: i64 = 24 * 60 * 60;
+        let mut todo_list = TodoList::new();
+        for task in [
+            "Fix login",
+            "Fix search",
+            "Fix export",
+            "Fix import",
+            "Tidy up",
+        ] {
+            todo_list.add_todo(task, 4);
+        }
+        todo_list.add_todo("Write docs", 2);
+        let now = todo_list.todos[0].created;
+        // Completed bugs that took 1, 2, 3, 4 and 10 days
+        let completed: Vec<Completed> = todo_list.todos[..5]
+            .iter()
+            .zip([1, 2, 3, 4, 10])
+            .map(|(todo, days)| {
+                let mut todo = todo.clone();
+                todo.tags = vec!["bug".to_string()];
+                todo.created = now - 20 * DAY;
+                Completed {
+                    completed: todo.created + days * DAY,
+                    todo,
+                }
+            })
+            .collect();
+        let todo = &mut todo_list.todos[0];
+        todo.tags = vec!["bug".to_string()];
+
+        let prediction = predict(todo, &completed, now).unwrap();
+        assert_eq!(
+            prediction,
+            Prediction {
+                likely: now + 3 * DAY,
+                by: now + 4 * DAY,
+                samples: 5,
+                basis: Basis::TagAndPriority("bug".to_string(), 4),
+            }
+        );
+        let short = DateFormat::parse("%Y-%m-%d").unwrap();
+        assert!(describe(&prediction, &short, now)
+            .ends_with("(from 5 tasks tagged +bug of priority 4)"));
+
+        // Open for 3.5 days: only the two that took longer count, too few
+        todo.created = now - 3 * DAY - DAY / 2;
+        assert_eq!(predict(todo, &completed, now), None);
+        todo.created = now - 2 * DAY - DAY / 2;
+        assert_eq!(
+            predict(todo, &completed, now).unwrap().likely,
+            now + DAY + DAY / 2
+        );
+
+        // Without the tag, by priority; of another priority, from every one
+        todo.created = now;
+        todo.tags.clear();
+        assert_eq!(
+            predict(todo, &completed, now).unwrap().basis,
+            Basis::Priority(4)
+        );
+        let docs = &todo_list.todos[5];
+        assert_eq!(predict(docs, &completed, now).unwrap().basis, Basis::All);
+        todo_list.todos[5].recur = Some("FREQ=DAILY".to_string());
+        assert_eq!(predict(&todo_list.todos[5], &completed, now), None);
+
+        let short = DateFormat::parse("short").unwrap();
+        let mut rows = vec![
+            (&todo_list.todos[5], None),
+            (
+                &todo_list.todos[1],
+                predict(&todo_list.todos[1], &completed, now),
+            ),
+        ];
+        let forecast = render_forecast(&mut rows, &short, now);
+        let lines: Vec<&str> = forecast.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("task           likely      80% by      from"));
+        assert!(lines[1].starts_with("2: Fix search  "));
+        assert!(lines[1].ends_with("  5 tasks of priority 4"));
+        assert!(lines[2].ends_with("-           -           not enough history"));
+    }
+}
@@ -0,0 +1,255 @@
+//! The priority scale: how many priorities there are, what they are called and the colors
+//! tasks are listed in, configured with `priorities` from the least urgent to the most:
+//!
+//! ```json
+//! "priorities": [
+//!     { "name": "low", "color": "blue" },
+//!     { "name": "medium" },
+//!     { "name": "high", "color": "yellow" },
+//!     { "name": "critical", "color": "bold red" }
+//! ]
+//! ```
+//!
+//! Priorities are stored as numbers, 1 the least urgent, and wherever one is given its name
+//! works as well as its number. Without `priorities` there are five, without names. A
+//! priority without a color is listed in the theme's `priority1` to `priority5`, spread over
+//! the scale when it has more or fewer than five.
+
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::theme;
+
+/// The number of priorities when none are configured.
+const DEFAULT_LEVELS: usize = 5;
+
+/// The number of bands priorities are colored by, like the `priority` roles of a theme.
+const BANDS: u64 = 5;
+
+/// The scale every command uses, set once the configuration is read.
+static SCALE: OnceLock<Scale> = OnceLock::new();
+
+/// A configured priority.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Level {
+    /// What it is called, e.g. `"high"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The color tasks with it are listed in, e.g. `"bold red"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+/// The priorities tasks can have, from the least urgent to the most.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scale {
+    levels: Vec<Level>,
+}
+
+impl Default for Scale {
+    fn default() -> Scale {
+        Scale {
+            levels: vec![Level::default(); DEFAULT_LEVELS],
+        }
+    }
+}
+
+impl Scale {
+    /// Makes the scale of the configured `levels`, the default one when there are none.
+    pub fn new(levels: &[Level]) -> Result<Scale, String> {
+        if levels.is_empty() {
+            return Ok(Scale::default());
+        }
+        for (index, level) in levels.iter().enumerate() {
+            if let Some(name) = &level.name {
+                if name.trim().is_empty() || name.parse::<u64>().is_ok() {
+                    return Err(format!(
+                        "Invalid priority name: '{}'. Expected a word, not a number",
+                        name
+                    ));
+                }
+                if levels[..index].iter().any(|other| {
+                    other
+                        .name
+                        .as_deref()
+                        .is_some_and(|other| other.eq_ignore_ascii_case(name))
+                }) {
+                    return Err(format!("The priority name '{}' is given twice", name));
+                }
+            }
+            if let Some(color) = &level.color {
+                theme::check_color(color)
+                    .map_err(|error| format!("{} (for priority {})", error, index + 1))?;
+            }
+        }
+        Ok(Scale {
+            levels: levels.to_vec(),
+        })
+    }
+
+    /// Returns the number of priorities, which is also the most urgent one.
+    pub fn levels(&self) -> u64 {
+        self.levels.len() as u64
+    }
+
+    /// Returns whether `priority` is on the scale.
+    pub fn contains(&self, priority: u64) -> bool {
+        (1..=self.levels()).contains(&priority)
+    }
+
+    /// Returns the priority on the scale closest to `priority`.
+    pub fn clamp(&self, priority: i64) -> u64 {
+        priority.clamp(1, self.levels() as i64) as u64
+    }
+
+    /// Parses a priority given by its number or, whatever its case, its name.
+    pub fn parse(&self, input: &str) -> Result<u64, String> {
+        let input = input.trim();
+        let by_number = input
+            .parse::<u64>()
+            .ok()
+            .filter(|priority| self.contains(*priority));
+        let by_name = || {
+            self.levels
+                .iter()
+                .position(|level| {
+                    level
+                        .name
+                        .as_deref()
+                        .is_some_and(|name| name.eq_ignore_ascii_case(input))
+                })
+                .map(|index| index as u64 + 1)
+        };
+        by_number.or_else(by_name).ok_or_else(|| {
+            format!(
+                "Invalid priority: '{}'. Expected {}",
+                input,
+                self.expected()
+            )
+        })
+    }
+
+    /// Describes the priorities `parse` accepts, e.g. `1 to 4, or low, medium, high or
+    /// critical`.
+    pub fn expected(&self) -> String {
+        let range = format!("1 to {}", self.levels());
+        let names: Vec<&str> = self
+            .levels
+            .iter()
+            .filter_map(|level| level.name.as_deref())
+            .collect();
+        match names.split_last() {
+            None => range,
+            Some((last, [])) => format!("{}, or {}", range, last),
+            Some((last, names)) => format!("{}, or {} or {}", range, names.join(", "), last),
+        }
+    }
+
+    /// Returns the name of `priority`, if it has one.
+    pub fn name(&self, priority: u64) -> Option<&str> {
+        self.level(priority)?.name.as_deref()
+    }
+
+    /// Returns how `priority` is shown: its name, or else its number.
+    pub fn label(&self, priority: u64) -> String {
+        match self.name(priority) {
+            Some(name) => name.to_string(),
+            None => priority.to_string(),
+        }
+    }
+
+    /// Returns the color configured for `priority`, if any.
+    pub fn color(&self, priority: u64) -> Option<&str> {
+        self.level(priority)?.color.as_deref()
+    }
+
+    /// Returns which of five bands `priority` falls in, 1 to 5, the scale spread evenly over
+    /// them, for outputs with a color per band.
+    pub fn band(&self, priority: u64) -> u64 {
+        let priority = self.clamp(priority as i64);
+        match self.levels() {
+            1 => BANDS,
+            levels => 1 + ((priority - 1) * (BANDS - 1) + (levels - 1) / 2) / (levels - 1),
+        }
+    }
+
+    /// Returns the priority on this scale matching `priority` on the default one, 1 to 5, for
+    /// priorities read from elsewhere, e.g. an iCalendar `PRIORITY`.
+    pub fn rescale(&self, priority: u64) -> u64 {
+        let priority = priority.clamp(1, DEFAULT_LEVELS as u64);
+        1 + ((priority - 1) * (self.levels() - 1) + 2) / (DEFAULT_LEVELS as u64 - 1)
+    }
+
+    /// Returns the theme role tasks with `priority` are listed in without a color of its
+    /// own, `priority1` to `priority5` by its band.
+    pub fn role(&self, priority: u64) -> String {
+        format!("priority{}", self.band(priority))
+    }
+
+    fn level(&self, priority: u64) -> Option<&Level> {
+        self.levels.get(priority.checked_sub(1)? as usize)
+    }
+}
+
+/// Makes `scale` the one every command uses. Only the first scale set is kept.
+pub fn set(scale: Scale) {
+    let _ = SCALE.set(scale);
+}
+
+/// Returns the scale every command uses, the default one until another is set.
+pub fn scale() -> &'static Scale {
+    SCALE.get_or_init(Scale::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Level, Scale};
+
+    #[test]
+    fn test_scale() {
+        let level = |name: &str, color: Option<&str>| Level {
+            name: Some(name.to_string()),
+            color: color.map(str::to_string),
+        };
+        let scale = Scale::new(&[
+            level("low", Some("blue")),
+            level("medium", None),
+            level("high", None),
+            level("critical", Some("bold red")),
+        ])
+        .unwrap();
+        assert_eq!(scale.levels(), 4);
+        assert_eq!(scale.parse("High"), Ok(3));
+        assert_eq!(scale.parse("4"), Ok(4));
+        assert_eq!(
+            scale.parse("5"),
+            Err("Invalid priority: '5'. Expected 1 to 4, or low, medium, high or critical".into())
+        );
+        assert_eq!(scale.label(1), "low");
+        assert_eq!(scale.color(4), Some("bold red"));
+        assert_eq!(scale.color(2), None);
+        assert_eq!(scale.clamp(9), 4);
+        assert_eq!(scale.clamp(-1), 1);
+        let bands: Vec<u64> = (1..=4).map(|priority| scale.band(priority)).collect();
+        assert_eq!(bands, [1, 2, 4, 5]);
+        assert_eq!(scale.role(4), "priority5");
+        let converted: Vec<u64> = (1..=5).map(|priority| scale.rescale(priority)).collect();
+        assert_eq!(converted, [1, 2, 3, 3, 4]);
+
+        let default = Scale::new(&[]).unwrap();
+        assert_eq!(default, Scale::default());
+        assert_eq!(
+            default.parse("low"),
+            Err("Invalid priority: 'low'. Expected 1 to 5".into())
+        );
+        assert_eq!(default.label(3), "3");
+        assert_eq!(default.role(3), "priority3");
+        assert_eq!(default.rescale(4), 4);
+
+        assert!(Scale::new(&[level("low", None), level("LOW", None)]).is_err());
+        assert!(Scale::new(&[level("2", None)]).is_err());
+        assert!(Scale::new(&[level("low", Some("mauve"))]).is_err());
+    }
+}
@@ -0,0 +1,501 @@
+//! A dependency-free QR code encoder (ISO/IEC 18004) for showing tasks in the terminal.
+//!
+//! Data is always encoded in byte mode at error correction level M, in the smallest
+//! version (1-40) it fits, with the mask chosen by the standard penalty rules.
+
+/// Error correction codewords per block at level M, indexed by version.
+const ECC_CODEWORDS_PER_BLOCK: [usize; 41] = [
+    0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28,
+    28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+];
+
+/// Error correction blocks at level M, indexed by version.
+const ERROR_CORRECTION_BLOCKS: [usize; 41] = [
+    0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23,
+    25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+];
+
+/// The two format bits identifying error correction level M.
+const LEVEL_M_BITS: u32 = 0b00;
+
+/// An encoded QR code: a square grid of dark and light modules.
+#[derive(Debug)]
+pub struct QrCode {
+    /// The number of modules along each side.
+    pub size: usize,
+    modules: Vec<bool>,
+    function: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encodes `data` into the smallest QR code that holds it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let code = QrCode::encode(b"https://example.com")?;
+    /// print!("{}", code.render());
+    /// ```
+    pub fn encode(data: &[u8]) -> Result<QrCode, String> {
+        let version = (1..=40)
+            .find(|version| {
+                let count_bits = if *version < 10 { 8 } else { 16 };
+                4 + count_bits + data.len() * 8 <= data_codewords(*version) * 8
+            })
+            .ok_or_else(|| format!("{} bytes is too long for a QR code", data.len()))?;
+
+        let capacity = data_codewords(version) * 8;
+        let mut bits = Vec::with_capacity(capacity);
+        push_bits(&mut bits, 0b0100, 4);
+        push_bits(
+            &mut bits,
+            data.len() as u32,
+            if version < 10 { 8 } else { 16 },
+        );
+        for byte in data {
+            push_bits(&mut bits, *byte as u32, 8);
+        }
+        let terminator = (capacity - bits.len()).min(4);
+        push_bits(&mut bits, 0, terminator);
+        let padding = (8 - bits.len() % 8) % 8;
+        push_bits(&mut bits, 0, padding);
+        let mut codewords: Vec<u8> = bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |acc, bit| (acc << 1) | *bit as u8))
+            .collect();
+        for pad in [0xEC, 0x11].into_iter().cycle() {
+            if codewords.len() >= capacity / 8 {
+                break;
+            }
+            codewords.push(pad);
+        }
+
+        let size = version * 4 + 17;
+        let mut code = QrCode {
+            size,
+            modules: vec![false; size * size],
+            function: vec![false; size * size],
+        };
+        code.draw_function_patterns(version);
+        code.draw_codewords(&add_error_correction(&codewords, version));
+        let mask = (0..8)
+            .min_by_key(|mask| {
+                code.apply_mask(*mask);
+                code.draw_format_bits(*mask);
+                let penalty = code.penalty();
+                code.apply_mask(*mask);
+                penalty
+            })
+            .unwrap_or(0);
+        code.apply_mask(mask);
+        code.draw_format_bits(mask);
+        Ok(code)
+    }
+
+    /// Returns whether the module at column `x` and row `y` is dark.
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    /// Renders the code for a terminal with a dark background, two rows of modules per line
+    /// using half-block characters, inside the light border scanners need.
+    pub fn render(&self) -> String {
+        const BORDER: usize = 2;
+        let size = self.size as isize;
+        let border = BORDER as isize;
+        let light = |x: isize, y: isize| {
+            x < 0 || y < 0 || x >= size || y >= size || !self.is_dark(x as usize, y as usize)
+        };
+        let mut output = String::new();
+        let mut y = -border;
+        while y < size + border {
+            for x in -border..size + border {
+                output.push(match (light(x, y), light(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            output.push('\n');
+            y += 2;
+        }
+        output
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    fn draw_function_patterns(&mut self, version: usize) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+        for (x, y) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4isize..=4 {
+                for dx in -4isize..=4 {
+                    let (xx, yy) = (x as isize + dx, y as isize + dy);
+                    if (0..size as isize).contains(&xx) && (0..size as isize).contains(&yy) {
+                        let distance = dx.abs().max(dy.abs());
+                        self.set_function(xx as usize, yy as usize, distance != 2 && distance != 4);
+                    }
+                }
+            }
+        }
+        let positions = alignment_positions(version);
+        let last = positions.len().saturating_sub(1);
+        for (i, x) in positions.iter().enumerate() {
+            for (j, y) in positions.iter().enumerate() {
+                // Skip the three corners taken by finder patterns.
+                if [(0, 0), (0, last), (last, 0)].contains(&(i, j)) {
+                    continue;
+                }
+                for dy in -2isize..=2 {
+                    for dx in -2isize..=2 {
+                        let dark = dx.abs().max(dy.abs()) != 1;
+                        self.set_function(
+                            (*x as isize + dx) as usize,
+                            (*y as isize + dy) as usize,
+                            dark,
+                        );
+                    }
+                }
+            }
+        }
+        // Reserve the format areas; the real bits are drawn once the mask is chosen.
+        self.draw_format_bits(0);
+        if version >= 7 {
+            let bits = version_bits(version);
+            for i in 0..18 {
+                let dark = (bits >> i) & 1 == 1;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                self.set_function(a, b, dark);
+                self.set_function(b, a, dark);
+            }
+        }
+    }
+
+    fn draw_format_bits(&mut self, mask: u32) {
+        let bits = format_bits(mask);
+        let bit = |i: usize| (bits >> i) & 1 == 1;
+        let size = self.size;
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Places the codewords in the zigzag order of the standard, skipping function modules.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let size = self.size;
+        let mut index = 0;
+        let mut right = size - 1;
+        loop {
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward {
+                        size - 1 - vertical
+                    } else {
+                        vertical
+                    };
+                    if !self.function[y * size + x] && index < codewords.len() * 8 {
+                        self.modules[y * size + x] =
+                            (codewords[index / 8] >> (7 - index % 8)) & 1 == 1;
+                        index += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    /// Inverts the data modules selected by `mask`. Applying a mask twice undoes it.
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                let index = y * self.size + x;
+                if invert && !self.function[index] {
+                    self.modules[index] = !self.modules[index];
+                }
+            }
+        }
+    }
+
+    /// Scores how hard the code is to scan: long runs, 2x2 blocks, finder-like patterns
+    /// and an imbalance of dark and light modules all add to the penalty.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let mut penalty = 0;
+        let finder_like = [true, false, true, true, true, false, true];
+        let lines = (0..size).flat_map(|i| {
+            [
+                (0..size).map(|j| self.is_dark(j, i)).collect::<Vec<bool>>(),
+                (0..size).map(|j| self.is_dark(i, j)).collect::<Vec<bool>>(),
+            ]
+        });
+        for line in lines {
+            let mut run = 1;
+            for j in 1..=size {
+                if j < size && line[j] == line[j - 1] {
+                    run += 1;
+                } else {
+                    if run >= 5 {
+                        penalty += run - 2;
+                    }
+                    run = 1;
+                }
+            }
+            for start in 0..=size - 7 {
+                if line[start..start + 7] != finder_like {
+                    continue;
+                }
+                let light = |from: isize, to: isize| {
+                    (from..to).all(|k| k < 0 || k >= size as isize || !line[k as usize])
+                };
+                let start = start as isize;
+                if light(start - 4, start) || light(start + 7, start + 11) {
+                    penalty += 40;
+                }
+            }
+        }
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let dark = self.is_dark(x, y);
+                if dark == self.is_dark(x + 1, y)
+                    && dark == self.is_dark(x, y + 1)
+                    && dark == self.is_dark(x + 1, y + 1)
+                {
+                    penalty += 3;
+                }
+            }
+        }
+        let dark = self.modules.iter().filter(|dark| **dark).count();
+        let total = size * size;
+        // The number of 5% steps the dark proportion is away from 50%.
+        penalty += (dark * 20)
+            .abs_diff(total * 10)
+            .div_ceil(total)
+            .saturating_sub(1)
+            * 10;
+        penalty
+    }
+}
+
+fn push_bits(bits: &mut Vec<bool>, value: u32, count: usize) {
+    for i in (0..count).rev() {
+        bits.push((value >> i) & 1 == 1);
+    }
+}
+
+/// The number of modules available for data and error correction in a version.
+fn raw_data_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let alignments = version / 7 + 2;
+        modules -= (25 * alignments - 10) * alignments - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+/// The number of data codewords a version holds at level M.
+fn data_codewords(version: usize) -> usize {
+    raw_data_modules(version) / 8
+        - ECC_CODEWORDS_PER_BLOCK[version] * ERROR_CORRECTION_BLOCKS[version]
+}
+
+/// The row and column coordinates of the alignment pattern centres.
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = if version == 32 {
+        26
+    } else {
+        (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2
+    };
+    let size = version * 4 + 17;
+    let mut positions: Vec<usize> = (0..count - 1).map(|i| size - 7 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+/// The 15 format bits for level M and `mask`, with their BCH error correction.
+fn format_bits(mask: u32) -> u32 {
+    let data = (LEVEL_M_BITS << 3) | mask;
+    let mut remainder = data;
+    for _ in 0..10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    ((data << 10) | remainder) ^ 0x5412
+}
+
+/// The 18 version bits, with their BCH error correction, for versions 7 and up.
+fn version_bits(version: usize) -> u32 {
+    let mut remainder = version as u32;
+    for _ in 0..12 {
+        remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+    }
+    ((version as u32) << 12) | remainder
+}
+
+/// Splits the data codewords into blocks, appends each block's Reed-Solomon codewords and
+/// interleaves the result.
+fn add_error_correction(data: &[u8], version: usize) -> Vec<u8> {
+    let blocks_count = ERROR_CORRECTION_BLOCKS[version];
+    let ecc_length = ECC_CODEWORDS_PER_BLOCK[version];
+    let raw_codewords = raw_data_modules(version) / 8;
+    let short_blocks = blocks_count - raw_codewords % blocks_count;
+    let short_length = raw_codewords / blocks_count;
+    let divisor = reed_solomon_divisor(ecc_length);
+
+    let mut blocks = Vec::with_capacity(blocks_count);
+    let mut start = 0;
+    for i in 0..blocks_count {
+        let length = short_length - ecc_length + usize::from(i >= short_blocks);
+        let mut block = data[start..start + length].to_vec();
+        start += length;
+        let ecc = reed_solomon_remainder(&block, &divisor);
+        if i < short_blocks {
+            // A placeholder keeping the blocks aligned; skipped when interleaving.
+            block.push(0);
+        }
+        block.extend(ecc);
+        blocks.push(block);
+    }
+    let mut result = Vec::with_capacity(raw_codewords);
+    for i in 0..blocks[0].len() {
+        for (j, block) in blocks.iter().enumerate() {
+            if i != short_length - ecc_length || j >= short_blocks {
+                result.push(block[i]);
+            }
+        }
+    }
+    result
+}
+
+/// The generator polynomial of a Reed-Solomon code with `degree` check codewords.
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut divisor = vec![0; degree];
+    divisor[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            divisor[j] = gf_multiply(divisor[j], root);
+            if j + 1 < degree {
+                divisor[j] ^= divisor[j + 1];
+            }
+        }
+        root = gf_multiply(root, 0x02);
+    }
+    divisor
+}
+
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut remainder = vec![0; divisor.len()];
+    for byte in data {
+        let factor = byte ^ remainder.remove(0);
+        remainder.push(0);
+        for (r, d) in remainder.iter_mut().zip(divisor) {
+            *r ^= gf_multiply(*d, factor);
+        }
+    }
+    remainder
+}
+
+/// Multiplies in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1.
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut product: u8 = 0;
+    for i in (0..8).rev() {
+        product = (product << 1) ^ ((product >> 7) * 0x1D);
+        product ^= ((y >> i) & 1) * x;
+    }
+    product
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        alignment_positions, data_codewords, format_bits, reed_solomon_divisor,
+        reed_solomon_remainder, version_bits, QrCode,
+    };
+
+    #[test]
+    fn test_reed_solomon() {
+        // The "HELLO WORLD" version 1-M example from the standard's annex.
+        let data = [
+            32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17,
+        ];
+        assert_eq!(
+            reed_solomon_remainder(&data, &reed_solomon_divisor(10)),
+            vec![196, 35, 39, 119, 235, 215, 231, 226, 93, 23]
+        );
+    }
+
+    #[test]
+    fn test_tables() {
+        assert_eq!(data_codewords(1), 16);
+        assert_eq!(data_codewords(10), 216);
+        assert_eq!(data_codewords(40), 2334);
+        assert_eq!(alignment_positions(2), vec![6, 18]);
+        assert_eq!(alignment_positions(7), vec![6, 22, 38]);
+        assert_eq!(alignment_positions(32), vec![6, 34, 60, 86, 112, 138]);
+        assert_eq!(format_bits(0), 0b101010000010010);
+        assert_eq!(format_bits(5), 0b100000011001110);
+        assert_eq!(version_bits(7), 0b000111110010010100);
+    }
+
+    #[test]
+    fn test_encode() {
+        let code = QrCode::encode(b"Pay rent").unwrap();
+        assert_eq!(code.size, 21);
+        // Finder pattern corners and the dark module.
+        assert!(code.is_dark(0, 0) && code.is_dark(20, 0) && code.is_dark(0, 20));
+        assert!(!code.is_dark(7, 7));
+        assert!(code.is_dark(8, 13));
+        assert_eq!(QrCode::encode(&[b'x'; 100]).unwrap().size, 41);
+        assert_eq!(QrCode::encode(&[b'x'; 2331]).unwrap().size, 177);
+        assert!(QrCode::encode(&[b'x'; 2332]).is_err());
+
+        let rendered = code.render();
+        assert_eq!(rendered.lines().count(), 13);
+        assert!(rendered.lines().all(|line| line.chars().count() == 25));
+    }
+}
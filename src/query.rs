@@ -0,0 +1,1016 @@
+//! A small query language for selecting, sorting and reshaping todo items, e.g.
+//! `"tag:work priority>=3 !has:assignee sort:-priority limit:10"` or
+//! `"(priority >= 4 and tag:work) or overdue"`.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::Value;
+
+use crate::dates;
+use crate::fold;
+use crate::priorities;
+use crate::Todo;
+
+/// A parsed query: the filters every todo item must pass, then how to order, truncate and
+/// project the items that do.
+#[derive(Debug, Default, PartialEq)]
+pub struct Query {
+    filter: Option<Expr>,
+    sort: SortOrder,
+    limit: Option<usize>,
+    fields: Option<Vec<String>>,
+    fuzzy: bool,
+    ignore_accents: bool,
+    /// The tags todo items inherit from their project, by project.
+    inherited: BTreeMap<String, Vec<String>>,
+}
+
+/// How similar a word of a fuzzy query has to be to a task to match it, from 0 to 1.
+const FUZZY_THRESHOLD: f64 = 0.45;
+
+/// A boolean combination of filters.
+#[derive(Debug, PartialEq)]
+enum Expr {
+    Filter(Filter),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Filter {
+    Text(String),
+    Tag(String),
+    Assignee(String),
+    Creator(String),
+    Location(String),
+    Project(String),
+    /// Planned by `plan today` for the day containing the timestamp.
+    Planned(i64),
+    Has(String),
+    Overdue,
+    Compare(SortKey, Operator, i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Operator {
+    /// Returns whether an actual value comparing as `ordering` to the expected one passes.
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Equal => ordering.is_eq(),
+            Operator::NotEqual => ordering.is_ne(),
+            Operator::Less => ordering.is_lt(),
+            Operator::LessOrEqual => ordering.is_le(),
+            Operator::Greater => ordering.is_gt(),
+            Operator::GreaterOrEqual => ordering.is_ge(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortKey {
+    Id,
+    Task,
+    Priority,
+    Created,
+    Due,
+    Modified,
+}
+
+impl SortKey {
+    fn parse(name: &str) -> Option<SortKey> {
+        match name {
+            "id" => Some(SortKey::Id),
+            "task" => Some(SortKey::Task),
+            "priority" => Some(SortKey::Priority),
+            "created" => Some(SortKey::Created),
+            "due" => Some(SortKey::Due),
+            "modified" => Some(SortKey::Modified),
+            _ => None,
+        }
+    }
+
+    /// The numeric value of the key, for comparisons. Tasks have none.
+    fn number(self, todo: &Todo) -> Option<i64> {
+        match self {
+            SortKey::Id => Some(todo.id as i64),
+            SortKey::Task => None,
+            SortKey::Priority => Some(todo.priority as i64),
+            SortKey::Created => Some(todo.created),
+            SortKey::Due => todo.due,
+            SortKey::Modified => Some(todo.last_modified()),
+        }
+    }
+
+    fn compare(self, a: &Todo, b: &Todo) -> Ordering {
+        match self {
+            SortKey::Task => fold::fold(&a.task).cmp(&fold::fold(&b.task)),
+            // Todo items without a due date sort last.
+            SortKey::Due => match (a.due, b.due) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            },
+            _ => self.number(a).cmp(&self.number(b)),
+        }
+    }
+}
+
+/// An ordering of todo items by several keys, each ascending or descending, where later
+/// keys break the ties of earlier ones.
+#[derive(Debug, Default, PartialEq)]
+pub struct SortOrder(Vec<(SortKey, bool)>);
+
+impl SortOrder {
+    /// Parses a comma-separated list of keys, each optionally followed by `asc` (the
+    /// default) or `desc`: `id`, `task`, `priority`, `created`, `due` or `modified`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let order = SortOrder::parse("due asc, priority desc, created asc")?;
+    /// order.sort(&mut todo_list.todos);
+    /// ```
+    pub fn parse(input: &str) -> Result<SortOrder, String> {
+        let mut keys = Vec::new();
+        for part in input.split(',') {
+            let mut words = part.split_whitespace();
+            let name = words
+                .next()
+                .ok_or_else(|| format!("Missing sort key in '{}'", input.trim()))?;
+            let key =
+                SortKey::parse(name).ok_or_else(|| format!("Unknown sort key: '{}'", name))?;
+            let descending = match words.next().map(str::to_lowercase).as_deref() {
+                None | Some("asc") => false,
+                Some("desc") => true,
+                Some(other) => {
+                    return Err(format!(
+                        "Expected 'asc' or 'desc' after '{}', found '{}'",
+                        name, other
+                    ))
+                }
+            };
+            if let Some(extra) = words.next() {
+                return Err(format!("Unexpected '{}' in '{}'", extra, part.trim()));
+            }
+            keys.push((key, descending));
+        }
+        Ok(SortOrder(keys))
+    }
+
+    /// Sorts `todos` in this order, keeping the existing order of items that tie.
+    pub fn sort(&self, todos: &mut [Todo]) {
+        todos.sort_by(|a, b| {
+            self.0
+                .iter()
+                .map(|(key, descending)| {
+                    let ordering = key.compare(a, b);
+                    if *descending {
+                        ordering.reverse()
+                    } else {
+                        ordering
+                    }
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+}
+
+impl Query {
+    /// Parses a query. Dates are interpreted relative to the timestamp `now`.
+    ///
+    /// Filters separated by spaces must all match, and can be combined with `and`, `or`,
+    /// `not` (or `!`) and parentheses, where `not` binds tightest and `or` loosest:
+    ///
+    /// * `word` - the task contains the word, ignoring case and how its accented letters
+    ///   were typed (see `fold`); quote it to search for several words or a keyword, e.g.
+    ///   `"buy milk"` or `"or"`
+    /// * `tag:<tag>`, `assignee:<name>`, `created-by:<name>`, `location:<place>`,
+    ///   `project:<name>`
+    /// * `planned:<day>` - planned by `plan today` for the day, e.g. `planned:today`
+    /// * `has:<field>` - the field is set, e.g. `has:due` or `has:attachments`
+    /// * `overdue` - the due date has passed
+    /// * `<key> <op> <value>` - compares `id`, `priority`, `created`, `due` or `modified`
+    ///   using `=`, `!=`, `<`, `<=`, `>` or `>=`, with or without spaces; dates may be
+    ///   `YYYY-MM-DD`, `today`, `yesterday`, `tomorrow`, `now` or e.g. `"2 days ago"`
+    ///
+    /// These apply to the whole query and can't be inside parentheses:
+    ///
+    /// * `sort:<key>` or `sort:-<key>` - sorts ascending or descending by `id`, `task`,
+    ///   `priority`, `created`, `due` or `modified`; later sorts break ties
+    /// * `limit:<n>` - keeps the first n items
+    /// * `fields:<field>,...` - outputs only these fields of each item
+    ///
+    /// Errors name the column where the problem was found.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let now = Utc::now().timestamp();
+    /// let query = Query::parse("(priority >= 4 and tag:work) or overdue sort:-priority", now)?;
+    /// ```
+    pub fn parse(input: &str, now: i64) -> Result<Query, String> {
+        let mut query = Query::default();
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+        for (column, token) in tokenize(input)? {
+            match &token {
+                Token::Open => depth += 1,
+                Token::Close => depth -= 1,
+                Token::Word(word, false) if is_option(word) => {
+                    if depth > 0 {
+                        return Err(format!(
+                            "'{}' applies to the whole query and can't be inside parentheses \
+                             (column {})",
+                            word, column
+                        ));
+                    }
+                    query.parse_option(word)?;
+                    continue;
+                }
+                _ => {}
+            }
+            tokens.push((column, token));
+        }
+        if !tokens.is_empty() {
+            let mut parser = Parser {
+                tokens,
+                position: 0,
+                now,
+            };
+            let expr = parser.or()?;
+            if let Some((column, token)) = parser.peek() {
+                return Err(match token {
+                    Token::Close => format!("Unmatched ')' at column {}", column),
+                    _ => format!("Unexpected {} at column {}", token, column),
+                });
+            }
+            query.filter = Some(expr);
+        }
+        Ok(query)
+    }
+
+    fn parse_option(&mut self, term: &str) -> Result<(), String> {
+        if let Some(key) = term.strip_prefix("sort:") {
+            let (key, descending) = match key.strip_prefix('-') {
+                Some(key) => (key, true),
+                None => (key, false),
+            };
+            let key = SortKey::parse(key).ok_or_else(|| format!("Unknown sort key: '{}'", key))?;
+            self.sort.0.push((key, descending));
+        } else if let Some(limit) = term.strip_prefix("limit:") {
+            let limit = limit
+                .parse()
+                .map_err(|_| format!("Invalid limit: '{}'", limit))?;
+            self.limit = Some(limit);
+        } else if let Some(fields) = term.strip_prefix("fields:") {
+            self.fields = Some(fields.split(',').map(str::to_string).collect());
+        }
+        Ok(())
+    }
+
+    /// Makes the words of the query match tasks that contain something like them, such as
+    /// "invioce" for "Send invoice", ranking the items by how well they match unless the
+    /// query sorts them.
+    pub fn fuzzy(mut self) -> Query {
+        self.fuzzy = true;
+        self
+    }
+
+    /// Makes the words and names of the query match whatever accents the todo items have,
+    /// such as "cafe" for "Café".
+    pub fn ignore_accents(mut self) -> Query {
+        self.ignore_accents = true;
+        self
+    }
+
+    /// Makes `tag:` match the todo items of a project with one of the tags `inherited`
+    /// gives the project, as well as those with the tag themselves.
+    pub fn inherit_tags(mut self, inherited: BTreeMap<String, Vec<String>>) -> Query {
+        self.inherited = inherited;
+        self
+    }
+
+    /// Returns the projects every todo item the query selects is in, or `None` if it can
+    /// select items of any project or of none, e.g. for loading only their shards.
+    pub fn projects(&self) -> Option<BTreeSet<String>> {
+        self.filter.as_ref().and_then(Expr::projects)
+    }
+
+    /// Returns whether `todo` passes the filters of the query at the timestamp `now`.
+    pub fn matches(&self, todo: &Todo, now: i64) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(todo, now, self))
+    }
+
+    /// Filters, sorts and truncates `todos`.
+    pub fn select(&self, mut todos: Vec<Todo>, now: i64) -> Vec<Todo> {
+        todos.retain(|todo| self.matches(todo, now));
+        match &self.filter {
+            Some(filter) if self.fuzzy && self.sort.0.is_empty() => {
+                let score = |todo: &Todo| filter.score(todo, self.ignore_accents);
+                todos.sort_by(|a, b| score(b).total_cmp(&score(a)))
+            }
+            _ => self.sort.sort(&mut todos),
+        }
+        todos.truncate(self.limit.unwrap_or(usize::MAX));
+        todos
+    }
+
+    /// Selects from `todos` like `select`, then projects them onto the requested fields.
+    pub fn apply(&self, todos: Vec<Todo>, now: i64) -> Vec<Value> {
+        self.select(todos, now)
+            .iter()
+            .map(|todo| {
+                let value = serde_json::to_value(todo).expect("Unable to serialize todo");
+                match &self.fields {
+                    Some(fields) => Value::Object(
+                        fields
+                            .iter()
+                            .filter_map(|field| {
+                                value.get(field).map(|v| (field.clone(), v.clone()))
+                            })
+                            .collect(),
+                    ),
+                    None => value,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Expr {
+    fn matches(&self, todo: &Todo, now: i64, query: &Query) -> bool {
+        match self {
+            Expr::Filter(filter) => filter.matches(todo, now, query),
+            Expr::Not(expr) => !expr.matches(todo, now, query),
+            Expr::And(exprs) => exprs.iter().all(|expr| expr.matches(todo, now, query)),
+            Expr::Or(exprs) => exprs.iter().any(|expr| expr.matches(todo, now, query)),
+        }
+    }
+
+    /// Returns the projects the todo items the expression matches are in, if it limits them.
+    fn projects(&self) -> Option<BTreeSet<String>> {
+        match self {
+            Expr::Filter(Filter::Project(project)) => Some(BTreeSet::from([project.clone()])),
+            Expr::Filter(_) | Expr::Not(_) => None,
+            // Every part must match, so any part that limits the projects limits them all
+            Expr::And(exprs) => exprs
+                .iter()
+                .filter_map(Expr::projects)
+                .reduce(|a, b| a.intersection(&b).cloned().collect()),
+            Expr::Or(exprs) => exprs
+                .iter()
+                .map(Expr::projects)
+                .collect::<Option<Vec<_>>>()
+                .map(|projects| projects.into_iter().flatten().collect()),
+        }
+    }
+
+    /// Returns how well the words of the expression match the task of `todo`, for ranking
+    /// fuzzy matches: the sum of their `fuzzy_score`s, leaving out negated words.
+    fn score(&self, todo: &Todo, ignore_accents: bool) -> f64 {
+        match self {
+            Expr::Filter(Filter::Text(word)) => fuzzy_score(
+                &fold::key(word, ignore_accents),
+                &fold::key(&todo.task, ignore_accents),
+            ),
+            Expr::Filter(_) | Expr::Not(_) => 0.0,
+            Expr::And(exprs) | Expr::Or(exprs) => exprs
+                .iter()
+                .map(|expr| expr.score(todo, ignore_accents))
+                .sum(),
+        }
+    }
+}
+
+/// Returns how similar `word` is to the most similar word of `task`, or to the whole task if
+/// `word` has spaces, both folded by `fold::key`, from 0 to 1: 1 if it is contained in it,
+/// otherwise the share of trigrams the two have in common (their Dice coefficient), so that
+/// misspellings with most letters in place still score well.
+fn fuzzy_score(word: &str, task: &str) -> f64 {
+    if fold::contains(task, word) {
+        return 1.0;
+    }
+    let trigrams = |text: &str| -> Vec<[char; 3]> {
+        // Padding counts the first and last letters as trigrams of their own, as
+        // PostgreSQL's pg_trgm does
+        let chars: Vec<char> = format!("  {} ", text).chars().collect();
+        let mut trigrams: Vec<[char; 3]> = chars
+            .windows(3)
+            .map(|window| [window[0], window[1], window[2]])
+            .collect();
+        trigrams.sort_unstable();
+        trigrams.dedup();
+        trigrams
+    };
+    let expected = trigrams(word);
+    let similarity = |candidate: &str| {
+        let actual = trigrams(candidate);
+        let common = expected
+            .iter()
+            .filter(|trigram| actual.contains(trigram))
+            .count();
+        2.0 * common as f64 / (expected.len() + actual.len()) as f64
+    };
+    if word.contains(' ') {
+        return similarity(task);
+    }
+    task.split(|c: char| !c.is_alphanumeric())
+        .filter(|candidate| !candidate.is_empty())
+        .map(similarity)
+        .fold(0.0, f64::max)
+}
+
+impl Filter {
+    fn matches(&self, todo: &Todo, now: i64, query: &Query) -> bool {
+        let key = |text: &str| fold::key(text, query.ignore_accents);
+        let equal = |value: &Option<String>, expected: &str| {
+            value
+                .as_deref()
+                .is_some_and(|value| key(value) == key(expected))
+        };
+        match self {
+            Filter::Text(word) if query.fuzzy => {
+                fuzzy_score(&key(word), &key(&todo.task)) >= FUZZY_THRESHOLD
+            }
+            Filter::Text(word) => fold::contains(&key(&todo.task), &key(word)),
+            Filter::Tag(tag) => {
+                let inherited = todo
+                    .project
+                    .as_ref()
+                    .and_then(|project| query.inherited.get(project));
+                todo.has_tag(tag, now)
+                    || todo
+                        .tags
+                        .iter()
+                        .chain(inherited.into_iter().flatten())
+                        .any(|other| key(other) == key(tag))
+            }
+            Filter::Assignee(name) => equal(&todo.assignee, name),
+            Filter::Creator(name) => equal(&todo.created_by, name),
+            Filter::Location(place) => equal(&todo.location, place),
+            Filter::Project(project) => equal(&todo.project, project),
+            Filter::Planned(at) => todo.planned_at(*at),
+            Filter::Has(field) => serde_json::to_value(todo)
+                .ok()
+                .and_then(|value| value.get(field).cloned())
+                .is_some(),
+            Filter::Overdue => todo.due.is_some_and(|due| due < now),
+            Filter::Compare(key, operator, value) => key
+                .number(todo)
+                .is_some_and(|actual| operator.holds(actual.cmp(value))),
+        }
+    }
+}
+
+/// Returns whether `word` is one of the terms setting how the whole query's results are
+/// sorted, truncated or projected.
+fn is_option(word: &str) -> bool {
+    ["sort:", "limit:", "fields:"]
+        .iter()
+        .any(|prefix| word.starts_with(prefix))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Not,
+    Operator(Operator, &'static str),
+    /// A word, and whether any of it was quoted.
+    Word(String, bool),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::Not => write!(f, "'!'"),
+            Token::Operator(_, symbol) => write!(f, "'{}'", symbol),
+            Token::Word(word, _) => write!(f, "'{}'", word),
+        }
+    }
+}
+
+/// Splits a query into tokens, each with the column (counting from 1) where it starts.
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i + 1;
+        let followed_by_equal = chars.get(i + 1) == Some(&'=');
+        let (token, length) = match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            '!' if followed_by_equal => (Token::Operator(Operator::NotEqual, "!="), 2),
+            '!' => (Token::Not, 1),
+            '<' if followed_by_equal => (Token::Operator(Operator::LessOrEqual, "<="), 2),
+            '>' if followed_by_equal => (Token::Operator(Operator::GreaterOrEqual, ">="), 2),
+            '<' => (Token::Operator(Operator::Less, "<"), 1),
+            '>' => (Token::Operator(Operator::Greater, ">"), 1),
+            '=' => (Token::Operator(Operator::Equal, "="), 1),
+            _ => {
+                let mut word = String::new();
+                let mut quoted = false;
+                let mut j = i;
+                while let Some(&c) = chars.get(j) {
+                    // A `!` inside a word ends it only when it starts `!=`.
+                    let ends_word = c.is_whitespace()
+                        || "()<>=".contains(c)
+                        || (c == '!' && chars.get(j + 1) == Some(&'='));
+                    if ends_word {
+                        break;
+                    }
+                    if c == '"' || c == '\'' {
+                        let end = chars[j + 1..]
+                            .iter()
+                            .position(|&end| end == c)
+                            .ok_or_else(|| format!("Unterminated quote at column {}", j + 1))?;
+                        word.extend(&chars[j + 1..j + 1 + end]);
+                        quoted = true;
+                        j += end + 2;
+                    } else {
+                        word.push(c);
+                        j += 1;
+                    }
+                }
+                (Token::Word(word, quoted), j - i)
+            }
+        };
+        tokens.push((column, token));
+        i += length;
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser over the tokens of a query.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    now: i64,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<(usize, &Token)> {
+        self.tokens
+            .get(self.position)
+            .map(|(column, token)| (*column, token))
+    }
+
+    /// The column just past the last token, for errors at the end of the query.
+    fn end(&self) -> usize {
+        self.tokens.last().map_or(1, |(column, token)| {
+            column + token.to_string().chars().count() - 2
+        })
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some((_, Token::Word(word, false))) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    /// `or := and ("or" and)*`
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut exprs = vec![self.and()?];
+        while self.is_keyword("or") {
+            self.position += 1;
+            exprs.push(self.operand("or")?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::Or(exprs)
+        })
+    }
+
+    /// `and := unary (["and"] unary)*`
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut exprs = vec![self.unary()?];
+        loop {
+            if self.is_keyword("and") {
+                self.position += 1;
+                let expr = self.operand("and")?;
+                exprs.push(expr);
+            } else if matches!(self.peek(), None | Some((_, Token::Close))) || self.is_keyword("or")
+            {
+                break;
+            } else {
+                exprs.push(self.unary()?);
+            }
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::And(exprs)
+        })
+    }
+
+    /// Parses the operand after `keyword`, explaining when it is missing.
+    fn operand(&mut self, keyword: &str) -> Result<Expr, String> {
+        if matches!(self.peek(), None | Some((_, Token::Close))) {
+            let column = self.peek().map_or(self.end(), |(column, _)| column);
+            return Err(format!(
+                "Expected a filter after '{}' at column {}",
+                keyword, column
+            ));
+        }
+        if keyword == "not" || keyword == "!" {
+            self.unary()
+        } else {
+            self.and()
+        }
+    }
+
+    /// `unary := ("not" | "!") unary | "(" or ")" | comparison | filter`
+    fn unary(&mut self) -> Result<Expr, String> {
+        let (column, token) = match self.peek() {
+            Some((column, token)) => (column, token.clone()),
+            None => return Err(format!("Expected a filter at column {}", self.end())),
+        };
+        self.position += 1;
+        match token {
+            Token::Not => Ok(Expr::Not(Box::new(self.operand("!")?))),
+            Token::Word(word, false) if word.eq_ignore_ascii_case("not") => {
+                Ok(Expr::Not(Box::new(self.operand("not")?)))
+            }
+            Token::Word(word, false)
+                if word.eq_ignore_ascii_case("and") || word.eq_ignore_ascii_case("or") =>
+            {
+                Err(format!(
+                    "Expected a filter before '{}' at column {}",
+                    word, column
+                ))
+            }
+            Token::Open => {
+                if matches!(self.peek(), Some((_, Token::Close))) {
+                    return Err(format!("Empty parentheses at column {}", column));
+                }
+                let expr = self.or()?;
+                match self.peek() {
+                    Some((_, Token::Close)) => {
+                        self.position += 1;
+                        Ok(expr)
+                    }
+                    _ => Err(format!("Missing ')' for the '(' at column {}", column)),
+                }
+            }
+            Token::Close => Err(format!("Unmatched ')' at column {}", column)),
+            Token::Operator(_, symbol) => Err(format!(
+                "Expected a field before '{}' at column {}",
+                symbol, column
+            )),
+            Token::Word(word, quoted) => {
+                if let Some((operator_column, Token::Operator(operator, symbol))) = self.peek() {
+                    let (operator, symbol) = (*operator, *symbol);
+                    self.position += 1;
+                    let value = match self.peek() {
+                        Some((_, Token::Word(value, _))) => value.clone(),
+                        _ => {
+                            return Err(format!(
+                                "Expected a value after '{}' at column {}",
+                                symbol, operator_column
+                            ))
+                        }
+                    };
+                    self.position += 1;
+                    return compare(&word, operator, &value, self.now)
+                        .map(Expr::Filter)
+                        .map_err(|error| format!("{} (column {})", error, column));
+                }
+                if quoted {
+                    return Ok(Expr::Filter(Filter::Text(word)));
+                }
+                parse_filter(&word, self.now)
+                    .map(Expr::Filter)
+                    .map_err(|error| format!("{} (column {})", error, column))
+            }
+        }
+    }
+}
+
+fn parse_filter(term: &str, now: i64) -> Result<Filter, String> {
+    if term == "overdue" {
+        return Ok(Filter::Overdue);
+    }
+    if let Some((name, value)) = term.split_once(':') {
+        let value = value.to_string();
+        return match name {
+            "tag" => Ok(Filter::Tag(value)),
+            "assignee" => Ok(Filter::Assignee(value)),
+            "created-by" => Ok(Filter::Creator(value)),
+            "location" => Ok(Filter::Location(value)),
+            "project" => Ok(Filter::Project(value)),
+            "has" => Ok(Filter::Has(value)),
+            // The middle of a date, so it is within the planned day whatever its timezone
+            "planned" => match value.as_str() {
+                "today" => Ok(Filter::Planned(now)),
+                "tomorrow" => Ok(Filter::Planned(now + 24 * 60 * 60)),
+                _ => parse_when(&value, now).map(|day| Filter::Planned(day + 12 * 60 * 60)),
+            },
+            _ => Err(format!("Unknown filter: '{}'", term)),
+        };
+    }
+    Ok(Filter::Text(term.to_string()))
+}
+
+/// Parses a comparison such as `priority >= 4`, `priority >= high` with a priority named
+/// `high`, or `due < tomorrow`.
+fn compare(name: &str, operator: Operator, value: &str, now: i64) -> Result<Filter, String> {
+    let key = SortKey::parse(name)
+        .filter(|key| *key != SortKey::Task)
+        .ok_or_else(|| format!("Unknown field '{}' in comparison", name))?;
+    let value = match key {
+        SortKey::Id | SortKey::Priority => match value.parse() {
+            Ok(value) => value,
+            Err(_) if key == SortKey::Priority => priorities::scale()
+                .parse(value)
+                .map_err(|_| format!("Invalid number '{}' for {}", value, name))?
+                as i64,
+            Err(_) => return Err(format!("Invalid number '{}' for {}", value, name)),
+        },
+        _ => parse_when(value, now)?,
+    };
+    Ok(Filter::Compare(key, operator, value))
+}
+
+/// Parses `now`, `tomorrow` or anything accepted by `dates::parse_since`.
+fn parse_when(input: &str, now: i64) -> Result<i64, String> {
+    match input {
+        "now" => Ok(now),
+        "tomorrow" => dates::parse_since("today", now).map(|today| today + 24 * 60 * 60),
+        _ => dates::parse_since(input, now),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fuzzy_score, Query, SortOrder};
+    use crate::TodoList;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_query() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Write report", 2);
+        todo_list.add_todo("Review report", 4);
+        todo_list.add_todo("Buy milk", 5);
+        let now = todo_list.todos[0].created;
+        todo_list.todos[0].tags = vec!["work".to_string()];
+        todo_list.todos[1].tags = vec!["work".to_string()];
+        todo_list.todos[1].assignee = Some("bob".to_string());
+        todo_list.todos[2].due = Some(now - 60);
+
+        let ids = |query: &str| -> Vec<u64> {
+            Query::parse(query, now)
+                .unwrap()
+                .apply(todo_list.todos.clone(), now)
+                .iter()
+                .map(|todo| todo["id"].as_u64().unwrap())
+                .collect()
+        };
+        assert_eq!(ids(""), vec![1, 2, 3]);
+        assert_eq!(ids("REPORT"), vec![1, 2]);
+        assert_eq!(ids("tag:work !has:assignee"), vec![1]);
+        assert_eq!(ids("priority>=4 sort:-priority"), vec![3, 2]);
+        assert_eq!(ids("priority!=4"), vec![1, 3]);
+        assert_eq!(ids("overdue"), vec![3]);
+        assert_eq!(ids("due<tomorrow"), vec![3]);
+        assert_eq!(ids("sort:task limit:2"), vec![3, 2]);
+        assert_eq!(ids("sort:due"), vec![3, 1, 2]);
+
+        let projected = Query::parse("assignee:Bob fields:id,task", now)
+            .unwrap()
+            .apply(todo_list.todos.clone(), now);
+        assert_eq!(
+            projected,
+            vec![serde_json::json!({ "id": 2, "task": "Review report" })]
+        );
+
+        assert_eq!(ids("(priority >= 4 and tag:work) or overdue"), vec![2, 3]);
+        assert_eq!(ids("priority>=4 and tag:work or overdue"), vec![2, 3]);
+        assert_eq!(ids("tag:work and (priority = 2 or overdue)"), vec![1]);
+        assert_eq!(ids("not tag:work or NOT has:due"), vec![1, 2, 3]);
+        assert_eq!(ids("!(tag:work or overdue)"), Vec::<u64>::new());
+        assert_eq!(ids("!tag:work priority > 2"), vec![3]);
+        assert_eq!(ids("\"buy milk\" or \"write\""), vec![1, 3]);
+        assert_eq!(ids("(report) sort:-id limit:1"), vec![2]);
+        assert_eq!(
+            ids("created <= now and due < \"1 minute ago\""),
+            Vec::<u64>::new()
+        );
+
+        assert!(Query::parse("sort:colour", now).is_err());
+        assert!(Query::parse("colour:red", now).is_err());
+        assert!(Query::parse("priority>high", now).is_err());
+        assert!(Query::parse("task>a", now).is_err());
+    }
+    #[test]
+    fn test_fuzzy() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Pay rent", 5);
+        todo_list.add_todo("Send invoice to ACME", 3);
+        todo_list.add_todo("Invoice review", 2);
+        todo_list.add_todo("Read docs", 1);
+        let now = todo_list.todos[0].created;
+        let ids = |query: &str| -> Vec<u64> {
+            Query::parse(query, now)
+                .unwrap()
+                .fuzzy()
+                .select(todo_list.todos.clone(), now)
+                .iter()
+                .map(|todo| todo.id)
+                .collect()
+        };
+        assert_eq!(fuzzy_score("invoice", "Send invoice"), 1.0);
+        assert!(fuzzy_score("invioce", "Send invoice") >= 0.45);
+        assert!(fuzzy_score("rent", "Read docs") < 0.45);
+        assert_eq!(ids("invioce"), vec![2, 3]);
+        // Better matches rank first, unless the query sorts
+        assert_eq!(ids("invioce acme"), vec![2]);
+        assert_eq!(ids("invioce or revew"), vec![3, 2]);
+        assert_eq!(ids("invioce sort:-id"), vec![3, 2]);
+        assert_eq!(ids("rnet"), Vec::<u64>::new());
+        assert!(Query::parse("invioce", now)
+            .unwrap()
+            .select(todo_list.todos.clone(), now)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_ignore_accents() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Réserver le Café", 3);
+        todo_list.add_todo("Cafe\u{301} au lait", 3);
+        todo_list.add_todo("Buy coffee", 3);
+        todo_list.add_todo("Straße fegen", 3);
+        let now = todo_list.todos[0].created;
+        todo_list.todos[2].assignee = Some("José".to_string());
+        todo_list.todos[2].tags = vec!["Küche".to_string()];
+        let ids = |query: &str, ignore_accents: bool| -> Vec<u64> {
+            let query = Query::parse(query, now).unwrap();
+            let query = if ignore_accents {
+                query.ignore_accents()
+            } else {
+                query
+            };
+            query
+                .select(todo_list.todos.clone(), now)
+                .iter()
+                .map(|todo| todo.id)
+                .collect()
+        };
+        // However the accent was typed, in any case
+        assert_eq!(ids("CAFÉ", false), vec![1, 2]);
+        assert_eq!(ids("cafe", false), Vec::<u64>::new());
+        assert_eq!(ids("cafe", true), vec![1, 2]);
+        assert_eq!(ids("reserver", true), vec![1]);
+        assert_eq!(ids("STRASSE", false), vec![4]);
+        assert_eq!(ids("assignee:josé tag:KÜCHE", false), vec![3]);
+        assert_eq!(ids("assignee:jose tag:kuche", true), vec![3]);
+        assert_eq!(ids("sort:task", false), vec![3, 2, 1, 4]);
+        let fuzzy = Query::parse("reserer", now)
+            .unwrap()
+            .fuzzy()
+            .ignore_accents();
+        let found = fuzzy.select(todo_list.todos.clone(), now);
+        assert_eq!(
+            found.iter().map(|todo| todo.id).collect::<Vec<_>>(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn test_inherit_tags() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Write the changelog", 3);
+        todo_list.add_todo("Tag the release", 3);
+        todo_list.add_todo("Water the plants", 3);
+        let now = todo_list.todos[0].created;
+        todo_list.todos[0].project = Some("release-x".to_string());
+        todo_list.todos[1].tags = vec!["releaseX".to_string()];
+        let inherited = BTreeMap::from([(
+            "release-x".to_string(),
+            vec!["releaseX".to_string(), "work".to_string()],
+        )]);
+        let ids = |query: Query| -> Vec<u64> {
+            query
+                .select(todo_list.todos.clone(), now)
+                .iter()
+                .map(|todo| todo.id)
+                .collect()
+        };
+        let query = || Query::parse("tag:releaseX", now).unwrap();
+        assert_eq!(ids(query()), vec![2]);
+        assert_eq!(ids(query().inherit_tags(inherited.clone())), vec![1, 2]);
+        let query = Query::parse("not tag:work", now).unwrap();
+        assert_eq!(ids(query.inherit_tags(inherited)), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_sort_order() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Write report", 2);
+        todo_list.add_todo("Review report", 4);
+        todo_list.add_todo("Buy milk", 4);
+        let now = todo_list.todos[0].created;
+        todo_list.todos[0].due = Some(now);
+        todo_list.todos[2].due = Some(now);
+
+        let ids = |order: &str| -> Vec<u64> {
+            let mut todos = todo_list.todos.clone();
+            SortOrder::parse(order).unwrap().sort(&mut todos);
+            todos.iter().map(|todo| todo.id).collect()
+        };
+        assert_eq!(ids("due asc, priority desc, created asc"), vec![3, 1, 2]);
+        assert_eq!(ids("priority DESC,task"), vec![3, 2, 1]);
+        assert_eq!(ids("id desc"), vec![3, 2, 1]);
+
+        assert_eq!(
+            SortOrder::parse("due up").unwrap_err(),
+            "Expected 'asc' or 'desc' after 'due', found 'up'"
+        );
+        assert_eq!(
+            SortOrder::parse("colour").unwrap_err(),
+            "Unknown sort key: 'colour'"
+        );
+        assert!(SortOrder::parse("due,").is_err());
+        assert!(SortOrder::parse("due asc desc").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |query: &str| Query::parse(query, 0).unwrap_err();
+        assert_eq!(
+            error("(priority >= 4 and tag:work"),
+            "Missing ')' for the '(' at column 1"
+        );
+        assert_eq!(error("tag:work)"), "Unmatched ')' at column 9");
+        assert_eq!(
+            error("tag:work or"),
+            "Expected a filter after 'or' at column 12"
+        );
+        assert_eq!(error("tag:a and ()"), "Empty parentheses at column 11");
+        assert_eq!(
+            error("or tag:a"),
+            "Expected a filter before 'or' at column 1"
+        );
+        assert_eq!(error(">= 4"), "Expected a field before '>=' at column 1");
+        assert_eq!(
+            error("priority >="),
+            "Expected a value after '>=' at column 10"
+        );
+        assert_eq!(
+            error("priority >= high"),
+            "Invalid number 'high' for priority (column 1)"
+        );
+        assert_eq!(
+            error("size > 3"),
+            "Unknown field 'size' in comparison (column 1)"
+        );
+        assert_eq!(error("\"buy milk"), "Unterminated quote at column 1");
+        assert_eq!(
+            error("(tag:a sort:id)"),
+            "'sort:id' applies to the whole query and can't be inside parentheses (column 8)"
+        );
+    }
+
+    #[test]
+    fn test_projects() {
+        let projects = |query: &str| {
+            Query::parse(query, 0)
+                .unwrap()
+                .projects()
+                .map(|projects| projects.into_iter().collect::<Vec<_>>())
+        };
+        assert_eq!(
+            projects("project:work tag:urgent"),
+            Some(vec!["work".to_string()])
+        );
+        assert_eq!(
+            projects("project:work or (project:home and overdue)"),
+            Some(vec!["home".to_string(), "work".to_string()])
+        );
+        assert_eq!(projects("project:work and project:home"), Some(vec![]));
+        assert_eq!(projects("project:work or tag:urgent"), None);
+        assert_eq!(projects("!project:work"), None);
+        assert_eq!(projects("tag:urgent"), None);
+        assert_eq!(projects(""), None);
+    }
+}
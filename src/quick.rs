@@ -0,0 +1,190 @@
+//! Parsing of free-form quick-capture strings such as
+//! `"Pay rent tomorrow 9am p:5 +finance @home"`.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+
+use crate::priorities;
+
+/// The fields extracted from a quick-capture string.
+#[derive(Debug, PartialEq)]
+pub struct QuickTask {
+    /// The remaining words, forming the task description.
+    pub task: String,
+    /// The priority given as `p:<n>`, if any.
+    pub priority: Option<u64>,
+    /// The due date-time (in Utc) given by date and time words, if any.
+    pub due: Option<NaiveDateTime>,
+    /// The tags given as `+tag`.
+    pub tags: Vec<String>,
+    /// The context given as `@context`, if any.
+    pub context: Option<String>,
+}
+
+/// Splits a quick-capture string into its task description and structured tokens.
+///
+/// Recognised tokens are `p:<priority>` (a number or a priority's name, e.g. `p:high`),
+/// `+tag`, `@context`, the dates `today`, `tomorrow`, weekday names and `YYYY-MM-DD`, and the
+/// times `9am`, `5:30pm`, `17:00` and `noon`.
+/// A time without a date means today; a weekday means its next occurrence after today.
+///
+/// # Example
+///
+/// ```
+/// let quick = parse_quick("Pay rent tomorrow 9am p:5 +finance @home", Utc::now().naive_utc())?;
+/// ```
+pub fn parse_quick(input: &str, now: NaiveDateTime) -> Result<QuickTask, String> {
+    let mut words = Vec::new();
+    let mut priority = None;
+    let mut date = None;
+    let mut time = None;
+    let mut tags = Vec::new();
+    let mut context = None;
+
+    for word in input.split_whitespace() {
+        if let Some(value) = word.strip_prefix("p:") {
+            priority = Some(priorities::scale().parse(value)?);
+        } else if let Some(tag) = word.strip_prefix('+').filter(|tag| !tag.is_empty()) {
+            if !tags.iter().any(|existing| existing == tag) {
+                tags.push(tag.to_string());
+            }
+        } else if let Some(name) = word.strip_prefix('@').filter(|name| !name.is_empty()) {
+            context = Some(name.to_string());
+        } else if let Some(day) = parse_date_word(word, now.date()) {
+            date = Some(day);
+        } else if let Some(clock) = parse_time_word(word) {
+            time = Some(clock);
+        } else {
+            words.push(word);
+        }
+    }
+
+    if words.is_empty() {
+        return Err("The task description is empty".to_string());
+    }
+    let due = match (date, time) {
+        (None, None) => None,
+        (date, time) => Some(
+            date.unwrap_or(now.date())
+                .and_time(time.unwrap_or(NaiveTime::MIN)),
+        ),
+    };
+    Ok(QuickTask {
+        task: words.join(" "),
+        priority,
+        due,
+        tags,
+        context,
+    })
+}
+
+/// Parses `today`, `tomorrow`, a weekday name or an ISO date relative to `today`.
+pub fn parse_date_word(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let lower = word.to_lowercase();
+    match lower.as_str() {
+        "today" => return Some(today),
+        "tomorrow" => return today.succ_opt(),
+        _ => {}
+    }
+    if let Some(weekday) = parse_weekday(&lower) {
+        let ahead = (7 + weekday.num_days_from_monday() as i64
+            - today.weekday().num_days_from_monday() as i64
+            - 1)
+            % 7
+            + 1;
+        return Some(today + Duration::days(ahead));
+    }
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    let weekdays = [
+        ("mon", "monday", Weekday::Mon),
+        ("tue", "tuesday", Weekday::Tue),
+        ("wed", "wednesday", Weekday::Wed),
+        ("thu", "thursday", Weekday::Thu),
+        ("fri", "friday", Weekday::Fri),
+        ("sat", "saturday", Weekday::Sat),
+        ("sun", "sunday", Weekday::Sun),
+    ];
+    weekdays
+        .iter()
+        .find(|(short, long, _)| word == *short || word == *long)
+        .map(|(_, _, weekday)| *weekday)
+}
+
+/// Parses `9am`, `5:30pm`, `17:00` or `noon`.
+pub fn parse_time_word(word: &str) -> Option<NaiveTime> {
+    let lower = word.to_lowercase();
+    if lower == "noon" {
+        return NaiveTime::from_hms_opt(12, 0, 0);
+    }
+    let (clock, offset) = if let Some(clock) = lower.strip_suffix("am") {
+        (clock, Some(0))
+    } else if let Some(clock) = lower.strip_suffix("pm") {
+        (clock, Some(12))
+    } else {
+        (lower.as_str(), None)
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) if minute.len() == 2 => (hour, minute),
+        Some(_) => return None,
+        None if offset.is_some() => (clock, "00"),
+        None => return None,
+    };
+    let mut hour: u32 = hour.parse().ok()?;
+    let minute: u32 = minute.parse().ok()?;
+    if let Some(offset) = offset {
+        if !(1..=12).contains(&hour) {
+            return None;
+        }
+        hour = hour % 12 + offset;
+    }
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_quick;
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_quick() {
+        // 2024-06-05 is a Wednesday.
+        let now = at(2024, 6, 5, 14, 0);
+        let quick = parse_quick("Pay rent tomorrow 9am p:5 +finance @home", now).unwrap();
+        assert_eq!(quick.task, "Pay rent");
+        assert_eq!(quick.priority, Some(5));
+        assert_eq!(quick.due, Some(at(2024, 6, 6, 9, 0)));
+        assert_eq!(quick.tags, vec!["finance"]);
+        assert_eq!(quick.context.as_deref(), Some("home"));
+
+        let quick = parse_quick("Call mum fri 5:30pm", now).unwrap();
+        assert_eq!(quick.task, "Call mum");
+        assert_eq!(quick.due, Some(at(2024, 6, 7, 17, 30)));
+        assert_eq!(quick.priority, None);
+
+        let quick = parse_quick("Standup wednesday", now).unwrap();
+        assert_eq!(quick.due, Some(at(2024, 6, 12, 0, 0)));
+
+        let quick = parse_quick("Lunch noon +food +food", now).unwrap();
+        assert_eq!(quick.due, Some(at(2024, 6, 5, 12, 0)));
+        assert_eq!(quick.tags, vec!["food"]);
+
+        let quick = parse_quick("Renew passport 2024-09-01 12am", now).unwrap();
+        assert_eq!(quick.due, Some(at(2024, 9, 1, 0, 0)));
+
+        let quick = parse_quick("Read chapter 12 of the book", now).unwrap();
+        assert_eq!(quick.task, "Read chapter 12 of the book");
+        assert_eq!(quick.due, None);
+
+        assert!(parse_quick("Pay rent p:9", now).is_err());
+        assert!(parse_quick("tomorrow +finance", now).is_err());
+    }
+}
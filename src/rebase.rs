@@ -0,0 +1,200 @@
+//! Saving over changes made by another process: what the list file held is recorded
+//! when it is loaded, and if the file has changed by the time the list is saved, the
+//! changes the command made are applied to the list now in the file instead of
+//! overwriting it, as git rebases a branch.
+//!
+//! Todo items are matched by their hash, since IDs shift when items are removed. A change
+//! to an item the other process changed or removed too cannot be applied, and nothing is
+//! saved then.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+use crate::diff::changed_fields;
+use crate::{ids, Todo, TodoList};
+
+/// The list files as they were loaded or last saved, by path.
+static LOADED: Mutex<BTreeMap<PathBuf, String>> = Mutex::new(BTreeMap::new());
+
+/// Records that the list file at `path` held `contents` when it was loaded or saved.
+pub fn record(path: &Path, contents: &str) {
+    let mut loaded = LOADED.lock().unwrap_or_else(|error| error.into_inner());
+    loaded.insert(path.to_path_buf(), contents.to_string());
+}
+
+/// Returns what the list file at `path` held when it was last loaded or saved, if it was.
+pub fn loaded(path: &Path) -> Option<String> {
+    let loaded = LOADED.lock().unwrap_or_else(|error| error.into_inner());
+    loaded.get(path).cloned()
+}
+
+/// Applies the changes that turned `base` into `ours` to `theirs`, another version of
+/// `base`, and returns the result. Items added by `ours` come after those of `theirs`.
+pub fn rebase(base: &TodoList, ours: &TodoList, theirs: &TodoList) -> Result<TodoList, String> {
+    let find = |todos: &[Todo], hash: &str| -> Option<usize> {
+        todos.iter().position(|todo| todo.hash == hash)
+    };
+    let same = |a: &Todo, b: &Todo| changed_fields(&to_value(a), &to_value(b)).is_empty();
+    let describe = |todo: &Todo| format!("Task {} ({})", todo.id, todo.task);
+
+    // Dependencies by hash, so that they survive the renumbering
+    let mut todos: Vec<(Todo, Vec<String>)> = theirs
+        .todos
+        .iter()
+        .map(|todo| (todo.clone(), depends_by_hash(todo, theirs)))
+        .collect();
+    for old in &base.todos {
+        let Some(index) = todos.iter().position(|(todo, _)| todo.hash == old.hash) else {
+            continue;
+        };
+        let current = &todos[index].0;
+        match find(&ours.todos, &old.hash).map(|index| &ours.todos[index]) {
+            Some(new) if same(old, new) || same(current, new) => {}
+            Some(new) if same(old, current) => {
+                todos[index] = (new.clone(), depends_by_hash(new, ours));
+            }
+            Some(_) => {
+                return Err(format!(
+                    "{} was changed by another command at the same time",
+                    describe(current)
+                ))
+            }
+            None if same(old, current) => {
+                todos.remove(index);
+            }
+            None => {
+                return Err(format!(
+                    "{} was changed by another command at the same time, so it was not removed",
+                    describe(current)
+                ))
+            }
+        }
+    }
+    for old in &base.todos {
+        let removed = !todos.iter().any(|(todo, _)| todo.hash == old.hash);
+        if let Some(new) = find(&ours.todos, &old.hash).map(|index| &ours.todos[index]) {
+            if removed && !same(old, new) {
+                return Err(format!(
+                    "{} was removed by another command at the same time",
+                    describe(new)
+                ));
+            }
+        }
+    }
+    for new in &ours.todos {
+        if find(&base.todos, &new.hash).is_some() {
+            continue;
+        }
+        let mut added = new.clone();
+        if todos.iter().any(|(todo, _)| todo.hash == added.hash) {
+            let existing: Vec<Todo> = todos.iter().map(|(todo, _)| todo.clone()).collect();
+            let seed = format!("{}:{}:{}", added.created, added.id, added.task);
+            added.hash = ids::generate(&existing, &seed);
+        }
+        todos.push((added, depends_by_hash(new, ours)));
+    }
+
+    let hashes: Vec<String> = todos.iter().map(|(todo, _)| todo.hash.clone()).collect();
+    let mut rebased = theirs.clone();
+    rebased.todos = todos
+        .into_iter()
+        .zip(1..)
+        .map(|((mut todo, depends), id)| {
+            todo.id = id;
+            todo.depends = depends
+                .iter()
+                .filter_map(|hash| hashes.iter().position(|other| other == hash))
+                .map(|index| index as u64 + 1)
+                .collect();
+            todo.depends.sort_unstable();
+            todo
+        })
+        .collect();
+    Ok(rebased)
+}
+
+/// Returns the hashes of the items of `todo_list` that `todo` depends on.
+fn depends_by_hash(todo: &Todo, todo_list: &TodoList) -> Vec<String> {
+    todo.depends
+        .iter()
+        .filter_map(|id| todo_list.todos.iter().find(|other| other.id == *id))
+        .map(|other| other.hash.clone())
+        .collect()
+}
+
+fn to_value(todo: &Todo) -> Value {
+    serde_json::to_value(todo).expect("Unable to serialize todo")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{loaded, rebase, record};
+    use crate::TodoList;
+    use std::path::Path;
+
+    #[test]
+    fn test_rebase() {
+        let mut base = TodoList::new();
+        for task in ["Buy milk", "Call mum", "Fix the bike"] {
+            base.add_todo(task, 3);
+        }
+        base.assign_hashes();
+
+        // This command edits 3 and removes 1, while another adds a task and removes 2
+        let mut ours = base.clone();
+        ours.todos[2].priority = 5;
+        ours.add_dependencies(3, &[2]);
+        ours.remove_todo(1);
+        let mut theirs = base.clone();
+        theirs.add_todo("Water the plants", 2);
+        theirs.assign_hashes();
+        theirs.remove_todo(2);
+
+        let rebased = rebase(&base, &ours, &theirs).unwrap();
+        let tasks: Vec<(u64, &str, u64)> = rebased
+            .todos
+            .iter()
+            .map(|todo| (todo.id, todo.task.as_str(), todo.priority))
+            .collect();
+        assert_eq!(tasks, [(1, "Fix the bike", 5), (2, "Water the plants", 2)]);
+        // The dependency on the task removed by the other command is dropped
+        assert!(rebased.todos[0].depends.is_empty());
+
+        // Both adding a task: the other's comes first
+        let mut ours = base.clone();
+        ours.add_todo("Book a table", 4);
+        ours.assign_hashes();
+        let rebased = rebase(&base, &ours, &theirs).unwrap();
+        assert_eq!(rebased.todos[3].task, "Book a table");
+        assert_eq!(rebased.todos[3].id, 4);
+
+        // Changing a task the other command changed or removed is refused
+        let mut ours = base.clone();
+        ours.todos[1].task = "Call dad".to_string();
+        assert_eq!(
+            rebase(&base, &ours, &theirs).unwrap_err(),
+            "Task 2 (Call dad) was removed by another command at the same time"
+        );
+        let mut theirs = base.clone();
+        theirs.todos[1].priority = 1;
+        assert!(rebase(&base, &ours, &theirs).is_err());
+        ours.remove_todo(2);
+        assert_eq!(
+            rebase(&base, &ours, &theirs).unwrap_err(),
+            "Task 2 (Call mum) was changed by another command at the same time, so it was not \
+             removed"
+        );
+        // The same change made by both is no conflict
+        let mut ours = base.clone();
+        ours.todos[1].priority = 1;
+        assert_eq!(rebase(&base, &ours, &theirs).unwrap().todos[1].priority, 1);
+
+        let path = Path::new("rebase-test.json");
+        record(path, "[]");
+        assert_eq!(loaded(path), Some("[]".to_string()));
+        assert_eq!(loaded(Path::new("other.json")), None);
+    }
+}
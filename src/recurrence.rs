@@ -0,0 +1,587 @@
+//! Recurrence rules modelled on the RFC 5545 `RRULE` property.
+//!
+//! Occurrences are computed on wall-clock (naive) date-times, so a task that
+//! repeats at 09:00 stays at 09:00 regardless of daylight saving changes.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Weekday};
+
+use crate::dates::Zone;
+
+/// How far past the start date the expansion is allowed to search before giving up.
+const MAX_YEARS: i32 = 200;
+
+/// The base frequency of a recurrence rule (`FREQ=`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// A weekday, optionally qualified by its position within the month or year (`2TU`, `-1FR`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByDay {
+    /// The ordinal of the weekday, counted from the end of the period when negative.
+    pub ordinal: Option<i32>,
+    /// The day of the week.
+    pub weekday: Weekday,
+}
+
+/// A parsed recurrence rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    /// The base frequency.
+    pub freq: Frequency,
+    /// The number of frequency periods between repetitions.
+    pub interval: u32,
+    /// The total number of occurrences, if limited.
+    pub count: Option<u32>,
+    /// The last date-time an occurrence may fall on, if limited.
+    pub until: Option<NaiveDateTime>,
+    /// Weekday filters (`BYDAY=`).
+    pub by_day: Vec<ByDay>,
+    /// Day-of-month filters (`BYMONTHDAY=`), negative values count from the month end.
+    pub by_month_day: Vec<i32>,
+    /// Month filters (`BYMONTH=`).
+    pub by_month: Vec<u32>,
+    /// Positions kept from each period's expanded set (`BYSETPOS=`).
+    pub by_set_pos: Vec<i32>,
+}
+
+impl Rule {
+    /// Parses a rule such as `FREQ=MONTHLY;BYDAY=2TU;COUNT=6`.
+    ///
+    /// An optional leading `RRULE:` is accepted, and keys and values are case-insensitive.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let rule = Rule::parse("FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1").unwrap();
+    /// ```
+    pub fn parse(input: &str) -> Result<Rule, String> {
+        let input = input.trim();
+        let body = match input.get(..6) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("RRULE:") => &input[6..],
+            _ => input,
+        };
+
+        let mut freq = None;
+        let mut rule = Rule {
+            freq: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+            by_set_pos: Vec::new(),
+        };
+
+        for part in body.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Expected KEY=VALUE, found '{}'", part))?;
+            let value = value.to_ascii_uppercase();
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    freq = Some(match value.as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(format!("Unsupported FREQ: {}", value)),
+                    })
+                }
+                "INTERVAL" => {
+                    rule.interval = parse_number(&value, "INTERVAL")?;
+                    if rule.interval == 0 {
+                        return Err("INTERVAL must be at least 1".to_string());
+                    }
+                }
+                "COUNT" => rule.count = Some(parse_number(&value, "COUNT")?),
+                "UNTIL" => rule.until = Some(parse_until(&value)?),
+                "BYDAY" => {
+                    rule.by_day = split_list(&value)
+                        .map(parse_by_day)
+                        .collect::<Result<_, _>>()?
+                }
+                "BYMONTHDAY" => {
+                    rule.by_month_day = parse_signed_list(&value, "BYMONTHDAY", 31)?;
+                }
+                "BYMONTH" => {
+                    rule.by_month = split_list(&value)
+                        .map(|month| match parse_number::<u32>(month, "BYMONTH")? {
+                            month @ 1..=12 => Ok(month),
+                            month => Err(format!("BYMONTH out of range: {}", month)),
+                        })
+                        .collect::<Result<_, _>>()?
+                }
+                "BYSETPOS" => rule.by_set_pos = parse_signed_list(&value, "BYSETPOS", 366)?,
+                "WKST" if value == "MO" => {}
+                other => return Err(format!("Unsupported rule part: {}", other)),
+            }
+        }
+
+        if rule.count.is_some() && rule.until.is_some() {
+            return Err("COUNT and UNTIL cannot both be set".to_string());
+        }
+        rule.freq = freq.ok_or_else(|| "Missing FREQ".to_string())?;
+        Ok(rule)
+    }
+
+    /// Iterates over every occurrence of the series starting at the timestamp `start` as
+    /// timestamps, computed on the wall clock of `zone` so that an occurrence keeps its time
+    /// of day when daylight saving begins or ends.
+    pub fn timestamps(&self, start: i64, zone: Zone) -> impl Iterator<Item = i64> + '_ {
+        self.occurrences(zone.local(start))
+            .filter_map(move |occurrence| zone.timestamp_or_later(occurrence))
+    }
+
+    /// Iterates over every occurrence of the series starting at `start`, in order.
+    pub fn occurrences(&self, start: NaiveDateTime) -> impl Iterator<Item = NaiveDateTime> + '_ {
+        let limit_year = start.year() + MAX_YEARS;
+        (0u32..)
+            .map_while(move |period| {
+                let dates = self.expand_period(start.date(), period)?;
+                Some(
+                    dates
+                        .into_iter()
+                        .map(move |date| date.and_time(start.time())),
+                )
+            })
+            .flatten()
+            .skip_while(move |occurrence| *occurrence < start)
+            .take_while(move |occurrence| {
+                occurrence.year() <= limit_year
+                    && self.until.is_none_or(|until| *occurrence <= until)
+            })
+            .take(self.count.map_or(usize::MAX, |count| count as usize))
+    }
+
+    /// Expands the `period`-th frequency period after `start` into its matching dates.
+    ///
+    /// Returns `None` once the period lies beyond the search horizon.
+    fn expand_period(&self, start: NaiveDate, period: u32) -> Option<Vec<NaiveDate>> {
+        let step = i64::from(period) * i64::from(self.interval);
+        let mut dates = match self.freq {
+            Frequency::Daily => {
+                let day = start.checked_add_signed(Duration::days(step))?;
+                if day.year() > start.year() + MAX_YEARS {
+                    return None;
+                }
+                let keep = self.month_matches(day.month())
+                    && (self.by_month_day.is_empty() || month_day_matches(day, &self.by_month_day))
+                    && (self.by_day.is_empty()
+                        || self.by_day.iter().any(|by| by.weekday == day.weekday()));
+                if keep {
+                    vec![day]
+                } else {
+                    Vec::new()
+                }
+            }
+            Frequency::Weekly => {
+                let monday = start - Duration::days(start.weekday().num_days_from_monday().into());
+                let monday = monday.checked_add_signed(Duration::weeks(step))?;
+                if monday.year() > start.year() + MAX_YEARS {
+                    return None;
+                }
+                (0..7)
+                    .map(|offset| monday + Duration::days(offset))
+                    .filter(|day| {
+                        let weekday_matches = if self.by_day.is_empty() {
+                            day.weekday() == start.weekday()
+                        } else {
+                            self.by_day.iter().any(|by| by.weekday == day.weekday())
+                        };
+                        weekday_matches && self.month_matches(day.month())
+                    })
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let months = i64::from(start.month0()) + step;
+                let year = start.year() + i32::try_from(months / 12).ok()?;
+                let month = (months % 12) as u32 + 1;
+                if year > start.year() + MAX_YEARS {
+                    return None;
+                }
+                if self.month_matches(month) {
+                    self.expand_month(start, year, month)
+                } else {
+                    Vec::new()
+                }
+            }
+            Frequency::Yearly => {
+                let year = start.year() + i32::try_from(step).ok()?;
+                if year > start.year() + MAX_YEARS {
+                    return None;
+                }
+                if !self.by_month.is_empty() || !self.by_month_day.is_empty() {
+                    let mut months = if self.by_month.is_empty() {
+                        (1..=12).collect()
+                    } else {
+                        self.by_month.clone()
+                    };
+                    months.sort_unstable();
+                    months.dedup();
+                    months
+                        .into_iter()
+                        .flat_map(|month| self.expand_month(start, year, month))
+                        .collect()
+                } else if !self.by_day.is_empty() {
+                    let days = days_between(
+                        NaiveDate::from_ymd_opt(year, 1, 1)?,
+                        NaiveDate::from_ymd_opt(year + 1, 1, 1)?,
+                    );
+                    filter_by_day(&days, &self.by_day)
+                } else {
+                    NaiveDate::from_ymd_opt(year, start.month(), start.day())
+                        .into_iter()
+                        .collect()
+                }
+            }
+        };
+        if !self.by_set_pos.is_empty() {
+            dates = select_positions(&dates, &self.by_set_pos);
+        }
+        Some(dates)
+    }
+
+    /// Expands a single month according to the day-of-month and weekday filters.
+    fn expand_month(&self, start: NaiveDate, year: i32, month: u32) -> Vec<NaiveDate> {
+        let first = match NaiveDate::from_ymd_opt(year, month, 1) {
+            Some(first) => first,
+            None => return Vec::new(),
+        };
+        let next = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(year, month + 1, 1)
+        };
+        let days = match next {
+            Some(next) => days_between(first, next),
+            None => return Vec::new(),
+        };
+
+        let mut dates: Vec<NaiveDate> = if !self.by_month_day.is_empty() {
+            days.iter()
+                .copied()
+                .filter(|day| month_day_matches(*day, &self.by_month_day))
+                .collect()
+        } else if self.by_day.is_empty() {
+            NaiveDate::from_ymd_opt(year, month, start.day())
+                .into_iter()
+                .collect()
+        } else {
+            days.clone()
+        };
+        if !self.by_day.is_empty() {
+            let allowed = filter_by_day(&days, &self.by_day);
+            dates.retain(|day| allowed.contains(day));
+        }
+        dates
+    }
+
+    fn month_matches(&self, month: u32) -> bool {
+        self.by_month.is_empty() || self.by_month.contains(&month)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, key: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid {} value: {}", key, value))
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+fn parse_signed_list(value: &str, key: &str, max: i32) -> Result<Vec<i32>, String> {
+    split_list(value)
+        .map(|item| {
+            let number: i32 = parse_number(item, key)?;
+            if number == 0 || number.abs() > max {
+                Err(format!("{} out of range: {}", key, number))
+            } else {
+                Ok(number)
+            }
+        })
+        .collect()
+}
+
+fn parse_by_day(value: &str) -> Result<ByDay, String> {
+    if value.len() < 2 || !value.is_char_boundary(value.len() - 2) {
+        return Err(format!("Invalid BYDAY value: {}", value));
+    }
+    let (ordinal, day) = value.split_at(value.len() - 2);
+    let weekday = match day {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return Err(format!("Invalid BYDAY value: {}", value)),
+    };
+    let ordinal = if ordinal.is_empty() {
+        None
+    } else {
+        let ordinal = ordinal.strip_prefix('+').unwrap_or(ordinal);
+        match ordinal.parse::<i32>() {
+            Ok(number) if number != 0 && number.abs() <= 53 => Some(number),
+            _ => return Err(format!("Invalid BYDAY value: {}", value)),
+        }
+    };
+    Ok(ByDay { ordinal, weekday })
+}
+
+/// Parses an `UNTIL` value in either `YYYYMMDD` or `YYYYMMDDTHHMMSS[Z]` form.
+fn parse_until(value: &str) -> Result<NaiveDateTime, String> {
+    let value = value.trim_end_matches('Z');
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        return Ok(date_time);
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(23, 59, 59))
+        .ok_or_else(|| format!("Invalid UNTIL value: {}", value))
+}
+
+fn days_between(first: NaiveDate, end: NaiveDate) -> Vec<NaiveDate> {
+    first.iter_days().take_while(|day| *day < end).collect()
+}
+
+fn month_day_matches(day: NaiveDate, by_month_day: &[i32]) -> bool {
+    let length = days_in_month(day);
+    by_month_day.iter().any(|wanted| {
+        let wanted = if *wanted < 0 {
+            length + 1 + wanted
+        } else {
+            *wanted
+        };
+        wanted == day.day() as i32
+    })
+}
+
+fn days_in_month(day: NaiveDate) -> i32 {
+    (28..=31)
+        .rev()
+        .find(|length| day.with_day(*length as u32).is_some())
+        .unwrap_or(28)
+}
+
+/// Keeps the days of `period` matching any of the weekday rules, honouring ordinals
+/// relative to the whole period.
+fn filter_by_day(period: &[NaiveDate], by_day: &[ByDay]) -> Vec<NaiveDate> {
+    let mut matches: Vec<NaiveDate> = by_day
+        .iter()
+        .flat_map(|by| {
+            let same_weekday: Vec<NaiveDate> = period
+                .iter()
+                .copied()
+                .filter(|day| day.weekday() == by.weekday)
+                .collect();
+            match by.ordinal {
+                None => same_weekday,
+                Some(ordinal) => select_positions(&same_weekday, &[ordinal]),
+            }
+        })
+        .collect();
+    matches.sort_unstable();
+    matches.dedup();
+    matches
+}
+
+/// Selects 1-based positions from `dates`, with negative positions counting from the end.
+fn select_positions(dates: &[NaiveDate], positions: &[i32]) -> Vec<NaiveDate> {
+    let length = dates.len() as i32;
+    let mut selected: Vec<NaiveDate> = positions
+        .iter()
+        .filter_map(|position| {
+            let index = if *position > 0 {
+                position - 1
+            } else {
+                length + position
+            };
+            if (0..length).contains(&index) {
+                Some(dates[index as usize])
+            } else {
+                None
+            }
+        })
+        .collect();
+    selected.sort_unstable();
+    selected.dedup();
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ByDay, Frequency, Rule};
+    use crate::dates::Zone;
+    use crate::zoneinfo::TimeZone;
+    use chrono::{NaiveDate, NaiveDateTime, Weekday};
+
+    fn at(year: i32, month: u32, day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    /// Returns the first occurrence strictly after `after` for a series starting at `start`.
+    fn next_after(
+        rule: &Rule,
+        start: NaiveDateTime,
+        after: NaiveDateTime,
+    ) -> Option<NaiveDateTime> {
+        rule.occurrences(start)
+            .find(|occurrence| *occurrence > after)
+    }
+
+    fn first_n(rule: &str, start: NaiveDateTime, n: usize) -> Vec<NaiveDateTime> {
+        Rule::parse(rule)
+            .unwrap()
+            .occurrences(start)
+            .take(n)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let rule = Rule::parse("RRULE:freq=monthly;interval=2;byday=2TU,-1fr;count=4").unwrap();
+        assert_eq!(rule.freq, Frequency::Monthly);
+        assert_eq!(rule.interval, 2);
+        assert_eq!(rule.count, Some(4));
+        assert_eq!(
+            rule.by_day,
+            vec![
+                ByDay {
+                    ordinal: Some(2),
+                    weekday: Weekday::Tue
+                },
+                ByDay {
+                    ordinal: Some(-1),
+                    weekday: Weekday::Fri
+                },
+            ]
+        );
+
+        assert!(Rule::parse("INTERVAL=2").is_err());
+        assert!(Rule::parse("FREQ=HOURLY").is_err());
+        assert!(Rule::parse("FREQ=DAILY;INTERVAL=0").is_err());
+        assert!(Rule::parse("FREQ=DAILY;COUNT=2;UNTIL=20240101").is_err());
+        assert!(Rule::parse("FREQ=WEEKLY;BYDAY=XX").is_err());
+        assert!(Rule::parse("FREQ=MONTHLY;BYMONTHDAY=0").is_err());
+        assert!(Rule::parse("FREQ=YEARLY;BYMONTH=13").is_err());
+    }
+
+    #[test]
+    fn test_every_second_tuesday() {
+        let occurrences = first_n("FREQ=MONTHLY;BYDAY=2TU", at(2024, 1, 1, 9), 3);
+        assert_eq!(
+            occurrences,
+            vec![at(2024, 1, 9, 9), at(2024, 2, 13, 9), at(2024, 3, 12, 9)]
+        );
+    }
+
+    #[test]
+    fn test_last_weekday_of_month() {
+        let occurrences = first_n(
+            "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+            at(2024, 1, 1, 17),
+            3,
+        );
+        // March 2024 ends on a Sunday, so the last weekday is Friday the 29th.
+        assert_eq!(
+            occurrences,
+            vec![
+                at(2024, 1, 31, 17),
+                at(2024, 2, 29, 17),
+                at(2024, 3, 29, 17)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_and_until() {
+        let rule = Rule::parse("FREQ=WEEKLY;BYDAY=MO,WE;COUNT=3").unwrap();
+        let occurrences: Vec<_> = rule.occurrences(at(2024, 5, 1, 8)).collect();
+        assert_eq!(
+            occurrences,
+            vec![at(2024, 5, 1, 8), at(2024, 5, 6, 8), at(2024, 5, 8, 8)]
+        );
+        assert_eq!(
+            next_after(&rule, at(2024, 5, 1, 8), at(2024, 5, 8, 8)),
+            None
+        );
+
+        let rule = Rule::parse("FREQ=DAILY;INTERVAL=2;UNTIL=20240105T080000Z").unwrap();
+        let occurrences: Vec<_> = rule.occurrences(at(2024, 1, 1, 8)).collect();
+        assert_eq!(
+            occurrences,
+            vec![at(2024, 1, 1, 8), at(2024, 1, 3, 8), at(2024, 1, 5, 8)]
+        );
+    }
+
+    #[test]
+    fn test_month_day_edges() {
+        let occurrences = first_n("FREQ=MONTHLY;BYMONTHDAY=31", at(2024, 1, 31, 12), 3);
+        assert_eq!(
+            occurrences,
+            vec![
+                at(2024, 1, 31, 12),
+                at(2024, 3, 31, 12),
+                at(2024, 5, 31, 12)
+            ]
+        );
+
+        let occurrences = first_n("FREQ=MONTHLY;BYMONTHDAY=-1", at(2024, 1, 15, 12), 2);
+        assert_eq!(occurrences, vec![at(2024, 1, 31, 12), at(2024, 2, 29, 12)]);
+
+        let occurrences = first_n("FREQ=YEARLY", at(2024, 2, 29, 12), 2);
+        assert_eq!(occurrences, vec![at(2024, 2, 29, 12), at(2028, 2, 29, 12)]);
+
+        let rule = Rule::parse("FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30").unwrap();
+        assert_eq!(rule.occurrences(at(2024, 1, 1, 12)).next(), None);
+    }
+
+    #[test]
+    fn test_dst_boundaries() {
+        let zone = |rule: &str| {
+            let zone = TimeZone::from_rule(rule).unwrap();
+            Zone::Named(Box::leak(Box::new(zone)))
+        };
+        let new_york = zone("EST5EDT,M3.2.0,M11.1.0");
+        let berlin = zone("CET-1CEST,M3.5.0,M10.5.0/3");
+        let daily = Rule::parse("FREQ=DAILY").unwrap();
+
+        // New York springs forward on 2024-03-10, so 09:00 daily moves from 14:00 to 13:00
+        // Utc
+        let timestamps: Vec<i64> = daily.timestamps(1709992800, new_york).take(3).collect();
+        assert_eq!(timestamps, [1709992800, 1710075600, 1710162000]);
+        // and falls back on 2024-11-03, whose 01:00 comes twice but is one occurrence, the
+        // first, 25 hours before the next
+        let timestamps: Vec<i64> = daily.timestamps(1730523600, new_york).take(3).collect();
+        assert_eq!(timestamps, [1730523600, 1730610000, 1730700000]);
+        // 02:00 on Sundays skips 2024-03-31 in Berlin, so that occurrence is at 03:00
+        let sundays = Rule::parse("FREQ=WEEKLY;BYDAY=SU").unwrap();
+        let timestamps: Vec<i64> = sundays.timestamps(1711242000, berlin).take(3).collect();
+        assert_eq!(timestamps, [1711242000, 1711846800, 1712448000]);
+
+        // In Berlin, 09:00 daily is an hour earlier in Utc from 2024-03-31, and back again
+        // from 2024-10-27
+        let timestamps: Vec<i64> = daily.timestamps(1711785600, berlin).take(3).collect();
+        assert_eq!(timestamps, [1711785600, 1711868400, 1711954800]);
+        let timestamps: Vec<i64> = daily.timestamps(1729926000, berlin).take(2).collect();
+        assert_eq!(timestamps, [1729926000, 1730016000]);
+        // 02:30 does not exist on 2024-03-31, so that occurrence is at 03:30
+        let timestamps: Vec<i64> = daily.timestamps(1711762200, berlin).take(2).collect();
+        assert_eq!(timestamps, [1711762200, 1711848600]);
+    }
+}
@@ -0,0 +1,689 @@
+//! A small backtracking regular expression engine, for `replace`.
+//!
+//! It supports the common syntax: literals, `.`, classes such as `[a-z]` and `[^,]`, the
+//! escapes `\d`, `\w`, `\s`, their negations and `\b`, the anchors `^` and `$`, groups
+//! (`(...)` capturing, `(?:...)` not), alternation with `|`, and the quantifiers `*`, `+`,
+//! `?` and `{n}`, `{n,}` or `{n,m}`, each greedy or, followed by `?`, lazy. A leading `(?i)`
+//! ignores case.
+//!
+//! Patterns are compiled to a small program run by a backtracking machine with a stack of
+//! its own, so long titles can't overflow the call stack. The machine remembers which
+//! instructions it has tried at which positions, so nested quantifiers such as `(a+)+`
+//! don't backtrack exponentially, and it gives up with an error after `MAX_STEPS` steps.
+
+/// How many steps matching may take across a title before giving up.
+const MAX_STEPS: usize = 10_000_000;
+
+/// How many instructions a pattern may compile to, once its counted repetitions are
+/// expanded.
+const MAX_PROGRAM: usize = 10_000;
+
+/// How many instruction and position pairs matching may keep track of, a bit each.
+const MAX_STATES: usize = 1 << 28;
+
+/// A compiled regular expression.
+#[derive(Debug)]
+pub struct Regex {
+    program: Vec<Instruction>,
+    /// The number of capturing groups, not counting the whole match.
+    groups: usize,
+    ignore_case: bool,
+}
+
+#[derive(Debug)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    WordBoundary(bool),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+    },
+}
+
+#[derive(Clone, Debug)]
+enum ClassItem {
+    Range(char, char),
+    /// One of the escapes `\d`, `\w` or `\s`, and whether it is negated.
+    Escape(char, bool),
+}
+
+/// An instruction of a compiled pattern. Jumps are to the index of an instruction.
+#[derive(Debug)]
+enum Instruction {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    WordBoundary(bool),
+    /// Records the current position in a capture slot: `2n` for the start of group `n` and
+    /// `2n + 1` for its end.
+    Save(usize),
+    /// Continues at the first instruction, and if that fails, at the second.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+/// What is left to try when the current way of matching fails.
+enum Backtrack {
+    /// Continue from an instruction at a position.
+    Try(usize, usize),
+    /// Undo the recording of a capture slot.
+    Restore(usize, Option<usize>),
+}
+
+/// The start and end, in characters, of the whole match and of each group.
+type Captures = Vec<Option<(usize, usize)>>;
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns whether `c` is in the class of the escape letter `d`, `w` or `s`.
+fn in_escape_class(escape: char, c: char) -> bool {
+    match escape {
+        'd' => c.is_ascii_digit(),
+        'w' => is_word(c),
+        _ => c.is_whitespace(),
+    }
+}
+
+impl Regex {
+    /// Compiles `pattern`, naming the column of the first problem if it is invalid.
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let (pattern, ignore_case, offset) = match pattern.strip_prefix("(?i)") {
+            Some(rest) => (rest, true, 4),
+            None => (pattern, false, 0),
+        };
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            position: 0,
+            offset,
+            groups: 0,
+        };
+        let node = parser.alternation()?;
+        if parser.position < parser.chars.len() {
+            return Err(format!("Unmatched ')' at column {}", parser.column()));
+        }
+        let mut program = Vec::new();
+        compile(&node, &mut program);
+        program.push(Instruction::Match);
+        if program.len() > MAX_PROGRAM {
+            return Err("Pattern too large once its repetitions are expanded".to_string());
+        }
+        Ok(Regex {
+            program,
+            groups: parser.groups,
+            ignore_case,
+        })
+    }
+
+    /// Replaces every match in `text` with `replacement`, where `$0` or `${0}` stand for the
+    /// whole match, `$1` or `${1}` for the first group and so on, and `$$` for `$`. Fails if
+    /// matching takes more than `MAX_STEPS` steps.
+    pub fn replace_all(&self, text: &str, replacement: &str) -> Result<String, String> {
+        let chars: Vec<char> = text.chars().collect();
+        let states = self.program.len() * (chars.len() + 1);
+        if states > MAX_STATES {
+            return Err("The text is too long to match against this pattern".to_string());
+        }
+        let mut matcher = Matcher {
+            visited: vec![0; states.div_ceil(64)],
+            steps: 0,
+        };
+        let mut result = String::new();
+        let mut position = 0;
+        let mut copied = 0;
+        let mut last_end = None;
+        while position <= chars.len() {
+            let captures = match self.match_at(&chars, position, &mut matcher)? {
+                Some(captures) => captures,
+                None => {
+                    position += 1;
+                    continue;
+                }
+            };
+            let (start, end) = captures[0].expect("The whole match is always captured");
+            // An empty match right after another match is not a match of its own
+            if start == end && last_end == Some(start) {
+                position += 1;
+                continue;
+            }
+            last_end = Some(end);
+            result.extend(&chars[copied..start]);
+            self.expand(replacement, &chars, &captures, &mut result);
+            copied = end;
+            // An empty match moves on by a character, so it is not found again
+            position = if end > start { end } else { end + 1 };
+        }
+        result.extend(&chars[copied.min(chars.len())..]);
+        Ok(result)
+    }
+
+    /// Appends `replacement` to `result` with its group references filled in.
+    fn expand(&self, replacement: &str, chars: &[char], captures: &Captures, result: &mut String) {
+        let mut rest = replacement;
+        while let Some(index) = rest.find('$') {
+            result.push_str(&rest[..index]);
+            rest = &rest[index + 1..];
+            let (number, length) = if let Some(braced) = rest.strip_prefix('{') {
+                match braced.find('}') {
+                    Some(close) => (braced[..close].parse::<usize>().ok(), close + 2),
+                    None => (None, 0),
+                }
+            } else {
+                let digits = rest.chars().take_while(char::is_ascii_digit).count();
+                (rest[..digits].parse::<usize>().ok(), digits)
+            };
+            match number {
+                Some(number) if number <= self.groups => {
+                    if let Some((start, end)) = captures[number] {
+                        result.extend(&chars[start..end]);
+                    }
+                    rest = &rest[length..];
+                }
+                _ if rest.starts_with('$') => {
+                    result.push('$');
+                    rest = &rest[1..];
+                }
+                _ => result.push('$'),
+            }
+        }
+        result.push_str(rest);
+    }
+
+    /// Returns the captures of the leftmost-first match starting at `start`, if there is
+    /// one.
+    fn match_at(
+        &self,
+        chars: &[char],
+        start: usize,
+        matcher: &mut Matcher,
+    ) -> Result<Option<Captures>, String> {
+        let mut slots = vec![None; 2 * (self.groups + 1)];
+        let mut stack = vec![Backtrack::Try(0, start)];
+        while let Some(entry) = stack.pop() {
+            let (mut pc, mut position) = match entry {
+                Backtrack::Try(pc, position) => (pc, position),
+                Backtrack::Restore(slot, value) => {
+                    slots[slot] = value;
+                    continue;
+                }
+            };
+            loop {
+                // Whether the rest of the pattern matches from here doesn't depend on how
+                // it was reached, so a state tried before has failed, or is being tried by a
+                // repetition that matched nothing
+                if !matcher.visit(pc * (chars.len() + 1) + position)? {
+                    break;
+                }
+                let current = chars.get(position).copied();
+                let next = match &self.program[pc] {
+                    Instruction::Char(expected) => current
+                        .filter(|c| {
+                            c == expected
+                                || (self.ignore_case
+                                    && c.to_lowercase().eq(expected.to_lowercase()))
+                        })
+                        .map(|_| position + 1),
+                    Instruction::Any => current.filter(|c| *c != '\n').map(|_| position + 1),
+                    Instruction::Class(items, negated) => current
+                        .filter(|c| self.in_class(items, *c) != *negated)
+                        .map(|_| position + 1),
+                    Instruction::Start => (position == 0).then_some(position),
+                    Instruction::End => (position == chars.len()).then_some(position),
+                    Instruction::WordBoundary(expected) => {
+                        let before = position > 0 && is_word(chars[position - 1]);
+                        let after = current.is_some_and(is_word);
+                        ((before != after) == *expected).then_some(position)
+                    }
+                    Instruction::Save(slot) => {
+                        stack.push(Backtrack::Restore(*slot, slots[*slot]));
+                        slots[*slot] = Some(position);
+                        Some(position)
+                    }
+                    Instruction::Split(first, second) => {
+                        stack.push(Backtrack::Try(*second, position));
+                        pc = *first;
+                        continue;
+                    }
+                    Instruction::Jump(target) => {
+                        pc = *target;
+                        continue;
+                    }
+                    Instruction::Match => {
+                        matcher.visited.fill(0);
+                        let mut captures: Captures =
+                            slots.chunks(2).map(|pair| pair[0].zip(pair[1])).collect();
+                        captures[0] = Some((start, position));
+                        return Ok(Some(captures));
+                    }
+                };
+                match next {
+                    Some(next) => {
+                        pc += 1;
+                        position = next;
+                    }
+                    None => break,
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn in_class(&self, items: &[ClassItem], c: char) -> bool {
+        let variants: Vec<char> = if self.ignore_case {
+            c.to_lowercase()
+                .chain(c.to_uppercase())
+                .chain([c])
+                .collect()
+        } else {
+            vec![c]
+        };
+        items.iter().any(|item| match item {
+            ClassItem::Range(low, high) => variants.iter().any(|c| (*low..=*high).contains(c)),
+            ClassItem::Escape(escape, negated) => in_escape_class(*escape, c) != *negated,
+        })
+    }
+}
+
+/// What matching has done so far across a title.
+struct Matcher {
+    /// Which instructions were tried at which positions since the last match, a bit each
+    /// numbered `instruction * (length + 1) + position`.
+    visited: Vec<u64>,
+    steps: usize,
+}
+
+impl Matcher {
+    /// Records a step trying `state`, returning whether it wasn't tried before.
+    fn visit(&mut self, state: usize) -> Result<bool, String> {
+        let (word, bit) = (state / 64, 1 << (state % 64));
+        if self.visited[word] & bit != 0 {
+            return Ok(false);
+        }
+        self.visited[word] |= bit;
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return Err("The pattern takes too long to match".to_string());
+        }
+        Ok(true)
+    }
+}
+
+/// Appends the instructions matching `node` to `program`.
+fn compile(node: &Node, program: &mut Vec<Instruction>) {
+    match node {
+        Node::Char(c) => program.push(Instruction::Char(*c)),
+        Node::Any => program.push(Instruction::Any),
+        Node::Class(items, negated) => program.push(Instruction::Class(items.clone(), *negated)),
+        Node::Start => program.push(Instruction::Start),
+        Node::End => program.push(Instruction::End),
+        Node::WordBoundary(expected) => program.push(Instruction::WordBoundary(*expected)),
+        Node::Group(node, None) => compile(node, program),
+        Node::Group(node, Some(index)) => {
+            program.push(Instruction::Save(2 * index));
+            compile(node, program);
+            program.push(Instruction::Save(2 * index + 1));
+        }
+        Node::Concat(nodes) => nodes.iter().for_each(|node| compile(node, program)),
+        Node::Alternation(nodes) => {
+            let mut jumps = Vec::new();
+            for (index, node) in nodes.iter().enumerate() {
+                if index + 1 == nodes.len() {
+                    compile(node, program);
+                    break;
+                }
+                let split = program.len();
+                program.push(Instruction::Split(split + 1, 0));
+                compile(node, program);
+                jumps.push(program.len());
+                program.push(Instruction::Jump(0));
+                program[split] = Instruction::Split(split + 1, program.len());
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Instruction::Jump(end);
+            }
+        }
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            // Splits try the repetition first when greedy, and what follows it first when lazy
+            let split = |repeat: usize, skip: usize| match greedy {
+                true => Instruction::Split(repeat, skip),
+                false => Instruction::Split(skip, repeat),
+            };
+            for _ in 0..*min {
+                compile(node, program);
+                // Stop expanding a pattern that is too large already
+                if program.len() > MAX_PROGRAM {
+                    return;
+                }
+            }
+            match max {
+                None => {
+                    let start = program.len();
+                    program.push(Instruction::Jump(0));
+                    compile(node, program);
+                    program.push(Instruction::Jump(start));
+                    program[start] = split(start + 1, program.len());
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Instruction::Jump(0));
+                        compile(node, program);
+                        if program.len() > MAX_PROGRAM {
+                            return;
+                        }
+                    }
+                    let end = program.len();
+                    for start in splits {
+                        program[start] = split(start + 1, end);
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    /// The length of the flags before the pattern, for columns in errors.
+    offset: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn column(&self) -> usize {
+        self.offset + self.position + 1
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn eat(&mut self, expected: &str) -> bool {
+        let expected: Vec<char> = expected.chars().collect();
+        if self.chars[self.position..].starts_with(&expected) {
+            self.position += expected.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.concat()?];
+        while self.eat("|") {
+            branches.push(self.concat()?);
+        }
+        Ok(match branches.len() {
+            1 => branches.remove(0),
+            _ => Node::Alternation(branches),
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek().filter(|c| *c != '|' && *c != ')') {
+            let column = self.column();
+            if "*+?".contains(c) || (c == '{' && self.bounds().is_some()) {
+                return Err(format!("Nothing to repeat at column {}", column));
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantifier(atom));
+        }
+        Ok(match nodes.len() {
+            1 => nodes.remove(0),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let column = self.column();
+        let c = self.chars[self.position];
+        self.position += 1;
+        Ok(match c {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                let index = if self.eat("?:") {
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let node = self.alternation()?;
+                if !self.eat(")") {
+                    return Err(format!("Missing ')' for the '(' at column {}", column));
+                }
+                Node::Group(Box::new(node), index)
+            }
+            '[' => self.class(column)?,
+            '\\' => match self.escape()? {
+                'b' => Node::WordBoundary(true),
+                'B' => Node::WordBoundary(false),
+                c @ ('d' | 'w' | 's') => Node::Class(vec![ClassItem::Escape(c, false)], false),
+                c @ ('D' | 'W' | 'S') => {
+                    Node::Class(vec![ClassItem::Escape(c.to_ascii_lowercase(), true)], false)
+                }
+                c => Node::Char(c),
+            },
+            c => Node::Char(c),
+        })
+    }
+
+    /// Reads the character after a `\`, translating `\n` and `\t`.
+    fn escape(&mut self) -> Result<char, String> {
+        let c = self
+            .peek()
+            .ok_or_else(|| format!("Trailing '\\' at column {}", self.column() - 1))?;
+        self.position += 1;
+        Ok(match c {
+            'n' => '\n',
+            't' => '\t',
+            c => c,
+        })
+    }
+
+    fn class(&mut self, column: usize) -> Result<Node, String> {
+        let negated = self.eat("^");
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self
+                .peek()
+                .ok_or_else(|| format!("Missing ']' for the '[' at column {}", column))?;
+            self.position += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let low = if c == '\\' {
+                match self.escape()? {
+                    c @ ('d' | 'w' | 's') => {
+                        items.push(ClassItem::Escape(c, false));
+                        continue;
+                    }
+                    c @ ('D' | 'W' | 'S') => {
+                        items.push(ClassItem::Escape(c.to_ascii_lowercase(), true));
+                        continue;
+                    }
+                    c => c,
+                }
+            } else {
+                c
+            };
+            let high = match (self.peek(), self.chars.get(self.position + 1)) {
+                (Some('-'), Some(&high)) if high != ']' => {
+                    self.position += 2;
+                    if high == '\\' {
+                        self.escape()?
+                    } else {
+                        high
+                    }
+                }
+                _ => low,
+            };
+            if high < low {
+                return Err(format!(
+                    "Invalid range {}-{} at column {}",
+                    low, high, column
+                ));
+            }
+            items.push(ClassItem::Range(low, high));
+        }
+        Ok(Node::Class(items, negated))
+    }
+
+    /// Reads the bounds of a `{n}`, `{n,}` or `{n,m}` quantifier at the current position
+    /// without consuming it, returning them and its length.
+    fn bounds(&self) -> Option<(usize, Option<usize>, usize)> {
+        let rest: String = self.chars[self.position..].iter().collect();
+        let close = rest.find('}')?;
+        let inner = rest.strip_prefix('{')?.get(..close - 1)?;
+        let (min, max) = match inner.split_once(',') {
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+            None => (inner.parse().ok()?, Some(inner.parse().ok()?)),
+        };
+        Some((min, max, rest[..=close].chars().count()))
+    }
+
+    fn quantifier(&mut self, node: Node) -> Node {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => match self.bounds() {
+                Some((min, max, length)) => {
+                    self.position += length - 1;
+                    (min, max)
+                }
+                None => return node,
+            },
+            _ => return node,
+        };
+        self.position += 1;
+        let greedy = !self.eat("?");
+        Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+            greedy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Regex;
+
+    #[test]
+    fn test_regex() {
+        let replace = |pattern: &str, text: &str, with: &str| {
+            Regex::new(pattern)
+                .unwrap()
+                .replace_all(text, with)
+                .unwrap()
+        };
+        assert_eq!(
+            replace("ACME", "Call ACME about ACME", "Initech"),
+            "Call Initech about Initech"
+        );
+        assert_eq!(replace("(?i)acme", "Call Acme", "Initech"), "Call Initech");
+        assert_eq!(replace(r"(\w+) (\w+)", "Pay rent", "$2 ${1}!"), "rent Pay!");
+        assert_eq!(
+            replace(r"\d{4}-\d\d", "Due 2024-06 or 2025-1", "<$0>"),
+            "Due <2024-06> or 2025-1"
+        );
+        assert_eq!(replace(r"^\s+|\s+$", "  padded  ", ""), "padded");
+        assert_eq!(replace("a*", "baaa", "-"), "-b-");
+        assert_eq!(replace("<.+?>", "<a><b>", "x"), "xx");
+        assert_eq!(replace("<.+>", "<a><b>", "x"), "x");
+        assert_eq!(
+            replace(r"\bcat\b", "cat concat cat", "dog"),
+            "dog concat dog"
+        );
+        assert_eq!(replace("[^a-c,]+", "abxyc,d", "_"), "ab_c,_");
+        assert_eq!(replace("(?:ab)+|z", "ababz", "$$"), "$$");
+        assert_eq!(replace("x{2,}", "x xx xxx", "y"), "x y y");
+        assert_eq!(
+            replace("colou?r", "Paint the color", "colour"),
+            "Paint the colour"
+        );
+        assert_eq!(
+            replace("^color$", "Paint the color", "x"),
+            "Paint the color"
+        );
+
+        assert_eq!(
+            Regex::new("(ab").unwrap_err(),
+            "Missing ')' for the '(' at column 1"
+        );
+        assert_eq!(Regex::new("ab)").unwrap_err(), "Unmatched ')' at column 3");
+        assert_eq!(
+            Regex::new("*a").unwrap_err(),
+            "Nothing to repeat at column 1"
+        );
+        assert_eq!(
+            Regex::new("[z-a]").unwrap_err(),
+            "Invalid range z-a at column 1"
+        );
+        assert_eq!(
+            Regex::new("[ab").unwrap_err(),
+            "Missing ']' for the '[' at column 1"
+        );
+        assert_eq!(
+            Regex::new("(?:a{100}){200}").unwrap_err(),
+            "Pattern too large once its repetitions are expanded"
+        );
+    }
+
+    #[test]
+    fn test_long_inputs() {
+        let replace = |pattern: &str, text: &str, with: &str| {
+            Regex::new(pattern).unwrap().replace_all(text, with)
+        };
+        // Matching doesn't recurse per character
+        let long = "a".repeat(100_000);
+        assert_eq!(replace("a+", &long, "b").unwrap(), "b");
+        assert_eq!(replace("(a|b)*c", &long, "").unwrap(), long);
+        assert_eq!(replace(".*?$", &long, "-").unwrap(), "-");
+        // Nested quantifiers don't backtrack exponentially
+        let text = "a".repeat(30);
+        assert_eq!(replace("(a+)+b", &text, "").unwrap(), text);
+        assert_eq!(
+            replace("(a*)*b|(a+)+$", &text, "<$2>").unwrap(),
+            format!("<{}>", text)
+        );
+        // Matching that would take too long or need too much memory fails
+        let text = "a".repeat(20_000);
+        assert_eq!(
+            replace("(?:a{600})*y", &text, "").unwrap_err(),
+            "The pattern takes too long to match"
+        );
+        assert_eq!(
+            replace("(?:a{1000}){9}", &long, "").unwrap_err(),
+            "The text is too long to match against this pattern"
+        );
+    }
+}
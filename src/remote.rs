@@ -0,0 +1,1612 @@
+//! Sharing one todo list between machines: `serve` publishes a list file over HTTP, and
+//! `--remote` runs a command against the served list instead of a local file.
+//!
+//! The API has a single resource, `/todos`, in the format of the list file. `GET` returns
+//! it with an `ETag` of its checksum, and `PUT` replaces it only if the `If-Match` header
+//! still names the current checksum, so two machines changing the list at once cannot
+//! silently overwrite each other. Given query parameters, e.g. `/todos?tag=work&limit=50`,
+//! `GET` returns a page of the matching todo items instead, for clients that use the server
+//! as a backend rather than copying the whole list. `/openapi.json` describes the API (see
+//! `openapi`), and `POST /inbox` adds a todo item made from what another service sends (see
+//! `inbox`).
+//!
+//! Requests carry a bearer token when `serve` is given any, and each token is either
+//! read-only, allowing `GET`, or read-write, allowing `PUT` too. Both sides speak HTTPS
+//! instead of plain HTTP when built with TLS (see `tls`). A client making too many requests
+//! a minute is turned away with 429 when `serve` is given a rate limit.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use crate::dates::DateFormat;
+use crate::formats::Format;
+use crate::inbox::{self, Inbox};
+use crate::openapi;
+use crate::query::Query;
+use crate::store::{list_directory, try_save_todo_list};
+use crate::sync::{self, Queue};
+use crate::tls::{self, Acceptor};
+use crate::{Todo, TodoList};
+
+/// The largest request or response body accepted, in bytes.
+const MAX_BODY: usize = 64 * 1024 * 1024;
+/// The longest line accepted in the head of a message or before a chunk, in bytes.
+const MAX_LINE: usize = 8 * 1024;
+/// The most headers, or trailer fields, a message can have.
+const MAX_HEADERS: usize = 100;
+/// How long to wait to connect to, or hear from, the other side.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// How many todo items a page of `GET /todos?...` holds unless `limit` says otherwise.
+const PAGE_SIZE: usize = 100;
+/// The most todo items a page can hold.
+pub const MAX_PAGE_SIZE: usize = 1000;
+/// The query parameters `GET /todos` takes, with what each does.
+pub const PARAMETERS: &[(&str, &str)] = &[
+    ("tag", "Only todo items with this tag."),
+    ("assignee", "Only todo items assigned to this person."),
+    ("created_by", "Only todo items created by this person."),
+    ("project", "Only todo items in this project."),
+    ("location", "Only todo items at this location."),
+    (
+        "due_before",
+        "Only todo items due before this time, e.g. friday or 2024-06-01.",
+    ),
+    ("due_after", "Only todo items due after this time."),
+    (
+        "modified_since",
+        "Only todo items changed since this time, e.g. 2 days ago.",
+    ),
+    (
+        "where",
+        "Only todo items matching a query, e.g. priority >= 4 or overdue.",
+    ),
+    (
+        "limit",
+        "How many todo items the page holds, 100 unless given.",
+    ),
+    (
+        "cursor",
+        "The next of the previous page, to continue after it.",
+    ),
+];
+
+/// Why a request to a served list failed.
+#[derive(Debug)]
+pub enum Error {
+    /// The server could not be reached, so the command can work offline.
+    Unreachable(String),
+    /// The server answered, but refused the request.
+    Rejected(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Unreachable(error) | Error::Rejected(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// A served todo list, e.g. `http://nas.local:8080`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remote {
+    /// Whether it is served over HTTPS.
+    https: bool,
+    host: String,
+    port: u16,
+    /// The path the API is under, without a trailing slash, e.g. `/todo` behind a proxy.
+    prefix: String,
+    /// The certificate, or the authority signing it, trusted for an HTTPS server, when it
+    /// is not one the system trusts, e.g. a self-signed one.
+    ca: Option<PathBuf>,
+}
+
+impl Remote {
+    /// Parses an `http://host[:port][/prefix]` or `https://...` URL; the port defaults to
+    /// 8080, as for `serve`.
+    pub fn parse(url: &str) -> Result<Remote, String> {
+        Remote::parse_with_port(url, 8080)
+    }
+
+    /// Parses a URL as `parse` does, with the port defaulting to `default_port`.
+    pub fn parse_with_port(url: &str, default_port: u16) -> Result<Remote, String> {
+        let (https, rest) = match url.strip_prefix("https://") {
+            Some(rest) => (true, rest),
+            None => (
+                false,
+                url.strip_prefix("http://").ok_or_else(|| {
+                    format!("Invalid remote: '{}'. Expected http://host:port", url)
+                })?,
+            ),
+        };
+        let (authority, prefix) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => match port.parse() {
+                Ok(port) => (host, port),
+                Err(_) => return Err(format!("Invalid port in remote: '{}'", url)),
+            },
+            None => (authority, default_port),
+        };
+        if host.is_empty() {
+            return Err(format!(
+                "Invalid remote: '{}'. Expected http://host:port",
+                url
+            ));
+        }
+        Ok(Remote {
+            https,
+            host: host.to_string(),
+            port,
+            prefix: prefix.trim_end_matches('/').to_string(),
+            ca: None,
+        })
+    }
+
+    /// Trusts the certificate at `ca`, or those it signs, for an HTTPS server.
+    pub fn trusting(self, ca: Option<PathBuf>) -> Remote {
+        Remote { ca, ..self }
+    }
+
+    /// Returns the file the served list is copied to while a command runs, in the user's
+    /// cache directory, e.g. `~/.cache/todo/remote/nas.local-8080/todos.json`.
+    pub fn cache_file(&self) -> PathBuf {
+        let cache = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .unwrap_or_else(std::env::temp_dir);
+        cache
+            .join("todo")
+            .join("remote")
+            .join(format!("{}-{}", self.host, self.port))
+            .join(crate::TODO_FILE)
+    }
+
+    /// Fetches the served list, returning it with its checksum.
+    pub fn pull(&self, token: Option<&str>) -> Result<(String, String), Error> {
+        let response = self.send("GET", &[], "", token)?;
+        match (response.status, response.etag()) {
+            (200, Some(etag)) => Ok((response.body, etag)),
+            (200, None) => Err(Error::Rejected(format!(
+                "{} sent the list without an ETag",
+                self
+            ))),
+            _ => Err(Error::Rejected(response.error(self))),
+        }
+    }
+
+    /// Replaces the served list with `contents` if it still has the checksum `checksum`,
+    /// returning its new checksum, or `None` if it has changed since.
+    pub fn push(
+        &self,
+        contents: &str,
+        checksum: &str,
+        token: Option<&str>,
+    ) -> Result<Option<String>, Error> {
+        let if_match = format!("\"{}\"", checksum);
+        let response = self.send("PUT", &[("If-Match", &if_match)], contents, token)?;
+        match response.status {
+            200 | 204 => Ok(response.etag()),
+            412 => Ok(None),
+            _ => Err(Error::Rejected(response.error(self))),
+        }
+    }
+
+    /// Sends a request for `/todos` and reads the whole response.
+    fn send(
+        &self,
+        method: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+        token: Option<&str>,
+    ) -> Result<Response, Error> {
+        let authorization = token.map(|token| format!("Bearer {}", token));
+        let mut headers = headers.to_vec();
+        headers.extend(
+            authorization
+                .as_deref()
+                .map(|value| ("Authorization", value)),
+        );
+        self.request(method, "/todos", &headers, body)
+    }
+
+    /// Sends a request for `path`, under the prefix, and reads the whole response.
+    pub fn request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> Result<Response, Error> {
+        let unreachable = |error: std::io::Error| {
+            Error::Unreachable(format!("Unable to reach {}: {}", self, error))
+        };
+        let address = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(unreachable)?
+            .next()
+            .ok_or_else(|| {
+                Error::Unreachable(format!("Unable to reach {}: no address found", self))
+            })?;
+        let stream = TcpStream::connect_timeout(&address, TIMEOUT).map_err(unreachable)?;
+        stream
+            .set_read_timeout(Some(TIMEOUT))
+            .map_err(unreachable)?;
+        let mut request = format!(
+            "{} {}{} HTTP/1.1\r\nHost: {}:{}\r\nConnection: close\r\nContent-Length: {}\r\n",
+            method,
+            self.prefix,
+            path,
+            self.host,
+            self.port,
+            body.len()
+        );
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        request.push_str(body);
+        if self.https {
+            // A server that can't be trusted is no better than one that can't be reached,
+            // but trying again later won't help
+            let stream = tls::connect(stream, &self.host, self.ca.as_deref())
+                .map_err(|error| Error::Rejected(format!("{}: {}", self, error)))?;
+            self.exchange(stream, &request)
+        } else {
+            self.exchange(stream, &request)
+        }
+    }
+
+    /// Sends `request` on `stream` and reads the whole response.
+    fn exchange(&self, mut stream: impl Read + Write, request: &str) -> Result<Response, Error> {
+        let unreachable = |error: std::io::Error| {
+            Error::Unreachable(format!("Unable to reach {}: {}", self, error))
+        };
+        stream.write_all(request.as_bytes()).map_err(unreachable)?;
+        let mut reader = BufReader::new(stream);
+        // A connection dropped halfway through is as good as no connection.
+        let (status_line, headers, body) = read_message(&mut reader)
+            .map_err(|error| Error::Unreachable(format!("{}: {}", self, error)))?;
+        let status = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(|| {
+                Error::Rejected(format!(
+                    "{} sent an invalid response: {}",
+                    self, status_line
+                ))
+            })?;
+        Ok(Response {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+impl std::fmt::Display for Remote {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let scheme = if self.https { "https" } else { "http" };
+        write!(f, "{}://{}:{}{}", scheme, self.host, self.port, self.prefix)
+    }
+}
+
+/// An HTTP request received by `serve`.
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// The headers, with lowercase names.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// The bearer token in the `Authorization` header or, as services that post to the
+    /// inbox can often only be given a URL, in the `token` parameter of an `/inbox` request.
+    fn token(&self) -> Option<&str> {
+        self.header("authorization")
+            .and_then(|authorization| authorization.strip_prefix("Bearer "))
+            .map(str::trim)
+            .or_else(|| {
+                let (path, query) = self.path.split_once('?')?;
+                if !path.starts_with("/inbox") {
+                    return None;
+                }
+                query
+                    .split('&')
+                    .find_map(|parameter| parameter.strip_prefix("token="))
+            })
+    }
+}
+
+/// An HTTP response, sent by `serve` or received by a remote command.
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    /// The headers, with lowercase names.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    fn new(status: u16, body: &str) -> Response {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: body.to_string(),
+        }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// The checksum in the `ETag` header, without its quotes.
+    fn etag(&self) -> Option<String> {
+        self.header("etag")
+            .map(|etag| etag.trim_matches('"').to_string())
+    }
+
+    /// Describes an unexpected response, with the server's explanation if it sent one.
+    fn error(&self, remote: &Remote) -> String {
+        match self.body.trim() {
+            "" => format!("{} answered with status {}", remote, self.status),
+            reason => format!(
+                "{} answered with status {}: {}",
+                remote, self.status, reason
+            ),
+        }
+    }
+}
+
+/// What a bearer token lets the requests carrying it do with a served list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scope {
+    /// Read the list.
+    ReadOnly,
+    /// Read and replace the list.
+    ReadWrite,
+}
+
+impl FromStr for Scope {
+    type Err = String;
+    fn from_str(scope: &str) -> Result<Scope, String> {
+        match scope {
+            "read-only" => Ok(Scope::ReadOnly),
+            "read-write" => Ok(Scope::ReadWrite),
+            _ => Err(format!(
+                "Unknown scope: '{}'. Expected read-only or read-write",
+                scope
+            )),
+        }
+    }
+}
+
+/// The bearer tokens `serve` accepts and the scope of each. With none, anyone who can reach
+/// the server can read and change the list.
+#[derive(Debug, Default, Clone)]
+pub struct Access {
+    pub tokens: BTreeMap<String, Scope>,
+    /// A file of more tokens, one per line followed by its scope, which is read again for
+    /// every request so tokens can be added and revoked while serving.
+    pub file: Option<PathBuf>,
+}
+
+impl Access {
+    /// Returns whether requests are let through without a token.
+    fn is_open(&self) -> bool {
+        self.tokens.is_empty() && self.file.is_none()
+    }
+
+    /// Returns the scope of `token`, or `None` if it is not accepted.
+    pub fn scope(&self, token: &str) -> Result<Option<Scope>, String> {
+        let mut tokens: Vec<(String, Scope)> = self
+            .tokens
+            .iter()
+            .map(|(token, scope)| (token.clone(), *scope))
+            .collect();
+        if let Some(file) = &self.file {
+            let contents = std::fs::read_to_string(file)
+                .map_err(|error| format!("Unable to read {}: {}", file.display(), error))?;
+            let listed = parse_tokens(&contents)
+                .map_err(|error| format!("{}: {}", file.display(), error))?;
+            tokens.extend(listed);
+        }
+        // Every token is compared in full, so how long it takes does not give one away
+        let mut found = None;
+        for (accepted, scope) in tokens {
+            if same(accepted.as_bytes(), token.as_bytes()) {
+                found = Some(scope);
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// Reads a tokens file: a token and its scope on each line, e.g. `9f86d08 read-only`, with
+/// blank lines and lines starting with `#` left out.
+pub fn parse_tokens(contents: &str) -> Result<Vec<(String, Scope)>, String> {
+    let mut tokens = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some(token), Some(scope), None) => {
+                let scope = scope
+                    .parse()
+                    .map_err(|error| format!("Line {}: {}", index + 1, error))?;
+                tokens.push((token.to_string(), scope));
+            }
+            _ => {
+                return Err(format!(
+                    "Line {}: expected a token and its scope, read-only or read-write",
+                    index + 1
+                ))
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Counts the requests each client makes in the current minute, to turn away those making
+/// more than `per_minute`. Clients are told apart by their accepted bearer token, or by
+/// their address when they have none.
+#[derive(Debug, Default)]
+pub struct RateLimit {
+    per_minute: Option<u32>,
+    /// The minute of each client's latest request, and how many it made in that minute.
+    clients: BTreeMap<String, (u64, u32)>,
+}
+
+impl RateLimit {
+    /// Allows `per_minute` requests a minute to each client, or any number without it.
+    pub fn new(per_minute: Option<u32>) -> RateLimit {
+        RateLimit {
+            per_minute,
+            clients: BTreeMap::new(),
+        }
+    }
+
+    /// Counts a request by `client` at `now`, in seconds since the epoch. Returns how many
+    /// seconds it has to wait if it has already made as many as are allowed this minute.
+    fn check(&mut self, client: &str, now: u64) -> Result<(), u64> {
+        let per_minute = match self.per_minute {
+            Some(per_minute) => per_minute,
+            None => return Ok(()),
+        };
+        let minute = now / 60;
+        // Clients from earlier minutes start again from nothing
+        self.clients.retain(|_, (last, _)| *last == minute);
+        let count = &mut self
+            .clients
+            .entry(client.to_string())
+            .or_insert((minute, 0))
+            .1;
+        if *count >= per_minute {
+            return Err(60 - now % 60);
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+/// Compares two byte strings in a time that depends only on their lengths.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |differ, (a, b)| differ | (a ^ b)) == 0
+}
+
+/// Serves the todo list file at `file` on `listener`, one request at a time, so every
+/// change is applied to the list as the one before left it. Requests must carry a bearer
+/// token that `access` accepts, when it has any, with a scope allowing what they ask for.
+/// With `tls`, connections are made over TLS. Clients making more requests than `limit`
+/// allows are answered with 429, and payloads posted to the inbox are made into todo items
+/// as `inbox` maps them.
+pub fn serve(
+    listener: TcpListener,
+    file: &Path,
+    access: &Access,
+    tls: Option<&Acceptor>,
+    limit: &mut RateLimit,
+    inbox: &Inbox,
+) -> std::io::Result<()> {
+    for stream in listener.incoming() {
+        // A connection that fails before it is served only loses that connection
+        let accepted = stream.and_then(|stream| {
+            let peer = stream.peer_addr()?;
+            stream.set_read_timeout(Some(TIMEOUT))?;
+            Ok((stream, peer))
+        });
+        let (stream, peer) = match accepted {
+            Ok(accepted) => accepted,
+            Err(error) => {
+                eprintln!("Unable to accept a connection: {}", error);
+                continue;
+            }
+        };
+        let client = peer.ip().to_string();
+        let served = match tls {
+            Some(acceptor) => acceptor
+                .accept(stream)
+                .and_then(|stream| serve_connection(stream, &client, file, access, limit, inbox)),
+            None => serve_connection(stream, &client, file, access, limit, inbox),
+        };
+        if let Err(error) = served {
+            eprintln!("{}: {}", peer, error);
+        }
+    }
+    Ok(())
+}
+
+/// Reads one request from `stream`, from the client at the address `client`, and answers it.
+fn serve_connection(
+    stream: impl Read + Write,
+    client: &str,
+    file: &Path,
+    access: &Access,
+    limit: &mut RateLimit,
+    inbox: &Inbox,
+) -> Result<(), String> {
+    let mut reader = BufReader::new(stream);
+    let response = match read_message(&mut reader) {
+        Ok((request_line, headers, body)) => {
+            let mut words = request_line.split_whitespace();
+            let request = Request {
+                method: words.next().unwrap_or_default().to_string(),
+                path: words.next().unwrap_or_default().to_string(),
+                headers,
+                body,
+            };
+            let client = match request.token() {
+                Some(token) if matches!(access.scope(token), Ok(Some(_))) => token,
+                _ => client,
+            };
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs());
+            match limit.check(client, now) {
+                Ok(()) => handle(&request, file, access, inbox),
+                Err(wait) => {
+                    let mut response = Response::new(429, "Too many requests; try again later");
+                    response
+                        .headers
+                        .push(("retry-after".to_string(), wait.to_string()));
+                    response
+                }
+            }
+        }
+        Err(error) => Response::new(400, &error),
+    };
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nConnection: close\r\nContent-Type: {}\r\nContent-Length: {}\r\n",
+        response.status,
+        reason(response.status),
+        if matches!(response.status, 200 | 201) {
+            "application/json"
+        } else {
+            "text/plain; charset=utf-8"
+        },
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    let mut stream = reader.into_inner();
+    stream
+        .write_all(head.as_bytes())
+        .and_then(|_| stream.write_all(response.body.as_bytes()))
+        .map_err(|error| error.to_string())
+}
+
+/// Answers a request for the todo list file at `file`.
+pub fn handle(request: &Request, file: &Path, access: &Access, inbox: &Inbox) -> Response {
+    let (path, query) = request
+        .path
+        .split_once('?')
+        .unwrap_or((request.path.as_str(), ""));
+    // The description of the API gives nothing away, so it needs no token
+    if path == "/openapi.json" && request.method == "GET" {
+        return Response::new(200, &openapi::spec().to_string());
+    }
+    if !access.is_open() {
+        let scope = match request.token().map(|token| access.scope(token)).transpose() {
+            Ok(scope) => scope.flatten(),
+            Err(error) => return Response::new(500, &error),
+        };
+        match scope {
+            None => {
+                let mut response = Response::new(401, "A valid bearer token is required");
+                response
+                    .headers
+                    .push(("www-authenticate".to_string(), "Bearer".to_string()));
+                return response;
+            }
+            Some(Scope::ReadOnly) if request.method != "GET" => {
+                return Response::new(403, "This token can only read the list")
+            }
+            Some(_) => {}
+        }
+    }
+    let path = path.trim_end_matches('/');
+    if path == "/inbox" {
+        return receive(request, None, file, inbox);
+    }
+    if let Some(name) = path.strip_prefix("/inbox/") {
+        return receive(request, Some(name), file, inbox);
+    }
+    if path != "/todos" {
+        return Response::new(404, "Not found. The todo list is at /todos");
+    }
+    let parameters = match parse_parameters(query) {
+        Ok(parameters) => parameters,
+        Err(error) => return Response::new(400, &error),
+    };
+    let current = match read_list(file) {
+        Ok(current) => current,
+        Err(error) => return Response::new(500, &error),
+    };
+    let etag = format!("\"{}\"", current.compute_checksum());
+    match request.method.as_str() {
+        "GET" => {
+            let body = if parameters.is_empty() {
+                current.to_json()
+            } else {
+                match page(&current, &parameters, crate::now()) {
+                    Ok(page) => page,
+                    Err(error) => return Response::new(400, &error),
+                }
+            };
+            let mut response = Response::new(200, &body);
+            response.headers.push(("etag".to_string(), etag));
+            response
+        }
+        "PUT" => {
+            match request.header("if-match") {
+                None => return Response::new(428, "PUT needs an If-Match header"),
+                Some(if_match) if if_match != etag && if_match != "*" => {
+                    return Response::new(412, "The list has changed since it was read")
+                }
+                Some(_) => {}
+            }
+            let updated: TodoList = match serde_json::from_str(&request.body) {
+                Ok(updated) => updated,
+                Err(error) => return Response::new(400, &format!("Invalid todo list: {}", error)),
+            };
+            if let Err(conflict) = try_save_todo_list(file, &updated) {
+                return Response::new(409, &conflict);
+            }
+            let mut response = Response::new(204, "");
+            let etag = format!("\"{}\"", updated.compute_checksum());
+            response.headers.push(("etag".to_string(), etag));
+            response
+        }
+        _ => {
+            let mut response = Response::new(405, "Only GET and PUT are supported");
+            response
+                .headers
+                .push(("allow".to_string(), "GET, PUT".to_string()));
+            response
+        }
+    }
+}
+
+/// Adds the todo item made from the payload of a `POST` to `/inbox`, or `/inbox/<name>` for
+/// the mapping called `name`, to the list file at `file`.
+fn receive(request: &Request, name: Option<&str>, file: &Path, inbox: &Inbox) -> Response {
+    if request.method != "POST" {
+        let mut response = Response::new(405, "Only POST is supported");
+        response
+            .headers
+            .push(("allow".to_string(), "POST".to_string()));
+        return response;
+    }
+    let mapping = match inbox.mapping(name) {
+        Some(mapping) => mapping,
+        None => {
+            let name = name.unwrap_or_default();
+            return Response::new(404, &format!("No inbox mapping named '{}'", name));
+        }
+    };
+    let form = request
+        .header("content-type")
+        .is_some_and(|kind| kind.starts_with("application/x-www-form-urlencoded"));
+    let payload = match inbox::parse_payload(&request.body, form) {
+        Ok(payload) => payload,
+        Err(error) => return Response::new(400, &error),
+    };
+    let mut list = match read_list(file) {
+        Ok(list) => list,
+        Err(error) => return Response::new(500, &error),
+    };
+    match inbox.add(&mut list, &mapping, &payload, crate::now()) {
+        Ok(id) => {
+            if let Err(conflict) = try_save_todo_list(file, &list) {
+                return Response::new(409, &conflict);
+            }
+            Response::new(201, &format!("{{\"id\":{}}}", id))
+        }
+        Err(error) => Response::new(400, &error),
+    }
+}
+
+/// A page of the todo items matching the parameters of a `GET /todos?tag=work`, in ID
+/// order, with the cursor to ask for the next page with when there are more.
+#[derive(Serialize)]
+struct Page<'a> {
+    todos: Vec<&'a Todo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next: Option<String>,
+}
+
+/// Returns the page of `list` that the query parameters `parameters` ask for, as JSON:
+/// the todo items passing every filter among them, such as `tag=work` or
+/// `due_before=friday`, from after `cursor` and at most `limit` of them.
+fn page(list: &TodoList, parameters: &[(String, String)], now: i64) -> Result<String, String> {
+    let mut filters = Vec::new();
+    let mut limit = PAGE_SIZE;
+    let mut after = 0;
+    for (name, value) in parameters {
+        let invalid = || format!("Invalid {}: '{}'", name, value);
+        // Each parameter stands for a term of the query language, as the list options do
+        let when = |operator: &str| {
+            if value.contains('"') {
+                return Err(invalid());
+            }
+            Ok(format!("{} \"{}\"", operator, value))
+        };
+        let query = match name.as_str() {
+            "tag" | "assignee" | "created_by" | "project" | "location" => {
+                let special = |c: char| c.is_whitespace() || "()<>=!\"'".contains(c);
+                if value.is_empty() || value.contains(special) {
+                    return Err(invalid());
+                }
+                format!("{}:{}", name.replace('_', "-"), value)
+            }
+            "due_before" => when("due <")?,
+            "due_after" => when("due >")?,
+            "modified_since" => when("modified >=")?,
+            "where" => value.clone(),
+            "limit" => {
+                limit = value
+                    .parse()
+                    .ok()
+                    .filter(|limit| (1..=MAX_PAGE_SIZE).contains(limit))
+                    .ok_or_else(|| format!("{}. Expected 1 to {}", invalid(), MAX_PAGE_SIZE))?;
+                continue;
+            }
+            "cursor" => {
+                after = value.parse().map_err(|_| invalid())?;
+                continue;
+            }
+            _ => return Err(format!("Unknown parameter: '{}'", name)),
+        };
+        let query =
+            Query::parse(&query, now).map_err(|error| format!("{}: {}", invalid(), error))?;
+        filters.push(query);
+    }
+    let mut todos: Vec<&Todo> = list
+        .todos
+        .iter()
+        .filter(|todo| todo.id > after && filters.iter().all(|query| query.matches(todo, now)))
+        .collect();
+    todos.sort_by_key(|todo| todo.id);
+    let next = (todos.len() > limit).then(|| todos[limit - 1].id.to_string());
+    todos.truncate(limit);
+    serde_json::to_string(&Page { todos, next }).map_err(|error| error.to_string())
+}
+
+/// Splits the query string of a URL, e.g. `tag=work&due_before=next%20week`, into its
+/// decoded names and values.
+pub fn parse_parameters(query: &str) -> Result<Vec<(String, String)>, String> {
+    query
+        .split('&')
+        .filter(|parameter| !parameter.is_empty())
+        .map(|parameter| {
+            let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            Ok((decode(name)?, decode(value)?))
+        })
+        .collect()
+}
+
+/// Escapes `text` for a query string, the opposite of `decode`.
+pub fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Decodes a component of a query string, where `%20` and `+` stand for a space.
+fn decode(encoded: &str) -> Result<String, String> {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let byte = bytes
+                    .get(index + 1..index + 3)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())
+                    .ok_or_else(|| format!("Invalid escape in '{}'", encoded))?;
+                decoded.push(byte);
+                index += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8(decoded).map_err(|_| format!("'{}' is not UTF-8", encoded))
+}
+
+/// Reads the todo list file at `file`, or an empty list if it does not exist.
+fn read_list(file: &Path) -> Result<TodoList, String> {
+    if !file.exists() {
+        return Ok(TodoList::new());
+    }
+    let contents = std::fs::read_to_string(file)
+        .map_err(|error| format!("Unable to read {}: {}", file.display(), error))?;
+    let list = TodoList::parse(&contents, Format::of(file))
+        .map_err(|error| format!("Unable to parse {}: {}", file.display(), error))?;
+    // Saving then only rebases over what changed while this request was answered
+    crate::rebase::record(file, &contents);
+    Ok(list)
+}
+
+/// An HTTP message: its first line, its headers with lowercase names, and its body.
+type Message = (String, Vec<(String, String)>, String);
+
+/// Reads an HTTP message.
+fn read_message(reader: &mut impl BufRead) -> Result<Message, String> {
+    let first_line = read_line(reader, "the message")?;
+    if first_line.is_empty() {
+        return Err("Empty message".to_string());
+    }
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(reader, "the message")?;
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(format!("Too many headers: over {}", MAX_HEADERS));
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid header: {}", line))?;
+        headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+    }
+    if find_header(&headers, "transfer-encoding")
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"))
+    {
+        let body = read_chunks(reader)?;
+        return Ok((first_line, headers, body));
+    }
+    let length: usize = match find_header(&headers, "content-length") {
+        Some(length) => length
+            .parse()
+            .map_err(|_| format!("Invalid Content-Length: {}", length))?,
+        None => 0,
+    };
+    if length > MAX_BODY {
+        return Err(format!("The body is too large: {} bytes", length));
+    }
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|error| format!("Unable to read the body: {}", error))?;
+    let body = String::from_utf8(body).map_err(|_| "The body is not UTF-8".to_string())?;
+    Ok((first_line, headers, body))
+}
+
+/// Reads a body sent in chunks, each after a line with its length in hex, up to the empty
+/// one and the trailer after it.
+fn read_chunks(reader: &mut impl BufRead) -> Result<String, String> {
+    let mut body = Vec::new();
+    loop {
+        let line = read_line(reader, "the body")?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size =
+            usize::from_str_radix(size, 16).map_err(|_| format!("Invalid chunk size: {}", size))?;
+        if size > MAX_BODY - body.len() {
+            return Err(format!("The body is too large: over {} bytes", MAX_BODY));
+        }
+        if size == 0 {
+            break;
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader
+            .read_exact(&mut body[start..])
+            .map_err(|error| format!("Unable to read the body: {}", error))?;
+        read_line(reader, "the body")?;
+    }
+    // Skip the trailer, whose fields nothing reads
+    for _ in 0..=MAX_HEADERS {
+        if read_line(reader, "the body")?.is_empty() {
+            return String::from_utf8(body).map_err(|_| "The body is not UTF-8".to_string());
+        }
+    }
+    Err(format!("Too many trailer fields: over {}", MAX_HEADERS))
+}
+
+/// Reads a line of at most `MAX_LINE` bytes, without its line ending, or an empty one at
+/// the end of the input. `what` names what is being read, for the error.
+fn read_line(reader: &mut impl BufRead, what: &str) -> Result<String, String> {
+    let mut line = String::new();
+    reader
+        .by_ref()
+        .take(MAX_LINE as u64 + 1)
+        .read_line(&mut line)
+        .map_err(|error| format!("Unable to read {}: {}", what, error))?;
+    if line.len() > MAX_LINE {
+        return Err(format!("A line of {} is over {} bytes", what, MAX_LINE));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// The reason phrase of the HTTP status `status`, among those `serve` answers with.
+pub fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        412 => "Precondition Failed",
+        428 => "Precondition Required",
+        429 => "Too Many Requests",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Runs this command against the list served at `remote`: the list is copied to a cache
+/// file, the command runs on it as a separate invocation of this program, and the result
+/// is sent back if the command changed it. Returns the command's exit status, or 3 if the
+/// served list changed in the meantime and the change was not sent.
+///
+/// When the server cannot be reached, the command runs on the cached copy instead, and a
+/// command that changes it is queued to be replayed on the served list, along with any
+/// others, the next time it can be reached.
+pub fn run(remote: &Remote, token: Option<&str>) -> Result<i32, String> {
+    let arguments = local_arguments(std::env::args_os().skip(1));
+    let cache = remote.cache_file();
+    let queue_file = cache.with_file_name(sync::QUEUE_FILE);
+    let mut queue = Queue::load(&queue_file)?;
+    let (contents, checksum) = match remote.pull(token) {
+        Ok(pulled) => pulled,
+        Err(Error::Unreachable(error)) => {
+            return run_offline(remote, &cache, &mut queue, &queue_file, &arguments, &error)
+        }
+        Err(Error::Rejected(error)) => return Err(error),
+    };
+    let (contents, checksum) = replay(remote, token, &cache, &mut queue, contents, checksum)?;
+    queue.save(&queue_file)?;
+    let status = run_local(&cache, &arguments)?;
+    let updated = read_file(&cache)?;
+    if updated != contents {
+        match remote.push(&updated, &checksum, token) {
+            Ok(Some(etag)) => queue.synced = Some(etag),
+            Ok(None) => {
+                // Undone, so the cached copy is still the served list as last seen
+                write_file(&cache, &contents)?;
+                println!(
+                    "The list on {} changed while this command ran, so the change was not saved there. Run the command again",
+                    remote
+                );
+                return Ok(3);
+            }
+            Err(error) => return Err(error.to_string()),
+        }
+        queue.save(&queue_file)?;
+    }
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Runs a command on the cached copy of the list at `remote`, which could not be reached
+/// because of `error`, queuing it if it changes the list.
+fn run_offline(
+    remote: &Remote,
+    cache: &Path,
+    queue: &mut Queue,
+    queue_file: &Path,
+    arguments: &[OsString],
+    error: &str,
+) -> Result<i32, String> {
+    if !cache.exists() {
+        return Err(format!(
+            "{}, and there is no copy of the list to work on offline",
+            error
+        ));
+    }
+    eprintln!("{}. Working offline on the copy from the last time", error);
+    let before = read_file(cache)?;
+    let status = run_local(cache, arguments)?;
+    let after = read_file(cache)?;
+    if after != before {
+        queue.pending.push(sync::Change {
+            args: arguments
+                .iter()
+                .map(|argument| argument.to_string_lossy().into_owned())
+                .collect(),
+            queued: crate::now(),
+            touched: sync::touched(&parse_list(&before)?, &parse_list(&after)?),
+            conflict: None,
+        });
+        queue.save(queue_file)?;
+        eprintln!(
+            "{} change(s) waiting to be sent to {} (see sync status)",
+            queue.pending.len(),
+            remote
+        );
+    }
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Replays the commands queued offline on the served list, `contents` with the checksum
+/// `checksum`, and sends the result. Returns the served list afterwards, which is also
+/// written to the cache file. Commands whose tasks changed on the server are moved to the
+/// queue's conflicts instead of being replayed, and so are commands that fail when replayed.
+fn replay(
+    remote: &Remote,
+    token: Option<&str>,
+    cache: &Path,
+    queue: &mut Queue,
+    contents: String,
+    checksum: String,
+) -> Result<(String, String), String> {
+    if queue.pending.is_empty() {
+        write_file(cache, &contents)?;
+        queue.synced = Some(checksum.clone());
+        return Ok((contents, checksum));
+    }
+    let mut conflicts = Vec::new();
+    let updated = if queue.synced.as_deref() == Some(checksum.as_str()) {
+        // Nothing changed on the server, so the cached copy already is the served list
+        // with the queued commands applied.
+        read_file(cache)?
+    } else {
+        write_file(cache, &contents)?;
+        for change in &queue.pending {
+            let conflict = |reason: String| sync::Change {
+                conflict: Some(reason),
+                ..change.clone()
+            };
+            if let Err(reason) = change.check(&parse_list(&read_file(cache)?)?) {
+                conflicts.push(conflict(reason));
+                continue;
+            }
+            let output = std::process::Command::new(current_exe()?)
+                .arg("--file")
+                .arg(cache)
+                .arg("--remote=")
+                .args(&change.args)
+                .output()
+                .map_err(|error| format!("Unable to replay a change: {}", error))?;
+            if !output.status.success() {
+                // Commands print most errors on stdout, and panics on stderr
+                let message = match String::from_utf8_lossy(&output.stderr).trim() {
+                    "" => String::from_utf8_lossy(&output.stdout).trim().to_string(),
+                    stderr => stderr.to_string(),
+                };
+                conflicts.push(conflict(format!("It failed when replayed: {}", message)));
+            }
+        }
+        read_file(cache)?
+    };
+    let checksum = if updated == contents {
+        checksum
+    } else {
+        match remote.push(&updated, &checksum, token) {
+            Ok(Some(etag)) => etag,
+            Ok(None) => {
+                return Err(format!(
+                    "The list on {} changed while the changes made offline were sent. They are still queued; run the command again",
+                    remote
+                ))
+            }
+            Err(error) => return Err(error.to_string()),
+        }
+    };
+    let sent = queue.pending.len() - conflicts.len();
+    eprintln!("Sent {} change(s) made offline to {}", sent, remote);
+    for conflict in &conflicts {
+        eprintln!(
+            "Not sent: {}: {}",
+            conflict.command(),
+            conflict.conflict.as_deref().unwrap_or_default()
+        );
+    }
+    queue.pending.clear();
+    queue.conflicts.extend(conflicts);
+    queue.synced = Some(checksum.clone());
+    Ok((updated, checksum))
+}
+
+#[derive(Debug, StructOpt)]
+pub enum SyncAction {
+    /// List the changes waiting to be sent, and those that could not be.
+    #[structopt(name = "status")]
+    Status,
+    /// Send the waiting changes now, rather than with the next command.
+    #[structopt(name = "now")]
+    Now,
+    /// Forget the changes that could not be sent because their tasks changed on the server.
+    #[structopt(name = "discard")]
+    Discard,
+    /// Mirror the Jira issues a search finds as tasks, and move the issues of the tasks
+    /// removed since the last sync through the done transition.
+    #[structopt(name = "jira")]
+    Jira {
+        /// The search, e.g. "assignee = currentUser() AND status != Done". Defaults to the
+        /// configured jira_jql.
+        #[structopt(long)]
+        jql: Option<String>,
+        /// The transition to move the issues of removed tasks through, e.g. "Resolve".
+        /// Defaults to the configured jira_transition, or "Done".
+        #[structopt(long, value_name = "name")]
+        transition: Option<String>,
+    },
+    /// Mirror the Asana tasks assigned to you in some projects as tasks, and complete those
+    /// of the tasks removed since the last sync.
+    #[structopt(name = "asana")]
+    Asana {
+        /// The ID of a project, as in its URL, e.g. 1204512345678901. Defaults to the
+        /// configured asana_projects.
+        #[structopt(long = "project", value_name = "id")]
+        projects: Vec<String>,
+    },
+}
+
+/// Shows, sends or discards the commands queued while `remote` could not be reached.
+pub fn sync(
+    remote: &Remote,
+    token: Option<&str>,
+    action: &SyncAction,
+    dates: &DateFormat,
+) -> Result<i32, String> {
+    // Mirroring a tracker changes the list as any other command does
+    if let SyncAction::Jira { .. } | SyncAction::Asana { .. } = action {
+        return run(remote, token);
+    }
+    let cache = remote.cache_file();
+    let queue_file = cache.with_file_name(sync::QUEUE_FILE);
+    let mut queue = Queue::load(&queue_file)?;
+    match action {
+        SyncAction::Status => {
+            let now = crate::now();
+            let print = |changes: &[sync::Change]| {
+                for (number, change) in (1..).zip(changes) {
+                    println!(
+                        "  {}. {} (queued {})",
+                        number,
+                        change.command(),
+                        dates.format(change.queued, now, "%Y-%m-%d %H:%M")
+                    );
+                    if let Some(conflict) = &change.conflict {
+                        println!("     {}", conflict);
+                    }
+                }
+            };
+            if queue.pending.is_empty() && queue.conflicts.is_empty() {
+                println!("Nothing waiting to be sent to {}", remote);
+            }
+            if !queue.pending.is_empty() {
+                println!("Waiting to be sent to {}:", remote);
+                print(&queue.pending);
+            }
+            if !queue.conflicts.is_empty() {
+                println!("Not sent, because their tasks changed on the server (sync discard forgets them):");
+                print(&queue.conflicts);
+            }
+        }
+        SyncAction::Now => {
+            let (contents, checksum) = remote.pull(token).map_err(|error| match error {
+                Error::Unreachable(error) if !queue.pending.is_empty() => format!(
+                    "{}. {} change(s) are still waiting to be sent",
+                    error,
+                    queue.pending.len()
+                ),
+                error => error.to_string(),
+            })?;
+            if queue.pending.is_empty() {
+                println!("Nothing waiting to be sent to {}", remote);
+            }
+            replay(remote, token, &cache, &mut queue, contents, checksum)?;
+            queue.save(&queue_file)?;
+        }
+        SyncAction::Discard => {
+            println!(
+                "Discarded {} change(s) that could not be sent",
+                queue.conflicts.len()
+            );
+            queue.conflicts.clear();
+            queue.save(&queue_file)?;
+        }
+        SyncAction::Jira { .. } | SyncAction::Asana { .. } => unreachable!("Run above"),
+    }
+    Ok(0)
+}
+
+/// Runs a command on the list file at `file`, as a separate invocation of this program.
+fn run_local(file: &Path, arguments: &[OsString]) -> Result<std::process::ExitStatus, String> {
+    std::process::Command::new(current_exe()?)
+        .arg("--file")
+        .arg(file)
+        .arg("--remote=")
+        .args(arguments)
+        .status()
+        .map_err(|error| format!("Unable to run the command: {}", error))
+}
+
+fn current_exe() -> Result<PathBuf, String> {
+    std::env::current_exe().map_err(|error| format!("Unable to find this program: {}", error))
+}
+
+fn read_file(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path)
+        .map_err(|error| format!("Unable to read {}: {}", path.display(), error))
+}
+
+/// Writes `contents` to `path`, creating its directory if needed.
+fn write_file(path: &Path, contents: &str) -> Result<(), String> {
+    std::fs::create_dir_all(list_directory(path))
+        .and_then(|_| std::fs::write(path, contents))
+        .map_err(|error| format!("Unable to write {}: {}", path.display(), error))
+}
+
+fn parse_list(contents: &str) -> Result<TodoList, String> {
+    serde_json::from_str(contents).map_err(|error| format!("Unable to parse the list: {}", error))
+}
+
+/// Returns the command-line arguments without `--remote` and `--file`, which the remote
+/// run replaces.
+fn local_arguments(arguments: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    let mut kept = Vec::new();
+    let mut skip_value = false;
+    for argument in arguments {
+        if skip_value {
+            skip_value = false;
+            continue;
+        }
+        match argument.to_str() {
+            Some("--remote" | "--file" | "-f") => skip_value = true,
+            Some(option)
+                if option.starts_with("--remote=")
+                    || option.starts_with("--file=")
+                    || (option.starts_with("-f") && !option.starts_with("--")) =>
+            {
+                continue
+            }
+            _ => kept.push(argument),
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode, encode, handle, local_arguments, page, parse_tokens, read_message, Access, Inbox,
+        OsString, RateLimit, Remote, Request, Scope, TodoList, MAX_BODY, MAX_HEADERS, MAX_LINE,
+        PARAMETERS,
+    };
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Remote::parse("http://nas.local:9000/todo/"),
+            Ok(Remote {
+                https: false,
+                host: "nas.local".to_string(),
+                port: 9000,
+                prefix: "/todo".to_string(),
+                ca: None,
+            })
+        );
+        assert_eq!(Remote::parse("http://nas.local").unwrap().port, 8080);
+        let https = Remote::parse("https://nas.local:8443").unwrap();
+        assert!(https.https);
+        assert_eq!(https.to_string(), "https://nas.local:8443");
+        assert!(Remote::parse("nas.local:8080").is_err());
+        assert!(Remote::parse("http://nas.local:80a").is_err());
+        let jira = Remote::parse_with_port("https://acme.atlassian.net", 443).unwrap();
+        assert_eq!(jira.to_string(), "https://acme.atlassian.net:443");
+    }
+
+    #[test]
+    fn test_read_chunks() {
+        let message = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                       4\r\n{\"a\"\r\n3;x=1\r\n: 1\r\n1\r\n}\r\n0\r\nTrailer: t\r\n\r\n";
+        let (_, _, body) = read_message(&mut message.as_bytes()).unwrap();
+        assert_eq!(body, "{\"a\": 1}");
+        let invalid = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n";
+        assert!(read_message(&mut invalid.as_bytes()).is_err());
+        // A chunk size that would overflow the body's length is refused, not added
+        let huge = "PUT /todos HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                    1\r\nx\r\nffffffffffffffff\r\n";
+        assert_eq!(
+            read_message(&mut huge.as_bytes()).unwrap_err(),
+            format!("The body is too large: over {} bytes", MAX_BODY)
+        );
+        let long = format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", "a".repeat(MAX_LINE));
+        assert!(read_message(&mut long.as_bytes())
+            .unwrap_err()
+            .contains("over 8192 bytes"));
+        let many = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X: 1\r\n".repeat(MAX_HEADERS + 1)
+        );
+        assert!(read_message(&mut many.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_local_arguments() {
+        let arguments = ["--remote", "http://nas", "-f", "a.json", "add", "Call", "4"]
+            .iter()
+            .map(OsString::from);
+        assert_eq!(local_arguments(arguments), ["add", "Call", "4"]);
+        let arguments = ["list", "--remote=http://nas", "--file=a.json"]
+            .iter()
+            .map(OsString::from);
+        assert_eq!(local_arguments(arguments), ["list"]);
+    }
+
+    #[test]
+    fn test_handle() {
+        let directory = std::env::temp_dir().join(format!("todo-remote-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let file = directory.join("todos.json");
+        let request = |method: &str, headers: &[(&str, &str)], body: &str| Request {
+            method: method.to_string(),
+            path: "/todos".to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: body.to_string(),
+        };
+        let open = Access::default();
+        let inbox = Inbox::default();
+        let response = handle(&request("GET", &[], ""), &file, &open, &inbox);
+        assert_eq!(response.status, 200);
+        let etag = response.header("etag").unwrap().to_string();
+
+        let body = r#"{ "todos": [{ "id": 1, "task": "Pay rent", "priority": 5, "created": 0 }] }"#;
+        assert_eq!(
+            handle(&request("PUT", &[], body), &file, &open, &inbox).status,
+            428
+        );
+        let response = handle(
+            &request("PUT", &[("if-match", &etag)], body),
+            &file,
+            &open,
+            &inbox,
+        );
+        assert_eq!(response.status, 204);
+        assert_ne!(response.header("etag"), Some(etag.as_str()));
+        assert_eq!(
+            handle(
+                &request("PUT", &[("if-match", &etag)], body),
+                &file,
+                &open,
+                &inbox
+            )
+            .status,
+            412
+        );
+        let response = handle(&request("GET", &[], ""), &file, &open, &inbox);
+        assert!(response.body.contains("Pay rent"));
+
+        let tokens = directory.join("tokens");
+        std::fs::write(&tokens, "# The phone\nr3ad read-only\n").unwrap();
+        let access = Access {
+            tokens: [("s3cret".to_string(), Scope::ReadWrite)].into(),
+            file: Some(tokens.clone()),
+        };
+        let response = handle(&request("GET", &[], ""), &file, &access, &inbox);
+        assert_eq!(response.status, 401);
+        assert_eq!(response.header("www-authenticate"), Some("Bearer"));
+        let wrong = [("authorization", "Bearer s3cre")];
+        assert_eq!(
+            handle(&request("GET", &wrong, ""), &file, &access, &inbox).status,
+            401
+        );
+        let authorized = [("authorization", "Bearer s3cret")];
+        assert_eq!(
+            handle(&request("GET", &authorized, ""), &file, &access, &inbox).status,
+            200
+        );
+        let etag = handle(&request("GET", &[], ""), &file, &open, &inbox)
+            .header("etag")
+            .unwrap()
+            .to_string();
+        let read_only = [("authorization", "Bearer r3ad"), ("if-match", &etag)];
+        assert_eq!(
+            handle(&request("GET", &read_only, ""), &file, &access, &inbox).status,
+            200
+        );
+        assert_eq!(
+            handle(&request("PUT", &read_only, body), &file, &access, &inbox).status,
+            403
+        );
+        // Revoked by editing the file, without restarting
+        std::fs::write(&tokens, "").unwrap();
+        assert_eq!(
+            handle(&request("GET", &read_only, ""), &file, &access, &inbox).status,
+            401
+        );
+        assert!(parse_tokens("abc read-write\n\nxyz admin")
+            .unwrap_err()
+            .contains("Line 3"));
+        assert!(parse_tokens("abc").is_err());
+        assert_eq!(
+            handle(&request("DELETE", &[], ""), &file, &open, &inbox).status,
+            405
+        );
+
+        // The inbox needs a token allowed to change the list, which can be in the URL
+        let post = |path: &str, body: &str| Request {
+            method: "POST".to_string(),
+            path: path.to_string(),
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: body.to_string(),
+        };
+        let task = r#"{ "task": "Call Sam", "priority": 4 }"#;
+        std::fs::write(&tokens, "r3ad read-only\n").unwrap();
+        assert_eq!(
+            handle(&post("/inbox", task), &file, &access, &inbox).status,
+            401
+        );
+        let read_only = post("/inbox?token=r3ad", task);
+        assert_eq!(handle(&read_only, &file, &access, &inbox).status, 403);
+        let response = handle(&post("/inbox?token=s3cret", task), &file, &access, &inbox);
+        assert_eq!(
+            (response.status, response.body.as_str()),
+            (201, r#"{"id":2}"#)
+        );
+        assert_eq!(
+            handle(&post("/inbox", "{}"), &file, &open, &inbox).status,
+            400
+        );
+        assert_eq!(
+            handle(&post("/inbox/jira", task), &file, &open, &inbox).status,
+            404
+        );
+        // A token in the URL is only taken for the inbox
+        let get = Request {
+            path: "/todos?token=s3cret".to_string(),
+            ..request("GET", &[], "")
+        };
+        assert_eq!(handle(&get, &file, &access, &inbox).status, 401);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_page() {
+        let directory = std::env::temp_dir().join(format!("todo-page-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let file = directory.join("todos.json");
+        let todos: Vec<String> = (1..=5)
+            .map(|id| {
+                let tags = if id % 2 == 1 {
+                    r#", "tags": ["work"]"#
+                } else {
+                    ""
+                };
+                format!(
+                    r#"{{ "id": {}, "task": "Task {}", "priority": 3, "created": 0{} }}"#,
+                    id, id, tags
+                )
+            })
+            .collect();
+        std::fs::write(&file, format!(r#"{{ "todos": [{}] }}"#, todos.join(", "))).unwrap();
+        let get = |path: &str| {
+            let request = Request {
+                method: "GET".to_string(),
+                path: path.to_string(),
+                headers: Vec::new(),
+                body: String::new(),
+            };
+            let response = handle(&request, &file, &Access::default(), &Inbox::default());
+            let body: serde_json::Value = serde_json::from_str(&response.body).unwrap_or_default();
+            (response.status, body)
+        };
+        let ids = |page: &serde_json::Value| -> Vec<u64> {
+            page["todos"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|todo| todo["id"].as_u64().unwrap())
+                .collect()
+        };
+
+        let (status, page) = get("/todos?tag=work&limit=2");
+        assert_eq!(status, 200);
+        assert_eq!(ids(&page), [1, 3]);
+        assert_eq!(page["next"], "3");
+        let (_, page) = get("/todos?tag=work&limit=2&cursor=3");
+        assert_eq!(ids(&page), [5]);
+        assert!(page.get("next").is_none());
+        let (_, page) = get("/todos?where=%21tag%3Awork+and+id+%3C+4");
+        assert_eq!(ids(&page), [2]);
+        // Without parameters, the whole list file as before
+        let (_, list) = get("/todos");
+        assert!(list.get("next").is_none());
+        assert_eq!(ids(&list).len(), 5);
+
+        assert_eq!(get("/todos?limit=0").0, 400);
+        assert_eq!(get("/todos?tag=a%20b").0, 400);
+        assert_eq!(get("/todos?color=red").0, 400);
+        assert_eq!(get("/todos?due_before=someday").0, 400);
+        assert_eq!(decode("next+week%21").unwrap(), "next week!");
+        assert!(decode("%2").is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_parameters() {
+        let list = TodoList::new();
+        for (name, _) in PARAMETERS {
+            let parameters = [(name.to_string(), "1".to_string())];
+            let error = page(&list, &parameters, 0).err().unwrap_or_default();
+            assert!(!error.starts_with("Unknown parameter"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        let text = "a b&c=d/é~";
+        assert_eq!(encode(text), "a%20b%26c%3Dd%2F%C3%A9~");
+        assert_eq!(decode(&encode(text)).unwrap(), text);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut limit = RateLimit::new(Some(2));
+        assert_eq!(limit.check("s3cret", 120), Ok(()));
+        assert_eq!(limit.check("s3cret", 150), Ok(()));
+        assert_eq!(limit.check("s3cret", 170), Err(10));
+        assert_eq!(limit.check("10.0.0.2", 170), Ok(()));
+        // A new minute
+        assert_eq!(limit.check("s3cret", 180), Ok(()));
+        let mut unlimited = RateLimit::new(None);
+        assert!((0..100).all(|_| unlimited.check("s3cret", 0).is_ok()));
+    }
+}
@@ -0,0 +1,203 @@
+//! The setup offered the first time the application runs, with neither a config file nor a
+//! todo list in the current directory, so the list does not end up wherever it happens to
+//! be run.
+//!
+//! It asks where to keep the list, the default priority, whether to color output and the
+//! timezone, and returns the answers as settings for the config file. Declining writes an
+//! empty config file instead, so that it is not offered again.
+
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use todo::dates::Zone;
+use todo::priorities::Scale;
+
+/// What the user chose.
+#[derive(Debug, PartialEq)]
+pub enum Setup {
+    /// The settings to write, as `config set` takes them.
+    Settings(Vec<(&'static str, String)>),
+    /// Not now, and not again.
+    Declined,
+}
+
+/// Returns where the todo list is suggested to go: `$XDG_DATA_HOME/todo/todos.json` or
+/// `~/.local/share/todo/todos.json`, or `None` without either variable.
+pub fn default_file() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+        })?;
+    Some(base.join("todo").join("todos.json"))
+}
+
+/// Prints `question` and reads answers from `input` until `check` accepts one, an empty one
+/// standing for `default`. Returns `None` at the end of the input.
+fn ask<T>(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+    default: &str,
+    check: impl Fn(&str) -> Result<T, String>,
+) -> Option<T> {
+    loop {
+        let _ = write!(output, "{} [{}]: ", question, default);
+        let _ = output.flush();
+        let mut answer = String::new();
+        match input.read_line(&mut answer) {
+            Ok(0) | Err(_) => return None,
+            Ok(_) => {}
+        }
+        let answer = match answer.trim() {
+            "" => default,
+            answer => answer,
+        };
+        match check(answer) {
+            Ok(value) => return Some(value),
+            Err(error) => {
+                let _ = writeln!(output, "{}", error);
+            }
+        }
+    }
+}
+
+/// Reads a yes or no answer.
+fn yes_or_no(answer: &str) -> Result<bool, String> {
+    match answer.to_lowercase().as_str() {
+        "y" | "yes" => Ok(true),
+        "n" | "no" => Ok(false),
+        _ => Err("Please answer y or n".to_string()),
+    }
+}
+
+/// Walks the user through the setup, reading answers from `input` and writing questions to
+/// `output`, with `default_file` suggested for the todo list and the default priority taken
+/// from `scale`. Returns `None` if the input ends before every question is answered.
+pub fn run(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    default_file: Option<PathBuf>,
+    scale: &Scale,
+) -> Option<Setup> {
+    let _ = writeln!(
+        output,
+        "No settings or todo list were found. Setting things up writes a config file, so \
+         your list is kept in one place wherever you run this."
+    );
+    if !ask(input, output, "Set up now? (y/n)", "y", yes_or_no)? {
+        return Some(Setup::Declined);
+    }
+    let suggested = default_file
+        .map(|file| file.display().to_string())
+        .unwrap_or_else(|| "todos.json".to_string());
+    let file = ask(
+        input,
+        output,
+        "Where should the todo list be kept?",
+        &suggested,
+        |file| {
+            let file = match (file.strip_prefix("~/"), std::env::var_os("HOME")) {
+                (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+                _ => PathBuf::from(file),
+            };
+            // A relative path would follow wherever this runs
+            let file = match std::env::current_dir() {
+                Ok(dir) => dir.join(file),
+                Err(_) => file,
+            };
+            Ok(file.display().to_string())
+        },
+    )?;
+    // The middle of the scale is suggested, 3 of the default 1 to 5
+    let priority = ask(
+        input,
+        output,
+        &format!("Default priority of new tasks, from {}", scale.expected()),
+        &scale.levels().div_ceil(2).to_string(),
+        |priority| {
+            scale
+                .parse(priority)
+                .map_err(|_| format!("Please give a number from {}", scale.expected()))
+        },
+    )?;
+    let color = ask(input, output, "Color the output? (y/n)", "y", yes_or_no)?;
+    let timezone = ask(
+        input,
+        output,
+        "Timezone: utc, local, a name such as Europe/Paris or an offset such as +02:00",
+        "local",
+        |zone| Zone::parse(zone).map(|_| zone.to_string()),
+    )?;
+    let color = match color {
+        true => "auto",
+        false => "never",
+    };
+    Some(Setup::Settings(vec![
+        ("file", file),
+        ("default_priority", priority.to_string()),
+        ("color", color.to_string()),
+        ("timezone", timezone),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, Setup};
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use todo::priorities::{Level, Scale};
+
+    #[test]
+    fn test_run() {
+        let scale = Scale::default();
+        let suggested = Some(PathBuf::from("/home/alice/.local/share/todo/todos.json"));
+        let mut output = Vec::new();
+        let mut input = Cursor::new("\n\n7\n4\nn\nmars\n+02:00\n");
+        assert_eq!(
+            run(&mut input, &mut output, suggested.clone(), &scale),
+            Some(Setup::Settings(vec![
+                (
+                    "file",
+                    "/home/alice/.local/share/todo/todos.json".to_string()
+                ),
+                ("default_priority", "4".to_string()),
+                ("color", "never".to_string()),
+                ("timezone", "+02:00".to_string()),
+            ]))
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Please give a number from 1 to 5\n"));
+        assert!(output.contains("Invalid timezone: 'mars'"));
+
+        let mut input = Cursor::new("no\n");
+        assert_eq!(
+            run(&mut input, &mut Vec::new(), suggested.clone(), &scale),
+            Some(Setup::Declined)
+        );
+        let mut input = Cursor::new("y\n~/todos.json\n");
+        assert_eq!(
+            run(&mut input, &mut Vec::new(), suggested.clone(), &scale),
+            None
+        );
+
+        // The bounds and names of the configured priorities
+        let level = |name: &str| Level {
+            name: Some(name.to_string()),
+            color: None,
+        };
+        let scale = Scale::new(&[level("low"), level("medium"), level("high")]).unwrap();
+        let mut output = Vec::new();
+        let mut input = Cursor::new("\n\n5\nHigh\n\n\n");
+        let Some(Setup::Settings(settings)) = run(&mut input, &mut output, suggested, &scale)
+        else {
+            panic!("Not set up");
+        };
+        assert_eq!(settings[1], ("default_priority", "3".to_string()));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output
+            .contains("Default priority of new tasks, from 1 to 3, or low, medium or high [2]"));
+        assert!(output.contains("Please give a number from 1 to 3, or low, medium or high\n"));
+    }
+}
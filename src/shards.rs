@@ -0,0 +1,280 @@
+//! Sharded lists: a directory holding each project's todo items in a list file of its own,
+//! with a small index of the files, for lists too big to read and write whole on every
+//! command.
+//!
+//! The `file` is a directory instead of a list file: one that exists, or a path ending in
+//! `/` to start one. Every shard is an ordinary JSON list file with its own checksum and
+//! backups, and items without a project share one too. Items keep the IDs they have in the
+//! whole list, so a command reading only some projects, e.g. `list --where project:work`,
+//! shows the same IDs as one reading them all. Saving rewrites only the shards whose items
+//! changed.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::formats::Format;
+use crate::store::rotate_backups;
+use crate::{fold, TodoList};
+
+/// The index of a sharded list, in its directory.
+pub const INDEX_FILE: &str = "index.json";
+
+/// The name of the shard of the todo items without a project.
+const UNFILED: &str = "unfiled";
+
+/// Which file holds each project's todo items.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Index {
+    /// When a shard was last written, so that watching the index notices every change.
+    #[serde(default)]
+    pub saved: i64,
+    /// The shards by project, the one of the items without a project under `""`.
+    #[serde(default)]
+    pub projects: BTreeMap<String, Shard>,
+}
+
+/// A project's list file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shard {
+    /// The file's name, in the list's directory.
+    pub file: String,
+    /// How many todo items it holds.
+    pub items: usize,
+}
+
+/// Returns whether the todo list at `path` is sharded: an existing directory, or a path
+/// ending in a separator.
+pub fn is_sharded(path: &Path) -> bool {
+    path.is_dir()
+        || path
+            .as_os_str()
+            .to_string_lossy()
+            .ends_with(std::path::is_separator)
+}
+
+impl Index {
+    /// Reads the index of the sharded list in `dir`, which is empty if there is none yet.
+    pub fn load(dir: &Path) -> Result<Index, String> {
+        let path = dir.join(INDEX_FILE);
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|error| format!("Unable to parse {}: {}", path.display(), error)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Index::default()),
+            Err(error) => Err(format!("Unable to read {}: {}", path.display(), error)),
+        }
+    }
+
+    /// Returns the projects whose shards hold todo items of one of `projects`, compared as
+    /// queries compare them, so that a shard a query could match is never left out.
+    fn matching(&self, projects: &BTreeSet<String>) -> BTreeSet<String> {
+        let keys: BTreeSet<String> = projects
+            .iter()
+            .map(|project| fold::key(project, true))
+            .collect();
+        self.projects
+            .keys()
+            .filter(|project| !project.is_empty() && keys.contains(&fold::key(project, true)))
+            .cloned()
+            .collect()
+    }
+
+    /// Names a new shard for `project`, from its name and unlike any other file's.
+    fn file_for(&self, project: &str) -> String {
+        let slug: String = match project {
+            "" => UNFILED.to_string(),
+            project => project
+                .to_lowercase()
+                .chars()
+                .map(|c| if c.is_alphanumeric() { c } else { '-' })
+                .collect(),
+        };
+        let slug = match slug.trim_matches('-') {
+            "" => "project",
+            slug => slug,
+        };
+        let taken = |file: &str| {
+            file == INDEX_FILE || self.projects.values().any(|shard| shard.file == file)
+        };
+        (1..)
+            .map(|n| match n {
+                1 => format!("{}.json", slug),
+                n => format!("{}-{}.json", slug, n),
+            })
+            .find(|file| !taken(file))
+            .expect("Some file name is free")
+    }
+}
+
+/// Loads the sharded list in `dir`: every shard, or with `only` those of the projects in it,
+/// leaving the items without a project out too. A shard whose checksum does not match its
+/// items is warned about, as a list file is.
+pub fn load(dir: &Path, only: Option<&BTreeSet<String>>) -> Result<TodoList, String> {
+    let index = Index::load(dir)?;
+    let loaded = only.map(|projects| index.matching(projects));
+    let mut todo_list = TodoList::new();
+    for (project, shard) in &index.projects {
+        if loaded
+            .as_ref()
+            .is_some_and(|loaded| !loaded.contains(project))
+        {
+            continue;
+        }
+        let path = dir.join(&shard.file);
+        let contents = fs::read_to_string(&path)
+            .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+        let shard = TodoList::parse(&contents, Format::of(&path))
+            .map_err(|error| format!("Unable to parse {}: {}", path.display(), error))?;
+        if !shard.verify_checksum() {
+            eprintln!(
+                "WARNING: {} does not match its checksum. It was modified outside of this tool or is corrupted.",
+                path.display()
+            );
+        }
+        todo_list.todos.extend(shard.todos);
+    }
+    todo_list.todos.sort_by_key(|todo| todo.id);
+    todo_list.shards = loaded;
+    Ok(todo_list)
+}
+
+/// Saves `todo_list` as the sharded list in `dir` at the timestamp `now`, writing only the
+/// shards whose items changed. The shards of projects left without items are removed,
+/// unless the list was loaded without them.
+pub fn save(dir: &Path, todo_list: &TodoList, now: i64) -> Result<(), String> {
+    fs::create_dir_all(dir)
+        .map_err(|error| format!("Unable to create {}: {}", dir.display(), error))?;
+    let mut index = Index::load(dir)?;
+    let mut projects: BTreeMap<String, TodoList> = BTreeMap::new();
+    for todo in &todo_list.todos {
+        let project = todo.project.clone().unwrap_or_default();
+        projects
+            .entry(project)
+            .or_default()
+            .todos
+            .push(todo.clone());
+    }
+    let mut changed = false;
+    let emptied: Vec<String> = index
+        .projects
+        .keys()
+        .filter(|project| !projects.contains_key(*project))
+        .filter(|project| {
+            todo_list
+                .shards
+                .as_ref()
+                .is_none_or(|loaded| loaded.contains(*project))
+        })
+        .cloned()
+        .collect();
+    for project in emptied {
+        if let Some(shard) = index.projects.remove(&project) {
+            let path = dir.join(&shard.file);
+            fs::remove_file(&path)
+                .map_err(|error| format!("Unable to remove {}: {}", path.display(), error))?;
+            changed = true;
+        }
+    }
+    for (project, shard) in projects {
+        let file = match index.projects.get(&project) {
+            Some(existing) => existing.file.clone(),
+            None => index.file_for(&project),
+        };
+        let path = dir.join(&file);
+        let contents = shard.to_format(Format::of(&path));
+        if fs::read_to_string(&path).is_ok_and(|existing| existing == contents) {
+            continue;
+        }
+        if path.exists() {
+            rotate_backups(&path);
+        }
+        fs::write(&path, contents)
+            .map_err(|error| format!("Unable to write {}: {}", path.display(), error))?;
+        let items = shard.todos.len();
+        index.projects.insert(project, Shard { file, items });
+        changed = true;
+    }
+    if !changed {
+        return Ok(());
+    }
+    index.saved = now;
+    let path = dir.join(INDEX_FILE);
+    let contents = serde_json::to_string_pretty(&index).expect("Unable to serialize the index");
+    fs::write(&path, contents)
+        .map_err(|error| format!("Unable to write {}: {}", path.display(), error))
+}
+
+/// Returns the file to watch for changes to the todo list at `path`: its index if it is
+/// sharded.
+pub fn watched(path: &Path) -> PathBuf {
+    match is_sharded(path) {
+        true => path.join(INDEX_FILE),
+        false => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, save, Index, INDEX_FILE};
+    use crate::TodoList;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_shards() {
+        let dir = std::env::temp_dir().join(format!("todo-shards-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut todo_list = TodoList::new();
+        for task in [
+            "Draft the spec",
+            "Water the plants",
+            "Fix the build",
+            "Call mum",
+        ] {
+            todo_list.add_todo(task, 3);
+        }
+        todo_list.todos[0].project = Some("Work".to_string());
+        todo_list.todos[2].project = Some("Work".to_string());
+        todo_list.todos[1].project = Some("home/garden".to_string());
+        save(&dir, &todo_list, 100).unwrap();
+
+        let index = Index::load(&dir).unwrap();
+        let files: Vec<&str> = index
+            .projects
+            .values()
+            .map(|shard| shard.file.as_str())
+            .collect();
+        assert_eq!(files, ["unfiled.json", "work.json", "home-garden.json"]);
+        assert_eq!(index.projects["Work"].items, 2);
+        assert_eq!(index.saved, 100);
+
+        // All of it, in the order of the IDs, or only the shards a query could match
+        let ids = |todo_list: &TodoList| -> Vec<u64> {
+            todo_list.todos.iter().map(|todo| todo.id).collect()
+        };
+        let loaded = load(&dir, None).unwrap();
+        assert_eq!(ids(&loaded), [1, 2, 3, 4]);
+        assert_eq!(loaded.todos[1].task, "Water the plants");
+        let work = BTreeSet::from(["work".to_string()]);
+        let partial = load(&dir, Some(&work)).unwrap();
+        assert_eq!(ids(&partial), [1, 3]);
+
+        // Saving what was partly loaded leaves the other shards, and an unchanged one is
+        // not written again
+        let mut partial = partial;
+        partial.todos.retain(|todo| todo.id == 1);
+        save(&dir, &partial, 200).unwrap();
+        assert_eq!(load(&dir, None).unwrap().todos.len(), 3);
+        save(&dir, &load(&dir, None).unwrap(), 300).unwrap();
+        assert_eq!(Index::load(&dir).unwrap().saved, 200);
+
+        // A project left without items loses its shard
+        let mut whole = load(&dir, None).unwrap();
+        whole.todos.retain(|todo| todo.project.is_some());
+        save(&dir, &whole, 400).unwrap();
+        assert!(!dir.join("unfiled.json").exists());
+        assert!(dir.join(INDEX_FILE).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+//! Signing and verification of todo list files with SSH (ed25519) keys.
+//!
+//! The work is delegated to OpenSSH's `ssh-keygen -Y`, the same mechanism git uses for SSH
+//! commit signing, so teammates can reuse their existing keys and `allowed_signers` files.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The signature namespace, which stops a todo list signature being reused for anything else.
+pub const NAMESPACE: &str = "todo";
+
+/// Returns the path of the detached signature for `file`.
+pub fn signature_path(file: &Path) -> PathBuf {
+    let mut path = file.as_os_str().to_owned();
+    path.push(".sig");
+    PathBuf::from(path)
+}
+
+/// Signs `file` with the private key at `key`, writing the signature next to it as
+/// `<file>.sig`.
+///
+/// # Example
+///
+/// ```
+/// sign_file(Path::new("./todos.json"), Path::new("~/.ssh/id_ed25519"))?;
+/// ```
+pub fn sign_file(file: &Path, key: &Path) -> Result<PathBuf, String> {
+    let signature = signature_path(file);
+    // ssh-keygen refuses to overwrite an existing signature.
+    if signature.exists() {
+        std::fs::remove_file(&signature).map_err(|error| error.to_string())?;
+    }
+    let output = Command::new("ssh-keygen")
+        .args(["-Y", "sign", "-n", NAMESPACE, "-f"])
+        .arg(key)
+        .arg(file)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| format!("Unable to run ssh-keygen: {}", error))?;
+    if output.status.success() {
+        Ok(signature)
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Checks that `file` was signed by `signer`, whose public key must be listed in the
+/// `allowed_signers` file (see `ssh-keygen(1)`).
+///
+/// # Example
+///
+/// ```
+/// verify_file(Path::new("./todos.json"), "lead@example.com", Path::new("./allowed_signers"))?;
+/// ```
+pub fn verify_file(file: &Path, signer: &str, allowed_signers: &Path) -> Result<(), String> {
+    let signature = signature_path(file);
+    if !signature.exists() {
+        return Err(format!("No signature found at {}", signature.display()));
+    }
+    let contents = std::fs::File::open(file)
+        .map_err(|error| format!("Unable to open {}: {}", file.display(), error))?;
+    let output = Command::new("ssh-keygen")
+        .args(["-Y", "verify", "-n", NAMESPACE, "-I", signer, "-f"])
+        .arg(allowed_signers)
+        .arg("-s")
+        .arg(&signature)
+        .stdin(contents)
+        .output()
+        .map_err(|error| format!("Unable to run ssh-keygen: {}", error))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Err(format!("{}{}", stdout, stderr).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::signature_path;
+    use std::path::Path;
+
+    #[test]
+    fn test_signature_path() {
+        assert_eq!(
+            signature_path(Path::new("./shared/todos.json")),
+            Path::new("./shared/todos.json.sig")
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Ad-hoc SQL queries over the todo list.
+//!
+//! The list is loaded into an in-memory database of the `sqlite3` command-line shell, so any
+//! SQLite query works, including joins, aggregates and date functions. Times are stored as
+//! `YYYY-MM-DD HH:MM:SS` text in Utc, which compares correctly with `date('now')`.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::process::{Command, Stdio};
+
+use chrono::DateTime;
+
+use crate::Todo;
+
+/// The tables the list is loaded into, shown by `sql --schema`.
+pub const SCHEMA: &str = "CREATE TABLE todos (
+  id INTEGER PRIMARY KEY,
+  task TEXT NOT NULL,
+  priority INTEGER NOT NULL,
+  created TEXT NOT NULL,
+  modified TEXT NOT NULL,
+  due TEXT,
+  recur TEXT,
+  project TEXT,
+  assignee TEXT,
+  created_by TEXT,
+  location TEXT,
+  url TEXT,
+  tags TEXT NOT NULL,
+  attachments INTEGER NOT NULL
+);
+CREATE TABLE tags (
+  todo_id INTEGER NOT NULL REFERENCES todos (id),
+  tag TEXT NOT NULL
+);
+";
+
+/// How `sqlite3` prints the result of a query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputMode {
+    /// Aligned columns with a header, for reading.
+    Table,
+    /// CSV with a header.
+    Csv,
+    /// A JSON array of objects.
+    Json,
+    /// A `column = value` line per column and a blank line between rows, for screen
+    /// readers.
+    Lines,
+}
+
+impl std::str::FromStr for OutputMode {
+    type Err = String;
+    fn from_str(mode: &str) -> Result<OutputMode, String> {
+        match mode {
+            "table" => Ok(OutputMode::Table),
+            "csv" => Ok(OutputMode::Csv),
+            "json" => Ok(OutputMode::Json),
+            _ => Err(format!("Unknown format: {}", mode)),
+        }
+    }
+}
+impl OutputMode {
+    fn flags(self) -> &'static [&'static str] {
+        match self {
+            OutputMode::Table => &["-header", "-column"],
+            OutputMode::Csv => &["-header", "-csv"],
+            OutputMode::Json => &["-json"],
+            OutputMode::Lines => &["-line"],
+        }
+    }
+}
+
+/// Runs `query` against the todo items and returns what `sqlite3` printed.
+///
+/// # Example
+///
+/// ```
+/// let output = sql::query(
+///     &todo_list.todos,
+///     "SELECT task, priority FROM todos WHERE due < date('now') ORDER BY priority DESC",
+///     OutputMode::Table,
+/// )?;
+/// ```
+pub fn query(todos: &[Todo], query: &str, mode: OutputMode) -> Result<String, String> {
+    let mut child = Command::new("sqlite3")
+        .args(mode.flags())
+        .args(["-bail", ":memory:"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Unable to run sqlite3, which must be installed: {}", error))?;
+    let mut script = load_script(todos);
+    script.push_str(query.trim().trim_end_matches(';'));
+    script.push_str(";\n");
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(script.as_bytes())
+            .map_err(|error| format!("Unable to send the query to sqlite3: {}", error))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|error| format!("Unable to run sqlite3: {}", error))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() || !stderr.trim().is_empty() {
+        return Err(without_line_number(stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Builds the statements creating the tables and inserting every todo item.
+fn load_script(todos: &[Todo]) -> String {
+    let mut script = String::from(SCHEMA);
+    script.push_str("BEGIN;\n");
+    for todo in todos {
+        let text = |value: Option<&str>| value.map_or("NULL".to_string(), quote);
+        let _ = writeln!(
+            script,
+            "INSERT INTO todos VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {});",
+            todo.id,
+            quote(&todo.task),
+            todo.priority,
+            timestamp(todo.created),
+            timestamp(todo.last_modified()),
+            todo.due.map_or("NULL".to_string(), timestamp),
+            text(todo.recur.as_deref()),
+            text(todo.project.as_deref()),
+            text(todo.assignee.as_deref()),
+            text(todo.created_by.as_deref()),
+            text(todo.location.as_deref()),
+            text(todo.url.as_deref()),
+            quote(&todo.tags.join(" ")),
+            todo.attachments.len()
+        );
+        for tag in &todo.tags {
+            let _ = writeln!(
+                script,
+                "INSERT INTO tags VALUES ({}, {});",
+                todo.id,
+                quote(tag)
+            );
+        }
+    }
+    script.push_str("COMMIT;\n");
+    script
+}
+
+/// Removes the "near line N" from an sqlite3 error, since N counts the lines of the
+/// generated statements rather than the user's query.
+fn without_line_number(error: &str) -> String {
+    match error.split_once(" near line ") {
+        Some((start, rest)) => {
+            format!(
+                "{}{}",
+                start,
+                rest.trim_start_matches(|c: char| c.is_ascii_digit())
+            )
+        }
+        None => error.to_string(),
+    }
+}
+
+/// Quotes text as an SQL string literal.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+fn timestamp(timestamp: i64) -> String {
+    let datetime = DateTime::from_timestamp(timestamp, 0).unwrap_or_default();
+    quote(&datetime.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_script, quote, without_line_number};
+    use crate::TodoList;
+
+    #[test]
+    fn test_load_script() {
+        assert_eq!(quote("it's"), "'it''s'");
+
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Pay Bob's rent", 5);
+        todo_list.add_tags(1, &["home".to_string(), "money".to_string()]);
+        todo_list.set_due(1, 1717200000);
+        let created = todo_list.todos[0].created;
+        let script = load_script(&todo_list.todos);
+        assert!(script.contains(&format!(
+            "INSERT INTO todos VALUES (1, 'Pay Bob''s rent', 5, {}, {}, '2024-06-01 00:00:00', \
+             NULL, NULL, NULL, NULL, NULL, NULL, 'home money', 0);",
+            super::timestamp(created),
+            super::timestamp(todo_list.todos[0].last_modified())
+        )));
+        assert!(script.contains("INSERT INTO tags VALUES (1, 'money');"));
+        assert!(script.ends_with("COMMIT;\n"));
+    }
+
+    #[test]
+    fn test_without_line_number() {
+        assert_eq!(
+            without_line_number("Parse error near line 25: no such column: nope"),
+            "Parse error: no such column: nope"
+        );
+        assert_eq!(without_line_number("Error: oops"), "Error: oops");
+    }
+}
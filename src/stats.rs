@@ -0,0 +1,675 @@
+//! Statistics about the todo list as a whole, for `stats`: how many todo items there are
+//! and in what state, which have gone untouched the longest, and how each tag or project is
+//! getting on, and charts of how the items spread over priorities, weekdays and weeks.
+//!
+//! Completed todo items leave the list, so `remove` records them in a log next to it, which
+//! the per-group statistics read for what was completed and how long it took.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+
+use crate::dates::{self, DateFormat, Zone};
+use crate::{priorities, Todo};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// The log of completed todo items, relative to the todo list's directory.
+pub const COMPLETED_FILE: &str = ".todo-completed.json";
+
+/// How long completed todo items are kept in the log.
+const KEEP_COMPLETED: i64 = 365 * DAY;
+
+/// How far back `stats --by` counts completed todo items.
+const RECENT: i64 = 30 * DAY;
+
+/// How long a todo item may go untouched before `stats` counts it as stale, unless
+/// `--older-than` says otherwise.
+pub const STALE_AFTER: &str = "60d";
+
+/// Counts of the todo items on the list.
+#[derive(Debug, Default, PartialEq)]
+pub struct Stats {
+    pub total: usize,
+    /// The number of items at each priority, the least urgent first.
+    pub by_priority: Vec<usize>,
+    /// The number of overdue items, leaving out those on the someday/maybe list.
+    pub overdue: usize,
+    pub blocked: usize,
+    pub waiting: usize,
+    /// The number of items untouched for longer than the staleness threshold.
+    pub stale: usize,
+    /// How long ago the oldest item was created, in seconds.
+    pub oldest: Option<i64>,
+}
+
+/// A todo item that has gone untouched for a long time.
+#[derive(Debug, PartialEq)]
+pub struct Stale {
+    pub id: u64,
+    pub task: String,
+    /// How long ago it was created or last changed, in seconds.
+    pub age: i64,
+    pub last_modified: i64,
+}
+
+/// A todo item as it was when it was completed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Completed {
+    /// When it was removed from the list.
+    pub completed: i64,
+    #[serde(flatten)]
+    pub todo: Todo,
+}
+
+/// What `stats --by` groups todo items by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    /// Each tag, counting an item with several tags in each.
+    Tag,
+    /// The project.
+    Project,
+}
+
+impl std::str::FromStr for GroupBy {
+    type Err = String;
+    fn from_str(by: &str) -> Result<GroupBy, String> {
+        match by {
+            "tag" => Ok(GroupBy::Tag),
+            "project" => Ok(GroupBy::Project),
+            _ => Err(format!("Unknown grouping: {}", by)),
+        }
+    }
+}
+
+impl GroupBy {
+    /// Returns the groups `todo` belongs to; none is the group of items without any.
+    fn groups(self, todo: &Todo) -> Vec<Option<String>> {
+        match self {
+            GroupBy::Tag if todo.tags.is_empty() => vec![None],
+            GroupBy::Tag => todo.tags.iter().cloned().map(Some).collect(),
+            GroupBy::Project => vec![todo.project.clone()],
+        }
+    }
+}
+
+/// The statistics of one tag or project.
+#[derive(Debug, Default, PartialEq)]
+pub struct Group {
+    /// The tag or project, or none for the items without one.
+    pub name: Option<String>,
+    /// The number of todo items on the list.
+    pub open: usize,
+    /// The number of todo items completed in the last 30 days.
+    pub completed: usize,
+    /// The average time from creating to completing those items, in seconds.
+    pub average: Option<i64>,
+    /// The number of overdue items, leaving out those on the someday/maybe list.
+    pub overdue: usize,
+}
+
+/// Reads the log of completed todo items kept next to the todo list in `base`, which is
+/// empty if there is none yet.
+pub fn load_completed(base: &Path) -> Vec<Completed> {
+    fs::read_to_string(base.join(COMPLETED_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Adds `todos` to the log of completed todo items in `base`, as completed at the
+/// timestamp `now`, dropping those completed more than a year ago.
+pub fn record_completed(base: &Path, todos: &[Todo], now: i64) -> Result<(), String> {
+    if todos.is_empty() {
+        return Ok(());
+    }
+    let mut completed = load_completed(base);
+    completed.retain(|item| now - item.completed <= KEEP_COMPLETED);
+    completed.extend(todos.iter().map(|todo| Completed {
+        completed: now,
+        todo: todo.clone(),
+    }));
+    let contents = serde_json::to_string_pretty(&completed).expect("Unable to serialize log");
+    fs::write(base.join(COMPLETED_FILE), contents)
+        .map_err(|error| format!("Unable to record the completed tasks: {}", error))
+}
+
+/// Applies `update` to every todo item in the log of completed todo items in `base`,
+/// returning how many it changed, as it tells by returning `true`. The log is only written
+/// when some were.
+pub fn update_completed(
+    base: &Path,
+    mut update: impl FnMut(&mut Todo) -> bool,
+) -> Result<usize, String> {
+    let mut completed = load_completed(base);
+    let mut updated = 0;
+    for item in &mut completed {
+        updated += update(&mut item.todo) as usize;
+    }
+    if updated > 0 {
+        let contents = serde_json::to_string_pretty(&completed).expect("Unable to serialize log");
+        fs::write(base.join(COMPLETED_FILE), contents)
+            .map_err(|error| format!("Unable to update the completed tasks: {}", error))?;
+    }
+    Ok(updated)
+}
+
+/// Returns the todo items in the log in `base` completed at or after the timestamp `since`,
+/// or all of them, in the order they were completed.
+pub fn completed_since(base: &Path, since: Option<i64>) -> Vec<Completed> {
+    let mut completed = load_completed(base);
+    completed.retain(|item| since.is_none_or(|since| item.completed >= since));
+    completed.sort_by_key(|item| item.completed);
+    completed
+}
+
+/// Works out the statistics of each group of the open todo items `todos` and those
+/// `completed`, at the timestamp `now`, sorted by name with the items in no group last.
+pub fn by_group(todos: &[Todo], completed: &[Completed], by: GroupBy, now: i64) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    let mut durations: Vec<Vec<i64>> = Vec::new();
+    let mut group = |name: Option<String>, groups: &mut Vec<Group>| match groups
+        .iter()
+        .position(|group| group.name == name)
+    {
+        Some(index) => index,
+        None => {
+            groups.push(Group {
+                name,
+                ..Group::default()
+            });
+            durations.push(Vec::new());
+            groups.len() - 1
+        }
+    };
+    for todo in todos {
+        for name in by.groups(todo) {
+            let index = group(name, &mut groups);
+            groups[index].open += 1;
+            groups[index].overdue += overdue(todo, now) as usize;
+        }
+    }
+    let mut recent = Vec::new();
+    for item in completed
+        .iter()
+        .filter(|item| now - item.completed <= RECENT)
+    {
+        for name in by.groups(&item.todo) {
+            recent.push((group(name, &mut groups), item.completed - item.todo.created));
+        }
+    }
+    for (index, duration) in recent {
+        durations[index].push(duration);
+    }
+    for (group, durations) in groups.iter_mut().zip(&durations) {
+        group.completed = durations.len();
+        if !durations.is_empty() {
+            group.average = Some(durations.iter().sum::<i64>() / durations.len() as i64);
+        }
+    }
+    groups.sort_by(|a, b| (a.name.is_none(), &a.name).cmp(&(b.name.is_none(), &b.name)));
+    groups
+}
+
+/// Formats the statistics of each group as a table, with a header naming the grouping.
+pub fn render_groups(groups: &[Group], by: GroupBy) -> String {
+    let name = |group: &Group| group.name.clone().unwrap_or_else(|| "(none)".to_string());
+    let heading = match by {
+        GroupBy::Tag => "tag",
+        GroupBy::Project => "project",
+    };
+    let width = groups
+        .iter()
+        .map(|group| name(group).chars().count())
+        .chain([heading.len()])
+        .max()
+        .unwrap_or_default();
+    let mut output = String::new();
+    let _ = writeln!(
+        output,
+        "{:width$}  {:>5}  {:>14}  {:>13}  {:>7}",
+        heading, "open", "done (30 days)", "avg. time", "overdue"
+    );
+    for group in groups {
+        let average = match group.average {
+            Some(average) if average >= DAY => days(average),
+            Some(average) => dates::format_duration(average),
+            None => "-".to_string(),
+        };
+        let _ = writeln!(
+            output,
+            "{:width$}  {:>5}  {:>14}  {:>13}  {:>7}",
+            name(group),
+            group.open,
+            group.completed,
+            average,
+            group.overdue
+        );
+    }
+    output
+}
+
+/// Returns whether the todo item counts as overdue at the timestamp `now`, which those on
+/// the someday/maybe list don't, as in `overdue`.
+fn overdue(todo: &Todo, now: i64) -> bool {
+    todo.has_tag("OVERDUE", now) && !todo.someday
+}
+
+/// Counts the todo items at the timestamp `now`, those not changed for `stale_after`
+/// seconds being stale.
+pub fn stats(todos: &[Todo], stale_after: i64, now: i64) -> Stats {
+    let mut stats = Stats {
+        total: todos.len(),
+        stale: stale(todos, stale_after, now).len(),
+        oldest: todos.iter().map(|todo| now - todo.created).max(),
+        by_priority: by_priority(todos),
+        ..Stats::default()
+    };
+    for todo in todos {
+        stats.overdue += overdue(todo, now) as usize;
+        stats.blocked += todo.has_tag("BLOCKED", now) as usize;
+        stats.waiting += todo.has_tag("WAITING", now) as usize;
+    }
+    stats
+}
+
+/// How many weeks back `stats --charts` counts the todo items added.
+const WEEKS: usize = 12;
+
+/// The widest a bar of `stats --charts` is drawn, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// The distributions `stats --charts` draws.
+#[derive(Debug, Default, PartialEq)]
+pub struct Charts {
+    /// The number of open items at each priority, the least urgent first.
+    pub by_priority: Vec<usize>,
+    /// The number of items in the log completed on each day of the week, Monday first.
+    pub by_weekday: [usize; 7],
+    /// The number of items added in each of the last 12 weeks, the oldest first.
+    pub by_week: [usize; WEEKS],
+}
+
+/// Works out the distributions of the open todo items `todos` and those `completed`, with
+/// days of the week in the timezone `zone`, at the timestamp `now`.
+pub fn charts(todos: &[Todo], completed: &[Completed], zone: Zone, now: i64) -> Charts {
+    let mut charts = Charts {
+        by_priority: by_priority(todos),
+        ..Charts::default()
+    };
+    for item in completed {
+        let weekday = zone.local(item.completed).weekday();
+        charts.by_weekday[weekday.num_days_from_monday() as usize] += 1;
+    }
+    let created = todos
+        .iter()
+        .chain(completed.iter().map(|item| &item.todo))
+        .map(|todo| todo.created);
+    for created in created {
+        let weeks_ago = (now - created).div_euclid(7 * DAY);
+        if (0..WEEKS as i64).contains(&weeks_ago) {
+            charts.by_week[WEEKS - 1 - weeks_ago as usize] += 1;
+        }
+    }
+    charts
+}
+
+/// Counts `todos` at each priority of the scale, the least urgent first.
+fn by_priority(todos: &[Todo]) -> Vec<usize> {
+    let mut counts = vec![0; priorities::scale().levels() as usize];
+    for todo in todos {
+        if let Some(count) = counts.get_mut(todo.priority.wrapping_sub(1) as usize) {
+            *count += 1;
+        }
+    }
+    counts
+}
+
+/// Draws `count` as a bar of block characters, as long as `BAR_WIDTH` for `max`.
+fn bar(count: usize, max: usize) -> String {
+    const EIGHTHS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+    if max == 0 {
+        return String::new();
+    }
+    let eighths = (count * BAR_WIDTH * 8).div_ceil(max);
+    let mut bar = "█".repeat(eighths / 8);
+    let rest = eighths % 8;
+    if rest > 0 {
+        bar.push(EIGHTHS[rest]);
+    }
+    bar
+}
+
+/// Draws `counts` as a sparkline, one block character each, the highest for the largest.
+fn sparkline(counts: &[usize]) -> String {
+    const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = counts.iter().copied().max().unwrap_or_default().max(1);
+    counts
+        .iter()
+        .map(|count| LEVELS[count * (LEVELS.len() - 1) / max])
+        .collect()
+}
+
+impl Charts {
+    /// Formats the distributions as bar charts and a sparkline, or as labeled lines of
+    /// counts when `plain` is set.
+    pub fn render(&self, plain: bool) -> String {
+        const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+        let mut output = String::new();
+        let weeks: Vec<String> = self.by_week.iter().map(usize::to_string).collect();
+        if plain {
+            let weekdays: Vec<String> = WEEKDAYS
+                .iter()
+                .zip(self.by_weekday)
+                .map(|(day, count)| format!("{}: {}", day, count))
+                .collect();
+            let _ = writeln!(output, "Completed by weekday: {}", weekdays.join(", "));
+            let _ = writeln!(output, "Added per week, oldest first: {}", weeks.join(", "));
+            return output;
+        }
+        let max = self.by_priority.iter().copied().max().unwrap_or_default();
+        let _ = writeln!(output, "By priority");
+        // Names of priorities are padded so the bars line up
+        let labels: Vec<String> = (1..=self.by_priority.len() as u64)
+            .map(|priority| priorities::scale().label(priority))
+            .collect();
+        let width = labels.iter().map(|label| label.chars().count()).max();
+        let width = width.unwrap_or_default();
+        for (label, count) in labels.iter().zip(&self.by_priority).rev() {
+            let _ = writeln!(output, "  {:<width$} {} {}", label, bar(*count, max), count);
+        }
+        let max = self.by_weekday.iter().copied().max().unwrap_or_default();
+        let _ = writeln!(output, "\nCompleted by weekday");
+        for (day, count) in WEEKDAYS.iter().zip(self.by_weekday) {
+            let _ = writeln!(output, "  {} {} {}", day, bar(count, max), count);
+        }
+        let _ = writeln!(output, "\nAdded per week, last {} weeks", WEEKS);
+        let _ = writeln!(
+            output,
+            "  {}  {} this week, {} in all",
+            sparkline(&self.by_week),
+            self.by_week[WEEKS - 1],
+            self.by_week.iter().sum::<usize>()
+        );
+        output
+    }
+}
+
+/// Returns the todo items not changed for at least `older_than` seconds at the timestamp
+/// `now`, the longest untouched first.
+pub fn stale(todos: &[Todo], older_than: i64, now: i64) -> Vec<Stale> {
+    let mut stale: Vec<Stale> = todos
+        .iter()
+        .filter(|todo| now - todo.last_modified() >= older_than)
+        .map(|todo| Stale {
+            id: todo.id,
+            task: todo.task.clone(),
+            age: now - todo.last_modified(),
+            last_modified: todo.last_modified(),
+        })
+        .collect();
+    stale.sort_by_key(|stale| (-stale.age, stale.id));
+    stale
+}
+
+/// Formats a length of time in whole days, e.g. `94 days`.
+fn days(seconds: i64) -> String {
+    match seconds / DAY {
+        1 => "1 day".to_string(),
+        days => format!("{} days", days),
+    }
+}
+
+impl Stats {
+    /// Formats the counts as labeled lines, naming the staleness threshold `stale_after`.
+    pub fn render(&self, stale_after: &str) -> String {
+        let mut output = String::new();
+        let _ = writeln!(output, "Tasks: {}", self.total);
+        let counts: Vec<String> = self
+            .by_priority
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(index, count)| {
+                let priority = priorities::scale().label(index as u64 + 1);
+                format!("{}: {}", priority, count)
+            })
+            .collect();
+        let _ = writeln!(output, "By priority: {}", counts.join(", "));
+        let _ = writeln!(output, "Overdue: {}", self.overdue);
+        let _ = writeln!(output, "Blocked: {}", self.blocked);
+        let _ = writeln!(output, "Waiting: {}", self.waiting);
+        if let Some(oldest) = self.oldest {
+            let _ = writeln!(output, "Oldest: created {} ago", days(oldest));
+        }
+        let _ = writeln!(
+            output,
+            "Untouched for {} or more: {}",
+            stale_after, self.stale
+        );
+        output
+    }
+}
+
+/// Formats the stale todo items one per line, with their age and when they were last
+/// changed in the format `dates`.
+pub fn render_stale(stale: &[Stale], dates: &DateFormat, now: i64) -> String {
+    let mut output = String::new();
+    for item in stale {
+        let _ = writeln!(
+            output,
+            "{}: {} (untouched for {}, last modified {})",
+            item.id,
+            item.task,
+            days(item.age),
+            dates.format(item.last_modified, now, "%Y-%m-%d")
+        );
+    }
+    output
+}
+
+/// Formats a length of time in hours and minutes under a day, and in whole days otherwise.
+fn open_for(seconds: i64) -> String {
+    match seconds < DAY {
+        true => dates::format_duration(seconds),
+        false => days(seconds),
+    }
+}
+
+/// Formats the completed todo items one per line, with when they were completed in the
+/// format `dates` and how long they were open, or as labeled lines when `plain` is set.
+pub fn render_completed(
+    completed: &[Completed],
+    dates: &DateFormat,
+    now: i64,
+    plain: bool,
+) -> String {
+    let mut output = String::new();
+    for (index, item) in completed.iter().enumerate() {
+        let todo = &item.todo;
+        let when = dates.format(item.completed, now, "%Y-%m-%d %H:%M");
+        let open = open_for(item.completed - todo.created);
+        if plain {
+            if index > 0 {
+                output.push('\n');
+            }
+            let _ = writeln!(output, "task: {}", todo.task);
+            let _ = writeln!(output, "completed: {}", when);
+            let _ = writeln!(output, "open for: {}", open);
+            if let Some(project) = &todo.project {
+                let _ = writeln!(output, "project: {}", project);
+            }
+            if !todo.tags.is_empty() {
+                let tags: Vec<String> = todo.tags.iter().map(|tag| format!("+{}", tag)).collect();
+                let _ = writeln!(output, "tags: {}", tags.join(" "));
+            }
+            continue;
+        }
+        let _ = write!(output, "{}  {}", when, todo.task);
+        for tag in &todo.tags {
+            let _ = write!(output, " +{}", tag);
+        }
+        match &todo.project {
+            Some(project) => {
+                let _ = writeln!(output, " (project: {}, open for {})", project, open);
+            }
+            None => {
+                let _ = writeln!(output, " (open for {})", open);
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        bar, by_group, charts, render_completed, render_stale, sparkline, stale, stats, Completed,
+        GroupBy, DAY,
+    };
+    use crate::dates::{DateFormat, Zone};
+    use crate::TodoList;
+
+    #[test]
+    fn test_stale() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Pay rent", 5);
+        todo_list.add_todo("Read docs", 2);
+        todo_list.add_todo("Clean garage", 1);
+        let now = todo_list.todos[0].created;
+        todo_list.todos[1].created = now - 90 * DAY;
+        todo_list.todos[2].created = now - 120 * DAY;
+        todo_list.todos[2].modified = Some(now - 70 * DAY);
+        todo_list.todos[0].due = Some(now - 60);
+
+        let found = stale(&todo_list.todos, 60 * DAY, now);
+        let short = DateFormat::parse("short").unwrap();
+        let ids: Vec<u64> = found.iter().map(|item| item.id).collect();
+        assert_eq!(ids, [2, 3]);
+        assert_eq!(
+            render_stale(&found[1..], &short, now),
+            format!(
+                "3: Clean garage (untouched for 70 days, last modified {})\n",
+                short.format(now - 70 * DAY, now, "")
+            )
+        );
+        assert_eq!(stale(&todo_list.todos, 80 * DAY, now).len(), 1);
+
+        let stats = stats(&todo_list.todos, 60 * DAY, now);
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.by_priority, [1, 1, 0, 0, 1]);
+        assert_eq!((stats.overdue, stats.stale), (1, 2));
+        assert_eq!(stats.oldest, Some(120 * DAY));
+        // Not overdue on the someday/maybe list
+        todo_list.todos[0].someday = true;
+        assert_eq!(super::stats(&todo_list.todos, 60 * DAY, now).overdue, 0);
+    }
+
+    #[test]
+    fn test_by_group() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Send invoice", 4);
+        todo_list.add_todo("Call client", 3);
+        todo_list.add_todo("Water plants", 1);
+        let now = todo_list.todos[0].created;
+        todo_list.todos[0].tags = vec!["client".to_string(), "money".to_string()];
+        todo_list.todos[1].tags = vec!["client".to_string()];
+        todo_list.todos[1].due = Some(now - 60);
+        let done = |task: &str, tags: &[&str], days_ago: i64, took: i64| {
+            let mut todo = todo_list.todos[2].clone();
+            todo.task = task.to_string();
+            todo.tags = tags.iter().map(|tag| tag.to_string()).collect();
+            todo.created = now - (days_ago + took) * DAY;
+            Completed {
+                completed: now - days_ago * DAY,
+                todo,
+            }
+        };
+        let completed = [
+            done("Quote", &["client"], 1, 2),
+            done("Contract", &["client"], 5, 4),
+            done("Old invoice", &["money"], 40, 1),
+        ];
+
+        let groups = by_group(&todo_list.todos, &completed, GroupBy::Tag, now);
+        let rows: Vec<_> = groups
+            .iter()
+            .map(|group| {
+                let name = group.name.as_deref();
+                (
+                    name,
+                    group.open,
+                    group.completed,
+                    group.average,
+                    group.overdue,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (Some("client"), 2, 2, Some(3 * DAY), 1),
+                (Some("money"), 1, 0, None, 0),
+                (None, 1, 0, None, 0),
+            ]
+        );
+        let groups = by_group(&todo_list.todos, &completed, GroupBy::Project, now);
+        assert_eq!(groups.len(), 1);
+        assert_eq!((groups[0].open, groups[0].completed), (3, 2));
+
+        let short = DateFormat::parse("short").unwrap();
+        let mut quick = done("Reply", &[], 0, 0);
+        quick.todo.created = now - 90 * 60;
+        quick.todo.project = Some("acme".to_string());
+        assert_eq!(
+            render_completed(&[completed[0].clone(), quick.clone()], &short, now, false),
+            format!(
+                "{}  Quote +client (open for 2 days)\n{}  Reply (project: acme, open for 1h30m)\n",
+                short.format(now - DAY, now, ""),
+                short.format(now, now, "")
+            )
+        );
+        assert_eq!(
+            render_completed(&[quick], &short, now, true),
+            format!(
+                "task: Reply\ncompleted: {}\nopen for: 1h30m\nproject: acme\n",
+                short.format(now, now, "")
+            )
+        );
+    }
+
+    #[test]
+    fn test_charts() {
+        assert_eq!(bar(0, 4), "");
+        assert_eq!(bar(4, 4), "█".repeat(30));
+        assert_eq!(bar(1, 4), "███████▌");
+        assert_eq!(sparkline(&[0, 1, 7, 3]), "▁▂█▄");
+        assert_eq!(sparkline(&[0, 0]), "▁▁");
+
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Send invoice", 4);
+        todo_list.add_todo("Call client", 4);
+        // Monday, 3 June 2024
+        let now = 1717416000;
+        todo_list.todos[0].created = now;
+        todo_list.todos[1].created = now - 15 * DAY;
+        let done = |completed: i64| Completed {
+            completed,
+            todo: todo_list.todos[0].clone(),
+        };
+        let completed = [done(now), done(now - 5 * DAY), done(now - 7 * DAY)];
+        let charts = charts(&todo_list.todos, &completed, Zone::Utc, now);
+        assert_eq!(charts.by_priority, [0, 0, 0, 2, 0]);
+        assert_eq!(charts.by_weekday, [2, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(charts.by_week[9..], [1, 0, 4]);
+        assert!(charts
+            .render(false)
+            .contains("  Mon ██████████████████████████████ 2\n"));
+        assert!(charts
+            .render(true)
+            .ends_with("Added per week, oldest first: 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 4\n"));
+    }
+}
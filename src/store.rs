@@ -0,0 +1,203 @@
+//! Loading and saving the todo list file, in the format of its extension, and keeping its
+//! previous versions as backups and in its history.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, UNIX_EPOCH};
+
+use crate::formats::Format;
+use crate::{history, now, rebase, shards, timings, TodoList};
+
+/// The directory, next to the todo list file, that its previous versions are kept in.
+pub const BACKUP_DIR: &str = ".todo-backups";
+/// The number of previous versions of the todo list file to keep.
+const MAX_BACKUPS: usize = 10;
+/// Loads a todo list from a JSON file, warning if it fails its checksum.
+///
+/// # Arguments
+///
+/// * `path` - The todo list file to load.
+///
+/// # Returns
+///
+/// Returns `Ok(Some(todo_list))` with the loaded todo list if the file exists, `Ok(None)`
+/// if it doesn't, and an error pointing to `restore` if it can't be read or parsed.
+///
+/// # Example
+///
+/// ```
+/// match load_todo_list(Path::new("./todos.json")) {
+///     Ok(Some(todo_list)) => { /* Process the loaded todo list... */ }
+///     Ok(None) => { /* No todo list file found. */ }
+///     Err(error) => println!("{}", error),
+/// }
+/// ```
+pub fn load_todo_list(path: &Path) -> Result<Option<TodoList>, String> {
+    let loaded = load_todo_list_from(path).map_err(|error| {
+        format!(
+            "{}. Run `restore 1` to go back to the most recent backup",
+            error
+        )
+    })?;
+    let Some(mut todo_list) = loaded else {
+        return Ok(None);
+    };
+    if !todo_list.verify_checksum() {
+        eprintln!(
+            "WARNING: {} does not match its checksum. It was modified outside of this tool or is corrupted.\n\
+             Run `restore 1` to go back to the most recent backup, or save any change to accept the file as it is.",
+            path.display()
+        );
+    }
+    // After the checksum, which covers the items as they were saved
+    todo_list.assign_hashes();
+    Ok(Some(todo_list))
+}
+/// Loads a todo list from the file at `path`, in the format of its extension, returning
+/// `None` if it doesn't exist, and an error if it can't be read or parsed.
+pub fn load_todo_list_from(path: &Path) -> Result<Option<TodoList>, String> {
+    if shards::is_sharded(path) {
+        shards::load(path, None).map(Some)
+    } else if path.exists() {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+        let todo_list = TodoList::parse(&contents, Format::of(path))
+            .map_err(|error| format!("Unable to parse {}: {}", path.display(), error))?;
+        rebase::record(path, &contents);
+        Ok(Some(todo_list))
+    } else {
+        Ok(None)
+    }
+}
+/// Saves a todo list to a file in the format of its extension, backing up the previous
+/// version. If another process changed the file since it was loaded, the changes made to
+/// the list are applied to the file's current list instead, and if they can't be, nothing is
+/// saved and the conflict is returned.
+pub fn try_save_todo_list(path: &Path, todo_list: &TodoList) -> Result<(), String> {
+    let loaded = rebase::loaded(path);
+    let saved = save_todo_list_over(path, &mut todo_list.clone(), loaded.as_deref())?;
+    if let Some(saved) = saved {
+        rebase::record(path, &saved);
+    }
+    Ok(())
+}
+/// Saves a todo list as `try_save_todo_list` does, over the file as it held `loaded` when
+/// the list was loaded, if it was, and returns what was written, which is nothing for a
+/// sharded list. When the list is applied over another process's changes, it is replaced
+/// by the list saved.
+pub fn save_todo_list_over(
+    path: &Path,
+    todo_list: &mut TodoList,
+    loaded: Option<&str>,
+) -> Result<Option<String>, String> {
+    let saving = Instant::now();
+    if shards::is_sharded(path) {
+        shards::save(path, todo_list, now())?;
+        timings::saved(saving, todo_list.todos.len());
+        return Ok(None);
+    }
+    let current = std::fs::read_to_string(path).ok();
+    let replaced = std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_secs() as i64);
+    if let (Some(loaded), Some(current)) = (loaded, &current) {
+        if loaded != current {
+            *todo_list = rebase_onto(path, loaded, current, todo_list)?;
+        }
+    }
+    if path.exists() {
+        rotate_backups(path);
+    }
+    let serialized = todo_list.to_format(Format::of(path));
+    File::create(path)
+        .and_then(|mut file| file.write_all(serialized.as_bytes()))
+        .map_err(|error| format!("Unable to write {}: {}", path.display(), error))?;
+    let previous = current.as_deref().map(|current| (current, replaced));
+    history::record(path, previous, todo_list, now());
+    timings::saved(saving, todo_list.todos.len());
+    Ok(Some(serialized))
+}
+/// Applies the changes made to the list file at `path` since it held `loaded` to what it
+/// holds now, `current`, warning that it was changed meanwhile.
+fn rebase_onto(
+    path: &Path,
+    loaded: &str,
+    current: &str,
+    todo_list: &TodoList,
+) -> Result<TodoList, String> {
+    let parse = |contents: &str| {
+        let mut todo_list = TodoList::parse(contents, Format::of(path))
+            .map_err(|error| format!("Unable to parse {}: {}", path.display(), error))?;
+        todo_list.assign_hashes();
+        Ok::<TodoList, String>(todo_list)
+    };
+    let rebased = rebase::rebase(&parse(loaded)?, todo_list, &parse(current)?)?;
+    eprintln!(
+        "WARNING: {} was changed by another command since it was loaded. This command's changes were applied on top.",
+        path.display()
+    );
+    Ok(rebased)
+}
+/// Loads the todo list at `path` as it was at the timestamp `at`, from its history.
+pub fn load_todo_list_as_of(path: &Path, at: i64) -> Result<TodoList, String> {
+    history::as_of(path, at)
+}
+/// Keeps the previous version of the todo list file as backup 1, shifting older backups
+/// along and dropping the oldest once there are `MAX_BACKUPS`.
+pub fn rotate_backups(path: &Path) {
+    let result = std::fs::create_dir_all(list_directory(path).join(BACKUP_DIR)).and_then(|_| {
+        for n in (1..MAX_BACKUPS).rev() {
+            let from = backup_path(path, n);
+            if from.exists() {
+                std::fs::rename(&from, backup_path(path, n + 1))?;
+            }
+        }
+        std::fs::copy(path, backup_path(path, 1)).map(|_| ())
+    });
+    if let Err(error) = result {
+        println!("Unable to back up the todo list: {}", error);
+    }
+}
+/// Returns the path of the n-th most recent backup of the todo list file at `path`.
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "todos.json".to_string());
+    list_directory(path)
+        .join(BACKUP_DIR)
+        .join(format!("{}.{}", name, n))
+}
+/// Returns the directory containing the todo list file, which backups and attachments are
+/// kept relative to.
+pub fn list_directory(path: &Path) -> PathBuf {
+    if shards::is_sharded(path) {
+        return path.to_path_buf();
+    }
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::load_todo_list;
+    use std::path::Path;
+    #[test]
+    fn test_load_mangled() {
+        let path = std::env::temp_dir().join(format!("todo-mangled-{}.json", std::process::id()));
+        std::fs::write(&path, "{\"todos\": [{\"id\": 1, \"ta").unwrap();
+        let error = load_todo_list(&path).unwrap_err();
+        assert!(error.starts_with(&format!("Unable to parse {}: ", path.display())));
+        assert!(error.ends_with("Run `restore 1` to go back to the most recent backup"));
+        std::fs::remove_file(&path).unwrap();
+        assert!(load_todo_list(Path::new("no-such-list.json"))
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,203 @@
+//! The offline queue of a remote list: the commands run on the cached copy while the server
+//! was unreachable, replayed on the served list once it can be reached again.
+//!
+//! Each queued command remembers the tasks it changed or removed, as they were before it
+//! ran. It is only replayed if those tasks are still at the same IDs, with the same
+//! revisions, on the server; otherwise it is kept as a conflict for `sync status` to show.
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Todo, TodoList};
+
+/// The file holding the queue, next to the cached copy of the list.
+pub const QUEUE_FILE: &str = "queue.json";
+
+/// The state of a remote list's cached copy.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Queue {
+    /// The checksum of the served list the cached copy was last the same as, before any
+    /// pending commands ran on it.
+    pub synced: Option<String>,
+    /// The commands waiting to be replayed, oldest first.
+    pub pending: Vec<Change>,
+    /// The commands that were not replayed because their tasks changed on the server.
+    pub conflicts: Vec<Change>,
+}
+
+/// A command run offline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    /// The command-line arguments, without `--remote` and `--file`.
+    pub args: Vec<String>,
+    /// When the command ran.
+    pub queued: i64,
+    /// The tasks the command changed or removed, as they were before it ran.
+    #[serde(default)]
+    pub touched: Vec<Touched>,
+    /// Why the command was not replayed, for a conflict.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<String>,
+}
+
+/// What identifies a task at a point in time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Touched {
+    pub id: u64,
+    pub task: String,
+    pub created: i64,
+    #[serde(default)]
+    pub rev: u64,
+}
+
+impl Touched {
+    fn of(todo: &Todo) -> Touched {
+        Touched {
+            id: todo.id,
+            task: todo.task.clone(),
+            created: todo.created,
+            rev: todo.rev,
+        }
+    }
+}
+
+impl Change {
+    /// The command as it would be typed, e.g. `add "Renew passport" 4`.
+    pub fn command(&self) -> String {
+        self.args
+            .iter()
+            .map(|arg| {
+                if arg.is_empty() || arg.contains(char::is_whitespace) {
+                    format!("\"{}\"", arg.replace('"', "\\\""))
+                } else {
+                    arg.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Checks that every task the command touched is still as it was, at the same ID, in
+    /// `todo_list`, so replaying it changes the same tasks.
+    pub fn check(&self, todo_list: &TodoList) -> Result<(), String> {
+        match self
+            .touched
+            .iter()
+            .find(|touched| todo_list.todo(touched.id).map(Touched::of).as_ref() != Some(*touched))
+        {
+            Some(touched) => Err(format!(
+                "Task {} ('{}') was changed, moved or removed on the server",
+                touched.id, touched.task
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Queue {
+    /// Loads the queue at `path`, or an empty one if it does not exist.
+    pub fn load(path: &Path) -> Result<Queue, String> {
+        if !path.exists() {
+            return Ok(Queue::default());
+        }
+        let contents = fs::read_to_string(path)
+            .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+        serde_json::from_str(&contents)
+            .map_err(|error| format!("Unable to parse {}: {}", path.display(), error))
+    }
+
+    /// Writes the queue to `path`.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self).expect("Unable to serialize queue");
+        fs::write(path, contents)
+            .map_err(|error| format!("Unable to write {}: {}", path.display(), error))
+    }
+}
+
+/// Returns the tasks of `before` a command changed or removed to leave `after`. Tasks
+/// that were only renumbered are not included.
+pub fn touched(before: &TodoList, after: &TodoList) -> Vec<Touched> {
+    let kept: Vec<Touched> = after
+        .todos
+        .iter()
+        .map(|todo| Touched {
+            id: 0,
+            ..Touched::of(todo)
+        })
+        .collect();
+    before
+        .todos
+        .iter()
+        .filter(|todo| {
+            !kept.contains(&Touched {
+                id: 0,
+                ..Touched::of(todo)
+            })
+        })
+        .map(Touched::of)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{touched, Change};
+    use crate::TodoList;
+
+    #[test]
+    fn test_touched() {
+        let mut before = TodoList::new();
+        before.add_todo("Pay rent", 5);
+        before.add_todo("Read docs", 2);
+        before.add_todo("Call the plumber", 4);
+        let mut after = before.clone();
+        after.remove_todo(1);
+        after.todo_mut(2).unwrap().task = "Call the plumber today".to_string();
+        let touched = touched(&before, &after);
+        let tasks: Vec<(u64, &str)> = touched
+            .iter()
+            .map(|touched| (touched.id, touched.task.as_str()))
+            .collect();
+        assert_eq!(tasks, [(1, "Pay rent"), (3, "Call the plumber")]);
+
+        let change = Change {
+            args: vec!["remove".to_string(), "1".to_string()],
+            queued: 0,
+            touched,
+            conflict: None,
+        };
+        assert_eq!(change.check(&before), Ok(()));
+        let mut server = before.clone();
+        server.todo_mut(3).unwrap().task = "Call the plumber now".to_string();
+        assert_eq!(
+            change.check(&server),
+            Err(
+                "Task 3 ('Call the plumber') was changed, moved or removed on the server"
+                    .to_string()
+            )
+        );
+        let mut server = before.clone();
+        server.remove_todo(1);
+        assert_eq!(
+            change.check(&server),
+            Err("Task 1 ('Pay rent') was changed, moved or removed on the server".to_string())
+        );
+    }
+
+    #[test]
+    fn test_command() {
+        let change = Change {
+            args: vec![
+                "add".to_string(),
+                "Renew passport".to_string(),
+                "4".to_string(),
+            ],
+            queued: 0,
+            touched: Vec::new(),
+            conflict: None,
+        };
+        assert_eq!(change.command(), "add \"Renew passport\" 4");
+    }
+}
@@ -0,0 +1,180 @@
+//! Virtual tags: tags computed from a todo item's dates and dependencies when it is
+//! queried, rather than stored with it. Also renaming the stored ones, for `tag rename`.
+
+use chrono::{DateTime, Datelike, Duration};
+
+use crate::{fold, Todo};
+
+/// Every virtual tag with what it means, in the order they are documented.
+pub const VIRTUAL_TAGS: &[(&str, &str)] = &[
+    ("OVERDUE", "The due date has passed."),
+    ("TODAY", "Due today (in Utc)."),
+    ("WEEK", "Due this week, Monday to Sunday (in Utc)."),
+    (
+        "BLOCKED",
+        "Depends on other tasks that are still on the list.",
+    ),
+    ("WAITING", "Waiting until a date that has not come yet."),
+    ("SOMEDAY", "On the someday/maybe list."),
+    ("PLANNED", "In the plan accepted for today by plan today."),
+];
+
+/// Returns whether `todo` has the virtual tag `tag` at the timestamp `now`, or `None` if
+/// `tag` is not a virtual tag.
+///
+/// # Example
+///
+/// ```
+/// let overdue = tags::virtual_tag(&todo, "OVERDUE", Utc::now().timestamp());
+/// ```
+pub fn virtual_tag(todo: &Todo, tag: &str, now: i64) -> Option<bool> {
+    let due_between =
+        |(start, end): (i64, i64)| todo.due.is_some_and(|due| start <= due && due < end);
+    match tag {
+        "OVERDUE" => Some(todo.due.is_some_and(|due| due < now)),
+        "TODAY" => Some(due_between(day(now))),
+        "WEEK" => Some(due_between(week(now))),
+        "BLOCKED" => Some(!todo.depends.is_empty()),
+        "WAITING" => Some(todo.wait.is_some_and(|wait| wait > now)),
+        "SOMEDAY" => Some(todo.someday),
+        "PLANNED" => Some(todo.planned_at(now)),
+        _ => None,
+    }
+}
+
+/// Returns the virtual tags `todo` has at the timestamp `now`.
+pub fn virtual_tags(todo: &Todo, now: i64) -> Vec<&'static str> {
+    VIRTUAL_TAGS
+        .iter()
+        .map(|(tag, _)| *tag)
+        .filter(|tag| virtual_tag(todo, tag, now) == Some(true))
+        .collect()
+}
+
+/// Parses a tag given to `tag rename`, with or without its `+`, e.g. `+clientA`.
+pub fn parse_name(input: &str) -> Result<String, String> {
+    let tag = input.trim();
+    let tag = tag.strip_prefix('+').unwrap_or(tag);
+    if tag.is_empty() || tag.contains(char::is_whitespace) {
+        return Err(format!(
+            "Invalid tag: '{}'. Expected one word, e.g. +work",
+            input
+        ));
+    }
+    if VIRTUAL_TAGS.iter().any(|(name, _)| *name == tag) {
+        return Err(format!(
+            "{} is a virtual tag, worked out when queried rather than stored",
+            tag
+        ));
+    }
+    Ok(tag.to_string())
+}
+
+/// Renames the tag `from` of `todo` to `to`, comparing tags as queries do, whatever their
+/// case. Returns whether `todo` had it. A todo item that has `to` already keeps it once.
+pub fn rename(todo: &mut Todo, from: &str, to: &str) -> bool {
+    let from = fold::key(from, false);
+    if !todo.tags.iter().any(|tag| fold::key(tag, false) == from) {
+        return false;
+    }
+    let mut tags = Vec::new();
+    for tag in todo.tags.drain(..) {
+        let tag = match fold::key(&tag, false) == from {
+            true => to.to_string(),
+            false => tag,
+        };
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    todo.tags = tags;
+    true
+}
+
+/// The start and end timestamps of the day containing `now`.
+fn day(now: i64) -> (i64, i64) {
+    let start = DateTime::from_timestamp(now, 0)
+        .unwrap_or_default()
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or_default()
+        .and_utc();
+    (start.timestamp(), (start + Duration::days(1)).timestamp())
+}
+
+/// The start and end timestamps of the week, from Monday, containing `now`.
+fn week(now: i64) -> (i64, i64) {
+    let (today, _) = day(now);
+    let weekday = DateTime::from_timestamp(now, 0)
+        .unwrap_or_default()
+        .weekday()
+        .num_days_from_monday() as i64;
+    let start = today - weekday * 24 * 60 * 60;
+    (start, start + 7 * 24 * 60 * 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_name, rename, virtual_tag, virtual_tags};
+    use crate::TodoList;
+
+    #[test]
+    fn test_virtual_tags() {
+        // Wednesday 2024-06-05 12:00 Utc.
+        let now = 1717588800;
+        let day = 24 * 60 * 60;
+        let mut todo_list = TodoList::new();
+        for task in ["Yesterday", "Tonight", "Sunday", "Next Monday", "Blocked"] {
+            todo_list.add_todo(task, 3);
+        }
+        todo_list.set_due(1, now - day);
+        todo_list.set_due(2, now + 11 * 60 * 60);
+        todo_list.set_due(3, now + 4 * day);
+        todo_list.set_due(4, now + 5 * day);
+        todo_list.add_dependencies(5, &[1]);
+        todo_list.set_wait(5, now + 60);
+        todo_list.todos[4].someday = true;
+
+        let tags: Vec<Vec<&str>> = todo_list
+            .todos
+            .iter()
+            .map(|todo| virtual_tags(todo, now))
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                vec!["OVERDUE", "WEEK"],
+                vec!["TODAY", "WEEK"],
+                vec!["WEEK"],
+                vec![],
+                vec!["BLOCKED", "WAITING", "SOMEDAY"],
+            ]
+        );
+        assert_eq!(
+            virtual_tag(&todo_list.todos[4], "WAITING", now + 60),
+            Some(false)
+        );
+        assert_eq!(virtual_tag(&todo_list.todos[0], "work", now), None);
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Send the invoice", 3);
+        todo_list.add_todo("Call the client", 3);
+        todo_list.todos[0].tags = vec!["clientA".into(), "billing".into()];
+        todo_list.todos[1].tags = vec!["ClientA".into(), "acme-corp".into(), "phone".into()];
+        assert!(rename(&mut todo_list.todos[0], "clienta", "acme-corp"));
+        assert_eq!(todo_list.todos[0].tags, ["acme-corp", "billing"]);
+        // The new tag is kept once, where the first of the two was
+        assert!(rename(&mut todo_list.todos[1], "clientA", "acme-corp"));
+        assert_eq!(todo_list.todos[1].tags, ["acme-corp", "phone"]);
+        assert!(!rename(&mut todo_list.todos[1], "clientA", "acme-corp"));
+
+        assert_eq!(parse_name("+clientA"), Ok("clientA".to_string()));
+        assert_eq!(parse_name("work"), Ok("work".to_string()));
+        assert!(parse_name("+").is_err());
+        assert!(parse_name("client a").is_err());
+        assert!(parse_name("+OVERDUE").is_err());
+    }
+}
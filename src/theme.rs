@@ -0,0 +1,247 @@
+//! Color themes: the colors each kind of output is printed in, and the one place that
+//! decides whether output is colored at all.
+
+use std::collections::BTreeMap;
+
+/// Every part of the output a theme colors, with what it is, in the order they are
+/// documented.
+pub const ROLES: &[(&str, &str)] = &[
+    ("priority1", "Tasks with priority 1."),
+    ("priority2", "Tasks with priority 2."),
+    ("priority3", "Tasks with priority 3."),
+    ("priority4", "Tasks with priority 4."),
+    ("priority5", "Tasks with priority 5."),
+    ("overdue", "The due date of overdue tasks."),
+    ("blocked", "Tasks depending on other tasks."),
+    ("waiting", "Tasks waiting until a later date."),
+    ("added", "Tasks added, in diff."),
+    ("removed", "Tasks removed, in diff."),
+    ("changed", "Tasks changed, in diff."),
+];
+
+/// Every built-in theme with what it is for, in the order they are documented.
+pub const THEMES: &[(&str, &str)] = &[
+    ("default", "Colors for a dark or light terminal."),
+    (
+        "high-contrast",
+        "Bright, bold colors for low-contrast displays.",
+    ),
+    (
+        "monochrome",
+        "Bold, underlined and dimmed text only, for terminals or readers without color.",
+    ),
+];
+
+/// The colors of a built-in theme, in the order of `ROLES`.
+fn builtin(name: &str) -> Option<[&'static str; ROLES.len()]> {
+    match name {
+        "default" => Some([
+            "blue", "cyan", "none", "yellow", "bold red", "red", "dim", "dim", "green", "red",
+            "yellow",
+        ]),
+        "high-contrast" => Some([
+            "bright-blue",
+            "bright-cyan",
+            "bright-white",
+            "bold bright-yellow",
+            "bold bright-white on-red",
+            "bold bright-red",
+            "dim",
+            "italic",
+            "bold bright-green",
+            "bold bright-red",
+            "bold bright-yellow",
+        ]),
+        "monochrome" => Some([
+            "dim",
+            "none",
+            "none",
+            "bold",
+            "bold underline",
+            "reverse",
+            "dim",
+            "italic",
+            "bold",
+            "dim",
+            "underline",
+        ]),
+        _ => None,
+    }
+}
+
+const COLORS: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// The colors output is printed in. Every colored print goes through `paint`, so a theme
+/// made with colors disabled prints nothing but the text.
+#[derive(Debug, Default)]
+pub struct Theme {
+    /// The escape code parameters of each role, e.g. `"1;31"`.
+    codes: BTreeMap<&'static str, String>,
+    enabled: bool,
+}
+
+impl Theme {
+    /// Makes the built-in theme `name` with the colors in `overrides` replacing its own,
+    /// coloring output only when `enabled` is set.
+    ///
+    /// A color is a space-separated list of words: a color (`red`, `bright-red`), a
+    /// background (`on-red`), `bold`, `dim`, `italic`, `underline`, `reverse`, or `none`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let theme = Theme::new("default", &config.colors, config.use_color())?;
+    /// println!("{}", theme.paint("overdue", "due: yesterday"));
+    /// ```
+    pub fn new(
+        name: &str,
+        overrides: &BTreeMap<String, String>,
+        enabled: bool,
+    ) -> Result<Theme, String> {
+        let colors = builtin(name).ok_or_else(|| {
+            format!(
+                "Unknown theme: '{}'. The themes are: {}",
+                name,
+                THEMES
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })?;
+        let mut codes = BTreeMap::new();
+        for ((role, _), color) in ROLES.iter().zip(colors) {
+            codes.insert(*role, parse_color(color)?);
+        }
+        for (role, color) in overrides {
+            let role = ROLES
+                .iter()
+                .map(|(role, _)| *role)
+                .find(|known| known == role)
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown color role: '{}'. The roles are: {}",
+                        role,
+                        ROLES
+                            .iter()
+                            .map(|(role, _)| *role)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })?;
+            let code = parse_color(color).map_err(|error| format!("{} (for '{}')", error, role))?;
+            codes.insert(role, code);
+        }
+        Ok(Theme { codes, enabled })
+    }
+
+    /// Returns `text` in the color of `role`, or unchanged when colors are disabled.
+    pub fn paint(&self, role: &str, text: &str) -> String {
+        match self.codes.get(role) {
+            Some(code) if self.enabled && !code.is_empty() => {
+                format!("\x1b[{}m{}\x1b[0m", code, text)
+            }
+            _ => text.to_string(),
+        }
+    }
+
+    /// Returns `text` in `color`, such as a todo item's highlight, or unchanged when colors
+    /// are disabled or `color` is invalid.
+    pub fn paint_color(&self, color: &str, text: &str) -> String {
+        match parse_color(color) {
+            Ok(code) if self.enabled && !code.is_empty() => {
+                format!("\x1b[{}m{}\x1b[0m", code, text)
+            }
+            _ => text.to_string(),
+        }
+    }
+}
+
+/// Checks that `color` is a valid color, e.g. for a todo item's highlight.
+pub fn check_color(color: &str) -> Result<(), String> {
+    parse_color(color).map(|_| ())
+}
+
+/// Parses a color such as `"bold bright-red"` into escape code parameters, e.g. `"1;91"`.
+fn parse_color(color: &str) -> Result<String, String> {
+    let mut codes = Vec::new();
+    for word in color.split_whitespace() {
+        let code = match word {
+            "none" => continue,
+            "bold" => 1,
+            "dim" => 2,
+            "italic" => 3,
+            "underline" => 4,
+            "reverse" => 7,
+            _ => {
+                let (base, name) = match (word.strip_prefix("bright-"), word.strip_prefix("on-")) {
+                    (Some(name), _) => (90, name),
+                    (_, Some(name)) => (40, name),
+                    _ => (30, word),
+                };
+                match COLORS.iter().position(|color| *color == name) {
+                    Some(index) => base + index,
+                    None => return Err(format!("Unknown color: '{}'", word)),
+                }
+            }
+        };
+        codes.push(code.to_string());
+    }
+    Ok(codes.join(";"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_color, Theme, ROLES, THEMES};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("red"), Ok("31".to_string()));
+        assert_eq!(
+            parse_color("bold bright-white on-red"),
+            Ok("1;97;41".to_string())
+        );
+        assert_eq!(parse_color("none"), Ok(String::new()));
+        assert_eq!(
+            parse_color("bold purple"),
+            Err("Unknown color: 'purple'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_theme() {
+        for (name, _) in THEMES {
+            assert!(Theme::new(name, &BTreeMap::new(), true).is_ok());
+        }
+        let mut colors = BTreeMap::new();
+        colors.insert("priority5".to_string(), "magenta underline".to_string());
+        let theme = Theme::new("default", &colors, true).unwrap();
+        assert_eq!(
+            theme.paint("priority5", "5: Pay rent"),
+            "\x1b[35;4m5: Pay rent\x1b[0m"
+        );
+        assert_eq!(theme.paint("overdue", "due"), "\x1b[31mdue\x1b[0m");
+        assert_eq!(theme.paint("priority3", "3: Read"), "3: Read");
+        assert_eq!(
+            theme.paint_color("black on-yellow", "3: Read"),
+            "\x1b[30;43m3: Read\x1b[0m"
+        );
+
+        let theme = Theme::new("high-contrast", &colors, false).unwrap();
+        for (role, _) in ROLES {
+            assert_eq!(theme.paint(role, "text"), "text");
+        }
+        assert_eq!(theme.paint_color("red", "text"), "text");
+
+        assert!(Theme::new("neon", &BTreeMap::new(), true)
+            .unwrap_err()
+            .starts_with("Unknown theme: 'neon'"));
+        colors.insert("urgent".to_string(), "red".to_string());
+        assert!(Theme::new("default", &colors, true)
+            .unwrap_err()
+            .starts_with("Unknown color role: 'urgent'"));
+    }
+}
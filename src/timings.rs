@@ -0,0 +1,149 @@
+//! `--timings`: how long loading the todo list, running the command and saving the list
+//! took, reported on stderr when the program exits, so slow lists can be measured where
+//! they are used.
+//!
+//! Most commands end with `std::process::exit`, which skips the end of `main`, so the
+//! report is printed by a handler registered with the C library's `atexit`, which runs on
+//! every normal exit.
+
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// What has been timed so far, once timing is enabled.
+static TIMINGS: Mutex<Option<Timings>> = Mutex::new(None);
+
+#[derive(Debug)]
+struct Timings {
+    started: Instant,
+    /// How long loading the list took and how many items it had.
+    load: Option<(Duration, usize)>,
+    /// When the command started running, after the list was loaded.
+    command: Option<Instant>,
+    /// How long each save took and how many items were saved.
+    saves: Vec<(Duration, usize)>,
+}
+
+extern "C" {
+    fn atexit(callback: extern "C" fn()) -> i32;
+}
+
+/// Starts timing, to be reported on stderr when the program exits.
+pub fn enable() {
+    if let Ok(mut timings) = TIMINGS.lock() {
+        *timings = Some(Timings {
+            started: Instant::now(),
+            load: None,
+            command: None,
+            saves: Vec::new(),
+        });
+    }
+    // SAFETY: `report` only reads the timings, and does not unwind
+    unsafe {
+        atexit(report);
+    }
+}
+
+/// Records loading the list, which started at `started`, with `items` todo items, and
+/// starts timing the command.
+pub fn loaded(started: Instant, items: usize) {
+    record(|timings| {
+        timings.load = Some((started.elapsed(), items));
+        timings.command = Some(Instant::now());
+    });
+}
+
+/// Records saving the list, which started at `started`, with `items` todo items.
+pub fn saved(started: Instant, items: usize) {
+    record(|timings| timings.saves.push((started.elapsed(), items)));
+}
+
+fn record(update: impl FnOnce(&mut Timings)) {
+    if let Ok(mut timings) = TIMINGS.lock() {
+        if let Some(timings) = timings.as_mut() {
+            update(timings);
+        }
+    }
+}
+
+extern "C" fn report() {
+    let now = Instant::now();
+    let report = match TIMINGS.lock() {
+        Ok(timings) => timings.as_ref().map(|timings| timings.render(now)),
+        Err(_) => None,
+    };
+    if let Some(report) = report {
+        eprint!("{}", report);
+    }
+}
+
+impl Timings {
+    /// Formats the timings as one `timings:` line per phase, as of `now`. The command's
+    /// time leaves out the saves made during it.
+    fn render(&self, now: Instant) -> String {
+        let saving: Duration = self.saves.iter().map(|(took, _)| *took).sum();
+        let mut output = String::new();
+        if let Some((took, items)) = self.load {
+            let _ = writeln!(output, "timings: load {} ({} items)", millis(took), items);
+        }
+        if let Some(command) = self.command {
+            let took = now.duration_since(command).saturating_sub(saving);
+            let _ = writeln!(output, "timings: command {}", millis(took));
+        }
+        if let Some((_, items)) = self.saves.last() {
+            let saves = match self.saves.len() {
+                1 => "1 save".to_string(),
+                count => format!("{} saves", count),
+            };
+            let _ = writeln!(
+                output,
+                "timings: save {} ({} items, {})",
+                millis(saving),
+                items,
+                saves
+            );
+        }
+        let total = now.duration_since(self.started);
+        let _ = writeln!(output, "timings: total {}", millis(total));
+        output
+    }
+}
+
+/// Formats a duration in milliseconds, e.g. `12.345ms`.
+fn millis(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{millis, Timings};
+
+    #[test]
+    fn test_render() {
+        let started = Instant::now();
+        let ms = Duration::from_millis;
+        let timings = Timings {
+            started,
+            load: Some((ms(5), 1500)),
+            command: Some(started + ms(5)),
+            saves: vec![(ms(4), 1500), (ms(6), 1501)],
+        };
+        assert_eq!(
+            timings.render(started + ms(40)),
+            "timings: load 5.000ms (1500 items)\n\
+             timings: command 25.000ms\n\
+             timings: save 10.000ms (1501 items, 2 saves)\n\
+             timings: total 40.000ms\n"
+        );
+        let timings = Timings {
+            started,
+            load: None,
+            command: None,
+            saves: Vec::new(),
+        };
+        assert_eq!(timings.render(started), "timings: total 0.000ms\n");
+        assert_eq!(millis(Duration::from_micros(1234)), "1.234ms");
+    }
+}
@@ -0,0 +1,444 @@
+//! HTTPS for `serve` and `https://` remotes, through the system's OpenSSL library.
+//!
+//! TLS is only built with the `tls` feature, `cargo build --features tls`, which links
+//! libssl and libcrypto (OpenSSL 1.1.1 or 3); without it the program needs nothing but the
+//! C library, and asking for TLS is reported as an error. The self-signed certificate of
+//! `serve --tls-self-signed` is made by the `openssl` command, in the same way as signing
+//! is left to `ssh-keygen`.
+
+use std::net::{IpAddr, TcpStream};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// The directory, next to the todo list file, that `serve --tls-self-signed` keeps its
+/// certificate and key in.
+pub const SELF_SIGNED_DIR: &str = ".todo-tls";
+
+/// How many days a self-signed certificate is valid for, the most Apple devices accept.
+const SELF_SIGNED_DAYS: &str = "825";
+
+/// Accepts TLS connections with a certificate and its private key.
+pub struct Acceptor {
+    #[cfg(feature = "tls")]
+    context: openssl::Context,
+}
+
+/// An encrypted connection, read from and written to like the `TcpStream` it wraps.
+#[cfg(feature = "tls")]
+pub struct TlsStream {
+    ssl: *mut openssl::Ssl,
+    // Kept open as long as the connection is, as OpenSSL only has its file descriptor
+    _stream: TcpStream,
+}
+
+/// An encrypted connection, which cannot be made without the `tls` feature.
+#[cfg(not(feature = "tls"))]
+pub enum TlsStream {}
+
+#[cfg(not(feature = "tls"))]
+const NOT_BUILT: &str =
+    "This build has no TLS support. Rebuild it with `cargo build --release --features tls`";
+
+impl Acceptor {
+    /// Loads the certificate chain at `cert` and the private key at `key`, both PEM.
+    #[cfg(feature = "tls")]
+    pub fn new(cert: &Path, key: &Path) -> Result<Acceptor, String> {
+        let context = openssl::Context::server(cert, key)?;
+        Ok(Acceptor { context })
+    }
+
+    #[cfg(not(feature = "tls"))]
+    pub fn new(_cert: &Path, _key: &Path) -> Result<Acceptor, String> {
+        Err(NOT_BUILT.to_string())
+    }
+
+    /// Makes the TLS handshake with a client that connected on `stream`.
+    #[cfg(feature = "tls")]
+    pub fn accept(&self, stream: TcpStream) -> Result<TlsStream, String> {
+        openssl::accept(&self.context, stream)
+    }
+
+    #[cfg(not(feature = "tls"))]
+    pub fn accept(&self, _stream: TcpStream) -> Result<TlsStream, String> {
+        Err(NOT_BUILT.to_string())
+    }
+}
+
+/// Makes the TLS handshake with the server `host` on `stream`, checking that its
+/// certificate is for `host` and is signed by an authority the system trusts, or is the
+/// certificate at `ca`, or signed by it, when given.
+#[cfg(feature = "tls")]
+pub fn connect(stream: TcpStream, host: &str, ca: Option<&Path>) -> Result<TlsStream, String> {
+    openssl::connect(stream, host, ca)
+}
+
+#[cfg(not(feature = "tls"))]
+pub fn connect(_stream: TcpStream, _host: &str, _ca: Option<&Path>) -> Result<TlsStream, String> {
+    Err(NOT_BUILT.to_string())
+}
+
+#[cfg(not(feature = "tls"))]
+impl std::io::Read for TlsStream {
+    fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+        match *self {}
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+impl std::io::Write for TlsStream {
+    fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+        match *self {}
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match *self {}
+    }
+}
+
+/// Makes a self-signed certificate for `names`, host names or IP addresses, at `cert` and
+/// its private key at `key`, with the `openssl` command.
+pub fn self_signed(cert: &Path, key: &Path, names: &[String]) -> Result<(), String> {
+    let alt_names: Vec<String> = names
+        .iter()
+        .map(|name| match name.parse::<IpAddr>() {
+            Ok(_) => format!("IP:{}", name),
+            Err(_) => format!("DNS:{}", name),
+        })
+        .collect();
+    let output = Command::new("openssl")
+        .args(["req", "-x509", "-newkey", "ec", "-pkeyopt"])
+        .arg("ec_paramgen_curve:prime256v1")
+        .args(["-nodes", "-days", SELF_SIGNED_DAYS, "-subj"])
+        .arg(format!(
+            "/CN={}",
+            names.first().map_or("localhost", String::as_str)
+        ))
+        .arg("-addext")
+        .arg(format!("subjectAltName={}", alt_names.join(",")))
+        .arg("-keyout")
+        .arg(key)
+        .arg("-out")
+        .arg(cert)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| format!("Unable to run openssl: {}", error))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Unable to make a certificate: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(key, std::fs::Permissions::from_mode(0o600))
+            .map_err(|error| format!("Unable to protect {}: {}", key.display(), error))?;
+    }
+    Ok(())
+}
+
+/// Returns the SHA-256 fingerprint of the certificate at `cert`, e.g. `AB:CD:...`, to check
+/// against what a phone shows before trusting it.
+pub fn fingerprint(cert: &Path) -> Result<String, String> {
+    let output = Command::new("openssl")
+        .args(["x509", "-noout", "-fingerprint", "-sha256", "-in"])
+        .arg(cert)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| format!("Unable to run openssl: {}", error))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match stdout.split_once('=') {
+        Some((_, fingerprint)) if output.status.success() => Ok(fingerprint.trim().to_string()),
+        _ => Err(format!(
+            "Unable to read {}: {}",
+            cert.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+/// The few libssl functions the connections need, declared as in `openssl/ssl.h`.
+#[cfg(feature = "tls")]
+mod openssl {
+    use std::ffi::CString;
+    use std::io::{Read, Write};
+    use std::net::{IpAddr, TcpStream};
+    use std::os::raw::{c_char, c_int, c_long, c_ulong, c_void};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    use super::TlsStream;
+
+    #[repr(C)]
+    pub struct Ssl {
+        _private: [u8; 0],
+    }
+    #[repr(C)]
+    pub struct SslContext {
+        _private: [u8; 0],
+    }
+    #[repr(C)]
+    pub struct SslMethod {
+        _private: [u8; 0],
+    }
+
+    const SSL_FILETYPE_PEM: c_int = 1;
+    const SSL_VERIFY_PEER: c_int = 1;
+    const SSL_CTRL_SET_TLSEXT_HOSTNAME: c_int = 55;
+    const SSL_CTRL_SET_MIN_PROTO_VERSION: c_int = 123;
+    const TLSEXT_NAMETYPE_HOST_NAME: c_long = 0;
+    const TLS1_2_VERSION: c_long = 0x0303;
+    const SSL_ERROR_SYSCALL: c_int = 5;
+    const SSL_ERROR_ZERO_RETURN: c_int = 6;
+
+    #[link(name = "ssl")]
+    extern "C" {
+        fn TLS_server_method() -> *const SslMethod;
+        fn TLS_client_method() -> *const SslMethod;
+        fn SSL_CTX_new(method: *const SslMethod) -> *mut SslContext;
+        fn SSL_CTX_free(context: *mut SslContext);
+        fn SSL_CTX_ctrl(
+            context: *mut SslContext,
+            cmd: c_int,
+            larg: c_long,
+            parg: *mut c_void,
+        ) -> c_long;
+        fn SSL_CTX_use_certificate_chain_file(
+            context: *mut SslContext,
+            file: *const c_char,
+        ) -> c_int;
+        fn SSL_CTX_use_PrivateKey_file(
+            context: *mut SslContext,
+            file: *const c_char,
+            kind: c_int,
+        ) -> c_int;
+        fn SSL_CTX_check_private_key(context: *const SslContext) -> c_int;
+        fn SSL_CTX_set_verify(context: *mut SslContext, mode: c_int, callback: *const c_void);
+        fn SSL_CTX_set_default_verify_paths(context: *mut SslContext) -> c_int;
+        fn SSL_CTX_load_verify_locations(
+            context: *mut SslContext,
+            file: *const c_char,
+            path: *const c_char,
+        ) -> c_int;
+        fn SSL_new(context: *mut SslContext) -> *mut Ssl;
+        fn SSL_free(ssl: *mut Ssl);
+        fn SSL_set_fd(ssl: *mut Ssl, fd: c_int) -> c_int;
+        fn SSL_set1_host(ssl: *mut Ssl, host: *const c_char) -> c_int;
+        fn SSL_ctrl(ssl: *mut Ssl, cmd: c_int, larg: c_long, parg: *mut c_void) -> c_long;
+        fn SSL_accept(ssl: *mut Ssl) -> c_int;
+        fn SSL_connect(ssl: *mut Ssl) -> c_int;
+        fn SSL_read(ssl: *mut Ssl, buf: *mut c_void, num: c_int) -> c_int;
+        fn SSL_write(ssl: *mut Ssl, buf: *const c_void, num: c_int) -> c_int;
+        fn SSL_get_error(ssl: *const Ssl, ret: c_int) -> c_int;
+        fn SSL_shutdown(ssl: *mut Ssl) -> c_int;
+    }
+
+    #[link(name = "crypto")]
+    extern "C" {
+        fn ERR_get_error() -> c_ulong;
+        fn ERR_error_string_n(error: c_ulong, buf: *mut c_char, len: usize);
+    }
+
+    /// An `SSL_CTX`: the settings connections are made with.
+    pub struct Context(*mut SslContext);
+
+    impl Drop for Context {
+        fn drop(&mut self) {
+            // SAFETY: the context was made by SSL_CTX_new and is freed once
+            unsafe { SSL_CTX_free(self.0) }
+        }
+    }
+
+    impl Context {
+        /// Makes a context allowing TLS 1.2 and later.
+        fn new(method: *const SslMethod) -> Result<Context, String> {
+            // SAFETY: the method is one of OpenSSL's static ones
+            let context = unsafe { SSL_CTX_new(method) };
+            if context.is_null() {
+                return Err(error("Unable to set up TLS"));
+            }
+            // SAFETY: the context is valid, and this control takes no pointer
+            unsafe {
+                SSL_CTX_ctrl(
+                    context,
+                    SSL_CTRL_SET_MIN_PROTO_VERSION,
+                    TLS1_2_VERSION,
+                    std::ptr::null_mut(),
+                )
+            };
+            Ok(Context(context))
+        }
+
+        /// Makes a context serving the certificate chain at `cert` with the key at `key`.
+        pub fn server(cert: &Path, key: &Path) -> Result<Context, String> {
+            // SAFETY: TLS_server_method returns a static method
+            let context = Context::new(unsafe { TLS_server_method() })?;
+            let (cert_path, key_path) = (c_path(cert)?, c_path(key)?);
+            // SAFETY: the context is valid and the paths are NUL-terminated
+            unsafe {
+                if SSL_CTX_use_certificate_chain_file(context.0, cert_path.as_ptr()) != 1 {
+                    return Err(error(&format!("Unable to read {}", cert.display())));
+                }
+                if SSL_CTX_use_PrivateKey_file(context.0, key_path.as_ptr(), SSL_FILETYPE_PEM) != 1
+                {
+                    return Err(error(&format!("Unable to read {}", key.display())));
+                }
+                if SSL_CTX_check_private_key(context.0) != 1 {
+                    return Err(error(&format!(
+                        "{} is not the key of {}",
+                        key.display(),
+                        cert.display()
+                    )));
+                }
+            }
+            Ok(context)
+        }
+    }
+
+    /// Wraps `stream` in a new connection of `context`.
+    fn wrap(context: &Context, stream: TcpStream) -> Result<TlsStream, String> {
+        // SAFETY: the context is valid, and the new SSL holds its own reference to it
+        let ssl = unsafe { SSL_new(context.0) };
+        if ssl.is_null() {
+            return Err(error("Unable to set up TLS"));
+        }
+        // Made first, so the SSL is freed whatever happens next
+        let tls = TlsStream {
+            ssl,
+            _stream: stream,
+        };
+        // SAFETY: the SSL is valid and the descriptor stays open as long as it does
+        if unsafe { SSL_set_fd(ssl, tls._stream.as_raw_fd()) } != 1 {
+            return Err(error("Unable to set up TLS"));
+        }
+        Ok(tls)
+    }
+
+    pub fn accept(context: &Context, stream: TcpStream) -> Result<TlsStream, String> {
+        let tls = wrap(context, stream)?;
+        // SAFETY: the SSL is valid
+        if unsafe { SSL_accept(tls.ssl) } != 1 {
+            return Err(error("TLS handshake failed"));
+        }
+        Ok(tls)
+    }
+
+    pub fn connect(stream: TcpStream, host: &str, ca: Option<&Path>) -> Result<TlsStream, String> {
+        // SAFETY: TLS_client_method returns a static method
+        let context = Context::new(unsafe { TLS_client_method() })?;
+        // SAFETY: the context is valid and the paths are NUL-terminated
+        unsafe {
+            SSL_CTX_set_verify(context.0, SSL_VERIFY_PEER, std::ptr::null());
+            let loaded = match ca {
+                Some(ca) => {
+                    let path = c_path(ca)?;
+                    SSL_CTX_load_verify_locations(context.0, path.as_ptr(), std::ptr::null())
+                }
+                None => SSL_CTX_set_default_verify_paths(context.0),
+            };
+            if loaded != 1 {
+                let ca = ca.map_or("the trusted certificates".to_string(), |ca| {
+                    ca.display().to_string()
+                });
+                return Err(error(&format!("Unable to read {}", ca)));
+            }
+        }
+        let tls = wrap(&context, stream)?;
+        let name = CString::new(host).map_err(|_| format!("Invalid host: {}", host))?;
+        // SAFETY: the SSL is valid and the name NUL-terminated; OpenSSL copies both
+        unsafe {
+            if SSL_set1_host(tls.ssl, name.as_ptr()) != 1 {
+                return Err(error("Unable to set up TLS"));
+            }
+            // Servers hosting several names pick the certificate by this, which is only
+            // sent for names, not addresses
+            if host.parse::<IpAddr>().is_err() {
+                SSL_ctrl(
+                    tls.ssl,
+                    SSL_CTRL_SET_TLSEXT_HOSTNAME,
+                    TLSEXT_NAMETYPE_HOST_NAME,
+                    name.as_ptr() as *mut c_void,
+                );
+            }
+            if SSL_connect(tls.ssl) != 1 {
+                return Err(error(&format!("TLS handshake with {} failed", host)));
+            }
+        }
+        Ok(tls)
+    }
+
+    impl Read for TlsStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(c_int::MAX as usize) as c_int;
+            // SAFETY: the SSL is valid and the buffer holds at least `len` bytes
+            let read = unsafe { SSL_read(self.ssl, buf.as_mut_ptr() as *mut c_void, len) };
+            if read > 0 {
+                return Ok(read as usize);
+            }
+            // SAFETY: the SSL is valid
+            match unsafe { SSL_get_error(self.ssl, read) } {
+                SSL_ERROR_ZERO_RETURN => Ok(0),
+                SSL_ERROR_SYSCALL => Err(std::io::Error::last_os_error()),
+                _ => Err(std::io::Error::other(error("Unable to read"))),
+            }
+        }
+    }
+
+    impl Write for TlsStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(c_int::MAX as usize) as c_int;
+            // SAFETY: the SSL is valid and the buffer holds at least `len` bytes
+            let written = unsafe { SSL_write(self.ssl, buf.as_ptr() as *const c_void, len) };
+            if written > 0 {
+                return Ok(written as usize);
+            }
+            // SAFETY: the SSL is valid
+            match unsafe { SSL_get_error(self.ssl, written) } {
+                SSL_ERROR_SYSCALL => Err(std::io::Error::last_os_error()),
+                _ => Err(std::io::Error::other(error("Unable to write"))),
+            }
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Drop for TlsStream {
+        fn drop(&mut self) {
+            // SAFETY: the SSL was made by SSL_new and is freed once
+            unsafe {
+                SSL_shutdown(self.ssl);
+                SSL_free(self.ssl);
+            }
+        }
+    }
+
+    fn c_path(path: &Path) -> Result<CString, String> {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| format!("Invalid path: {}", path.display()))
+    }
+
+    /// Describes the failure `what` with the reason OpenSSL gives, emptying its error queue.
+    fn error(what: &str) -> String {
+        let mut reasons = Vec::new();
+        loop {
+            // SAFETY: reads OpenSSL's thread-local error queue
+            let code = unsafe { ERR_get_error() };
+            if code == 0 {
+                break;
+            }
+            let mut buf = [0 as c_char; 256];
+            // SAFETY: the buffer is as long as the length given, and is NUL-terminated
+            unsafe { ERR_error_string_n(code, buf.as_mut_ptr(), buf.len()) };
+            // SAFETY: ERR_error_string_n always NUL-terminates
+            let reason = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+            reasons.push(reason.to_string_lossy().into_owned());
+        }
+        match reasons.last() {
+            Some(reason) => format!("{}: {}", what, reason),
+            None => what.to_string(),
+        }
+    }
+}
@@ -0,0 +1,653 @@
+//! Reading and writing TOML 1.0 (https://toml.io), for todo list files in that format.
+//!
+//! A list is written as a table holding an array of tables, `[[todos]]`, one per todo
+//! item, with each item's own arrays of tables, such as its reminders, after it. Dates and
+//! times are read as the strings they are written as, and infinity and NaN are not read.
+
+use std::collections::HashSet;
+
+use serde_json::{Map, Number, Value};
+
+/// Writes out `value`, which has to be a table, as a TOML document. TOML has no null, so
+/// null values are left out.
+pub fn write(value: &Value) -> String {
+    let Value::Object(table) = value else {
+        panic!("Only a table can be written as a TOML document");
+    };
+    let mut out = String::new();
+    write_table(&mut Vec::new(), table, &mut out);
+    out
+}
+
+/// Writes the keys of `table`, whose header has been written, then its tables and arrays of
+/// tables under their own headers, `path` being the keys that lead to it.
+fn write_table(path: &mut Vec<String>, table: &Map<String, Value>, out: &mut String) {
+    for (key, value) in table {
+        if !value.is_null() && !value.is_object() && !is_table_array(value) {
+            out.push_str(&format!("{} = {}\n", write_key(key), inline(value)));
+        }
+    }
+    for (key, value) in table {
+        path.push(write_key(key));
+        match value {
+            Value::Object(table) => {
+                header(&format!("[{}]", path.join(".")), out);
+                write_table(path, table, out);
+            }
+            Value::Array(items) if is_table_array(value) => {
+                for item in items {
+                    header(&format!("[[{}]]", path.join(".")), out);
+                    write_table(path, item.as_object().expect("An array of tables"), out);
+                }
+            }
+            _ => {}
+        }
+        path.pop();
+    }
+}
+
+fn header(header: &str, out: &mut String) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(header);
+    out.push('\n');
+}
+
+/// Returns whether `value` is written as an array of tables: a list of objects.
+fn is_table_array(value: &Value) -> bool {
+    value
+        .as_array()
+        .is_some_and(|items| !items.is_empty() && items.iter().all(Value::is_object))
+}
+
+fn write_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        write_string(key)
+    }
+}
+
+/// Writes `value` as it is written after a key's `=`.
+fn inline(value: &Value) -> String {
+    match value {
+        Value::String(text) if text.contains('\n') => {
+            // A line break right after the opening quotes is not part of the string
+            let mut written = String::from("\"\"\"\n");
+            for c in text.chars() {
+                match c {
+                    '\n' => written.push('\n'),
+                    c => written.push_str(&escape(c)),
+                }
+            }
+            written.push_str("\"\"\"");
+            written
+        }
+        Value::String(text) => write_string(text),
+        Value::Array(items) => {
+            let items: Vec<String> = items
+                .iter()
+                .filter(|item| !item.is_null())
+                .map(inline)
+                .collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(table) if table.is_empty() => "{}".to_string(),
+        Value::Object(table) => {
+            let entries: Vec<String> = table
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| format!("{} = {}", write_key(key), inline(value)))
+                .collect();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        _ => value.to_string(),
+    }
+}
+
+fn write_string(text: &str) -> String {
+    let escaped: String = text.chars().map(escape).collect();
+    format!("\"{}\"", escaped)
+}
+
+/// Escapes `c` for a basic string.
+fn escape(c: char) -> String {
+    match c {
+        '\\' => "\\\\".to_string(),
+        '"' => "\\\"".to_string(),
+        '\n' => "\\n".to_string(),
+        '\t' => "\\t".to_string(),
+        '\r' => "\\r".to_string(),
+        c if c.is_control() => format!("\\u{:04X}", c as u32),
+        c => c.to_string(),
+    }
+}
+
+/// Reads a TOML document.
+pub fn parse(contents: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        text: contents.strip_prefix('\u{feff}').unwrap_or(contents),
+        pos: 0,
+    };
+    let mut root = Map::new();
+    // The table that keys are added to, and the tables that have had a header
+    let mut current: Vec<String> = Vec::new();
+    let mut defined: HashSet<Vec<String>> = HashSet::new();
+    loop {
+        parser.skip_spaces();
+        match parser.peek() {
+            None => break,
+            Some('#' | '\n' | '\r') => {}
+            Some('[') => {
+                parser.pos += 1;
+                let array = parser.peek() == Some('[');
+                if array {
+                    parser.pos += 1;
+                }
+                parser.skip_spaces();
+                let path = parser.keys()?;
+                parser.expect(if array { "]]" } else { "]" })?;
+                if array {
+                    let (parent, last) = path.split_at(path.len() - 1);
+                    let table =
+                        table_at(&mut root, parent).map_err(|error| parser.error(&error))?;
+                    let items = table
+                        .entry(last[0].clone())
+                        .or_insert_with(|| Value::Array(Vec::new()));
+                    match items {
+                        Value::Array(items) => items.push(Value::Object(Map::new())),
+                        _ => {
+                            return Err(parser
+                                .error(&format!("'{}' is not an array of tables", path.join("."))))
+                        }
+                    }
+                    // The tables of the new item can be given headers again
+                    defined.retain(|defined| !defined.starts_with(&path));
+                } else {
+                    if !defined.insert(path.clone()) {
+                        return Err(
+                            parser.error(&format!("table '{}' defined twice", path.join(".")))
+                        );
+                    }
+                    table_at(&mut root, &path).map_err(|error| parser.error(&error))?;
+                }
+                current = path;
+            }
+            Some(_) => {
+                let table = table_at(&mut root, &current).map_err(|error| parser.error(&error))?;
+                parser.key_value(table)?;
+            }
+        }
+        parser.skip_spaces();
+        parser.skip_comment();
+        match parser.peek() {
+            None => break,
+            Some('\n') => parser.pos += 1,
+            Some('\r') if parser.text[parser.pos..].starts_with("\r\n") => parser.pos += 2,
+            Some(c) => return Err(parser.error(&format!("unexpected '{}'", c))),
+        }
+    }
+    Ok(Value::Object(root))
+}
+
+/// Returns the table that `path` leads to from `root`, adding the tables missing on the way,
+/// where a key holding an array of tables leads to its last one.
+fn table_at<'m>(
+    root: &'m mut Map<String, Value>,
+    path: &[String],
+) -> Result<&'m mut Map<String, Value>, String> {
+    let mut table = root;
+    for key in path {
+        table = match table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(Map::new()))
+        {
+            Value::Object(table) => table,
+            Value::Array(items) => match items.last_mut() {
+                Some(Value::Object(table)) => table,
+                _ => return Err(format!("'{}' is not a table", key)),
+            },
+            _ => return Err(format!("'{}' is not a table", key)),
+        };
+    }
+    Ok(table)
+}
+
+struct Parser<'t> {
+    text: &'t str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn error(&self, message: &str) -> String {
+        let line = self.text[..self.pos].matches('\n').count() + 1;
+        format!("Line {}: {}", line, message)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        self.skip_spaces();
+        if self.text[self.pos..].starts_with(expected) {
+            self.pos += expected.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            self.pos = self.text[self.pos..]
+                .find('\n')
+                .map_or(self.text.len(), |end| self.pos + end);
+        }
+    }
+
+    /// Skips spaces, line breaks and comments, as arrays allow between their items.
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n' | '\r') => self.pos += 1,
+                _ => return,
+            }
+        }
+    }
+
+    /// Reads a key, which may be dotted, e.g. `todos.reminders`.
+    fn keys(&mut self) -> Result<Vec<String>, String> {
+        let mut keys = Vec::new();
+        loop {
+            self.skip_spaces();
+            let key = match self.peek() {
+                Some('"') => {
+                    self.pos += 1;
+                    self.basic_string()?
+                }
+                Some('\'') => {
+                    self.pos += 1;
+                    self.literal_string()?
+                }
+                _ => {
+                    let rest = &self.text[self.pos..];
+                    let end = rest
+                        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+                        .unwrap_or(rest.len());
+                    if end == 0 {
+                        return Err(self.error("expected a key"));
+                    }
+                    self.pos += end;
+                    rest[..end].to_string()
+                }
+            };
+            keys.push(key);
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                return Ok(keys);
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Reads `key = value` into `table`.
+    fn key_value(&mut self, table: &mut Map<String, Value>) -> Result<(), String> {
+        let keys = self.keys()?;
+        self.expect("=")?;
+        self.skip_spaces();
+        let value = self.value()?;
+        let (parent, last) = keys.split_at(keys.len() - 1);
+        let table = table_at(table, parent).map_err(|error| self.error(&error))?;
+        if table.contains_key(&last[0]) {
+            return Err(self.error(&format!("duplicate key '{}'", keys.join("."))));
+        }
+        table.insert(last[0].clone(), value);
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        let rest = &self.text[self.pos..];
+        if rest.starts_with("\"\"\"") || rest.starts_with("'''") {
+            self.pos += 3;
+            return self
+                .multiline_string(rest.starts_with('"'))
+                .map(Value::String);
+        }
+        match self.peek() {
+            Some('"') => {
+                self.pos += 1;
+                self.basic_string().map(Value::String)
+            }
+            Some('\'') => {
+                self.pos += 1;
+                self.literal_string().map(Value::String)
+            }
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_blank();
+                    if self.peek() == Some(']') {
+                        self.pos += 1;
+                        return Ok(Value::Array(items));
+                    }
+                    items.push(self.value()?);
+                    self.skip_blank();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some(']') => {
+                            self.pos += 1;
+                            return Ok(Value::Array(items));
+                        }
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut table = Map::new();
+                loop {
+                    self.skip_blank();
+                    if self.peek() == Some('}') {
+                        self.pos += 1;
+                        return Ok(Value::Object(table));
+                    }
+                    self.key_value(&mut table)?;
+                    self.skip_blank();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some('}') => {
+                            self.pos += 1;
+                            return Ok(Value::Object(table));
+                        }
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            }
+            _ => self.scalar(),
+        }
+    }
+
+    /// Reads a boolean, number, date or time.
+    fn scalar(&mut self) -> Result<Value, String> {
+        let rest = &self.text[self.pos..];
+        let token_end = |from: usize| {
+            rest[from..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || "_+-.:".contains(c)))
+                .map_or(rest.len(), |end| from + end)
+        };
+        let mut end = token_end(0);
+        // A date and a time may be separated by a space
+        let time = &rest.as_bytes()[end..];
+        if is_date(&rest[..end])
+            && time.len() >= 4
+            && time[0] == b' '
+            && time[1..3].iter().all(u8::is_ascii_digit)
+            && time[3] == b':'
+        {
+            end = token_end(end + 1);
+        }
+        let word = &rest[..end];
+        let invalid = || self.error(&format!("invalid value '{}'", word));
+        let value = match word {
+            "" => return Err(self.error("expected a value")),
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            "inf" | "+inf" | "-inf" | "nan" | "+nan" | "-nan" => {
+                return Err(self.error("infinity and NaN are not supported"))
+            }
+            word if is_date(word) || word.contains(':') => Value::String(word.to_string()),
+            word => {
+                let digits = word.replace('_', "");
+                let radix = [("0x", 16), ("0o", 8), ("0b", 2)]
+                    .into_iter()
+                    .find_map(|(prefix, radix)| Some((digits.strip_prefix(prefix)?, radix)));
+                if let Some((digits, radix)) = radix {
+                    Value::from(i64::from_str_radix(digits, radix).map_err(|_| invalid())?)
+                } else if let Ok(integer) = digits.parse::<i64>() {
+                    Value::from(integer)
+                } else if word.contains(['.', 'e', 'E']) && !word.contains("__") {
+                    digits
+                        .parse()
+                        .ok()
+                        .and_then(Number::from_f64)
+                        .map(Value::Number)
+                        .ok_or_else(invalid)?
+                } else {
+                    return Err(invalid());
+                }
+            }
+        };
+        self.pos += end;
+        Ok(value)
+    }
+
+    /// Reads the rest of a basic string, whose opening quote has been read.
+    fn basic_string(&mut self) -> Result<String, String> {
+        let mut string = String::new();
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(string);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    string.push(self.escape()?);
+                }
+                Some('\n') | None => return Err(self.error("the string is not closed")),
+                Some(c) => {
+                    self.pos += c.len_utf8();
+                    string.push(c);
+                }
+            }
+        }
+    }
+
+    /// Reads the rest of a literal string, whose opening quote has been read.
+    fn literal_string(&mut self) -> Result<String, String> {
+        let rest = &self.text[self.pos..];
+        match rest.find(['\'', '\n']) {
+            Some(end) if rest[end..].starts_with('\'') => {
+                self.pos += end + 1;
+                Ok(rest[..end].to_string())
+            }
+            _ => Err(self.error("the string is not closed")),
+        }
+    }
+
+    /// Reads the rest of a multi-line basic or literal string, whose opening quotes have been
+    /// read.
+    fn multiline_string(&mut self, basic: bool) -> Result<String, String> {
+        let quote = if basic { '"' } else { '\'' };
+        let closing = if basic { "\"\"\"" } else { "'''" };
+        // A line break right after the opening quotes is left out
+        if self.text[self.pos..].starts_with("\r\n") {
+            self.pos += 2;
+        } else if self.text[self.pos..].starts_with('\n') {
+            self.pos += 1;
+        }
+        let mut string = String::new();
+        loop {
+            let rest = &self.text[self.pos..];
+            if rest.starts_with(closing) {
+                // Up to two more quotes before the closing ones are part of the string
+                let quotes = rest[3..].chars().take_while(|&c| c == quote).count().min(2);
+                string.push_str(&quote.to_string().repeat(quotes));
+                self.pos += 3 + quotes;
+                return Ok(string);
+            }
+            match self.peek() {
+                Some('\\') if basic => {
+                    self.pos += 1;
+                    let after = &self.text[self.pos..];
+                    let spaces = after.len() - after.trim_start_matches([' ', '\t']).len();
+                    if after[spaces..].starts_with(['\n', '\r']) {
+                        // A backslash at the end of a line joins it to the next one
+                        let blank =
+                            after.len() - after.trim_start_matches([' ', '\t', '\n', '\r']).len();
+                        self.pos += blank;
+                    } else {
+                        string.push(self.escape()?);
+                    }
+                }
+                Some(c) => {
+                    self.pos += c.len_utf8();
+                    string.push(c);
+                }
+                None => return Err(self.error("the string is not closed")),
+            }
+        }
+    }
+
+    /// Reads an escape in a basic string after its backslash.
+    fn escape(&mut self) -> Result<char, String> {
+        let c = self
+            .peek()
+            .ok_or_else(|| self.error("the string is not closed"))?;
+        self.pos += c.len_utf8();
+        let digits = match c {
+            'b' => return Ok('\u{8}'),
+            't' => return Ok('\t'),
+            'n' => return Ok('\n'),
+            'f' => return Ok('\u{c}'),
+            'r' => return Ok('\r'),
+            'e' => return Ok('\u{1b}'),
+            '"' => return Ok('"'),
+            '\\' => return Ok('\\'),
+            'x' => 2,
+            'u' => 4,
+            'U' => 8,
+            c => return Err(self.error(&format!("invalid escape '\\{}'", c))),
+        };
+        let hex = self.text[self.pos..].get(..digits).unwrap_or_default();
+        let c = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == digits)
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("invalid escape"))?;
+        self.pos += digits;
+        Ok(c)
+    }
+}
+
+/// Returns whether `word` starts with a date, e.g. `2024-06-01`.
+fn is_date(word: &str) -> bool {
+    let bytes = word.as_bytes();
+    bytes.len() >= 10
+        && bytes[..4].iter().all(u8::is_ascii_digit)
+        && bytes[4] == b'-'
+        && bytes[5..7].iter().all(u8::is_ascii_digit)
+        && bytes[7] == b'-'
+        && bytes[8..10].iter().all(u8::is_ascii_digit)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{parse, write};
+
+    #[test]
+    fn test_toml() {
+        let contents = r#"
+# Written by hand
+checksum = 'C:\list'
+
+[[todos]]
+id = 1
+task = "Say \"hi\"\u00E9"
+"created" = 1_717_243_200
+tags = [
+  "home", # where
+  'errand',
+]
+reminders = [{ at = 0x10, fired = false }]
+notes.first = """
+Line one \
+  continued
+Line two"""
+due = 2024-06-01 09:00:00Z
+
+[[todos]]
+id = 2
+task = '''It's "quoted"'''
+estimate = 1.5e3
+
+[[todos.reminders]]
+at = -7
+"#;
+        assert_eq!(
+            parse(contents),
+            Ok(json!({
+                "checksum": "C:\\list",
+                "todos": [
+                    {
+                        "id": 1,
+                        "task": "Say \"hi\"é",
+                        "created": 1717243200,
+                        "tags": ["home", "errand"],
+                        "reminders": [{ "at": 16, "fired": false }],
+                        "notes": { "first": "Line one continued\nLine two" },
+                        "due": "2024-06-01 09:00:00Z",
+                    },
+                    {
+                        "id": 2,
+                        "task": "It's \"quoted\"",
+                        "estimate": 1500.0,
+                        "reminders": [{ "at": -7 }],
+                    },
+                ],
+            }))
+        );
+
+        let value = json!({
+            "todos": [
+                { "id": 1, "task": "Buy milk", "due": null, "reminders": [{ "at": 5 }] },
+                { "id": 2, "task": "Line one\nLine \"two\"", "tags": [] },
+            ],
+            "checksum": "abc",
+        });
+        let written = write(&value);
+        assert_eq!(
+            written,
+            r#"checksum = "abc"
+
+[[todos]]
+id = 1
+task = "Buy milk"
+
+[[todos.reminders]]
+at = 5
+
+[[todos]]
+id = 2
+tags = []
+task = """
+Line one
+Line \"two\""""
+"#
+        );
+
+        assert!(parse("a = 1\na = 2").unwrap_err().contains("duplicate key"));
+        assert!(parse("[a]\n[a]").unwrap_err().starts_with("Line 2"));
+        assert!(parse("a = 1\n[a.b]").unwrap_err().contains("not a table"));
+        assert!(parse("a = \"open").is_err());
+        assert!(parse("a = 1 2").is_err());
+        assert!(parse("a = nan").is_err());
+    }
+}
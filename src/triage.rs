@@ -0,0 +1,234 @@
+//! Suggesting priorities from rules, for `add --suggest-priority` and `triage`, so a shared
+//! list ranks alike tasks alike.
+//!
+//! A todo item starts at the default priority. The highest priority of the rules it matches
+//! replaces it, then their adjustments are added, keeping it on the priority scale. The
+//! rules come from `priority_rules` in the config, or are the built-in ones when none are
+//! configured.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{dates, priorities, Todo};
+
+/// A rule suggesting a priority for the todo items meeting all of its conditions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rule {
+    /// A word or words the task contains, ignoring case and punctuation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword: Option<String>,
+    /// A tag the todo item has.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// How soon the todo item is due, e.g. "2d". Overdue items are due within any time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_within: Option<String>,
+    /// The priority the todo item gets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u64>,
+    /// How much the priority is raised, or lowered when negative.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adjust: Option<i64>,
+}
+
+/// The rules used when none are configured, with their priorities on the configured scale.
+pub fn default_rules() -> Vec<Rule> {
+    let scale = priorities::scale();
+    let keyword = |keyword: &str, priority| Rule {
+        keyword: Some(keyword.to_string()),
+        priority: Some(priority),
+        ..Rule::default()
+    };
+    let due = |within: &str, priority| Rule {
+        due_within: Some(within.to_string()),
+        priority: Some(priority),
+        ..Rule::default()
+    };
+    vec![
+        keyword("urgent", scale.rescale(5)),
+        keyword("asap", scale.rescale(5)),
+        keyword("blocker", scale.rescale(5)),
+        due("1d", scale.rescale(5)),
+        due("3d", scale.rescale(4)),
+        Rule {
+            keyword: Some("someday".to_string()),
+            adjust: Some(-2),
+            ..Rule::default()
+        },
+    ]
+}
+
+/// Returns the lowercase words of a text, separated and surrounded by single spaces.
+fn words(text: &str) -> String {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    format!(" {} ", words.join(" "))
+}
+
+impl Rule {
+    /// Checks that the rule has a condition and a valid effect.
+    pub fn check(&self) -> Result<(), String> {
+        if self.keyword.is_none() && self.tag.is_none() && self.due_within.is_none() {
+            return Err("A priority rule needs a keyword, tag or due_within".to_string());
+        }
+        if let Some(within) = &self.due_within {
+            dates::parse_duration(within)?;
+        }
+        match (self.priority, self.adjust) {
+            (None, None) => Err("A priority rule needs a priority or adjust".to_string()),
+            (Some(priority), _) if !priorities::scale().contains(priority) => {
+                Err(format!("Invalid priority in a priority rule: {}", priority))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns why the rule applies to `todo` at the timestamp `now`, or `None` if it
+    /// does not.
+    fn reason(&self, todo: &Todo, now: i64) -> Option<String> {
+        let mut reasons = Vec::new();
+        if let Some(keyword) = &self.keyword {
+            let keyword = words(keyword);
+            if keyword.trim().is_empty() || !words(&todo.task).contains(&keyword) {
+                return None;
+            }
+            reasons.push(format!("\"{}\"", keyword.trim()));
+        }
+        if let Some(tag) = &self.tag {
+            if !todo.has_tag(tag, now) {
+                return None;
+            }
+            reasons.push(format!("+{}", tag));
+        }
+        if let Some(within) = &self.due_within {
+            let within = dates::parse_duration(within).ok()?;
+            todo.due.filter(|due| *due - now <= within)?;
+            reasons.push(format!("due within {}", self.due_within.as_deref()?));
+        }
+        Some(reasons.join(" "))
+    }
+}
+
+/// A suggested priority and the reasons for it.
+#[derive(Debug, PartialEq)]
+pub struct Suggestion {
+    pub priority: u64,
+    /// Why each matching rule applies, in the order of the rules.
+    pub reasons: Vec<String>,
+}
+
+impl fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reasons.is_empty() {
+            true => write!(f, "{} (no rule applies)", self.priority),
+            false => write!(f, "{} ({})", self.priority, self.reasons.join(", ")),
+        }
+    }
+}
+
+/// Suggests a priority for `todo` at the timestamp `now` by `rules`, starting from
+/// `default`.
+///
+/// # Example
+///
+/// ```
+/// let suggestion = triage::suggest(&todo, &triage::default_rules(), 3, now);
+/// ```
+pub fn suggest(todo: &Todo, rules: &[Rule], default: u64, now: i64) -> Suggestion {
+    let mut priority = None;
+    let mut adjust = 0;
+    let mut reasons = Vec::new();
+    for rule in rules {
+        if let Some(reason) = rule.reason(todo, now) {
+            priority = priority.max(rule.priority);
+            adjust += rule.adjust.unwrap_or_default();
+            reasons.push(reason);
+        }
+    }
+    let priority = priority.unwrap_or(default) as i64 + adjust;
+    Suggestion {
+        priority: priorities::scale().clamp(priority),
+        reasons,
+    }
+}
+
+/// What the user answers when asked to confirm a suggested priority.
+#[derive(Debug, PartialEq)]
+pub enum Answer {
+    /// Set this priority: the suggested one, or the one typed instead.
+    Priority(u64),
+    /// Keep the current priority.
+    Skip,
+    /// Keep it and stop asking.
+    Quit,
+}
+
+/// Reads an answer to the suggestion `suggested`: nothing accepts it, a priority overrides it,
+/// and `s` or `q` skip or quit if `skippable`. Returns `None` for anything else.
+pub fn parse_answer(answer: &str, suggested: u64, skippable: bool) -> Option<Answer> {
+    match answer.trim() {
+        "" => Some(Answer::Priority(suggested)),
+        "s" | "skip" if skippable => Some(Answer::Skip),
+        "q" | "quit" if skippable => Some(Answer::Quit),
+        answer => priorities::scale().parse(answer).ok().map(Answer::Priority),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_rules, parse_answer, suggest, Answer, Rule};
+    use crate::TodoList;
+
+    #[test]
+    fn test_suggest() {
+        const DAY: i64 = 24 * 60 * 60;
+        let mut todo_list = TodoList::new();
+        todo_list.add_todo("Fix the login BLOCKER!", 2);
+        todo_list.add_todo("Send invoice", 2);
+        todo_list.add_todo("Learn the banjo someday", 2);
+        todo_list.add_todo("Water plants", 2);
+        let now = todo_list.todos[0].created;
+        todo_list.todos[1].due = Some(now + 2 * DAY);
+        todo_list.todos[1].tags = vec!["client".to_string()];
+
+        let rules = default_rules();
+        let suggestion = |index: usize, rules: &[Rule]| {
+            let suggestion = suggest(&todo_list.todos[index], rules, 3, now);
+            (suggestion.priority, suggestion.to_string())
+        };
+        assert_eq!(suggestion(0, &rules), (5, "5 (\"blocker\")".to_string()));
+        assert_eq!(suggestion(1, &rules), (4, "4 (due within 3d)".to_string()));
+        assert_eq!(suggestion(2, &rules).0, 1);
+        assert_eq!(
+            suggestion(3, &rules),
+            (3, "3 (no rule applies)".to_string())
+        );
+
+        let client: Rule = serde_json::from_str(r#"{ "tag": "client", "adjust": 1 }"#).unwrap();
+        let rules = [rules, vec![client]].concat();
+        assert_eq!(
+            suggestion(1, &rules),
+            (5, "5 (due within 3d, +client)".to_string())
+        );
+
+        assert!(rules.iter().all(|rule| rule.check().is_ok()));
+        assert!(Rule::default().check().is_err());
+        let rule: Rule = serde_json::from_str(r#"{ "tag": "x", "priority": 7 }"#).unwrap();
+        assert!(rule.check().is_err());
+        let rule: Rule = serde_json::from_str(r#"{ "due_within": "soon", "adjust": 1 }"#).unwrap();
+        assert!(rule.check().is_err());
+        assert!(serde_json::from_str::<Rule>(r#"{ "keywrd": "x" }"#).is_err());
+
+        assert_eq!(parse_answer("\n", 4, false), Some(Answer::Priority(4)));
+        assert_eq!(parse_answer(" 2 ", 4, false), Some(Answer::Priority(2)));
+        assert_eq!(parse_answer("s", 4, true), Some(Answer::Skip));
+        assert_eq!(parse_answer("s", 4, false), None);
+        assert_eq!(parse_answer("q", 4, true), Some(Answer::Quit));
+        assert_eq!(parse_answer("6", 4, true), None);
+    }
+}
@@ -0,0 +1,119 @@
+//! Working days: Monday to Friday, except the holidays configured with `holidays`, for due
+//! dates given in business days, e.g. `--due +3bd`, and for recurring tasks added with
+//! `--workdays`, whose occurrences move off weekends and holidays.
+//!
+//! A holiday is a date, `2026-04-03`, or a month and day kept every year, `12-25`.
+
+use std::collections::BTreeSet;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Weekday};
+
+/// How many days in a row may be days off before a date is taken as it is, so a calendar
+/// listing a whole year of holidays cannot loop forever.
+const MAX_DAYS_OFF: usize = 366;
+
+/// The days off besides weekends.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Workdays {
+    /// Holidays on one date.
+    dates: BTreeSet<NaiveDate>,
+    /// Holidays on the same month and day every year.
+    yearly: BTreeSet<(u32, u32)>,
+}
+
+impl Workdays {
+    /// Parses the configured holidays, each `YYYY-MM-DD` or `MM-DD`.
+    pub fn parse(holidays: &[String]) -> Result<Workdays, String> {
+        let mut workdays = Workdays::default();
+        for holiday in holidays {
+            let invalid = || {
+                format!(
+                    "Invalid holiday: '{}'. Expected YYYY-MM-DD, or MM-DD for every year",
+                    holiday
+                )
+            };
+            match NaiveDate::parse_from_str(holiday, "%Y-%m-%d") {
+                Ok(date) => {
+                    workdays.dates.insert(date);
+                }
+                Err(_) => {
+                    // Checked against a leap year, so that 02-29 is a day too
+                    let date = NaiveDate::parse_from_str(&format!("2000-{}", holiday), "%Y-%m-%d")
+                        .map_err(|_| invalid())?;
+                    workdays.yearly.insert((date.month(), date.day()));
+                }
+            }
+        }
+        Ok(workdays)
+    }
+
+    /// Returns whether `date` is a working day.
+    pub fn is_workday(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+            && !self.dates.contains(&date)
+            && !self.yearly.contains(&(date.month(), date.day()))
+    }
+
+    /// Returns `date` if it is a working day, and otherwise the next one.
+    pub fn next_workday(&self, date: NaiveDate) -> NaiveDate {
+        date.iter_days()
+            .take(MAX_DAYS_OFF)
+            .find(|day| self.is_workday(*day))
+            .unwrap_or(date)
+    }
+
+    /// Returns the working day `days` working days after `date`.
+    pub fn add(&self, date: NaiveDate, days: u32) -> NaiveDate {
+        (0..days).fold(date, |date, _| match date.succ_opt() {
+            Some(next) => self.next_workday(next),
+            None => date,
+        })
+    }
+
+    /// Moves the occurrence `at` of a recurring task to the same time on the next working day
+    /// if it falls on a day off.
+    pub fn shift(&self, at: NaiveDateTime) -> NaiveDateTime {
+        self.next_workday(at.date()).and_time(at.time())
+    }
+}
+
+/// Parses a number of business days from today, e.g. `+3bd`.
+pub fn parse_offset(word: &str) -> Option<u32> {
+    word.strip_prefix('+')?
+        .to_lowercase()
+        .strip_suffix("bd")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_offset, Workdays};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_workdays() {
+        let date = |month, day| NaiveDate::from_ymd_opt(2026, month, day).unwrap();
+        let workdays = Workdays::parse(&["12-25".to_string(), "2026-12-28".to_string()]).unwrap();
+        assert!(workdays.is_workday(date(12, 24)));
+        assert!(!workdays.is_workday(date(12, 25)));
+        assert!(!workdays.is_workday(date(12, 26)));
+        // Friday the 25th, the weekend and Monday the 28th are all off
+        assert_eq!(workdays.next_workday(date(12, 25)), date(12, 29));
+        assert_eq!(workdays.next_workday(date(12, 24)), date(12, 24));
+        // Three business days after Wednesday the 23rd
+        assert_eq!(workdays.add(date(12, 23), 3), date(12, 30));
+        assert_eq!(workdays.add(date(12, 23), 0), date(12, 23));
+        assert_eq!(
+            workdays.shift(date(12, 25).and_hms_opt(9, 0, 0).unwrap()),
+            date(12, 29).and_hms_opt(9, 0, 0).unwrap()
+        );
+        assert!(Workdays::parse(&["02-29".to_string()]).is_ok());
+        assert!(Workdays::parse(&["christmas".to_string()]).is_err());
+
+        assert_eq!(parse_offset("+3bd"), Some(3));
+        assert_eq!(parse_offset("+10BD"), Some(10));
+        assert_eq!(parse_offset("3bd"), None);
+        assert_eq!(parse_offset("+3d"), None);
+    }
+}
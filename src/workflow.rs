@@ -0,0 +1,139 @@
+//! Named sequences of todo commands defined in the config, run with `run <name>`.
+
+/// Expands the steps of a workflow into the arguments of each command to run.
+///
+/// Each step is a command line such as `"list --tag {1}"`, split into words like a shell
+/// would (with `'` and `"` quoting and `\` escapes). `{1}`, `{2}`, ... are replaced by the
+/// arguments given to `run`, and `{args}` by all of them. Substitution happens after
+/// splitting, so an argument containing spaces stays one word.
+///
+/// # Example
+///
+/// ```
+/// let commands = expand(&["search \"tag:{1}\"".to_string()], &["work".to_string()])?;
+/// assert_eq!(commands, vec![vec!["search", "tag:work"]]);
+/// ```
+pub fn expand(steps: &[String], args: &[String]) -> Result<Vec<Vec<String>>, String> {
+    let mut commands = Vec::new();
+    for step in steps {
+        let mut command = Vec::new();
+        for word in split_words(step)? {
+            if word == "{args}" {
+                command.extend(args.iter().cloned());
+            } else {
+                command.push(substitute(&word, args)?);
+            }
+        }
+        if command.is_empty() {
+            return Err("A workflow step is empty".to_string());
+        }
+        commands.push(command);
+    }
+    Ok(commands)
+}
+
+/// Replaces `{n}` placeholders in `word` with the n-th argument, and `{args}` with all of
+/// them separated by spaces.
+fn substitute(word: &str, args: &[String]) -> Result<String, String> {
+    let mut output = String::new();
+    let mut rest = word;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let placeholder = rest[start + 1..]
+            .find('}')
+            .map(|end| &rest[start + 1..start + 1 + end]);
+        match placeholder {
+            Some("args") => output.push_str(&args.join(" ")),
+            Some(name) if !name.is_empty() && name.chars().all(|c| c.is_ascii_digit()) => {
+                let value = name
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| n.checked_sub(1))
+                    .and_then(|index| args.get(index))
+                    .ok_or_else(|| format!("The workflow needs an argument for {{{}}}", name))?;
+                output.push_str(value);
+            }
+            // Anything else, e.g. a JSON snippet, is kept as it is.
+            _ => {
+                output.push('{');
+                rest = &rest[start + 1..];
+                continue;
+            }
+        }
+        rest = &rest[start + placeholder.map_or(0, str::len) + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Splits a command line into words, honouring single quotes, double quotes and
+/// backslash escapes.
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                words.extend(word.take());
+            }
+            '\'' | '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some('\\') if c == '"' => word.extend(chars.next()),
+                        Some(other) => word.push(other),
+                        None => return Err(format!("Unterminated quote in '{}'", line)),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand, split_words};
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(
+            split_words(r#"search "tag:work sort:-priority" --count-only"#).unwrap(),
+            vec!["search", "tag:work sort:-priority", "--count-only"]
+        );
+        assert_eq!(
+            split_words(r#"add 'it'\''s done' 3 "" a\ b"#).unwrap(),
+            vec!["add", "it's done", "3", "", "a b"]
+        );
+        assert!(split_words("add \"oops 3").is_err());
+    }
+
+    #[test]
+    fn test_expand() {
+        let steps = vec![
+            "overdue".to_string(),
+            "search \"tag:{1} priority>={2}\"".to_string(),
+            "add {args} 3".to_string(),
+        ];
+        let args = vec!["work".to_string(), "4".to_string()];
+        assert_eq!(
+            expand(&steps, &args).unwrap(),
+            vec![
+                vec!["overdue"],
+                vec!["search", "tag:work priority>=4"],
+                vec!["add", "work", "4", "3"],
+            ]
+        );
+        assert!(expand(&steps, &args[..1]).is_err());
+        assert_eq!(
+            expand(&["filter {x}".to_string()], &[]).unwrap(),
+            vec![vec!["filter", "{x}"]]
+        );
+        assert!(expand(&["".to_string()], &[]).is_err());
+    }
+}
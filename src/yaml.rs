@@ -0,0 +1,1010 @@
+//! Reading and writing YAML, for todo list files in that format.
+//!
+//! Lists are written in block style, one field per line and multi-line text as literal
+//! blocks, so a change to an item shows as a change to its own lines. What is read is the
+//! part of YAML 1.2 that such files and hand edits of them use: block and flow mappings and
+//! sequences, plain, quoted and block scalars, and comments. Anchors, aliases, tags, complex
+//! keys and more than one document in a file are not supported.
+
+use serde_json::{Map, Number, Value};
+
+use crate::formats::signed;
+
+/// The number of spaces each level of a written file is indented by.
+const INDENT: usize = 2;
+
+/// Writes out `value` as a YAML document.
+pub fn write(value: &Value) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Object(map) if !map.is_empty() => write_mapping(map, 0, &mut out),
+        Value::Array(items) if !items.is_empty() => write_sequence(items, 0, &mut out),
+        _ => {
+            out.push_str(&scalar(value));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn write_mapping(map: &Map<String, Value>, indent: usize, out: &mut String) {
+    for (key, value) in map {
+        out.push_str(&" ".repeat(indent));
+        out.push_str(&string(key));
+        out.push(':');
+        write_nested(value, indent + INDENT, out);
+    }
+}
+
+fn write_sequence(items: &[Value], indent: usize, out: &mut String) {
+    for item in items {
+        out.push_str(&" ".repeat(indent));
+        out.push('-');
+        // A collection starts on the dash's line, in place of its own indentation
+        let start = out.len() + 1;
+        match item {
+            Value::Object(map) if !map.is_empty() => {
+                out.push(' ');
+                write_mapping(map, indent + INDENT, out);
+                out.replace_range(start..start + indent + INDENT, "");
+            }
+            Value::Array(items) if !items.is_empty() => {
+                out.push(' ');
+                write_sequence(items, indent + INDENT, out);
+                out.replace_range(start..start + indent + INDENT, "");
+            }
+            _ => write_nested(item, indent + INDENT, out),
+        }
+    }
+}
+
+/// Writes `value` after a key's colon or an item's dash: a scalar on the same line, and a
+/// collection or block of text on the lines after, indented by `indent`.
+fn write_nested(value: &Value, indent: usize, out: &mut String) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            out.push('\n');
+            write_mapping(map, indent, out);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push('\n');
+            write_sequence(items, indent, out);
+        }
+        Value::String(text) if is_block(text) => {
+            let content = text.trim_end_matches('\n');
+            let newlines = text.len() - content.len();
+            out.push_str(match newlines {
+                0 => " |-\n",
+                1 => " |\n",
+                _ => " |+\n",
+            });
+            for line in content.split('\n') {
+                if !line.is_empty() {
+                    out.push_str(&" ".repeat(indent));
+                    out.push_str(line);
+                }
+                out.push('\n');
+            }
+            out.push_str(&"\n".repeat(newlines.saturating_sub(1)));
+        }
+        _ => {
+            out.push(' ');
+            out.push_str(&scalar(value));
+            out.push('\n');
+        }
+    }
+}
+
+/// Returns whether `text` is written as a literal block: it has several lines, none of them
+/// blank but for spaces, and nothing a block can't hold.
+fn is_block(text: &str) -> bool {
+    let content = text.trim_end_matches('\n');
+    content.contains('\n')
+        && !content.starts_with(char::is_whitespace)
+        && content
+            .split('\n')
+            .all(|line| line.is_empty() || !line.trim().is_empty())
+        && !text.contains(|c: char| (c.is_control() && c != '\n' && c != '\t') || is_special(c))
+}
+
+/// Returns whether `c` is a byte order mark or a line break other than `\n`, which are
+/// escaped wherever they appear.
+fn is_special(c: char) -> bool {
+    matches!(c, '\u{feff}' | '\u{85}' | '\u{2028}' | '\u{2029}')
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(text) => string(text),
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// Writes `text` plain if it reads back as the same string, in this and other YAML readers,
+/// and double-quoted otherwise.
+fn string(text: &str) -> String {
+    let mut chars = text.chars();
+    let first = chars.next().unwrap_or(' ');
+    let second = chars.next();
+    let quote = first.is_whitespace()
+        || text.ends_with(char::is_whitespace)
+        || "-?:,[]{}#&*!|>'\"%@`".contains(first)
+        || first.is_ascii_digit()
+        || (matches!(first, '+' | '.') && second.is_some_and(|c| c.is_ascii_digit()))
+        || text
+            .contains(|c: char| c.is_control() || (c.is_whitespace() && c != ' ') || is_special(c))
+        || text.contains(": ")
+        || text.contains(" #")
+        || text.ends_with(':')
+        || matches!(
+            text.to_lowercase().as_str(),
+            "y" | "n" | "yes" | "no" | "on" | "off"
+        )
+        || resolve(text, 0) != Ok(Value::String(text.to_string()));
+    if !quote {
+        return text.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() || is_special(c) => {
+                quoted.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Reads a YAML document.
+pub fn parse(contents: &str) -> Result<Value, String> {
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+    let mut lines = Vec::new();
+    let mut started = false;
+    let raw_lines: Vec<&str> = contents.lines().collect();
+    for (index, raw) in raw_lines.iter().enumerate() {
+        let number = index + 1;
+        let marker = ["---", "..."].into_iter().find(|marker| {
+            raw.strip_prefix(marker)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t']))
+        });
+        if let Some(marker) = marker {
+            let rest = raw[3..].trim();
+            if !rest.is_empty() && !rest.starts_with('#') {
+                return Err(format!(
+                    "Line {}: text after {} is not supported",
+                    number, marker
+                ));
+            }
+            if started || marker == "..." {
+                let more = raw_lines[index + 1..].iter().position(|raw| {
+                    let raw = raw.trim();
+                    !raw.is_empty() && !raw.starts_with('#') && raw != "---" && raw != "..."
+                });
+                if let Some(offset) = more {
+                    return Err(format!(
+                        "Line {}: only one YAML document can be read",
+                        number + 1 + offset
+                    ));
+                }
+                break;
+            }
+            continue;
+        }
+        if !started && raw.starts_with('%') {
+            continue;
+        }
+        let indent = raw.len() - raw.trim_start_matches(' ').len();
+        let line = Line {
+            number,
+            indent,
+            text: &raw[indent..],
+        };
+        started |= !line.is_blank();
+        lines.push(line);
+    }
+    let mut parser = Parser { lines, pos: 0 };
+    let value = parser.node(0)?;
+    parser.skip_blank();
+    match parser.lines.get(parser.pos) {
+        None => Ok(value),
+        Some(line) => Err(format!("Line {}: unexpected indentation", line.number)),
+    }
+}
+
+/// A line of a YAML document, split into its indentation and the rest.
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    text: &'a str,
+}
+
+impl Line<'_> {
+    /// Returns whether the line holds nothing but spaces or a comment.
+    fn is_blank(&self) -> bool {
+        let text = self.text.trim();
+        text.is_empty() || text.starts_with('#')
+    }
+
+    /// Returns whether the line starts an item of a block sequence.
+    fn is_item(&self) -> bool {
+        self.text == "-" || self.text.starts_with("- ") || self.text.starts_with("-\t")
+    }
+}
+
+struct Parser<'a> {
+    lines: Vec<Line<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_blank(&mut self) {
+        while self.lines.get(self.pos).is_some_and(Line::is_blank) {
+            self.pos += 1;
+        }
+    }
+
+    /// Reads the node on the next lines indented by at least `min_indent`, which is null if
+    /// there is none.
+    fn node(&mut self, min_indent: usize) -> Result<Value, String> {
+        self.skip_blank();
+        let Some(line) = self.lines.get(self.pos) else {
+            return Ok(Value::Null);
+        };
+        if line.indent < min_indent {
+            return Ok(Value::Null);
+        }
+        let (number, indent, text) = (line.number, line.indent, line.text);
+        if line.is_item() {
+            return self.sequence(indent);
+        }
+        if split_key(text, number)?.is_some() {
+            return self.mapping(indent);
+        }
+        self.pos += 1;
+        self.inline(text, min_indent, number)
+    }
+
+    /// Reads the items of a block sequence whose dashes are indented by `indent`.
+    fn sequence(&mut self, indent: usize) -> Result<Value, String> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank();
+            let Some(line) = self.lines.get(self.pos) else {
+                break;
+            };
+            if line.indent > indent {
+                return Err(format!("Line {}: unexpected indentation", line.number));
+            }
+            if line.indent < indent || !line.is_item() {
+                break;
+            }
+            let content = line.text[1..].trim_start_matches([' ', '\t']);
+            if content.is_empty() || content.starts_with('#') {
+                self.pos += 1;
+            } else {
+                // Read what follows the dash as a node starting at its column
+                let offset = line.text.len() - content.len();
+                let line = &mut self.lines[self.pos];
+                line.indent += offset;
+                line.text = content;
+            }
+            items.push(self.node(indent + 1)?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    /// Reads the entries of a block mapping whose keys are indented by `indent`.
+    fn mapping(&mut self, indent: usize) -> Result<Value, String> {
+        let mut map = Map::new();
+        loop {
+            self.skip_blank();
+            let Some(line) = self.lines.get(self.pos) else {
+                break;
+            };
+            let (number, text) = (line.number, line.text);
+            if line.indent > indent {
+                return Err(format!("Line {}: unexpected indentation", number));
+            }
+            if line.indent < indent {
+                break;
+            }
+            let key = match split_key(text, number)? {
+                Some(_) if line.is_item() => None,
+                key => key,
+            };
+            let Some((key, rest)) = key else {
+                return Err(format!("Line {}: expected a key", number));
+            };
+            self.pos += 1;
+            let rest = rest.trim_start_matches([' ', '\t']);
+            let value = if rest.is_empty() || rest.starts_with('#') {
+                self.skip_blank();
+                match self.lines.get(self.pos) {
+                    // A sequence may be indented as much as its key
+                    Some(next) if next.indent == indent && next.is_item() => {
+                        self.sequence(indent)?
+                    }
+                    _ => self.node(indent + 1)?,
+                }
+            } else {
+                self.inline(rest, indent + 1, number)?
+            };
+            if map.contains_key(&key) {
+                return Err(format!("Line {}: duplicate key '{}'", number, key));
+            }
+            map.insert(key, value);
+        }
+        Ok(Value::Object(map))
+    }
+
+    /// Reads the scalar or flow collection starting with `text` on line `number`, and any
+    /// lines after that belong to it, which are indented by at least `min_indent`.
+    fn inline(&mut self, text: &str, min_indent: usize, number: usize) -> Result<Value, String> {
+        match text.chars().next() {
+            Some('&' | '*' | '!') => Err(format!(
+                "Line {}: anchors, aliases and tags are not supported",
+                number
+            )),
+            Some('|' | '>') => self.block(text, min_indent, number),
+            Some('"' | '\'') => {
+                let mut joined = text.to_string();
+                loop {
+                    if let Some((value, end)) = quoted(&joined, number)? {
+                        expect_end(&joined[end..], number + joined.matches('\n').count())?;
+                        return Ok(Value::String(value));
+                    }
+                    let Some(line) = self.lines.get(self.pos) else {
+                        return Err(format!(
+                            "Line {}: the quote opened here is not closed",
+                            number
+                        ));
+                    };
+                    joined.push('\n');
+                    joined.push_str(line.text);
+                    self.pos += 1;
+                }
+            }
+            Some('[' | '{') => {
+                let mut joined = text.to_string();
+                loop {
+                    let mut flow = Flow {
+                        text: &joined,
+                        pos: 0,
+                        number,
+                    };
+                    match flow.value() {
+                        Ok(value) => {
+                            expect_end(&joined[flow.pos..], flow.line())?;
+                            return Ok(value);
+                        }
+                        Err(FlowError::Invalid(error)) => return Err(error),
+                        Err(FlowError::Incomplete) => {}
+                    }
+                    let Some(line) = self.lines.get(self.pos) else {
+                        return Err(format!(
+                            "Line {}: the bracket opened here is not closed",
+                            number
+                        ));
+                    };
+                    joined.push('\n');
+                    joined.push_str(line.text);
+                    self.pos += 1;
+                }
+            }
+            _ => {
+                let (first, mut ended) = strip_comment(text);
+                let mut value = first.trim_end().to_string();
+                // Later lines indented enough continue the scalar, a blank one standing
+                // for a line break and otherwise a line break for a space
+                while !ended {
+                    let empty = self.lines[self.pos..]
+                        .iter()
+                        .take_while(|line| line.text.trim().is_empty())
+                        .count();
+                    match self.lines.get(self.pos + empty) {
+                        Some(line)
+                            if line.indent >= min_indent
+                                && !line.is_blank()
+                                && split_key(line.text, line.number)?.is_none() =>
+                        {
+                            let (text, comment) = strip_comment(line.text);
+                            value.push_str(&if empty == 0 {
+                                " ".to_string()
+                            } else {
+                                "\n".repeat(empty)
+                            });
+                            value.push_str(text.trim());
+                            ended = comment;
+                            self.pos += empty + 1;
+                        }
+                        _ => break,
+                    }
+                }
+                resolve(&value, number)
+            }
+        }
+    }
+
+    /// Reads a literal (`|`) or folded (`>`) block scalar whose header is `header`.
+    fn block(&mut self, header: &str, min_indent: usize, number: usize) -> Result<Value, String> {
+        let folded = header.starts_with('>');
+        let mut chomp = '\0';
+        let mut explicit = None;
+        for c in strip_comment(&header[1..]).0.trim_end().chars() {
+            match c {
+                '-' | '+' if chomp == '\0' => chomp = c,
+                '1'..='9' if explicit.is_none() => explicit = c.to_digit(10),
+                _ => {
+                    return Err(format!(
+                        "Line {}: invalid block scalar header '{}'",
+                        number,
+                        header.trim_end()
+                    ))
+                }
+            }
+        }
+        // The content is indented as much as its first line, unless the header says
+        let indent = match explicit {
+            Some(digits) => min_indent - 1 + digits as usize,
+            None => self.lines[self.pos..]
+                .iter()
+                .find(|line| !line.text.trim().is_empty())
+                .map_or(min_indent, |line| line.indent.max(min_indent)),
+        };
+        let mut lines = Vec::new();
+        while let Some(line) = self.lines.get(self.pos) {
+            if line.text.trim().is_empty() && line.indent <= indent {
+                lines.push(String::new());
+            } else if line.indent >= indent {
+                lines.push(format!("{}{}", " ".repeat(line.indent - indent), line.text));
+            } else {
+                break;
+            }
+            self.pos += 1;
+        }
+        let trailing = lines
+            .iter()
+            .rev()
+            .take_while(|line| line.is_empty())
+            .count();
+        let content = &lines[..lines.len() - trailing];
+        let mut text = if folded {
+            fold(content)
+        } else {
+            content.join("\n")
+        };
+        match chomp {
+            '-' => {}
+            '+' => {
+                if !content.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(&"\n".repeat(trailing));
+            }
+            _ if !content.is_empty() => text.push('\n'),
+            _ => {}
+        }
+        Ok(Value::String(text))
+    }
+}
+
+/// Joins the lines of a folded block scalar: a line break between two lines of text becomes
+/// a space, and is kept around blank lines and lines indented more than the rest.
+fn fold(lines: &[String]) -> String {
+    let mut text = String::new();
+    let mut previous: Option<bool> = None;
+    let mut breaks = 0;
+    for line in lines {
+        if line.is_empty() {
+            breaks += 1;
+            continue;
+        }
+        let indented = line.starts_with([' ', '\t']);
+        match previous {
+            None => text.push_str(&"\n".repeat(breaks)),
+            Some(false) if !indented && breaks == 0 => text.push(' '),
+            Some(false) if !indented => text.push_str(&"\n".repeat(breaks)),
+            Some(_) => text.push_str(&"\n".repeat(breaks + 1)),
+        }
+        text.push_str(line);
+        previous = Some(indented);
+        breaks = 0;
+    }
+    text
+}
+
+/// Splits a key off `text` if it is a mapping entry, returning the key and what follows its
+/// colon.
+fn split_key(text: &str, number: usize) -> Result<Option<(String, &str)>, String> {
+    match text.chars().next() {
+        Some('"' | '\'') => {
+            let Some((key, end)) = quoted(text, number)? else {
+                return Ok(None);
+            };
+            let rest = text[end..].trim_start_matches([' ', '\t']);
+            match rest.strip_prefix(':') {
+                Some(after) if after.is_empty() || after.starts_with([' ', '\t']) => {
+                    Ok(Some((key, after)))
+                }
+                _ => Ok(None),
+            }
+        }
+        Some('[' | '{') | None => Ok(None),
+        Some('?') if text == "?" || text.starts_with("? ") => {
+            Err(format!("Line {}: complex keys are not supported", number))
+        }
+        _ => {
+            let bytes = text.as_bytes();
+            for (index, &byte) in bytes.iter().enumerate() {
+                let before = index.checked_sub(1).map(|before| bytes[before]);
+                let after = bytes.get(index + 1);
+                match byte {
+                    b'#' if matches!(before, Some(b' ' | b'\t')) => return Ok(None),
+                    b':' if matches!(after, None | Some(b' ' | b'\t')) => {
+                        let key = text[..index].trim_end();
+                        if key.is_empty() {
+                            return Ok(None);
+                        }
+                        return Ok(Some((key.to_string(), &text[index + 1..])));
+                    }
+                    _ => {}
+                }
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// Splits a plain scalar from a comment after it, returning whether there was one.
+fn strip_comment(text: &str) -> (&str, bool) {
+    let bytes = text.as_bytes();
+    let comment = (0..bytes.len()).find(|&index| {
+        bytes[index] == b'#' && (index == 0 || matches!(bytes[index - 1], b' ' | b'\t'))
+    });
+    match comment {
+        Some(index) => (&text[..index], true),
+        None => (text, false),
+    }
+}
+
+/// Checks that only spaces or a comment follow a value on line `number`.
+fn expect_end(rest: &str, number: usize) -> Result<(), String> {
+    let rest = rest.trim();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err(format!(
+            "Line {}: unexpected '{}' after the value",
+            number, rest
+        ))
+    }
+}
+
+/// Reads the quoted scalar at the start of `text`, which begins on line `number`, returning
+/// its value and the index after its closing quote, or `None` if `text` ends before it.
+/// Line breaks in it become spaces, or line breaks if blank lines follow them.
+fn quoted(text: &str, number: usize) -> Result<Option<(String, usize)>, String> {
+    let mut chars = text.char_indices().peekable();
+    let quote = match chars.next() {
+        Some((_, quote @ ('"' | '\''))) => quote,
+        _ => return Ok(None),
+    };
+    let mut value = String::new();
+    let line = |index: usize| number + text[..index].matches('\n').count();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\'' if quote == '\'' => {
+                if chars.peek().is_some_and(|&(_, next)| next == '\'') {
+                    chars.next();
+                    value.push('\'');
+                } else {
+                    return Ok(Some((value, index + 1)));
+                }
+            }
+            '"' if quote == '"' => return Ok(Some((value, index + 1))),
+            '\n' => {
+                value.truncate(value.trim_end_matches([' ', '\t']).len());
+                let mut breaks = 0;
+                while let Some(&(_, next)) = chars.peek() {
+                    match next {
+                        ' ' | '\t' => {}
+                        '\n' => breaks += 1,
+                        _ => break,
+                    }
+                    chars.next();
+                }
+                if breaks == 0 {
+                    value.push(' ');
+                } else {
+                    value.push_str(&"\n".repeat(breaks));
+                }
+            }
+            '\\' if quote == '"' => {
+                let Some((_, escape)) = chars.next() else {
+                    return Ok(None);
+                };
+                let mut hex = |digits: usize| -> Result<char, String> {
+                    let mut code = 0;
+                    for _ in 0..digits {
+                        let digit = chars
+                            .next()
+                            .and_then(|(_, c)| c.to_digit(16))
+                            .ok_or_else(|| format!("Line {}: invalid escape", line(index)))?;
+                        code = code * 16 + digit;
+                    }
+                    char::from_u32(code)
+                        .ok_or_else(|| format!("Line {}: invalid escape", line(index)))
+                };
+                match escape {
+                    '0' => value.push('\0'),
+                    'a' => value.push('\u{7}'),
+                    'b' => value.push('\u{8}'),
+                    't' | '\t' => value.push('\t'),
+                    'n' => value.push('\n'),
+                    'v' => value.push('\u{b}'),
+                    'f' => value.push('\u{c}'),
+                    'r' => value.push('\r'),
+                    'e' => value.push('\u{1b}'),
+                    'N' => value.push('\u{85}'),
+                    '_' => value.push('\u{a0}'),
+                    'L' => value.push('\u{2028}'),
+                    'P' => value.push('\u{2029}'),
+                    'x' => value.push(hex(2)?),
+                    'u' => value.push(hex(4)?),
+                    'U' => value.push(hex(8)?),
+                    ' ' | '"' | '/' | '\\' => value.push(escape),
+                    '\n' => {
+                        // An escaped line break joins the lines without a space
+                        while chars
+                            .peek()
+                            .is_some_and(|&(_, next)| next == ' ' || next == '\t')
+                        {
+                            chars.next();
+                        }
+                    }
+                    c => return Err(format!("Line {}: invalid escape '\\{}'", line(index), c)),
+                }
+            }
+            c => value.push(c),
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the value of a plain scalar on line `number`: null, a boolean, a number or a
+/// string, as in the YAML 1.2 core schema.
+fn resolve(text: &str, number: usize) -> Result<Value, String> {
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => return Ok(Value::Null),
+        "true" | "True" | "TRUE" => return Ok(Value::Bool(true)),
+        "false" | "False" | "FALSE" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    if matches!(
+        unsigned,
+        ".inf" | ".Inf" | ".INF" | ".nan" | ".NaN" | ".NAN"
+    ) {
+        return Err(format!(
+            "Line {}: infinity and NaN are not supported",
+            number
+        ));
+    }
+    let radix = [("0x", 16), ("0o", 8)]
+        .into_iter()
+        .find_map(|(prefix, radix)| Some((text.strip_prefix(prefix)?, radix)));
+    if let Some((digits, radix)) = radix {
+        if let Ok(magnitude) = u64::from_str_radix(digits, radix) {
+            return Ok(Value::from(magnitude));
+        }
+    }
+    if !unsigned.is_empty() && unsigned.bytes().all(|byte| byte.is_ascii_digit()) {
+        if let Some(value) = unsigned
+            .parse()
+            .ok()
+            .and_then(|magnitude| signed(magnitude, negative))
+        {
+            return Ok(value);
+        }
+    }
+    if is_float(unsigned) {
+        if let Some(number) = text.parse().ok().and_then(Number::from_f64) {
+            return Ok(Value::Number(number));
+        }
+    }
+    Ok(Value::String(text.to_string()))
+}
+
+/// Returns whether `text` is a float without its sign: digits with a decimal point, an
+/// exponent or both.
+fn is_float(text: &str) -> bool {
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(index) => (&text[..index], Some(&text[index + 1..])),
+        None => (text, None),
+    };
+    let digits = |text: &str| !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_digit());
+    let mantissa = match mantissa.split_once('.') {
+        Some((whole, fraction)) => {
+            (whole.is_empty() || digits(whole))
+                && (fraction.is_empty() || digits(fraction))
+                && !(whole.is_empty() && fraction.is_empty())
+        }
+        None => digits(mantissa),
+    };
+    let exponent = exponent
+        .is_none_or(|exponent| digits(exponent.strip_prefix(['+', '-']).unwrap_or(exponent)));
+    mantissa && exponent
+}
+
+/// Why a flow collection could not be read.
+enum FlowError {
+    /// The text ended before it did, so it continues on the next line.
+    Incomplete,
+    Invalid(String),
+}
+
+/// A reader of a flow collection, e.g. `[a, b]` or `{ a: 1 }`, which starts on line
+/// `number` of the document.
+struct Flow<'t> {
+    text: &'t str,
+    pos: usize,
+    number: usize,
+}
+
+impl Flow<'_> {
+    fn line(&self) -> usize {
+        self.number + self.text[..self.pos].matches('\n').count()
+    }
+
+    fn invalid(&self, message: &str) -> FlowError {
+        FlowError::Invalid(format!("Line {}: {}", self.line(), message))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    /// Skips spaces, line breaks and comments.
+    fn skip_blank(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\n' => self.pos += 1,
+                '#' => {
+                    self.pos = self.text[self.pos..]
+                        .find('\n')
+                        .map_or(self.text.len(), |end| self.pos + end)
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, FlowError> {
+        self.skip_blank();
+        match self.peek() {
+            None => Err(FlowError::Incomplete),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_blank();
+                    if self.peek() == Some(']') {
+                        self.pos += 1;
+                        return Ok(Value::Array(items));
+                    }
+                    items.push(self.value()?);
+                    if self.separator(']')? {
+                        return Ok(Value::Array(items));
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut map = Map::new();
+                loop {
+                    self.skip_blank();
+                    if self.peek() == Some('}') {
+                        self.pos += 1;
+                        return Ok(Value::Object(map));
+                    }
+                    let key = match self.scalar(true)? {
+                        Value::String(key) => key,
+                        key => key.to_string(),
+                    };
+                    self.skip_blank();
+                    let value = if self.peek() == Some(':') {
+                        self.pos += 1;
+                        match self.peek() {
+                            Some(',' | '}') => Value::Null,
+                            _ => self.value()?,
+                        }
+                    } else {
+                        Value::Null
+                    };
+                    if map.insert(key.clone(), value).is_some() {
+                        return Err(self.invalid(&format!("duplicate key '{}'", key)));
+                    }
+                    if self.separator('}')? {
+                        return Ok(Value::Object(map));
+                    }
+                }
+            }
+            Some(_) => self.scalar(false),
+        }
+    }
+
+    /// Reads the comma after an item or the `close` bracket, returning whether it was the
+    /// bracket.
+    fn separator(&mut self, close: char) -> Result<bool, FlowError> {
+        self.skip_blank();
+        match self.peek() {
+            None => Err(FlowError::Incomplete),
+            Some(',') => {
+                self.pos += 1;
+                Ok(false)
+            }
+            Some(c) if c == close => {
+                self.pos += 1;
+                Ok(true)
+            }
+            Some(c) => Err(self.invalid(&format!("expected ',' or '{}', found '{}'", close, c))),
+        }
+    }
+
+    /// Reads a quoted or plain scalar, which ends before a `:` if it is a key.
+    fn scalar(&mut self, key: bool) -> Result<Value, FlowError> {
+        let rest = &self.text[self.pos..];
+        if rest.starts_with(['"', '\'']) {
+            let number = self.line();
+            return match quoted(rest, number).map_err(FlowError::Invalid)? {
+                Some((value, end)) => {
+                    self.pos += end;
+                    Ok(Value::String(value))
+                }
+                None => Err(FlowError::Incomplete),
+            };
+        }
+        if rest.starts_with(['&', '*', '!']) {
+            return Err(self.invalid("anchors, aliases and tags are not supported"));
+        }
+        let bytes = rest.as_bytes();
+        let end = (0..bytes.len())
+            .find(|&index| match bytes[index] {
+                b',' | b'[' | b']' | b'{' | b'}' | b'\n' => true,
+                b':' => key || matches!(bytes.get(index + 1), None | Some(b' ' | b'\n')),
+                b'#' => index > 0 && matches!(bytes[index - 1], b' ' | b'\t'),
+                _ => false,
+            })
+            .unwrap_or(bytes.len());
+        let text = rest[..end].trim();
+        if text.is_empty() {
+            return match self.text[self.pos + end..].chars().next() {
+                None => Err(FlowError::Incomplete),
+                Some(c) => Err(self.invalid(&format!("unexpected '{}'", c))),
+            };
+        }
+        self.pos += end;
+        if key {
+            Ok(Value::String(text.to_string()))
+        } else {
+            resolve(text, self.line()).map_err(FlowError::Invalid)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{parse, write};
+
+    #[test]
+    fn test_yaml() {
+        let contents = "\
+# Written by hand
+---
+todos:
+- id: 1
+  task: Buy milk   # and eggs
+  tags: [home, 'errand' ]
+  reminders:
+    - { at: 1717243200, fired: no }
+- id: 2
+  task: >-
+    Write the
+    report
+
+    by Friday
+  notes: |
+    line one
+      indented
+
+  due: ~
+  estimate: 1.5e3
+  done: [
+    0x10,
+    -7,
+  ]
+- \"Say \\\"hi\\\"\\tthere\"
+- 'It''s
+  wrapped'
+- plain text
+  over two lines
+...
+";
+        assert_eq!(
+            parse(contents),
+            Ok(json!({
+                "todos": [
+                    {
+                        "id": 1,
+                        "task": "Buy milk",
+                        "tags": ["home", "errand"],
+                        "reminders": [{ "at": 1717243200, "fired": "no" }],
+                    },
+                    {
+                        "id": 2,
+                        "task": "Write the report\nby Friday",
+                        "notes": "line one\n  indented\n",
+                        "due": null,
+                        "estimate": 1500.0,
+                        "done": [16, -7],
+                    },
+                    "Say \"hi\"\tthere",
+                    "It's wrapped",
+                    "plain text over two lines",
+                ],
+            }))
+        );
+
+        let value = json!({
+            "todos": [
+                { "id": 1, "task": "yes", "tags": ["2024-06-01", "a: b", "#x"] },
+                { "id": 2, "task": "Line one\n\n  Line two\n\n", "depends": [[]] },
+            ],
+        });
+        let written = write(&value);
+        assert_eq!(
+            written,
+            "\
+todos:
+  - id: 1
+    tags:
+      - \"2024-06-01\"
+      - \"a: b\"
+      - \"#x\"
+    task: \"yes\"
+  - depends:
+      - []
+    id: 2
+    task: |+
+      Line one
+
+        Line two
+
+"
+        );
+        assert_eq!(parse(&written), Ok(value));
+
+        assert!(parse("a: 1\na: 2").unwrap_err().contains("duplicate key"));
+        assert!(parse("a: 1\n   b: 2").unwrap_err().starts_with("Line 2"));
+        assert!(parse("a: &x 1").unwrap_err().contains("not supported"));
+        assert!(parse("a: 'open").is_err());
+        assert!(parse("a: [1, 2").is_err());
+        assert!(parse("a: 1\n---\nb: 2")
+            .unwrap_err()
+            .contains("one YAML document"));
+    }
+}
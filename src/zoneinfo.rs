@@ -0,0 +1,391 @@
+//! IANA timezones such as `Europe/Berlin`, read from the system's zoneinfo files (RFC 8536),
+//! so that a time of day stays put when daylight saving begins or ends.
+//!
+//! The files are looked up in `$TZDIR`, or `/usr/share/zoneinfo`. Their transitions cover
+//! the past, and the POSIX rule at their end, e.g. `CET-1CEST,M3.5.0,M10.5.0/3`, the years
+//! after the last one.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+
+/// Where zoneinfo files are looked up without `$TZDIR`.
+const ZONEINFO: &str = "/usr/share/zoneinfo";
+
+/// The timezones loaded so far, kept for the rest of the run.
+static LOADED: Mutex<BTreeMap<String, &'static TimeZone>> = Mutex::new(BTreeMap::new());
+
+/// A timezone's offsets from Utc over time.
+#[derive(Debug, PartialEq)]
+pub struct TimeZone {
+    /// Its name, e.g. `Europe/Berlin`.
+    pub name: String,
+    /// When the offset changed, in Unix time, and the index of the offset it changed to.
+    transitions: Vec<(i64, usize)>,
+    /// The offsets from Utc in seconds, the first one in effect before any transition.
+    offsets: Vec<i32>,
+    /// The rule for the times after the last transition.
+    rule: Option<Rule>,
+}
+
+/// A POSIX `TZ` rule: a standard offset, and daylight saving time between two days a year.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rule {
+    /// The standard offset from Utc in seconds.
+    standard: i32,
+    /// The daylight saving offset and when it begins and ends, if there is one.
+    daylight: Option<Daylight>,
+}
+
+/// When daylight saving time is in effect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Daylight {
+    offset: i32,
+    /// The day it begins, and the wall-clock time in standard time it begins at.
+    start: (Day, i32),
+    /// The day it ends, and the wall-clock time in daylight saving time it ends at.
+    end: (Day, i32),
+}
+
+/// A day of the year in a POSIX rule.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Day {
+    /// `Jn`: the day from 1 to 365, February 29 never counted.
+    Julian(u32),
+    /// `n`: the day from 0 to 365, February 29 counted.
+    Ordinal(u32),
+    /// `Mm.w.d`: weekday `d` (0 for Sunday) of week `w` of month `m`, 5 meaning the last.
+    Month(u32, u32, u32),
+}
+
+/// Returns the timezone called `name`, read once from its zoneinfo file.
+pub fn load(name: &str) -> Result<&'static TimeZone, String> {
+    let valid = !name.is_empty()
+        && !name.starts_with('/')
+        && !name.split('/').any(|part| part.is_empty() || part == "..")
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/_-+".contains(c));
+    if !valid {
+        return Err(format!("Unknown timezone: '{}'", name));
+    }
+    let mut loaded = LOADED.lock().unwrap_or_else(|error| error.into_inner());
+    if let Some(zone) = loaded.get(name) {
+        return Ok(zone);
+    }
+    let dir = std::env::var_os("TZDIR")
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| PathBuf::from(ZONEINFO), PathBuf::from);
+    let bytes =
+        std::fs::read(dir.join(name)).map_err(|_| format!("Unknown timezone: '{}'", name))?;
+    let zone = parse(name, &bytes)?;
+    // Leaked so that a Zone can hold on to it and stay Copy, once per timezone
+    let zone: &'static TimeZone = Box::leak(Box::new(zone));
+    loaded.insert(name.to_string(), zone);
+    Ok(zone)
+}
+
+/// Makes `zone` the timezone called `name` for the rest of the run, for tests that can't
+/// rely on the system's zoneinfo files.
+#[cfg(test)]
+pub fn insert(name: &str, zone: TimeZone) {
+    let mut loaded = LOADED.lock().unwrap_or_else(|error| error.into_inner());
+    loaded.insert(name.to_string(), Box::leak(Box::new(zone)));
+}
+
+/// Reads a big-endian number of `N` bytes from `bytes` at `*at`, moving past it.
+fn read<const N: usize>(bytes: &[u8], at: &mut usize) -> Option<[u8; N]> {
+    let read = bytes.get(*at..*at + N)?.try_into().ok()?;
+    *at += N;
+    Some(read)
+}
+
+/// Parses the zoneinfo file `bytes` of the timezone `name`.
+fn parse(name: &str, bytes: &[u8]) -> Result<TimeZone, String> {
+    let invalid = || format!("Invalid zoneinfo file for {}", name);
+    // The magic, the version, 15 bytes unused and six counts
+    let header = |start: usize| -> Option<(u8, [usize; 6])> {
+        if bytes.get(start..start + 4)? != b"TZif" {
+            return None;
+        }
+        let version = *bytes.get(start + 4)?;
+        let mut counts = [0; 6];
+        let mut at = start + 20;
+        for count in &mut counts {
+            *count = u32::from_be_bytes(read(bytes, &mut at)?) as usize;
+        }
+        Some((version, counts))
+    };
+    let (version, counts) = header(0).ok_or_else(invalid)?;
+    // The size of a block of data, with times of `size` bytes
+    let block = |[isut, isstd, leap, time, types, chars]: [usize; 6], size: usize| {
+        time * size + time + types * 6 + chars + leap * (size + 4) + isstd + isut
+    };
+    let (mut at, counts, size) = match version {
+        0 => (44, counts, 4),
+        _ => {
+            let second = 44 + block(counts, 4);
+            let (_, counts) = header(second).ok_or_else(invalid)?;
+            (second + 44, counts, 8)
+        }
+    };
+    let [_, _, _, time_count, type_count, _] = counts;
+    let end = at + block(counts, size);
+    let mut times = Vec::with_capacity(time_count);
+    for _ in 0..time_count {
+        let time = match size {
+            4 => i32::from_be_bytes(read(bytes, &mut at).ok_or_else(invalid)?) as i64,
+            _ => i64::from_be_bytes(read(bytes, &mut at).ok_or_else(invalid)?),
+        };
+        times.push(time);
+    }
+    let mut indices = Vec::with_capacity(time_count);
+    for _ in 0..time_count {
+        let [index] = read(bytes, &mut at).ok_or_else(invalid)?;
+        indices.push(index as usize);
+    }
+    let mut offsets = Vec::with_capacity(type_count);
+    for _ in 0..type_count {
+        offsets.push(i32::from_be_bytes(
+            read(bytes, &mut at).ok_or_else(invalid)?,
+        ));
+        at += 2;
+    }
+    if offsets.is_empty() || indices.iter().any(|index| *index >= offsets.len()) {
+        return Err(invalid());
+    }
+    // The footer of version 2 and later, a POSIX rule between newlines
+    let rule = match version {
+        0 => None,
+        _ => bytes
+            .get(end..)
+            .and_then(|footer| std::str::from_utf8(footer).ok())
+            .and_then(|footer| footer.trim_matches('\n').lines().next())
+            .and_then(parse_rule),
+    };
+    Ok(TimeZone {
+        name: name.to_string(),
+        transitions: times.into_iter().zip(indices).collect(),
+        offsets,
+        rule,
+    })
+}
+
+impl TimeZone {
+    /// Makes a timezone of the POSIX rule `rule` alone, e.g. `CET-1CEST,M3.5.0,M10.5.0/3`.
+    #[cfg(test)]
+    pub fn from_rule(rule: &str) -> Option<TimeZone> {
+        let rule = parse_rule(rule)?;
+        Some(TimeZone {
+            name: String::new(),
+            transitions: Vec::new(),
+            offsets: vec![rule.standard],
+            rule: Some(rule),
+        })
+    }
+
+    /// Returns the offset from Utc in seconds at the timestamp `timestamp`.
+    pub fn offset(&self, timestamp: i64) -> i32 {
+        let after = self
+            .transitions
+            .partition_point(|(time, _)| *time <= timestamp);
+        match (after.checked_sub(1), &self.rule) {
+            (None, Some(rule)) if self.transitions.is_empty() => rule.offset(timestamp),
+            (None, _) => self.offsets[0],
+            (Some(last), Some(rule)) if last + 1 == self.transitions.len() => {
+                rule.offset(timestamp)
+            }
+            (Some(last), _) => self.offsets[self.transitions[last].1],
+        }
+    }
+}
+
+impl Rule {
+    /// Returns the offset in effect at the timestamp `timestamp`.
+    fn offset(&self, timestamp: i64) -> i32 {
+        let Some(daylight) = self.daylight else {
+            return self.standard;
+        };
+        let year = chrono::DateTime::from_timestamp(timestamp + self.standard as i64, 0)
+            .map_or(1970, |datetime| datetime.year());
+        let at = |(day, time): (Day, i32), offset: i32| {
+            day.date(year).map(|date| {
+                date.and_hms_opt(0, 0, 0)
+                    .expect("Midnight exists")
+                    .and_utc()
+                    .timestamp()
+                    + time as i64
+                    - offset as i64
+            })
+        };
+        let (Some(start), Some(end)) = (
+            at(daylight.start, self.standard),
+            at(daylight.end, daylight.offset),
+        ) else {
+            return self.standard;
+        };
+        // South of the equator, daylight saving time spans the new year
+        let daylight_saving = match start <= end {
+            true => start <= timestamp && timestamp < end,
+            false => !(end <= timestamp && timestamp < start),
+        };
+        match daylight_saving {
+            true => daylight.offset,
+            false => self.standard,
+        }
+    }
+}
+
+impl Day {
+    /// Returns the day in `year`.
+    fn date(self, year: i32) -> Option<NaiveDate> {
+        match self {
+            Day::Julian(day) => {
+                let leap = NaiveDate::from_ymd_opt(year, 2, 29).is_some();
+                let day = match leap && day >= 60 {
+                    true => day + 1,
+                    false => day,
+                };
+                NaiveDate::from_yo_opt(year, day)
+            }
+            Day::Ordinal(day) => NaiveDate::from_yo_opt(year, day + 1),
+            Day::Month(month, week, weekday) => {
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let weekday = Weekday::try_from(((weekday + 6) % 7) as u8).ok()?;
+                let ahead = (7 + weekday.num_days_from_monday()
+                    - first.weekday().num_days_from_monday())
+                    % 7;
+                // The fifth week is the last, whichever it is
+                (0..week)
+                    .rev()
+                    .filter_map(|weeks| {
+                        first.checked_add_days(chrono::Days::new((ahead + weeks * 7) as u64))
+                    })
+                    .find(|date| date.month() == month)
+            }
+        }
+    }
+}
+
+/// Parses a POSIX rule such as `EST5EDT,M3.2.0,M11.1.0` or `<+0330>-3:30`.
+fn parse_rule(rule: &str) -> Option<Rule> {
+    let mut rest = rule;
+    let name = |rest: &mut &str| -> Option<()> {
+        let length = match rest.strip_prefix('<') {
+            Some(quoted) => quoted.find('>')? + 2,
+            None => rest
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len()),
+        };
+        if length < 3 {
+            return None;
+        }
+        *rest = &rest[length..];
+        Some(())
+    };
+    // A time such as `2`, `-1`, `+5:30` or `167:59:59`, in seconds
+    let time = |rest: &mut &str| -> Option<i32> {
+        let length = rest
+            .find(|c: char| !(c.is_ascii_digit() || ":+-".contains(c)))
+            .unwrap_or(rest.len());
+        let (time, after) = rest.split_at(length);
+        *rest = after;
+        let (sign, time) = match time.strip_prefix('-') {
+            Some(time) => (-1, time),
+            None => (1, time.strip_prefix('+').unwrap_or(time)),
+        };
+        let mut seconds = 0;
+        for (part, unit) in time.split(':').zip([3600, 60, 1]) {
+            seconds += part.parse::<i32>().ok()? * unit;
+        }
+        Some(sign * seconds)
+    };
+    name(&mut rest)?;
+    // POSIX offsets are west of Greenwich, the other way round from Utc offsets
+    let standard = -time(&mut rest)?;
+    if rest.is_empty() {
+        return Some(Rule {
+            standard,
+            daylight: None,
+        });
+    }
+    name(&mut rest)?;
+    let offset = match rest.starts_with(',') {
+        true => standard + 3600,
+        false => -time(&mut rest)?,
+    };
+    let change = |rest: &mut &str| -> Option<(Day, i32)> {
+        *rest = rest.strip_prefix(',')?;
+        let length = rest.find([',', '/']).unwrap_or(rest.len());
+        let (day, after) = rest.split_at(length);
+        *rest = after;
+        let day = match (day.strip_prefix('J'), day.strip_prefix('M')) {
+            (Some(day), _) => Day::Julian(day.parse().ok().filter(|day| (1..=365).contains(day))?),
+            (_, Some(day)) => {
+                let parts: Vec<u32> = day
+                    .split('.')
+                    .map(|part| part.parse().ok())
+                    .collect::<Option<_>>()?;
+                match parts[..] {
+                    [month, week, weekday]
+                        if (1..=12).contains(&month) && (1..=5).contains(&week) && weekday <= 6 =>
+                    {
+                        Day::Month(month, week, weekday)
+                    }
+                    _ => return None,
+                }
+            }
+            _ => Day::Ordinal(day.parse().ok().filter(|day| *day <= 365)?),
+        };
+        let at = match rest.strip_prefix('/') {
+            Some(after) => {
+                *rest = after;
+                time(rest)?
+            }
+            None => 2 * 3600,
+        };
+        Some((day, at))
+    };
+    let start = change(&mut rest)?;
+    let end = change(&mut rest)?;
+    rest.is_empty().then_some(Rule {
+        standard,
+        daylight: Some(Daylight { offset, start, end }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, TimeZone};
+
+    #[test]
+    fn test_rules() {
+        let berlin = TimeZone::from_rule("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        // 2024-03-31 00:59:59 and 01:00 Utc, around the change to summer time
+        assert_eq!(berlin.offset(1711846799), 3600);
+        assert_eq!(berlin.offset(1711846800), 7200);
+        // 2024-10-27 00:59:59 and 01:00 Utc, around the change back
+        assert_eq!(berlin.offset(1729990799), 7200);
+        assert_eq!(berlin.offset(1729990800), 3600);
+
+        let new_york = TimeZone::from_rule("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        // 2024-03-10 06:59:59 and 07:00 Utc
+        assert_eq!(new_york.offset(1710053999), -5 * 3600);
+        assert_eq!(new_york.offset(1710054000), -4 * 3600);
+
+        // Summer over the new year, in the south
+        let sydney = TimeZone::from_rule("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(sydney.offset(1704067200), 11 * 3600); // 2024-01-01
+        assert_eq!(sydney.offset(1719792000), 10 * 3600); // 2024-07-01
+
+        let tehran = TimeZone::from_rule("<+0330>-3:30").unwrap();
+        assert_eq!(tehran.offset(0), 3 * 3600 + 1800);
+
+        assert!(TimeZone::from_rule("CET-1CEST,M3.5.0").is_none());
+        assert!(TimeZone::from_rule("C-1").is_none());
+        assert!(load("../etc/passwd").is_err());
+        assert!(load("Nowhere/Atlantis").is_err());
+    }
+}